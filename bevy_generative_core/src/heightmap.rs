@@ -0,0 +1,82 @@
+use alloc::vec::Vec;
+
+use num_traits::Float;
+
+use crate::noise::NoiseValue;
+
+/// Height of a vertex in world units from its noise value (0 to 100).
+/// Values below `sea_percent` are flat, the others are raised to `height_exponent`
+#[must_use]
+pub fn height(noise_value: f32, sea_percent: f32, height_exponent: f32) -> f32 {
+    let height_value = 0_f32.max(noise_value - sea_percent) / 100.0;
+    (Float::powf(height_value * 1.2, height_exponent) - 0.5) * 2.0
+}
+
+/// Height of the flat sea floor in world units
+#[must_use]
+pub fn sea_level(height_exponent: f32) -> f32 {
+    (Float::powf(0_f32, height_exponent) - 0.5) * 2.0
+}
+
+/// Height of a vertex in world units from its noise value (0 to 100), from 0 to `height_scale`.
+/// Values below `sea_percent` are at 0, the others are raised to `height_exponent`
+#[must_use]
+pub fn scaled_height(
+    noise_value: f32,
+    sea_percent: f32,
+    height_exponent: f32,
+    height_scale: f32,
+) -> f32 {
+    let land = (100.0 - sea_percent).max(f32::EPSILON);
+    let height_value = (0_f32.max(noise_value - sea_percent) / land).min(1.0);
+    Float::powf(height_value, height_exponent) * height_scale
+}
+
+/// Height of every value of `noise_map` in world units, indexed like the noise map
+#[must_use]
+pub fn heights<T: NoiseValue>(
+    noise_map: &[Vec<T>],
+    sea_percent: f32,
+    height_exponent: f32,
+) -> Vec<Vec<f32>> {
+    noise_map
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|&noise_value| height(value(noise_value), sea_percent, height_exponent))
+                .collect()
+        })
+        .collect()
+}
+
+/// Height of every value of `noise_map` in world units from `scaled_height`, indexed like the noise map
+#[must_use]
+pub fn scaled_heights<T: NoiseValue>(
+    noise_map: &[Vec<T>],
+    sea_percent: f32,
+    height_exponent: f32,
+    height_scale: f32,
+) -> Vec<Vec<f32>> {
+    noise_map
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|&noise_value| {
+                    scaled_height(
+                        value(noise_value),
+                        sea_percent,
+                        height_exponent,
+                        height_scale,
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Noise value of a map of any precision as `f32`
+fn value<T: NoiseValue>(noise_value: T) -> f32 {
+    Into::<f64>::into(noise_value) as f32
+}
@@ -0,0 +1,51 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![warn(
+    clippy::all,
+    clippy::pedantic,
+    clippy::nursery,
+    clippy::cargo,
+    missing_docs
+)]
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::cast_sign_loss)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_possible_wrap)]
+#![allow(clippy::multiple_crate_versions)]
+#![allow(clippy::module_name_repetitions)]
+#![allow(clippy::similar_names)]
+#![allow(clippy::too_many_lines)]
+#![allow(clippy::suboptimal_flops)]
+
+//! Noise, heightmap and mesh data generation of `bevy_generative` without Bevy.
+//! Servers and offline tools can generate the same worlds headlessly
+//! ```
+//! use bevy_generative_core::heightmap::heights;
+//! use bevy_generative_core::mesh::GridMesh;
+//! use bevy_generative_core::noise::{generate_base_noise_map, Function, Method};
+//!
+//! let (size, resolution) = ([2, 2], 15);
+//! let noise_map = generate_base_noise_map(
+//!     size.map(|side| side * resolution),
+//!     0,
+//!     50.0,
+//!     [0.0; 2],
+//!     &Method::Perlin,
+//!     &Function::default(),
+//! );
+//! let mesh = GridMesh::new(&heights(&noise_map, 10.0, 1.0), resolution, size);
+//! assert_eq!(mesh.positions.len(), 31 * 31);
+//! ```
+//! Disable the default `std` feature to build without the standard library
+
+extern crate alloc;
+
+/// Heights from noise maps
+pub mod heightmap;
+/// Mesh data of heightfields
+pub mod mesh;
+/// Noise sampling and noise map generation
+pub mod noise;
+/// Deterministic random numbers
+pub mod rng;
+
+pub use rng::Rng;
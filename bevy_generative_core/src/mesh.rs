@@ -0,0 +1,348 @@
+use alloc::{vec, vec::Vec};
+
+use hashbrown::HashMap;
+use num_traits::Float;
+
+/// Vertices and indices of a heightfield grid, ready to be uploaded to any renderer
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct GridMesh {
+    /// Position of every vertex, rows along `z` ordered by `x`
+    pub positions: Vec<[f32; 3]>,
+    /// Normal of every vertex
+    pub normals: Vec<[f32; 3]>,
+    /// Grid index of every vertex as `x`, `z`
+    pub uvs: Vec<[f32; 2]>,
+    /// Three indices per triangle, or two per line if built by `wireframe`
+    pub indices: Vec<u32>,
+}
+
+impl GridMesh {
+    /// Grid over `size` world units centered on the origin, with `resolution` vertices per world unit.
+    /// `heights` are indexed by `[x][z]`
+    #[must_use]
+    pub fn new(heights: &[Vec<f32>], resolution: u32, size: [u32; 2]) -> Self {
+        let rows = heights.len();
+        let cols = heights.first().map_or(0, Vec::len);
+        let resolution = resolution.max(1) as f32;
+        let half = [size[0] as f32 / 2.0, size[1] as f32 / 2.0];
+        let mut positions = Vec::with_capacity(rows * cols);
+        let mut uvs = Vec::with_capacity(rows * cols);
+        for (row, column) in heights.iter().enumerate() {
+            for (col, height) in column.iter().enumerate() {
+                let [row, col] = [row as f32, col as f32];
+                positions.push([
+                    row / resolution - half[0],
+                    *height,
+                    col / resolution - half[1],
+                ]);
+                uvs.push([row, col]);
+            }
+        }
+        Self {
+            normals: vec![[0.0, 1.0, 0.0]; positions.len()],
+            positions,
+            uvs,
+            indices: grid_indices(rows as u32, cols as u32),
+        }
+    }
+
+    /// Multiplies every position by `scale` along each axis, e.g. to stretch the grid to a size in world units
+    pub fn scale(&mut self, scale: [f32; 3]) {
+        for position in &mut self.positions {
+            for (axis, scale) in position.iter_mut().zip(scale) {
+                *axis *= scale;
+            }
+        }
+    }
+
+    /// Moves every position by `offset`
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        for position in &mut self.positions {
+            for (axis, offset) in position.iter_mut().zip(offset) {
+                *axis += offset;
+            }
+        }
+    }
+
+    /// Merges cells of a grid of `rows` by `cols` vertices into larger triangles where `criteria` allow it,
+    /// so the grid stays dense at ridgelines and is sparse on plains. Must be called before the grid is changed otherwise.
+    /// Returns the index of the original vertex of every kept vertex, to copy other attributes with
+    pub fn simplify(&mut self, rows: usize, cols: usize, criteria: MergeCriteria) -> Vec<u32> {
+        if rows < 2 || cols < 2 || self.positions.len() != rows * cols {
+            return (0..self.positions.len() as u32).collect();
+        }
+        let normals = criteria
+            .max_angle
+            .map(|_| self.smooth_normals(rows, cols))
+            .unwrap_or_default();
+        let mergeable = |corner: [usize; 2], size: usize| {
+            criteria
+                .tolerance
+                .is_none_or(|tolerance| self.is_flat(corner, size, cols, tolerance))
+                && criteria
+                    .max_angle
+                    .is_none_or(|max_angle| is_smooth(&normals, corner, size, cols, max_angle))
+        };
+        let size = (rows - 1).max(cols - 1).next_power_of_two();
+        let mut blocks = vec![];
+        blocks_of([0, 0], size, [rows, cols], &mergeable, &mut blocks);
+
+        // Corners of every block, the edges of the larger blocks pass through them
+        let mut corners = vec![false; rows * cols];
+        for &([x, z], size) in &blocks {
+            for [i, j] in [[x, z], [x + size, z], [x, z + size], [x + size, z + size]] {
+                corners[i * cols + j] = true;
+            }
+        }
+        let index = |[x, z]: [usize; 2]| (x * cols + z) as u32;
+        let mut indices = vec![];
+        for &([x, z], size) in &blocks {
+            if size == 1 {
+                let [current, next_row] = [index([x, z]), index([x + 1, z])];
+                indices.extend([current, current + 1, next_row]);
+                indices.extend([next_row, current + 1, next_row + 1]);
+                continue;
+            }
+            // Fan from the center through every corner on the edges, so no edge has a T-junction
+            let center = [x + size / 2, z + size / 2];
+            let mut edge: Vec<[usize; 2]> = (0..size).map(|step| [x, z + step]).collect();
+            edge.extend((0..size).map(|step| [x + step, z + size]));
+            edge.extend((0..size).map(|step| [x + size, z + size - step]));
+            edge.extend((0..size).map(|step| [x + size - step, z]));
+            edge.retain(|&vertex| corners[index(vertex) as usize]);
+            for (k, &from) in edge.iter().enumerate() {
+                let to = edge[(k + 1) % edge.len()];
+                let [ax, az] = [
+                    from[0] as f32 - center[0] as f32,
+                    from[1] as f32 - center[1] as f32,
+                ];
+                let [bx, bz] = [
+                    to[0] as f32 - center[0] as f32,
+                    to[1] as f32 - center[1] as f32,
+                ];
+                // Same winding as the triangles of `grid_indices`
+                if ax * bz - az * bx < 0.0 {
+                    indices.extend([index(center), index(from), index(to)]);
+                } else {
+                    indices.extend([index(center), index(to), index(from)]);
+                }
+            }
+        }
+
+        let mut kept = vec![u32::MAX; rows * cols];
+        let mut sources = vec![];
+        for index in &mut indices {
+            if kept[*index as usize] == u32::MAX {
+                kept[*index as usize] = sources.len() as u32;
+                sources.push(*index);
+            }
+            *index = kept[*index as usize];
+        }
+        self.positions = sources
+            .iter()
+            .map(|&i| self.positions[i as usize])
+            .collect();
+        self.normals = sources.iter().map(|&i| self.normals[i as usize]).collect();
+        self.uvs = sources.iter().map(|&i| self.uvs[i as usize]).collect();
+        self.indices = indices;
+        sources
+    }
+
+    /// Normal of every vertex of a grid of `rows` by `cols` vertices from the heights of its neighbors
+    fn smooth_normals(&self, rows: usize, cols: usize) -> Vec<[f32; 3]> {
+        let position = |i: usize, j: usize| self.positions[i * cols + j];
+        let mut normals = Vec::with_capacity(rows * cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                let [left, right] = [
+                    position(i.saturating_sub(1), j),
+                    position((i + 1).min(rows - 1), j),
+                ];
+                let [near, far] = [
+                    position(i, j.saturating_sub(1)),
+                    position(i, (j + 1).min(cols - 1)),
+                ];
+                let dx = (right[1] - left[1]) / (right[0] - left[0]);
+                let dz = (far[1] - near[1]) / (far[2] - near[2]);
+                let length = Float::sqrt(Float::mul_add(dx, dx, Float::mul_add(dz, dz, 1.0)));
+                normals.push([-dx / length, 1.0 / length, -dz / length]);
+            }
+        }
+        normals
+    }
+
+    /// Returns true if every height of the block is within `tolerance` of the surface between its corners
+    fn is_flat(&self, [x, z]: [usize; 2], size: usize, cols: usize, tolerance: f32) -> bool {
+        let height = |i: usize, j: usize| self.positions[(x + i) * cols + z + j][1];
+        let [near, right, far, both] = [
+            height(0, 0),
+            height(size, 0),
+            height(0, size),
+            height(size, size),
+        ];
+        // The fan through the center differs from the bilinear surface by the twist of the corners
+        if Float::abs(near + both - right - far) / 4.0 > tolerance {
+            return false;
+        }
+        (0..=size).all(|i| {
+            (0..=size).all(|j| {
+                let [u, v] = [i as f32 / size as f32, j as f32 / size as f32];
+                let expected =
+                    (near * (1.0 - u) + right * u) * (1.0 - v) + (far * (1.0 - u) + both * u) * v;
+                Float::abs(height(i, j) - expected) <= tolerance
+            })
+        })
+    }
+
+    /// Gives every triangle three vertices of its own with the normal of the face, for a faceted look.
+    /// Returns the index of the original vertex of every new vertex, to copy other attributes with
+    pub fn flat_shade(&mut self) -> Vec<u32> {
+        let sources = core::mem::take(&mut self.indices);
+        let mut positions = Vec::with_capacity(sources.len());
+        let mut normals = Vec::with_capacity(sources.len());
+        let mut uvs = Vec::with_capacity(sources.len());
+        for triangle in sources.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.positions[triangle[i] as usize]);
+            let [ab, ac] = [b, c].map(|p| [p[0] - a[0], p[1] - a[1], p[2] - a[2]]);
+            let normal = [
+                ab[1] * ac[2] - ab[2] * ac[1],
+                ab[2] * ac[0] - ab[0] * ac[2],
+                ab[0] * ac[1] - ab[1] * ac[0],
+            ];
+            let length = Float::sqrt(normal.iter().map(|axis| axis * axis).sum::<f32>());
+            let normal = if length > 0.0 {
+                normal.map(|axis| axis / length)
+            } else {
+                [0.0, 1.0, 0.0]
+            };
+            for &index in triangle {
+                positions.push(self.positions[index as usize]);
+                normals.push(normal);
+                uvs.push(self.uvs[index as usize]);
+            }
+        }
+        self.indices = (0..positions.len() as u32).collect();
+        self.positions = positions;
+        self.normals = normals;
+        self.uvs = uvs;
+        sources
+    }
+
+    /// Replaces the triangles by the lines along their edges
+    pub fn wireframe(&mut self) {
+        self.indices = self
+            .indices
+            .chunks_exact(3)
+            .flat_map(|triangle| [0, 1, 1, 2, 2, 0].map(|i| triangle[i]))
+            .collect();
+    }
+
+    /// Splits the mesh into parts of at most `max_vertices` vertices, keeping every primitive of
+    /// `primitive` indices whole, e.g. 3 for triangles and 2 for the lines of `wireframe`.
+    /// Primitives keep their order, so the parts of a grid are strips of rows.
+    /// Returns every part with the index of the original vertex of each of its vertices, to copy other attributes with
+    #[must_use]
+    pub fn split(&self, max_vertices: usize, primitive: usize) -> Vec<(Self, Vec<u32>)> {
+        let primitive = primitive.max(1);
+        let max_vertices = max_vertices.max(primitive);
+        let mut parts = Vec::new();
+        let mut part = Self::default();
+        let mut sources = Vec::new();
+        let mut remap = HashMap::new();
+        for corners in self.indices.chunks_exact(primitive) {
+            let added = corners
+                .iter()
+                .filter(|index| !remap.contains_key(*index))
+                .count();
+            if sources.len() + added > max_vertices {
+                parts.push((core::mem::take(&mut part), core::mem::take(&mut sources)));
+                remap.clear();
+            }
+            for &index in corners {
+                let local = *remap.entry(index).or_insert_with(|| {
+                    let source = index as usize;
+                    part.positions.push(self.positions[source]);
+                    part.normals.extend(self.normals.get(source));
+                    part.uvs.extend(self.uvs.get(source));
+                    sources.push(index);
+                    sources.len() as u32 - 1
+                });
+                part.indices.push(local);
+            }
+        }
+        if !part.indices.is_empty() {
+            parts.push((part, sources));
+        }
+        parts
+    }
+}
+
+/// When cells of a grid may be merged by `GridMesh::simplify`, every set criterion has to hold
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct MergeCriteria {
+    /// Largest distance in world units of a height from the surface between the corners of the merged cells
+    pub tolerance: Option<f32>,
+    /// Largest angle in radians between the normals within the merged cells, so curved areas stay dense
+    pub max_angle: Option<f32>,
+}
+
+/// Splits the block of `size` cells at `corner` until it is `mergeable` or a single cell
+fn blocks_of(
+    corner: [usize; 2],
+    size: usize,
+    [rows, cols]: [usize; 2],
+    mergeable: &impl Fn([usize; 2], usize) -> bool,
+    blocks: &mut Vec<([usize; 2], usize)>,
+) {
+    let [x, z] = corner;
+    if x >= rows - 1 || z >= cols - 1 {
+        return;
+    }
+    let inside = x + size < rows && z + size < cols;
+    if size == 1 || (inside && mergeable(corner, size)) {
+        blocks.push((corner, size));
+        return;
+    }
+    let half = size / 2;
+    for [i, j] in [[0, 0], [half, 0], [0, half], [half, half]] {
+        blocks_of([x + i, z + j], half, [rows, cols], mergeable, blocks);
+    }
+}
+
+/// Returns true if no normal of the block is more than `max_angle` radians from their mean
+fn is_smooth(
+    normals: &[[f32; 3]],
+    [x, z]: [usize; 2],
+    size: usize,
+    cols: usize,
+    max_angle: f32,
+) -> bool {
+    let block =
+        || (0..=size).flat_map(move |i| (0..=size).map(move |j| normals[(x + i) * cols + z + j]));
+    let mut mean = [0.0; 3];
+    for normal in block() {
+        for (axis, value) in mean.iter_mut().zip(normal) {
+            *axis += value;
+        }
+    }
+    let length = Float::sqrt(mean.iter().map(|axis| axis * axis).sum::<f32>());
+    let min_cos = Float::cos(max_angle);
+    block()
+        .all(|normal| normal.iter().zip(mean).map(|(a, b)| a * b).sum::<f32>() >= min_cos * length)
+}
+
+/// Indices of two triangles per cell of a grid of `rows` by `cols` vertices, rows along `cols`
+#[must_use]
+pub fn grid_indices(rows: u32, cols: u32) -> Vec<u32> {
+    let mut indices =
+        Vec::with_capacity((rows.saturating_sub(1) * cols.saturating_sub(1) * 6) as usize);
+    for i in 0..rows.saturating_sub(1) {
+        for j in 0..cols.saturating_sub(1) {
+            let current = i * cols + j;
+            let next_row = (i + 1) * cols + j;
+            indices.extend([current, current + 1, next_row]);
+            indices.extend([next_row, current + 1, next_row + 1]);
+        }
+    }
+    indices
+}
@@ -0,0 +1,835 @@
+use alloc::vec::Vec;
+use core::{fmt, ops::Range};
+
+use noise::{BasicMulti, Billow, Fbm, HybridMulti, RidgedMulti};
+use noise::{MultiFractal, NoiseFn, Seedable};
+use noise::{OpenSimplex, Perlin, PerlinSurflet, Simplex, SuperSimplex, Value, Worley};
+use num_traits::Float;
+use serde::{Deserialize, Serialize};
+
+/// 2D noise method used to generate noise map
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Method {
+    /// Open Simplex noise
+    OpenSimplex,
+    /// Perlin noise
+    Perlin,
+    /// Perlin Surflet noise
+    PerlinSurflet,
+    /// Simplex noise
+    Simplex,
+    /// Super Simplex noise
+    SuperSimplex,
+    /// Value noise
+    Value,
+    /// Worley noise
+    Worley,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OpenSimplex => write!(f, "Open Simplex"),
+            Self::Perlin => write!(f, "Perlin"),
+            Self::PerlinSurflet => write!(f, "Perlin Surflet"),
+            Self::Simplex => write!(f, "Simplex"),
+            Self::SuperSimplex => write!(f, "Super Simplex"),
+            Self::Value => write!(f, "Value"),
+            Self::Worley => write!(f, "Worley"),
+        }
+    }
+}
+
+/// Fractal function that should be applied on the noise values
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FunctionName {
+    /// See [`BasicMulti`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html)
+    BasicMulti,
+    /// See [`Billow`](https://docs.rs/noise/latest/noise/struct.Billow.html)
+    Billow,
+    /// See [`Fbm`](https://docs.rs/noise/latest/noise/struct.Fbm.html)
+    Fbm,
+    /// See [`HybridMulti`](https://docs.rs/noise/latest/noise/struct.HybridMulti.html)
+    HybridMulti,
+    /// See [`RidgedMulti`](https://docs.rs/noise/latest/noise/struct.RidgedMulti.html)
+    RidgedMulti,
+}
+
+impl fmt::Display for FunctionName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BasicMulti => write!(f, "Basic Multi"),
+            Self::Billow => write!(f, "Billow"),
+            Self::Fbm => write!(f, "FBM"),
+            Self::HybridMulti => write!(f, "Hybrid Multi"),
+            Self::RidgedMulti => write!(f, "Ridged Multi"),
+        }
+    }
+}
+
+/// Fractal function configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Function {
+    /// Name of the function
+    pub name: Option<FunctionName>,
+    /// See [`Octaves`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html#structfield.octaves)
+    pub octaves: usize,
+    /// See [`Frequency`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html#structfield.octaves)
+    pub frequency: f64,
+    /// See [`Lacunarity`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html#structfield.octaves)
+    pub lacunarity: f64,
+    /// See [`Persistance`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html#structfield.octaves)
+    pub persistence: f64,
+}
+
+impl Default for Function {
+    fn default() -> Self {
+        Self {
+            name: Some(FunctionName::Fbm),
+            octaves: noise::Fbm::<noise::Perlin>::DEFAULT_OCTAVE_COUNT,
+            frequency: noise::Fbm::<noise::Perlin>::DEFAULT_FREQUENCY,
+            lacunarity: noise::Fbm::<noise::Perlin>::DEFAULT_LACUNARITY,
+            persistence: noise::Fbm::<noise::Perlin>::DEFAULT_PERSISTENCE,
+        }
+    }
+}
+
+/// Value type of noise maps. Noise is always sampled as `f64`, `f32` maps take half the memory
+/// and are rounded like the `f32` vertices they end up in, `f64` maps keep every sampled digit
+pub trait NoiseValue: Copy + Send + Into<f64> {
+    /// Value stored for the sampled `value`
+    fn from_f64(value: f64) -> Self;
+}
+
+impl NoiseValue for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as Self
+    }
+}
+
+impl NoiseValue for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// Precision the values of noise maps are stored with
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Precision {
+    /// `f32` values, rounded like the vertices they end up in
+    #[default]
+    Single,
+    /// `f64` values, for modifiers and lookups telling apart values closer than `f32` can
+    Double,
+}
+
+/// Noise map with the values of a [`Precision`], indexed by `[x][z]`
+#[derive(Clone, PartialEq, Debug)]
+pub enum NoiseMap {
+    /// Values of `Precision::Single`
+    Single(Vec<Vec<f32>>),
+    /// Values of `Precision::Double`
+    Double(Vec<Vec<f64>>),
+}
+
+impl Default for NoiseMap {
+    fn default() -> Self {
+        Self::Single(Vec::new())
+    }
+}
+
+impl NoiseMap {
+    /// Empty map of `precision`
+    #[must_use]
+    pub const fn new(precision: Precision) -> Self {
+        match precision {
+            Precision::Single => Self::Single(Vec::new()),
+            Precision::Double => Self::Double(Vec::new()),
+        }
+    }
+
+    /// Noise map of [`generate_base_noise_map`] with values of `precision`
+    #[must_use]
+    pub fn generate(
+        precision: Precision,
+        size: [u32; 2],
+        seed: u32,
+        scale: f64,
+        offset: [f64; 2],
+        method: &Method,
+        function: &Function,
+    ) -> Self {
+        let mut map = Self::new(precision);
+        map.extend_columns(size, 0..size[0] + 1, seed, scale, offset, method, function);
+        map
+    }
+
+    /// Noise map of [`generate_base_noise_map_parallel`] with values of `precision`
+    #[cfg(feature = "parallel")]
+    #[must_use]
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_parallel(
+        precision: Precision,
+        size: [u32; 2],
+        seed: u32,
+        scale: f64,
+        offset: [f64; 2],
+        method: &Method,
+        function: &Function,
+        threads: usize,
+    ) -> Self {
+        match precision {
+            Precision::Single => Self::Single(generate_base_noise_map_parallel_as(
+                size, seed, scale, offset, method, function, threads,
+            )),
+            Precision::Double => Self::Double(generate_base_noise_map_parallel_as(
+                size, seed, scale, offset, method, function, threads,
+            )),
+        }
+    }
+
+    /// Appends columns `columns` of the noise map of [`generate_base_noise_map`], so large maps can be generated in parts
+    #[allow(clippy::too_many_arguments)]
+    pub fn extend_columns(
+        &mut self,
+        size: [u32; 2],
+        columns: Range<u32>,
+        seed: u32,
+        scale: f64,
+        offset: [f64; 2],
+        method: &Method,
+        function: &Function,
+    ) {
+        match self {
+            Self::Single(values) => values.extend(generate_base_noise_columns_as::<f32>(
+                size, columns, seed, scale, offset, method, function,
+            )),
+            Self::Double(values) => values.extend(generate_base_noise_columns_as::<f64>(
+                size, columns, seed, scale, offset, method, function,
+            )),
+        }
+    }
+
+    /// Precision of the values
+    #[must_use]
+    pub const fn precision(&self) -> Precision {
+        match self {
+            Self::Single(_) => Precision::Single,
+            Self::Double(_) => Precision::Double,
+        }
+    }
+
+    /// Number of columns along `x`
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        match self {
+            Self::Single(values) => values.len(),
+            Self::Double(values) => values.len(),
+        }
+    }
+
+    /// True if the map has no columns
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of values along `z` in the first column
+    #[must_use]
+    pub fn rows(&self) -> usize {
+        match self {
+            Self::Single(values) => values.first().map_or(0, Vec::len),
+            Self::Double(values) => values.first().map_or(0, Vec::len),
+        }
+    }
+
+    /// Value at column `x` and row `z`, `None` outside of the map
+    #[must_use]
+    pub fn get(&self, x: usize, z: usize) -> Option<f64> {
+        match self {
+            Self::Single(values) => values.get(x)?.get(z).copied().map(f64::from),
+            Self::Double(values) => values.get(x)?.get(z).copied(),
+        }
+    }
+
+    /// Values of column `x`, empty outside of the map
+    pub fn column(&self, x: usize) -> impl Iterator<Item = f64> + '_ {
+        let (single, double): (&[f32], &[f64]) = match self {
+            Self::Single(values) => (values.get(x).map_or(&[], Vec::as_slice), &[]),
+            Self::Double(values) => (&[], values.get(x).map_or(&[], Vec::as_slice)),
+        };
+        single
+            .iter()
+            .map(|&value| f64::from(value))
+            .chain(double.iter().copied())
+    }
+
+    /// Values of every column in order
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = f64> + '_> + '_ {
+        (0..self.len()).map(|x| self.column(x))
+    }
+}
+
+/// Noise map of `size + 1` values along each axis from 0 to 100
+#[must_use]
+pub fn generate_base_noise_map(
+    size: [u32; 2],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    method: &Method,
+    function: &Function,
+) -> Vec<Vec<f64>> {
+    generate_base_noise_columns(size, 0..size[0] + 1, seed, scale, offset, method, function)
+}
+
+/// Noise map of [`generate_base_noise_map`] generated on up to `threads` threads.
+///
+/// Every thread takes a contiguous range of columns and the ranges are joined in order,
+/// the map is identical to the serial one for any number of threads
+#[cfg(feature = "parallel")]
+#[must_use]
+pub fn generate_base_noise_map_parallel(
+    size: [u32; 2],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    method: &Method,
+    function: &Function,
+    threads: usize,
+) -> Vec<Vec<f64>> {
+    generate_base_noise_map_parallel_as(size, seed, scale, offset, method, function, threads)
+}
+
+/// Noise map of [`generate_base_noise_map_parallel`] with values stored as `T`
+#[cfg(feature = "parallel")]
+#[must_use]
+pub fn generate_base_noise_map_parallel_as<T: NoiseValue>(
+    size: [u32; 2],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    method: &Method,
+    function: &Function,
+    threads: usize,
+) -> Vec<Vec<T>> {
+    let total = size[0] + 1;
+    let step = total.div_ceil(u32::try_from(threads).unwrap_or(u32::MAX).max(1));
+    std::thread::scope(|scope| {
+        // Every thread is started before the first one is joined
+        let mut parts = Vec::new();
+        for start in (0..total).step_by(step as usize) {
+            let columns = start..(start + step).min(total);
+            parts.push(scope.spawn(move || {
+                generate_base_noise_columns_as(size, columns, seed, scale, offset, method, function)
+            }));
+        }
+        parts
+            .into_iter()
+            .flat_map(|part| {
+                part.join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+/// Columns `columns` of the noise map of [`generate_base_noise_map`], so large maps can be generated in parts.
+/// Every value only depends on its position, the parts equal the columns of the whole map
+#[must_use]
+pub fn generate_base_noise_columns(
+    size: [u32; 2],
+    columns: Range<u32>,
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    method: &Method,
+    function: &Function,
+) -> Vec<Vec<f64>> {
+    generate_base_noise_columns_as(size, columns, seed, scale, offset, method, function)
+}
+
+/// Noise map of [`generate_base_noise_map`] with values stored as `T`.
+/// Sampling is done in `f64`, `f32` maps take half the memory and are rounded like the `f32` vertices they end up in
+#[must_use]
+pub fn generate_base_noise_map_as<T: NoiseValue>(
+    size: [u32; 2],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    method: &Method,
+    function: &Function,
+) -> Vec<Vec<T>> {
+    generate_base_noise_columns_as(size, 0..size[0] + 1, seed, scale, offset, method, function)
+}
+
+/// Columns `columns` of the noise map of [`generate_base_noise_map_as`]
+#[must_use]
+pub fn generate_base_noise_columns_as<T: NoiseValue>(
+    size: [u32; 2],
+    columns: Range<u32>,
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    method: &Method,
+    function: &Function,
+) -> Vec<Vec<T>> {
+    function.name.as_ref().map_or_else(
+        || {
+            let generate_noise_map = match method {
+                Method::OpenSimplex => generate_noise::<T, OpenSimplex>,
+                Method::Perlin => generate_noise::<T, Perlin>,
+                Method::PerlinSurflet => generate_noise::<T, PerlinSurflet>,
+                Method::Simplex => generate_noise::<T, Simplex>,
+                Method::SuperSimplex => generate_noise::<T, SuperSimplex>,
+                Method::Value => generate_noise::<T, Value>,
+                Method::Worley => generate_noise::<T, Worley>,
+            };
+            generate_noise_map(size, columns.clone(), seed, scale, offset)
+        },
+        |function_name| {
+            let generate_noise_map = match function_name {
+                FunctionName::BasicMulti => match method {
+                    Method::OpenSimplex => generate_fractal_noise::<T, BasicMulti<OpenSimplex>>,
+                    Method::Perlin => generate_fractal_noise::<T, BasicMulti<Perlin>>,
+                    Method::PerlinSurflet => generate_fractal_noise::<T, BasicMulti<PerlinSurflet>>,
+                    Method::Simplex => generate_fractal_noise::<T, BasicMulti<Simplex>>,
+                    Method::SuperSimplex => generate_fractal_noise::<T, BasicMulti<SuperSimplex>>,
+                    Method::Value => generate_fractal_noise::<T, BasicMulti<Value>>,
+                    Method::Worley => generate_fractal_noise::<T, BasicMulti<Worley>>,
+                },
+                FunctionName::Billow => match method {
+                    Method::OpenSimplex => generate_fractal_noise::<T, Billow<OpenSimplex>>,
+                    Method::Perlin => generate_fractal_noise::<T, Billow<Perlin>>,
+                    Method::PerlinSurflet => generate_fractal_noise::<T, Billow<PerlinSurflet>>,
+                    Method::Simplex => generate_fractal_noise::<T, Billow<Simplex>>,
+                    Method::SuperSimplex => generate_fractal_noise::<T, Billow<SuperSimplex>>,
+                    Method::Value => generate_fractal_noise::<T, Billow<Value>>,
+                    Method::Worley => generate_fractal_noise::<T, Billow<Worley>>,
+                },
+                FunctionName::Fbm => match method {
+                    Method::OpenSimplex => generate_fractal_noise::<T, Fbm<OpenSimplex>>,
+                    Method::Perlin => generate_fractal_noise::<T, Fbm<Perlin>>,
+                    Method::PerlinSurflet => generate_fractal_noise::<T, Fbm<PerlinSurflet>>,
+                    Method::Simplex => generate_fractal_noise::<T, Fbm<Simplex>>,
+                    Method::SuperSimplex => generate_fractal_noise::<T, Fbm<SuperSimplex>>,
+                    Method::Value => generate_fractal_noise::<T, Fbm<Value>>,
+                    Method::Worley => generate_fractal_noise::<T, Fbm<Worley>>,
+                },
+                FunctionName::HybridMulti => match method {
+                    Method::OpenSimplex => generate_fractal_noise::<T, HybridMulti<OpenSimplex>>,
+                    Method::Perlin => generate_fractal_noise::<T, HybridMulti<Perlin>>,
+                    Method::PerlinSurflet => {
+                        generate_fractal_noise::<T, HybridMulti<PerlinSurflet>>
+                    }
+                    Method::Simplex => generate_fractal_noise::<T, HybridMulti<Simplex>>,
+                    Method::SuperSimplex => generate_fractal_noise::<T, HybridMulti<SuperSimplex>>,
+                    Method::Value => generate_fractal_noise::<T, HybridMulti<Value>>,
+                    Method::Worley => generate_fractal_noise::<T, HybridMulti<Worley>>,
+                },
+                FunctionName::RidgedMulti => match method {
+                    Method::OpenSimplex => generate_fractal_noise::<T, RidgedMulti<OpenSimplex>>,
+                    Method::Perlin => generate_fractal_noise::<T, RidgedMulti<Perlin>>,
+                    Method::PerlinSurflet => {
+                        generate_fractal_noise::<T, RidgedMulti<PerlinSurflet>>
+                    }
+                    Method::Simplex => generate_fractal_noise::<T, RidgedMulti<Simplex>>,
+                    Method::SuperSimplex => generate_fractal_noise::<T, RidgedMulti<SuperSimplex>>,
+                    Method::Value => generate_fractal_noise::<T, RidgedMulti<Value>>,
+                    Method::Worley => generate_fractal_noise::<T, RidgedMulti<Worley>>,
+                },
+            };
+            generate_noise_map(size, columns.clone(), seed, scale, offset, function)
+        },
+    )
+}
+
+fn generate_noise<V, T>(
+    size: [u32; 2],
+    columns: Range<u32>,
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+) -> Vec<Vec<V>>
+where
+    V: NoiseValue,
+    T: Default + Seedable + NoiseFn<f64, 2>,
+{
+    let mut noise = T::default();
+    noise = noise.set_seed(seed);
+    generate_noise_vector(noise, size, columns, scale, offset)
+}
+
+fn generate_fractal_noise<V, T>(
+    size: [u32; 2],
+    columns: Range<u32>,
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    function: &Function,
+) -> Vec<Vec<V>>
+where
+    V: NoiseValue,
+    T: Default + Seedable + NoiseFn<f64, 2> + MultiFractal,
+{
+    let mut noise = T::default();
+    noise = noise.set_seed(seed);
+    noise = noise.set_octaves(function.octaves);
+    noise = noise.set_frequency(function.frequency);
+    noise = noise.set_lacunarity(function.lacunarity);
+    noise = noise.set_persistence(function.persistence);
+    generate_noise_vector(noise, size, columns, scale, offset)
+}
+
+fn generate_noise_vector<V: NoiseValue>(
+    noise: impl NoiseFn<f64, 2>,
+    size: [u32; 2],
+    columns: Range<u32>,
+    scale: f64,
+    offset: [f64; 2],
+) -> Vec<Vec<V>> {
+    let mut noise_vector: Vec<Vec<V>> = Vec::with_capacity(columns.len());
+    let noise = noise::Clamp::new(noise).set_bounds(-1.0, 1.0);
+    for i in columns {
+        let mut row: Vec<V> = Vec::with_capacity(size[1] as usize);
+        for j in 0..=size[1] {
+            let x = f64::from(i as i32 - (size[0] / 2) as i32) / scale + offset[0];
+            let y = f64::from(j as i32 - (size[1] / 2) as i32) / scale + offset[1];
+            let value = f64::midpoint(noise.get([x, y]), 1.0) * 100.0;
+            row.push(V::from_f64(value));
+        }
+        noise_vector.push(row);
+    }
+    noise_vector
+}
+
+/// Sum of `octaves` octaves following those of `function`, for detail finer than a noise map of `size` values.
+/// Sampled at the centers of `texels` texels covering the noise map, from -1 to 1
+#[must_use]
+#[allow(clippy::too_many_arguments)]
+pub fn generate_detail_map(
+    size: [u32; 2],
+    texels: [u32; 2],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    method: &Method,
+    function: &Function,
+    octaves: usize,
+) -> Vec<Vec<f64>> {
+    let generate_detail_map = match method {
+        Method::OpenSimplex => generate_detail::<OpenSimplex>,
+        Method::Perlin => generate_detail::<Perlin>,
+        Method::PerlinSurflet => generate_detail::<PerlinSurflet>,
+        Method::Simplex => generate_detail::<Simplex>,
+        Method::SuperSimplex => generate_detail::<SuperSimplex>,
+        Method::Value => generate_detail::<Value>,
+        Method::Worley => generate_detail::<Worley>,
+    };
+    generate_detail_map(size, texels, seed, scale, offset, function, octaves)
+}
+
+fn generate_detail<T>(
+    size: [u32; 2],
+    texels: [u32; 2],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    function: &Function,
+    octaves: usize,
+) -> Vec<Vec<f64>>
+where
+    T: Default + Seedable + NoiseFn<f64, 2>,
+{
+    // Without a fractal function the noise is a single octave of frequency 1
+    let (first, frequency, lacunarity, persistence) = if function.name.is_some() {
+        (
+            function.octaves,
+            function.frequency,
+            function.lacunarity,
+            function.persistence,
+        )
+    } else {
+        (1, 1.0, 2.0, 0.5)
+    };
+    // Octaves are seeded like those of the fractal functions
+    let octaves: Vec<(T, f64, f64)> = (first..first + octaves)
+        .map(|octave| {
+            (
+                T::default().set_seed(seed.wrapping_add(octave as u32)),
+                frequency * Float::powi(lacunarity, octave as i32),
+                Float::powi(persistence, octave as i32),
+            )
+        })
+        .collect();
+    let total: f64 = octaves.iter().map(|(_, _, amplitude)| amplitude).sum();
+    let coordinate = |texel: u32, axis: usize| {
+        let cell =
+            (f64::from(texel) + 0.5) * f64::from(size[axis]) / f64::from(texels[axis].max(1));
+        (cell - f64::from(size[axis] / 2)) / scale + offset[axis]
+    };
+    (0..texels[0])
+        .map(|i| {
+            let x = coordinate(i, 0);
+            (0..texels[1])
+                .map(|j| {
+                    let y = coordinate(j, 1);
+                    let value: f64 = octaves
+                        .iter()
+                        .map(|(noise, frequency, amplitude)| {
+                            noise.get([x * frequency, y * frequency]) * amplitude
+                        })
+                        .sum();
+                    if total > 0.0 {
+                        (value / total).clamp(-1.0, 1.0)
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Noise value at `point` from -1 to 1
+#[must_use]
+pub fn get_noise_at_point_3d(
+    point: [f64; 3],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 3],
+    method: &Method,
+    function: &Function,
+) -> f64 {
+    function.name.as_ref().map_or_else(
+        || {
+            let noise_at_point_3d = match method {
+                Method::OpenSimplex => noise_at_point_3d::<OpenSimplex>,
+                Method::Perlin => noise_at_point_3d::<Perlin>,
+                Method::PerlinSurflet => noise_at_point_3d::<PerlinSurflet>,
+                Method::Simplex => noise_at_point_3d::<Simplex>,
+                Method::SuperSimplex => noise_at_point_3d::<SuperSimplex>,
+                Method::Value => noise_at_point_3d::<Value>,
+                Method::Worley => noise_at_point_3d::<Worley>,
+            };
+            noise_at_point_3d(point, seed, scale, offset)
+        },
+        |function_name| {
+            let noise_at_point_3d = match function_name {
+                FunctionName::BasicMulti => match method {
+                    Method::OpenSimplex => fractal_noise_at_point_3d::<BasicMulti<OpenSimplex>>,
+                    Method::Perlin => fractal_noise_at_point_3d::<BasicMulti<Perlin>>,
+                    Method::PerlinSurflet => fractal_noise_at_point_3d::<BasicMulti<PerlinSurflet>>,
+                    Method::Simplex => fractal_noise_at_point_3d::<BasicMulti<Simplex>>,
+                    Method::SuperSimplex => fractal_noise_at_point_3d::<BasicMulti<SuperSimplex>>,
+                    Method::Value => fractal_noise_at_point_3d::<BasicMulti<Value>>,
+                    Method::Worley => fractal_noise_at_point_3d::<BasicMulti<Worley>>,
+                },
+                FunctionName::Billow => match method {
+                    Method::OpenSimplex => fractal_noise_at_point_3d::<Billow<OpenSimplex>>,
+                    Method::Perlin => fractal_noise_at_point_3d::<Billow<Perlin>>,
+                    Method::PerlinSurflet => fractal_noise_at_point_3d::<Billow<PerlinSurflet>>,
+                    Method::Simplex => fractal_noise_at_point_3d::<Billow<Simplex>>,
+                    Method::SuperSimplex => fractal_noise_at_point_3d::<Billow<SuperSimplex>>,
+                    Method::Value => fractal_noise_at_point_3d::<Billow<Value>>,
+                    Method::Worley => fractal_noise_at_point_3d::<Billow<Worley>>,
+                },
+                FunctionName::Fbm => match method {
+                    Method::OpenSimplex => fractal_noise_at_point_3d::<Fbm<OpenSimplex>>,
+                    Method::Perlin => fractal_noise_at_point_3d::<Fbm<Perlin>>,
+                    Method::PerlinSurflet => fractal_noise_at_point_3d::<Fbm<PerlinSurflet>>,
+                    Method::Simplex => fractal_noise_at_point_3d::<Fbm<Simplex>>,
+                    Method::SuperSimplex => fractal_noise_at_point_3d::<Fbm<SuperSimplex>>,
+                    Method::Value => fractal_noise_at_point_3d::<Fbm<Value>>,
+                    Method::Worley => fractal_noise_at_point_3d::<Fbm<Worley>>,
+                },
+                FunctionName::HybridMulti => match method {
+                    Method::OpenSimplex => fractal_noise_at_point_3d::<HybridMulti<OpenSimplex>>,
+                    Method::Perlin => fractal_noise_at_point_3d::<HybridMulti<Perlin>>,
+                    Method::PerlinSurflet => {
+                        fractal_noise_at_point_3d::<HybridMulti<PerlinSurflet>>
+                    }
+                    Method::Simplex => fractal_noise_at_point_3d::<HybridMulti<Simplex>>,
+                    Method::SuperSimplex => fractal_noise_at_point_3d::<HybridMulti<SuperSimplex>>,
+                    Method::Value => fractal_noise_at_point_3d::<HybridMulti<Value>>,
+                    Method::Worley => fractal_noise_at_point_3d::<HybridMulti<Worley>>,
+                },
+                FunctionName::RidgedMulti => match method {
+                    Method::OpenSimplex => fractal_noise_at_point_3d::<RidgedMulti<OpenSimplex>>,
+                    Method::Perlin => fractal_noise_at_point_3d::<RidgedMulti<Perlin>>,
+                    Method::PerlinSurflet => {
+                        fractal_noise_at_point_3d::<RidgedMulti<PerlinSurflet>>
+                    }
+                    Method::Simplex => fractal_noise_at_point_3d::<RidgedMulti<Simplex>>,
+                    Method::SuperSimplex => fractal_noise_at_point_3d::<RidgedMulti<SuperSimplex>>,
+                    Method::Value => fractal_noise_at_point_3d::<RidgedMulti<Value>>,
+                    Method::Worley => fractal_noise_at_point_3d::<RidgedMulti<Worley>>,
+                },
+            };
+            noise_at_point_3d(point, seed, scale, offset, function)
+        },
+    )
+}
+
+fn fractal_noise_at_point_3d<T>(
+    point: [f64; 3],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 3],
+    function: &Function,
+) -> f64
+where
+    T: Default + Seedable + NoiseFn<f64, 3> + MultiFractal,
+{
+    let mut noise = T::default();
+    noise = noise.set_seed(seed);
+    noise = noise.set_octaves(function.octaves);
+    noise = noise.set_frequency(function.frequency);
+    noise = noise.set_lacunarity(function.lacunarity);
+    noise = noise.set_persistence(function.persistence);
+    let x = point[0] / scale + offset[0];
+    let y = point[1] / scale + offset[1];
+    let z = point[2] / scale + offset[2];
+    let noise = noise::Clamp::new(noise).set_bounds(-1.0, 1.0);
+    noise.get([x, y, z])
+}
+
+fn noise_at_point_3d<T>(point: [f64; 3], seed: u32, scale: f64, offset: [f64; 3]) -> f64
+where
+    T: Default + Seedable + NoiseFn<f64, 3>,
+{
+    let mut noise = T::default();
+    noise = noise.set_seed(seed);
+    let x = point[0] / scale + offset[0];
+    let y = point[1] / scale + offset[1];
+    let z = point[2] / scale + offset[2];
+    let noise = noise::Clamp::new(noise).set_bounds(-1.0, 1.0);
+    noise.get([x, y, z])
+}
+
+/// Noise value at `point` from -1 to 1.
+/// `SuperSimplex` has no 4D implementation and falls back to `OpenSimplex`
+#[must_use]
+pub fn get_noise_at_point_4d(
+    point: [f64; 4],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 4],
+    method: &Method,
+    function: &Function,
+) -> f64 {
+    function.name.as_ref().map_or_else(
+        || {
+            let noise_at_point_4d = match method {
+                Method::OpenSimplex | Method::SuperSimplex => noise_at_point_4d::<OpenSimplex>,
+                Method::Perlin => noise_at_point_4d::<Perlin>,
+                Method::PerlinSurflet => noise_at_point_4d::<PerlinSurflet>,
+                Method::Simplex => noise_at_point_4d::<Simplex>,
+                Method::Value => noise_at_point_4d::<Value>,
+                Method::Worley => noise_at_point_4d::<Worley>,
+            };
+            noise_at_point_4d(point, seed, scale, offset)
+        },
+        |function_name| {
+            let noise_at_point_4d = match function_name {
+                FunctionName::BasicMulti => match method {
+                    Method::OpenSimplex | Method::SuperSimplex => {
+                        fractal_noise_at_point_4d::<BasicMulti<OpenSimplex>>
+                    }
+                    Method::Perlin => fractal_noise_at_point_4d::<BasicMulti<Perlin>>,
+                    Method::PerlinSurflet => fractal_noise_at_point_4d::<BasicMulti<PerlinSurflet>>,
+                    Method::Simplex => fractal_noise_at_point_4d::<BasicMulti<Simplex>>,
+                    Method::Value => fractal_noise_at_point_4d::<BasicMulti<Value>>,
+                    Method::Worley => fractal_noise_at_point_4d::<BasicMulti<Worley>>,
+                },
+                FunctionName::Billow => match method {
+                    Method::OpenSimplex | Method::SuperSimplex => {
+                        fractal_noise_at_point_4d::<Billow<OpenSimplex>>
+                    }
+                    Method::Perlin => fractal_noise_at_point_4d::<Billow<Perlin>>,
+                    Method::PerlinSurflet => fractal_noise_at_point_4d::<Billow<PerlinSurflet>>,
+                    Method::Simplex => fractal_noise_at_point_4d::<Billow<Simplex>>,
+                    Method::Value => fractal_noise_at_point_4d::<Billow<Value>>,
+                    Method::Worley => fractal_noise_at_point_4d::<Billow<Worley>>,
+                },
+                FunctionName::Fbm => match method {
+                    Method::OpenSimplex | Method::SuperSimplex => {
+                        fractal_noise_at_point_4d::<Fbm<OpenSimplex>>
+                    }
+                    Method::Perlin => fractal_noise_at_point_4d::<Fbm<Perlin>>,
+                    Method::PerlinSurflet => fractal_noise_at_point_4d::<Fbm<PerlinSurflet>>,
+                    Method::Simplex => fractal_noise_at_point_4d::<Fbm<Simplex>>,
+                    Method::Value => fractal_noise_at_point_4d::<Fbm<Value>>,
+                    Method::Worley => fractal_noise_at_point_4d::<Fbm<Worley>>,
+                },
+                FunctionName::HybridMulti => match method {
+                    Method::OpenSimplex | Method::SuperSimplex => {
+                        fractal_noise_at_point_4d::<HybridMulti<OpenSimplex>>
+                    }
+                    Method::Perlin => fractal_noise_at_point_4d::<HybridMulti<Perlin>>,
+                    Method::PerlinSurflet => {
+                        fractal_noise_at_point_4d::<HybridMulti<PerlinSurflet>>
+                    }
+                    Method::Simplex => fractal_noise_at_point_4d::<HybridMulti<Simplex>>,
+                    Method::Value => fractal_noise_at_point_4d::<HybridMulti<Value>>,
+                    Method::Worley => fractal_noise_at_point_4d::<HybridMulti<Worley>>,
+                },
+                FunctionName::RidgedMulti => match method {
+                    Method::OpenSimplex | Method::SuperSimplex => {
+                        fractal_noise_at_point_4d::<RidgedMulti<OpenSimplex>>
+                    }
+                    Method::Perlin => fractal_noise_at_point_4d::<RidgedMulti<Perlin>>,
+                    Method::PerlinSurflet => {
+                        fractal_noise_at_point_4d::<RidgedMulti<PerlinSurflet>>
+                    }
+                    Method::Simplex => fractal_noise_at_point_4d::<RidgedMulti<Simplex>>,
+                    Method::Value => fractal_noise_at_point_4d::<RidgedMulti<Value>>,
+                    Method::Worley => fractal_noise_at_point_4d::<RidgedMulti<Worley>>,
+                },
+            };
+            noise_at_point_4d(point, seed, scale, offset, function)
+        },
+    )
+}
+
+fn fractal_noise_at_point_4d<T>(
+    point: [f64; 4],
+    seed: u32,
+    scale: f64,
+    offset: [f64; 4],
+    function: &Function,
+) -> f64
+where
+    T: Default + Seedable + NoiseFn<f64, 4> + MultiFractal,
+{
+    let mut noise = T::default();
+    noise = noise.set_seed(seed);
+    noise = noise.set_octaves(function.octaves);
+    noise = noise.set_frequency(function.frequency);
+    noise = noise.set_lacunarity(function.lacunarity);
+    noise = noise.set_persistence(function.persistence);
+    let x = point[0] / scale + offset[0];
+    let y = point[1] / scale + offset[1];
+    let z = point[2] / scale + offset[2];
+    let w = point[3] / scale + offset[3];
+    let noise = noise::Clamp::new(noise).set_bounds(-1.0, 1.0);
+    noise.get([x, y, z, w])
+}
+
+fn noise_at_point_4d<T>(point: [f64; 4], seed: u32, scale: f64, offset: [f64; 4]) -> f64
+where
+    T: Default + Seedable + NoiseFn<f64, 4>,
+{
+    let mut noise = T::default();
+    noise = noise.set_seed(seed);
+    let x = point[0] / scale + offset[0];
+    let y = point[1] / scale + offset[1];
+    let z = point[2] / scale + offset[2];
+    let w = point[3] / scale + offset[3];
+    let noise = noise::Clamp::new(noise).set_bounds(-1.0, 1.0);
+    noise.get([x, y, z, w])
+}
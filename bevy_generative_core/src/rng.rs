@@ -0,0 +1,35 @@
+/// Small deterministic random number generator (`SplitMix64`).
+///
+/// Produces the same sequence on every platform for a given seed, which keeps
+/// seeded generation reproducible without depending on `rand` internals.
+pub struct Rng(u64);
+
+impl Rng {
+    /// Generator starting from `seed`
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Next random 64-bit value
+    pub const fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Random value in the range `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Random integer in the range `[min, max]`
+    pub fn range(&mut self, min: u32, max: u32) -> u32 {
+        if max <= min {
+            return min;
+        }
+        min + (self.next_u64() % (u64::from(max) - u64::from(min) + 1)) as u32
+    }
+}
@@ -0,0 +1,189 @@
+//! Color a band of sand along the shores of terrain
+//! # Example
+//! For configuration, see [`Beach`](struct.Beach.html).
+//! The band follows the distance to the sea and to the lakes of a [`Lakes`](../lake/struct.Lakes.html) component
+//! instead of the height, so coastlines get sand whatever regions the gradient has at sea level
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::beach::Beach;
+//! use bevy_generative::terrain::{Terrain, TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle {
+//!             terrain: Terrain::islands(),
+//!             ..default()
+//!         },
+//!         Beach::default(),
+//!     ));
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    distance::DistanceField,
+    lake::LakeMap,
+    snow::slope_degrees,
+    terrain::{GeneratedTerrain, TerrainColors},
+};
+
+/// Component for beach configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Beach {
+    /// Distance from the water in world units where the sand is half faded out
+    pub width: f32,
+    /// Distance in world units over which the sand fades out around `width`
+    pub transition: f32,
+    /// Slope in degrees where the sand is half faded out, so cliffs along the coast keep their color
+    pub max_slope: f32,
+    /// Slope in degrees over which the sand fades out around `max_slope`
+    pub slope_transition: f32,
+    /// If true, lakes of a `Lakes` component get beaches as well
+    pub lakes: bool,
+    /// Color of the sand
+    pub color: [u8; 4],
+}
+
+impl Default for Beach {
+    fn default() -> Self {
+        Self {
+            width: 0.04,
+            transition: 0.02,
+            max_slope: 35.0,
+            slope_transition: 10.0,
+            lakes: true,
+            color: [225, 205, 150, 255],
+        }
+    }
+}
+
+impl Beach {
+    /// Sand cover of every vertex of `heights` from 0 to 1, `heights` and `water` are indexed by `[x][z]`.
+    /// `cell_size` is the distance between neighbouring vertices in world units
+    #[must_use]
+    pub fn coverage(
+        &self,
+        heights: &[Vec<f32>],
+        water: &[Vec<bool>],
+        resolution: u32,
+        cell_size: f32,
+    ) -> Vec<Vec<f32>> {
+        let step = 1.0 / resolution.max(1) as f32;
+        let fade = |value: f32, width: f32| {
+            let x = (value / width.max(f32::EPSILON) + 0.5).clamp(0.0, 1.0);
+            x * x * 2.0f32.mul_add(-x, 3.0)
+        };
+        let distance = DistanceField::from_mask(water, cell_size);
+        heights
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                (0..column.len())
+                    .map(|j| {
+                        // Vertices under water count as on the shore, so the sand continues below the surface
+                        let distance = distance.get(i, j).unwrap_or(f32::INFINITY).max(0.0);
+                        let near = fade(self.width - distance, self.transition);
+                        let slope = slope_degrees(heights, i, j, step);
+                        near * fade(self.max_slope - slope, self.slope_transition)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// `color` with the sand blended over it by `coverage`
+    pub(crate) fn blend(&self, color: [f32; 4], coverage: f32) -> [f32; 4] {
+        let sand = Vec4::from(self.color.map(|channel| f32::from(channel) / 255.0));
+        Vec4::from(color).lerp(sand, coverage).to_array()
+    }
+}
+
+/// Sand cover of the terrain, inserted on the entity with the `Beach` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct BeachCover {
+    /// Sand cover of every vertex from 0 to 1, indexed like `GeneratedTerrain::heights`
+    pub coverage: Vec<Vec<f32>>,
+}
+
+/// Updates the sand cover of regenerated terrains and blends it over recolored ones
+#[allow(clippy::type_complexity)]
+pub(crate) fn cover_beaches(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        Option<&mut TerrainColors>,
+        Ref<GeneratedTerrain>,
+        Ref<Beach>,
+        Option<&LakeMap>,
+        Option<&BeachCover>,
+    )>,
+) {
+    for (entity, colors, terrain_data, beach, lake_map, beach_cover) in &mut query {
+        let mut coverage = beach_cover.map(|beach_cover| &beach_cover.coverage);
+        let new =
+            (terrain_data.is_changed() || beach.is_changed() || coverage.is_none()).then(|| {
+                let lake_cells = lake_map
+                    .filter(|_| beach.lakes)
+                    .map(|lake_map| &lake_map.cells);
+                let water: Vec<Vec<bool>> = terrain_data
+                    .heights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| {
+                        column
+                            .iter()
+                            .enumerate()
+                            .map(|(j, &height)| {
+                                height <= terrain_data.sea_level + f32::EPSILON
+                                    || lake_cells.is_some_and(|cells| cells[i][j].is_some())
+                            })
+                            .collect()
+                    })
+                    .collect();
+                let cell_size = terrain_data.stretch().x / terrain_data.resolution.max(1) as f32;
+                BeachCover {
+                    coverage: beach.coverage(
+                        &terrain_data.heights,
+                        &water,
+                        terrain_data.resolution,
+                        cell_size,
+                    ),
+                }
+            });
+        if let Some(new) = &new {
+            coverage = Some(&new.coverage);
+        }
+        if let (Some(mut colors), Some(coverage)) = (colors, coverage) {
+            if colors.is_recoloring() {
+                colors.blend(&terrain_data, |[i, j], _, color| {
+                    coverage
+                        .get(i)
+                        .and_then(|column| column.get(j))
+                        .map_or(color, |cover| beach.blend(color, *cover))
+                });
+            }
+        }
+        if let Some(new) = new.filter(|new| beach_cover != Some(new)) {
+            commands.entity(entity).insert(new);
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! Classify biomes from temperature and precipitation with a Whittaker diagram
+//! # Example
+//! For configuration, see [`Whittaker`](struct.Whittaker.html).
+//! Temperature and precipitation of any source, e.g. maps or planets, can be classified directly
+//! with [`Whittaker::classify`](struct.Whittaker.html#method.classify).
+//! Borders between biomes are softened with [`BiomeMap::weights`](struct.BiomeMap.html#method.weights)
+//! and a [`BiomeTransition`](struct.BiomeTransition.html), used by palettes, splat layers and scatter rules
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::biome::{Biome, BiomeMap, BiomePlugin, Whittaker};
+//! use bevy_generative::climate::{Climate, ClimatePlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     assert_eq!(Whittaker::default().classify(25.0, 300.0), Biome::TropicalRainforest);
+//!
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, ClimatePlugin, BiomePlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_center)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Climate::default(), Whittaker::default()));
+//! }
+//!
+//! fn print_center(query: Query<&BiomeMap, Changed<BiomeMap>>) {
+//!     for biomes in &query {
+//!         println!("{:?}", biomes.biome_at(0.0, 0.0));
+//!     }
+//! }
+//! ```
+use core::fmt;
+
+use bevy::prelude::*;
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+use crate::climate::ClimateData;
+
+/// Biome of the Whittaker diagram
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Biome {
+    /// Cold and treeless
+    Tundra,
+    /// Cold coniferous forest, also called taiga
+    BorealForest,
+    /// Dry grassland and cold desert
+    TemperateGrassland,
+    /// Dry woodland and shrubland
+    Woodland,
+    /// Deciduous forest
+    TemperateSeasonalForest,
+    /// Wet temperate forest
+    TemperateRainforest,
+    /// Hot desert
+    SubtropicalDesert,
+    /// Savanna and dry tropical forest
+    TropicalSeasonalForest,
+    /// Wet tropical forest
+    TropicalRainforest,
+}
+
+impl Biome {
+    /// Color of the biome in the Whittaker diagram
+    #[must_use]
+    pub const fn color(self) -> [u8; 4] {
+        match self {
+            Self::Tundra => [147, 167, 172, 255],
+            Self::BorealForest => [91, 143, 82, 255],
+            Self::TemperateGrassland => [250, 219, 7, 255],
+            Self::Woodland => [179, 124, 6, 255],
+            Self::TemperateSeasonalForest => [41, 188, 86, 255],
+            Self::TemperateRainforest => [10, 84, 109, 255],
+            Self::SubtropicalDesert => [200, 113, 55, 255],
+            Self::TropicalSeasonalForest => [152, 167, 34, 255],
+            Self::TropicalRainforest => [7, 83, 48, 255],
+        }
+    }
+}
+
+impl fmt::Display for Biome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tundra => write!(f, "Tundra"),
+            Self::BorealForest => write!(f, "Boreal Forest"),
+            Self::TemperateGrassland => write!(f, "Temperate Grassland"),
+            Self::Woodland => write!(f, "Woodland"),
+            Self::TemperateSeasonalForest => write!(f, "Temperate Seasonal Forest"),
+            Self::TemperateRainforest => write!(f, "Temperate Rainforest"),
+            Self::SubtropicalDesert => write!(f, "Subtropical Desert"),
+            Self::TropicalSeasonalForest => write!(f, "Tropical Seasonal Forest"),
+            Self::TropicalRainforest => write!(f, "Tropical Rainforest"),
+        }
+    }
+}
+
+/// Area of the Whittaker diagram covered by a biome
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Zone {
+    /// Biome of the zone
+    pub biome: Biome,
+    /// Minimum and maximum mean temperature in °C, the maximum is exclusive
+    pub temperature: [f32; 2],
+    /// Minimum and maximum precipitation in cm per year, the maximum is exclusive
+    pub precipitation: [f32; 2],
+}
+
+impl Zone {
+    fn contains(&self, temperature: f32, precipitation: f32) -> bool {
+        (self.temperature[0]..self.temperature[1]).contains(&temperature)
+            && (self.precipitation[0]..self.precipitation[1]).contains(&precipitation)
+    }
+}
+
+/// Component for biome classification, added to an entity with a `Climate` component.
+/// Can also be used on its own to classify temperature and precipitation
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Whittaker {
+    /// Zones of the diagram, the first zone containing a climate is used
+    pub zones: Vec<Zone>,
+    /// Biome of climates outside of every zone
+    pub fallback: Biome,
+}
+
+impl Default for Whittaker {
+    fn default() -> Self {
+        let zone = |biome, temperature, precipitation| Zone {
+            biome,
+            temperature,
+            precipitation,
+        };
+        let [cold, hot] = [f32::MIN, f32::MAX];
+        Self {
+            zones: vec![
+                zone(Biome::Tundra, [cold, -5.0], [0.0, hot]),
+                zone(Biome::TemperateGrassland, [-5.0, 20.0], [0.0, 25.0]),
+                zone(Biome::BorealForest, [-5.0, 5.0], [25.0, hot]),
+                zone(Biome::Woodland, [5.0, 20.0], [25.0, 100.0]),
+                zone(Biome::TemperateSeasonalForest, [5.0, 20.0], [100.0, 200.0]),
+                zone(Biome::TemperateRainforest, [5.0, 20.0], [200.0, hot]),
+                zone(Biome::SubtropicalDesert, [20.0, hot], [0.0, 50.0]),
+                zone(Biome::TropicalSeasonalForest, [20.0, hot], [50.0, 250.0]),
+                zone(Biome::TropicalRainforest, [20.0, hot], [250.0, hot]),
+            ],
+            fallback: Biome::Tundra,
+        }
+    }
+}
+
+impl Whittaker {
+    /// Biome of a mean `temperature` in °C and `precipitation` in cm per year
+    #[must_use]
+    pub fn classify(&self, temperature: f32, precipitation: f32) -> Biome {
+        self.zones
+            .iter()
+            .find(|zone| zone.contains(temperature, precipitation))
+            .map_or(self.fallback, |zone| zone.biome)
+    }
+
+    /// Biomes of grids of temperature and precipitation with the same layout
+    #[must_use]
+    pub fn classify_grid(
+        &self,
+        temperature: &[Vec<f32>],
+        precipitation: &[Vec<f32>],
+    ) -> Vec<Vec<Biome>> {
+        temperature
+            .iter()
+            .zip(precipitation)
+            .map(|(temperature, precipitation)| {
+                temperature
+                    .iter()
+                    .zip(precipitation)
+                    .map(|(&temperature, &precipitation)| self.classify(temperature, precipitation))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Biome of every terrain vertex, inserted on the entity with the `Whittaker` component
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug)]
+pub struct BiomeMap {
+    /// Biome of every vertex, indexed like `GeneratedTerrain::heights`
+    pub cells: Vec<Vec<Biome>>,
+    /// Number of vertices per world unit
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl BiomeMap {
+    /// Biome at the vertex nearest to `x`, `z`, `None` outside of the terrain
+    #[must_use]
+    pub fn biome_at(&self, x: f32, z: f32) -> Option<Biome> {
+        let resolution = self.resolution as f32;
+        let i = ((x + self.size[0] as f32 / 2.0) * resolution).round();
+        let j = ((z + self.size[1] as f32 / 2.0) * resolution).round();
+        if i < 0.0 || j < 0.0 {
+            return None;
+        }
+        self.cells.get(i as usize)?.get(j as usize).copied()
+    }
+
+    /// Weight of `biomes` at every vertex from 0 to 1, softened across their borders by `transition`
+    #[must_use]
+    pub fn weights(&self, biomes: &[Biome], transition: &BiomeTransition) -> BiomeWeights {
+        let resolution = self.resolution.max(1) as f32;
+        let columns = self.cells.len();
+        let rows = self.cells.first().map_or(0, Vec::len);
+        let [along_x, along_z] = [
+            Perlin::new(transition.seed),
+            Perlin::new(transition.seed.wrapping_add(1)),
+        ];
+        // Borders wriggle on the scale of the dithering
+        let frequency = 1.0 / f64::from((transition.dither * 4.0).max(f32::EPSILON));
+        let mask: Vec<Vec<f32>> = (0..columns)
+            .map(|i| {
+                (0..rows)
+                    .map(|j| {
+                        let (mut x, mut z) = (i as f32, j as f32);
+                        if transition.dither > 0.0 {
+                            let point = [
+                                i as f64 / f64::from(resolution) * frequency,
+                                j as f64 / f64::from(resolution) * frequency,
+                            ];
+                            // Perlin noise rarely leaves -0.5 to 0.5
+                            let offset = |noise: &Perlin| (noise.get(point) * 2.0).clamp(-1.0, 1.0);
+                            x += offset(&along_x) as f32 * transition.dither * resolution;
+                            z += offset(&along_z) as f32 * transition.dither * resolution;
+                        }
+                        let i = (x.round().max(0.0) as usize).min(columns - 1);
+                        let j = (z.round().max(0.0) as usize).min(rows - 1);
+                        if biomes.contains(&self.cells[i][j]) {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let radius = (transition.width.max(0.0) * resolution / 2.0).round() as usize;
+        BiomeWeights {
+            weights: box_blur(&mask, radius),
+            resolution: self.resolution,
+            size: self.size,
+        }
+    }
+}
+
+/// Blending across the borders of biomes, see [`BiomeMap::weights`]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BiomeTransition {
+    /// Width in world units over which biomes blend across their borders, 0 for hard borders
+    pub width: f32,
+    /// Largest distance in world units the borders are moved by noise,
+    /// so they do not follow the contours of the climate
+    pub dither: f32,
+    /// Seed of the dithering noise
+    pub seed: u32,
+}
+
+impl Default for BiomeTransition {
+    fn default() -> Self {
+        Self {
+            width: 0.1,
+            dither: 0.05,
+            seed: 0,
+        }
+    }
+}
+
+impl BiomeTransition {
+    /// Transition without blending or dithering
+    pub const HARD: Self = Self {
+        width: 0.0,
+        dither: 0.0,
+        seed: 0,
+    };
+}
+
+/// Weight of a set of biomes at every terrain vertex, see [`BiomeMap::weights`]
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct BiomeWeights {
+    /// Weight of every vertex from 0 to 1, indexed like `GeneratedTerrain::heights`
+    pub weights: Vec<Vec<f32>>,
+    /// Number of vertices per world unit
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl BiomeWeights {
+    /// Bilinearly interpolated weight at `x`, `z`, 0 outside of the terrain
+    #[must_use]
+    pub fn weight_at(&self, x: f32, z: f32) -> f32 {
+        let resolution = self.resolution as f32;
+        let i = (x + self.size[0] as f32 / 2.0) * resolution;
+        let j = (z + self.size[1] as f32 / 2.0) * resolution;
+        let columns = self.weights.len();
+        let rows = self.weights.first().map_or(0, Vec::len);
+        if i < 0.0 || j < 0.0 || i > (columns as f32 - 1.0) || j > (rows as f32 - 1.0) {
+            return 0.0;
+        }
+        let (i0, j0) = (i as usize, j as usize);
+        let (i1, j1) = ((i0 + 1).min(columns - 1), (j0 + 1).min(rows - 1));
+        let (tx, tz) = (i - i0 as f32, j - j0 as f32);
+        let weight = |i: usize, j: usize| self.weights[i][j];
+        let near = (weight(i1, j0) - weight(i0, j0)).mul_add(tx, weight(i0, j0));
+        let far = (weight(i1, j1) - weight(i0, j1)).mul_add(tx, weight(i0, j1));
+        (far - near).mul_add(tz, near)
+    }
+}
+
+/// Mean of `mask` in a square of `radius` vertices around every vertex, clamped at the sides
+fn box_blur(mask: &[Vec<f32>], radius: usize) -> Vec<Vec<f32>> {
+    let rows = mask.first().map_or(0, Vec::len);
+    if radius == 0 || rows == 0 {
+        return mask.to_vec();
+    }
+    let blur = |values: &[f32]| -> Vec<f32> {
+        let last = values.len() - 1;
+        (0..values.len())
+            .map(|index| {
+                let (start, end) = (index.saturating_sub(radius), (index + radius).min(last));
+                values[start..=end].iter().sum::<f32>() / (end - start + 1) as f32
+            })
+            .collect()
+    };
+    let columns: Vec<Vec<f32>> = mask.iter().map(|column| blur(column)).collect();
+    let mut blurred = columns.clone();
+    for j in 0..rows {
+        let row: Vec<f32> = columns.iter().map(|column| column[j]).collect();
+        for (i, value) in blur(&row).into_iter().enumerate() {
+            blurred[i][j] = value;
+        }
+    }
+    blurred
+}
+
+/// Plugin to classify biomes
+pub struct BiomePlugin;
+
+impl Plugin for BiomePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, classify_biomes);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn classify_biomes(
+    mut commands: Commands,
+    query: Query<
+        (Entity, &Whittaker, &ClimateData, Option<&BiomeMap>),
+        Or<(Changed<Whittaker>, Changed<ClimateData>)>,
+    >,
+) {
+    for (entity, whittaker, climate, old) in &query {
+        let biomes = BiomeMap {
+            cells: whittaker.classify_grid(&climate.temperature, &climate.precipitation),
+            resolution: climate.resolution,
+            size: climate.size,
+        };
+        if old != Some(&biomes) {
+            commands.entity(entity).insert(biomes);
+        }
+    }
+}
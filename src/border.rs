@@ -0,0 +1,301 @@
+//! Extract coastlines and region borders from terrain and maps
+//! # Example
+//! For configuration, see [`Borders`](struct.Borders.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::border::{BorderLines, BorderPlugin, Borders};
+//! use bevy_generative::terrain::{TerrainBundle, GeneratedTerrain, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, BorderPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_coastlines)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Borders::default()));
+//! }
+//!
+//! fn print_coastlines(query: Query<(&BorderLines, &GeneratedTerrain), Changed<BorderLines>>) {
+//!     for (lines, terrain_data) in &query {
+//!         for coastline in lines.coastlines() {
+//!             println!("{:?}", coastline.on_terrain(terrain_data));
+//!         }
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "map")]
+use crate::{map::Map, noise::generate_noise_map};
+use crate::{
+    marching_squares::contours,
+    noise::{Noise, NoiseMap},
+    terrain::{GeneratedTerrain, Terrain},
+};
+
+/// Component for border extraction configuration, added to an entity with a `Terrain` or `Map` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Borders {
+    /// If true, extracts the coastlines of a terrain
+    pub coastline: bool,
+    /// If true, extracts the borders between noise regions
+    pub regions: bool,
+    /// Borders shorter than this are dropped, in world units for terrain and pixels for maps
+    pub min_length: f32,
+}
+
+impl Default for Borders {
+    fn default() -> Self {
+        Self {
+            coastline: true,
+            regions: true,
+            min_length: 0.0,
+        }
+    }
+}
+
+/// What a border separates
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BorderKind {
+    /// Land and sea of a terrain
+    Coastline,
+    /// Two neighbouring noise regions
+    Region {
+        /// Label of the region with the lower noise values
+        below: String,
+        /// Label of the region with the higher noise values
+        above: String,
+    },
+}
+
+/// Polyline along a border
+#[derive(Clone, PartialEq, Debug)]
+pub struct Border {
+    /// What the border separates
+    pub kind: BorderKind,
+    /// Points along the border, `x`, `z` relative to the terrain entity or pixel coordinates of a map.
+    /// Higher noise values, e.g. land, are on the left
+    pub points: Vec<Vec2>,
+    /// If true, the last point connects back to the first, e.g. around an island.
+    /// Borders that run off the side of the terrain or map are open
+    pub closed: bool,
+}
+
+impl Border {
+    /// Length of the border
+    #[must_use]
+    pub fn length(&self) -> f32 {
+        let open: f32 = self
+            .points
+            .windows(2)
+            .map(|pair| pair[0].distance(pair[1]))
+            .sum();
+        match (self.closed, self.points.first(), self.points.last()) {
+            (true, Some(first), Some(last)) => open + first.distance(*last),
+            _ => open,
+        }
+    }
+
+    /// Points of a terrain border on the terrain surface, relative to the terrain entity
+    #[must_use]
+    pub fn on_terrain(&self, terrain_data: &GeneratedTerrain) -> Vec<Vec3> {
+        self.points
+            .iter()
+            .map(|point| {
+                let height = terrain_data
+                    .height(point.x, point.y)
+                    .unwrap_or(terrain_data.sea_level);
+                Vec3::new(point.x, height, point.y)
+            })
+            .collect()
+    }
+}
+
+/// Extracted borders, inserted on the entity with the `Borders` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct BorderLines {
+    /// Coastlines followed by region borders
+    pub borders: Vec<Border>,
+}
+
+impl BorderLines {
+    /// Borders between land and sea
+    pub fn coastlines(&self) -> impl Iterator<Item = &Border> {
+        self.borders
+            .iter()
+            .filter(|border| border.kind == BorderKind::Coastline)
+    }
+
+    /// Borders between noise regions
+    pub fn region_borders(&self) -> impl Iterator<Item = &Border> {
+        self.borders
+            .iter()
+            .filter(|border| matches!(border.kind, BorderKind::Region { .. }))
+    }
+}
+
+/// Plugin to extract borders
+pub struct BorderPlugin;
+
+impl Plugin for BorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, extract_terrain_borders);
+        #[cfg(feature = "map")]
+        app.add_systems(Update, extract_map_borders);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn extract_terrain_borders(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &Borders,
+            &Terrain,
+            &GeneratedTerrain,
+            Option<&BorderLines>,
+        ),
+        Or<(Changed<Borders>, Changed<GeneratedTerrain>)>,
+    >,
+) {
+    for (entity, config, terrain, terrain_data, lines) in &query {
+        let resolution = terrain_data.resolution.max(1) as f32;
+        let half = Vec2::new(terrain_data.size[0] as f32, terrain_data.size[1] as f32) / 2.0;
+        let to_world = |point: Vec2| point / resolution - half;
+        let mut borders = vec![];
+        if config.coastline {
+            borders.extend(extract(
+                &terrain_data.noise,
+                f64::from(terrain.sea_percent),
+                &BorderKind::Coastline,
+                to_world,
+            ));
+        }
+        if config.regions {
+            borders.extend(region_borders(
+                &terrain_data.noise,
+                &terrain.noise,
+                to_world,
+            ));
+        }
+        let new_lines = finish(borders, config.min_length);
+        if lines != Some(&new_lines) {
+            commands.entity(entity).insert(new_lines);
+        }
+    }
+}
+
+#[cfg(feature = "map")]
+#[allow(clippy::needless_pass_by_value)]
+fn extract_map_borders(
+    mut commands: Commands,
+    query: Query<(Entity, &Borders, &Map, Option<&BorderLines>), Without<Terrain>>,
+) {
+    for (entity, config, map, lines) in &query {
+        // The map sets the size of its noise when it is generated
+        if map.noise.size != map.size {
+            continue;
+        }
+        let mut borders = vec![];
+        if config.regions {
+            let noise_values = generate_noise_map(&map.noise, map.noise.size);
+            borders.extend(region_borders(&noise_values, &map.noise, |point| point));
+        }
+        let new_lines = finish(borders, config.min_length);
+        if lines != Some(&new_lines) {
+            commands.entity(entity).insert(new_lines);
+        }
+    }
+}
+
+fn finish(mut borders: Vec<Border>, min_length: f32) -> BorderLines {
+    borders.retain(|border| border.points.len() >= 2 && border.length() >= min_length);
+    BorderLines { borders }
+}
+
+/// Borders at the position of every region but the highest, see [`Noise::region`]
+fn region_borders(
+    noise_values: &NoiseMap,
+    noise: &Noise,
+    to_world: impl Fn(Vec2) -> Vec2 + Copy,
+) -> Vec<Border> {
+    let mut regions: Vec<_> = noise.regions.iter().collect();
+    regions.sort_by(|a, b| a.position.total_cmp(&b.position));
+    regions
+        .windows(2)
+        .flat_map(|pair| {
+            let kind = BorderKind::Region {
+                below: pair[0].label.clone(),
+                above: pair[1].label.clone(),
+            };
+            extract(noise_values, pair[0].position, &kind, to_world)
+        })
+        .collect()
+}
+
+/// Contours of `grid` at `threshold`, split where they run along the side of the grid
+fn extract(
+    grid: &NoiseMap,
+    threshold: f64,
+    kind: &BorderKind,
+    to_world: impl Fn(Vec2) -> Vec2,
+) -> Vec<Border> {
+    let max = Vec2::new(
+        grid.len().saturating_sub(1) as f32,
+        grid.rows().saturating_sub(1) as f32,
+    );
+    // Contour points are clamped to the grid, so points on a side are exactly on it
+    let on_side = |a: Vec2, b: Vec2| {
+        (a.cmple(Vec2::ZERO) & b.cmple(Vec2::ZERO)).any() || (a.cmpge(max) & b.cmpge(max)).any()
+    };
+    let mut borders = vec![];
+    let contours = match grid {
+        NoiseMap::Single(values) => contours(values, threshold),
+        NoiseMap::Double(values) => contours(values, threshold),
+    };
+    for contour in contours {
+        let points = contour.points;
+        let count = points.len();
+        let Some(start) = (0..count).find(|&i| on_side(points[i], points[(i + 1) % count])) else {
+            borders.push(Border {
+                kind: kind.clone(),
+                points: points.into_iter().map(&to_world).collect(),
+                closed: true,
+            });
+            continue;
+        };
+        // Walk once around the contour from the end of a segment on the side, breaking at every such segment
+        let mut polyline: Vec<Vec2> = vec![];
+        for step in 1..=count {
+            let i = (start + step) % count;
+            polyline.push(to_world(points[i]));
+            if on_side(points[i], points[(i + 1) % count]) {
+                if polyline.len() >= 2 {
+                    borders.push(Border {
+                        kind: kind.clone(),
+                        points: std::mem::take(&mut polyline),
+                        closed: false,
+                    });
+                }
+                polyline.clear();
+            }
+        }
+    }
+    borders
+}
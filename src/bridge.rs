@@ -0,0 +1,388 @@
+//! Place bridges where roads cross water or chasms
+//! # Example
+//! For configuration, see [`Bridges`](struct.Bridges.html).
+//! Rivers are crossed if the terrain also has a `FlowData`, see [`Flow`](../flow/struct.Flow.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::bridge::{BridgePlacements, BridgePlugin, Bridges};
+//! use bevy_generative::flow::{Flow, FlowPlugin};
+//! use bevy_generative::road::{RoadPlugin, Roads};
+//! use bevy_generative::settlement::{Settlement, SettlementPlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((
+//!             TerrainPlugin,
+//!             SettlementPlugin,
+//!             RoadPlugin,
+//!             FlowPlugin,
+//!             BridgePlugin,
+//!         ))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_bridges)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Settlement::default(),
+//!         Roads::default(),
+//!         Flow::default(),
+//!         Bridges::default(),
+//!     ));
+//! }
+//!
+//! fn print_bridges(query: Query<&BridgePlacements, Changed<BridgePlacements>>) {
+//!     for placements in &query {
+//!         for bridge in &placements.bridges {
+//!             println!("{:?} from {} to {}", bridge.crossing, bridge.start, bridge.end);
+//!         }
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{flow::FlowData, headless::DataOnly, road::RoadNetwork, terrain::GeneratedTerrain};
+
+/// Component for bridge placement, added to an entity with a `Roads` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Bridges {
+    /// Number of vertices that must drain through a vertex for it to carry a river, requires `FlowData`
+    pub river_accumulation: f32,
+    /// If true, roads crossing the sea are bridged
+    pub sea: bool,
+    /// Depth in world units the terrain must drop below a straight span for it to be a chasm
+    pub chasm_depth: f32,
+    /// Longest bridge in world units, longer crossings are left unbridged
+    pub max_span: f32,
+    /// Minimum height of the deck above the water or the chasm floor in world units.
+    /// Decks lower than this are raised with ramps at both ends
+    pub clearance: f32,
+    /// Maximum slope of a ramp in degrees
+    pub ramp_slope: f32,
+    /// If true, simple bridge meshes are spawned as children of the terrain entity
+    pub mesh: bool,
+    /// Thickness of the deck of the bridge meshes in world units
+    pub thickness: f32,
+}
+
+impl Default for Bridges {
+    fn default() -> Self {
+        Self {
+            river_accumulation: 30.0,
+            sea: true,
+            chasm_depth: 0.05,
+            max_span: 0.5,
+            clearance: 0.02,
+            ramp_slope: 15.0,
+            mesh: true,
+            thickness: 0.01,
+        }
+    }
+}
+
+/// What a `Bridge` crosses
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Crossing {
+    /// Water draining through the terrain
+    River,
+    /// The flat sea floor
+    Sea,
+    /// Terrain dropping below the road
+    Chasm,
+}
+
+/// Bridge along a road, relative to the terrain entity
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bridge {
+    /// Index of the road in `RoadNetwork::roads`
+    pub road: usize,
+    /// What the bridge crosses
+    pub crossing: Crossing,
+    /// Start of the deck
+    pub start: Vec3,
+    /// End of the deck
+    pub end: Vec3,
+    /// Width of the deck in world units, the width of the road
+    pub width: f32,
+    /// Lowest height of the deck above the water or the terrain below it in world units
+    pub clearance: f32,
+}
+
+/// Ramp from the terrain up to the deck of a raised `Bridge`, relative to the terrain entity
+#[derive(Clone, PartialEq, Debug)]
+pub struct Ramp {
+    /// Index of the bridge in `BridgePlacements::bridges`
+    pub bridge: usize,
+    /// Lower end of the ramp on the terrain
+    pub start: Vec3,
+    /// Upper end of the ramp at the deck
+    pub end: Vec3,
+    /// Width of the ramp in world units
+    pub width: f32,
+}
+
+/// Placed bridges and ramps, inserted on the entity with the `Bridges` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct BridgePlacements {
+    /// Bridges in the order of the roads
+    pub bridges: Vec<Bridge>,
+    /// Ramps of raised bridges
+    pub ramps: Vec<Ramp>,
+}
+
+/// Marker for a bridge or ramp mesh, spawned as a child of the terrain entity
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct BridgeMesh;
+
+/// Plugin to place bridges
+pub struct BridgePlugin;
+
+impl Plugin for BridgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (place_bridges, spawn_bridge_meshes).chain());
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn place_bridges(
+    mut commands: Commands,
+    query: Query<(
+        Entity,
+        Ref<Bridges>,
+        Ref<RoadNetwork>,
+        &GeneratedTerrain,
+        Option<Ref<FlowData>>,
+        Option<&BridgePlacements>,
+    )>,
+) {
+    for (entity, bridges, network, terrain_data, flow, old) in &query {
+        if !(bridges.is_changed()
+            || network.is_changed()
+            || flow.as_ref().is_some_and(DetectChanges::is_changed))
+        {
+            continue;
+        }
+        let new = place(&bridges, &network, terrain_data, flow.as_deref());
+        if old != Some(&new) {
+            commands.entity(entity).insert(new);
+        }
+    }
+}
+
+/// Scans every road for crossings, in the order the road points are listed
+fn place(
+    config: &Bridges,
+    network: &RoadNetwork,
+    terrain_data: &GeneratedTerrain,
+    flow: Option<&FlowData>,
+) -> BridgePlacements {
+    let mut placements = BridgePlacements::default();
+    let water = |point: Vec3| {
+        if point.y <= terrain_data.sea_level + f32::EPSILON {
+            config.sea.then_some(Crossing::Sea)
+        } else {
+            flow.and_then(|flow| flow.accumulation_at(point.x, point.z))
+                .is_some_and(|accumulation| accumulation >= config.river_accumulation)
+                .then_some(Crossing::River)
+        }
+    };
+    let horizontal = |a: Vec3, b: Vec3| a.xz().distance(b.xz());
+    for (index, road) in network.roads.iter().enumerate() {
+        let points = &road.points;
+        let mut i = 0;
+        while i + 1 < points.len() {
+            let found = water(points[i + 1]).map_or_else(
+                || {
+                    chasm_end(config, points, i).map(|end| {
+                        // Chasms with water at the bottom are crossings of the water
+                        let water = points[i + 1..end].iter().find_map(|&point| water(point));
+                        (end, water.unwrap_or(Crossing::Chasm))
+                    })
+                },
+                |crossing| {
+                    // From the last dry point to the next dry point
+                    let end = (i + 1..points.len()).find(|&j| water(points[j]).is_none());
+                    end.map(|end| (end, crossing))
+                },
+            );
+            let Some((end, crossing)) = found else {
+                i += 1;
+                continue;
+            };
+            let (start_point, end_point) = (points[i], points[end]);
+            if horizontal(start_point, end_point) > config.max_span {
+                i = end;
+                continue;
+            }
+            let ground = |point: Vec3| match crossing {
+                Crossing::Sea => terrain_data.sea_level,
+                Crossing::River | Crossing::Chasm => point.y,
+            };
+            let clearance = points[i + 1..end]
+                .iter()
+                .map(|&point| deck_height(start_point, end_point, point) - ground(point))
+                .fold(f32::INFINITY, f32::min);
+            let clearance = if clearance.is_finite() {
+                clearance
+            } else {
+                0.0
+            };
+            let raise = (config.clearance - clearance).max(0.0);
+            let lift = Vec3::Y * raise;
+            let bridge = placements.bridges.len();
+            placements.bridges.push(Bridge {
+                road: index,
+                crossing,
+                start: start_point + lift,
+                end: end_point + lift,
+                width: network.width,
+                clearance: clearance + raise,
+            });
+            if raise > 0.0 {
+                let length = raise / config.ramp_slope.to_radians().tan().max(f32::EPSILON);
+                let outward = (end_point - start_point).xz().normalize_or_zero();
+                for (deck, direction) in [(start_point, -outward), (end_point, outward)] {
+                    let foot = deck.xz() + direction * length;
+                    let foot = Vec3::new(
+                        foot.x,
+                        terrain_data.height(foot.x, foot.y).unwrap_or(deck.y),
+                        foot.y,
+                    );
+                    placements.ramps.push(Ramp {
+                        bridge,
+                        start: foot,
+                        end: deck + lift,
+                        width: network.width,
+                    });
+                }
+            }
+            i = end;
+        }
+    }
+    placements
+}
+
+/// End of a chasm starting at `points[start]`, where the road starts to drop into it and climbs back
+/// to the height of `start` within one span
+fn chasm_end(config: &Bridges, points: &[Vec3], start: usize) -> Option<usize> {
+    if points.get(start + 1)?.y >= points[start].y {
+        return None;
+    }
+    let rim = points[start].y - config.chasm_depth / 2.0;
+    let mut lowest = f32::INFINITY;
+    (start + 1..points.len())
+        .take_while(|&j| points[start].xz().distance(points[j].xz()) <= config.max_span)
+        .find(|&j| {
+            lowest = lowest.min(points[j].y);
+            lowest < points[start].y - config.chasm_depth && points[j].y >= rim
+        })
+        .filter(|&end| depth_below(&points[start..=end]) >= config.chasm_depth)
+}
+
+/// Height of a straight deck from `start` to `end` above `point`
+fn deck_height(start: Vec3, end: Vec3, point: Vec3) -> f32 {
+    let direction = end.xz() - start.xz();
+    let t = ((point.xz() - start.xz()).dot(direction)
+        / direction.length_squared().max(f32::EPSILON))
+    .clamp(0.0, 1.0);
+    (end.y - start.y).mul_add(t, start.y)
+}
+
+/// Deepest drop of the points between the ends of `points` below the straight line connecting the ends
+fn depth_below(points: &[Vec3]) -> f32 {
+    let (Some(&start), Some(&end)) = (points.first(), points.last()) else {
+        return 0.0;
+    };
+    points
+        .iter()
+        .map(|&point| deck_height(start, end, point) - point.y)
+        .fold(0.0, f32::max)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn spawn_bridge_meshes(
+    mut commands: Commands,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    query: Query<(Entity, &Bridges, Ref<BridgePlacements>, Has<DataOnly>)>,
+    spawned: Query<(Entity, &Parent), With<BridgeMesh>>,
+) {
+    for (entity, config, placements, data_only) in &query {
+        if !placements.is_changed() {
+            continue;
+        }
+        for (child, parent) in &spawned {
+            if parent.get() == entity {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+        // Without the render assets only the `BridgePlacements` are generated
+        let (true, Some(meshes), Some(materials)) = (
+            config.mesh && !data_only,
+            meshes.as_deref_mut(),
+            materials.as_deref_mut(),
+        ) else {
+            continue;
+        };
+        let material = materials.add(StandardMaterial {
+            base_color: Color::rgb(0.45, 0.33, 0.22),
+            perceptual_roughness: 0.9,
+            ..default()
+        });
+        let spans = placements
+            .bridges
+            .iter()
+            .map(|bridge| (bridge.start, bridge.end, bridge.width))
+            .chain(
+                placements
+                    .ramps
+                    .iter()
+                    .map(|ramp| (ramp.start, ramp.end, ramp.width)),
+            );
+        commands.entity(entity).with_children(|children| {
+            for (start, end, width) in spans {
+                let direction = end - start;
+                let length = direction.length();
+                if length <= f32::EPSILON {
+                    continue;
+                }
+                children.spawn((
+                    BridgeMesh,
+                    PbrBundle {
+                        mesh: meshes.add(Mesh::from(shape::Box::new(
+                            length,
+                            config.thickness,
+                            width,
+                        ))),
+                        material: material.clone(),
+                        transform: Transform {
+                            // The top of the deck is level with the road
+                            translation: (start + end) / 2.0 - Vec3::Y * config.thickness / 2.0,
+                            // Yaw and pitch only, so the deck does not roll
+                            rotation: Quat::from_rotation_y((-direction.z).atan2(direction.x))
+                                * Quat::from_rotation_z((direction.y / length).asin()),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ));
+            }
+        });
+    }
+}
@@ -0,0 +1,326 @@
+//! Generate animated caustics and water normal textures
+//! # Example
+//! For configuration, see [`WaterTexture`](struct.WaterTexture.html).
+//! Frames tile in both directions and loop over `WaterTexture::duration`, the noise is sampled on a 4D torus.
+//! With a `StandardMaterial` on the entity the current frame is bound as its base color or normal map texture
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::caustics::{WaterTexture, WaterTextureKind, WaterTexturePlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(WaterTexturePlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(
+//!     mut commands: Commands,
+//!     mut meshes: ResMut<Assets<Mesh>>,
+//!     mut materials: ResMut<Assets<StandardMaterial>>,
+//! ) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         WaterTexture {
+//!             kind: WaterTextureKind::Caustics,
+//!             ..default()
+//!         },
+//!         PbrBundle {
+//!             mesh: meshes.add(shape::Plane::from_size(2.0).into()),
+//!             material: materials.add(StandardMaterial::default()),
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use std::f64::consts::TAU;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::noise::{get_noise_at_point_4d, Function, Method};
+#[cfg(feature = "image")]
+use crate::util::export_sequence;
+
+/// Kind of a `WaterTexture`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WaterTextureKind {
+    /// Lines of light focused by waves onto the ground under water
+    #[default]
+    Caustics,
+    /// Tangent space normal map of the waves
+    Normals,
+}
+
+/// Component for animated water texture configuration
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct WaterTexture {
+    /// What the texture shows
+    pub kind: WaterTextureKind,
+    /// Seed of the noise
+    pub seed: u32,
+    /// Width and height of every frame in texels
+    pub size: u32,
+    /// Number of frames in a loop
+    pub frames: u32,
+    /// Seconds of a loop
+    pub duration: f32,
+    /// Roughly the number of waves across a frame
+    pub scale: f64,
+    /// How far the waves drift during a loop, relative to their size
+    pub motion: f64,
+    /// Exponent thinning the lines of the caustics
+    pub sharpness: f32,
+    /// How strongly the normal map tilts the surface, 0 is flat
+    pub strength: f32,
+    /// Color of the caustics, the alpha channel is multiplied with their brightness
+    pub color: [u8; 4],
+    /// If true, exports the frames as a png sequence
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for WaterTexture {
+    fn default() -> Self {
+        Self {
+            kind: WaterTextureKind::Caustics,
+            seed: 0,
+            size: 128,
+            frames: 32,
+            duration: 4.0,
+            scale: 4.0,
+            motion: 0.5,
+            sharpness: 4.0,
+            strength: 1.0,
+            color: [255, 255, 255, 255],
+            export: false,
+        }
+    }
+}
+
+impl WaterTexture {
+    /// Frame at `phase` of the loop, from 0 to 1. The image has a repeating sampler
+    #[must_use]
+    pub fn frame(&self, phase: f64) -> Image {
+        let size = self.size.max(1);
+        let heights = self.heights(phase, self.seed);
+        let mut data = Vec::with_capacity(size as usize * size as usize * 4);
+        let format = match self.kind {
+            WaterTextureKind::Caustics => {
+                // Two ridged layers cross into the web of bright lines
+                let crossing = self.heights(phase, self.seed.wrapping_add(1));
+                let [r, g, b, a] = self.color;
+                for (first, second) in heights.iter().flatten().zip(crossing.iter().flatten()) {
+                    let brightness = ((1.0 - first.abs()) * (1.0 - second.abs()))
+                        .powf(self.sharpness.max(0.0))
+                        .clamp(0.0, 1.0);
+                    for channel in [r, g, b, a] {
+                        data.push((f32::from(channel) * brightness).round() as u8);
+                    }
+                }
+                TextureFormat::Rgba8UnormSrgb
+            }
+            WaterTextureKind::Normals => {
+                let last = size as usize - 1;
+                // Heights range from -1 to 1 over about a tenth of the frame
+                let slope = self.strength * size as f32 / 20.0;
+                for j in 0..=last {
+                    for i in 0..=last {
+                        let (left, right) = (
+                            if i == 0 { last } else { i - 1 },
+                            if i == last { 0 } else { i + 1 },
+                        );
+                        let (back, front) = (
+                            if j == 0 { last } else { j - 1 },
+                            if j == last { 0 } else { j + 1 },
+                        );
+                        let dx = (heights[j][right] - heights[j][left]) * slope;
+                        let dz = (heights[front][i] - heights[back][i]) * slope;
+                        let normal = Vec3::new(-dx, 1.0, -dz).normalize();
+                        // Tangent along `u`, bitangent along `v`
+                        for value in [normal.x, normal.z, normal.y] {
+                            data.push((value.mul_add(0.5, 0.5) * 255.0).round() as u8);
+                        }
+                        data.push(255);
+                    }
+                }
+                TextureFormat::Rgba8Unorm
+            }
+        };
+        let mut image = Image::new(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+        );
+        image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: ImageAddressMode::Repeat,
+            address_mode_v: ImageAddressMode::Repeat,
+            ..ImageSamplerDescriptor::linear()
+        });
+        image
+    }
+
+    /// Noise from -1 to 1 of every texel indexed by `[v][u]`.
+    /// Both texture axes wrap around circles of the torus, and the looping phase moves the torus along another circle
+    fn heights(&self, phase: f64, seed: u32) -> Vec<Vec<f32>> {
+        let size = self.size.max(1);
+        let radius = self.scale / TAU;
+        let (time_x, time_y) = ((TAU * phase).cos(), (TAU * phase).sin());
+        let drift = self.motion * radius;
+        let function = Function {
+            octaves: 3,
+            ..default()
+        };
+        (0..size)
+            .map(|j| {
+                let v = TAU * f64::from(j) / f64::from(size);
+                (0..size)
+                    .map(|i| {
+                        let u = TAU * f64::from(i) / f64::from(size);
+                        let point = [
+                            radius.mul_add(u.cos(), drift * time_x),
+                            radius.mul_add(u.sin(), drift * time_y),
+                            radius.mul_add(v.cos(), drift * time_y),
+                            radius.mul_add(v.sin(), -(drift * time_x)),
+                        ];
+                        get_noise_at_point_4d(
+                            point,
+                            seed,
+                            1.0,
+                            [0.0; 4],
+                            &Method::Perlin,
+                            &function,
+                        ) as f32
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Frames of a `WaterTexture`, inserted on the entity with the `WaterTexture` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct WaterTextureFrames {
+    /// Images of the loop in order
+    pub frames: Vec<Handle<Image>>,
+    /// Seconds of a loop
+    pub duration: f32,
+}
+
+impl WaterTextureFrames {
+    /// Frame shown `seconds` after the start, looping
+    #[must_use]
+    pub fn at(&self, seconds: f32) -> Option<&Handle<Image>> {
+        let phase = (seconds / self.duration.max(f32::EPSILON)).rem_euclid(1.0);
+        let index = (phase * self.frames.len() as f32) as usize;
+        self.frames
+            .get(index.min(self.frames.len().saturating_sub(1)))
+    }
+}
+
+/// Plugin to generate and animate water textures
+pub struct WaterTexturePlugin;
+
+impl Plugin for WaterTexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (generate_water_textures, animate_water_textures).chain(),
+        );
+    }
+}
+
+fn generate_water_textures(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut query: Query<(Entity, &mut WaterTexture), Changed<WaterTexture>>,
+) {
+    for (entity, mut texture) in &mut query {
+        let count = texture.frames.max(1);
+        let frames: Vec<Handle<Image>> = (0..count)
+            .map(|frame| images.add(texture.frame(f64::from(frame) / f64::from(count))))
+            .collect();
+        #[cfg(feature = "image")]
+        if texture.export {
+            let name = match texture.kind {
+                WaterTextureKind::Caustics => "caustics",
+                WaterTextureKind::Normals => "water_normals",
+            };
+            export_sequence(
+                name,
+                frames
+                    .iter()
+                    .filter_map(|frame| images.get(frame))
+                    .filter_map(|frame| {
+                        image::ImageBuffer::from_raw(
+                            frame.width(),
+                            frame.height(),
+                            frame.data.clone(),
+                        )
+                    })
+                    .collect(),
+            );
+        }
+        texture.bypass_change_detection().export = false;
+        commands.entity(entity).insert(WaterTextureFrames {
+            frames,
+            duration: texture.duration,
+        });
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn animate_water_textures(
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query: Query<(
+        &WaterTexture,
+        &WaterTextureFrames,
+        &Handle<StandardMaterial>,
+    )>,
+) {
+    for (texture, frames, material) in &query {
+        let Some(frame) = frames.at(time.elapsed_seconds()) else {
+            continue;
+        };
+        let Some(current) = materials.get(material) else {
+            continue;
+        };
+        let bound = match texture.kind {
+            WaterTextureKind::Caustics => &current.base_color_texture,
+            WaterTextureKind::Normals => &current.normal_map_texture,
+        };
+        // Only writes when the frame changes, so the material is not prepared again every update
+        if bound.as_ref() == Some(frame) {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(material) {
+            match texture.kind {
+                WaterTextureKind::Caustics => material.base_color_texture = Some(frame.clone()),
+                WaterTextureKind::Normals => material.normal_map_texture = Some(frame.clone()),
+            }
+        }
+    }
+}
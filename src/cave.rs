@@ -0,0 +1,376 @@
+//! Generate caves
+//! # Example
+//! For configuration, see [`Cave`](struct.Cave.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::cave::{CaveBundle, CavePlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(CavePlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(CaveBundle::default());
+//! }
+//! ```
+use bevy::{prelude::*, render::render_resource::PrimitiveTopology};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dungeon::{carve_segment, surround_with_walls, Tile, TileMesh},
+    headless::DataOnly,
+    util::{export_model, Rng},
+};
+
+/// Component for cave configuration
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Cave {
+    /// Seed of the random fill
+    pub seed: u32,
+    /// Size of the cave in tiles
+    pub size: [u32; 2],
+    /// Percentage of tiles that start out solid
+    pub fill_percent: f64,
+    /// Number of smoothing iterations
+    pub iterations: u32,
+    /// Number of solid neighbours (out of 8) at which a tile becomes solid.
+    /// Tiles with one neighbour less keep their state, tiles with fewer become open
+    pub smoothing_threshold: u32,
+    /// Caverns with fewer tiles are filled in
+    pub min_cavern_size: usize,
+    /// If true, caverns are connected with tunnels so every open tile is reachable
+    pub connect: bool,
+    /// Width of the connecting tunnels in tiles
+    pub tunnel_width: u32,
+    /// Thickness of the walls around open tiles, solid tiles further away are empty
+    pub wall_thickness: u32,
+    /// If true, extrudes floor and wall mesh written to `PbrBundle`
+    pub mesh: bool,
+    /// Size of a tile in world units
+    pub tile_size: f32,
+    /// Height of the walls in world units
+    pub wall_height: f32,
+    /// Color of floor tiles
+    pub floor_color: [u8; 4],
+    /// Color of wall tiles
+    pub wall_color: [u8; 4],
+    /// If true, renders cave mesh as wireframe
+    pub wireframe: bool,
+    /// If true, exports model in glb format
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for Cave {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: [64, 48],
+            fill_percent: 45.0,
+            iterations: 5,
+            smoothing_threshold: 5,
+            min_cavern_size: 20,
+            connect: true,
+            tunnel_width: 2,
+            wall_thickness: 1,
+            mesh: true,
+            tile_size: 0.075,
+            wall_height: 0.15,
+            floor_color: [110, 100, 90, 255],
+            wall_color: [60, 55, 50, 255],
+            wireframe: false,
+            export: false,
+        }
+    }
+}
+
+/// Generated cave, inserted on the entity with the `Cave` component
+#[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaveLayout {
+    /// Tile grid, indexed by `[x][y]`.
+    /// Caverns are `Tile::Floor`, connecting tunnels are `Tile::Corridor`
+    pub tiles: Vec<Vec<Tile>>,
+    /// Tiles of each cavern before tunnels are carved, largest cavern first
+    pub caverns: Vec<Vec<[u32; 2]>>,
+}
+
+impl CaveLayout {
+    /// Tile at `x`, `y`, `None` if outside of the cave
+    #[must_use]
+    pub fn get(&self, x: u32, y: u32) -> Option<Tile> {
+        self.tiles
+            .get(x as usize)
+            .and_then(|column| column.get(y as usize))
+            .copied()
+    }
+
+    /// Walkability grid, indexed by `[x][y]`
+    #[must_use]
+    pub fn walkable(&self) -> Vec<Vec<bool>> {
+        self.tiles
+            .iter()
+            .map(|column| column.iter().map(|tile| tile.is_walkable()).collect())
+            .collect()
+    }
+}
+
+/// Render `Cave` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct CaveBundle {
+    /// Cave configuration
+    pub cave: Cave,
+    /// Generated mesh data is written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate caves
+pub struct CavePlugin;
+
+impl Plugin for CavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_cave);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_cave(
+    mut commands: Commands,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Cave,
+            Option<&mut Handle<Mesh>>,
+            Option<&Handle<StandardMaterial>>,
+            Has<DataOnly>,
+        ),
+        Changed<Cave>,
+    >,
+) {
+    for (entity, mut cave, mesh_handle, material, data_only) in &mut query {
+        let layout = generate_layout(&cave);
+
+        // Without the render assets only the layout is generated
+        if let (true, Some(meshes)) = (cave.mesh && !data_only, meshes.as_deref_mut()) {
+            if let Some(material) = materials
+                .as_deref_mut()
+                .zip(material)
+                .and_then(|(materials, material)| materials.get_mut(material))
+            {
+                *material = StandardMaterial {
+                    perceptual_roughness: 0.9,
+                    ..default()
+                };
+            }
+            let TileMesh {
+                positions,
+                normals,
+                uvs,
+                colors,
+                mut indices,
+            } = TileMesh::extrude(
+                &layout.tiles,
+                cave.tile_size,
+                cave.wall_height,
+                cave.floor_color,
+                cave.wall_color,
+            );
+
+            if cave.wireframe {
+                let triangle_number = indices.len() / 3;
+                let cloned_indices = indices.clone();
+                indices = vec![];
+                for i in 0..triangle_number {
+                    for j in &[0, 1, 1, 2, 2, 0] {
+                        indices.push(cloned_indices[i * 3 + j]);
+                    }
+                }
+            }
+
+            let mut mesh = if cave.wireframe {
+                Mesh::new(PrimitiveTopology::LineList)
+            } else {
+                Mesh::new(PrimitiveTopology::TriangleList)
+            };
+            mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+            if let Some(mut mesh_handle) = mesh_handle {
+                *mesh_handle = meshes.add(mesh);
+            }
+
+            if cave.export {
+                export_model(&positions, indices, &colors);
+                cave.bypass_change_detection().export = false;
+            }
+        }
+
+        commands.entity(entity).insert(layout);
+    }
+}
+
+fn generate_layout(cave: &Cave) -> CaveLayout {
+    let [width, depth] = cave.size.map(|side| side as usize);
+    let border = cave.wall_thickness.max(1) as usize;
+    let mut rng = Rng::new(u64::from(cave.seed));
+
+    // Solid tiles are true, the border of the cave is always solid
+    let mut solid: Vec<Vec<bool>> = (0..width)
+        .map(|x| {
+            (0..depth)
+                .map(|y| {
+                    x < border
+                        || y < border
+                        || x + border >= width
+                        || y + border >= depth
+                        || rng.next_f64() * 100.0 < cave.fill_percent
+                })
+                .collect()
+        })
+        .collect();
+
+    for _ in 0..cave.iterations {
+        let previous = solid.clone();
+        for (x, column) in solid.iter_mut().enumerate() {
+            for (y, tile) in column.iter_mut().enumerate() {
+                if x < border || y < border || x + border >= width || y + border >= depth {
+                    continue;
+                }
+                let neighbours = (x - 1..=x + 1)
+                    .flat_map(|nx| (y - 1..=y + 1).map(move |ny| (nx, ny)))
+                    .filter(|&(nx, ny)| (nx, ny) != (x, y) && previous[nx][ny])
+                    .count() as u32;
+                if neighbours >= cave.smoothing_threshold {
+                    *tile = true;
+                } else if neighbours + 1 < cave.smoothing_threshold {
+                    *tile = false;
+                }
+            }
+        }
+    }
+
+    let mut caverns = find_caverns(&solid);
+    caverns.retain(|cavern| {
+        let keep = cavern.len() >= cave.min_cavern_size;
+        if !keep {
+            for &[x, y] in cavern {
+                solid[x as usize][y as usize] = true;
+            }
+        }
+        keep
+    });
+    caverns.sort_by_key(|cavern| std::cmp::Reverse(cavern.len()));
+
+    let mut tiles: Vec<Vec<Tile>> = solid
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|&solid| if solid { Tile::Empty } else { Tile::Floor })
+                .collect()
+        })
+        .collect();
+
+    if cave.connect && caverns.len() > 1 {
+        // Connect the closest unconnected cavern to the connected ones until all are connected
+        let edges: Vec<Vec<[u32; 2]>> = caverns
+            .iter()
+            .map(|cavern| {
+                cavern
+                    .iter()
+                    .copied()
+                    .filter(|&[x, y]| {
+                        let (x, y) = (x as usize, y as usize);
+                        solid[x - 1][y] || solid[x + 1][y] || solid[x][y - 1] || solid[x][y + 1]
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut connected = vec![false; caverns.len()];
+        connected[0] = true;
+        for _ in 1..caverns.len() {
+            let mut closest: Option<(u32, usize, [u32; 2], [u32; 2])> = None;
+            for (_, from_edge) in edges.iter().enumerate().filter(|(i, _)| connected[*i]) {
+                for (to, to_edge) in edges.iter().enumerate().filter(|(i, _)| !connected[*i]) {
+                    for &start in from_edge {
+                        for &end in to_edge {
+                            let distance = start[0].abs_diff(end[0]) + start[1].abs_diff(end[1]);
+                            if closest.is_none_or(|(closest, ..)| distance < closest) {
+                                closest = Some((distance, to, start, end));
+                            }
+                        }
+                    }
+                }
+            }
+            let Some((_, to, start, end)) = closest else {
+                break;
+            };
+            connected[to] = true;
+            let corner = if rng.next_u64() & 1 == 0 {
+                [end[0], start[1]]
+            } else {
+                [start[0], end[1]]
+            };
+            carve_segment(&mut tiles, start, corner, cave.tunnel_width);
+            carve_segment(&mut tiles, corner, end, cave.tunnel_width);
+        }
+    }
+
+    surround_with_walls(&mut tiles, cave.wall_thickness.max(1));
+
+    CaveLayout { tiles, caverns }
+}
+
+/// Groups open tiles into 4-connected caverns
+fn find_caverns(solid: &[Vec<bool>]) -> Vec<Vec<[u32; 2]>> {
+    let width = solid.len();
+    let depth = solid.first().map_or(0, Vec::len);
+    let mut visited = vec![vec![false; depth]; width];
+    let mut caverns = vec![];
+    for x in 0..width {
+        for y in 0..depth {
+            if solid[x][y] || visited[x][y] {
+                continue;
+            }
+            let mut cavern = vec![];
+            let mut stack = vec![(x, y)];
+            visited[x][y] = true;
+            while let Some((cx, cy)) = stack.pop() {
+                cavern.push([cx as u32, cy as u32]);
+                let neighbours = [
+                    (cx.wrapping_sub(1), cy),
+                    (cx + 1, cy),
+                    (cx, cy.wrapping_sub(1)),
+                    (cx, cy + 1),
+                ];
+                for (nx, ny) in neighbours {
+                    if nx < width && ny < depth && !solid[nx][ny] && !visited[nx][ny] {
+                        visited[nx][ny] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            caverns.push(cavern);
+        }
+    }
+    caverns
+}
@@ -0,0 +1,231 @@
+//! Query the generated terrain cell by cell, one cell per vertex of the grid
+//! # Example
+//! Cells of a terrain are queried with [`GeneratedTerrain::cells`](../terrain/struct.GeneratedTerrain.html#method.cells),
+//! biomes and flow are added from their components with [`TerrainCells::with_biomes`](struct.TerrainCells.html#method.with_biomes)
+//! and [`TerrainCells::with_flow`](struct.TerrainCells.html#method.with_flow)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::biome::BiomeMap;
+//! use bevy_generative::terrain::{GeneratedTerrain, TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_center)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(TerrainBundle::default());
+//! }
+//!
+//! fn print_center(
+//!     query: Query<(&GeneratedTerrain, Option<&BiomeMap>), Changed<GeneratedTerrain>>,
+//! ) {
+//!     for (terrain, biomes) in &query {
+//!         let mut cells = terrain.cells();
+//!         if let Some(biomes) = biomes {
+//!             cells = cells.with_biomes(biomes);
+//!         }
+//!         let Some(center) = cells.cell(0.0, 0.0) else {
+//!             continue;
+//!         };
+//!         let lower = cells
+//!             .neighbors8(center.index)
+//!             .filter(|neighbor| neighbor.height < center.height)
+//!             .count();
+//!         let sea = cells.in_radius(Vec2::ZERO, 1.0).filter(|cell| cell.sea).count();
+//!         println!("{lower} lower neighbors, {sea} sea cells nearby");
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+
+use crate::{
+    biome::{Biome, BiomeMap},
+    flow::{FlowData, FlowDirection},
+    terrain::GeneratedTerrain,
+};
+
+/// Offsets to the neighbours sharing a side, clockwise from east
+const SIDES: [FlowDirection; 4] = [
+    FlowDirection::East,
+    FlowDirection::South,
+    FlowDirection::West,
+    FlowDirection::North,
+];
+
+/// Data of the terrain at one vertex of the grid
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TerrainCell {
+    /// Index of the vertex along `x` and `z`, as in `GeneratedTerrain::heights[x][z]`
+    pub index: UVec2,
+    /// Position of the vertex along `x` and `z` relative to the terrain entity
+    pub position: Vec2,
+    /// Height in world units
+    pub height: f32,
+    /// Noise value, 0 to 100
+    pub noise: f64,
+    /// Index into `Noise::regions`, `None` if the terrain has no regions
+    pub region: Option<usize>,
+    /// Slope in radians, 0 is flat
+    pub slope: f32,
+    /// Biome, `None` without a `BiomeMap`
+    pub biome: Option<Biome>,
+    /// True on the flat sea floor
+    pub sea: bool,
+    /// Number of vertices draining through the cell, `None` without `FlowData`
+    pub flow: Option<f32>,
+}
+
+/// Cell queries over a `GeneratedTerrain` and optionally its `BiomeMap` and `FlowData`
+#[derive(Clone, Copy)]
+pub struct TerrainCells<'a> {
+    terrain: &'a GeneratedTerrain,
+    biomes: Option<&'a BiomeMap>,
+    flow: Option<&'a FlowData>,
+}
+
+impl<'a> TerrainCells<'a> {
+    /// Cells of `terrain` without biomes and flow
+    #[must_use]
+    pub const fn new(terrain: &'a GeneratedTerrain) -> Self {
+        Self {
+            terrain,
+            biomes: None,
+            flow: None,
+        }
+    }
+
+    /// Fills `TerrainCell::biome` from the `BiomeMap` of the terrain
+    #[must_use]
+    pub const fn with_biomes(mut self, biomes: &'a BiomeMap) -> Self {
+        self.biomes = Some(biomes);
+        self
+    }
+
+    /// Fills `TerrainCell::flow` from the `FlowData` of the terrain
+    #[must_use]
+    pub const fn with_flow(mut self, flow: &'a FlowData) -> Self {
+        self.flow = Some(flow);
+        self
+    }
+
+    /// Number of cells along `x` and `z`
+    #[must_use]
+    pub fn dimensions(&self) -> UVec2 {
+        let columns = self.terrain.heights.len();
+        let rows = self.terrain.heights.first().map_or(0, Vec::len);
+        UVec2::new(columns as u32, rows as u32)
+    }
+
+    /// Cell at `index`, `None` outside of the grid
+    #[must_use]
+    pub fn at(&self, index: UVec2) -> Option<TerrainCell> {
+        let (i, j) = (index.x as usize, index.y as usize);
+        let position = self.terrain.vertex_position(i, j)?;
+        let (x, z) = (position.x, position.z);
+        Some(TerrainCell {
+            index,
+            position: Vec2::new(x, z),
+            height: position.y,
+            noise: self.terrain.noise.get(i, j).unwrap_or_default(),
+            region: self
+                .terrain
+                .regions
+                .get(i)
+                .and_then(|column| column.get(j))
+                .copied(),
+            slope: self.terrain.slope(x, z).unwrap_or_default(),
+            biome: self
+                .biomes
+                .and_then(|biomes| biomes.cells.get(i)?.get(j).copied()),
+            sea: position.y <= self.terrain.sea_level + f32::EPSILON,
+            flow: self
+                .flow
+                .and_then(|flow| flow.accumulation.get(i)?.get(j).copied()),
+        })
+    }
+
+    /// Index of the vertex nearest to `x`, `z`, `None` outside of the terrain
+    #[must_use]
+    pub fn index(&self, x: f32, z: f32) -> Option<UVec2> {
+        let index = self.terrain.grid_position(x, z).round();
+        let last = self.dimensions().as_vec2() - Vec2::ONE;
+        (index.cmpge(Vec2::ZERO).all() && index.cmple(last).all()).then(|| index.as_uvec2())
+    }
+
+    /// Cell of the vertex nearest to `x`, `z`, `None` outside of the terrain
+    #[must_use]
+    pub fn cell(&self, x: f32, z: f32) -> Option<TerrainCell> {
+        self.at(self.index(x, z)?)
+    }
+
+    /// Up to four cells sharing a side with `index`, clockwise from east (`+x`)
+    pub fn neighbors4(&self, index: UVec2) -> impl Iterator<Item = TerrainCell> + 'a {
+        self.neighbors(index, SIDES)
+    }
+
+    /// Up to eight cells around `index`, clockwise from east (`+x`)
+    pub fn neighbors8(&self, index: UVec2) -> impl Iterator<Item = TerrainCell> + 'a {
+        self.neighbors(index, FlowDirection::ALL)
+    }
+
+    /// Every cell, column by column
+    pub fn iter(&self) -> impl Iterator<Item = TerrainCell> + 'a {
+        self.indices(UVec2::ZERO, self.dimensions())
+    }
+
+    /// Cells with a vertex inside the rectangle from `min` to `max`, relative to the terrain entity
+    pub fn in_rect(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = TerrainCell> + 'a {
+        let (first, last) = self.index_range(min, max);
+        self.indices(first, last)
+    }
+
+    /// Cells with a vertex within `radius` world units of `center`, relative to the terrain entity
+    pub fn in_radius(&self, center: Vec2, radius: f32) -> impl Iterator<Item = TerrainCell> + 'a {
+        let (first, last) = self.index_range(center - radius, center + radius);
+        self.indices(first, last)
+            .filter(move |cell| cell.position.distance_squared(center) <= radius * radius)
+    }
+
+    fn neighbors<const N: usize>(
+        &self,
+        index: UVec2,
+        directions: [FlowDirection; N],
+    ) -> impl Iterator<Item = TerrainCell> + 'a {
+        let cells = *self;
+        directions.into_iter().filter_map(move |direction| {
+            let neighbor = index.as_ivec2() + IVec2::from(direction.offset());
+            cells.at(UVec2::try_from(neighbor).ok()?)
+        })
+    }
+
+    /// Cells from `first` to `last` exclusive
+    fn indices(&self, first: UVec2, last: UVec2) -> impl Iterator<Item = TerrainCell> + 'a {
+        let cells = *self;
+        (first.x..last.x)
+            .flat_map(move |i| (first.y..last.y).map(move |j| UVec2::new(i, j)))
+            .filter_map(move |index| cells.at(index))
+    }
+
+    /// First index and the index after the last one with a vertex from `min` to `max`
+    fn index_range(&self, min: Vec2, max: Vec2) -> (UVec2, UVec2) {
+        let dimensions = self.dimensions().as_vec2();
+        let first = self.terrain.grid_position(min.x, min.y).ceil();
+        let last = self.terrain.grid_position(max.x, max.y).floor() + Vec2::ONE;
+        let first = first.clamp(Vec2::ZERO, dimensions).as_uvec2();
+        let last = last.clamp(Vec2::ZERO, dimensions).as_uvec2();
+        (first, last.max(first))
+    }
+}
@@ -0,0 +1,297 @@
+//! Simulate climate over terrain
+//! # Example
+//! For configuration, see [`Climate`](struct.Climate.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::climate::{Climate, ClimateData, ClimatePlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, ClimatePlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_center)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Climate::default()));
+//! }
+//!
+//! fn print_center(query: Query<&ClimateData, Changed<ClimateData>>) {
+//!     for climate in &query {
+//!         println!(
+//!             "{:?} °C, {:?} cm",
+//!             climate.temperature_at(0.0, 0.0),
+//!             climate.precipitation_at(0.0, 0.0)
+//!         );
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::GeneratedTerrain;
+
+/// Component for climate configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Climate {
+    /// Direction the prevailing wind blows towards in degrees, 0 is `+x` and 90 is `+z`
+    pub wind_direction: f32,
+    /// Moisture of the air entering the terrain on the windward side, 0 to 1
+    pub inflow: f32,
+    /// Fraction of the missing moisture picked up per world unit over the sea
+    pub evaporation: f32,
+    /// Fraction of the missing moisture picked up per world unit over land, from lakes and plants
+    pub land_evaporation: f32,
+    /// Fraction of the moisture rained out per world unit over land
+    pub rainfall: f32,
+    /// Fraction of the moisture rained out per world unit of rise, wet windward slopes and dry lee sides
+    pub orographic: f32,
+    /// Precipitation in cm per year for one unit of moisture rained out per world unit
+    pub precipitation_scale: f32,
+    /// Temperature at sea level on the `-z` side of the terrain in °C
+    pub temperature: f32,
+    /// Drop in temperature from the `-z` to the `+z` side of the terrain in °C
+    pub latitude_gradient: f32,
+    /// Drop in temperature per world unit of height above sea level in °C
+    pub lapse_rate: f32,
+}
+
+impl Default for Climate {
+    fn default() -> Self {
+        Self {
+            wind_direction: 0.0,
+            inflow: 0.8,
+            evaporation: 2.0,
+            land_evaporation: 0.2,
+            rainfall: 0.3,
+            orographic: 4.0,
+            precipitation_scale: 150.0,
+            temperature: 28.0,
+            latitude_gradient: 20.0,
+            lapse_rate: 12.0,
+        }
+    }
+}
+
+/// Simulated climate of every terrain vertex, inserted on the entity with the `Climate` component.
+/// Classified into biomes by [`Whittaker`](../biome/struct.Whittaker.html)
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct ClimateData {
+    /// Mean temperature in °C, indexed like `GeneratedTerrain::heights`
+    pub temperature: Vec<Vec<f32>>,
+    /// Precipitation in cm per year, indexed like `GeneratedTerrain::heights`
+    pub precipitation: Vec<Vec<f32>>,
+    /// Number of vertices per world unit
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl ClimateData {
+    /// Temperature at `x`, `z` in °C, `None` outside of the terrain
+    #[must_use]
+    pub fn temperature_at(&self, x: f32, z: f32) -> Option<f32> {
+        self.sample(&self.temperature, x, z)
+    }
+
+    /// Precipitation at `x`, `z` in cm per year, `None` outside of the terrain
+    #[must_use]
+    pub fn precipitation_at(&self, x: f32, z: f32) -> Option<f32> {
+        self.sample(&self.precipitation, x, z)
+    }
+
+    fn sample(&self, grid: &[Vec<f32>], x: f32, z: f32) -> Option<f32> {
+        let resolution = self.resolution as f32;
+        let column = (x + self.size[0] as f32 / 2.0) * resolution;
+        let row = (z + self.size[1] as f32 / 2.0) * resolution;
+        let columns = grid.len().checked_sub(1)?;
+        let rows = grid.first()?.len().checked_sub(1)?;
+        if !(0.0..=columns as f32).contains(&column) || !(0.0..=rows as f32).contains(&row) {
+            return None;
+        }
+        let i = (column as usize).min(columns.saturating_sub(1));
+        let j = (row as usize).min(rows.saturating_sub(1));
+        let (tx, tz) = (column - i as f32, row - j as f32);
+        let value = |di: usize, dj: usize| grid[(i + di).min(columns)][(j + dj).min(rows)];
+        let near = (value(1, 0) - value(0, 0)).mul_add(tx, value(0, 0));
+        let far = (value(1, 1) - value(0, 1)).mul_add(tx, value(0, 1));
+        Some((far - near).mul_add(tz, near))
+    }
+}
+
+/// Plugin to simulate climate
+pub struct ClimatePlugin;
+
+impl Plugin for ClimatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, simulate_climate);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn simulate_climate(
+    mut commands: Commands,
+    query: Query<
+        (Entity, &Climate, &GeneratedTerrain, Option<&ClimateData>),
+        Or<(Changed<Climate>, Changed<GeneratedTerrain>)>,
+    >,
+) {
+    for (entity, climate, terrain_data, old) in &query {
+        let new = simulate(climate, terrain_data);
+        if old != Some(&new) {
+            commands.entity(entity).insert(new);
+        }
+    }
+}
+
+fn simulate(climate: &Climate, terrain_data: &GeneratedTerrain) -> ClimateData {
+    let heights = &terrain_data.base_heights;
+    let resolution = terrain_data.resolution.max(1);
+    let rows = heights.first().map_or(0, Vec::len);
+    let temperature = heights
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .enumerate()
+                .map(|(j, &height)| {
+                    let latitude = j as f32 / rows.saturating_sub(1).max(1) as f32;
+                    climate.lapse_rate.mul_add(
+                        -(height - terrain_data.sea_level).max(0.0),
+                        climate
+                            .latitude_gradient
+                            .mul_add(-latitude, climate.temperature),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+
+    let wind = Vec2::from_angle(climate.wind_direction.to_radians());
+    // The wind is swept along its dominant axis, so transpose the grid if that is `z`
+    let precipitation = if wind.x.abs() >= wind.y.abs() {
+        rain(climate, heights, terrain_data.sea_level, wind, resolution)
+    } else {
+        let transposed = transpose(heights);
+        let swapped = Vec2::new(wind.y, wind.x);
+        transpose(&rain(
+            climate,
+            &transposed,
+            terrain_data.sea_level,
+            swapped,
+            resolution,
+        ))
+    };
+
+    ClimateData {
+        temperature,
+        precipitation: blur(&precipitation),
+        resolution,
+        size: terrain_data.size,
+    }
+}
+
+/// Carries moisture column by column along `x` in the direction of `wind`,
+/// whose `x` component must be at least as long as its `y` component
+fn rain(
+    climate: &Climate,
+    heights: &[Vec<f32>],
+    sea_level: f32,
+    wind: Vec2,
+    resolution: u32,
+) -> Vec<Vec<f32>> {
+    let columns = heights.len();
+    let rows = heights.first().map_or(0, Vec::len);
+    let mut air = vec![vec![climate.inflow.clamp(0.0, 1.0); rows]; columns];
+    let mut precipitation = vec![vec![0.0; rows]; columns];
+    if columns == 0 || rows == 0 {
+        return precipitation;
+    }
+    // Offset along `y` and distance travelled per column
+    let slope = wind.y / wind.x.abs().max(f32::EPSILON);
+    let step = slope.hypot(1.0) / resolution as f32;
+    let order: Vec<usize> = if wind.x >= 0.0 {
+        (0..columns).collect()
+    } else {
+        (0..columns).rev().collect()
+    };
+    for pair in order.windows(2) {
+        let [upwind, current] = [pair[0], pair[1]];
+        for j in 0..rows {
+            let height = heights[current][j];
+            let source = j as f32 - slope;
+            let (moisture, upwind_height) = if (0.0..=(rows - 1) as f32).contains(&source) {
+                let low = (source as usize).min(rows.saturating_sub(2));
+                let high = (low + 1).min(rows - 1);
+                let t = source - low as f32;
+                let lerp = |grid: &[Vec<f32>]| {
+                    (grid[upwind][high] - grid[upwind][low]).mul_add(t, grid[upwind][low])
+                };
+                (lerp(&air), lerp(heights))
+            } else {
+                (climate.inflow.clamp(0.0, 1.0), height)
+            };
+            let mut moisture = moisture;
+            let rained = if height <= sea_level + f32::EPSILON {
+                moisture += (1.0 - moisture) * (climate.evaporation * step).clamp(0.0, 1.0);
+                moisture * (climate.rainfall * step).clamp(0.0, 1.0)
+            } else {
+                moisture += (1.0 - moisture) * (climate.land_evaporation * step).clamp(0.0, 1.0);
+                let rise = (height - upwind_height).max(0.0);
+                moisture
+                    * climate
+                        .rainfall
+                        .mul_add(step, climate.orographic * rise)
+                        .clamp(0.0, 1.0)
+            };
+            air[current][j] = moisture - rained;
+            precipitation[current][j] = rained / step * climate.precipitation_scale;
+        }
+    }
+    // Nothing has rained out upwind of the first column, use the rate of its neighbour
+    if let (Some(&first), Some(&second)) = (order.first(), order.get(1)) {
+        let neighbour = precipitation[second].clone();
+        precipitation[first] = neighbour;
+    }
+    precipitation
+}
+
+fn transpose(grid: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let rows = grid.first().map_or(0, Vec::len);
+    (0..rows)
+        .map(|j| grid.iter().map(|column| column[j]).collect())
+        .collect()
+}
+
+/// Averages every value with its neighbours, smoothing the streaks along the wind
+fn blur(grid: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let columns = grid.len();
+    let rows = grid.first().map_or(0, Vec::len);
+    (0..columns)
+        .map(|i| {
+            (0..rows)
+                .map(|j| {
+                    let neighbours: Vec<f32> = grid[i.saturating_sub(1)..=(i + 1).min(columns - 1)]
+                        .iter()
+                        .flat_map(|column| &column[j.saturating_sub(1)..=(j + 1).min(rows - 1)])
+                        .copied()
+                        .collect();
+                    neighbours.iter().sum::<f32>() / neighbours.len() as f32
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,254 @@
+//! Generate animated cloud layers
+//! # Example
+//! For configuration, see [`Clouds`](struct.Clouds.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::cloud::{CloudPlugin, Clouds};
+//! use bevy_generative::planet::{PlanetBundle, PlanetPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((PlanetPlugin, CloudPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((PlanetBundle::default(), Clouds::default()));
+//! }
+//! ```
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    noise::{get_noise_at_point_4d, Function, Method},
+    util::uv_sphere,
+};
+
+/// How the cloud texture is mapped
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CloudProjection {
+    /// Texture tiles seamlessly in both directions, for skies and flat terrain
+    Tileable,
+    /// Equirectangular texture, for cloud shells around planets
+    Sphere,
+}
+
+/// Component for cloud layer configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Clouds {
+    /// Seed of the noise
+    pub seed: u32,
+    /// Scale of the noise
+    pub scale: f64,
+    /// Offset of the noise
+    pub offset: [f64; 4],
+    /// Method used to generate noise
+    pub method: Method,
+    /// Function used to generate noise
+    pub function: Function,
+    /// Projection of the cloud texture
+    pub projection: CloudProjection,
+    /// Size of the cloud texture
+    pub size: [u32; 2],
+    /// Percentage of the sky covered by clouds
+    pub coverage: f64,
+    /// Width of the transition between clear sky and clouds, in percent
+    pub softness: f64,
+    /// Speed at which the clouds evolve, in noise units per second
+    pub speed: f64,
+    /// Seconds between texture updates, 0 updates every frame
+    pub interval: f64,
+    /// Color of the clouds, alpha determines the opacity of fully covered areas
+    pub color: [u8; 4],
+    /// If true, spawns a translucent cloud shell as a child of the entity
+    pub shell: bool,
+    /// Radius of the cloud shell
+    pub shell_radius: f32,
+    /// Generated cloud texture
+    #[serde(skip)]
+    pub image: Handle<Image>,
+}
+
+impl Default for Clouds {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: 60.0,
+            offset: [0.0; 4],
+            method: Method::Perlin,
+            function: Function::default(),
+            projection: CloudProjection::Sphere,
+            size: [512, 256],
+            coverage: 40.0,
+            softness: 10.0,
+            speed: 0.02,
+            interval: 0.1,
+            color: [255, 255, 255, 230],
+            shell: true,
+            shell_radius: 1.15,
+            image: Handle::default(),
+        }
+    }
+}
+
+/// Marker component for the cloud shell spawned by `Clouds`
+#[derive(Component)]
+pub struct CloudShell;
+
+/// Plugin to generate cloud layers
+pub struct CloudPlugin;
+
+impl Plugin for CloudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (generate_clouds, generate_cloud_shell).chain());
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn generate_clouds(
+    time: Res<Time>,
+    mut images: ResMut<Assets<Image>>,
+    mut query: Query<&mut Clouds>,
+) {
+    let elapsed = time.elapsed_seconds_f64();
+    let previous = elapsed - time.delta_seconds_f64();
+    for mut clouds in &mut query {
+        let interval = clouds.interval.max(f64::EPSILON);
+        let stepped = (elapsed / interval) as u64 != (previous / interval) as u64;
+        if !stepped && !clouds.is_changed() {
+            continue;
+        }
+        let [width, height] = clouds.size;
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let phase = elapsed * clouds.speed;
+        let threshold = 100.0 - clouds.coverage;
+        let softness = clouds.softness.max(f64::EPSILON);
+        let color = clouds.color;
+
+        let mut data: Vec<u8> = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let v = (f64::from(y) + 0.5) / f64::from(height);
+            for x in 0..width {
+                let u = (f64::from(x) + 0.5) / f64::from(width);
+                let point = match clouds.projection {
+                    // Two circles in 4D map to a torus, which tiles in both directions.
+                    // Moving the torus through the noise animates it without breaking tiling
+                    CloudProjection::Tileable => [
+                        (u * TAU).cos() / TAU + phase,
+                        (u * TAU).sin() / TAU,
+                        (v * TAU).cos() / TAU,
+                        (v * TAU).sin() / TAU + phase,
+                    ],
+                    CloudProjection::Sphere => {
+                        let latitude = FRAC_PI_2 - v * PI;
+                        let longitude = u * TAU;
+                        [
+                            latitude.cos() * longitude.cos(),
+                            latitude.sin(),
+                            latitude.cos() * longitude.sin(),
+                            phase,
+                        ]
+                    }
+                };
+                let value = (get_noise_at_point_4d(
+                    point,
+                    clouds.seed,
+                    clouds.scale / 100.0,
+                    clouds.offset,
+                    &clouds.method,
+                    &clouds.function,
+                ) + 1.0)
+                    * 50.0;
+                let density = ((value - threshold) / softness + 0.5).clamp(0.0, 1.0);
+                let density = density * density * 2.0f64.mul_add(-density, 3.0);
+                data.extend([
+                    color[0],
+                    color[1],
+                    color[2],
+                    (density * f64::from(color[3])) as u8,
+                ]);
+            }
+        }
+
+        clouds.bypass_change_detection().image = images.add(Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        ));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn generate_cloud_shell(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    clouds: Query<(Entity, &Clouds, Option<&Children>)>,
+    mut shells: Query<(&mut Transform, &Handle<StandardMaterial>), With<CloudShell>>,
+) {
+    for (entity, clouds, children) in &clouds {
+        let shell = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| shells.contains(**child))
+                .copied()
+        });
+        match (clouds.shell, shell) {
+            (true, Some(shell)) => {
+                if let Ok((mut transform, material)) = shells.get_mut(shell) {
+                    transform.scale = Vec3::splat(clouds.shell_radius);
+                    if let Some(material) = materials.get_mut(material) {
+                        material.base_color_texture = Some(clouds.image.clone());
+                    }
+                }
+            }
+            (true, None) => {
+                let shell = commands
+                    .spawn((
+                        CloudShell,
+                        PbrBundle {
+                            // Poles on `y`, where the caps and bands of the texture put them
+                            mesh: meshes.add(uv_sphere(1.0, 64, 32)),
+                            material: materials.add(StandardMaterial {
+                                base_color_texture: Some(clouds.image.clone()),
+                                alpha_mode: AlphaMode::Blend,
+                                perceptual_roughness: 1.0,
+                                ..default()
+                            }),
+                            transform: Transform::from_scale(Vec3::splat(clouds.shell_radius)),
+                            ..default()
+                        },
+                    ))
+                    .id();
+                commands.entity(entity).add_child(shell);
+            }
+            (false, Some(shell)) => commands.entity(shell).despawn_recursive(),
+            (false, None) => {}
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Spawn generators without assembling their bundles
+//! # Example
+//! For the spawned bundles, see [`SpawnGeneratorExt`](trait.SpawnGeneratorExt.html).
+//! Every spawned terrain and planet gets its own material, so changing one does not change the others
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::commands::SpawnGeneratorExt;
+//! use bevy_generative::terrain::{Terrain, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn_terrain(Terrain {
+//!         size: [4, 4],
+//!         ..default()
+//!     });
+//! }
+//! ```
+#[cfg(any(feature = "terrain", feature = "planet"))]
+use bevy::ecs::world::EntityWorldMut;
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+#[cfg(feature = "map")]
+use crate::map::{Map, MapBundle};
+#[cfg(feature = "planet")]
+use crate::planet::{Planet, PlanetBundle};
+#[cfg(feature = "terrain")]
+use crate::terrain::{Terrain, TerrainBundle};
+
+/// Extension of `Commands` spawning the bundle of a generator from its configuration
+pub trait SpawnGeneratorExt<'w, 's> {
+    /// Spawns a `TerrainBundle` with `terrain` and a new material
+    #[cfg(feature = "terrain")]
+    fn spawn_terrain<'a>(&'a mut self, terrain: Terrain) -> EntityCommands<'w, 's, 'a>;
+
+    /// Spawns a `MapBundle` with `map`
+    #[cfg(feature = "map")]
+    fn spawn_map<'a>(&'a mut self, map: Map) -> EntityCommands<'w, 's, 'a>;
+
+    /// Spawns a `PlanetBundle` with `planet` and a new material
+    #[cfg(feature = "planet")]
+    fn spawn_planet<'a>(&'a mut self, planet: Planet) -> EntityCommands<'w, 's, 'a>;
+}
+
+impl<'w, 's> SpawnGeneratorExt<'w, 's> for Commands<'w, 's> {
+    #[cfg(feature = "terrain")]
+    fn spawn_terrain<'a>(&'a mut self, terrain: Terrain) -> EntityCommands<'w, 's, 'a> {
+        let mut entity = self.spawn(TerrainBundle {
+            terrain,
+            ..default()
+        });
+        entity.add(insert_material);
+        entity
+    }
+
+    #[cfg(feature = "map")]
+    fn spawn_map<'a>(&'a mut self, map: Map) -> EntityCommands<'w, 's, 'a> {
+        self.spawn(MapBundle { map, ..default() })
+    }
+
+    #[cfg(feature = "planet")]
+    fn spawn_planet<'a>(&'a mut self, planet: Planet) -> EntityCommands<'w, 's, 'a> {
+        let mut entity = self.spawn(PlanetBundle {
+            planet,
+            ..default()
+        });
+        entity.add(insert_material);
+        entity
+    }
+}
+
+/// Inserts a new material, the default handle is shared between every entity without one.
+/// Without the render assets, e.g. on a headless server, the entity keeps the default handle
+#[cfg(any(feature = "terrain", feature = "planet"))]
+fn insert_material(mut entity: EntityWorldMut) {
+    let material = entity.world_scope(|world| {
+        world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .map(|mut materials| materials.add(StandardMaterial::default()))
+    });
+    if let Some(material) = material {
+        entity.insert(material);
+    }
+}
@@ -0,0 +1,226 @@
+//! Draw gizmos over generated terrain for debugging
+//! # Example
+//! For configuration, see [`GenerativeDebug`](struct.GenerativeDebug.html).
+//! Every category can be toggled at runtime through the resource
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::debug::{GenerativeDebug, GenerativeDebugPlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, GenerativeDebugPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, toggle)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(TerrainBundle::default());
+//! }
+//!
+//! fn toggle(keys: Res<Input<KeyCode>>, mut debug: ResMut<GenerativeDebug>) {
+//!     if keys.just_pressed(KeyCode::B) {
+//!         debug.chunk_bounds = !debug.chunk_bounds;
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+
+use crate::{
+    flow::FlowData, poi::PointOfInterest, road::RoadNetwork, scatter::Scattered,
+    terrain::GeneratedTerrain,
+};
+
+/// Resource toggling the gizmo categories of the `GenerativeDebugPlugin`
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct GenerativeDebug {
+    /// Bounding box of every terrain chunk, from its lowest to its highest vertex
+    pub chunk_bounds: bool,
+    /// Vertex grid on the floor of every terrain, colored by its resolution
+    pub lod: bool,
+    /// Position of every scattered object
+    pub scatter: bool,
+    /// Flow lines of vertices draining at least `river_accumulation` vertices, requires `FlowData`
+    pub rivers: bool,
+    /// Road splines and nodes of `RoadNetwork`
+    pub roads: bool,
+    /// Markers of the points of interest
+    pub poi: bool,
+    /// Number of vertices that must drain through a vertex for it to be drawn as a river
+    pub river_accumulation: f32,
+}
+
+impl Default for GenerativeDebug {
+    fn default() -> Self {
+        Self {
+            chunk_bounds: true,
+            lod: false,
+            scatter: true,
+            rivers: true,
+            roads: true,
+            poi: true,
+            river_accumulation: 30.0,
+        }
+    }
+}
+
+/// Plugin to draw debug gizmos of generated data
+pub struct GenerativeDebugPlugin;
+
+impl Plugin for GenerativeDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GenerativeDebug>().add_systems(
+            Update,
+            (
+                draw_chunk_bounds.run_if(|debug: Res<GenerativeDebug>| debug.chunk_bounds),
+                draw_lod.run_if(|debug: Res<GenerativeDebug>| debug.lod),
+                draw_scatter.run_if(|debug: Res<GenerativeDebug>| debug.scatter),
+                draw_rivers.run_if(|debug: Res<GenerativeDebug>| debug.rivers),
+                draw_roads.run_if(|debug: Res<GenerativeDebug>| debug.roads),
+                draw_poi.run_if(|debug: Res<GenerativeDebug>| debug.poi),
+            ),
+        );
+    }
+}
+
+/// Lowest and highest corner of `terrain_data` relative to the terrain entity
+fn bounds(terrain_data: &GeneratedTerrain) -> (Vec3, Vec3) {
+    let half = terrain_data.world_size / 2.0;
+    let min = terrain_data.center - half;
+    let max = terrain_data.center + half;
+    (
+        Vec3::new(min.x, terrain_data.stats.min_height, min.y),
+        Vec3::new(max.x, terrain_data.stats.max_height, max.y),
+    )
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_chunk_bounds(mut gizmos: Gizmos, query: Query<(&GeneratedTerrain, &GlobalTransform)>) {
+    for (terrain_data, transform) in &query {
+        let (min, max) = bounds(terrain_data);
+        let size = (max - min).max(Vec3::splat(f32::EPSILON));
+        let local = Transform::from_translation((min + max) / 2.0).with_scale(size);
+        gizmos.cuboid(transform.mul_transform(local), Color::rgb(1.0, 0.9, 0.2));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_lod(mut gizmos: Gizmos, query: Query<(&GeneratedTerrain, &GlobalTransform)>) {
+    // At most this many lines along every axis, denser grids are thinned out
+    const MAX_LINES: usize = 64;
+    for (terrain_data, transform) in &query {
+        let columns = terrain_data.heights.len();
+        let rows = terrain_data.heights.first().map_or(0, Vec::len);
+        if columns < 2 || rows < 2 {
+            continue;
+        }
+        // Every doubling of the resolution shifts the hue
+        let hue = (terrain_data.resolution.max(1) as f32).log2() * 60.0;
+        let color = Color::hsl(hue.rem_euclid(360.0), 0.9, 0.55);
+        let (min, _) = bounds(terrain_data);
+        let corner = |i: usize, j: usize| {
+            terrain_data
+                .vertex_position(i, j)
+                .map(|position| transform.transform_point(Vec3::new(position.x, min.y, position.z)))
+        };
+        let (last_column, last_row) = (columns - 1, rows - 1);
+        for i in (0..columns)
+            .step_by(columns.div_ceil(MAX_LINES))
+            .chain([last_column])
+        {
+            if let (Some(start), Some(end)) = (corner(i, 0), corner(i, last_row)) {
+                gizmos.line(start, end, color);
+            }
+        }
+        for j in (0..rows)
+            .step_by(rows.div_ceil(MAX_LINES))
+            .chain([last_row])
+        {
+            if let (Some(start), Some(end)) = (corner(0, j), corner(last_column, j)) {
+                gizmos.line(start, end, color);
+            }
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_scatter(mut gizmos: Gizmos, query: Query<&GlobalTransform, With<Scattered>>) {
+    for transform in &query {
+        gizmos.circle(
+            transform.translation(),
+            Vec3::Y,
+            0.01,
+            Color::rgb(0.3, 1.0, 0.3),
+        );
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_rivers(
+    mut gizmos: Gizmos,
+    debug: Res<GenerativeDebug>,
+    query: Query<(&GeneratedTerrain, &FlowData, &GlobalTransform)>,
+) {
+    let color = Color::rgb(0.2, 0.5, 1.0);
+    for (terrain_data, flow, transform) in &query {
+        for (i, column) in flow.accumulation.iter().enumerate() {
+            for (j, &accumulation) in column.iter().enumerate() {
+                if accumulation < debug.river_accumulation {
+                    continue;
+                }
+                let Some((next_i, next_j)) = flow.downstream(i, j) else {
+                    continue;
+                };
+                if let (Some(start), Some(end)) = (
+                    terrain_data.vertex_position(i, j),
+                    terrain_data.vertex_position(next_i, next_j),
+                ) {
+                    gizmos.line(
+                        transform.transform_point(start),
+                        transform.transform_point(end),
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_roads(mut gizmos: Gizmos, query: Query<(&RoadNetwork, &GlobalTransform)>) {
+    for (network, transform) in &query {
+        for road in &network.roads {
+            gizmos.linestrip(
+                road.points
+                    .iter()
+                    .map(|&point| transform.transform_point(point)),
+                Color::rgb(1.0, 0.5, 0.1),
+            );
+        }
+        for &node in &network.nodes {
+            gizmos.circle(
+                transform.transform_point(node),
+                Vec3::Y,
+                network.width.max(0.01),
+                Color::rgb(1.0, 0.2, 0.1),
+            );
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_poi(mut gizmos: Gizmos, query: Query<&GlobalTransform, With<PointOfInterest>>) {
+    let color = Color::rgb(1.0, 0.2, 0.8);
+    for transform in &query {
+        let position = transform.translation();
+        gizmos.line(position, position + Vec3::Y * 0.1, color);
+        gizmos.circle(position, Vec3::Y, 0.03, color);
+    }
+}
@@ -0,0 +1,524 @@
+//! Deform terrain at runtime, e.g. craters from explosions or digging
+//! # Example
+//! For configuration, see [`TerrainDeform`](struct.TerrainDeform.html).
+//! Deformations are kept in a [`TerrainDeformation`](struct.TerrainDeformation.html) on the terrain entity,
+//! which can be saved and restored. Props placed by `Scatter` are placed again and entities with
+//! `SnapToTerrain` follow the new surface. Areas are levelled for buildings with [`TerrainFlatten`](struct.TerrainFlatten.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::deform::{DeformPlugin, DeformProfile, TerrainDeform, TerrainDeformed};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, DeformPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, (explode, print_deformed))
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(TerrainBundle::default());
+//! }
+//!
+//! fn explode(mut events: EventWriter<TerrainDeform>, keys: Res<Input<KeyCode>>) {
+//!     if keys.just_pressed(KeyCode::Space) {
+//!         events.send(TerrainDeform {
+//!             position: Vec3::new(0.2, 0.0, -0.3),
+//!             radius: 0.2,
+//!             depth: 0.1,
+//!             profile: DeformProfile::Crater { rim: 0.3 },
+//!         });
+//!     }
+//! }
+//!
+//! fn print_deformed(mut events: EventReader<TerrainDeformed>) {
+//!     for event in events.read() {
+//!         println!("{:?} changed between {} and {}", event.terrain, event.min, event.max);
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::GeneratedTerrain;
+
+/// Shape of a deformation
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeformProfile {
+    /// Cosine falloff from the full depth at the center to nothing at the radius
+    Smooth,
+    /// Full depth up to the radius, e.g. digging a shaft
+    Flat,
+    /// Bowl with a rim of ejected material just outside of the radius
+    Crater {
+        /// Height of the rim as a fraction of the depth
+        rim: f32,
+    },
+}
+
+impl DeformProfile {
+    /// Height offset at `distance` radii from the center for a deformation of `depth`
+    #[must_use]
+    pub fn offset(self, distance: f32, depth: f32) -> f32 {
+        match self {
+            Self::Smooth if distance < 1.0 => {
+                -depth * (1.0 + (distance * std::f32::consts::PI).cos()) / 2.0
+            }
+            Self::Flat if distance < 1.0 => -depth,
+            Self::Crater { rim } => {
+                let bowl = if distance < 1.0 {
+                    -depth * distance.mul_add(-distance, 1.0)
+                } else {
+                    0.0
+                };
+                let ridge = (distance - 1.0) / 0.25;
+                (depth * rim).mul_add((-ridge * ridge).exp(), bowl)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Distance in radii beyond which the profile has no effect
+    const fn reach(self) -> f32 {
+        match self {
+            Self::Smooth | Self::Flat => 1.0,
+            Self::Crater { .. } => 2.0,
+        }
+    }
+}
+
+/// Event to carve or raise every terrain at a position
+#[derive(Event, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerrainDeform {
+    /// Center of the deformation in world space, only `x` and `z` are used
+    pub position: Vec3,
+    /// Radius of the deformation in world units
+    pub radius: f32,
+    /// Depth at the center in world units, negative values raise the terrain
+    pub depth: f32,
+    /// Shape of the deformation
+    pub profile: DeformProfile,
+}
+
+/// Area levelled by a `TerrainFlatten`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FlattenArea {
+    /// Rectangle between two corners as `x`, `z`
+    Rect {
+        /// Lower corner
+        min: Vec2,
+        /// Upper corner
+        max: Vec2,
+    },
+    /// Polygon through points as `x`, `z`, in either winding
+    Polygon(Vec<Vec2>),
+}
+
+impl FlattenArea {
+    /// Corners of the area in order
+    fn points(&self) -> Vec<Vec2> {
+        match self {
+            Self::Rect { min, max } => {
+                vec![*min, Vec2::new(max.x, min.y), *max, Vec2::new(min.x, max.y)]
+            }
+            Self::Polygon(points) => points.clone(),
+        }
+    }
+
+    /// Distance from `point` to the area, 0 inside
+    #[must_use]
+    pub fn distance(&self, point: Vec2) -> f32 {
+        let points = self.points();
+        if polygon_contains(&points, point) {
+            return 0.0;
+        }
+        let mut distance = f32::INFINITY;
+        for (k, &a) in points.iter().enumerate() {
+            let b = points[(k + 1) % points.len()];
+            let edge = b - a;
+            let t =
+                ((point - a).dot(edge) / edge.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+            distance = distance.min(point.distance(a + edge * t));
+        }
+        distance
+    }
+}
+
+/// Returns true if `point` is inside `polygon` by the even-odd rule
+pub(crate) fn polygon_contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (k, &a) in polygon.iter().enumerate() {
+        let b = polygon[(k + 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < ((point.y - a.y) / (b.y - a.y)).mul_add(b.x - a.x, a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Event to level every terrain within an area, e.g. for buildings or runways
+#[derive(Event, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerrainFlatten {
+    /// Levelled area in world space as `x`, `z`
+    pub area: FlattenArea,
+    /// Height of the levelled area in world space
+    pub height: f32,
+    /// Distance around the area in world units over which the level blends into the terrain
+    pub blend_radius: f32,
+}
+
+/// Event sent after a terrain was deformed, e.g. to update colliders or props in the area
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub struct TerrainDeformed {
+    /// Deformed terrain entity
+    pub terrain: Entity,
+    /// Lower corner of the changed area as `x`, `z` relative to the terrain entity
+    pub min: Vec2,
+    /// Upper corner of the changed area as `x`, `z` relative to the terrain entity
+    pub max: Vec2,
+}
+
+/// Accumulated deformations, added to the terrain entity by the first `TerrainDeform`.
+/// Ignored once the terrain changes size or resolution
+#[derive(Component, Clone, Default, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct TerrainDeformation {
+    /// Height offset of every vertex in world units, indexed like `GeneratedTerrain::heights`
+    pub offsets: Vec<Vec<f32>>,
+}
+
+impl TerrainDeformation {
+    /// Adds the offsets to `heights` if they have the same layout
+    pub(crate) fn apply(&self, heights: &mut [Vec<f32>]) {
+        if !self.fits(heights) {
+            return;
+        }
+        for (column, offsets) in heights.iter_mut().zip(&self.offsets) {
+            for (height, offset) in column.iter_mut().zip(offsets) {
+                *height += offset;
+            }
+        }
+    }
+
+    fn fits(&self, heights: &[Vec<f32>]) -> bool {
+        self.offsets.len() == heights.len()
+            && self
+                .offsets
+                .iter()
+                .zip(heights)
+                .all(|(offsets, column)| offsets.len() == column.len())
+    }
+
+    /// Levels `area` to `height`, both relative to the terrain, blending into the terrain within `blend_radius`.
+    /// Returns the changed area, `None` if the area misses the terrain
+    pub fn flatten_region(
+        &mut self,
+        terrain_data: &GeneratedTerrain,
+        area: &FlattenArea,
+        height: f32,
+        blend_radius: f32,
+    ) -> Option<[Vec2; 2]> {
+        if !self.fits(&terrain_data.heights) {
+            self.offsets = terrain_data
+                .heights
+                .iter()
+                .map(|column| vec![0.0; column.len()])
+                .collect();
+        }
+        let blend_radius = blend_radius.max(0.0);
+        let (mut min, mut max) = (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY));
+        for (i, column) in self.offsets.iter_mut().enumerate() {
+            for (j, offset) in column.iter_mut().enumerate() {
+                let Some(vertex) = terrain_data.vertex_position(i, j) else {
+                    continue;
+                };
+                let distance = area.distance(vertex.xz());
+                if distance > blend_radius {
+                    continue;
+                }
+                // Cosine falloff from the level at the border of the area to the terrain at the blend radius
+                let weight = if distance <= 0.0 {
+                    1.0
+                } else {
+                    (distance / blend_radius * std::f32::consts::PI)
+                        .cos()
+                        .mul_add(0.5, 0.5)
+                };
+                *offset += (height - vertex.y) * weight;
+                min = min.min(vertex.xz());
+                max = max.max(vertex.xz());
+            }
+        }
+        (min.x <= max.x).then_some([min, max])
+    }
+
+    /// Adds a deformation centered at `center` relative to the terrain entity, returns the changed area.
+    /// `None` if the deformation misses the terrain
+    fn deform(
+        &mut self,
+        terrain_data: &GeneratedTerrain,
+        center: Vec2,
+        deform: &TerrainDeform,
+    ) -> Option<[Vec2; 2]> {
+        if !self.fits(&terrain_data.heights) {
+            self.offsets = terrain_data
+                .heights
+                .iter()
+                .map(|column| vec![0.0; column.len()])
+                .collect();
+        }
+        let radius = deform.radius.max(f32::EPSILON);
+        let reach = radius * deform.profile.reach();
+        let resolution = terrain_data.resolution.max(1) as f32;
+        let half = Vec2::new(terrain_data.size[0] as f32, terrain_data.size[1] as f32) / 2.0;
+        let columns = self.offsets.len();
+        let rows = self.offsets.first().map_or(0, Vec::len);
+        let overlay = terrain_data.to_overlay(center);
+        let reach = reach / terrain_data.stretch();
+        let min = ((overlay - reach + half) * resolution)
+            .floor()
+            .max(Vec2::ZERO);
+        let max = ((overlay + reach + half) * resolution).ceil();
+        if columns == 0
+            || rows == 0
+            || max.x < 0.0
+            || max.y < 0.0
+            || min.x > (columns - 1) as f32
+            || min.y > (rows - 1) as f32
+        {
+            return None;
+        }
+        let [min_i, min_j] = [min.x as usize, min.y as usize];
+        let [max_i, max_j] = [
+            (max.x as usize).min(columns - 1),
+            (max.y as usize).min(rows - 1),
+        ];
+        for (i, column) in self
+            .offsets
+            .iter_mut()
+            .enumerate()
+            .take(max_i + 1)
+            .skip(min_i)
+        {
+            for (j, offset) in column.iter_mut().enumerate().take(max_j + 1).skip(min_j) {
+                let point =
+                    terrain_data.from_overlay(Vec2::new(i as f32, j as f32) / resolution - half);
+                let distance = point.distance(center) / radius;
+                *offset += deform.profile.offset(distance, deform.depth);
+            }
+        }
+        Some([
+            terrain_data.from_overlay(Vec2::new(min_i as f32, min_j as f32) / resolution - half),
+            terrain_data.from_overlay(Vec2::new(max_i as f32, max_j as f32) / resolution - half),
+        ])
+    }
+}
+
+/// Plugin to deform terrain at runtime
+pub struct DeformPlugin;
+
+impl Plugin for DeformPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TerrainDeform>()
+            .add_event::<TerrainFlatten>()
+            .add_event::<TerrainDeformed>()
+            .add_systems(Update, deform_terrain);
+    }
+}
+
+fn deform_terrain(
+    mut commands: Commands,
+    mut events: EventReader<TerrainDeform>,
+    mut flattens: EventReader<TerrainFlatten>,
+    mut deformed: EventWriter<TerrainDeformed>,
+    mut query: Query<(
+        Entity,
+        &GeneratedTerrain,
+        &GlobalTransform,
+        Option<&mut TerrainDeformation>,
+    )>,
+) {
+    let deforms: Vec<TerrainDeform> = events.read().copied().collect();
+    let flattens: Vec<&TerrainFlatten> = flattens.read().collect();
+    if deforms.is_empty() && flattens.is_empty() {
+        return;
+    }
+    for (entity, terrain_data, transform, deformation) in &mut query {
+        let to_local = transform.affine().inverse();
+        let mut new = deformation.as_deref().cloned().unwrap_or_default();
+        let mut changed = false;
+        for deform in &deforms {
+            let center = to_local.transform_point3(deform.position).xz();
+            if let Some([min, max]) = new.deform(terrain_data, center, deform) {
+                changed = true;
+                deformed.send(TerrainDeformed {
+                    terrain: entity,
+                    min,
+                    max,
+                });
+            }
+        }
+        for flatten in &flattens {
+            let local = |point: Vec2| {
+                to_local.transform_point3(Vec3::new(point.x, flatten.height, point.y))
+            };
+            // Rotated terrains turn rectangles into polygons
+            let area = FlattenArea::Polygon(
+                flatten
+                    .area
+                    .points()
+                    .into_iter()
+                    .map(|point| local(point).xz())
+                    .collect(),
+            );
+            let height = flatten
+                .area
+                .points()
+                .first()
+                .map_or(flatten.height, |&point| local(point).y);
+            // Blend radius in world units, scaled like the terrain
+            let blend_radius = flatten.blend_radius * to_local.transform_vector3(Vec3::X).length();
+            if let Some([min, max]) = new.flatten_region(terrain_data, &area, height, blend_radius)
+            {
+                changed = true;
+                deformed.send(TerrainDeformed {
+                    terrain: entity,
+                    min,
+                    max,
+                });
+            }
+        }
+        if !changed {
+            continue;
+        }
+        match deformation {
+            Some(mut deformation) => *deformation = new,
+            None => {
+                commands.entity(entity).insert(new);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bumpy terrain stretched to 6 by 4 world units around 1, 0
+    fn bumpy() -> GeneratedTerrain {
+        let mut terrain_data = GeneratedTerrain {
+            resolution: 4,
+            size: [3, 2],
+            world_size: Vec2::new(6.0, 4.0),
+            center: Vec2::new(1.0, 0.0),
+            heights: vec![vec![0.0; 9]; 13],
+            ..default()
+        };
+        for (i, column) in terrain_data.heights.iter_mut().enumerate() {
+            for (j, height) in column.iter_mut().enumerate() {
+                *height = (i as f32 * 0.7).sin() + (j as f32 * 1.3).cos();
+            }
+        }
+        terrain_data
+    }
+
+    /// Heights of `terrain_data` after `deformation`, with the position of every vertex
+    fn deformed(
+        terrain_data: &GeneratedTerrain,
+        deformation: &TerrainDeformation,
+    ) -> Vec<(Vec3, f32)> {
+        let mut heights = terrain_data.heights.clone();
+        deformation.apply(&mut heights);
+        heights
+            .iter()
+            .enumerate()
+            .flat_map(|(i, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .map(move |(j, &height)| (terrain_data.vertex_position(i, j).unwrap(), height))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flattened_areas_are_level() {
+        let terrain_data = bumpy();
+        for area in [
+            FlattenArea::Rect {
+                min: Vec2::new(-1.0, -1.0),
+                max: Vec2::new(1.5, 0.5),
+            },
+            FlattenArea::Polygon(vec![
+                Vec2::new(2.0, -1.5),
+                Vec2::new(0.0, 1.5),
+                Vec2::new(3.5, 1.0),
+            ]),
+        ] {
+            let mut deformation = TerrainDeformation::default();
+            let [min, max] = deformation
+                .flatten_region(&terrain_data, &area, 0.3, 1.0)
+                .unwrap();
+            let mut level = 0;
+            for (vertex, height) in deformed(&terrain_data, &deformation) {
+                let distance = area.distance(vertex.xz());
+                if distance <= 0.0 {
+                    level += 1;
+                    assert!((height - 0.3).abs() < 1e-5, "{vertex} is at {height}");
+                } else if distance > 1.0 {
+                    assert!(
+                        (height - vertex.y).abs() <= f32::EPSILON,
+                        "{vertex} outside the blend radius"
+                    );
+                } else {
+                    // Between the level and the terrain within the blend radius
+                    assert!(
+                        height >= vertex.y.min(0.3) - 1e-5 && height <= vertex.y.max(0.3) + 1e-5,
+                        "{vertex} blends to {height}"
+                    );
+                }
+                if distance <= 1.0 {
+                    assert!(vertex.x >= min.x && vertex.x <= max.x);
+                    assert!(vertex.z >= min.y && vertex.z <= max.y);
+                }
+            }
+            assert!(level > 4, "only {level} vertices in {area:?}");
+        }
+    }
+
+    #[test]
+    fn areas_beside_the_terrain_are_ignored() {
+        let terrain_data = bumpy();
+        let mut deformation = TerrainDeformation::default();
+        let area = FlattenArea::Rect {
+            min: Vec2::new(5.0, -1.0),
+            max: Vec2::new(6.0, 1.0),
+        };
+        assert_eq!(
+            deformation.flatten_region(&terrain_data, &area, 0.3, 0.5),
+            None
+        );
+        assert!(deformation
+            .offsets
+            .iter()
+            .flatten()
+            .all(|&offset| offset == 0.0));
+    }
+}
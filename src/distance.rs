@@ -0,0 +1,291 @@
+//! Compute signed distance fields from boolean masks
+//! # Example
+//! For configuration, see [`SeaDistance`](struct.SeaDistance.html).
+//! Masks can also be converted directly with [`DistanceField::from_mask`](struct.DistanceField.html#method.from_mask)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::distance::{DistancePlugin, SeaDistance, SeaDistanceField};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, DistancePlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_center)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), SeaDistance::default()));
+//! }
+//!
+//! fn print_center(query: Query<&SeaDistanceField, Changed<SeaDistanceField>>) {
+//!     for distance in &query {
+//!         println!("{:?}", distance.distance_to_sea(0.0, 0.0));
+//!     }
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::GeneratedTerrain;
+
+/// Signed distance to the border of a mask, sampled on a grid
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct DistanceField {
+    /// Distance of every cell in world units, negative inside of the mask. Indexed by `[x][y]`
+    pub values: Vec<Vec<f32>>,
+    /// Distance between neighbouring cells in world units
+    pub cell_size: f32,
+}
+
+impl DistanceField {
+    /// Exact euclidean distance from every cell to the nearest cell on the other side of the mask,
+    /// positive outside of the mask and negative inside. `mask` is indexed by `[x][y]`.
+    ///
+    /// Without any cell on the other side the distance is `f32::INFINITY`
+    #[must_use]
+    pub fn from_mask(mask: &[Vec<bool>], cell_size: f32) -> Self {
+        let outside = squared_distances(mask, true);
+        let inside = squared_distances(mask, false);
+        let values = mask
+            .iter()
+            .enumerate()
+            .map(|(x, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .map(|(y, &masked)| {
+                        if masked {
+                            -inside[x][y].sqrt() * cell_size
+                        } else {
+                            outside[x][y].sqrt() * cell_size
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { values, cell_size }
+    }
+
+    /// Distance of the cell at `x`, `y`, `None` outside of the grid
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize) -> Option<f32> {
+        self.values.get(x)?.get(y).copied()
+    }
+
+    /// Distance at `x`, `y` in cells, bilinearly interpolated. `None` outside of the grid
+    #[must_use]
+    pub fn sample(&self, x: f32, y: f32) -> Option<f32> {
+        let columns = self.values.len().checked_sub(1)?;
+        let rows = self.values.first()?.len().checked_sub(1)?;
+        if !(0.0..=columns as f32).contains(&x) || !(0.0..=rows as f32).contains(&y) {
+            return None;
+        }
+        let i = (x as usize).min(columns.saturating_sub(1));
+        let j = (y as usize).min(rows.saturating_sub(1));
+        let (tx, ty) = (x - i as f32, y - j as f32);
+        let value = |di: usize, dj: usize| {
+            self.get((i + di).min(columns), (j + dj).min(rows))
+                .unwrap_or(0.0)
+        };
+        let near = (value(1, 0) - value(0, 0)).mul_add(tx, value(0, 0));
+        let far = (value(1, 1) - value(0, 1)).mul_add(tx, value(0, 1));
+        Some((far - near).mul_add(ty, near))
+    }
+
+    /// Single channel texture of the field, one pixel per cell.
+    /// The border of the mask maps to 0.5, `max_distance` inside to 0 and `max_distance` outside to 1
+    #[must_use]
+    pub fn to_image(&self, max_distance: f32) -> Image {
+        let width = self.values.len() as u32;
+        let height = self.values.first().map_or(0, Vec::len) as u32;
+        let mut data = Vec::with_capacity((width * height) as usize);
+        for y in 0..height as usize {
+            for column in &self.values {
+                let value = (column[y] / max_distance.max(f32::EPSILON)).mul_add(0.5, 0.5);
+                data.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::R8Unorm,
+        )
+    }
+}
+
+/// Squared distance in cells from every cell to the nearest cell where the mask equals `target`,
+/// with the separable transform of Felzenszwalb and Huttenlocher
+fn squared_distances(mask: &[Vec<bool>], target: bool) -> Vec<Vec<f32>> {
+    let mut columns: Vec<Vec<f32>> = mask
+        .iter()
+        .map(|column| {
+            let initial: Vec<f32> = column
+                .iter()
+                .map(|&masked| if masked == target { 0.0 } else { f32::INFINITY })
+                .collect();
+            transform(&initial)
+        })
+        .collect();
+    let rows = columns.first().map_or(0, Vec::len);
+    for y in 0..rows {
+        let row: Vec<f32> = columns.iter().map(|column| column[y]).collect();
+        for (column, value) in columns.iter_mut().zip(transform(&row)) {
+            column[y] = value;
+        }
+    }
+    columns
+}
+
+/// One dimensional squared distance transform, the lower envelope of parabolas rooted at every sample
+fn transform(values: &[f32]) -> Vec<f32> {
+    let count = values.len();
+    let mut result = vec![f32::INFINITY; count];
+    // Roots of the parabolas on the envelope and where each starts to be the lowest
+    let mut roots: Vec<usize> = Vec::with_capacity(count);
+    let mut starts: Vec<f32> = Vec::with_capacity(count);
+    let intersection = |a: usize, b: usize| {
+        ((values[b] + (b * b) as f32) - (values[a] + (a * a) as f32)) / (2.0 * (b - a) as f32)
+    };
+    for (index, value) in values.iter().enumerate() {
+        if value.is_infinite() {
+            continue;
+        }
+        while let Some(&last) = roots.last() {
+            if intersection(last, index) > *starts.last().unwrap_or(&f32::NEG_INFINITY) {
+                break;
+            }
+            roots.pop();
+            starts.pop();
+        }
+        starts.push(
+            roots
+                .last()
+                .map_or(f32::NEG_INFINITY, |&last| intersection(last, index)),
+        );
+        roots.push(index);
+    }
+    let mut parabola = 0;
+    for (index, distance) in result.iter_mut().enumerate() {
+        while parabola + 1 < roots.len() && starts[parabola + 1] <= index as f32 {
+            parabola += 1;
+        }
+        if let Some(&root) = roots.get(parabola) {
+            let offset = index as f32 - root as f32;
+            *distance = offset.mul_add(offset, values[root]);
+        }
+    }
+    result
+}
+
+/// Component for sea distance configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct SeaDistance {
+    /// If true, the field is also written to `SeaDistanceField::texture`
+    pub texture: bool,
+    /// Distance in world units mapped to the ends of the texture range
+    pub max_distance: f32,
+}
+
+impl Default for SeaDistance {
+    fn default() -> Self {
+        Self {
+            texture: false,
+            max_distance: 0.5,
+        }
+    }
+}
+
+/// Signed distance to the sea of every terrain vertex, inserted on the entity with the `SeaDistance` component.
+/// Positive on land and negative in the sea
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct SeaDistanceField {
+    /// Distance of every vertex, indexed like `GeneratedTerrain::heights`
+    pub field: DistanceField,
+    /// Texture of the field if `SeaDistance::texture` is set, see [`DistanceField::to_image`]
+    pub texture: Option<Handle<Image>>,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl SeaDistanceField {
+    /// Distance from `x`, `z` to the nearest sea, negative in the sea. `None` outside of the terrain
+    #[must_use]
+    pub fn distance_to_sea(&self, x: f32, z: f32) -> Option<f32> {
+        let cells = 1.0 / self.field.cell_size.max(f32::EPSILON);
+        self.field.sample(
+            (x + self.size[0] as f32 / 2.0) * cells,
+            (z + self.size[1] as f32 / 2.0) * cells,
+        )
+    }
+}
+
+/// Plugin to compute distance fields
+pub struct DistancePlugin;
+
+impl Plugin for DistancePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_sea_distance);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_sea_distance(
+    mut commands: Commands,
+    mut images: Option<ResMut<Assets<Image>>>,
+    query: Query<
+        (Entity, &SeaDistance, &GeneratedTerrain),
+        Or<(Changed<SeaDistance>, Changed<GeneratedTerrain>)>,
+    >,
+) {
+    for (entity, config, terrain_data) in &query {
+        let field = DistanceField::from_mask(
+            &sea_mask(terrain_data),
+            1.0 / terrain_data.resolution.max(1) as f32,
+        );
+        // Without the render assets only the field is computed
+        let texture = images
+            .as_deref_mut()
+            .filter(|_| config.texture)
+            .map(|images| images.add(field.to_image(config.max_distance)));
+        commands.entity(entity).insert(SeaDistanceField {
+            field,
+            texture,
+            size: terrain_data.size,
+        });
+    }
+}
+
+/// Whether every vertex of `terrain_data` is in the sea, indexed like `GeneratedTerrain::heights`
+pub(crate) fn sea_mask(terrain_data: &GeneratedTerrain) -> Vec<Vec<bool>> {
+    terrain_data
+        .base_heights
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|&height| height <= terrain_data.sea_level + f32::EPSILON)
+                .collect()
+        })
+        .collect()
+}
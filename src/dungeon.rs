@@ -0,0 +1,610 @@
+//! Generate dungeons
+//! # Example
+//! For configuration, see [`Dungeon`](struct.Dungeon.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::dungeon::{DungeonBundle, DungeonPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(DungeonPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(DungeonBundle::default());
+//! }
+//! ```
+use bevy::{prelude::*, render::render_resource::PrimitiveTopology};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    headless::DataOnly,
+    util::{export_model, Rng},
+};
+
+/// Method used to place rooms
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DungeonMethod {
+    /// Recursively split the dungeon into areas and place one room in each area
+    Bsp,
+    /// Place rooms at random positions, rejecting rooms that overlap
+    RandomRooms,
+}
+
+/// Component for dungeon configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Dungeon {
+    /// Seed used to place rooms and corridors
+    pub seed: u32,
+    /// Size of the dungeon in tiles
+    pub size: [u32; 2],
+    /// Method used to place rooms
+    pub method: DungeonMethod,
+    /// Minimum and maximum side length of a room in tiles
+    pub room_size: [u32; 2],
+    /// Number of rooms placed by `DungeonMethod::RandomRooms`
+    pub room_count: usize,
+    /// Number of times the dungeon is split by `DungeonMethod::Bsp`
+    pub depth: u32,
+    /// Width of corridors in tiles
+    pub corridor_width: u32,
+    /// If true, extrudes floor and wall mesh written to `PbrBundle`
+    pub mesh: bool,
+    /// Size of a tile in world units
+    pub tile_size: f32,
+    /// Height of the walls in world units
+    pub wall_height: f32,
+    /// Color of floor tiles
+    pub floor_color: [u8; 4],
+    /// Color of wall tiles
+    pub wall_color: [u8; 4],
+    /// If true, renders dungeon mesh as wireframe
+    pub wireframe: bool,
+    /// If true, exports model in glb format
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for Dungeon {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: [48, 32],
+            method: DungeonMethod::Bsp,
+            room_size: [4, 10],
+            room_count: 12,
+            depth: 4,
+            corridor_width: 1,
+            mesh: true,
+            tile_size: 0.1,
+            wall_height: 0.2,
+            floor_color: [150, 140, 120, 255],
+            wall_color: [80, 75, 70, 255],
+            wireframe: false,
+            export: false,
+        }
+    }
+}
+
+/// Tile of a generated dungeon
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Tile {
+    /// Solid rock, not adjacent to any open tile
+    Empty,
+    /// Floor of a room
+    Floor,
+    /// Floor of a corridor
+    Corridor,
+    /// Wall surrounding rooms and corridors
+    Wall,
+}
+
+impl Tile {
+    /// Returns true for tiles that can be walked on
+    #[must_use]
+    pub const fn is_walkable(self) -> bool {
+        matches!(self, Self::Floor | Self::Corridor)
+    }
+}
+
+/// Rectangular room, measured in tiles
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Room {
+    /// Position of the corner with the lowest coordinates
+    pub position: [u32; 2],
+    /// Width and depth of the room
+    pub size: [u32; 2],
+}
+
+impl Room {
+    /// Center tile of the room
+    #[must_use]
+    pub const fn center(&self) -> [u32; 2] {
+        [
+            self.position[0] + self.size[0] / 2,
+            self.position[1] + self.size[1] / 2,
+        ]
+    }
+
+    const fn overlaps(&self, other: &Self, spacing: u32) -> bool {
+        self.position[0] < other.position[0] + other.size[0] + spacing
+            && other.position[0] < self.position[0] + self.size[0] + spacing
+            && self.position[1] < other.position[1] + other.size[1] + spacing
+            && other.position[1] < self.position[1] + self.size[1] + spacing
+    }
+}
+
+/// Corridor connecting two rooms
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Corridor {
+    /// Indices of the connected rooms in `DungeonLayout::rooms`
+    pub rooms: [usize; 2],
+    /// Tiles where the corridor starts, turns and ends
+    pub path: Vec<[u32; 2]>,
+}
+
+/// Generated dungeon, inserted on the entity with the `Dungeon` component
+#[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DungeonLayout {
+    /// Tile grid, indexed by `[x][y]`
+    pub tiles: Vec<Vec<Tile>>,
+    /// Rooms of the dungeon
+    pub rooms: Vec<Room>,
+    /// Corridors connecting the rooms, every room is reachable from every other room
+    pub corridors: Vec<Corridor>,
+}
+
+impl DungeonLayout {
+    /// Tile at `x`, `y`, `None` if outside of the dungeon
+    #[must_use]
+    pub fn get(&self, x: u32, y: u32) -> Option<Tile> {
+        self.tiles
+            .get(x as usize)
+            .and_then(|column| column.get(y as usize))
+            .copied()
+    }
+}
+
+/// Render `Dungeon` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct DungeonBundle {
+    /// Dungeon configuration
+    pub dungeon: Dungeon,
+    /// Generated mesh data is written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate dungeons
+pub struct DungeonPlugin;
+
+impl Plugin for DungeonPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_dungeon);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_dungeon(
+    mut commands: Commands,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Dungeon,
+            Option<&mut Handle<Mesh>>,
+            Option<&Handle<StandardMaterial>>,
+            Has<DataOnly>,
+        ),
+        Changed<Dungeon>,
+    >,
+) {
+    for (entity, mut dungeon, mesh_handle, material, data_only) in &mut query {
+        let layout = generate_layout(&dungeon);
+
+        // Without the render assets only the layout is generated
+        if let (true, Some(meshes)) = (dungeon.mesh && !data_only, meshes.as_deref_mut()) {
+            if let Some(material) = materials
+                .as_deref_mut()
+                .zip(material)
+                .and_then(|(materials, material)| materials.get_mut(material))
+            {
+                *material = StandardMaterial {
+                    perceptual_roughness: 0.9,
+                    ..default()
+                };
+            }
+            let tile_mesh = TileMesh::extrude(
+                &layout.tiles,
+                dungeon.tile_size,
+                dungeon.wall_height,
+                dungeon.floor_color,
+                dungeon.wall_color,
+            );
+            let TileMesh {
+                positions,
+                normals,
+                uvs,
+                colors,
+                mut indices,
+            } = tile_mesh;
+
+            if dungeon.wireframe {
+                let triangle_number = indices.len() / 3;
+                let cloned_indices = indices.clone();
+                indices = vec![];
+                for i in 0..triangle_number {
+                    for j in &[0, 1, 1, 2, 2, 0] {
+                        indices.push(cloned_indices[i * 3 + j]);
+                    }
+                }
+            }
+
+            let mut mesh = if dungeon.wireframe {
+                Mesh::new(PrimitiveTopology::LineList)
+            } else {
+                Mesh::new(PrimitiveTopology::TriangleList)
+            };
+            mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+            if let Some(mut mesh_handle) = mesh_handle {
+                *mesh_handle = meshes.add(mesh);
+            }
+
+            if dungeon.export {
+                export_model(&positions, indices, &colors);
+                dungeon.bypass_change_detection().export = false;
+            }
+        }
+
+        commands.entity(entity).insert(layout);
+    }
+}
+
+fn generate_layout(dungeon: &Dungeon) -> DungeonLayout {
+    let [width, depth] = dungeon.size;
+    let mut rng = Rng::new(u64::from(dungeon.seed));
+    let min_room = dungeon.room_size[0].max(1);
+    let max_room = dungeon.room_size[1].max(min_room);
+
+    let rooms = match dungeon.method {
+        DungeonMethod::Bsp => {
+            let mut leaves = vec![];
+            split_area(
+                Room {
+                    position: [0, 0],
+                    size: dungeon.size,
+                },
+                dungeon.depth,
+                min_room,
+                &mut rng,
+                &mut leaves,
+            );
+            leaves
+                .into_iter()
+                // Rooms keep a tile of space on every side of their area for walls
+                .filter(|leaf| leaf.size[0] >= min_room + 2 && leaf.size[1] >= min_room + 2)
+                .map(|leaf| {
+                    let size = [
+                        rng.range(min_room, max_room.min(leaf.size[0] - 2)),
+                        rng.range(min_room, max_room.min(leaf.size[1] - 2)),
+                    ];
+                    Room {
+                        position: [
+                            leaf.position[0] + 1 + rng.range(0, leaf.size[0] - 2 - size[0]),
+                            leaf.position[1] + 1 + rng.range(0, leaf.size[1] - 2 - size[1]),
+                        ],
+                        size,
+                    }
+                })
+                .collect()
+        }
+        DungeonMethod::RandomRooms => {
+            let mut rooms: Vec<Room> = vec![];
+            for _ in 0..dungeon.room_count * 20 {
+                if rooms.len() >= dungeon.room_count {
+                    break;
+                }
+                let size = [rng.range(min_room, max_room), rng.range(min_room, max_room)];
+                if size[0] + 2 > width || size[1] + 2 > depth {
+                    continue;
+                }
+                let room = Room {
+                    position: [
+                        rng.range(1, width - size[0] - 1),
+                        rng.range(1, depth - size[1] - 1),
+                    ],
+                    size,
+                };
+                if rooms.iter().all(|other| !room.overlaps(other, 2)) {
+                    rooms.push(room);
+                }
+            }
+            rooms
+        }
+    };
+
+    let mut tiles = vec![vec![Tile::Empty; depth as usize]; width as usize];
+    for room in &rooms {
+        for x in room.position[0]..room.position[0] + room.size[0] {
+            for y in room.position[1]..room.position[1] + room.size[1] {
+                tiles[x as usize][y as usize] = Tile::Floor;
+            }
+        }
+    }
+
+    // Connect rooms with a minimum spanning tree over their centers
+    let mut corridors = vec![];
+    let mut connected = vec![false; rooms.len()];
+    if let Some(first) = connected.first_mut() {
+        *first = true;
+    }
+    for _ in 1..rooms.len() {
+        let mut closest: Option<(u32, usize, usize)> = None;
+        for (from, room) in rooms.iter().enumerate().filter(|(i, _)| connected[*i]) {
+            for (to, other) in rooms.iter().enumerate().filter(|(i, _)| !connected[*i]) {
+                let [ax, ay] = room.center();
+                let [bx, by] = other.center();
+                let distance = ax.abs_diff(bx) + ay.abs_diff(by);
+                if closest.is_none_or(|(closest, _, _)| distance < closest) {
+                    closest = Some((distance, from, to));
+                }
+            }
+        }
+        let Some((_, from, to)) = closest else {
+            break;
+        };
+        connected[to] = true;
+
+        let start = rooms[from].center();
+        let end = rooms[to].center();
+        let corner = if rng.next_u64() & 1 == 0 {
+            [end[0], start[1]]
+        } else {
+            [start[0], end[1]]
+        };
+        let path = vec![start, corner, end];
+        for segment in path.windows(2) {
+            carve_segment(&mut tiles, segment[0], segment[1], dungeon.corridor_width);
+        }
+        corridors.push(Corridor {
+            rooms: [from, to],
+            path,
+        });
+    }
+
+    surround_with_walls(&mut tiles, 1);
+
+    DungeonLayout {
+        tiles,
+        rooms,
+        corridors,
+    }
+}
+
+fn split_area(area: Room, depth: u32, min_room: u32, rng: &mut Rng, leaves: &mut Vec<Room>) {
+    // Each area needs space for the smallest room and its walls
+    let min_area = min_room + 2;
+    let [width, height] = area.size;
+    let split_x = width >= min_area * 2;
+    let split_y = height >= min_area * 2;
+    if depth == 0 || (!split_x && !split_y) {
+        leaves.push(area);
+        return;
+    }
+    // Prefer splitting across the longer side to avoid long, narrow areas
+    let vertical = match (split_x, split_y) {
+        (true, true) if width == height => rng.next_u64() & 1 == 0,
+        (true, true) => width > height,
+        _ => split_x,
+    };
+    let [x, y] = area.position;
+    let (first, second) = if vertical {
+        let split = rng.range(min_area, width - min_area);
+        (
+            Room {
+                position: [x, y],
+                size: [split, height],
+            },
+            Room {
+                position: [x + split, y],
+                size: [width - split, height],
+            },
+        )
+    } else {
+        let split = rng.range(min_area, height - min_area);
+        (
+            Room {
+                position: [x, y],
+                size: [width, split],
+            },
+            Room {
+                position: [x, y + split],
+                size: [width, height - split],
+            },
+        )
+    };
+    split_area(first, depth - 1, min_room, rng, leaves);
+    split_area(second, depth - 1, min_room, rng, leaves);
+}
+
+/// Carves an axis aligned corridor segment, leaving the outer border for walls
+pub(crate) fn carve_segment(tiles: &mut [Vec<Tile>], start: [u32; 2], end: [u32; 2], width: u32) {
+    let max_x = tiles.len().saturating_sub(2);
+    let max_y = tiles.first().map_or(0, Vec::len).saturating_sub(2);
+    let width = width.max(1);
+    let before = (width - 1) / 2;
+    let after = width - 1 - before;
+    let x_range = start[0].min(end[0]).saturating_sub(before)..=start[0].max(end[0]) + after;
+    let y_range = start[1].min(end[1]).saturating_sub(before)..=start[1].max(end[1]) + after;
+    for x in x_range
+        .map(|x| x as usize)
+        .filter(|x| (1..=max_x).contains(x))
+    {
+        for y in y_range
+            .clone()
+            .map(|y| y as usize)
+            .filter(|y| (1..=max_y).contains(y))
+        {
+            if tiles[x][y] == Tile::Empty {
+                tiles[x][y] = Tile::Corridor;
+            }
+        }
+    }
+}
+
+/// Turns empty tiles within `thickness` tiles of a walkable tile into walls
+pub(crate) fn surround_with_walls(tiles: &mut [Vec<Tile>], thickness: u32) {
+    let width = tiles.len();
+    let depth = tiles.first().map_or(0, Vec::len);
+    let reach = thickness as usize;
+    let open: Vec<Vec<bool>> = tiles
+        .iter()
+        .map(|column| column.iter().map(|tile| tile.is_walkable()).collect())
+        .collect();
+    for (x, column) in tiles.iter_mut().enumerate() {
+        for (y, tile) in column.iter_mut().enumerate() {
+            if *tile != Tile::Empty {
+                continue;
+            }
+            let near_open = (x.saturating_sub(reach)..=(x + reach).min(width - 1)).any(|nx| {
+                (y.saturating_sub(reach)..=(y + reach).min(depth - 1)).any(|ny| open[nx][ny])
+            });
+            if near_open {
+                *tile = Tile::Wall;
+            }
+        }
+    }
+}
+
+/// Mesh data extruded from a tile grid
+pub(crate) struct TileMesh {
+    pub(crate) positions: Vec<[f32; 3]>,
+    pub(crate) normals: Vec<[f32; 3]>,
+    pub(crate) uvs: Vec<[f32; 2]>,
+    pub(crate) colors: Vec<[f32; 4]>,
+    pub(crate) indices: Vec<u32>,
+}
+
+impl TileMesh {
+    /// Builds floor quads for walkable tiles and boxes for walls,
+    /// centered around the origin
+    pub(crate) fn extrude(
+        tiles: &[Vec<Tile>],
+        tile_size: f32,
+        wall_height: f32,
+        floor_color: [u8; 4],
+        wall_color: [u8; 4],
+    ) -> Self {
+        let mut tile_mesh = Self {
+            positions: vec![],
+            normals: vec![],
+            uvs: vec![],
+            colors: vec![],
+            indices: vec![],
+        };
+        let width = tiles.len();
+        let depth = tiles.first().map_or(0, Vec::len);
+        let floor_color = floor_color.map(|channel| f32::from(channel) / 255.0);
+        let wall_color = wall_color.map(|channel| f32::from(channel) / 255.0);
+        let is_wall = |x: isize, y: isize| {
+            x >= 0
+                && y >= 0
+                && tiles
+                    .get(x as usize)
+                    .and_then(|column| column.get(y as usize))
+                    == Some(&Tile::Wall)
+        };
+
+        for (x, column) in tiles.iter().enumerate() {
+            for (y, tile) in column.iter().enumerate() {
+                let corner = Vec3::new(
+                    (x as f32 - width as f32 / 2.0) * tile_size,
+                    0.0,
+                    (y as f32 - depth as f32 / 2.0) * tile_size,
+                );
+                let size = tile_size;
+                match tile {
+                    Tile::Floor | Tile::Corridor => {
+                        tile_mesh.push_quad(corner, Vec3::Z * size, Vec3::X * size, floor_color);
+                    }
+                    Tile::Wall => {
+                        let height = Vec3::Y * wall_height;
+                        tile_mesh.push_quad(
+                            corner + height,
+                            Vec3::Z * size,
+                            Vec3::X * size,
+                            wall_color,
+                        );
+                        let (x, y) = (x as isize, y as isize);
+                        // Only sides facing open tiles or the outside are visible
+                        if !is_wall(x + 1, y) {
+                            tile_mesh.push_quad(
+                                corner + Vec3::X * size,
+                                height,
+                                Vec3::Z * size,
+                                wall_color,
+                            );
+                        }
+                        if !is_wall(x - 1, y) {
+                            tile_mesh.push_quad(corner, Vec3::Z * size, height, wall_color);
+                        }
+                        if !is_wall(x, y + 1) {
+                            tile_mesh.push_quad(
+                                corner + Vec3::Z * size,
+                                Vec3::X * size,
+                                height,
+                                wall_color,
+                            );
+                        }
+                        if !is_wall(x, y - 1) {
+                            tile_mesh.push_quad(corner, height, Vec3::X * size, wall_color);
+                        }
+                    }
+                    Tile::Empty => {}
+                }
+            }
+        }
+        tile_mesh
+    }
+
+    /// Pushes the quad spanned by `u` and `v`, facing `u × v`
+    fn push_quad(&mut self, origin: Vec3, u: Vec3, v: Vec3, color: [f32; 4]) {
+        let start = self.positions.len() as u32;
+        let normal = u.cross(v).normalize_or_zero().to_array();
+        for (position, uv) in [
+            (origin, [0.0, 0.0]),
+            (origin + u, [1.0, 0.0]),
+            (origin + u + v, [1.0, 1.0]),
+            (origin + v, [0.0, 1.0]),
+        ] {
+            self.positions.push(position.to_array());
+            self.normals.push(normal);
+            self.uvs.push(uv);
+            self.colors.push(color);
+        }
+        self.indices
+            .extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+}
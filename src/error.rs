@@ -0,0 +1,198 @@
+//! Errors returned instead of panicking deep in generation
+//! # Example
+//! Builders validate the configuration before it reaches a system. Generation only rejects settings of
+//! configurations built without a builder that would panic or produce NaN. Failures during generation are logged and sent as `GenerationError` events
+//! ```
+//! use bevy_generative::error::ConfigError;
+//! use bevy_generative::terrain::Terrain;
+//!
+//! let error = Terrain::builder().resolution(0).build().err();
+//! assert!(matches!(error, Some(ConfigError::Resolution { .. })));
+//! ```
+use std::fmt;
+
+use bevy::prelude::{Entity, Event, Vec2};
+
+/// Invalid configuration, returned by `build` of the builders and by `validate`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConfigError {
+    /// Resolution is 0 or above `max`
+    Resolution {
+        /// Configured resolution
+        resolution: u32,
+        /// Highest supported resolution
+        max: u32,
+    },
+    /// A side of the size is 0
+    EmptySize,
+    /// The mesh has more vertices than 32-bit indices can address
+    TooManyVertices {
+        /// Number of vertices of the configured mesh
+        vertices: u64,
+    },
+    /// Sea percent is outside of 0 to 100
+    SeaPercent(f32),
+    /// Height exponent is not a positive finite number
+    HeightExponent(f32),
+    /// Sea level is not a finite number
+    SeaLevel(f32),
+    /// A side of the world size is not a positive finite number
+    WorldSize(Vec2),
+    /// Height scale is negative or not finite
+    HeightScale(f32),
+    /// Simplification tolerance is negative or not finite
+    Simplification(f32),
+    /// Largest angle of the adaptive resolution is negative or not finite
+    AdaptiveResolution(f32),
+    /// Resolution of the detail normals is outside of 1 to `max`
+    DetailResolution {
+        /// Configured resolution
+        resolution: u32,
+        /// Highest supported resolution
+        max: u32,
+    },
+    /// Strength of the detail normals is not finite
+    DetailStrength(f32),
+    /// Noise scale is not a positive finite number
+    Scale(f64),
+    /// Octaves of the noise function are outside of 1 to `max`
+    Octaves {
+        /// Configured octaves
+        octaves: usize,
+        /// Highest supported number of octaves
+        max: usize,
+    },
+    /// There are no regions to color the noise with
+    NoRegions,
+    /// Position of the region at `index` is outside of 0 to 100
+    RegionPosition {
+        /// Index of the region
+        index: usize,
+        /// Configured position
+        position: f64,
+    },
+    /// The region at `index` is not above the region before it
+    RegionOrder {
+        /// Index of the region
+        index: usize,
+    },
+    /// A side of the gradient size is 0
+    EmptyGradient,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resolution { resolution, max } => {
+                write!(f, "Resolution {resolution} must be between 1 and {max}")
+            }
+            Self::EmptySize => write!(f, "Size must not be 0 along any axis"),
+            Self::TooManyVertices { vertices } => write!(
+                f,
+                "Mesh would have {vertices} vertices, more than {} can be indexed",
+                u32::MAX
+            ),
+            Self::SeaPercent(percent) => {
+                write!(f, "Sea percent {percent} must be between 0 and 100")
+            }
+            Self::HeightExponent(exponent) => {
+                write!(f, "Height exponent {exponent} must be positive")
+            }
+            Self::SeaLevel(level) => write!(f, "Sea level {level} must be finite"),
+            Self::WorldSize(size) => write!(f, "World size {size} must be positive"),
+            Self::HeightScale(scale) => {
+                write!(f, "Height scale {scale} must not be negative")
+            }
+            Self::Simplification(tolerance) => {
+                write!(
+                    f,
+                    "Simplification tolerance {tolerance} must not be negative"
+                )
+            }
+            Self::AdaptiveResolution(angle) => {
+                write!(f, "Adaptive resolution angle {angle} must not be negative")
+            }
+            Self::DetailResolution { resolution, max } => {
+                write!(
+                    f,
+                    "Detail resolution {resolution} must be between 1 and {max}"
+                )
+            }
+            Self::DetailStrength(strength) => {
+                write!(f, "Detail strength {strength} must be finite")
+            }
+            Self::Scale(scale) => write!(f, "Noise scale {scale} must be positive"),
+            Self::Octaves { octaves, max } => {
+                write!(f, "Octaves {octaves} must be between 1 and {max}")
+            }
+            Self::NoRegions => write!(f, "Noise needs at least one region"),
+            Self::RegionPosition { index, position } => write!(
+                f,
+                "Position {position} of region {index} must be between 0 and 100"
+            ),
+            Self::RegionOrder { index } => write!(
+                f,
+                "Region {index} must be above region {}, regions are ordered by position",
+                index.saturating_sub(1)
+            ),
+            Self::EmptyGradient => write!(f, "Gradient size must not be 0 along any axis"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Event sent when an entity could not be generated. Its previous mesh is kept
+#[derive(Event, Clone, PartialEq, Debug)]
+pub struct GenerationError {
+    /// Entity that could not be generated
+    pub entity: Entity,
+    /// What failed
+    pub reason: GenerationFailure,
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not generate {:?}: {}", self.entity, self.reason)
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+/// Step of the generation that failed
+#[derive(Clone, PartialEq, Debug)]
+pub enum GenerationFailure {
+    /// The configuration would panic or produce NaN, e.g. a `Terrain` built without its builder
+    Config(ConfigError),
+    /// The gradient could not be built from the regions, with the message of the gradient builder
+    Gradient(String),
+    /// The gradient texture could not be converted to sRGB
+    Texture,
+}
+
+impl fmt::Display for GenerationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(error) => write!(f, "Invalid configuration: {error}"),
+            Self::Gradient(message) => write!(f, "Could not build gradient: {message}"),
+            Self::Texture => write!(f, "Could not convert gradient to Rgba8UnormSrgb"),
+        }
+    }
+}
+
+/// Logs and sends a `GenerationError`, the previous mesh of the entity is kept
+#[cfg(any(
+    feature = "terrain",
+    feature = "map",
+    feature = "planet",
+    feature = "dungeon"
+))]
+pub(crate) fn fail(
+    errors: &mut bevy::prelude::EventWriter<GenerationError>,
+    entity: Entity,
+    reason: GenerationFailure,
+) {
+    let error = GenerationError { entity, reason };
+    bevy::log::warn!("{error}");
+    errors.send(error);
+}
@@ -0,0 +1,986 @@
+//! Export terrain together with its splines, scattered objects and points of interest
+//! # Example
+//! For configuration, see [`WorldExport`](struct.WorldExport.html).
+//! The terrain mesh is saved in glb format with a node per road and river holding its spline as a line strip,
+//! and a node per scattered object and point of interest holding its transform, so other tools and engines
+//! get the whole generated world. The same data is saved next to the model as json, see [`WorldExportData`](struct.WorldExportData.html).
+//! [`EngineExport`](struct.EngineExport.html) saves a 16 bit heightmap, splatmaps and metadata instead,
+//! following the import conventions of Unity terrains or Unreal landscapes.
+//! [`SceneExport`](struct.SceneExport.html) saves the whole environment as a single glb scene for review and
+//! rendering in other tools, the terrain, the water surface and the scattered objects with their materials,
+//! every kind of object is a mesh shared by its instances
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::export::{EngineExport, EngineProfile, SceneExport, WorldExport};
+//! use bevy_generative::poi::PointsOfInterest;
+//! use bevy_generative::road::Roads;
+//! use bevy_generative::terrain::TerrainBundle;
+//! use bevy_generative::GenerativePlugins;
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(GenerativePlugins)
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, export)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Roads::default(),
+//!         PointsOfInterest::default(),
+//!         WorldExport::default(),
+//!         EngineExport {
+//!             profile: EngineProfile::Unreal,
+//!             ..default()
+//!         },
+//!         SceneExport::default(),
+//!     ));
+//! }
+//!
+//! fn export(
+//!     keys: Res<Input<KeyCode>>,
+//!     mut query: Query<(&mut WorldExport, &mut EngineExport, &mut SceneExport)>,
+//! ) {
+//!     for (mut world_export, mut engine_export, mut scene_export) in &mut query {
+//!         world_export.export = keys.just_pressed(KeyCode::F6);
+//!         engine_export.export = keys.just_pressed(KeyCode::F7);
+//!         scene_export.export = keys.just_pressed(KeyCode::F8);
+//!     }
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder, ImageError};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    poi::PointOfInterest,
+    river::RiverNetwork,
+    road::RoadNetwork,
+    scatter::Scattered,
+    splat::SplatMap,
+    terrain::{GeneratedTerrain, Terrain, TerrainMeshPart},
+    util::{export_bytes, export_scene, ExportMaterial, ExportMesh, ExportNode, ExportScene},
+    water::{surface_mesh, Water},
+};
+
+/// Heightmap sizes Unreal recommends for landscapes
+const UNREAL_SIZES: [u32; 8] = [63, 127, 253, 505, 1009, 2017, 4033, 8129];
+
+/// Component for exporting a world, added to an entity with a `Terrain` component
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Component, Clone, PartialEq, Eq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct WorldExport {
+    /// If true, exports the generated terrain in glb format, reset after the export
+    pub export: bool,
+    /// If true, the splines of the `RoadNetwork` are exported
+    pub roads: bool,
+    /// If true, the splines of the `RiverNetwork` are exported
+    pub rivers: bool,
+    /// If true, the transforms of the `Scattered` children are exported
+    pub scatter: bool,
+    /// If true, the transforms of the `PointOfInterest` children are exported
+    pub points_of_interest: bool,
+    /// If true, the exported features are also saved as json next to the model
+    pub sidecar: bool,
+}
+
+impl Default for WorldExport {
+    fn default() -> Self {
+        Self {
+            export: false,
+            roads: true,
+            rivers: true,
+            scatter: true,
+            points_of_interest: true,
+            sidecar: true,
+        }
+    }
+}
+
+/// Exported spline, relative to the terrain entity
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ExportedSpline {
+    /// Points along the spline
+    pub points: Vec<Vec3>,
+    /// Width at every point in world units, empty for roads
+    pub widths: Vec<f32>,
+    /// Indices of the nodes of the `RoadNetwork` a road connects, `None` for rivers
+    pub nodes: Option<[usize; 2]>,
+}
+
+/// Exported object, relative to the terrain entity
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ExportedObject {
+    /// Kind of the scattered object or point of interest
+    pub kind: String,
+    /// Position of the object
+    pub translation: Vec3,
+    /// Rotation of the object
+    pub rotation: Quat,
+    /// Scale of the object
+    pub scale: Vec3,
+}
+
+impl ExportedObject {
+    fn new(kind: &str, transform: &Transform) -> Self {
+        Self {
+            kind: kind.to_string(),
+            translation: transform.translation,
+            rotation: transform.rotation,
+            scale: transform.scale,
+        }
+    }
+
+    fn node(&self, mesh: Option<usize>) -> ExportNode {
+        ExportNode {
+            name: self.kind.clone(),
+            translation: self.translation.to_array(),
+            rotation: self.rotation.to_array(),
+            scale: self.scale.to_array(),
+            mesh,
+            extras: serde_json::to_string(&serde_json::json!({ "kind": self.kind })).ok(),
+            ..default()
+        }
+    }
+}
+
+/// Features of an exported world, saved as the json sidecar of the model
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorldExportData {
+    /// Road splines
+    pub roads: Vec<ExportedSpline>,
+    /// River splines
+    pub rivers: Vec<ExportedSpline>,
+    /// Scattered objects
+    pub scatter: Vec<ExportedObject>,
+    /// Points of interest
+    pub points_of_interest: Vec<ExportedObject>,
+}
+
+impl WorldExportData {
+    /// Nodes of the features grouped under a node per kind of feature
+    fn nodes(&self) -> Vec<ExportNode> {
+        let group = |name: &str, children: Vec<ExportNode>| ExportNode {
+            name: name.to_string(),
+            children,
+            ..default()
+        };
+        let spline = |name: String, spline: &ExportedSpline| ExportNode {
+            name,
+            line: spline.points.iter().map(Vec3::to_array).collect(),
+            extras: serde_json::to_string(
+                &serde_json::json!({ "widths": spline.widths, "nodes": spline.nodes }),
+            )
+            .ok(),
+            ..group("", vec![])
+        };
+        [
+            group(
+                "Roads",
+                self.roads
+                    .iter()
+                    .enumerate()
+                    .map(|(index, road)| spline(format!("Road {index}"), road))
+                    .collect(),
+            ),
+            group(
+                "Rivers",
+                self.rivers
+                    .iter()
+                    .enumerate()
+                    .map(|(index, river)| spline(format!("River {index}"), river))
+                    .collect(),
+            ),
+            group(
+                "Scatter",
+                self.scatter
+                    .iter()
+                    .map(|object| object.node(None))
+                    .collect(),
+            ),
+            group(
+                "PointsOfInterest",
+                self.points_of_interest
+                    .iter()
+                    .map(|object| object.node(None))
+                    .collect(),
+            ),
+        ]
+        .into_iter()
+        .filter(|group| !group.children.is_empty())
+        .collect()
+    }
+}
+
+/// Component for exporting the generated environment as a single glb scene,
+/// added to an entity with a `Terrain` component
+///
+/// Materials keep their factors but not their textures, the terrain keeps its vertex colors
+#[derive(Component, Clone, PartialEq, Eq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct SceneExport {
+    /// If true, exports the scene, reset after the export
+    pub export: bool,
+    /// If true, the surface of the `Water` of the terrain is exported
+    pub water: bool,
+    /// If true, the `Scattered` children are exported. The objects of a kind are instances of the first mesh
+    /// found on one of them or on their children, objects of a kind without a mesh are empty nodes
+    pub scatter: bool,
+}
+
+impl Default for SceneExport {
+    fn default() -> Self {
+        Self {
+            export: false,
+            water: true,
+            scatter: true,
+        }
+    }
+}
+
+/// Engine whose terrain import conventions an `EngineExport` follows
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EngineProfile {
+    /// Unity terrain: square heightmap of `2^n + 1` samples as little endian RAW16 whose first row is the
+    /// `+z` edge, RGBA splatmaps of four layers each and the terrain size in meters
+    #[default]
+    Unity,
+    /// Unreal landscape: heightmap of a recommended size as little endian RAW16 whose first row is the `-z`
+    /// edge, a grayscale weightmap per layer and the scale and location in centimeters, `x`, `z`
+    /// of the terrain are `X`, `Y` of the landscape
+    Unreal,
+}
+
+/// Component for exporting a heightmap, splatmaps and metadata for another engine,
+/// added to an entity with a `Terrain` component
+#[derive(Component, Clone, PartialEq, Eq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct EngineExport {
+    /// If true, exports the files into a chosen folder, reset after the export
+    pub export: bool,
+    /// Import conventions of the files
+    pub profile: EngineProfile,
+    /// Name every file starts with, e.g. `terrain_height.raw`
+    pub name: String,
+    /// Heightmap samples along a side, rounded up to a size the engine imports.
+    /// `None` covers every vertex of the terrain
+    pub resolution: Option<u32>,
+    /// Unity splatmap texels along a side, rounded up to a power of two from 16 to 4096.
+    /// `None` is one texel per heightmap cell. Unreal weightmaps have the size of the heightmap
+    pub splat_resolution: Option<u32>,
+}
+
+impl Default for EngineExport {
+    fn default() -> Self {
+        Self {
+            export: false,
+            profile: EngineProfile::default(),
+            name: "terrain".to_string(),
+            resolution: None,
+            splat_resolution: None,
+        }
+    }
+}
+
+impl EngineExport {
+    /// Names and contents of the heightmap, splatmaps and metadata of `terrain_data`.
+    /// The layers are the regions of the noise of `terrain` followed by the first four layers of `splat_map`,
+    /// painted over the regions, so their weights add up to 1
+    ///
+    /// # Errors
+    /// If a splatmap or weightmap cannot be encoded as png
+    pub fn files(
+        &self,
+        terrain: &Terrain,
+        terrain_data: &GeneratedTerrain,
+        splat_map: Option<&SplatMap>,
+    ) -> Result<Vec<(String, Vec<u8>)>, ImageError> {
+        if terrain_data.heights.first().is_none_or(Vec::is_empty) {
+            return Ok(vec![]);
+        }
+        let columns = terrain_data.heights.len().max(2) as u32;
+        let rows = terrain_data.heights.first().map_or(0, Vec::len).max(2) as u32;
+        let min = terrain_data.stats.min_height;
+        let range = terrain_data.stats.max_height - min;
+        let name = &self.name;
+        let mut layers: Vec<String> = terrain
+            .noise
+            .regions
+            .iter()
+            .enumerate()
+            .map(|(index, region)| {
+                if region.label.is_empty() {
+                    format!("region{index}")
+                } else {
+                    region.label.clone()
+                }
+            })
+            .collect();
+        layers.extend(
+            splat_map
+                .into_iter()
+                .flat_map(|splat_map| splat_map.layers.iter().take(4))
+                .map(|layer| layer.name.clone()),
+        );
+        // Centered bounds of the terrain, the first and last vertex
+        let first = terrain_data.center - terrain_data.world_size / 2.0;
+        let last = terrain_data.center + terrain_data.world_size / 2.0;
+
+        let mut files = vec![];
+        match self.profile {
+            EngineProfile::Unity => {
+                let wanted = self.resolution.unwrap_or_else(|| columns.max(rows));
+                let size = (wanted.saturating_sub(1).clamp(32, 4096)).next_power_of_two() + 1;
+                // Unity rows start at its `z = 0`, the `+z` edge as Unity's `z` is flipped
+                let heightmap = heightmap(terrain_data, [size; 2], true, min, range);
+                let splat_size = self
+                    .splat_resolution
+                    .unwrap_or(size - 1)
+                    .clamp(16, 4096)
+                    .next_power_of_two();
+                let weights = layer_weights(terrain_data, splat_map, [splat_size; 2], layers.len());
+                let mut splatmaps = vec![];
+                for (index, channels) in (0..layers.len()).collect::<Vec<_>>().chunks(4).enumerate()
+                {
+                    let pixels: Vec<u8> = weights
+                        .iter()
+                        .flat_map(|texel| {
+                            [0, 1, 2, 3].map(|channel| {
+                                channels
+                                    .get(channel)
+                                    .map_or(0, |&layer| (texel[layer] * 255.0).round() as u8)
+                            })
+                        })
+                        .collect();
+                    let file = format!("{name}_splat{index}.png");
+                    files.push((
+                        file.clone(),
+                        png(&pixels, [splat_size; 2], ColorType::Rgba8)?,
+                    ));
+                    splatmaps.push(file);
+                }
+                let metadata = serde_json::json!({
+                    "heightmap": format!("{name}_height.raw"),
+                    "heightmapResolution": size,
+                    "depth": 16,
+                    "byteOrder": "windows",
+                    "flipVertically": false,
+                    "size": [terrain_data.world_size.x, range, terrain_data.world_size.y],
+                    "position": [first.x, min, -last.y],
+                    "alphamapResolution": splat_size,
+                    "layers": layers,
+                    "splatmaps": splatmaps,
+                });
+                files.insert(0, (format!("{name}_height.raw"), heightmap));
+                files.push((format!("{name}_unity.json"), json_bytes(&metadata)));
+            }
+            EngineProfile::Unreal => {
+                let fit = |wanted: u32| {
+                    UNREAL_SIZES
+                        .into_iter()
+                        .find(|&size| size >= wanted)
+                        .unwrap_or(UNREAL_SIZES[UNREAL_SIZES.len() - 1])
+                };
+                let size = self.resolution.map_or_else(
+                    || [fit(columns), fit(rows)],
+                    |resolution| [fit(resolution); 2],
+                );
+                let heightmap = heightmap(terrain_data, size, false, min, range);
+                let weights = layer_weights(terrain_data, splat_map, size, layers.len());
+                let mut weightmaps = vec![];
+                for (layer, layer_name) in layers.iter().enumerate() {
+                    let pixels: Vec<u8> = weights
+                        .iter()
+                        .map(|texel| (texel[layer] * 255.0).round() as u8)
+                        .collect();
+                    let file_name: String = layer_name
+                        .chars()
+                        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                        .collect();
+                    let file = format!("{name}_{file_name}.png");
+                    files.push((file.clone(), png(&pixels, size, ColorType::L8)?));
+                    weightmaps.push(serde_json::json!({ "name": layer_name, "weightmap": file }));
+                }
+                // Heights are `location + (sample - 32768) * scale / 128` in centimeters
+                let z_scale = if range > 0.0 {
+                    range * 100.0 * 128.0 / 65535.0
+                } else {
+                    100.0
+                };
+                let metadata = serde_json::json!({
+                    "heightmap": format!("{name}_height.r16"),
+                    "resolution": size,
+                    "format": "r16",
+                    "units": "centimeters",
+                    "scale": [
+                        terrain_data.world_size.x * 100.0 / (size[0] - 1) as f32,
+                        terrain_data.world_size.y * 100.0 / (size[1] - 1) as f32,
+                        z_scale,
+                    ],
+                    "location": [first.x * 100.0, first.y * 100.0, min.mul_add(100.0, 256.0 * z_scale)],
+                    "layers": weightmaps,
+                });
+                files.insert(0, (format!("{name}_height.r16"), heightmap));
+                files.push((format!("{name}_unreal.json"), json_bytes(&metadata)));
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Position of sample `i`, `j` of a `size` grid over the vertices of `terrain_data`, in vertices.
+/// Rows go from `+z` to `-z` if `flip` is true
+fn sample_index(
+    terrain_data: &GeneratedTerrain,
+    size: [u32; 2],
+    i: u32,
+    j: u32,
+    flip: bool,
+) -> Vec2 {
+    let columns = terrain_data.heights.len().saturating_sub(1) as f32;
+    let rows = terrain_data
+        .heights
+        .first()
+        .map_or(0, Vec::len)
+        .saturating_sub(1) as f32;
+    let u = i as f32 / (size[0] - 1).max(1) as f32;
+    let v = j as f32 / (size[1] - 1).max(1) as f32;
+    Vec2::new(u * columns, if flip { 1.0 - v } else { v } * rows)
+}
+
+/// Heights of `terrain_data` resampled to `size` as little endian 16 bit samples, `min` is 0 and `min + range` 65535
+fn heightmap(
+    terrain_data: &GeneratedTerrain,
+    size: [u32; 2],
+    flip: bool,
+    min: f32,
+    range: f32,
+) -> Vec<u8> {
+    let heights = &terrain_data.heights;
+    let mut bytes = Vec::with_capacity(size[0] as usize * size[1] as usize * 2);
+    for j in 0..size[1] {
+        for i in 0..size[0] {
+            let index = sample_index(terrain_data, size, i, j, flip);
+            let column = (index.x.floor() as usize).min(heights.len().saturating_sub(2));
+            let row = (index.y.floor() as usize).min(heights[0].len().saturating_sub(2));
+            let (tx, tz) = (index.x - column as f32, index.y - row as f32);
+            let height = |di: usize, dj: usize| {
+                heights
+                    .get(column + di)
+                    .and_then(|column| column.get(row + dj))
+                    .copied()
+                    .unwrap_or(min)
+            };
+            let near = (height(1, 0) - height(0, 0)).mul_add(tx, height(0, 0));
+            let far = (height(1, 1) - height(0, 1)).mul_add(tx, height(0, 1));
+            let height = (far - near).mul_add(tz, near);
+            let sample = if range > 0.0 {
+                ((height - min) / range * 65535.0)
+                    .round()
+                    .clamp(0.0, 65535.0) as u16
+            } else {
+                0
+            };
+            bytes.extend(sample.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Weight of `layers` layers in every texel of a `size` map, rows from `-z` to `+z`.
+/// The region of the nearest vertex is covered, then the layers of `splat_map` are painted over it
+fn layer_weights(
+    terrain_data: &GeneratedTerrain,
+    splat_map: Option<&SplatMap>,
+    size: [u32; 2],
+    layers: usize,
+) -> Vec<Vec<f32>> {
+    let regions = layers - splat_map.map_or(0, |splat_map| splat_map.layers.len().min(4));
+    let grid = Vec2::new(terrain_data.size[0] as f32, terrain_data.size[1] as f32);
+    let mut weights = Vec::with_capacity(size[0] as usize * size[1] as usize);
+    for j in 0..size[1] {
+        for i in 0..size[0] {
+            let mut texel = vec![0.0; layers];
+            let index = sample_index(terrain_data, size, i, j, false).round();
+            let region = terrain_data
+                .regions
+                .get(index.x as usize)
+                .and_then(|column| column.get(index.y as usize))
+                .copied()
+                .unwrap_or_default();
+            if regions > 0 {
+                texel[region.min(regions - 1)] = 1.0;
+            }
+            if let Some(splat_map) = splat_map {
+                let x = (i as f32 / (size[0] - 1).max(1) as f32 - 0.5) * grid.x;
+                let z = (j as f32 / (size[1] - 1).max(1) as f32 - 0.5) * grid.y;
+                for (layer, weight) in splat_map
+                    .weights_at(x, z, terrain_data.size)
+                    .into_iter()
+                    .take(layers - regions)
+                    .enumerate()
+                {
+                    for value in &mut texel {
+                        *value *= 1.0 - weight;
+                    }
+                    texel[regions + layer] += weight;
+                }
+            }
+            weights.push(texel);
+        }
+    }
+    weights
+}
+
+/// `pixels` of a `size` image encoded as png
+fn png(pixels: &[u8], size: [u32; 2], color_type: ColorType) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = vec![];
+    PngEncoder::new(&mut bytes).write_image(pixels, size[0], size[1], color_type)?;
+    Ok(bytes)
+}
+
+fn json_bytes(value: &serde_json::Value) -> Vec<u8> {
+    serde_json::to_vec_pretty(value).unwrap_or_default()
+}
+
+/// Plugin to export worlds
+pub struct WorldExportPlugin;
+
+impl Plugin for WorldExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (export_worlds, export_engines, export_environments));
+    }
+}
+
+fn export_engines(
+    mut query: Query<(
+        Entity,
+        &mut EngineExport,
+        &Terrain,
+        &GeneratedTerrain,
+        Option<&SplatMap>,
+    )>,
+) {
+    for (entity, mut engine_export, terrain, terrain_data, splat_map) in &mut query {
+        if !engine_export.export {
+            continue;
+        }
+        engine_export.bypass_change_detection().export = false;
+        match engine_export.files(terrain, terrain_data, splat_map) {
+            Ok(files) => export_bytes(files),
+            Err(error) => warn!("Could not export terrain {entity:?}: {error}"),
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn export_worlds(
+    meshes: Option<Res<Assets<Mesh>>>,
+    mut query: Query<
+        (
+            &mut WorldExport,
+            Option<&Handle<Mesh>>,
+            Option<&RoadNetwork>,
+            Option<&RiverNetwork>,
+            Option<&Children>,
+        ),
+        With<GeneratedTerrain>,
+    >,
+    children: Query<(
+        &Transform,
+        Option<&Handle<Mesh>>,
+        Option<&TerrainMeshPart>,
+        Option<&Scattered>,
+        Option<&PointOfInterest>,
+    )>,
+) {
+    for (mut world_export, mesh, road_network, river_network, terrain_children) in &mut query {
+        if !world_export.export {
+            continue;
+        }
+        world_export.bypass_change_detection().export = false;
+
+        let mut data = WorldExportData::default();
+        if let (true, Some(road_network)) = (world_export.roads, road_network) {
+            data.roads = road_network
+                .roads
+                .iter()
+                .map(|road| ExportedSpline {
+                    points: road.points.clone(),
+                    widths: vec![],
+                    nodes: Some([road.from, road.to]),
+                })
+                .collect();
+        }
+        if let (true, Some(river_network)) = (world_export.rivers, river_network) {
+            data.rivers = river_network
+                .rivers
+                .iter()
+                .map(|river| ExportedSpline {
+                    points: river.points.clone(),
+                    widths: river.widths.clone(),
+                    nodes: None,
+                })
+                .collect();
+        }
+        let mut handles: Vec<&Handle<Mesh>> = mesh.into_iter().collect();
+        for &child in terrain_children.into_iter().flatten() {
+            let Ok((transform, part_mesh, part, scattered, point)) = children.get(child) else {
+                continue;
+            };
+            if let (Some(part_mesh), Some(_)) = (part_mesh, part) {
+                handles.push(part_mesh);
+            }
+            if let (true, Some(scattered)) = (world_export.scatter, scattered) {
+                data.scatter
+                    .push(ExportedObject::new(&scattered.kind, transform));
+            }
+            if let (true, Some(point)) = (world_export.points_of_interest, point) {
+                data.points_of_interest
+                    .push(ExportedObject::new(&point.kind, transform));
+            }
+        }
+
+        let meshes: Vec<ExportMesh> = meshes.as_deref().map_or_else(Vec::new, |meshes| {
+            handles
+                .into_iter()
+                .filter_map(|handle| mesh_data(meshes.get(handle)?))
+                .collect()
+        });
+        let nodes = (0..meshes.len())
+            .map(|index| ExportNode {
+                mesh: Some(index),
+                ..default()
+            })
+            .chain(data.nodes())
+            .collect();
+        let sidecar = world_export
+            .sidecar
+            .then(|| serde_json::to_string_pretty(&data).ok())
+            .flatten();
+        export_scene(ExportScene {
+            meshes,
+            nodes,
+            sidecar,
+            ..default()
+        });
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn export_environments(
+    meshes: Option<Res<Assets<Mesh>>>,
+    materials: Option<Res<Assets<StandardMaterial>>>,
+    mut query: Query<(
+        &mut SceneExport,
+        &GeneratedTerrain,
+        Option<&Handle<Mesh>>,
+        Option<&Handle<StandardMaterial>>,
+        Option<&Water>,
+        Option<&Children>,
+    )>,
+    children: Query<(
+        &Transform,
+        Option<&Handle<Mesh>>,
+        Option<&Handle<StandardMaterial>>,
+        Option<&TerrainMeshPart>,
+        Option<&Scattered>,
+        Option<&Children>,
+    )>,
+) {
+    let get_mesh = |handle: &Handle<Mesh>| mesh_data(meshes.as_deref()?.get(handle)?);
+    let get_material = |name: &str, handle: Option<&Handle<StandardMaterial>>| {
+        let material = handle.and_then(|handle| materials.as_deref()?.get(handle));
+        material.map_or_else(
+            || ExportMaterial {
+                name: name.to_string(),
+                ..default()
+            },
+            |material| export_material(name, material),
+        )
+    };
+    for (mut scene_export, terrain_data, mesh, material, water, terrain_children) in &mut query {
+        if !scene_export.export {
+            continue;
+        }
+        scene_export.bypass_change_detection().export = false;
+        let terrain_children: Vec<Entity> =
+            terrain_children.into_iter().flatten().copied().collect();
+
+        let mut scene = ExportScene {
+            materials: vec![get_material("Terrain", material)],
+            ..default()
+        };
+        let mut terrain = ExportNode {
+            name: "Terrain".to_string(),
+            ..default()
+        };
+        let parts = terrain_children.iter().filter_map(|&child| {
+            let (_, mesh, _, part, ..) = children.get(child).ok()?;
+            part.and(mesh)
+        });
+        for handle in mesh.into_iter().chain(parts) {
+            if let Some(data) = get_mesh(handle) {
+                terrain.children.push(ExportNode {
+                    name: format!("Terrain {}", scene.meshes.len()),
+                    mesh: Some(scene.meshes.len()),
+                    ..default()
+                });
+                scene.meshes.push(ExportMesh {
+                    name: "Terrain".to_string(),
+                    material: Some(0),
+                    ..data
+                });
+            }
+        }
+        if !terrain.children.is_empty() {
+            scene.nodes.push(terrain);
+        }
+
+        if let (true, Some(water)) = (scene_export.water, water) {
+            let [r, g, b, a] = water.color;
+            scene.materials.push(ExportMaterial {
+                name: "Water".to_string(),
+                base_color: Color::rgba_u8(r, g, b, a).as_linear_rgba_f32(),
+                roughness: water.roughness,
+                blend: true,
+                ..default()
+            });
+            if let Some(data) = mesh_data(&surface_mesh(terrain_data)) {
+                scene.nodes.push(ExportNode {
+                    name: "Water".to_string(),
+                    mesh: Some(scene.meshes.len()),
+                    ..default()
+                });
+                scene.meshes.push(ExportMesh {
+                    name: "Water".to_string(),
+                    material: Some(scene.materials.len() - 1),
+                    ..data
+                });
+            }
+        }
+
+        if scene_export.scatter {
+            // Kinds of the objects with the index of the mesh their instances reference
+            let mut prototypes: Vec<(String, Option<usize>)> = vec![];
+            let mut instances = vec![];
+            for &child in &terrain_children {
+                let Ok((transform, mesh, material, _, Some(scattered), object_children)) =
+                    children.get(child)
+                else {
+                    continue;
+                };
+                let kind = &scattered.kind;
+                let index = prototypes
+                    .iter()
+                    .position(|(prototype, _)| prototype == kind)
+                    .unwrap_or_else(|| {
+                        prototypes.push((kind.clone(), None));
+                        prototypes.len() - 1
+                    });
+                if prototypes[index].1.is_none() {
+                    let found = object_children
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|&object_child| children.get(object_child).ok())
+                        .map(|(_, mesh, material, ..)| (mesh, material))
+                        .chain([(mesh, material)])
+                        .find_map(|(mesh, material)| Some((get_mesh(mesh?)?, material)));
+                    if let Some((data, material)) = found {
+                        scene.materials.push(get_material(kind, material));
+                        prototypes[index].1 = Some(scene.meshes.len());
+                        scene.meshes.push(ExportMesh {
+                            name: kind.clone(),
+                            material: Some(scene.materials.len() - 1),
+                            ..data
+                        });
+                    }
+                }
+                instances.push((index, ExportedObject::new(kind, transform)));
+            }
+            if !instances.is_empty() {
+                scene.nodes.push(ExportNode {
+                    name: "Scatter".to_string(),
+                    children: instances
+                        .iter()
+                        .map(|(index, object)| object.node(prototypes[*index].1))
+                        .collect(),
+                    ..default()
+                });
+            }
+        }
+        export_scene(scene);
+    }
+}
+
+/// Material of an exported scene with the factors of `material`
+fn export_material(name: &str, material: &StandardMaterial) -> ExportMaterial {
+    let [r, g, b, _] = material.emissive.as_linear_rgba_f32();
+    ExportMaterial {
+        name: name.to_string(),
+        base_color: material.base_color.as_linear_rgba_f32(),
+        metallic: material.metallic,
+        roughness: material.perceptual_roughness,
+        emissive: [r, g, b],
+        blend: !matches!(material.alpha_mode, AlphaMode::Opaque | AlphaMode::Mask(_)),
+        alpha_cutoff: match material.alpha_mode {
+            AlphaMode::Mask(cutoff) => Some(cutoff),
+            _ => None,
+        },
+        double_sided: material.double_sided,
+    }
+}
+
+/// Positions, triangle indices, colors and normals of `mesh`, `None` without positions
+fn mesh_data(mesh: &Mesh) -> Option<ExportMesh> {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let colors = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+        Some(VertexAttributeValues::Float32x4(colors)) => colors.clone(),
+        _ => vec![],
+    };
+    let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float32x3(normals)) => normals.clone(),
+        _ => vec![],
+    };
+    let indices = match mesh.indices() {
+        Some(Indices::U32(indices)) => indices.clone(),
+        Some(Indices::U16(indices)) => indices.iter().map(|&index| u32::from(index)).collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    Some(ExportMesh {
+        positions: positions.clone(),
+        indices,
+        colors,
+        normals,
+        ..default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::TerrainStats;
+
+    /// Terrain of 5 by 3 vertices rising from 0 at `-z` to 2 at `+z`
+    fn ramp() -> GeneratedTerrain {
+        let heights: Vec<Vec<f32>> = (0..5).map(|_| vec![0.0, 1.0, 2.0]).collect();
+        GeneratedTerrain {
+            regions: vec![vec![0; 3]; 5],
+            resolution: 1,
+            size: [4, 2],
+            world_size: Vec2::new(4.0, 2.0),
+            center: Vec2::new(1.0, -0.5),
+            stats: TerrainStats {
+                min_height: 0.0,
+                max_height: 2.0,
+                ..default()
+            },
+            base_heights: heights.clone(),
+            heights,
+            ..default()
+        }
+    }
+
+    /// Rows of the RAW16 heightmap of `files`
+    fn rows(files: &[(String, Vec<u8>)], width: usize) -> Vec<Vec<u16>> {
+        let samples: Vec<u16> = files[0]
+            .1
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        samples.chunks(width).map(<[u16]>::to_vec).collect()
+    }
+
+    fn metadata(files: &[(String, Vec<u8>)]) -> serde_json::Value {
+        serde_json::from_slice(&files.last().unwrap().1).unwrap()
+    }
+
+    #[test]
+    fn unity_rows_start_at_the_far_edge() {
+        let export = EngineExport::default();
+        let files = export.files(&Terrain::default(), &ramp(), None).unwrap();
+        assert_eq!(metadata(&files)["heightmapResolution"], 33);
+        let rows = rows(&files, 33);
+        assert_eq!(rows.len(), 33);
+        assert!(rows[0].iter().all(|&sample| sample == u16::MAX));
+        assert!(rows[32].iter().all(|&sample| sample == 0));
+        assert!(rows.windows(2).all(|pair| pair[0][0] >= pair[1][0]));
+    }
+
+    #[test]
+    fn unreal_rows_start_at_the_near_edge() {
+        let export = EngineExport {
+            profile: EngineProfile::Unreal,
+            ..default()
+        };
+        let files = export.files(&Terrain::default(), &ramp(), None).unwrap();
+        let rows = rows(&files, 63);
+        assert_eq!(rows.len(), 63);
+        assert!(rows[0].iter().all(|&sample| sample == 0));
+        assert!(rows[62].iter().all(|&sample| sample == u16::MAX));
+        assert!(rows.windows(2).all(|pair| pair[0][0] <= pair[1][0]));
+    }
+
+    #[test]
+    fn unreal_location_and_scale_map_samples_to_heights() {
+        let export = EngineExport {
+            profile: EngineProfile::Unreal,
+            ..default()
+        };
+        let files = export.files(&Terrain::default(), &ramp(), None).unwrap();
+        let metadata = metadata(&files);
+        let value = |key: &str, index: usize| metadata[key][index].as_f64().unwrap();
+        // Landscape heights in centimeters
+        let height =
+            |sample: f64| value("location", 2) + (sample - 32768.0) * value("scale", 2) / 128.0;
+        assert!(height(0.0).abs() < 0.01);
+        assert!((height(65535.0) - 200.0).abs() < 0.01);
+        assert!((value("location", 0) + 100.0).abs() < 1e-3);
+        assert!((value("location", 1) + 150.0).abs() < 1e-3);
+        assert!((value("scale", 0) - 400.0 / 62.0).abs() < 1e-3);
+        assert!((value("scale", 1) - 200.0 / 62.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn layer_weights_add_up_to_one() {
+        let terrain_data = ramp();
+        let splat_map = SplatMap {
+            resolution: 1,
+            size: [4, 2],
+            weights: (0..8).map(|texel| [texel * 30, 100, 0, 0]).collect(),
+            ..default()
+        };
+        let layers = Terrain::default().noise.regions.len() + splat_map.layers.len();
+        let weights = layer_weights(&terrain_data, Some(&splat_map), [16; 2], layers);
+        assert!(weights.iter().any(|texel| texel[layers - 1] > 0.0));
+        for texel in weights {
+            assert!((texel.iter().sum::<f32>() - 1.0).abs() < 1e-5, "{texel:?}");
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! Bake looping noise animations into sprite sheets and texture arrays
+//! # Example
+//! For configuration, see [`NoiseAnimation`](struct.NoiseAnimation.html).
+//! The noise moves along a circle through its third and fourth dimension, so the last frame leads back into the first.
+//! A `SpriteSheetBundle` on the entity gets the baked atlas and plays it back
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::flipbook::{FlipbookPlugin, NoiseAnimation};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(FlipbookPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(Camera2dBundle::default());
+//!     commands.spawn((NoiseAnimation::default(), SpriteSheetBundle::default()));
+//! }
+//! ```
+use std::f64::consts::TAU;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use image::Pixel;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::GenerationFailure,
+    noise::{build_gradient, get_noise_at_point_4d, Noise},
+    util::{export_asset, export_sequence, pack_sheet, sheet_grid},
+};
+
+/// Kind of image a `NoiseAnimation` is baked into
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FlipbookTarget {
+    /// Frames laid out in a grid of a single 2D image, with a `TextureAtlas`
+    #[default]
+    SpriteSheet,
+    /// One layer of a 2D array texture per frame, for custom materials
+    TextureArray,
+}
+
+/// Component for noise animation configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct NoiseAnimation {
+    /// Noise of every frame, the regions and gradient color it like a map
+    pub noise: Noise,
+    /// Width and height of a frame in pixels
+    pub frame_size: [u32; 2],
+    /// Number of frames in a loop
+    pub frames: u32,
+    /// Seconds of a loop
+    pub duration: f32,
+    /// How far the noise moves during a loop, in units of `Noise::scale`
+    pub motion: f64,
+    /// Kind of image the frames are baked into
+    pub target: FlipbookTarget,
+    /// If true, exports the sheet or the frames in png format
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for NoiseAnimation {
+    fn default() -> Self {
+        Self {
+            noise: Noise {
+                scale: 20.0,
+                ..default()
+            },
+            frame_size: [64, 64],
+            frames: 16,
+            duration: 1.0,
+            motion: 1.0,
+            target: FlipbookTarget::SpriteSheet,
+            export: false,
+        }
+    }
+}
+
+/// Layout of the frames of a baked `NoiseAnimation`
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlipbookLayout {
+    /// Width and height of a frame in pixels
+    pub frame_size: [u32; 2],
+    /// Columns and rows of the sprite sheet, `[1, frames]` for texture arrays
+    pub grid: [u32; 2],
+    /// Number of frames in a loop
+    pub frames: u32,
+    /// Seconds of a loop
+    pub duration: f32,
+}
+
+impl FlipbookLayout {
+    /// Frame shown `seconds` after the start, looping
+    #[must_use]
+    pub fn frame_at(&self, seconds: f32) -> u32 {
+        let phase = (seconds / self.duration.max(f32::EPSILON)).rem_euclid(1.0);
+        ((phase * self.frames as f32) as u32).min(self.frames.saturating_sub(1))
+    }
+
+    /// Pixels covered by `frame` in the sprite sheet
+    #[must_use]
+    pub fn frame_rect(&self, frame: u32) -> Rect {
+        let [width, height] = self.frame_size.map(|size| size as f32);
+        let columns = self.grid[0].max(1);
+        let min = Vec2::new(
+            (frame % columns) as f32 * width,
+            (frame / columns) as f32 * height,
+        );
+        Rect::from_corners(min, min + Vec2::new(width, height))
+    }
+}
+
+impl NoiseAnimation {
+    /// RGBA pixels of every frame of the loop, row by row
+    ///
+    /// # Errors
+    /// If the gradient could not be built from the regions of the noise
+    pub fn frame_pixels(&self) -> Result<Vec<Vec<u8>>, GenerationFailure> {
+        let [width, height] = self.frame_size.map(|size| size.max(1));
+        let count = self.frames.max(1);
+        let noise = &self.noise;
+        let grad = build_gradient(&noise.regions, &noise.gradient)
+            .map_err(|error| GenerationFailure::Gradient(error.to_string()))?;
+        let radius = self.motion * noise.scale / TAU;
+        Ok((0..count)
+            .map(|frame| {
+                let angle = TAU * f64::from(frame) / f64::from(count);
+                let (z, w) = (radius * angle.cos(), radius * angle.sin());
+                let mut values: Vec<Vec<f64>> = (0..width)
+                    .map(|x| {
+                        (0..height)
+                            .map(|y| {
+                                let value = get_noise_at_point_4d(
+                                    [f64::from(x), f64::from(y), z, w],
+                                    noise.seed,
+                                    noise.scale,
+                                    [noise.offset[0], noise.offset[1], 0.0, 0.0],
+                                    &noise.method,
+                                    &noise.function,
+                                );
+                                // Unlike maps the values are not normalized, which would flicker between frames
+                                (value + 1.0) * 50.0
+                            })
+                            .collect()
+                    })
+                    .collect();
+                for modifier in &noise.modifiers {
+                    modifier.apply(&mut values);
+                }
+                let mut data = Vec::with_capacity((width * height * 4) as usize);
+                for y in 0..height as usize {
+                    for column in &values {
+                        let mut pixel = image::Rgba(noise.base_color);
+                        pixel.blend(&image::Rgba(grad.at(column[y]).to_rgba8()));
+                        data.extend(pixel.0);
+                    }
+                }
+                data
+            })
+            .collect())
+    }
+
+    /// Layout of the frames in the baked image
+    #[must_use]
+    pub fn layout(&self) -> FlipbookLayout {
+        let frames = self.frames.max(1);
+        FlipbookLayout {
+            frame_size: self.frame_size.map(|size| size.max(1)),
+            grid: match self.target {
+                FlipbookTarget::SpriteSheet => sheet_grid(frames),
+                FlipbookTarget::TextureArray => [1, frames],
+            },
+            frames,
+            duration: self.duration,
+        }
+    }
+
+    /// Sprite sheet or array texture of the frames in `Rgba8UnormSrgb`
+    ///
+    /// # Errors
+    /// If the gradient could not be built from the regions of the noise
+    pub fn bake(&self) -> Result<Image, GenerationFailure> {
+        let layout = self.layout();
+        let frames = self.frame_pixels()?;
+        let [width, height] = layout.frame_size;
+        let [columns, rows] = layout.grid;
+        let data = match self.target {
+            FlipbookTarget::SpriteSheet => pack_sheet(&frames, layout.frame_size),
+            FlipbookTarget::TextureArray => frames.concat(),
+        };
+        let mut image = Image::new(
+            Extent3d {
+                width: columns * width,
+                height: rows * height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        if self.target == FlipbookTarget::TextureArray {
+            image.reinterpret_stacked_2d_as_array(layout.frames);
+        }
+        Ok(image)
+    }
+}
+
+/// Baked `NoiseAnimation`, inserted on the entity with the `NoiseAnimation` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct Flipbook {
+    /// Sprite sheet or array texture
+    pub image: Handle<Image>,
+    /// Layout of the frames in `image`
+    pub layout: FlipbookLayout,
+    /// Atlas of the sprite sheet, indexed by frame
+    pub atlas: Option<Handle<TextureAtlas>>,
+}
+
+/// Plugin to bake and play back noise animations
+pub struct FlipbookPlugin;
+
+impl Plugin for FlipbookPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (bake_noise_animations, play_flipbooks).chain());
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn bake_noise_animations(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut NoiseAnimation,
+            Option<&mut Handle<TextureAtlas>>,
+        ),
+        Changed<NoiseAnimation>,
+    >,
+) {
+    for (entity, mut animation, atlas_handle) in &mut query {
+        let layout = animation.layout();
+        let image = match animation.bake() {
+            Ok(image) => image,
+            Err(error) => {
+                warn!("Could not bake noise animation {entity:?}: {error}");
+                continue;
+            }
+        };
+        if animation.export {
+            let [width, height] = layout.frame_size;
+            match animation.target {
+                FlipbookTarget::SpriteSheet => {
+                    if let Some(buffer) = image::ImageBuffer::from_raw(
+                        image.width(),
+                        image.height(),
+                        image.data.clone(),
+                    ) {
+                        export_asset(buffer);
+                    }
+                }
+                FlipbookTarget::TextureArray => export_sequence(
+                    "noise",
+                    image
+                        .data
+                        .chunks_exact((width * height * 4) as usize)
+                        .filter_map(|frame| {
+                            image::ImageBuffer::from_raw(width, height, frame.to_vec())
+                        })
+                        .collect(),
+                ),
+            }
+        }
+        animation.bypass_change_detection().export = false;
+        let image = images.add(image);
+        let atlas = (animation.target == FlipbookTarget::SpriteSheet).then(|| {
+            atlases.add(TextureAtlas::from_grid(
+                image.clone(),
+                Vec2::new(layout.frame_size[0] as f32, layout.frame_size[1] as f32),
+                layout.grid[0] as usize,
+                layout.grid[1] as usize,
+                None,
+                None,
+            ))
+        });
+        if let (Some(mut atlas_handle), Some(atlas)) = (atlas_handle, &atlas) {
+            *atlas_handle = atlas.clone();
+        }
+        commands.entity(entity).insert(Flipbook {
+            image,
+            layout,
+            atlas,
+        });
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn play_flipbooks(time: Res<Time>, mut query: Query<(&Flipbook, &mut TextureAtlasSprite)>) {
+    for (flipbook, mut sprite) in &mut query {
+        let index = flipbook.layout.frame_at(time.elapsed_seconds()) as usize;
+        // Only writes when the frame changes, so the sprite is not extracted as changed every update
+        if sprite.index != index {
+            sprite.index = index;
+        }
+    }
+}
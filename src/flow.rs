@@ -0,0 +1,435 @@
+//! Compute D8 flow directions and flow accumulation over terrain
+//! # Example
+//! For configuration, see [`Flow`](struct.Flow.html).
+//! Heightfields of any source can also be routed directly with [`FlowData::from_heights`](struct.FlowData.html#method.from_heights)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::flow::{Flow, FlowData, FlowPlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, FlowPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_center)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Flow::default()));
+//! }
+//!
+//! fn print_center(query: Query<&FlowData, Changed<FlowData>>) {
+//!     for flow in &query {
+//!         println!(
+//!             "{:?} towards {:?}",
+//!             flow.accumulation_at(0.0, 0.0),
+//!             flow.direction_at(0.0, 0.0)
+//!         );
+//!     }
+//! }
+//! ```
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::GeneratedTerrain;
+
+/// Component for flow configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Flow {
+    /// If true, depressions are filled before routing so every vertex drains to the sea or the side of the terrain.
+    /// Otherwise vertices without a lower neighbour are sinks
+    pub fill_depressions: bool,
+}
+
+impl Default for Flow {
+    fn default() -> Self {
+        Self {
+            fill_depressions: true,
+        }
+    }
+}
+
+/// Direction towards one of the eight neighbours of a vertex, east is `+x` and south is `+z`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FlowDirection {
+    /// Towards `+x`
+    East,
+    /// Towards `+x`, `+z`
+    SouthEast,
+    /// Towards `+z`
+    South,
+    /// Towards `-x`, `+z`
+    SouthWest,
+    /// Towards `-x`
+    West,
+    /// Towards `-x`, `-z`
+    NorthWest,
+    /// Towards `-z`
+    North,
+    /// Towards `+x`, `-z`
+    NorthEast,
+}
+
+impl FlowDirection {
+    /// Every direction, clockwise from east
+    pub const ALL: [Self; 8] = [
+        Self::East,
+        Self::SouthEast,
+        Self::South,
+        Self::SouthWest,
+        Self::West,
+        Self::NorthWest,
+        Self::North,
+        Self::NorthEast,
+    ];
+
+    /// Offset to the neighbour in vertices along `x` and `z`
+    #[must_use]
+    pub const fn offset(self) -> [i32; 2] {
+        match self {
+            Self::East => [1, 0],
+            Self::SouthEast => [1, 1],
+            Self::South => [0, 1],
+            Self::SouthWest => [-1, 1],
+            Self::West => [-1, 0],
+            Self::NorthWest => [-1, -1],
+            Self::North => [0, -1],
+            Self::NorthEast => [1, -1],
+        }
+    }
+
+    /// Power of two code of the direction, as used by common GIS tools
+    #[must_use]
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::East => 1,
+            Self::SouthEast => 2,
+            Self::South => 4,
+            Self::SouthWest => 8,
+            Self::West => 16,
+            Self::NorthWest => 32,
+            Self::North => 64,
+            Self::NorthEast => 128,
+        }
+    }
+
+    /// Distance to the neighbour in vertices
+    const fn length(self) -> f64 {
+        match self.offset() {
+            [0, _] | [_, 0] => 1.0,
+            _ => std::f64::consts::SQRT_2,
+        }
+    }
+}
+
+/// Flow directions and accumulation of every terrain vertex, inserted on the entity with the `Flow` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct FlowData {
+    /// Direction of steepest descent of every vertex, `None` at sinks and outlets. Indexed like `GeneratedTerrain::heights`
+    pub directions: Vec<Vec<Option<FlowDirection>>>,
+    /// Number of vertices draining through every vertex, including itself. Indexed like `GeneratedTerrain::heights`
+    pub accumulation: Vec<Vec<f32>>,
+    /// Number of vertices per world unit
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl FlowData {
+    /// Routes flow over `heights`, indexed by `[x][z]`. Vertices where `outlets` is true drain out of the grid,
+    /// e.g. the sea. If `fill_depressions` is true, the sides of the grid are outlets as well
+    /// and every vertex drains to an outlet
+    #[must_use]
+    pub fn from_heights(
+        heights: &[Vec<f32>],
+        outlets: &[Vec<bool>],
+        fill_depressions: bool,
+    ) -> Self {
+        let outlet = |i: usize, j: usize| {
+            outlets
+                .get(i)
+                .and_then(|column| column.get(j))
+                .copied()
+                .unwrap_or(false)
+        };
+        let surface = if fill_depressions {
+            fill(heights, outlet)
+        } else {
+            heights
+                .iter()
+                .map(|column| column.iter().map(|&height| f64::from(height)).collect())
+                .collect()
+        };
+        let directions = surface
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                (0..column.len())
+                    .map(|j| {
+                        if outlet(i, j) {
+                            return None;
+                        }
+                        steepest_descent(&surface, i, j)
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut flow = Self {
+            directions,
+            ..default()
+        };
+        let ones: Vec<Vec<f32>> = heights
+            .iter()
+            .map(|column| vec![1.0; column.len()])
+            .collect();
+        flow.accumulation = flow.accumulate(&ones);
+        flow
+    }
+
+    /// Vertex that `i`, `j` drains into, `None` at sinks and outlets
+    #[must_use]
+    pub fn downstream(&self, i: usize, j: usize) -> Option<(usize, usize)> {
+        let [di, dj] = (*self.directions.get(i)?.get(j)?)?.offset();
+        Some((
+            i.checked_add_signed(di as isize)?,
+            j.checked_add_signed(dj as isize)?,
+        ))
+    }
+
+    /// Vertices along the flow from `i`, `j` to its sink or outlet, starting with `i`, `j`
+    #[must_use]
+    pub fn path(&self, i: usize, j: usize) -> Vec<(usize, usize)> {
+        let mut path = vec![(i, j)];
+        while let Some(next) = path.last().and_then(|&(i, j)| self.downstream(i, j)) {
+            path.push(next);
+        }
+        path
+    }
+
+    /// Sum of `weights` over every vertex draining through each vertex, including itself.
+    /// E.g. accumulating precipitation gives the discharge of rivers.
+    /// `weights` is indexed like `directions`, missing weights count as 0
+    #[must_use]
+    pub fn accumulate(&self, weights: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let mut totals: Vec<Vec<f32>> = self
+            .directions
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                (0..column.len())
+                    .map(|j| {
+                        weights
+                            .get(i)
+                            .and_then(|column| column.get(j))
+                            .copied()
+                            .unwrap_or(0.0)
+                    })
+                    .collect()
+            })
+            .collect();
+        // Number of upstream neighbours of every vertex that are not accumulated yet
+        let mut upstream: Vec<Vec<u8>> = self
+            .directions
+            .iter()
+            .map(|column| vec![0; column.len()])
+            .collect();
+        for (i, column) in self.directions.iter().enumerate() {
+            for j in 0..column.len() {
+                if let Some((di, dj)) = self.downstream(i, j) {
+                    upstream[di][dj] += 1;
+                }
+            }
+        }
+        let mut ready: Vec<(usize, usize)> = upstream
+            .iter()
+            .enumerate()
+            .flat_map(|(i, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &count)| count == 0)
+                    .map(move |(j, _)| (i, j))
+            })
+            .collect();
+        while let Some((i, j)) = ready.pop() {
+            if let Some((di, dj)) = self.downstream(i, j) {
+                totals[di][dj] += totals[i][j];
+                upstream[di][dj] -= 1;
+                if upstream[di][dj] == 0 {
+                    ready.push((di, dj));
+                }
+            }
+        }
+        totals
+    }
+
+    /// Direction at the vertex nearest to `x`, `z`, `None` at sinks, outlets and outside of the terrain
+    #[must_use]
+    pub fn direction_at(&self, x: f32, z: f32) -> Option<FlowDirection> {
+        let (i, j) = self.vertex(x, z)?;
+        *self.directions.get(i)?.get(j)?
+    }
+
+    /// Accumulation at the vertex nearest to `x`, `z`, `None` outside of the terrain
+    #[must_use]
+    pub fn accumulation_at(&self, x: f32, z: f32) -> Option<f32> {
+        let (i, j) = self.vertex(x, z)?;
+        self.accumulation.get(i)?.get(j).copied()
+    }
+
+    fn vertex(&self, x: f32, z: f32) -> Option<(usize, usize)> {
+        let resolution = self.resolution as f32;
+        let i = ((x + self.size[0] as f32 / 2.0) * resolution).round();
+        let j = ((z + self.size[1] as f32 / 2.0) * resolution).round();
+        if i < 0.0 || j < 0.0 {
+            return None;
+        }
+        Some((i as usize, j as usize))
+    }
+}
+
+/// Neighbour of `i`, `j` with the steepest drop, `None` if no neighbour is lower
+fn steepest_descent(surface: &[Vec<f64>], i: usize, j: usize) -> Option<FlowDirection> {
+    let height = surface[i][j];
+    FlowDirection::ALL
+        .into_iter()
+        .filter_map(|direction| {
+            let [di, dj] = direction.offset();
+            let neighbour = surface
+                .get(i.checked_add_signed(di as isize)?)?
+                .get(j.checked_add_signed(dj as isize)?)?;
+            let drop = (height - neighbour) / direction.length();
+            (drop > 0.0).then_some((direction, drop))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(direction, _)| direction)
+}
+
+/// Vertex in the flooding queue, ordered so the lowest is popped first
+struct Flooded {
+    height: f64,
+    vertex: (usize, usize),
+}
+
+impl PartialEq for Flooded {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Flooded {}
+
+impl PartialOrd for Flooded {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Flooded {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .height
+            .total_cmp(&self.height)
+            .then_with(|| other.vertex.cmp(&self.vertex))
+    }
+}
+
+/// Raises every depression until it spills over, with priority flood from the outlets and the sides of the grid.
+/// Filled vertices keep a slight slope towards the spill point so that no flats remain
+pub(crate) fn fill(heights: &[Vec<f32>], outlet: impl Fn(usize, usize) -> bool) -> Vec<Vec<f64>> {
+    const SLOPE: f64 = 1e-6;
+    let columns = heights.len();
+    let rows = heights.first().map_or(0, Vec::len);
+    let mut surface: Vec<Vec<f64>> = heights
+        .iter()
+        .map(|column| column.iter().map(|&height| f64::from(height)).collect())
+        .collect();
+    let mut flooded = vec![vec![false; rows]; columns];
+    let mut queue = BinaryHeap::new();
+    for i in 0..columns {
+        for j in 0..rows {
+            if i == 0 || j == 0 || i + 1 == columns || j + 1 == rows || outlet(i, j) {
+                flooded[i][j] = true;
+                queue.push(Flooded {
+                    height: surface[i][j],
+                    vertex: (i, j),
+                });
+            }
+        }
+    }
+    while let Some(Flooded { height, vertex }) = queue.pop() {
+        for direction in FlowDirection::ALL {
+            let [di, dj] = direction.offset();
+            let (Some(ni), Some(nj)) = (
+                vertex.0.checked_add_signed(di as isize),
+                vertex.1.checked_add_signed(dj as isize),
+            ) else {
+                continue;
+            };
+            if ni >= columns || nj >= rows || flooded[ni][nj] {
+                continue;
+            }
+            flooded[ni][nj] = true;
+            surface[ni][nj] = surface[ni][nj].max(height + SLOPE);
+            queue.push(Flooded {
+                height: surface[ni][nj],
+                vertex: (ni, nj),
+            });
+        }
+    }
+    surface
+}
+
+/// Plugin to compute flow
+pub struct FlowPlugin;
+
+impl Plugin for FlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_flow);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_flow(
+    mut commands: Commands,
+    query: Query<
+        (Entity, &Flow, &GeneratedTerrain, Option<&FlowData>),
+        Or<(Changed<Flow>, Changed<GeneratedTerrain>)>,
+    >,
+) {
+    for (entity, config, terrain_data, old) in &query {
+        let sea: Vec<Vec<bool>> = terrain_data
+            .base_heights
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|&height| height <= terrain_data.sea_level + f32::EPSILON)
+                    .collect()
+            })
+            .collect();
+        let new = FlowData {
+            resolution: terrain_data.resolution.max(1),
+            size: terrain_data.size,
+            ..FlowData::from_heights(&terrain_data.base_heights, &sea, config.fill_depressions)
+        };
+        if old != Some(&new) {
+            commands.entity(entity).insert(new);
+        }
+    }
+}
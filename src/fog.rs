@@ -0,0 +1,385 @@
+//! Reveal terrain through a fog of war
+//! # Example
+//! For configuration, see [`FogOfWar`](struct.FogOfWar.html) and [`FogRevealer`](struct.FogRevealer.html).
+//! Unexplored and explored but currently unseen terrain is darkened. `FogOfWar::texture` holds the mask
+//! for custom materials and minimaps, with 0 unexplored, 128 explored and 255 visible
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::fog::{FogOfWar, FogPlugin, FogRevealer};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, FogPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, walk)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), FogOfWar::default()));
+//!     commands.spawn((
+//!         SpatialBundle::default(),
+//!         FogRevealer {
+//!             radius: 0.3,
+//!             line_of_sight: true,
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//!
+//! fn walk(mut query: Query<&mut Transform, With<FogRevealer>>, time: Res<Time>) {
+//!     let angle = time.elapsed_seconds() * 0.2;
+//!     for mut transform in &mut query {
+//!         transform.translation = Vec3::new(angle.cos(), 0.0, angle.sin()) * 0.6;
+//!     }
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::{GeneratedTerrain, TerrainColors, TerrainSet};
+
+/// Component for a fog of war, added to an entity with a `Terrain` component.
+/// The explored cells serialize with it, so exploration is saved along with the other components
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct FogOfWar {
+    /// Number of cells per world unit
+    pub resolution: u32,
+    /// Brightness of explored terrain that is currently not visible, 0 to 1
+    pub explored_brightness: f32,
+    /// Brightness of unexplored terrain, 0 to 1
+    pub unexplored_brightness: f32,
+    /// Number of cells along `x` and `z`, set from the size of the terrain
+    pub size: [u32; 2],
+    /// True for every explored cell, rows along `x` ordered by `z`
+    pub explored: Vec<bool>,
+    /// True for every cell visible to a `FogRevealer` this frame, rows along `x` ordered by `z`
+    #[serde(skip)]
+    pub visible: Vec<bool>,
+    /// Texture of the mask, see the module documentation
+    #[serde(skip)]
+    pub texture: Handle<Image>,
+}
+
+impl Default for FogOfWar {
+    fn default() -> Self {
+        Self {
+            resolution: 32,
+            explored_brightness: 0.5,
+            unexplored_brightness: 0.1,
+            size: [0; 2],
+            explored: vec![],
+            visible: vec![],
+            texture: Handle::default(),
+        }
+    }
+}
+
+impl FogOfWar {
+    /// Returns true if the cell at `x`, `z` on the overlay was ever revealed, see `GeneratedTerrain::to_overlay`
+    #[must_use]
+    pub fn is_explored(&self, x: f32, z: f32, terrain_size: [u32; 2]) -> bool {
+        self.index(x, z, terrain_size)
+            .is_some_and(|index| self.explored[index])
+    }
+
+    /// Returns true if the cell at `x`, `z` on the overlay is revealed this frame
+    #[must_use]
+    pub fn is_visible(&self, x: f32, z: f32, terrain_size: [u32; 2]) -> bool {
+        self.index(x, z, terrain_size)
+            .is_some_and(|index| self.visible.get(index).copied().unwrap_or(false))
+    }
+
+    /// Reveals the cells within `radius` of `center`, in units of the overlay, see `GeneratedTerrain::to_overlay`
+    pub fn reveal_circle(&mut self, center: Vec2, radius: f32, terrain_size: [u32; 2]) {
+        self.reveal(center, radius, terrain_size, |_| true);
+    }
+
+    /// Reveals the cells within `radius` world units of `eye` that are not hidden behind the terrain,
+    /// `eye` is relative to the terrain entity
+    pub fn reveal_line_of_sight(
+        &mut self,
+        terrain_data: &GeneratedTerrain,
+        eye: Vec3,
+        radius: f32,
+    ) {
+        // The cells are on the overlay, the heights are sampled relative to the terrain entity like `eye`
+        let step = terrain_data.stretch().min_element() / self.resolution.max(1) as f32;
+        let center = terrain_data.to_overlay(eye.xz());
+        let radius = terrain_data.to_overlay_radius(radius);
+        self.reveal(center, radius, terrain_data.size, |point| {
+            let local = terrain_data.from_overlay(point);
+            let target = Vec3::new(
+                local.x,
+                terrain_data.height(local.x, local.y).unwrap_or(f32::MIN),
+                local.y,
+            );
+            let distance = eye.xz().distance(local);
+            let steps = (distance / step) as usize;
+            (1..steps).all(|sample| {
+                let along = eye.lerp(target, sample as f32 / steps as f32);
+                terrain_data
+                    .height(along.x, along.z)
+                    .is_none_or(|height| height <= along.y)
+            })
+        });
+    }
+
+    /// `color` darkened by the fog at `x`, `z`
+    pub(crate) fn darken(
+        &self,
+        color: [f32; 4],
+        x: f32,
+        z: f32,
+        terrain_size: [u32; 2],
+    ) -> [f32; 4] {
+        let Some(index) = self.index(x, z, terrain_size) else {
+            return color;
+        };
+        let brightness = if self.visible.get(index).copied().unwrap_or(false) {
+            1.0
+        } else if self.explored[index] {
+            self.explored_brightness
+        } else {
+            self.unexplored_brightness
+        };
+        [
+            color[0] * brightness,
+            color[1] * brightness,
+            color[2] * brightness,
+            color[3],
+        ]
+    }
+
+    /// Index of the cell at `x`, `z`, `None` outside of the mask
+    fn index(&self, x: f32, z: f32, terrain_size: [u32; 2]) -> Option<usize> {
+        let resolution = self.resolution.max(1) as f32;
+        let i = ((x + terrain_size[0] as f32 / 2.0) * resolution).round();
+        let j = ((z + terrain_size[1] as f32 / 2.0) * resolution).round();
+        if i < 0.0 || j < 0.0 || i >= self.size[0] as f32 || j >= self.size[1] as f32 {
+            return None;
+        }
+        let index = j as usize * self.size[0] as usize + i as usize;
+        (index < self.explored.len()).then_some(index)
+    }
+
+    /// Marks the cells within `radius` of `center` where `visible` is true as explored and visible
+    fn reveal(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        terrain_size: [u32; 2],
+        visible: impl Fn(Vec2) -> bool,
+    ) {
+        if !self.fits(terrain_size) {
+            return;
+        }
+        let resolution = self.resolution.max(1) as f32;
+        let half = Vec2::new(terrain_size[0] as f32, terrain_size[1] as f32) / 2.0;
+        let [columns, rows] = self.size.map(|side| side as usize);
+        let min = ((center - radius + half) * resolution)
+            .floor()
+            .max(Vec2::ZERO);
+        let max = ((center + radius + half) * resolution).ceil();
+        if max.x < 0.0 || max.y < 0.0 || min.x >= columns as f32 || min.y >= rows as f32 {
+            return;
+        }
+        let [max_i, max_j] = [
+            (max.x as usize).min(columns - 1),
+            (max.y as usize).min(rows - 1),
+        ];
+        for j in min.y as usize..=max_j {
+            for i in min.x as usize..=max_i {
+                let point = Vec2::new(i as f32, j as f32) / resolution - half;
+                if point.distance(center) > radius || !visible(point) {
+                    continue;
+                }
+                self.explored[j * columns + i] = true;
+                self.visible[j * columns + i] = true;
+            }
+        }
+    }
+
+    /// Returns true if the mask covers a terrain of `terrain_size`
+    fn fits(&self, terrain_size: [u32; 2]) -> bool {
+        let size = terrain_size.map(|side| side * self.resolution.max(1) + 1);
+        let cells = (size[0] * size[1]) as usize;
+        self.size == size && self.explored.len() == cells && self.visible.len() == cells
+    }
+
+    /// Resizes the mask to cover a terrain of `terrain_size`, keeping the explored cells if the size is unchanged
+    fn fit(&mut self, terrain_size: [u32; 2]) {
+        let size = terrain_size.map(|side| side * self.resolution.max(1) + 1);
+        let cells = (size[0] * size[1]) as usize;
+        if self.size != size || self.explored.len() != cells {
+            self.size = size;
+            self.explored = vec![false; cells];
+        }
+        self.visible = vec![false; cells];
+    }
+
+    /// Value of the cell at `index` in the texture
+    fn texel(&self, index: usize) -> u8 {
+        match (self.explored[index], self.visible[index]) {
+            (_, true) => 255,
+            (true, false) => 128,
+            (false, false) => 0,
+        }
+    }
+
+    fn to_image(&self) -> Image {
+        Image::new(
+            Extent3d {
+                width: self.size[0],
+                height: self.size[1],
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            (0..self.visible.len())
+                .map(|index| self.texel(index))
+                .collect(),
+            TextureFormat::R8Unorm,
+        )
+    }
+}
+
+/// Component for entities revealing the fog of war of every terrain around them
+#[derive(Component, Clone, Copy, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct FogRevealer {
+    /// Radius of the revealed area in world units
+    pub radius: f32,
+    /// If true, terrain hidden behind hills is not revealed
+    pub line_of_sight: bool,
+    /// Height of the eye above the terrain in world units, used for the line of sight
+    pub eye_height: f32,
+}
+
+impl Default for FogRevealer {
+    fn default() -> Self {
+        Self {
+            radius: 0.2,
+            line_of_sight: false,
+            eye_height: 0.05,
+        }
+    }
+}
+
+/// Event to reveal the fog of war of every terrain at a position for one frame, e.g. a flare
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub struct FogReveal {
+    /// Center of the revealed area in world space
+    pub position: Vec3,
+    /// How the area is revealed
+    pub revealer: FogRevealer,
+}
+
+/// Plugin to update fogs of war
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FogReveal>()
+            .add_systems(Update, update_fog.before(TerrainSet::Color));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn update_fog(
+    mut events: EventReader<FogReveal>,
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut terrains: Query<(&mut FogOfWar, &GeneratedTerrain, &GlobalTransform)>,
+    revealers: Query<(&FogRevealer, &GlobalTransform)>,
+) {
+    let reveals: Vec<(Vec3, FogRevealer)> = events
+        .read()
+        .map(|event| (event.position, event.revealer))
+        .chain(
+            revealers
+                .iter()
+                .map(|(revealer, transform)| (transform.translation(), *revealer)),
+        )
+        .collect();
+    for (mut changed_fog, terrain_data, transform) in &mut terrains {
+        // The visible cells are cleared and revealed again every frame, the fog only changes if they differ
+        let fog = changed_fog.bypass_change_detection();
+        let old = fog.visible.clone();
+        let old_size = fog.size;
+        fog.fit(terrain_data.size);
+        let to_local = transform.affine().inverse();
+        for (position, revealer) in &reveals {
+            let local = to_local.transform_point3(*position);
+            if revealer.line_of_sight {
+                let ground = terrain_data.height(local.x, local.z).unwrap_or(local.y);
+                let eye = Vec3::new(local.x, ground + revealer.eye_height, local.z);
+                fog.reveal_line_of_sight(terrain_data, eye, revealer.radius);
+            } else {
+                fog.reveal_circle(
+                    terrain_data.to_overlay(local.xz()),
+                    terrain_data.to_overlay_radius(revealer.radius),
+                    terrain_data.size,
+                );
+            }
+        }
+        // Explored cells only change where cells are visible, so comparing the visible cells is enough.
+        // Without the render assets the fog has no texture
+        let resized = fog.size != old_size || fog.visible.len() != old.len();
+        let changed: Vec<usize> = if resized {
+            Vec::new()
+        } else {
+            (0..old.len())
+                .filter(|&index| fog.visible[index] != old[index])
+                .collect()
+        };
+        if !resized
+            && changed.is_empty()
+            && images
+                .as_ref()
+                .is_none_or(|images| images.contains(&fog.texture))
+        {
+            continue;
+        }
+        changed_fog.set_changed();
+        let Some(images) = images.as_deref_mut() else {
+            continue;
+        };
+        // Only the changed cells are written to an existing texture of the same size
+        let fog = changed_fog.bypass_change_detection();
+        match images.get_mut(&fog.texture) {
+            Some(texture) if !resized && texture.data.len() == fog.visible.len() => {
+                for index in changed {
+                    texture.data[index] = fog.texel(index);
+                }
+            }
+            Some(texture) => *texture = fog.to_image(),
+            None => fog.texture = images.add(fog.to_image()),
+        }
+    }
+}
+
+/// Darkens recolored terrains by their fog
+pub(crate) fn darken_fog(mut query: Query<(&mut TerrainColors, &GeneratedTerrain, &FogOfWar)>) {
+    for (mut colors, terrain_data, fog) in &mut query {
+        if colors.is_recoloring() {
+            colors.blend(terrain_data, |_, point, color| {
+                fog.darken(color, point.x, point.y, terrain_data.size)
+            });
+        }
+    }
+}
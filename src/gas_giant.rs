@@ -0,0 +1,296 @@
+//! Generate gas giants
+//! # Example
+//! For configuration, see [`GasGiant`](struct.GasGiant.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::gas_giant::{GasGiantBundle, GasGiantPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(GasGiantPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(GasGiantBundle::default());
+//! }
+//! ```
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{fail, GenerationError, GenerationFailure},
+    noise::{build_gradient, get_noise_at_point_3d, Function, Gradient, Method, Region},
+    util::{uv_sphere, Rng},
+};
+
+/// Storm spot configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Storms {
+    /// Seed used to place the storms
+    pub seed: u32,
+    /// Number of storms
+    pub count: usize,
+    /// Minimum and maximum storm radius in radians
+    pub radius: [f32; 2],
+    /// Rotation at the center of the storm in radians
+    pub swirl: f32,
+    /// Color of the storms, alpha determines how strongly it is blended with the bands
+    pub color: [u8; 4],
+}
+
+impl Default for Storms {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            count: 3,
+            radius: [0.05, 0.2],
+            swirl: 4.0,
+            color: [200, 90, 60, 160],
+        }
+    }
+}
+
+/// Component for gas giant configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct GasGiant {
+    /// Seed of the noise
+    pub seed: u32,
+    /// Scale of the noise
+    pub scale: f64,
+    /// Offset of the noise
+    pub offset: [f64; 3],
+    /// Method used to generate noise
+    pub method: Method,
+    /// Function used to generate noise
+    pub function: Function,
+    /// Size of the equirectangular texture, width should be twice the height
+    pub size: [u32; 2],
+    /// Number of bands from pole to pole
+    pub bands: f32,
+    /// Horizontal stretch of the noise, higher values result in longer streaks
+    pub stretch: f32,
+    /// Strength of the domain warping applied to the bands
+    pub warp: f32,
+    /// Vector of regions, mapped from band value to color
+    pub regions: Vec<Region>,
+    /// Gradient determines how the band values are mapped to colors
+    pub gradient: Gradient,
+    /// Storm spots swirling the bands
+    pub storms: Option<Storms>,
+    /// If true, the texture is applied to a sphere mesh written to `PbrBundle`
+    pub sphere: bool,
+    /// Generated equirectangular texture
+    #[serde(skip)]
+    pub image: Handle<Image>,
+}
+
+impl Default for GasGiant {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: 50.0,
+            offset: [0.0; 3],
+            method: Method::Perlin,
+            function: Function::default(),
+            size: [1024, 512],
+            bands: 12.0,
+            stretch: 8.0,
+            warp: 0.6,
+            regions: vec![
+                Region {
+                    label: "Dark band".to_string(),
+                    color: [150, 100, 60, 255],
+                    position: 0.0,
+                },
+                Region {
+                    label: "Light band".to_string(),
+                    color: [235, 215, 180, 255],
+                    position: 100.0,
+                },
+            ],
+            gradient: Gradient::default(),
+            storms: Some(Storms::default()),
+            sphere: true,
+            image: Handle::default(),
+        }
+    }
+}
+
+/// Render `GasGiant` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct GasGiantBundle {
+    /// Gas giant configuration
+    pub gas_giant: GasGiant,
+    /// Generated mesh and texture are written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate gas giants
+pub struct GasGiantPlugin;
+
+impl Plugin for GasGiantPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GenerationError>()
+            .add_systems(Update, generate_gas_giant);
+    }
+}
+
+struct Storm {
+    center: Vec3,
+    radius: f32,
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_gas_giant(
+    mut errors: EventWriter<GenerationError>,
+    images: Option<ResMut<Assets<Image>>>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut GasGiant,
+            Option<&mut Handle<Mesh>>,
+            Option<&Handle<StandardMaterial>>,
+        ),
+        Changed<GasGiant>,
+    >,
+) {
+    // Without the render assets there is nothing to generate
+    let (Some(mut images), Some(mut meshes), Some(mut materials)) = (images, meshes, materials)
+    else {
+        return;
+    };
+    for (entity, mut gas_giant, mesh_handle, material) in &mut query {
+        let [width, height] = gas_giant.size;
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let grad = match build_gradient(&gas_giant.regions, &gas_giant.gradient) {
+            Ok(grad) => grad,
+            Err(error) => {
+                fail(
+                    &mut errors,
+                    entity,
+                    GenerationFailure::Gradient(error.to_string()),
+                );
+                continue;
+            }
+        };
+        let storms: Vec<Storm> = gas_giant.storms.as_ref().map_or_else(Vec::new, |storms| {
+            let mut rng = Rng::new(u64::from(storms.seed));
+            (0..storms.count)
+                .map(|_| {
+                    // Storms stay away from the poles where bands converge
+                    let latitude = (rng.next_f64() as f32 - 0.5) * PI * 0.7;
+                    let longitude = rng.next_f64() as f32 * TAU;
+                    let t = rng.next_f64() as f32;
+                    Storm {
+                        center: spherical_direction(latitude, longitude),
+                        radius: (storms.radius[1] - storms.radius[0]).mul_add(t, storms.radius[0]),
+                    }
+                })
+                .collect()
+        });
+
+        let mut data: Vec<u8> = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let latitude = ((y as f32 + 0.5) / height as f32).mul_add(-PI, FRAC_PI_2);
+            for x in 0..width {
+                let longitude = (x as f32 + 0.5) / width as f32 * TAU;
+                let mut direction = spherical_direction(latitude, longitude);
+
+                // Storms rotate the sampling direction around their center
+                let mut storm_strength = 0.0;
+                if let Some(config) = &gas_giant.storms {
+                    for storm in &storms {
+                        let distance = direction.angle_between(storm.center);
+                        if distance < storm.radius {
+                            let falloff = (1.0 - distance / storm.radius).powi(2);
+                            direction = Quat::from_axis_angle(storm.center, config.swirl * falloff)
+                                * direction;
+                            storm_strength = f32::max(storm_strength, falloff);
+                        }
+                    }
+                }
+
+                let point = [
+                    f64::from(direction.x),
+                    f64::from(direction.y * gas_giant.stretch),
+                    f64::from(direction.z),
+                ];
+                let warp = get_noise_at_point_3d(
+                    point,
+                    gas_giant.seed,
+                    gas_giant.scale / 100.0,
+                    gas_giant.offset,
+                    &gas_giant.method,
+                    &gas_giant.function,
+                ) as f32;
+                let band = direction.y.asin() / PI + 0.5 + warp * gas_giant.warp / gas_giant.bands;
+                let value = (band * gas_giant.bands * PI).sin().mul_add(0.5, 0.5);
+                let mut color = grad.at(f64::from(value) * 100.0);
+                if let Some(config) = &gas_giant.storms {
+                    let [r, g, b, a] = config.color;
+                    color = color.interpolate_rgb(
+                        &colorgrad::Color::from_rgba8(r, g, b, 255),
+                        f64::from(storm_strength * f32::from(a) / 255.0),
+                    );
+                }
+                data.extend(color.to_rgba8());
+            }
+        }
+
+        let image = images.add(Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        ));
+        if gas_giant.sphere {
+            if let Some(material) = material.and_then(|material| materials.get_mut(material)) {
+                *material = StandardMaterial {
+                    base_color_texture: Some(image.clone()),
+                    perceptual_roughness: 0.8,
+                    ..default()
+                };
+            }
+            if let Some(mut mesh_handle) = mesh_handle {
+                // Poles on `y`, where the bands of the texture put them
+                *mesh_handle = meshes.add(uv_sphere(1.0, 64, 32));
+            }
+        }
+        gas_giant.bypass_change_detection().image = image;
+    }
+}
+
+fn spherical_direction(latitude: f32, longitude: f32) -> Vec3 {
+    Vec3::new(
+        latitude.cos() * longitude.cos(),
+        latitude.sin(),
+        latitude.cos() * longitude.sin(),
+    )
+}
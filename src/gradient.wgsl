@@ -0,0 +1,13 @@
+#define_import_path bevy_generative::gradient
+
+// Color of a noise `value` from 0 to 100 in a `GeneratedTerrain::gradient_lut`, equal to the vertex color
+fn gradient_color(lut: texture_1d<f32>, lut_sampler: sampler, value: f32) -> vec4<f32> {
+    return textureSample(lut, lut_sampler, clamp(value / 100.0, 0.0, 1.0));
+}
+
+// Noise value of a height above the sea floor of a terrain with `Terrain::height_scale`, the inverse of `scaled_height`.
+// Heights at the sea floor give `sea_percent`
+fn noise_value(height: f32, sea_percent: f32, height_exponent: f32, height_scale: f32) -> f32 {
+    let height_value = clamp(height / max(height_scale, 0.0001), 0.0, 1.0);
+    return sea_percent + (100.0 - sea_percent) * pow(height_value, 1.0 / max(height_exponent, 0.0001));
+}
@@ -0,0 +1,41 @@
+//! Generate only the data of generators, without meshes, materials or textures
+//! # Example
+//! Entities with a [`DataOnly`](struct.DataOnly.html) component get their data components, e.g. `GeneratedTerrain`,
+//! `RiverNetwork` or `DungeonLayout`, but no meshes, so servers and headless simulations can share the world of
+//! their clients. Without the render plugins every generator is data only, the plugins of terrain, rivers, lakes,
+//! bridges, dungeons, caves, mazes, missions and wave function collapse run with `MinimalPlugins`, and so do the
+//! splat, territory, fog, distance and suitability overlays, which then skip their textures. Planets, gas giants,
+//! maps, rocks, plants and volumes only make meshes and textures, so they generate nothing without the render assets
+//! ```no_run
+//! use bevy::prelude::*;
+//! use bevy_generative::headless::DataOnly;
+//! use bevy_generative::terrain::{GeneratedTerrain, Terrain, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_height)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn((Terrain::default(), TransformBundle::default(), DataOnly));
+//! }
+//!
+//! fn print_height(query: Query<&GeneratedTerrain, Added<GeneratedTerrain>>) {
+//!     for terrain_data in &query {
+//!         println!("Height at the center: {:?}", terrain_data.height(0.0, 0.0));
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+
+/// Marker for a generator entity that gets only its data components
+///
+/// Added to an entity with e.g. a `Terrain` or `Dungeon` component, its meshes are not built
+/// and the surfaces of its rivers, lakes and water are not spawned
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug, Reflect)]
+#[reflect(Component)]
+pub struct DataOnly;
@@ -0,0 +1,245 @@
+//! Recolor terrain and maps by a single data channel for debugging
+//! # Example
+//! For configuration, see [`DebugView`](struct.DebugView.html).
+//! Add it next to a `Terrain` or `Map` and switch `DebugView::channel` while tuning,
+//! channels without data on the entity, e.g. temperature without a `Climate`, keep the normal colors
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::climate::{Climate, ClimatePlugin};
+//! use bevy_generative::heatmap::{DebugChannel, DebugView};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, ClimatePlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Climate::default(),
+//!         DebugView {
+//!             channel: Some(DebugChannel::Temperature),
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "map")]
+use crate::noise::NoiseMap;
+#[cfg(feature = "terrain")]
+use crate::{
+    biome::BiomeMap,
+    climate::ClimateData,
+    flow::FlowData,
+    terrain::{GeneratedTerrain, TerrainColors},
+};
+
+/// Data a `DebugView` shows
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DebugChannel {
+    /// Height of terrains, noise value of maps
+    Height,
+    /// Steepness in degrees
+    Slope,
+    /// Precipitation of `ClimateData`
+    Moisture,
+    /// Temperature of `ClimateData`
+    Temperature,
+    /// Biome of `BiomeMap`, in the colors of the Whittaker diagram
+    Biome,
+    /// Accumulation of `FlowData` on a logarithmic scale
+    Flow,
+    /// Height added or removed after generation by roads, deformations and snow,
+    /// red where the terrain was raised and blue where it was lowered
+    Delta,
+}
+
+/// Component recoloring a `Terrain` or `Map` on the same entity by a data channel
+#[derive(Component, Clone, Copy, Default, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct DebugView {
+    /// Channel shown, `None` for the normal colors
+    pub channel: Option<DebugChannel>,
+    /// Values mapped to the ends of the palette, the range of the data if `None`.
+    /// Deltas are mapped from `-max` to `max` of the larger end
+    pub range: Option<[f32; 2]>,
+}
+
+/// Data of a terrain a `DebugView` can show, indexed like `GeneratedTerrain::heights`
+#[cfg(feature = "terrain")]
+pub(crate) struct TerrainChannels<'a> {
+    pub heights: &'a [Vec<f32>],
+    pub base_heights: &'a [Vec<f32>],
+    /// World units between neighbouring vertices along `x` and `z`
+    pub spacing: Vec2,
+    pub climate: Option<&'a ClimateData>,
+    pub biomes: Option<&'a BiomeMap>,
+    pub flow: Option<&'a FlowData>,
+}
+
+impl DebugView {
+    /// Vertex colors of the channel over a terrain, one per vertex in the order of `GeneratedTerrain::heights`.
+    /// `None` if no channel is selected or the terrain has no data for it
+    #[cfg(feature = "terrain")]
+    pub(crate) fn terrain_colors(&self, channels: &TerrainChannels) -> Option<Vec<[f32; 4]>> {
+        let channel = self.channel?;
+        let same_size = |grid: &[Vec<f32>]| {
+            grid.len() == channels.heights.len()
+                && grid.first().map(Vec::len) == channels.heights.first().map(Vec::len)
+        };
+        let values = match channel {
+            DebugChannel::Height => channels.heights.to_vec(),
+            DebugChannel::Slope => slopes(channels.heights, channels.spacing),
+            DebugChannel::Moisture => channels.climate?.precipitation.clone(),
+            DebugChannel::Temperature => channels.climate?.temperature.clone(),
+            DebugChannel::Biome => {
+                let biomes = &channels.biomes?.cells;
+                let colors: Vec<[f32; 4]> = biomes
+                    .iter()
+                    .flatten()
+                    .map(|biome| biome.color().map(|channel| f32::from(channel) / 255.0))
+                    .collect();
+                let expected: usize = channels.heights.iter().map(Vec::len).sum();
+                return (colors.len() == expected).then_some(colors);
+            }
+            DebugChannel::Flow => channels
+                .flow?
+                .accumulation
+                .iter()
+                .map(|column| column.iter().map(|value| value.max(1.0).ln()).collect())
+                .collect(),
+            DebugChannel::Delta => channels
+                .heights
+                .iter()
+                .zip(channels.base_heights)
+                .map(|(column, base)| column.iter().zip(base).map(|(a, b)| a - b).collect())
+                .collect(),
+        };
+        // Data of the previous generation is stale while the terrain changes size
+        same_size(&values).then(|| self.heat(channel, &values))
+    }
+
+    /// Pixel colors of the channel over a map from its noise values, indexed by `[x][y]`.
+    /// Only height and slope are known for maps, `None` for other channels
+    #[cfg(feature = "map")]
+    pub(crate) fn map_colors(&self, noise_values: &NoiseMap) -> Option<Vec<[f32; 4]>> {
+        let channel = self.channel?;
+        let values: Vec<Vec<f32>> = noise_values
+            .columns()
+            .map(|column| column.map(|value| value as f32).collect())
+            .collect();
+        match channel {
+            DebugChannel::Height => Some(self.heat(channel, &values)),
+            DebugChannel::Slope => Some(self.heat(channel, &slopes(&values, Vec2::ONE))),
+            _ => None,
+        }
+    }
+
+    /// Colors of `values` in column order, on a diverging palette for deltas and a heat palette otherwise
+    fn heat(&self, channel: DebugChannel, values: &[Vec<f32>]) -> Vec<[f32; 4]> {
+        let [mut min, mut max] = self.range.unwrap_or_else(|| {
+            values
+                .iter()
+                .flatten()
+                .filter(|value| value.is_finite())
+                .fold([f32::MAX, f32::MIN], |[min, max], &value| {
+                    [min.min(value), max.max(value)]
+                })
+        });
+        let palette = if channel == DebugChannel::Delta {
+            let extent = min.abs().max(max.abs());
+            (min, max) = (-extent, extent);
+            colorgrad::rd_bu()
+        } else {
+            colorgrad::turbo()
+        };
+        let span = (max - min).max(f32::EPSILON);
+        values
+            .iter()
+            .flatten()
+            .map(|&value| {
+                let t = ((value - min) / span).clamp(0.0, 1.0);
+                // The diverging palette runs from red to blue, reversed so raised terrain is red
+                let t = if channel == DebugChannel::Delta {
+                    1.0 - t
+                } else {
+                    t
+                };
+                let color = palette.at(f64::from(t));
+                [color.r as f32, color.g as f32, color.b as f32, 1.0]
+            })
+            .collect()
+    }
+}
+
+/// Replaces the colors of recolored terrains by their debug view
+#[cfg(feature = "terrain")]
+#[allow(clippy::type_complexity)]
+pub(crate) fn show_debug_views(
+    mut query: Query<(
+        &mut TerrainColors,
+        &GeneratedTerrain,
+        &DebugView,
+        Option<&ClimateData>,
+        Option<&BiomeMap>,
+        Option<&FlowData>,
+    )>,
+) {
+    for (mut colors, terrain_data, debug_view, climate, biomes, flow) in &mut query {
+        if !colors.is_recoloring() {
+            continue;
+        }
+        let channels = TerrainChannels {
+            heights: &terrain_data.heights,
+            base_heights: &terrain_data.base_heights,
+            spacing: terrain_data.stretch() / terrain_data.resolution.max(1) as f32,
+            climate,
+            biomes,
+            flow,
+        };
+        if let Some(debug_colors) = debug_view.terrain_colors(&channels) {
+            if debug_colors.len() == colors.colors.len() {
+                colors.colors = debug_colors;
+            }
+        }
+    }
+}
+
+/// Slope of every vertex of `heights` in degrees, from central differences
+fn slopes(heights: &[Vec<f32>], spacing: Vec2) -> Vec<Vec<f32>> {
+    let columns = heights.len();
+    (0..columns)
+        .map(|i| {
+            let rows = heights[i].len();
+            (0..rows)
+                .map(|j| {
+                    let height = |i: usize, j: usize| heights[i][j];
+                    let (left, right) = (i.saturating_sub(1), (i + 1).min(columns - 1));
+                    let (back, front) = (j.saturating_sub(1), (j + 1).min(rows - 1));
+                    let dx = (height(right, j) - height(left, j))
+                        / ((right - left).max(1) as f32 * spacing.x);
+                    let dz = (height(i, front) - height(i, back))
+                        / ((front - back).max(1) as f32 * spacing.y);
+                    dx.hypot(dz).atan().to_degrees()
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,87 @@
+//! Cut holes into terrain meshes, e.g. for cave entrances, basements and tunnels
+//! # Example
+//! For configuration, see [`TerrainHoles`](struct.TerrainHoles.html).
+//! Triangles over the holes are left out of the mesh, `GeneratedTerrain::holes` marks the cut cells
+//! for colliders and raycasts go through them
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::hole::TerrainHoles;
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         TerrainHoles {
+//!             polygons: vec![vec![
+//!                 Vec2::new(-0.1, -0.1),
+//!                 Vec2::new(0.1, -0.1),
+//!                 Vec2::new(0.0, 0.1),
+//!             ]],
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::deform::polygon_contains;
+
+/// Component for holes, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Default, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct TerrainHoles {
+    /// Cells cut out of the terrain, by the index of the vertex at their lower corner as in `GeneratedTerrain::heights`
+    pub cells: Vec<UVec2>,
+    /// Polygons as `x`, `z` relative to the terrain entity, cells with their center inside are cut out
+    pub polygons: Vec<Vec<Vec2>>,
+}
+
+impl TerrainHoles {
+    /// Cut cells of a grid of `columns` by `rows` cells, `center` is the center of a cell relative to the terrain
+    pub(crate) fn mask(
+        &self,
+        columns: usize,
+        rows: usize,
+        center: impl Fn(usize, usize) -> Vec2,
+    ) -> Vec<Vec<bool>> {
+        let mut mask: Vec<Vec<bool>> = (0..columns)
+            .map(|i| {
+                (0..rows)
+                    .map(|j| {
+                        let center = center(i, j);
+                        self.polygons
+                            .iter()
+                            .any(|polygon| polygon_contains(polygon, center))
+                    })
+                    .collect()
+            })
+            .collect();
+        for cell in &self.cells {
+            if let Some(cut) = mask
+                .get_mut(cell.x as usize)
+                .and_then(|column| column.get_mut(cell.y as usize))
+            {
+                *cut = true;
+            }
+        }
+        mask
+    }
+}
@@ -0,0 +1,183 @@
+//! Tweak generator configs at runtime in an egui window
+//! # Example
+//! Every terrain, map and planet of the enabled features gets a section in the window, named by its `Name` if it has one.
+//! A config is only marked changed when one of its values is edited, so it regenerates once per edit
+//! ```no_run
+//! use bevy::prelude::*;
+//! use bevy_generative::inspector::InspectorPlugin;
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, InspectorPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Name::new("Island")));
+//! }
+//! ```
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, CollapsingHeader, DragValue, Slider, Ui},
+    EguiContexts, EguiPlugin,
+};
+
+#[cfg(feature = "map")]
+use crate::map::Map;
+use crate::noise::Function;
+#[cfg(any(feature = "terrain", feature = "map"))]
+use crate::noise::Noise;
+#[cfg(feature = "planet")]
+use crate::planet::Planet;
+#[cfg(feature = "terrain")]
+use crate::terrain::Terrain;
+
+/// Plugin to show the generator inspector window, adds the `EguiPlugin` if it is missing
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.add_systems(Update, inspect);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn inspect(
+    mut contexts: EguiContexts,
+    #[cfg(feature = "terrain")] mut terrains: Query<(Entity, Option<&Name>, &mut Terrain)>,
+    #[cfg(feature = "map")] mut maps: Query<(Entity, Option<&Name>, &mut Map)>,
+    #[cfg(feature = "planet")] mut planets: Query<(Entity, Option<&Name>, &mut Planet)>,
+) {
+    egui::Window::new("Generators").show(contexts.ctx_mut(), |ui| {
+        #[cfg(feature = "terrain")]
+        for (entity, name, mut terrain) in &mut terrains {
+            // Values are edited in place, the config only changes if egui reports an edit
+            if section(ui, entity, name, "Terrain", |ui| {
+                terrain_ui(ui, terrain.bypass_change_detection())
+            }) {
+                terrain.set_changed();
+            }
+        }
+        #[cfg(feature = "map")]
+        for (entity, name, mut map) in &mut maps {
+            if section(ui, entity, name, "Map", |ui| {
+                noise_ui(ui, &mut map.bypass_change_detection().noise)
+            }) {
+                map.set_changed();
+            }
+        }
+        #[cfg(feature = "planet")]
+        for (entity, name, mut planet) in &mut planets {
+            if section(ui, entity, name, "Planet", |ui| {
+                planet_ui(ui, planet.bypass_change_detection())
+            }) {
+                planet.set_changed();
+            }
+        }
+    });
+}
+
+/// Collapsible section of one generator, true if `add_contents` edited a value
+fn section(
+    ui: &mut Ui,
+    entity: Entity,
+    name: Option<&Name>,
+    kind: &str,
+    add_contents: impl FnOnce(&mut Ui) -> bool,
+) -> bool {
+    let label = name.map_or_else(|| format!("{kind} {entity:?}"), ToString::to_string);
+    CollapsingHeader::new(label)
+        .id_source(entity)
+        .show(ui, add_contents)
+        .body_returned
+        .unwrap_or(false)
+}
+
+#[cfg(feature = "terrain")]
+fn terrain_ui(ui: &mut Ui, terrain: &mut Terrain) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Size");
+        for side in &mut terrain.size {
+            changed |= ui.add(DragValue::new(side).clamp_range(1..=64)).changed();
+        }
+    });
+    changed |= ui
+        .add(Slider::new(&mut terrain.resolution, 1..=100).text("Resolution"))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut terrain.height_exponent, 0.1..=5.0).text("Height exponent"))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut terrain.sea_percent, 0.0..=100.0).text("Sea percent"))
+        .changed();
+    changed |= ui.checkbox(&mut terrain.wireframe, "Wireframe").changed();
+    changed |= ui
+        .checkbox(&mut terrain.flat_shading, "Flat shading")
+        .changed();
+    changed | noise_ui(ui, &mut terrain.noise)
+}
+
+#[cfg(any(feature = "terrain", feature = "map"))]
+fn noise_ui(ui: &mut Ui, noise: &mut Noise) -> bool {
+    let mut changed = ui
+        .add(DragValue::new(&mut noise.seed).prefix("Seed "))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut noise.scale, 1.0..=200.0).text("Scale"))
+        .changed();
+    ui.horizontal(|ui| {
+        ui.label("Offset");
+        for offset in &mut noise.offset {
+            changed |= ui.add(DragValue::new(offset).speed(0.1)).changed();
+        }
+    });
+    changed | function_ui(ui, &mut noise.function)
+}
+
+#[cfg(feature = "planet")]
+fn planet_ui(ui: &mut Ui, planet: &mut Planet) -> bool {
+    let mut changed = ui
+        .add(DragValue::new(&mut planet.seed).prefix("Seed "))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut planet.scale, 1.0..=100.0).text("Scale"))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut planet.resolution, 1..=256).text("Resolution"))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut planet.height_exponent, 0.1..=5.0).text("Height exponent"))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut planet.sea_percent, 0.0..=100.0).text("Sea percent"))
+        .changed();
+    changed |= ui.checkbox(&mut planet.ocean, "Ocean").changed();
+    changed | function_ui(ui, &mut planet.function)
+}
+
+fn function_ui(ui: &mut Ui, function: &mut Function) -> bool {
+    let max = noise::Fbm::<noise::Perlin>::MAX_OCTAVES;
+    let mut changed = ui
+        .add(Slider::new(&mut function.octaves, 1..=max).text("Octaves"))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut function.frequency, 0.1..=10.0).text("Frequency"))
+        .changed();
+    changed |= ui
+        .add(Slider::new(&mut function.lacunarity, 0.1..=10.0).text("Lacunarity"))
+        .changed();
+    changed
+        | ui.add(Slider::new(&mut function.persistence, 0.0..=1.0).text("Persistence"))
+            .changed()
+}
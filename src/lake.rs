@@ -0,0 +1,293 @@
+//! Fill closed depressions of terrain with lakes
+//! # Example
+//! For configuration, see [`Lakes`](struct.Lakes.html).
+//! Depressions that do not drain to the sea or the side of the terrain are filled up to the level where they spill over.
+//! The lakes are inserted as a `LakeMap` and their surfaces are spawned as a `LakeSurface` child of the terrain
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::lake::{LakePlugin, Lakes};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, LakePlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Lakes::default()));
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{flow::fill, headless::DataOnly, terrain::GeneratedTerrain};
+
+/// Component for lake configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Lakes {
+    /// Depth in world units a vertex must be below the spill level to be lake water
+    pub min_depth: f32,
+    /// Lakes with fewer vertices are dropped
+    pub min_vertices: u32,
+    /// Index into `Noise::regions` given to lake vertices in `GeneratedTerrain::regions`,
+    /// `None` keeps the regions of the noise
+    pub region: Option<usize>,
+    /// Color of the surface
+    pub color: [u8; 4],
+    /// Roughness of the surface
+    pub roughness: f32,
+}
+
+impl Default for Lakes {
+    fn default() -> Self {
+        Self {
+            min_depth: 0.002,
+            min_vertices: 4,
+            region: Some(0),
+            color: [40, 100, 140, 210],
+            roughness: 0.1,
+        }
+    }
+}
+
+/// Lake filling a closed depression
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lake {
+    /// Height of the surface in world units, the level where the depression spills over
+    pub level: f32,
+    /// Depth of the deepest vertex in world units
+    pub depth: f32,
+    /// Number of vertices under water
+    pub vertices: usize,
+}
+
+/// Lakes of a terrain, inserted on the entity with the `Lakes` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct LakeMap {
+    /// Filled depressions
+    pub lakes: Vec<Lake>,
+    /// Index into `lakes` of every vertex, `None` on dry land and in the sea. Indexed like `GeneratedTerrain::heights`
+    pub cells: Vec<Vec<Option<usize>>>,
+}
+
+impl LakeMap {
+    /// Fills the depressions of `heights`, indexed by `[x][z]`, that do not drain below `sea_level` or off the grid
+    #[must_use]
+    pub fn from_heights(heights: &[Vec<f32>], sea_level: f32, lakes: &Lakes) -> Self {
+        let columns = heights.len();
+        let rows = heights.first().map_or(0, Vec::len);
+        let surface = fill(heights, |i, j| heights[i][j] <= sea_level + f32::EPSILON);
+        let flooded = |i: usize, j: usize| {
+            surface[i][j] - f64::from(heights[i][j]) > f64::from(lakes.min_depth)
+        };
+
+        let mut map = Self {
+            lakes: Vec::new(),
+            cells: vec![vec![None; rows]; columns],
+        };
+        let mut visited = vec![vec![false; rows]; columns];
+        let mut stack = Vec::new();
+        for i in 0..columns {
+            for j in 0..rows {
+                if visited[i][j] || !flooded(i, j) {
+                    continue;
+                }
+                // Flood the depression through the vertices sharing a side
+                visited[i][j] = true;
+                stack.push((i, j));
+                let mut vertices = Vec::new();
+                while let Some((i, j)) = stack.pop() {
+                    vertices.push((i, j));
+                    let neighbours = [
+                        (i + 1, j),
+                        (i, j + 1),
+                        (i.wrapping_sub(1), j),
+                        (i, j.wrapping_sub(1)),
+                    ];
+                    for (ni, nj) in neighbours {
+                        if ni < columns && nj < rows && !visited[ni][nj] && flooded(ni, nj) {
+                            visited[ni][nj] = true;
+                            stack.push((ni, nj));
+                        }
+                    }
+                }
+                if vertices.len() < lakes.min_vertices as usize {
+                    continue;
+                }
+                let level = vertices
+                    .iter()
+                    .map(|&(i, j)| surface[i][j])
+                    .fold(f64::MIN, f64::max) as f32;
+                let depth = vertices
+                    .iter()
+                    .map(|&(i, j)| level - heights[i][j])
+                    .fold(0.0, f32::max);
+                for &(i, j) in &vertices {
+                    map.cells[i][j] = Some(map.lakes.len());
+                }
+                map.lakes.push(Lake {
+                    level,
+                    depth,
+                    vertices: vertices.len(),
+                });
+            }
+        }
+        map
+    }
+
+    /// Lake at vertex `i`, `j`, `None` on dry land, in the sea and outside of the grid
+    #[must_use]
+    pub fn lake(&self, i: usize, j: usize) -> Option<&Lake> {
+        self.lakes.get((*self.cells.get(i)?.get(j)?)?)
+    }
+}
+
+/// Marker of the lake surface child of a terrain with a `Lakes` component
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct LakeSurface;
+
+/// Plugin to spawn lake surfaces
+pub struct LakePlugin;
+
+impl Plugin for LakePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (generate_lake_surfaces, remove_lakes));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_lake_surfaces(
+    mut commands: Commands,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    query: Query<
+        (
+            Entity,
+            &Lakes,
+            &LakeMap,
+            &GeneratedTerrain,
+            Option<&Children>,
+        ),
+        (
+            Or<(Changed<Lakes>, Changed<LakeMap>, Changed<GeneratedTerrain>)>,
+            Without<DataOnly>,
+        ),
+    >,
+    mut surfaces: Query<(&mut Handle<Mesh>, &Handle<StandardMaterial>), With<LakeSurface>>,
+) {
+    // Without the render assets only the `LakeMap` of the terrain is generated
+    let (Some(mut materials), Some(mut meshes)) = (materials, meshes) else {
+        return;
+    };
+    for (entity, lakes, lake_map, terrain_data, children) in &query {
+        let mesh = meshes.add(surface_mesh(lake_map, terrain_data));
+        let [r, g, b, a] = lakes.color;
+        let material = StandardMaterial {
+            base_color: Color::rgba_u8(r, g, b, a),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: lakes.roughness,
+            ..default()
+        };
+
+        let surface = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| surfaces.contains(**child))
+                .copied()
+        });
+        let existing = surface.and_then(|surface| surfaces.get_mut(surface).ok());
+        if let Some((mut mesh_handle, material_handle)) = existing {
+            *mesh_handle = mesh;
+            if let Some(existing) = materials.get_mut(material_handle) {
+                *existing = material;
+            }
+        } else {
+            let surface = commands
+                .spawn((
+                    LakeSurface,
+                    PbrBundle {
+                        mesh,
+                        material: materials.add(material),
+                        ..default()
+                    },
+                ))
+                .id();
+            commands.entity(entity).add_child(surface);
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn remove_lakes(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Lakes>,
+    children: Query<&Children>,
+    surfaces: Query<(), With<LakeSurface>>,
+) {
+    for entity in removed.read() {
+        let Ok(children) = children.get(entity) else {
+            continue;
+        };
+        for &child in children {
+            if surfaces.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Flat quads at the lake level over every grid cell with a corner under water,
+/// the shore of the terrain rises through the edges
+fn surface_mesh(lake_map: &LakeMap, terrain_data: &GeneratedTerrain) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    let columns = lake_map.cells.len();
+    let rows = lake_map.cells.first().map_or(0, Vec::len);
+    let min = terrain_data.center - terrain_data.world_size / 2.0;
+    let size = terrain_data.world_size.max(Vec2::splat(f32::EPSILON));
+    for i in 0..columns.saturating_sub(1) {
+        for j in 0..rows.saturating_sub(1) {
+            let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+            let Some(lake) = corners.iter().find_map(|&(i, j)| lake_map.lake(i, j)) else {
+                continue;
+            };
+            let [Some(a), Some(b), Some(c), Some(d)] =
+                corners.map(|(i, j)| terrain_data.vertex_position(i, j))
+            else {
+                continue;
+            };
+            let first = positions.len() as u32;
+            for position in [a, b, c, d] {
+                positions.push([position.x, lake.level, position.z]);
+                uvs.push(((position.xz() - min) / size).to_array());
+            }
+            indices.extend([first, first + 2, first + 1, first, first + 3, first + 2]);
+        }
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 1.0, 0.0]; positions.len()],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
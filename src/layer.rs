@@ -0,0 +1,344 @@
+//! Blend several height sources into terrain through weighted masks
+//! # Example
+//! For configuration, see [`HeightLayers`](struct.HeightLayers.html).
+//! Noise and heightmap layers are blended into the noise of the terrain before its heights and colors,
+//! so e.g. a hand-authored hero area gets the gradient of the terrain and merges into the procedural surroundings.
+//! Stamp layers are blended into the heights before the `Stamps` of the terrain
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::layer::{HeightLayer, HeightLayers, HeightSource, LayerMask};
+//! use bevy_generative::noise::Noise;
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         HeightLayers {
+//!             layers: vec![
+//!                 HeightLayer {
+//!                     source: HeightSource::Noise(
+//!                         Noise::builder().seed(7).scale(40.0).build().unwrap(),
+//!                     ),
+//!                     mask: LayerMask::Circle {
+//!                         center: Vec2::new(0.5, 0.5),
+//!                         radius: 0.3,
+//!                         falloff: 0.3,
+//!                     },
+//!                     weight: 0.8,
+//!                 },
+//!                 HeightLayer {
+//!                     source: HeightSource::Heightmap {
+//!                         image: asset_server.load("stamps/mountain.png"),
+//!                         position: Vec2::new(-0.4, -0.4),
+//!                         size: Vec2::splat(0.8),
+//!                     },
+//!                     mask: LayerMask::Circle {
+//!                         center: Vec2::new(-0.4, -0.4),
+//!                         radius: 0.2,
+//!                         falloff: 0.2,
+//!                     },
+//!                     ..default()
+//!                 },
+//!             ],
+//!         },
+//!     ));
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deform::FlattenArea,
+    noise::{generate_noise_map_at, Noise, NoiseMap, NoiseValue},
+    stamp::{sample, Stamps},
+    terrain::Terrain,
+};
+
+/// Heights blended into the terrain by a `HeightLayer`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum HeightSource {
+    /// Noise stack sampled like the noise of the terrain, including its modifiers and `Terrain::seed_offset`.
+    /// The regions and gradient of the noise are not used, the terrain keeps its colors
+    Noise(Noise),
+    /// Grayscale image used as noise values, black is 0 and white is 100.
+    /// The layer is skipped until the image is loaded
+    Heightmap {
+        /// Grayscale image, the first channel is used
+        #[serde(skip)]
+        image: Handle<Image>,
+        /// Center of the image as `x`, `z` relative to the terrain entity
+        position: Vec2,
+        /// Size of the image along `x` and `z`, the top row of the image faces `-z`
+        size: Vec2,
+    },
+    /// Terrain with the stamps applied
+    Stamps(Stamps),
+}
+
+impl Default for HeightSource {
+    fn default() -> Self {
+        Self::Noise(Noise::default())
+    }
+}
+
+/// Where a `HeightLayer` is blended into the terrain, positions are `x`, `z` relative to the terrain entity
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum LayerMask {
+    /// Whole terrain
+    #[default]
+    All,
+    /// Circle fading out over `falloff` world units outside of its radius
+    Circle {
+        /// Center of the circle
+        center: Vec2,
+        /// Radius of the full weight
+        radius: f32,
+        /// Width of the transition to the terrain around it
+        falloff: f32,
+    },
+    /// Rectangle or polygon fading out over `falloff` world units outside of it
+    Area {
+        /// Area of the full weight
+        area: FlattenArea,
+        /// Width of the transition to the terrain around it
+        falloff: f32,
+    },
+    /// Grayscale image, black is 0 and white the full weight. Outside of the image the weight is 0,
+    /// the layer is skipped until the image is loaded
+    Image {
+        /// Grayscale image, the first channel is used
+        #[serde(skip)]
+        image: Handle<Image>,
+        /// Center of the image
+        position: Vec2,
+        /// Size of the image along `x` and `z`, the top row of the image faces `-z`
+        size: Vec2,
+    },
+}
+
+impl LayerMask {
+    /// Weight of the mask at `point` from 0 to 1, `image` is the image of `LayerMask::Image`
+    fn weight(&self, point: Vec2, image: Option<&Image>) -> f32 {
+        let fade = |outside: f32, falloff: f32| {
+            if outside <= 0.0 {
+                1.0
+            } else if outside >= falloff {
+                0.0
+            } else {
+                let x = 1.0 - outside / falloff;
+                x * x * 2.0f32.mul_add(-x, 3.0)
+            }
+        };
+        match self {
+            Self::All => 1.0,
+            Self::Circle {
+                center,
+                radius,
+                falloff,
+            } => fade(point.distance(*center) - radius, *falloff),
+            Self::Area { area, falloff } => fade(area.distance(point), *falloff),
+            Self::Image { position, size, .. } => image
+                .and_then(|image| image_uv(point, *position, *size).map(|uv| sample(image, uv)))
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+/// Height source blended into the terrain where its mask is set
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HeightLayer {
+    /// Heights of the layer
+    pub source: HeightSource,
+    /// Where the layer is blended in
+    pub mask: LayerMask,
+    /// Weight of the layer where the mask is full, from 0 to 1
+    pub weight: f32,
+}
+
+impl Default for HeightLayer {
+    fn default() -> Self {
+        Self {
+            source: HeightSource::default(),
+            mask: LayerMask::default(),
+            weight: 1.0,
+        }
+    }
+}
+
+/// Component for height layers, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Default, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct HeightLayers {
+    /// Layers blended in order, later layers are blended over earlier ones
+    pub layers: Vec<HeightLayer>,
+}
+
+impl HeightLayers {
+    /// If true, the source or the mask of a layer samples `image`
+    pub(crate) fn uses_image(&self, image: AssetId<Image>) -> bool {
+        self.layers.iter().any(|layer| {
+            let source = match &layer.source {
+                HeightSource::Noise(_) => false,
+                HeightSource::Heightmap { image: handle, .. } => handle.id() == image,
+                HeightSource::Stamps(stamps) => stamps.uses_image(image),
+            };
+            source
+                || matches!(&layer.mask, LayerMask::Image { image: handle, .. } if handle.id() == image)
+        })
+    }
+
+    /// Blends the noise and heightmap layers into `noise_map` of `terrain`, indexed by `[x][z]`
+    pub(crate) fn blend_noise(
+        &self,
+        noise_map: &mut NoiseMap,
+        terrain: &Terrain,
+        images: &Assets<Image>,
+    ) {
+        match noise_map {
+            NoiseMap::Single(values) => self.blend_noise_values(values, terrain, images),
+            NoiseMap::Double(values) => self.blend_noise_values(values, terrain, images),
+        }
+    }
+
+    fn blend_noise_values<T>(
+        &self,
+        noise_map: &mut [Vec<T>],
+        terrain: &Terrain,
+        images: &Assets<Image>,
+    ) where
+        T: NoiseValue
+            + std::ops::Add<Output = T>
+            + std::ops::Sub<Output = T>
+            + std::ops::Mul<Output = T>
+            + From<f32>,
+    {
+        let noise_size = terrain.size.map(|side| side * terrain.resolution);
+        for layer in &self.layers {
+            let Some(mask) = layer.mask(images) else {
+                continue;
+            };
+            match &layer.source {
+                HeightSource::Noise(noise) => {
+                    let values = generate_noise_map_at(
+                        &noise.offset_seeds(terrain.seed_offset),
+                        noise_size,
+                        terrain.noise_offset_of(noise),
+                    );
+                    blend(
+                        noise_map,
+                        terrain,
+                        |i, j, _| values.get(i, j).map(T::from_f64),
+                        mask,
+                    );
+                }
+                HeightSource::Heightmap {
+                    image,
+                    position,
+                    size,
+                } => {
+                    let Some(image) = images.get(image) else {
+                        continue;
+                    };
+                    blend(
+                        noise_map,
+                        terrain,
+                        |_, _, point| {
+                            let uv = image_uv(point, *position, *size)?;
+                            Some(T::from_f64(f64::from(sample(image, uv)) * 100.0))
+                        },
+                        mask,
+                    );
+                }
+                HeightSource::Stamps(_) => {}
+            }
+        }
+    }
+
+    /// Blends the stamp layers into `heights` of `terrain`, indexed by `[x][z]`
+    pub(crate) fn blend_stamps(
+        &self,
+        heights: &mut [Vec<f32>],
+        terrain: &Terrain,
+        images: &Assets<Image>,
+    ) {
+        for layer in &self.layers {
+            let HeightSource::Stamps(stamps) = &layer.source else {
+                continue;
+            };
+            let Some(mask) = layer.mask(images) else {
+                continue;
+            };
+            let mut stamped = heights.to_vec();
+            stamps.apply(&mut stamped, terrain.resolution, terrain.size, images);
+            blend(heights, terrain, |i, j, _| Some(stamped[i][j]), mask);
+        }
+    }
+}
+
+impl HeightLayer {
+    /// Weight of the layer at a point relative to the terrain entity, `None` until the image of the mask is loaded
+    fn mask<'a>(&'a self, images: &'a Assets<Image>) -> Option<impl Fn(Vec2) -> f32 + 'a> {
+        let image = match &self.mask {
+            LayerMask::Image { image, .. } => Some(images.get(image)?),
+            _ => None,
+        };
+        let weight = self.weight.clamp(0.0, 1.0);
+        Some(move |point| self.mask.weight(point, image) * weight)
+    }
+}
+
+/// Blends `values` towards `source` by `mask`, `source` gets the indices and the position of a vertex
+/// and is `None` where it does not cover the terrain
+fn blend<T>(
+    values: &mut [Vec<T>],
+    terrain: &Terrain,
+    source: impl Fn(usize, usize, Vec2) -> Option<T>,
+    mask: impl Fn(Vec2) -> f32,
+) where
+    T: Copy
+        + std::ops::Add<Output = T>
+        + std::ops::Sub<Output = T>
+        + std::ops::Mul<Output = T>
+        + From<f32>,
+{
+    let resolution = terrain.resolution.max(1) as f32;
+    let half = Vec2::new(terrain.size[0] as f32, terrain.size[1] as f32) / 2.0;
+    for (i, column) in values.iter_mut().enumerate() {
+        for (j, value) in column.iter_mut().enumerate() {
+            let point = Vec2::new(i as f32, j as f32) / resolution - half;
+            let weight = mask(point);
+            if weight <= 0.0 {
+                continue;
+            }
+            if let Some(target) = source(i, j, point) {
+                *value = *value + (target - *value) * T::from(weight);
+            }
+        }
+    }
+}
+
+/// Texture coordinates of `point` on an image of `size` around `position`, `None` outside of the image
+fn image_uv(point: Vec2, position: Vec2, size: Vec2) -> Option<Vec2> {
+    let uv = (point - position) / size.max(Vec2::splat(f32::EPSILON)) + 0.5;
+    (uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all()).then_some(uv)
+}
@@ -19,11 +19,281 @@
 
 mod util;
 
+/// Sand bands along shores
+#[cfg(feature = "terrain")]
+pub mod beach;
+/// Biome classification
+#[cfg(feature = "terrain")]
+pub mod biome;
+/// Coastline and region border extraction
+#[cfg(feature = "terrain")]
+pub mod border;
+/// Bridge placement where roads cross water or chasms
+#[cfg(feature = "terrain")]
+pub mod bridge;
+/// Animated caustics and water normal textures
+pub mod caustics;
+/// Cave generation
+#[cfg(feature = "dungeon")]
+pub mod cave;
+/// Cell queries on generated terrain with neighbours and areas
+#[cfg(feature = "terrain")]
+pub mod cell;
+/// Climate simulation
+#[cfg(feature = "terrain")]
+pub mod climate;
+/// Cloud layer generation
+pub mod cloud;
+/// Commands extension for spawning generators
+#[cfg(any(feature = "terrain", feature = "map", feature = "planet"))]
+pub mod commands;
+/// Gizmo overlays for generation debugging
+#[cfg(feature = "debug")]
+pub mod debug;
+/// Runtime terrain deformation
+#[cfg(feature = "terrain")]
+pub mod deform;
+/// Signed distance fields from masks
+#[cfg(feature = "terrain")]
+pub mod distance;
+/// Dungeon generation
+#[cfg(feature = "dungeon")]
+pub mod dungeon;
+/// Configuration and generation errors
+pub mod error;
+/// Terrain and environment export with splines, scattered objects and points of interest
+#[cfg(feature = "terrain")]
+pub mod export;
+/// Looping noise animations baked into sprite sheets
+#[cfg(feature = "map")]
+pub mod flipbook;
+/// Flow directions and accumulation
+#[cfg(feature = "terrain")]
+pub mod flow;
+/// Fog of war over terrain
+#[cfg(feature = "terrain")]
+pub mod fog;
+/// Gas giant generation
+#[cfg(feature = "planet")]
+pub mod gas_giant;
+/// Data-only generation for servers and headless simulations
+pub mod headless;
+/// Heat-map debug views of terrain and map data
+#[cfg(any(feature = "terrain", feature = "map"))]
+pub mod heatmap;
+/// Holes cut into terrain meshes
+#[cfg(feature = "terrain")]
+pub mod hole;
+/// Runtime inspector of generator configs
+#[cfg(all(
+    feature = "egui",
+    any(feature = "terrain", feature = "map", feature = "planet")
+))]
+pub mod inspector;
+/// Lakes filling closed depressions
+#[cfg(feature = "terrain")]
+pub mod lake;
+/// Height sources blended into terrain through masks
+#[cfg(feature = "terrain")]
+pub mod layer;
 /// Map and texture generation
+#[cfg(feature = "map")]
 pub mod map;
+/// Iso-contour extraction with marching squares
+pub mod marching_squares;
+/// Terrain materials and gradient lookup textures for shaders
+#[cfg(feature = "terrain")]
+pub mod material;
+/// Maze generation
+#[cfg(feature = "dungeon")]
+pub mod maze;
+/// Mission graph generation
+#[cfg(feature = "dungeon")]
+pub mod mission;
+/// Noise map modifiers
+pub mod modifier;
+/// Noise channels combined through splines
+pub mod multi_noise;
+/// Name generation
+pub mod names;
+/// Navigation mesh baking
+#[cfg(feature = "navmesh")]
+pub mod navmesh;
 /// Noise configuration
 pub mod noise;
+/// Ore vein generation
+#[cfg(feature = "terrain")]
+pub mod ore;
+/// Gradients selected per biome
+#[cfg(feature = "terrain")]
+pub mod palette;
+/// Saving and loading terrain edits
+#[cfg(feature = "terrain")]
+pub mod persist;
 /// Planet generation
+#[cfg(feature = "planet")]
 pub mod planet;
+/// Plant and tree generation
+pub mod plant;
+/// Point of interest placement
+#[cfg(feature = "terrain")]
+pub mod poi;
+/// Generation progress reporting
+pub mod progress;
+/// River splines and surface meshes
+#[cfg(feature = "terrain")]
+pub mod river;
+/// Road network generation
+#[cfg(feature = "terrain")]
+pub mod road;
+/// Rock and asteroid generation
+pub mod rock;
+/// Data-driven object scattering
+#[cfg(feature = "terrain")]
+pub mod scatter;
+/// Signed distance field composition
+pub mod sdf;
+/// On-demand chunk generation for dedicated servers
+#[cfg(feature = "terrain")]
+pub mod server;
+/// Settlement layout generation
+#[cfg(feature = "terrain")]
+pub mod settlement;
+/// Snapping entities to the terrain surface
+#[cfg(feature = "terrain")]
+pub mod snap;
+/// Generator configs saved into scenes
+#[cfg(feature = "scene")]
+pub mod snapshot;
+/// Snow cover by altitude and slope
+#[cfg(feature = "terrain")]
+pub mod snow;
+/// Runtime splat map painting
+#[cfg(feature = "terrain")]
+pub mod splat;
+/// Pixel-art sprite generation
+pub mod sprite;
+/// Landform stamps
+#[cfg(feature = "terrain")]
+pub mod stamp;
+/// Starfield and skybox generation
+pub mod starfield;
+/// Terrain chunk streaming around a focus
+#[cfg(feature = "terrain")]
+pub mod streaming;
+/// Structures placed on terrain
+#[cfg(feature = "terrain")]
+pub mod structure;
+/// Settlement suitability scoring
+#[cfg(feature = "terrain")]
+pub mod suitability;
+/// Exemplar-based texture synthesis
+pub mod synthesis;
 /// Terrain  generation
+#[cfg(feature = "terrain")]
 pub mod terrain;
+/// Territory ownership overlays
+#[cfg(feature = "terrain")]
+pub mod territory;
+/// Seamless material texture generation
+#[cfg(feature = "map")]
+pub mod texture;
+/// Terrain verification hashes for multiplayer
+#[cfg(feature = "terrain")]
+pub mod verify;
+/// Volume generation from density fields
+pub mod volume;
+/// Walkability grids for pathfinding
+#[cfg(feature = "terrain")]
+pub mod walkability;
+/// Animated water surfaces
+#[cfg(feature = "terrain")]
+pub mod water;
+/// Animated weather over terrain
+#[cfg(feature = "terrain")]
+pub mod weather;
+/// Wave function collapse generation
+#[cfg(feature = "dungeon")]
+pub mod wfc;
+
+use bevy::app::{PluginGroup, PluginGroupBuilder};
+
+/// Every plugin of the enabled features.
+/// Disable single plugins with `GenerativePlugins.build().disable::<T>()`
+/// ```
+/// use bevy::prelude::*;
+/// use bevy_generative::GenerativePlugins;
+///
+/// App::new()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugins(GenerativePlugins)
+///     .run();
+/// ```
+pub struct GenerativePlugins;
+
+impl PluginGroup for GenerativePlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(caustics::WaterTexturePlugin)
+            .add(cloud::CloudPlugin)
+            .add(plant::PlantPlugin)
+            .add(rock::RockPlugin)
+            .add(sprite::PixelSpritePlugin)
+            .add(starfield::StarfieldPlugin)
+            .add(synthesis::SynthesisPlugin)
+            .add(volume::VolumePlugin);
+        #[cfg(feature = "scene")]
+        let group = group.add(snapshot::SnapshotPlugin);
+        #[cfg(feature = "terrain")]
+        let group = group
+            .add(terrain::TerrainPlugin)
+            .add(biome::BiomePlugin)
+            .add(border::BorderPlugin)
+            .add(bridge::BridgePlugin)
+            .add(climate::ClimatePlugin)
+            .add(deform::DeformPlugin)
+            .add(distance::DistancePlugin)
+            .add(export::WorldExportPlugin)
+            .add(flow::FlowPlugin)
+            .add(fog::FogPlugin)
+            .add(lake::LakePlugin)
+            .add(material::TerrainMaterialPlugin)
+            .add(multi_noise::MultiNoisePlugin)
+            .add(ore::OrePlugin)
+            .add(persist::PersistPlugin)
+            .add(poi::PoiPlugin)
+            .add(river::RiverPlugin)
+            .add(road::RoadPlugin)
+            .add(scatter::ScatterPlugin)
+            .add(settlement::SettlementPlugin)
+            .add(snap::SnapPlugin)
+            .add(splat::SplatPlugin)
+            .add(streaming::StreamingPlugin)
+            .add(structure::StructurePlugin)
+            .add(suitability::SuitabilityPlugin)
+            .add(territory::TerritoryPlugin)
+            .add(verify::VerifyPlugin)
+            .add(walkability::WalkabilityPlugin)
+            .add(water::WaterPlugin)
+            .add(weather::WeatherPlugin);
+        #[cfg(feature = "navmesh")]
+        let group = group.add(navmesh::NavMeshPlugin::default());
+        #[cfg(feature = "map")]
+        let group = group
+            .add(map::MapPlugin)
+            .add(flipbook::FlipbookPlugin)
+            .add(texture::ProceduralTexturePlugin);
+        #[cfg(feature = "planet")]
+        let group = group
+            .add(planet::PlanetPlugin)
+            .add(gas_giant::GasGiantPlugin);
+        #[cfg(feature = "dungeon")]
+        let group = group
+            .add(dungeon::DungeonPlugin)
+            .add(cave::CavePlugin)
+            .add(maze::MazePlugin)
+            .add(mission::MissionPlugin)
+            .add(wfc::WfcPlugin);
+        group
+    }
+}
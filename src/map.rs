@@ -23,7 +23,9 @@ use image::{imageops::FilterType, DynamicImage, Pixel};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    noise::{generate_noise_map, Noise},
+    error::{fail, GenerationError, GenerationFailure},
+    heatmap::DebugView,
+    noise::{build_gradient, generate_noise_map, Noise},
     util::export_asset,
 };
 
@@ -32,13 +34,15 @@ pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, generate_map);
+        app.add_event::<GenerationError>()
+            .add_systems(Update, generate_map);
     }
 }
 
 /// Component for map configuration
-#[derive(Component, Serialize, Deserialize)]
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
 pub struct Map {
     /// Noise configuration of the map
     pub noise: Noise,
@@ -76,38 +80,31 @@ impl Default for Map {
         }
     }
 }
-fn generate_map(mut images: ResMut<Assets<Image>>, mut query: Query<(&mut Map, &mut UiImage)>) {
-    for (mut map, mut ui_image) in &mut query {
+fn generate_map(
+    mut errors: EventWriter<GenerationError>,
+    images: Option<ResMut<Assets<Image>>>,
+    mut query: Query<(Entity, &mut Map, &mut UiImage, Option<&DebugView>)>,
+) {
+    // Without the render assets there is nothing to generate
+    let Some(mut images) = images else {
+        return;
+    };
+    for (entity, mut map, mut ui_image, debug_view) in &mut query {
+        let grad = match build_gradient(&map.noise.regions, &map.noise.gradient) {
+            Ok(grad) => grad,
+            Err(error) => {
+                fail(
+                    &mut errors,
+                    entity,
+                    GenerationFailure::Gradient(error.to_string()),
+                );
+                continue;
+            }
+        };
         map.noise.size = map.size;
-        let noise_values = generate_noise_map(&map.noise);
+        let noise_values = generate_noise_map(&map.noise, map.size);
         let noise = &mut map.noise;
 
-        let mut colors: Vec<colorgrad::Color> = Vec::with_capacity(noise.regions.len());
-        let mut domain: Vec<f64> = Vec::with_capacity(noise.regions.len());
-        for region in &noise.regions {
-            colors.push(colorgrad::Color {
-                r: f64::from(region.color[0]) / 255.0,
-                g: f64::from(region.color[1]) / 255.0,
-                b: f64::from(region.color[2]) / 255.0,
-                a: f64::from(region.color[3]) / 255.0,
-            });
-            domain.push(region.position);
-        }
-        let mut grad = colorgrad::CustomGradient::new()
-            .colors(&colors)
-            .domain(&domain)
-            .build()
-            .unwrap_or_else(|_| {
-                colorgrad::CustomGradient::new()
-                    .colors(&colors)
-                    .build()
-                    .expect("Gradient generation failed")
-            });
-
-        if noise.gradient.segments != 0 {
-            grad = grad.sharp(noise.gradient.segments, noise.gradient.smoothness);
-        }
-
         let mut gradient_buffer = image::ImageBuffer::from_pixel(
             noise.gradient.size[0],
             noise.gradient.size[1],
@@ -121,11 +118,13 @@ fn generate_map(mut images: ResMut<Assets<Image>>, mut query: Query<(&mut Map, &
             pixel.blend(&image::Rgba(rgba));
         }
 
-        noise.gradient.image = images.add(
-            Image::from_dynamic(gradient_buffer.into(), true)
-                .convert(bevy::render::render_resource::TextureFormat::Rgba8UnormSrgb)
-                .expect("Could not convert to Rgba8UnormSrgb"),
-        );
+        let Some(gradient_image) = Image::from_dynamic(gradient_buffer.into(), true)
+            .convert(TextureFormat::Rgba8UnormSrgb)
+        else {
+            fail(&mut errors, entity, GenerationFailure::Texture);
+            continue;
+        };
+        noise.gradient.image = images.add(gradient_image);
 
         let mut image_buffer = image::ImageBuffer::from_pixel(
             noise.size[0],
@@ -133,8 +132,15 @@ fn generate_map(mut images: ResMut<Assets<Image>>, mut query: Query<(&mut Map, &
             image::Rgba(noise.base_color),
         );
 
+        let debug_colors = debug_view.and_then(|view| view.map_colors(&noise_values));
+        let rows = noise_values.rows();
         for (x, y, pixel) in image_buffer.enumerate_pixels_mut() {
-            let height = noise_values[x as usize][y as usize];
+            if let Some(colors) = &debug_colors {
+                let color = colors[x as usize * rows + y as usize];
+                *pixel = image::Rgba(color.map(|channel| (channel * 255.0).round() as u8));
+                continue;
+            }
+            let height = noise_values.get(x as usize, y as usize).unwrap_or_default();
             let target_color = grad.at(height).to_rgba8();
             pixel.blend(&image::Rgba(target_color));
         }
@@ -155,9 +161,12 @@ fn generate_map(mut images: ResMut<Assets<Image>>, mut query: Query<(&mut Map, &
             export_asset(image_buffer.clone());
             map.export = false;
         }
-        let map_texture = Image::from_dynamic(image_buffer.into(), true)
-            .convert(TextureFormat::Rgba8UnormSrgb)
-            .expect("Could not convert to Rgba8UnormSrgb");
+        let Some(map_texture) =
+            Image::from_dynamic(image_buffer.into(), true).convert(TextureFormat::Rgba8UnormSrgb)
+        else {
+            fail(&mut errors, entity, GenerationFailure::Texture);
+            continue;
+        };
 
         ui_image.texture = images.add(map_texture);
     }
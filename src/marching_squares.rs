@@ -0,0 +1,172 @@
+//! Extract iso-contours from scalar grids with marching squares
+//! # Example
+//! For details, see [`contours`](fn.contours.html)
+//! ```
+//! use bevy_generative::marching_squares::contours;
+//!
+//! let grid = vec![
+//!     vec![0.0, 0.0, 0.0],
+//!     vec![0.0, 1.0, 0.0],
+//!     vec![0.0, 0.0, 0.0],
+//! ];
+//! let contours = contours(&grid, 0.5);
+//! assert_eq!(contours.len(), 1);
+//! assert!(contours[0].area() > 0.0);
+//! ```
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+/// Closed polyline separating values below a threshold from values at or above it
+#[derive(Clone, PartialEq, Debug)]
+pub struct Contour {
+    /// Threshold the contour was extracted at
+    pub threshold: f64,
+    /// Points in grid coordinates, the last point connects back to the first.
+    /// Values at or above the threshold are on the left, so outlines are counter-clockwise and holes clockwise
+    pub points: Vec<Vec2>,
+}
+
+impl Contour {
+    /// Signed area enclosed by the contour in grid cells, negative for holes
+    #[must_use]
+    pub fn area(&self) -> f32 {
+        let count = self.points.len();
+        (0..count)
+            .map(|i| self.points[i].perp_dot(self.points[(i + 1) % count]))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    /// Returns true if the contour encloses values below the threshold
+    #[must_use]
+    pub fn is_hole(&self) -> bool {
+        self.area() < 0.0
+    }
+
+    /// Length of the contour in grid cells
+    #[must_use]
+    pub fn length(&self) -> f32 {
+        let count = self.points.len();
+        (0..count)
+            .map(|i| self.points[i].distance(self.points[(i + 1) % count]))
+            .sum()
+    }
+}
+
+/// Extracts the contours of `grid` at `threshold`, `grid` is indexed by `[x][y]`.
+///
+/// Values outside of the grid count as below the threshold,
+/// so regions touching the border are closed along the border.
+/// Ambiguous cells are resolved by the average of their corners.
+#[must_use]
+pub fn contours<T: Copy + Into<f64>>(grid: &[Vec<T>], threshold: f64) -> Vec<Contour> {
+    let width = grid.len();
+    let depth = grid.first().map_or(0, Vec::len);
+    if width == 0 || depth == 0 {
+        return vec![];
+    }
+    // Padding the grid with a ring of low values closes every contour
+    let outside = threshold - 1.0;
+    let value = |x: usize, y: usize| {
+        if x == 0 || y == 0 || x > width || y > depth {
+            outside
+        } else {
+            grid[x - 1]
+                .get(y - 1)
+                .map_or(outside, |&value| value.into())
+        }
+    };
+    let max = Vec2::new(width as f32 - 1.0, depth as f32 - 1.0);
+
+    // Crossings are keyed by the lower vertex of their grid edge and whether the edge is horizontal
+    let mut points: HashMap<(usize, usize, bool), Vec2> = HashMap::new();
+    let mut next: HashMap<(usize, usize, bool), (usize, usize, bool)> = HashMap::new();
+    for x in 0..=width {
+        for y in 0..=depth {
+            // Corners and edges in counter-clockwise order, edge `i` runs from corner `i` to corner `i + 1`
+            let corners = [[x, y], [x + 1, y], [x + 1, y + 1], [x, y + 1]];
+            let values = corners.map(|[cx, cy]| value(cx, cy));
+            let inside = values.map(|value| value >= threshold);
+            let edges = [
+                (x, y, true),
+                (x + 1, y, false),
+                (x, y + 1, true),
+                (x, y, false),
+            ];
+            let crossing: Vec<usize> = (0..4)
+                .filter(|&i| inside[i] != inside[(i + 1) % 4])
+                .collect();
+            let segments: Vec<[usize; 2]> = match crossing.len() {
+                2 => vec![[crossing[0], crossing[1]]],
+                4 => {
+                    let center = values.iter().sum::<f64>() / 4.0 >= threshold;
+                    // Corner `k` lies between edges `k - 1` and `k`, the corners unlike the center are cut off
+                    (0..4)
+                        .filter(|&k| inside[k] != center)
+                        .map(|k| [(k + 3) % 4, k])
+                        .collect()
+                }
+                _ => vec![],
+            };
+            for [first, second] in segments {
+                // The corners on the left of a segment from edge `i` to edge `j` run from `j + 1` to `i`
+                let [from, to] = if inside[(second + 1) % 4] {
+                    [first, second]
+                } else {
+                    [second, first]
+                };
+                for edge in [from, to] {
+                    points.entry(edges[edge]).or_insert_with(|| {
+                        let [a, b] = [corners[edge], corners[(edge + 1) % 4]];
+                        let [va, vb] = [values[edge], values[(edge + 1) % 4]];
+                        let t = ((threshold - va) / (vb - va)) as f32;
+                        let a = Vec2::new(a[0] as f32, a[1] as f32);
+                        let b = Vec2::new(b[0] as f32, b[1] as f32);
+                        // Back to unpadded coordinates, crossings with the padding lie on the border
+                        (a + (b - a) * t - Vec2::ONE).clamp(Vec2::ZERO, max)
+                    });
+                }
+                next.insert(edges[from], edges[to]);
+            }
+        }
+    }
+
+    // Follow the segments until they close, starting at the lowest key for deterministic output
+    let mut starts: Vec<(usize, usize, bool)> = next.keys().copied().collect();
+    starts.sort_unstable();
+    let mut contours = vec![];
+    for start in starts {
+        if !next.contains_key(&start) {
+            continue;
+        }
+        let mut polyline: Vec<Vec2> = vec![];
+        let mut current = start;
+        while let Some(following) = next.remove(&current) {
+            let point = points[&current];
+            if polyline.last() != Some(&point) {
+                polyline.push(point);
+            }
+            current = following;
+        }
+        if polyline.len() > 1 && polyline.first() == polyline.last() {
+            polyline.pop();
+        }
+        if polyline.len() >= 3 {
+            contours.push(Contour {
+                threshold,
+                points: polyline,
+            });
+        }
+    }
+    contours
+}
+
+/// Extracts the contours of `grid` at every threshold, see [`contours`](fn.contours.html)
+#[must_use]
+pub fn contours_at<T: Copy + Into<f64>>(grid: &[Vec<T>], thresholds: &[f64]) -> Vec<Contour> {
+    thresholds
+        .iter()
+        .flat_map(|&threshold| contours(grid, threshold))
+        .collect()
+}
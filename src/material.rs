@@ -0,0 +1,324 @@
+//! Render terrain with textures blended by height and slope
+//! # Example
+//! For configuration, see [`TerrainMaterial`](struct.TerrainMaterial.html) and [`TerrainLayer`](struct.TerrainLayer.html).
+//! Replace the `StandardMaterial` of a terrain with a `TerrainPbrMaterial`. The heightmap, the bounds of the terrain
+//! and the texture of its `SplatMap` are set by `TerrainMaterialPlugin`, painted splat layers force the layer with the same index.
+//! Custom materials can color by noise value like the vertex colors with a [`GradientLutPlugin`](struct.GradientLutPlugin.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::material::{
+//!     TerrainLayer, TerrainMaterial, TerrainMaterialPlugin, TerrainPbrMaterial,
+//! };
+//! use bevy_generative::terrain::{Terrain, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, TerrainMaterialPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands, mut materials: ResMut<Assets<TerrainPbrMaterial>>) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     let material = TerrainMaterial::from_layers(&[
+//!         TerrainLayer {
+//!             color: Color::DARK_GREEN.into(),
+//!             height: Vec2::new(-1.0, 0.5),
+//!             ..default()
+//!         },
+//!         TerrainLayer {
+//!             color: Color::GRAY.into(),
+//!             slope: Vec2::new(0.6, 1.6),
+//!             ..default()
+//!         },
+//!     ]);
+//!     commands.spawn((
+//!         Terrain::default(),
+//!         MaterialMeshBundle {
+//!             material: materials.add(TerrainPbrMaterial {
+//!                 base: StandardMaterial::default(),
+//!                 extension: material,
+//!             }),
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{load_internal_asset, Asset},
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    reflect::TypePath,
+    render::render_resource::{AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    splat::SplatMap,
+    terrain::{share_part_materials, GeneratedTerrain, TerrainSet},
+};
+
+const TERRAIN_MATERIAL_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(0x5f0e_8a2c_41d7_4b9e_a3c6_7d12_e48b_f903);
+const GRADIENT_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(0x9b3d_17e4_c2a8_4f06_8e51_d0b7_3a6c_2f94);
+
+/// `StandardMaterial` extended by a `TerrainMaterial`, used as the material of a terrain
+pub type TerrainPbrMaterial = ExtendedMaterial<StandardMaterial, TerrainMaterial>;
+
+pub use settings::{TerrainLayer, TerrainMaterialSettings};
+
+// The derive of `ShaderType` generates layout checks the lint only sees used by the render pipeline
+#[allow(dead_code)]
+mod settings {
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// Layer of a `TerrainMaterial`, covering the terrain where both its height and slope ranges match
+    #[derive(Clone, Copy, PartialEq, Debug, ShaderType)]
+    pub struct TerrainLayer {
+        /// Linear color multiplied with the texture of the layer
+        pub color: Vec4,
+        /// Lowest and highest height in world units, relative to the terrain entity
+        pub height: Vec2,
+        /// Lowest and highest slope in radians, 0 is flat
+        pub slope: Vec2,
+        /// Width of the transitions at the ends of the height and slope ranges
+        pub blend: Vec2,
+    }
+
+    impl Default for TerrainLayer {
+        fn default() -> Self {
+            Self {
+                color: Vec4::ONE,
+                height: Vec2::new(f32::MIN, f32::MAX),
+                slope: Vec2::new(0.0, std::f32::consts::FRAC_PI_2),
+                blend: Vec2::new(0.1, 0.1),
+            }
+        }
+    }
+
+    /// Uniform of a `TerrainMaterial`
+    #[derive(Clone, Copy, Default, PartialEq, Debug, ShaderType)]
+    pub struct TerrainMaterialSettings {
+        /// Layers in order, later layers are blended over earlier ones by weight
+        pub layers: [TerrainLayer; 4],
+        /// Number of used layers, at most 4
+        pub layer_count: u32,
+        /// World units covered by one repetition of the layer textures
+        pub tile_size: f32,
+        /// Weight the splat map adds to the layer of each channel, set to 0 without a `SplatMap`
+        pub splat_strength: f32,
+        /// Lowest `x`, `z` and size of the terrain in world space, set by `TerrainMaterialPlugin`
+        pub bounds: Vec4,
+        /// 1 if `TerrainMaterial::detail_normals` is set, set by `TerrainMaterialPlugin`
+        pub detail_normals: u32,
+    }
+}
+
+/// Material extension blending up to four textures by height and slope in the fragment shader.
+/// Slopes are computed from the heightmap, so they do not depend on the normals of the mesh
+#[derive(Asset, TypePath, AsBindGroup, Clone, Default, Debug)]
+pub struct TerrainMaterial {
+    /// Layers and bounds of the terrain
+    #[uniform(100)]
+    pub settings: TerrainMaterialSettings,
+    /// Heights of the terrain as `R32Float`, set by `TerrainMaterialPlugin`
+    #[texture(101, sample_type = "float", filterable = false)]
+    pub heightmap: Option<Handle<Image>>,
+    /// Texture of the `SplatMap` of the terrain, set by `TerrainMaterialPlugin`
+    #[texture(102)]
+    #[sampler(103)]
+    pub splat_map: Option<Handle<Image>>,
+    /// Texture of the first layer, white if `None`
+    #[texture(104)]
+    #[sampler(105)]
+    pub texture_0: Option<Handle<Image>>,
+    /// Texture of the second layer, white if `None`
+    #[texture(106)]
+    #[sampler(107)]
+    pub texture_1: Option<Handle<Image>>,
+    /// Texture of the third layer, white if `None`
+    #[texture(108)]
+    #[sampler(109)]
+    pub texture_2: Option<Handle<Image>>,
+    /// Texture of the fourth layer, white if `None`
+    #[texture(110)]
+    #[sampler(111)]
+    pub texture_3: Option<Handle<Image>>,
+    /// Normal map of `GeneratedTerrain::detail_normals`, set by `TerrainMaterialPlugin`
+    #[texture(112)]
+    #[sampler(113)]
+    pub detail_normals: Option<Handle<Image>>,
+}
+
+impl TerrainMaterial {
+    /// Material with untextured `layers`, only the first four are used
+    #[must_use]
+    pub fn from_layers(layers: &[TerrainLayer]) -> Self {
+        let mut settings = TerrainMaterialSettings {
+            layer_count: layers.len().min(4) as u32,
+            tile_size: 1.0,
+            ..default()
+        };
+        for (layer, used) in settings.layers.iter_mut().zip(layers) {
+            *layer = *used;
+        }
+        Self {
+            settings,
+            ..default()
+        }
+    }
+}
+
+impl MaterialExtension for TerrainMaterial {
+    fn fragment_shader() -> ShaderRef {
+        TERRAIN_MATERIAL_SHADER.into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        TERRAIN_MATERIAL_SHADER.into()
+    }
+}
+
+/// Plugin to render terrain with `TerrainPbrMaterial`
+pub struct TerrainMaterialPlugin;
+
+impl Plugin for TerrainMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            TERRAIN_MATERIAL_SHADER,
+            "terrain_material.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<TerrainPbrMaterial>::default())
+            .add_systems(
+                Update,
+                (
+                    update_terrain_materials,
+                    share_part_materials::<TerrainPbrMaterial>.in_set(TerrainSet::Apply),
+                ),
+            );
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn update_terrain_materials(
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<TerrainPbrMaterial>>,
+    query: Query<
+        (
+            Ref<GeneratedTerrain>,
+            Ref<GlobalTransform>,
+            &Handle<TerrainPbrMaterial>,
+            Option<&SplatMap>,
+        ),
+        Or<(
+            Changed<GeneratedTerrain>,
+            Changed<GlobalTransform>,
+            Changed<Handle<TerrainPbrMaterial>>,
+            Changed<SplatMap>,
+        )>,
+    >,
+) {
+    for (terrain_data, transform, material, splat_map) in &query {
+        let Some(material) = materials.get_mut(material) else {
+            continue;
+        };
+        let material = &mut material.extension;
+        let translation = transform.translation().xz();
+        let min = translation + terrain_data.center - terrain_data.world_size / 2.0;
+        material.settings.bounds = min
+            .extend(terrain_data.world_size.x)
+            .extend(terrain_data.world_size.y);
+        material.splat_map = splat_map.map(|splat_map| splat_map.texture.clone());
+        material.settings.splat_strength = if splat_map.is_some() { 1.0 } else { 0.0 };
+        material
+            .detail_normals
+            .clone_from(&terrain_data.detail_normals);
+        material.settings.detail_normals = u32::from(material.detail_normals.is_some());
+        if terrain_data.is_changed() || material.heightmap.is_none() {
+            let image = heightmap(&terrain_data);
+            match material
+                .heightmap
+                .as_ref()
+                .and_then(|handle| images.get_mut(handle))
+            {
+                Some(heightmap) => *heightmap = image,
+                None => material.heightmap = Some(images.add(image)),
+            }
+        }
+    }
+}
+
+/// Material with a 1D texture binding for `GeneratedTerrain::gradient_lut`.
+///
+/// Bind it with `#[texture(N, dimension = "1d")]` and a `#[sampler(M)]`, then import
+/// `bevy_generative::gradient::{gradient_color, noise_value}` in the shader
+pub trait GradientLutMaterial: Material {
+    /// Sets the lookup texture of the gradient
+    fn set_gradient_lut(&mut self, lut: Handle<Image>);
+}
+
+/// Plugin to bind the gradient lookup texture of every terrain to its `M` material.
+/// Also loads the `bevy_generative::gradient` shader import
+pub struct GradientLutPlugin<M>(PhantomData<M>);
+
+impl<M> Default for GradientLutPlugin<M> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<M: GradientLutMaterial> Plugin for GradientLutPlugin<M> {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(app, GRADIENT_SHADER, "gradient.wgsl", Shader::from_wgsl);
+        app.add_systems(Update, bind_gradient_luts::<M>);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn bind_gradient_luts<M: GradientLutMaterial>(
+    mut materials: ResMut<Assets<M>>,
+    query: Query<
+        (&GeneratedTerrain, &Handle<M>),
+        Or<(Changed<GeneratedTerrain>, Changed<Handle<M>>)>,
+    >,
+) {
+    for (terrain_data, material) in &query {
+        if let Some(material) = materials.get_mut(material) {
+            material.set_gradient_lut(terrain_data.gradient_lut.clone());
+        }
+    }
+}
+
+/// Heights of `terrain_data` as an `R32Float` image, rows along `x` ordered by `z`
+fn heightmap(terrain_data: &GeneratedTerrain) -> Image {
+    let width = terrain_data.heights.len();
+    let height = terrain_data.heights.first().map_or(0, Vec::len);
+    let mut data = Vec::with_capacity(width * height * 4);
+    for j in 0..height {
+        for column in &terrain_data.heights {
+            data.extend(column[j].to_le_bytes());
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: width.max(1) as u32,
+            height: height.max(1) as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        if data.is_empty() { vec![0; 4] } else { data },
+        TextureFormat::R32Float,
+    )
+}
@@ -0,0 +1,388 @@
+//! Generate mazes
+//! # Example
+//! For configuration, see [`Maze`](struct.Maze.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::maze::{MazeBundle, MazePlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(MazePlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(MazeBundle::default());
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::render_resource::{PrimitiveTopology, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dungeon::{Tile, TileMesh},
+    error::{fail, GenerationError, GenerationFailure},
+    headless::DataOnly,
+    util::{export_asset, export_model, Rng},
+};
+
+/// Algorithm used to carve the maze
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MazeAlgorithm {
+    /// Depth first search, results in long winding passages
+    RecursiveBacktracker,
+    /// Randomized Prim's algorithm, results in many short dead ends
+    Prim,
+    /// Loop-erased random walks, samples all possible mazes uniformly
+    Wilson,
+}
+
+/// Output generated from the maze
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MazeOutput {
+    /// Only the `MazeLayout` component is inserted
+    Grid,
+    /// A top down image is written to `Maze::image` and `UiImage`
+    Image,
+    /// Floor and wall mesh is written to `PbrBundle`
+    Mesh,
+}
+
+/// Component for maze configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Maze {
+    /// Seed used to carve the maze
+    pub seed: u32,
+    /// Number of cells in the maze
+    pub size: [u32; 2],
+    /// Algorithm used to carve the maze
+    pub algorithm: MazeAlgorithm,
+    /// Percentage of dead ends that are opened into a neighbouring passage, creating loops
+    pub braid: f64,
+    /// Output generated from the maze
+    pub output: MazeOutput,
+    /// Size of a tile in world units
+    pub tile_size: f32,
+    /// Height of the walls in world units
+    pub wall_height: f32,
+    /// Size of a tile in pixels
+    pub pixels_per_tile: u32,
+    /// Color of passages
+    pub floor_color: [u8; 4],
+    /// Color of walls
+    pub wall_color: [u8; 4],
+    /// If true, renders maze mesh as wireframe
+    pub wireframe: bool,
+    /// If true, exports model in glb format or image in png format
+    #[serde(skip)]
+    pub export: bool,
+    /// Generated image
+    #[serde(skip)]
+    pub image: Handle<Image>,
+}
+
+impl Default for Maze {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: [16, 12],
+            algorithm: MazeAlgorithm::RecursiveBacktracker,
+            braid: 0.0,
+            output: MazeOutput::Mesh,
+            tile_size: 0.1,
+            wall_height: 0.15,
+            pixels_per_tile: 8,
+            floor_color: [220, 215, 200, 255],
+            wall_color: [40, 40, 50, 255],
+            wireframe: false,
+            export: false,
+            image: Handle::default(),
+        }
+    }
+}
+
+/// Generated maze, inserted on the entity with the `Maze` component
+#[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MazeLayout {
+    /// Tile grid, indexed by `[x][y]`.
+    /// Cell `[x, y]` is the tile `[2 * x + 1, 2 * y + 1]`, tiles between cells are passages or walls
+    pub tiles: Vec<Vec<Tile>>,
+}
+
+impl MazeLayout {
+    /// Tile at `x`, `y`, `None` if outside of the maze
+    #[must_use]
+    pub fn get(&self, x: u32, y: u32) -> Option<Tile> {
+        self.tiles
+            .get(x as usize)
+            .and_then(|column| column.get(y as usize))
+            .copied()
+    }
+}
+
+/// Render `Maze` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct MazeBundle {
+    /// Maze configuration
+    pub maze: Maze,
+    /// Generated mesh data is written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate mazes
+pub struct MazePlugin;
+
+impl Plugin for MazePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GenerationError>()
+            .add_systems(Update, generate_maze);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_maze(
+    mut commands: Commands,
+    mut errors: EventWriter<GenerationError>,
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Maze,
+            Option<&mut Handle<Mesh>>,
+            Option<&Handle<StandardMaterial>>,
+            Option<&mut UiImage>,
+            Has<DataOnly>,
+        ),
+        Changed<Maze>,
+    >,
+) {
+    for (entity, mut maze, mesh_handle, material, ui_image, data_only) in &mut query {
+        let layout = generate_layout(&maze);
+        // Without the render assets only the layout is generated
+        let data_only = data_only || meshes.is_none();
+
+        match maze.output {
+            MazeOutput::Grid => {}
+            MazeOutput::Image => {
+                let scale = maze.pixels_per_tile.max(1);
+                let width = layout.tiles.len() as u32;
+                let depth = layout.tiles.first().map_or(0, Vec::len) as u32;
+                let image_buffer =
+                    image::ImageBuffer::from_fn(width * scale, depth * scale, |x, y| {
+                        if layout.tiles[(x / scale) as usize][(y / scale) as usize].is_walkable() {
+                            image::Rgba(maze.floor_color)
+                        } else {
+                            image::Rgba(maze.wall_color)
+                        }
+                    });
+                if maze.export {
+                    export_asset(image_buffer.clone());
+                    maze.bypass_change_detection().export = false;
+                }
+                if let Some(images) = images.as_deref_mut().filter(|_| !data_only) {
+                    let Some(image) = Image::from_dynamic(image_buffer.into(), true)
+                        .convert(TextureFormat::Rgba8UnormSrgb)
+                    else {
+                        fail(&mut errors, entity, GenerationFailure::Texture);
+                        continue;
+                    };
+                    let image = images.add(image);
+                    if let Some(mut ui_image) = ui_image {
+                        ui_image.texture = image.clone();
+                    }
+                    maze.bypass_change_detection().image = image;
+                }
+            }
+            MazeOutput::Mesh if data_only => {}
+            MazeOutput::Mesh => {
+                if let Some(material) = materials
+                    .as_deref_mut()
+                    .zip(material)
+                    .and_then(|(materials, material)| materials.get_mut(material))
+                {
+                    *material = StandardMaterial {
+                        perceptual_roughness: 0.9,
+                        ..default()
+                    };
+                }
+                let TileMesh {
+                    positions,
+                    normals,
+                    uvs,
+                    colors,
+                    mut indices,
+                } = TileMesh::extrude(
+                    &layout.tiles,
+                    maze.tile_size,
+                    maze.wall_height,
+                    maze.floor_color,
+                    maze.wall_color,
+                );
+
+                if maze.wireframe {
+                    let triangle_number = indices.len() / 3;
+                    let cloned_indices = indices.clone();
+                    indices = vec![];
+                    for i in 0..triangle_number {
+                        for j in &[0, 1, 1, 2, 2, 0] {
+                            indices.push(cloned_indices[i * 3 + j]);
+                        }
+                    }
+                }
+
+                let mut mesh = if maze.wireframe {
+                    Mesh::new(PrimitiveTopology::LineList)
+                } else {
+                    Mesh::new(PrimitiveTopology::TriangleList)
+                };
+                mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
+                mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+                mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+                mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+                mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+                if let (Some(mut mesh_handle), Some(meshes)) = (mesh_handle, meshes.as_deref_mut())
+                {
+                    *mesh_handle = meshes.add(mesh);
+                }
+
+                if maze.export {
+                    export_model(&positions, indices, &colors);
+                    maze.bypass_change_detection().export = false;
+                }
+            }
+        }
+
+        commands.entity(entity).insert(layout);
+    }
+}
+
+fn generate_layout(maze: &Maze) -> MazeLayout {
+    let [width, depth] = maze.size.map(|side| side.max(1) as usize);
+    let mut rng = Rng::new(u64::from(maze.seed));
+    let mut tiles = vec![vec![Tile::Wall; depth * 2 + 1]; width * 2 + 1];
+    let mut visited = vec![vec![false; depth]; width];
+
+    let neighbours = |[x, y]: [usize; 2]| {
+        [
+            (x > 0).then(|| [x - 1, y]),
+            (x + 1 < width).then(|| [x + 1, y]),
+            (y > 0).then(|| [x, y - 1]),
+            (y + 1 < depth).then(|| [x, y + 1]),
+        ]
+        .into_iter()
+        .flatten()
+    };
+    let pick = |rng: &mut Rng, len: usize| rng.range(0, len as u32 - 1) as usize;
+    let carve = |tiles: &mut Vec<Vec<Tile>>, from: [usize; 2], to: [usize; 2]| {
+        tiles[from[0] * 2 + 1][from[1] * 2 + 1] = Tile::Floor;
+        tiles[to[0] * 2 + 1][to[1] * 2 + 1] = Tile::Floor;
+        tiles[from[0] + to[0] + 1][from[1] + to[1] + 1] = Tile::Floor;
+    };
+
+    let start = [pick(&mut rng, width), pick(&mut rng, depth)];
+    visited[start[0]][start[1]] = true;
+    tiles[start[0] * 2 + 1][start[1] * 2 + 1] = Tile::Floor;
+
+    match maze.algorithm {
+        MazeAlgorithm::RecursiveBacktracker => {
+            let mut stack = vec![start];
+            while let Some(&cell) = stack.last() {
+                let unvisited: Vec<[usize; 2]> =
+                    neighbours(cell).filter(|&[x, y]| !visited[x][y]).collect();
+                if unvisited.is_empty() {
+                    stack.pop();
+                    continue;
+                }
+                let next = unvisited[pick(&mut rng, unvisited.len())];
+                visited[next[0]][next[1]] = true;
+                carve(&mut tiles, cell, next);
+                stack.push(next);
+            }
+        }
+        MazeAlgorithm::Prim => {
+            let mut frontier: Vec<([usize; 2], [usize; 2])> =
+                neighbours(start).map(|cell| (start, cell)).collect();
+            while !frontier.is_empty() {
+                let (from, cell) = frontier.swap_remove(pick(&mut rng, frontier.len()));
+                if visited[cell[0]][cell[1]] {
+                    continue;
+                }
+                visited[cell[0]][cell[1]] = true;
+                carve(&mut tiles, from, cell);
+                frontier.extend(
+                    neighbours(cell)
+                        .filter(|&[x, y]| !visited[x][y])
+                        .map(|next| (cell, next)),
+                );
+            }
+        }
+        MazeAlgorithm::Wilson => {
+            // Direction taken when a cell was last left, overwriting it erases loops
+            let mut walk: Vec<Vec<Option<[usize; 2]>>> = vec![vec![None; depth]; width];
+            for x in 0..width {
+                for y in 0..depth {
+                    if visited[x][y] {
+                        continue;
+                    }
+                    let mut cell = [x, y];
+                    while !visited[cell[0]][cell[1]] {
+                        let options: Vec<[usize; 2]> = neighbours(cell).collect();
+                        let next = options[pick(&mut rng, options.len())];
+                        walk[cell[0]][cell[1]] = Some(next);
+                        cell = next;
+                    }
+                    let mut cell = [x, y];
+                    while !visited[cell[0]][cell[1]] {
+                        let Some(next) = walk[cell[0]][cell[1]] else {
+                            break;
+                        };
+                        visited[cell[0]][cell[1]] = true;
+                        carve(&mut tiles, cell, next);
+                        cell = next;
+                    }
+                }
+            }
+        }
+    }
+
+    if maze.braid > 0.0 {
+        for x in 0..width {
+            for y in 0..depth {
+                let cell = [x, y];
+                let is_open = |tiles: &[Vec<Tile>], next: [usize; 2]| {
+                    tiles[cell[0] + next[0] + 1][cell[1] + next[1] + 1] == Tile::Floor
+                };
+                let closed: Vec<[usize; 2]> = neighbours(cell)
+                    .filter(|&next| !is_open(&tiles, next))
+                    .collect();
+                let openings = neighbours(cell).count() - closed.len();
+                if openings != 1 || closed.is_empty() || rng.next_f64() * 100.0 >= maze.braid {
+                    continue;
+                }
+                let next = closed[pick(&mut rng, closed.len())];
+                carve(&mut tiles, cell, next);
+            }
+        }
+    }
+
+    MazeLayout { tiles }
+}
@@ -0,0 +1,552 @@
+//! Generate lock-and-key mission graphs
+//! # Example
+//! For configuration, see [`Mission`](struct.Mission.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::mission::{MissionBundle, MissionPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(MissionPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(MissionBundle::default());
+//! }
+//! ```
+use std::collections::{HashSet, VecDeque};
+
+use bevy::{prelude::*, render::render_resource::PrimitiveTopology};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dungeon::{carve_segment, surround_with_walls, Corridor, DungeonLayout, Room, Tile, TileMesh},
+    headless::DataOnly,
+    util::{export_model, Rng},
+};
+
+/// Number of attempts to realize a mission graph as a dungeon before giving up
+const REALIZE_ATTEMPTS: u32 = 16;
+
+/// Rewriting rule of the mission graph grammar
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MissionRule {
+    /// Splits a random path with a new room
+    Extend,
+    /// Adds a dead-end room next to a random room
+    Branch,
+    /// Locks a random door and places its key in a room reachable without passing the door
+    LockAndKey,
+    /// Adds a dead-end treasure room next to a random room
+    Treasure,
+}
+
+/// Role of a node in the mission graph
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NodeKind {
+    /// Start of the mission
+    Entrance,
+    /// Room without special role
+    Room,
+    /// Room containing the key with the given index
+    Key(usize),
+    /// Room containing a reward
+    Treasure,
+    /// Room of the final encounter, always right before `Goal`
+    Boss,
+    /// End of the mission
+    Goal,
+}
+
+/// Node of the mission graph
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissionNode {
+    /// Role of the node
+    pub kind: NodeKind,
+    /// Number of edges between the node and the entrance
+    pub depth: u32,
+}
+
+/// Door between two nodes of the mission graph
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissionEdge {
+    /// Index of the node closer to the entrance
+    pub from: usize,
+    /// Index of the node further from the entrance
+    pub to: usize,
+    /// Index of the key opening the door, `None` if the door is not locked
+    pub lock: Option<usize>,
+}
+
+/// Component for mission configuration
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Mission {
+    /// Seed used to apply the rules and lay out the dungeon
+    pub seed: u32,
+    /// Rules applied in order to the initial graph `Entrance -> Boss -> Goal`
+    pub rules: Vec<MissionRule>,
+    /// If true, the graph is realized as a `DungeonLayout`
+    pub realize: bool,
+    /// Minimum and maximum side length of a room in tiles
+    pub room_size: [u32; 2],
+    /// Width of corridors in tiles
+    pub corridor_width: u32,
+    /// If true, extrudes floor and wall mesh written to `PbrBundle`
+    pub mesh: bool,
+    /// Size of a tile in world units
+    pub tile_size: f32,
+    /// Height of the walls in world units
+    pub wall_height: f32,
+    /// Color of floor tiles
+    pub floor_color: [u8; 4],
+    /// Color of wall tiles
+    pub wall_color: [u8; 4],
+    /// If true, renders mission mesh as wireframe
+    pub wireframe: bool,
+    /// If true, exports model in glb format
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for Mission {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            rules: vec![
+                MissionRule::Extend,
+                MissionRule::Extend,
+                MissionRule::Extend,
+                MissionRule::LockAndKey,
+                MissionRule::Extend,
+                MissionRule::Branch,
+                MissionRule::LockAndKey,
+                MissionRule::Extend,
+                MissionRule::Branch,
+                MissionRule::LockAndKey,
+                MissionRule::Treasure,
+                MissionRule::Treasure,
+            ],
+            realize: true,
+            room_size: [4, 8],
+            corridor_width: 1,
+            mesh: true,
+            tile_size: 0.1,
+            wall_height: 0.2,
+            floor_color: [150, 140, 120, 255],
+            wall_color: [80, 75, 70, 255],
+            wireframe: false,
+            export: false,
+        }
+    }
+}
+
+/// Generated mission, inserted on the entity with the `Mission` component
+///
+/// If `Mission::realize` is true, a `DungeonLayout` is inserted as well,
+/// with `rooms[i]` realizing `nodes[i]` and `corridors[i]` realizing `edges[i]`
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissionGraph {
+    /// Nodes of the graph, the first node is the entrance
+    pub nodes: Vec<MissionNode>,
+    /// Doors connecting the nodes, the graph is a tree
+    pub edges: Vec<MissionEdge>,
+    /// Number of keys
+    pub keys: usize,
+}
+
+impl MissionGraph {
+    /// Applies `rules` in order to the initial graph `Entrance -> Boss -> Goal`
+    #[must_use]
+    pub fn generate(rules: &[MissionRule], seed: u32) -> Self {
+        let mut rng = Rng::new(u64::from(seed));
+        let mut graph = Self {
+            nodes: [NodeKind::Entrance, NodeKind::Boss, NodeKind::Goal]
+                .map(|kind| MissionNode { kind, depth: 0 })
+                .to_vec(),
+            edges: vec![
+                MissionEdge {
+                    from: 0,
+                    to: 1,
+                    lock: None,
+                },
+                MissionEdge {
+                    from: 1,
+                    to: 2,
+                    lock: None,
+                },
+            ],
+            keys: 0,
+        };
+        for rule in rules {
+            match rule {
+                MissionRule::Extend => {
+                    // The boss stays right before the goal
+                    let edges: Vec<usize> = (0..graph.edges.len())
+                        .filter(|&edge| graph.nodes[graph.edges[edge].to].kind != NodeKind::Goal)
+                        .collect();
+                    let edge = edges[pick(&mut rng, edges.len())];
+                    let room = graph.push_node(NodeKind::Room);
+                    let to = graph.edges[edge].to;
+                    graph.edges[edge].to = room;
+                    graph.edges.push(MissionEdge {
+                        from: room,
+                        to,
+                        lock: None,
+                    });
+                }
+                MissionRule::Branch | MissionRule::Treasure => {
+                    let kind = if *rule == MissionRule::Branch {
+                        NodeKind::Room
+                    } else {
+                        NodeKind::Treasure
+                    };
+                    let parents: Vec<usize> = (0..graph.nodes.len())
+                        .filter(|&node| graph.can_branch(node))
+                        .collect();
+                    if parents.is_empty() {
+                        continue;
+                    }
+                    let parent = parents[pick(&mut rng, parents.len())];
+                    graph.push_child(parent, kind);
+                }
+                MissionRule::LockAndKey => {
+                    let edges: Vec<usize> = (0..graph.edges.len())
+                        .filter(|&edge| graph.edges[edge].lock.is_none())
+                        .collect();
+                    if edges.is_empty() {
+                        continue;
+                    }
+                    let edge = edges[pick(&mut rng, edges.len())];
+                    let key = graph.keys;
+                    graph.keys += 1;
+                    graph.edges[edge].lock = Some(key);
+                    let reachable = graph.reachable();
+                    let parents: Vec<usize> = (0..graph.nodes.len())
+                        .filter(|&node| reachable[node] && graph.can_branch(node))
+                        .collect();
+                    if parents.is_empty() {
+                        graph.edges[edge].lock = None;
+                        graph.keys -= 1;
+                        continue;
+                    }
+                    let parent = parents[pick(&mut rng, parents.len())];
+                    graph.push_child(parent, NodeKind::Key(key));
+                }
+            }
+        }
+        graph.update_depths();
+        graph
+    }
+
+    /// Returns true if every node can be reached from the entrance by collecting keys on the way
+    #[must_use]
+    pub fn is_solvable(&self) -> bool {
+        self.reachable().into_iter().all(|reached| reached)
+    }
+
+    /// Lays out the graph as a dungeon, `rooms[i]` realizes `nodes[i]` and `corridors[i]` realizes `edges[i]`.
+    /// Corridors only connect the rooms of their edge, so locked doors can not be bypassed.
+    /// `None` if the rooms could not be placed
+    #[must_use]
+    pub fn realize(
+        &self,
+        seed: u32,
+        room_size: [u32; 2],
+        corridor_width: u32,
+    ) -> Option<DungeonLayout> {
+        (0..REALIZE_ATTEMPTS).find_map(|attempt| {
+            self.try_realize(seed.wrapping_add(attempt), room_size, corridor_width)
+        })
+    }
+
+    fn try_realize(
+        &self,
+        seed: u32,
+        room_size: [u32; 2],
+        corridor_width: u32,
+    ) -> Option<DungeonLayout> {
+        let mut rng = Rng::new(u64::from(seed));
+        let min_room = room_size[0].max(1);
+        let max_room = room_size[1].max(min_room);
+        // Every room gets a cell of a coarse grid, leaving space for walls between rooms
+        let cell_size = max_room + 3;
+
+        // Nodes are placed breadth first in line with their parent, failing if the parent is enclosed
+        let mut occupied: HashSet<[i32; 2]> = HashSet::from([[0, 0]]);
+        let mut cells = vec![[0_i32; 2]; self.nodes.len()];
+        let mut queue = VecDeque::from([0]);
+        while let Some(node) = queue.pop_front() {
+            for edge in self.edges.iter().filter(|edge| edge.from == node) {
+                let mut directions = [[1, 0], [-1, 0], [0, 1], [0, -1]];
+                for i in (1..directions.len()).rev() {
+                    directions.swap(i, rng.range(0, i as u32) as usize);
+                }
+                // Rooms further away are connected through the empty cells in between
+                let (distance, [dx, dy]) = (1..=3)
+                    .flat_map(|distance| directions.map(|direction| (distance, direction)))
+                    .find(|&(distance, [dx, dy])| {
+                        (1..=distance).all(|step| {
+                            !occupied
+                                .contains(&[cells[node][0] + dx * step, cells[node][1] + dy * step])
+                        })
+                    })?;
+                for step in 1..distance {
+                    occupied.insert([cells[node][0] + dx * step, cells[node][1] + dy * step]);
+                }
+                let cell = [
+                    cells[node][0] + dx * distance,
+                    cells[node][1] + dy * distance,
+                ];
+                occupied.insert(cell);
+                cells[edge.to] = cell;
+                queue.push_back(edge.to);
+            }
+        }
+
+        let min = [0, 1].map(|axis| cells.iter().map(|cell| cell[axis]).min().unwrap_or(0));
+        let max = [0, 1].map(|axis| cells.iter().map(|cell| cell[axis]).max().unwrap_or(0));
+        let size = [0, 1].map(|axis| (max[axis] - min[axis] + 1) as u32 * cell_size + 2);
+        let mut tiles = vec![vec![Tile::Empty; size[1] as usize]; size[0] as usize];
+
+        let rooms: Vec<Room> = cells
+            .iter()
+            .map(|cell| {
+                let center = [0, 1]
+                    .map(|axis| (cell[axis] - min[axis]) as u32 * cell_size + 1 + cell_size / 2);
+                let room_size = [rng.range(min_room, max_room), rng.range(min_room, max_room)];
+                Room {
+                    position: [0, 1].map(|axis| center[axis] - room_size[axis] / 2),
+                    size: room_size,
+                }
+            })
+            .collect();
+        for room in &rooms {
+            for column in tiles
+                .iter_mut()
+                .skip(room.position[0] as usize)
+                .take(room.size[0] as usize)
+            {
+                for tile in column
+                    .iter_mut()
+                    .skip(room.position[1] as usize)
+                    .take(room.size[1] as usize)
+                {
+                    *tile = Tile::Floor;
+                }
+            }
+        }
+
+        // Connected rooms share their center row or column, so corridors are straight
+        let corridors: Vec<Corridor> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let path = vec![rooms[edge.from].center(), rooms[edge.to].center()];
+                carve_segment(&mut tiles, path[0], path[1], corridor_width);
+                Corridor {
+                    rooms: [edge.from, edge.to],
+                    path,
+                }
+            })
+            .collect();
+        surround_with_walls(&mut tiles, 1);
+
+        Some(DungeonLayout {
+            tiles,
+            rooms,
+            corridors,
+        })
+    }
+
+    fn push_node(&mut self, kind: NodeKind) -> usize {
+        self.nodes.push(MissionNode { kind, depth: 0 });
+        self.nodes.len() - 1
+    }
+
+    fn push_child(&mut self, parent: usize, kind: NodeKind) {
+        let child = self.push_node(kind);
+        self.edges.push(MissionEdge {
+            from: parent,
+            to: child,
+            lock: None,
+        });
+    }
+
+    /// Side rooms are not attached to the boss or the goal, or to rooms with three doors
+    fn can_branch(&self, node: usize) -> bool {
+        let doors = self
+            .edges
+            .iter()
+            .filter(|edge| edge.from == node || edge.to == node)
+            .count();
+        doors < 3 && !matches!(self.nodes[node].kind, NodeKind::Boss | NodeKind::Goal)
+    }
+
+    /// Nodes reachable from the entrance, opening locked doors once their key is reached
+    fn reachable(&self) -> Vec<bool> {
+        let mut reached = vec![false; self.nodes.len()];
+        let mut keys = vec![false; self.keys];
+        reached[0] = true;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for edge in &self.edges {
+                let open = edge.lock.is_none_or(|key| keys[key]);
+                if !open || reached[edge.from] == reached[edge.to] {
+                    continue;
+                }
+                for node in [edge.from, edge.to] {
+                    reached[node] = true;
+                    if let NodeKind::Key(key) = self.nodes[node].kind {
+                        keys[key] = true;
+                    }
+                }
+                changed = true;
+            }
+        }
+        reached
+    }
+
+    fn update_depths(&mut self) {
+        let mut queue = VecDeque::from([0]);
+        while let Some(node) = queue.pop_front() {
+            for edge in self.edges.iter().filter(|edge| edge.from == node) {
+                self.nodes[edge.to].depth = self.nodes[node].depth + 1;
+                queue.push_back(edge.to);
+            }
+        }
+    }
+}
+
+/// Random index below `len`
+fn pick(rng: &mut Rng, len: usize) -> usize {
+    rng.range(0, len.saturating_sub(1) as u32) as usize
+}
+
+/// Render `Mission` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct MissionBundle {
+    /// Mission configuration
+    pub mission: Mission,
+    /// Generated mesh data is written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate missions
+pub struct MissionPlugin;
+
+impl Plugin for MissionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_mission);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_mission(
+    mut commands: Commands,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Mission,
+            Option<&mut Handle<Mesh>>,
+            Option<&Handle<StandardMaterial>>,
+            Has<DataOnly>,
+        ),
+        Changed<Mission>,
+    >,
+) {
+    for (entity, mut mission, mesh_handle, material, data_only) in &mut query {
+        let graph = MissionGraph::generate(&mission.rules, mission.seed);
+        let layout = if mission.realize {
+            graph.realize(mission.seed, mission.room_size, mission.corridor_width)
+        } else {
+            None
+        };
+
+        // Without the render assets only the layout is generated
+        if let (true, Some(layout), Some(meshes)) =
+            (mission.mesh && !data_only, &layout, meshes.as_deref_mut())
+        {
+            if let Some(material) = materials
+                .as_deref_mut()
+                .zip(material)
+                .and_then(|(materials, material)| materials.get_mut(material))
+            {
+                *material = StandardMaterial {
+                    perceptual_roughness: 0.9,
+                    ..default()
+                };
+            }
+            let TileMesh {
+                positions,
+                normals,
+                uvs,
+                colors,
+                mut indices,
+            } = TileMesh::extrude(
+                &layout.tiles,
+                mission.tile_size,
+                mission.wall_height,
+                mission.floor_color,
+                mission.wall_color,
+            );
+
+            if mission.wireframe {
+                let triangle_number = indices.len() / 3;
+                let cloned_indices = indices.clone();
+                indices = vec![];
+                for i in 0..triangle_number {
+                    for j in &[0, 1, 1, 2, 2, 0] {
+                        indices.push(cloned_indices[i * 3 + j]);
+                    }
+                }
+            }
+
+            let mut mesh = if mission.wireframe {
+                Mesh::new(PrimitiveTopology::LineList)
+            } else {
+                Mesh::new(PrimitiveTopology::TriangleList)
+            };
+            mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+            if let Some(mut mesh_handle) = mesh_handle {
+                *mesh_handle = meshes.add(mesh);
+            }
+
+            if mission.export {
+                export_model(&positions, indices, &colors);
+                mission.bypass_change_detection().export = false;
+            }
+        }
+
+        let mut entity = commands.entity(entity);
+        entity.insert(graph);
+        if let Some(layout) = layout {
+            entity.insert(layout);
+        }
+    }
+}
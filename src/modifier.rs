@@ -0,0 +1,584 @@
+//! Modifiers applied to generated noise maps
+//!
+//! Modifiers are applied in order after the noise map has been generated,
+//! see [`Noise::modifiers`](../noise/struct.Noise.html#structfield.modifiers).
+//! Noise map values are percentages (0 to 100), modifiers keep them in that range.
+#[cfg(any(feature = "terrain", feature = "map"))]
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(feature = "terrain", feature = "map"))]
+use crate::noise::NoiseValue;
+use crate::{multi_noise::MultiNoise, util::Rng};
+
+/// Modifier applied to a noise map
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Modifier {
+    /// Stamp impact craters onto the noise map
+    Craters(Craters),
+    /// Replace the large scale shape of the noise map with drifting continental plates
+    Tectonics(Tectonics),
+    /// Replace the noise map with named noise channels combined through splines
+    MultiNoise(MultiNoise),
+    /// Add wind-driven sand dunes to the noise map
+    Dunes(Dunes),
+    /// Blur the noise map, optionally only some values and slopes
+    Smooth(Smooth),
+}
+
+impl Modifier {
+    #[cfg(any(feature = "terrain", feature = "map"))]
+    pub(crate) fn apply<T: NoiseValue>(&self, noise_map: &mut [Vec<T>]) {
+        match self {
+            Self::Craters(craters) => craters.apply(noise_map),
+            Self::Tectonics(tectonics) => tectonics.apply(noise_map),
+            Self::MultiNoise(multi_noise) => multi_noise.apply(noise_map),
+            Self::Dunes(dunes) => dunes.apply(noise_map),
+            Self::Smooth(smooth) => smooth.apply(noise_map),
+        }
+    }
+
+    /// Adds `offset` to every seed of the modifier
+    pub(crate) fn offset_seed(&mut self, offset: u32) {
+        match self {
+            Self::Craters(Craters { seed, .. })
+            | Self::Tectonics(Tectonics { seed, .. })
+            | Self::Dunes(Dunes { seed, .. }) => *seed = seed.wrapping_add(offset),
+            Self::MultiNoise(multi_noise) => {
+                for channel in &mut multi_noise.channels {
+                    channel.seed = channel.seed.wrapping_add(offset);
+                }
+            }
+            Self::Smooth(_) => {}
+        }
+    }
+}
+
+/// Crater stamping configuration, used for moons, asteroids and impact sites
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Craters {
+    /// Seed used to place the craters
+    pub seed: u32,
+    /// Number of craters
+    pub count: usize,
+    /// Minimum and maximum crater radius, relative to the size of the surface
+    pub radius: [f64; 2],
+    /// Exponent of the radius distribution.
+    /// Higher values result in more small craters
+    pub size_distribution: f64,
+    /// Depth of the largest crater bowl, in percent.
+    /// Smaller craters are shallower
+    pub depth: f64,
+    /// Height of the rim around the largest crater, in percent
+    pub rim_height: f64,
+    /// Width of the rim, relative to the crater radius
+    pub rim_width: f64,
+}
+
+impl Default for Craters {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            count: 30,
+            radius: [0.02, 0.15],
+            size_distribution: 3.0,
+            depth: 20.0,
+            rim_height: 6.0,
+            rim_width: 0.2,
+        }
+    }
+}
+
+/// Crater sampled from a `Craters` configuration.
+/// Position and radius are in the range 0 to 1
+#[derive(Clone, Copy)]
+pub(crate) struct Crater {
+    pub(crate) position: [f64; 2],
+    pub(crate) radius: f64,
+}
+
+impl Craters {
+    pub(crate) fn sample(&self) -> Vec<Crater> {
+        let mut rng = Rng::new(u64::from(self.seed));
+        (0..self.count)
+            .map(|_| {
+                let position = [rng.next_f64(), rng.next_f64()];
+                let t = rng
+                    .next_f64()
+                    .powf(self.size_distribution.max(f64::EPSILON));
+                let radius = (self.radius[1] - self.radius[0]).mul_add(t, self.radius[0]);
+                Crater { position, radius }
+            })
+            .collect()
+    }
+
+    /// Height offset in percent at `distance` from the center of `crater`,
+    /// with `distance` measured in the same units as the crater radius
+    pub(crate) fn offset(&self, crater: &Crater, distance: f64) -> f64 {
+        let largest = self.radius[0].max(self.radius[1]);
+        if crater.radius <= 0.0 || largest <= 0.0 {
+            return 0.0;
+        }
+        let d = distance / crater.radius;
+        let rim_width = self.rim_width.max(f64::EPSILON);
+        if d > rim_width.mul_add(3.0, 1.0) {
+            return 0.0;
+        }
+        let bowl = if d < 1.0 {
+            d.mul_add(d, -1.0) * self.depth
+        } else {
+            0.0
+        };
+        let rim = self.rim_height * (-((d - 1.0) / rim_width).powi(2)).exp();
+        (bowl + rim) * crater.radius / largest
+    }
+
+    /// Height offset in percent at a point on the unit sphere
+    pub(crate) fn offset_at_direction(&self, craters: &[Crater], direction: [f64; 3]) -> f64 {
+        craters
+            .iter()
+            .map(|crater| {
+                // Uniformly distributed point on the sphere
+                let z = crater.position[0].mul_add(2.0, -1.0);
+                let phi = crater.position[1] * std::f64::consts::TAU;
+                let r = z.mul_add(-z, 1.0).sqrt();
+                let center = [r * phi.cos(), r * phi.sin(), z];
+                // Chord distance, radius is relative to the diameter of the sphere
+                let distance = (direction[2] - center[2])
+                    .mul_add(
+                        direction[2] - center[2],
+                        (direction[1] - center[1])
+                            .mul_add(direction[1] - center[1], (direction[0] - center[0]).powi(2)),
+                    )
+                    .sqrt()
+                    / 2.0;
+                self.offset(crater, distance)
+            })
+            .sum()
+    }
+
+    #[cfg(any(feature = "terrain", feature = "map"))]
+    fn apply<T: NoiseValue>(&self, noise_map: &mut [Vec<T>]) {
+        let width = noise_map.len();
+        let depth = noise_map.first().map_or(0, Vec::len);
+        if width == 0 || depth == 0 {
+            return;
+        }
+        let size = (width.min(depth) - 1).max(1) as f64;
+        for crater in self.sample() {
+            let center = [
+                crater.position[0] * (width - 1) as f64,
+                crater.position[1] * (depth - 1) as f64,
+            ];
+            let reach = crater.radius * size * self.rim_width.mul_add(3.0, 1.0);
+            let min_x = (center[0] - reach).floor().max(0.0) as usize;
+            let max_x = ((center[0] + reach).ceil() as usize).min(width - 1);
+            let min_y = (center[1] - reach).floor().max(0.0) as usize;
+            let max_y = ((center[1] + reach).ceil() as usize).min(depth - 1);
+            for (x, row) in noise_map.iter_mut().enumerate().take(max_x + 1).skip(min_x) {
+                for (y, value) in row.iter_mut().enumerate().take(max_y + 1).skip(min_y) {
+                    let distance = (x as f64 - center[0]).hypot(y as f64 - center[1]) / size;
+                    let offset = (*value).into() + self.offset(&crater, distance);
+                    *value = T::from_f64(offset.clamp(0.0, 100.0));
+                }
+            }
+        }
+    }
+}
+
+/// Plate tectonics configuration, used for geologically plausible continents.
+///
+/// Plates drift for a number of steps, colliding plates push up mountain ranges
+/// and oceanic plates sink into trenches where they meet continents or drift apart.
+/// The noise map is kept as detail on top of the plates, so terrain usually needs a higher
+/// [`Terrain::sea_percent`](../terrain/struct.Terrain.html#structfield.sea_percent), around 35
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Tectonics {
+    /// Seed used to place the plates
+    pub seed: u32,
+    /// Number of plates
+    pub plates: usize,
+    /// Fraction of the plates that are continental, 0 to 1
+    pub continental: f64,
+    /// Number of drift steps, older boundaries leave fainter ranges
+    pub steps: usize,
+    /// Maximum distance a plate drifts per step, relative to the size of the map
+    pub speed: f64,
+    /// Height of mountain ranges where plates collide, in percent
+    pub mountain_height: f64,
+    /// Depth of trenches and rifts, in percent
+    pub trench_depth: f64,
+    /// Width of the area around a boundary raised or lowered by it, relative to the size of the map
+    pub boundary_width: f64,
+    /// Amount of the noise map kept as detail, 0 to 1
+    pub detail: f64,
+    /// Distortion of the plate boundaries by the noise map, relative to the size of the map
+    pub warp: f64,
+}
+
+impl Default for Tectonics {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            plates: 10,
+            continental: 0.4,
+            steps: 6,
+            speed: 0.02,
+            mountain_height: 35.0,
+            trench_depth: 20.0,
+            boundary_width: 0.05,
+            detail: 0.5,
+            warp: 0.1,
+        }
+    }
+}
+
+/// Plate sampled from a `Tectonics` configuration, in map units where the shorter side is 1
+#[cfg(any(feature = "terrain", feature = "map"))]
+struct Plate {
+    center: [f64; 2],
+    velocity: [f64; 2],
+    continental: bool,
+}
+
+#[cfg(any(feature = "terrain", feature = "map"))]
+impl Tectonics {
+    fn sample(&self, extent: [f64; 2]) -> Vec<Plate> {
+        let mut rng = Rng::new(u64::from(self.seed));
+        (0..self.plates.max(1))
+            .map(|_| {
+                let center = [rng.next_f64() * extent[0], rng.next_f64() * extent[1]];
+                let angle = rng.next_f64() * std::f64::consts::TAU;
+                let speed = rng.next_f64() * self.speed;
+                Plate {
+                    center,
+                    velocity: [angle.cos() * speed, angle.sin() * speed],
+                    continental: rng.next_f64() < self.continental,
+                }
+            })
+            .collect()
+    }
+
+    /// Elevation in percent of a cell of `plate` at `distance` from its boundary with `other`
+    fn elevation(&self, plates: &[Plate], plate: usize, other: usize, distance: f64) -> f64 {
+        let [a, b] = [&plates[plate], &plates[other]];
+        let base = |plate: &Plate| if plate.continental { 50.0 } else { 15.0 };
+        let falloff = (-(distance / self.boundary_width.max(f64::EPSILON)).powi(2)).exp();
+        // Average the bases at the boundary so shelves are continuous
+        let shelf = (base(b) - base(a)) / 2.0 * falloff;
+
+        let normal = [b.center[0] - a.center[0], b.center[1] - a.center[1]];
+        let length = normal[0].hypot(normal[1]).max(f64::EPSILON);
+        let relative = [b.velocity[0] - a.velocity[0], b.velocity[1] - a.velocity[1]];
+        // Positive where the plates move towards each other, -1 to 1
+        let closing = -relative[0].mul_add(normal[0], relative[1] * normal[1])
+            / length
+            / (2.0 * self.speed).max(f64::EPSILON);
+        let uplift = if closing > 0.0 {
+            match (a.continental, b.continental) {
+                (true, _) => self.mountain_height,
+                (false, true) => -self.trench_depth,
+                // The plate with the lower index sinks below the other into a trench next to an island arc
+                (false, false) if plate < other => -self.trench_depth,
+                (false, false) => self.mountain_height / 2.0,
+            }
+        } else {
+            self.trench_depth / 2.0
+        };
+        (uplift * closing).mul_add(falloff, base(a) + shelf)
+    }
+
+    fn apply<T: NoiseValue>(&self, noise_map: &mut [Vec<T>]) {
+        let width = noise_map.len();
+        let depth = noise_map.first().map_or(0, Vec::len);
+        if width == 0 || depth == 0 {
+            return;
+        }
+        let size = (width.min(depth) - 1).max(1) as f64;
+        let extent = [(width - 1) as f64 / size, (depth - 1) as f64 / size];
+        let mut plates = self.sample(extent);
+        let steps = self.steps.max(1);
+        let mut elevation = vec![vec![0.0; depth]; width];
+        // Later steps weigh more, so the current boundaries are the most pronounced
+        let total_weight = (steps * (steps + 1) / 2) as f64;
+        for step in 0..steps {
+            let weight = (step + 1) as f64 / total_weight;
+            for (x, column) in elevation.iter_mut().enumerate() {
+                for (y, value) in column.iter_mut().enumerate() {
+                    let warp = |value: f64| (value / 50.0 - 1.0) * self.warp;
+                    let point = [
+                        x as f64 / size + warp(noise_map[x][y].into()),
+                        y as f64 / size + warp(noise_map[width - 1 - x][depth - 1 - y].into()),
+                    ];
+                    let squared = |plate: &Plate| {
+                        (point[1] - plate.center[1]).mul_add(
+                            point[1] - plate.center[1],
+                            (point[0] - plate.center[0]).powi(2),
+                        )
+                    };
+                    let [mut nearest, mut second] = [0, usize::MAX];
+                    for (index, plate) in plates.iter().enumerate().skip(1) {
+                        if squared(plate) < squared(&plates[nearest]) {
+                            second = nearest;
+                            nearest = index;
+                        } else if second == usize::MAX || squared(plate) < squared(&plates[second])
+                        {
+                            second = index;
+                        }
+                    }
+                    let height = if second == usize::MAX {
+                        if plates[nearest].continental {
+                            50.0
+                        } else {
+                            15.0
+                        }
+                    } else {
+                        let [a, b] = [&plates[nearest], &plates[second]];
+                        // Distance to the bisector between the two nearest plates
+                        let distance = (squared(b) - squared(a))
+                            / (2.0 * (b.center[0] - a.center[0]).hypot(b.center[1] - a.center[1]))
+                                .max(f64::EPSILON);
+                        self.elevation(&plates, nearest, second, distance)
+                    };
+                    *value += height * weight;
+                }
+            }
+            for plate in &mut plates {
+                plate.center[0] += plate.velocity[0];
+                plate.center[1] += plate.velocity[1];
+            }
+        }
+        let detail = self.detail.clamp(0.0, 1.0);
+        for (column, elevation) in noise_map.iter_mut().zip(elevation) {
+            for (value, elevation) in column.iter_mut().zip(elevation) {
+                let detail = ((*value).into() - 50.0) * detail;
+                *value = T::from_f64((elevation + detail).clamp(0.0, 100.0));
+            }
+        }
+    }
+}
+
+/// Sand dune configuration, used for deserts.
+///
+/// Dunes are ridges across the wind with a long gentle windward slope and a short steep slip face
+/// on the lee side. Their crests meander and their height varies, so they do not look tiled
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Dunes {
+    /// Seed of the meandering and height variation
+    pub seed: u32,
+    /// Direction the wind blows towards in degrees, 0 is `+x` and 90 is `+z`
+    pub wind_direction: f64,
+    /// Distance between crests, relative to the size of the map
+    pub wavelength: f64,
+    /// Height of the dunes from trough to crest, in percent
+    pub height: f64,
+    /// Fraction of a dune taken up by the windward slope, 0.5 to 1.
+    /// Higher values result in steeper slip faces
+    pub asymmetry: f64,
+    /// Sideways distortion of the crests, relative to the wavelength
+    pub meander: f64,
+    /// Variation of the dune height, 0 to 1
+    pub variation: f64,
+    /// Noise map values the dunes appear between, in percent
+    pub range: [f64; 2],
+    /// Width of the transition at the ends of `range`, in percent
+    pub fade: f64,
+}
+
+impl Default for Dunes {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            wind_direction: 0.0,
+            wavelength: 0.05,
+            height: 6.0,
+            asymmetry: 0.8,
+            meander: 0.4,
+            variation: 0.5,
+            range: [0.0, 100.0],
+            fade: 5.0,
+        }
+    }
+}
+
+#[cfg(any(feature = "terrain", feature = "map"))]
+impl Dunes {
+    /// Height of a dune from 0 to 1 at `phase` wavelengths downwind of a trough
+    fn profile(&self, phase: f64) -> f64 {
+        let asymmetry = self.asymmetry.clamp(0.5, 1.0 - f64::EPSILON);
+        let phase = phase.rem_euclid(1.0);
+        if phase < asymmetry {
+            (1.0 - (phase / asymmetry * std::f64::consts::PI).cos()) / 2.0
+        } else {
+            // Slip faces are straight, sand slides down them at its angle of repose
+            (1.0 - phase) / (1.0 - asymmetry)
+        }
+    }
+
+    fn apply<T: NoiseValue>(&self, noise_map: &mut [Vec<T>]) {
+        let width = noise_map.len();
+        let depth = noise_map.first().map_or(0, Vec::len);
+        if width == 0 || depth == 0 {
+            return;
+        }
+        let size = (width.min(depth) - 1).max(1) as f64;
+        let wavelength = self.wavelength.max(f64::EPSILON);
+        let (sin, cos) = self.wind_direction.to_radians().sin_cos();
+        let meander = Perlin::new(self.seed);
+        let variation = Perlin::new(self.seed.wrapping_add(1));
+        let fade = self.fade.max(f64::EPSILON);
+        for (x, column) in noise_map.iter_mut().enumerate() {
+            for (y, value) in column.iter_mut().enumerate() {
+                let height: f64 = (*value).into();
+                let point = [x as f64 / size, y as f64 / size];
+                let along = point[0].mul_add(cos, point[1] * sin) / wavelength;
+                let across = point[1].mul_add(cos, -point[0] * sin) / wavelength;
+                // Crests are a few wavelengths long before they bend or break
+                let bend = meander.get([across / 4.0, along / 4.0]) * self.meander;
+                let scale = variation.get([across / 3.0, along / 3.0]).mul_add(0.5, 0.5);
+                let amplitude =
+                    self.height * self.variation.clamp(0.0, 1.0).mul_add(scale - 1.0, 1.0);
+                let mask = ((height - self.range[0]) / fade)
+                    .min((self.range[1] - height) / fade)
+                    .clamp(0.0, 1.0);
+                let dune = (self.profile(along + bend) - 0.5) * amplitude * mask;
+                *value = T::from_f64((height + dune).clamp(0.0, 100.0));
+            }
+        }
+    }
+}
+
+/// Weights of the values around a smoothed value
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Kernel {
+    /// Weights fall off with the distance, with a standard deviation of half the radius
+    #[default]
+    Gaussian,
+    /// Every value within the radius has the same weight
+    Box,
+}
+
+/// Smoothing configuration, used to tame noisy high octaves and to level buildable areas.
+///
+/// The mask of `range` and `slope_range` is taken from the map before smoothing
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Smooth {
+    /// Weights of the blur
+    pub kernel: Kernel,
+    /// Radius of the blur in map values
+    pub radius: u32,
+    /// Blend from the original map at 0 to the blurred map at 1
+    pub strength: f64,
+    /// Noise map values smoothed, in percent
+    pub range: [f64; 2],
+    /// Slopes smoothed, in percent per map value. `None` smooths every slope
+    pub slope_range: Option<[f64; 2]>,
+    /// Width of the transition at the ends of `range` and `slope_range`, in percent and percent per map value
+    pub fade: f64,
+}
+
+impl Default for Smooth {
+    fn default() -> Self {
+        Self {
+            kernel: Kernel::Gaussian,
+            radius: 2,
+            strength: 1.0,
+            range: [0.0, 100.0],
+            slope_range: None,
+            fade: 1.0,
+        }
+    }
+}
+
+#[cfg(any(feature = "terrain", feature = "map"))]
+impl Smooth {
+    /// Normalized weights from `-radius` to `radius`
+    fn weights(&self) -> Vec<f64> {
+        let radius = self.radius as i32;
+        let sigma = (f64::from(radius) / 2.0).max(f64::EPSILON);
+        let weights: Vec<f64> = (-radius..=radius)
+            .map(|offset| match self.kernel {
+                Kernel::Gaussian => (-(f64::from(offset).powi(2)) / (2.0 * sigma * sigma)).exp(),
+                Kernel::Box => 1.0,
+            })
+            .collect();
+        let total: f64 = weights.iter().sum();
+        weights.into_iter().map(|weight| weight / total).collect()
+    }
+
+    fn apply<T: NoiseValue>(&self, noise_map: &mut [Vec<T>]) {
+        let width = noise_map.len();
+        let depth = noise_map.first().map_or(0, Vec::len);
+        if width == 0 || depth == 0 || self.radius == 0 {
+            return;
+        }
+        let weights = self.weights();
+        let radius = self.radius as usize;
+        // Separable blur, values beyond the sides repeat the values at the sides
+        let blur = |get: &dyn Fn(usize) -> f64, index: usize, len: usize| {
+            weights
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    let sample = (index + k).saturating_sub(radius).min(len - 1);
+                    get(sample) * weight
+                })
+                .sum::<f64>()
+        };
+        let across: Vec<Vec<f64>> = (0..width)
+            .map(|x| {
+                (0..depth)
+                    .map(|y| blur(&|i| noise_map[i][y].into(), x, width))
+                    .collect()
+            })
+            .collect();
+        let blurred: Vec<Vec<f64>> = (0..width)
+            .map(|x| {
+                (0..depth)
+                    .map(|y| blur(&|j| across[x][j], y, depth))
+                    .collect()
+            })
+            .collect();
+
+        let fade = self.fade.max(f64::EPSILON);
+        let within = |value: f64, [min, max]: [f64; 2]| {
+            ((value - min) / fade)
+                .min((max - value) / fade)
+                .clamp(0.0, 1.0)
+        };
+        let strength = self.strength.clamp(0.0, 1.0);
+        let masks: Vec<Vec<f64>> = (0..width)
+            .map(|x| {
+                (0..depth)
+                    .map(|y| {
+                        let value: f64 = noise_map[x][y].into();
+                        let slope = self.slope_range.map_or(1.0, |slope_range| {
+                            let [left, right] = [x.saturating_sub(1), (x + 1).min(width - 1)];
+                            let [near, far] = [y.saturating_sub(1), (y + 1).min(depth - 1)];
+                            let difference =
+                                |a: T, b: T| Into::<f64>::into(a) - Into::<f64>::into(b);
+                            let dx = difference(noise_map[right][y], noise_map[left][y])
+                                / (right - left).max(1) as f64;
+                            let dy = difference(noise_map[x][far], noise_map[x][near])
+                                / (far - near).max(1) as f64;
+                            within(dx.hypot(dy), slope_range)
+                        });
+                        within(value, self.range) * slope * strength
+                    })
+                    .collect()
+            })
+            .collect();
+        for ((column, blurred), masks) in noise_map.iter_mut().zip(&blurred).zip(&masks) {
+            for ((value, blurred), mask) in column.iter_mut().zip(blurred).zip(masks) {
+                let original: f64 = (*value).into();
+                *value = T::from_f64((original + (blurred - original) * mask).clamp(0.0, 100.0));
+            }
+        }
+    }
+}
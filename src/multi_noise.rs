@@ -0,0 +1,498 @@
+//! Combine named noise channels through splines
+//!
+//! Large worlds are easier to control when height is built from several slow noise channels,
+//! e.g. continentalness for land and sea, erosion for flat and rugged areas and peaks for ridges,
+//! each mapped through a spline, instead of from a single fractal.
+//! # Example
+//! For configuration, see [`MultiNoise`](struct.MultiNoise.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::modifier::Modifier;
+//! use bevy_generative::multi_noise::{MultiNoise, MultiNoiseBiomes, MultiNoisePlugin};
+//! use bevy_generative::terrain::{Terrain, TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, MultiNoisePlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_center)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     let mut terrain = Terrain::default();
+//!     terrain.sea_percent = 35.0;
+//!     terrain.noise.modifiers = vec![Modifier::MultiNoise(MultiNoise::default())];
+//!     commands.spawn(TerrainBundle {
+//!         terrain,
+//!         ..default()
+//!     });
+//! }
+//!
+//! fn print_center(query: Query<&MultiNoiseBiomes, Changed<MultiNoiseBiomes>>) {
+//!     for biomes in &query {
+//!         println!("{:?}", biomes.biome_at(0.0, 0.0));
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(feature = "terrain", feature = "map"))]
+use crate::noise::NoiseValue;
+use crate::noise::{generate_base_noise_map, Function, FunctionName, Method};
+#[cfg(feature = "terrain")]
+use crate::{
+    modifier::Modifier,
+    terrain::{GeneratedTerrain, Terrain},
+};
+
+/// Smooth curve through control points, monotone between neighbouring points
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Spline {
+    /// Input and output of every control point, sorted by input.
+    /// Inputs beyond the first and last point keep their output
+    pub points: Vec<[f64; 2]>,
+}
+
+impl Default for Spline {
+    fn default() -> Self {
+        Self {
+            points: vec![[0.0, 0.0], [100.0, 100.0]],
+        }
+    }
+}
+
+impl Spline {
+    /// Spline through `points`, sorted by input
+    #[must_use]
+    pub fn new(points: &[[f64; 2]]) -> Self {
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        Self { points }
+    }
+
+    /// Output at `input`, 0 without control points
+    #[must_use]
+    pub fn at(&self, input: f64) -> f64 {
+        let points = &self.points;
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            return 0.0;
+        };
+        if input <= first[0] {
+            return first[1];
+        }
+        if input >= last[0] {
+            return last[1];
+        }
+        let segment = points
+            .windows(2)
+            .position(|pair| input < pair[1][0])
+            .unwrap_or(points.len() - 2);
+        let secant = |k: usize| {
+            let [a, b] = [points[k], points[k + 1]];
+            (b[1] - a[1]) / (b[0] - a[0]).max(f64::EPSILON)
+        };
+        // Harmonic mean of the neighbouring secants keeps the curve from overshooting
+        let tangent = |k: usize| {
+            if k == 0 {
+                secant(0)
+            } else if k == points.len() - 1 {
+                secant(k - 1)
+            } else {
+                let [before, after] = [secant(k - 1), secant(k)];
+                if before * after <= 0.0 {
+                    0.0
+                } else {
+                    2.0 / (1.0 / before + 1.0 / after)
+                }
+            }
+        };
+        let [a, b] = [points[segment], points[segment + 1]];
+        let width = b[0] - a[0];
+        let t = (input - a[0]) / width;
+        let (t2, t3) = (t * t, t * t * t);
+        (tangent(segment + 1) * width).mul_add(
+            t3 - t2,
+            b[1].mul_add(
+                (-2.0f64).mul_add(t3, 3.0 * t2),
+                a[1].mul_add(
+                    2.0f64.mul_add(t3, -(3.0 * t2)) + 1.0,
+                    tangent(segment) * width * (2.0f64.mul_add(-t2, t3) + t),
+                ),
+            ),
+        )
+    }
+}
+
+/// Named noise channel, sampled like a noise map with values from 0 to 100
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Channel {
+    /// Name referenced by terms and biome rules
+    pub name: String,
+    /// Seed of the noise
+    pub seed: u32,
+    /// Scale of the noise
+    pub scale: f64,
+    /// Offset of the noise
+    pub offset: [f64; 2],
+    /// Method used to generate noise
+    pub method: Method,
+    /// Function used to generate noise
+    pub function: Function,
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            seed: 0,
+            scale: 100.0,
+            offset: [0.0; 2],
+            method: Method::Perlin,
+            function: Function::default(),
+        }
+    }
+}
+
+/// How a term is combined with the height of the previous terms
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Operation {
+    /// Adds the output of the spline
+    Add,
+    /// Multiplies by the output of the spline
+    Multiply,
+}
+
+/// Channel mapped through a spline and combined with the height
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Term {
+    /// Name of the channel
+    pub channel: String,
+    /// Spline from the channel value to the contribution of the term
+    pub spline: Spline,
+    /// How the contribution is combined with the height
+    pub operation: Operation,
+}
+
+impl Default for Term {
+    fn default() -> Self {
+        Self {
+            channel: String::new(),
+            spline: Spline::default(),
+            operation: Operation::Add,
+        }
+    }
+}
+
+/// Range of values of a channel, inclusive
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelRange {
+    /// Name of the channel
+    pub channel: String,
+    /// Minimum and maximum value
+    pub range: [f64; 2],
+}
+
+impl Default for ChannelRange {
+    fn default() -> Self {
+        Self {
+            channel: String::new(),
+            range: [0.0, 100.0],
+        }
+    }
+}
+
+/// Biome assigned where every range matches
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BiomeRule {
+    /// Label of the biome
+    pub label: String,
+    /// Ranges of the channels, all have to match
+    pub ranges: Vec<ChannelRange>,
+}
+
+/// Multi noise configuration, used as a [`Modifier`](../modifier/enum.Modifier.html) of a noise map.
+///
+/// The height starts at 0 and every term is combined with it in order, the result replaces
+/// the noise map. The noise map being modified is available as the channel `base`,
+/// biome rules can also use the final value as the channel `height`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MultiNoise {
+    /// Noise channels
+    pub channels: Vec<Channel>,
+    /// Terms combined into the height
+    pub terms: Vec<Term>,
+    /// Biome rules, the first matching rule is used
+    pub biomes: Vec<BiomeRule>,
+}
+
+impl Default for MultiNoise {
+    fn default() -> Self {
+        let range = |channel: &str, range: [f64; 2]| ChannelRange {
+            channel: channel.to_string(),
+            range,
+        };
+        Self {
+            channels: vec![
+                Channel {
+                    name: "continentalness".to_string(),
+                    seed: 1,
+                    scale: 300.0,
+                    ..default()
+                },
+                Channel {
+                    name: "erosion".to_string(),
+                    seed: 2,
+                    scale: 150.0,
+                    ..default()
+                },
+                Channel {
+                    name: "peaks".to_string(),
+                    seed: 3,
+                    scale: 40.0,
+                    function: Function {
+                        name: Some(FunctionName::RidgedMulti),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ],
+            terms: vec![
+                Term {
+                    channel: "peaks".to_string(),
+                    spline: Spline::new(&[[0.0, 0.0], [50.0, 5.0], [70.0, 30.0], [100.0, 60.0]]),
+                    operation: Operation::Add,
+                },
+                Term {
+                    channel: "erosion".to_string(),
+                    spline: Spline::new(&[[0.0, 1.5], [40.0, 1.0], [60.0, 0.4], [100.0, 0.1]]),
+                    operation: Operation::Multiply,
+                },
+                Term {
+                    channel: "continentalness".to_string(),
+                    spline: Spline::new(&[
+                        [0.0, 5.0],
+                        [35.0, 20.0],
+                        [45.0, 38.0],
+                        [55.0, 45.0],
+                        [100.0, 60.0],
+                    ]),
+                    operation: Operation::Add,
+                },
+            ],
+            biomes: vec![
+                BiomeRule {
+                    label: "ocean".to_string(),
+                    ranges: vec![range("continentalness", [0.0, 40.0])],
+                },
+                BiomeRule {
+                    label: "mountains".to_string(),
+                    ranges: vec![range("height", [60.0, 100.0])],
+                },
+                BiomeRule {
+                    label: "badlands".to_string(),
+                    ranges: vec![range("erosion", [0.0, 35.0])],
+                },
+                BiomeRule {
+                    label: "plains".to_string(),
+                    ranges: vec![],
+                },
+            ],
+        }
+    }
+}
+
+impl MultiNoise {
+    /// Maps of every channel, in the order of `channels`, with `size + 1` values along each axis
+    #[must_use]
+    pub fn channel_maps(&self, size: [u32; 2]) -> Vec<Vec<Vec<f64>>> {
+        self.channels
+            .iter()
+            .map(|channel| {
+                generate_base_noise_map(
+                    size,
+                    channel.seed,
+                    channel.scale,
+                    channel.offset,
+                    &channel.method,
+                    &channel.function,
+                )
+            })
+            .collect()
+    }
+
+    /// Height from the values of the channels, in the order of `channels`, and the `base` value
+    #[must_use]
+    pub fn height(&self, values: &[f64], base: f64) -> f64 {
+        self.terms.iter().fold(0.0, |height, term| {
+            let Some(value) = self.value(&term.channel, values, base) else {
+                return height;
+            };
+            let output = term.spline.at(value);
+            match term.operation {
+                Operation::Add => height + output,
+                Operation::Multiply => height * output,
+            }
+        })
+    }
+
+    /// Index into `biomes` of the first rule matching the values of the channels and the `height`
+    #[must_use]
+    pub fn biome(&self, values: &[f64], height: f64) -> Option<usize> {
+        self.biomes.iter().position(|rule| {
+            rule.ranges.iter().all(|range| {
+                let value = if range.channel == "height" {
+                    Some(height)
+                } else {
+                    self.value(&range.channel, values, height)
+                };
+                value.is_some_and(|value| (range.range[0]..=range.range[1]).contains(&value))
+            })
+        })
+    }
+
+    fn value(&self, name: &str, values: &[f64], base: f64) -> Option<f64> {
+        if name == "base" {
+            return Some(base);
+        }
+        self.channels
+            .iter()
+            .position(|channel| channel.name == name)
+            .and_then(|index| values.get(index).copied())
+    }
+
+    #[cfg(any(feature = "terrain", feature = "map"))]
+    pub(crate) fn apply<T: NoiseValue>(&self, noise_map: &mut [Vec<T>]) {
+        let Some(size) = map_size(noise_map.len(), noise_map.first().map_or(0, Vec::len)) else {
+            return;
+        };
+        let maps = self.channel_maps(size);
+        for (x, column) in noise_map.iter_mut().enumerate() {
+            for (y, value) in column.iter_mut().enumerate() {
+                let values: Vec<f64> = maps.iter().map(|map| map[x][y]).collect();
+                *value = T::from_f64(self.height(&values, (*value).into()).clamp(0.0, 100.0));
+            }
+        }
+    }
+}
+
+/// Size passed to the noise map generator for a map of `columns` by `rows` values
+#[cfg(any(feature = "terrain", feature = "map"))]
+fn map_size(columns: usize, rows: usize) -> Option<[u32; 2]> {
+    let width = columns.checked_sub(1)?;
+    let depth = rows.checked_sub(1)?;
+    Some([width as u32, depth as u32])
+}
+
+/// Biomes of a terrain with a `MultiNoise` modifier, inserted on the entity with the `Terrain` component.
+/// Updated whenever the terrain changes
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug)]
+pub struct MultiNoiseBiomes {
+    /// Labels of the biomes, indexed by the values in `cells`
+    pub labels: Vec<String>,
+    /// Index into `labels` of every vertex, `None` if no rule matches. Indexed like `GeneratedTerrain::heights`
+    pub cells: Vec<Vec<Option<usize>>>,
+    /// Number of vertices per world unit
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl MultiNoiseBiomes {
+    /// Label of the biome at the vertex nearest to `x`, `z`, `None` outside of the terrain
+    #[must_use]
+    pub fn biome_at(&self, x: f32, z: f32) -> Option<&str> {
+        let resolution = self.resolution as f32;
+        let i = ((x + self.size[0] as f32 / 2.0) * resolution).round();
+        let j = ((z + self.size[1] as f32 / 2.0) * resolution).round();
+        if i < 0.0 || j < 0.0 {
+            return None;
+        }
+        let index = (*self.cells.get(i as usize)?.get(j as usize)?)?;
+        self.labels.get(index).map(String::as_str)
+    }
+}
+
+/// Plugin to classify the biomes of terrain with a `MultiNoise` modifier
+#[cfg(feature = "terrain")]
+pub struct MultiNoisePlugin;
+
+#[cfg(feature = "terrain")]
+impl Plugin for MultiNoisePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, classify_biomes);
+    }
+}
+
+#[cfg(feature = "terrain")]
+#[allow(clippy::needless_pass_by_value)]
+fn classify_biomes(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &Terrain,
+            &GeneratedTerrain,
+            Option<&MultiNoiseBiomes>,
+        ),
+        Changed<GeneratedTerrain>,
+    >,
+) {
+    for (entity, terrain, terrain_data, old) in &query {
+        let noise = terrain.instance_noise();
+        let Some(multi_noise) = noise.modifiers.iter().find_map(|modifier| match modifier {
+            Modifier::MultiNoise(multi_noise) => Some(multi_noise),
+            _ => None,
+        }) else {
+            continue;
+        };
+        let Some(size) = map_size(terrain_data.noise.len(), terrain_data.noise.rows()) else {
+            continue;
+        };
+        let maps = multi_noise.channel_maps(size);
+        let cells = terrain_data
+            .noise
+            .columns()
+            .enumerate()
+            .map(|(x, column)| {
+                column
+                    .enumerate()
+                    .map(|(y, height)| {
+                        let values: Vec<f64> = maps.iter().map(|map| map[x][y]).collect();
+                        multi_noise.biome(&values, height)
+                    })
+                    .collect()
+            })
+            .collect();
+        let biomes = MultiNoiseBiomes {
+            labels: multi_noise
+                .biomes
+                .iter()
+                .map(|rule| rule.label.clone())
+                .collect(),
+            cells,
+            resolution: terrain_data.resolution,
+            size: terrain_data.size,
+        };
+        if old != Some(&biomes) {
+            commands.entity(entity).insert(biomes);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Generate names with Markov chains
+//! # Example
+//! For configuration, see [`NameGenerator`](struct.NameGenerator.html)
+//! ```
+//! use bevy_generative::names::NameGenerator;
+//!
+//! let generator = NameGenerator::new(&["Avalon", "Brigadoon", "Camelot", "Lyonesse"], 2);
+//! let names = generator.names(0, 5);
+//! assert_eq!(names.len(), 5);
+//! assert_eq!(names, generator.names(0, 5));
+//! ```
+use std::collections::HashMap;
+
+use crate::util::Rng;
+
+/// Place names used by `NameGenerator::default`
+const DEFAULT_CORPUS: &[&str] = &[
+    "Aberdale",
+    "Ashford",
+    "Brambleton",
+    "Brightwater",
+    "Caldera",
+    "Carrow",
+    "Dunmere",
+    "Eastvale",
+    "Eldermoor",
+    "Fallowmere",
+    "Fenwick",
+    "Glenhollow",
+    "Greystone",
+    "Hadley",
+    "Harrowgate",
+    "Ironwood",
+    "Kestrel",
+    "Kingsbridge",
+    "Larkspur",
+    "Lindenfall",
+    "Marrowby",
+    "Millbrook",
+    "Northwatch",
+    "Oakheart",
+    "Orlaith",
+    "Pellmar",
+    "Quarrington",
+    "Ravenholm",
+    "Redmarsh",
+    "Rosemoor",
+    "Saltmere",
+    "Silverdale",
+    "Stonecrest",
+    "Thornbury",
+    "Tidewater",
+    "Umberlee",
+    "Valemont",
+    "Westmarch",
+    "Whitcombe",
+    "Wyndham",
+    "Yarrowdale",
+    "Zephyrine",
+];
+
+/// Marker for the start and end of a name in the chain
+const BOUNDARY: char = '\0';
+
+/// Markov chain over the letters of a corpus of names
+///
+/// Every letter is picked based on the `order` letters before it,
+/// so a higher order produces names closer to the corpus.
+#[derive(Clone, Debug)]
+pub struct NameGenerator {
+    /// Minimum length of generated names
+    pub min_length: usize,
+    /// Maximum length of generated names
+    pub max_length: usize,
+    /// If true, names from the corpus are not generated
+    pub unique: bool,
+    order: usize,
+    corpus: Vec<String>,
+    transitions: HashMap<Vec<char>, Vec<(char, u32)>>,
+}
+
+impl Default for NameGenerator {
+    fn default() -> Self {
+        Self::new(DEFAULT_CORPUS, 3)
+    }
+}
+
+impl NameGenerator {
+    /// Trains a chain of the given order on `corpus`
+    #[must_use]
+    pub fn new<S: AsRef<str>>(corpus: &[S], order: usize) -> Self {
+        let order = order.max(1);
+        let corpus: Vec<String> = corpus
+            .iter()
+            .map(|name| name.as_ref().trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+        let mut transitions: HashMap<Vec<char>, Vec<(char, u32)>> = HashMap::new();
+        for name in &corpus {
+            let letters: Vec<char> = std::iter::repeat_n(BOUNDARY, order)
+                .chain(name.chars())
+                .chain([BOUNDARY])
+                .collect();
+            for window in letters.windows(order + 1) {
+                let followers = transitions.entry(window[..order].to_vec()).or_default();
+                match followers
+                    .iter_mut()
+                    .find(|(letter, _)| *letter == window[order])
+                {
+                    Some((_, count)) => *count += 1,
+                    None => followers.push((window[order], 1)),
+                }
+            }
+        }
+        let lengths = corpus.iter().map(|name| name.chars().count());
+        Self {
+            min_length: lengths.clone().min().unwrap_or(3).max(3),
+            max_length: lengths.max().unwrap_or(12).max(3),
+            unique: true,
+            order,
+            corpus,
+            transitions,
+        }
+    }
+
+    /// Generates a single name, the same seed always produces the same name
+    #[must_use]
+    pub fn name(&self, seed: u64) -> String {
+        self.generate(&mut Rng::new(seed))
+    }
+
+    /// Generates `count` names, the same seed always produces the same names
+    #[must_use]
+    pub fn names(&self, seed: u64, count: usize) -> Vec<String> {
+        let mut rng = Rng::new(seed);
+        (0..count).map(|_| self.generate(&mut rng)).collect()
+    }
+
+    fn generate(&self, rng: &mut Rng) -> String {
+        let mut fallback = String::new();
+        for _ in 0..100 {
+            let mut context = vec![BOUNDARY; self.order];
+            let mut name = String::new();
+            while let Some(followers) = self.transitions.get(&context) {
+                let total: u32 = followers.iter().map(|(_, count)| count).sum();
+                let mut pick = (rng.next_f64() * f64::from(total)) as u32;
+                let Some(&(letter, _)) = followers.iter().find(|(_, count)| {
+                    let found = pick < *count;
+                    pick = pick.saturating_sub(*count);
+                    found
+                }) else {
+                    break;
+                };
+                if letter == BOUNDARY || name.chars().count() > self.max_length {
+                    break;
+                }
+                name.push(letter);
+                context.remove(0);
+                context.push(letter);
+            }
+            let length = name.chars().count();
+            if length < self.min_length || length > self.max_length {
+                continue;
+            }
+            if self.unique && self.corpus.contains(&name) {
+                fallback = name;
+                continue;
+            }
+            return capitalize(&name);
+        }
+        capitalize(&fallback)
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut letters = name.chars();
+    letters.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(letters).collect()
+    })
+}
@@ -0,0 +1,228 @@
+//! Bake navigation meshes from terrain with `oxidized_navigation`
+//! # Example
+//! For configuration, see [`NavMeshTerrain`](struct.NavMeshTerrain.html) and [`NavMeshPlugin`](struct.NavMeshPlugin.html).
+//! Requires the `navmesh` feature. Terrains with a `NavMeshTerrain` component and entities with a
+//! [`NavMeshObstacle`](struct.NavMeshObstacle.html) and an `Aabb`, e.g. scattered meshes, get a parry
+//! [`NavMeshCollider`](struct.NavMeshCollider.html) and a `NavMeshAffector`. `oxidized_navigation` bakes the tiles
+//! they overlap into its `NavMesh` resource and bakes them again when a terrain, e.g. a streamed chunk, or an obstacle changes
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::navmesh::{NavMeshPlugin, NavMeshTerrain};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//! use oxidized_navigation::{query::find_path, NavMesh, NavMeshSettings};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, NavMeshPlugin::default()))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_path)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), NavMeshTerrain::default()));
+//! }
+//!
+//! fn print_path(nav_mesh: Res<NavMesh>, settings: Res<NavMeshSettings>) {
+//!     let tiles = nav_mesh.get();
+//!     let Ok(tiles) = tiles.read() else {
+//!         return;
+//!     };
+//!     let start = Vec3::new(-0.5, 1.0, -0.5);
+//!     let end = Vec3::new(0.5, 1.0, 0.5);
+//!     if let Ok(path) = find_path(&tiles, &settings, start, end, None, None) {
+//!         println!("{} points", path.len());
+//!     }
+//! }
+//! ```
+use bevy::{prelude::*, render::primitives::Aabb};
+use oxidized_navigation::{
+    colliders::OxidizedCollider, NavMeshAffector, NavMeshSettings, OxidizedNavigationPlugin,
+};
+use parry3d::{
+    bounding_volume,
+    math::Point,
+    shape::{SharedShape, TypedShape},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::GeneratedTerrain;
+
+/// Component feeding the terrain into the navigation mesh, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Default, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct NavMeshTerrain {
+    /// If true, the sea floor is walkable. Otherwise cells below the sea level are left out
+    pub sea: bool,
+}
+
+/// Marker for entities cut out of the navigation mesh of every terrain below them.
+/// The footprint is the `Aabb` of the entity, which bevy computes for meshes
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct NavMeshObstacle;
+
+/// Parry shape the navigation mesh is baked from, inserted on terrains and obstacles.
+/// Insert it with a `NavMeshAffector` on other entities to bake them into the navigation mesh too
+#[derive(Component, Clone)]
+pub struct NavMeshCollider(pub SharedShape);
+
+impl OxidizedCollider for NavMeshCollider {
+    fn oxidized_into_typed_shape(&self) -> TypedShape<'_> {
+        self.0.as_typed_shape()
+    }
+
+    fn oxidized_compute_local_aabb(&self) -> bounding_volume::Aabb {
+        self.0.compute_local_aabb()
+    }
+}
+
+/// Plugin to bake navigation meshes, adds the `OxidizedNavigationPlugin` for `NavMeshCollider`s
+pub struct NavMeshPlugin {
+    /// Settings of the navigation mesh of the whole world
+    pub settings: NavMeshSettings,
+}
+
+impl Default for NavMeshPlugin {
+    /// Settings for agents of about 0.05 world units on the default terrain, whose cells are 1/15 world units wide
+    fn default() -> Self {
+        Self {
+            settings: NavMeshSettings {
+                cell_width: 0.01,
+                cell_height: 0.005,
+                tile_width: 50,
+                world_half_extents: 64.0,
+                world_bottom_bound: -16.0,
+                max_traversable_slope_radians: 35_f32.to_radians(),
+                walkable_height: 10,
+                walkable_radius: 1,
+                step_height: 2,
+                min_region_area: 20,
+                merge_region_area: 100,
+                max_contour_simplification_error: 1.1,
+                max_edge_length: 80,
+                max_tile_generation_tasks: Some(9),
+            },
+        }
+    }
+}
+
+impl Plugin for NavMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(OxidizedNavigationPlugin::<NavMeshCollider>::new(
+            self.settings.clone(),
+        ))
+        .add_systems(Update, (collide_terrains, collide_obstacles));
+    }
+}
+
+/// Feeds the cells of changed terrains into the navigation mesh as a triangle mesh
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn collide_terrains(
+    mut commands: Commands,
+    terrains: Query<
+        (Entity, &NavMeshTerrain, &GeneratedTerrain),
+        Or<(Changed<NavMeshTerrain>, Changed<GeneratedTerrain>)>,
+    >,
+) {
+    for (entity, settings, terrain_data) in &terrains {
+        match terrain_collider(settings, terrain_data) {
+            Some(collider) => {
+                commands.entity(entity).insert((collider, NavMeshAffector));
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .remove::<(NavMeshCollider, NavMeshAffector)>();
+            }
+        }
+    }
+}
+
+/// Triangles of the cells of `terrain_data`, without holes and, unless `settings.sea`, the sea floor.
+/// `None` without any cell
+fn terrain_collider(
+    settings: &NavMeshTerrain,
+    terrain_data: &GeneratedTerrain,
+) -> Option<NavMeshCollider> {
+    let columns = terrain_data.heights.len();
+    let rows = terrain_data.heights.first().map_or(0, Vec::len);
+    let mut vertices = Vec::with_capacity(columns * rows);
+    for i in 0..columns {
+        for j in 0..rows {
+            let vertex = terrain_data.vertex_position(i, j)?;
+            vertices.push(Point::new(vertex.x, vertex.y, vertex.z));
+        }
+    }
+    let index = |i: usize, j: usize| (i * rows + j) as u32;
+    let sea = |i: usize, j: usize| terrain_data.heights[i][j] <= terrain_data.sea_level;
+    let mut indices = vec![];
+    for i in 0..columns.saturating_sub(1) {
+        for j in 0..rows.saturating_sub(1) {
+            let corners = [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)];
+            if terrain_data.is_hole_cell(i, j)
+                || (!settings.sea && corners.iter().all(|&(i, j)| sea(i, j)))
+            {
+                continue;
+            }
+            // Counter-clockwise seen from above, matching the terrain mesh
+            let [a, b, c, d] = corners.map(|(i, j)| index(i, j));
+            indices.extend([[a, c, b], [b, c, d]]);
+        }
+    }
+    (!indices.is_empty()).then(|| NavMeshCollider(SharedShape::trimesh(vertices, indices)))
+}
+
+/// Feeds the footprints of obstacles into the navigation mesh as boxes
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn collide_obstacles(
+    mut commands: Commands,
+    obstacles: Query<(Entity, &Aabb), (With<NavMeshObstacle>, Changed<Aabb>)>,
+    mut removed: RemovedComponents<NavMeshObstacle>,
+) {
+    for (entity, aabb) in &obstacles {
+        let (min, max) = (Vec3::from(aabb.min()), Vec3::from(aabb.max()));
+        let vertices = (0..8)
+            .map(|corner| {
+                Point::new(
+                    if corner & 1 == 0 { min.x } else { max.x },
+                    if corner & 2 == 0 { min.y } else { max.y },
+                    if corner & 4 == 0 { min.z } else { max.z },
+                )
+            })
+            .collect();
+        // The `Aabb` may be off center, so the box is a triangle mesh of its corners
+        let indices = vec![
+            [0, 2, 1],
+            [1, 2, 3],
+            [4, 5, 6],
+            [5, 7, 6],
+            [0, 1, 4],
+            [1, 5, 4],
+            [2, 6, 3],
+            [3, 6, 7],
+            [0, 4, 2],
+            [2, 4, 6],
+            [1, 3, 5],
+            [3, 7, 5],
+        ];
+        commands.entity(entity).insert((
+            NavMeshCollider(SharedShape::trimesh(vertices, indices)),
+            NavMeshAffector,
+        ));
+    }
+    for entity in removed.read() {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.remove::<(NavMeshCollider, NavMeshAffector)>();
+        }
+    }
+}
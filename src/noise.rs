@@ -1,103 +1,22 @@
-use core::fmt;
-
 use bevy::prelude::{Handle, Image};
-use noise::{BasicMulti, Billow, Fbm, HybridMulti, RidgedMulti};
-use noise::{MultiFractal, NoiseFn, Seedable};
-use noise::{OpenSimplex, Perlin, PerlinSurflet, Simplex, SuperSimplex, Value, Worley};
+#[cfg(any(feature = "terrain", feature = "map", feature = "planet"))]
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+#[cfg(feature = "terrain")]
+pub(crate) use bevy_generative_core::noise::generate_detail_map;
+#[cfg(any(feature = "terrain", feature = "map"))]
+pub(crate) use bevy_generative_core::noise::NoiseValue;
+pub(crate) use bevy_generative_core::noise::{
+    generate_base_noise_map, get_noise_at_point_3d, get_noise_at_point_4d,
+};
+pub use bevy_generative_core::noise::{Function, FunctionName, Method, NoiseMap, Precision};
 use serde::{Deserialize, Serialize};
 
-/// 2D noise method used to generate noise map
-#[derive(PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub enum Method {
-    /// Open Simplex noise
-    OpenSimplex,
-    /// Perlin noise
-    Perlin,
-    /// Perlin Surflet noise
-    PerlinSurflet,
-    /// Simplex noise
-    Simplex,
-    /// Super Simplex noise
-    SuperSimplex,
-    /// Value noise
-    Value,
-    /// Worley noise
-    Worley,
-}
-
-impl fmt::Display for Method {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::OpenSimplex => write!(f, "Open Simplex"),
-            Self::Perlin => write!(f, "Perlin"),
-            Self::PerlinSurflet => write!(f, "Perlin Surflet"),
-            Self::Simplex => write!(f, "Simplex"),
-            Self::SuperSimplex => write!(f, "Super Simplex"),
-            Self::Value => write!(f, "Value"),
-            Self::Worley => write!(f, "Worley"),
-        }
-    }
-}
-
-/// Fractal function that should be applied on the noise values
-#[derive(PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub enum FunctionName {
-    /// See [`BasicMulti`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html)
-    BasicMulti,
-    /// See [`Billow`](https://docs.rs/noise/latest/noise/struct.Billow.html)
-    Billow,
-    /// See [`Fbm`](https://docs.rs/noise/latest/noise/struct.Fbm.html)
-    Fbm,
-    /// See [`HybridMulti`](https://docs.rs/noise/latest/noise/struct.HybridMulti.html)
-    HybridMulti,
-    /// See [`RidgedMulti`](https://docs.rs/noise/latest/noise/struct.RidgedMulti.html)
-    RidgedMulti,
-}
-
-impl fmt::Display for FunctionName {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::BasicMulti => write!(f, "Basic Multi"),
-            Self::Billow => write!(f, "Billow"),
-            Self::Fbm => write!(f, "FBM"),
-            Self::HybridMulti => write!(f, "Hybrid Multi"),
-            Self::RidgedMulti => write!(f, "Ridged Multi"),
-        }
-    }
-}
-
-/// Fractal function configuration
-#[derive(Serialize, Deserialize)]
-#[serde(default, rename_all = "camelCase")]
-pub struct Function {
-    /// Name of the function
-    pub name: Option<FunctionName>,
-    /// See [`Octaves`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html#structfield.octaves)
-    pub octaves: usize,
-    /// See [`Frequency`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html#structfield.octaves)
-    pub frequency: f64,
-    /// See [`Lacunarity`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html#structfield.octaves)
-    pub lacunarity: f64,
-    /// See [`Persistance`](https://docs.rs/noise/latest/noise/struct.BasicMulti.html#structfield.octaves)
-    pub persistence: f64,
-}
-
-impl Default for Function {
-    fn default() -> Self {
-        Self {
-            name: Some(FunctionName::Fbm),
-            octaves: noise::Fbm::<noise::Perlin>::DEFAULT_OCTAVE_COUNT,
-            frequency: noise::Fbm::<noise::Perlin>::DEFAULT_FREQUENCY,
-            lacunarity: noise::Fbm::<noise::Perlin>::DEFAULT_LACUNARITY,
-            persistence: noise::Fbm::<noise::Perlin>::DEFAULT_PERSISTENCE,
-        }
-    }
-}
+#[cfg(any(feature = "terrain", feature = "map", feature = "planet"))]
+use crate::error::GenerationFailure;
+use crate::{error::ConfigError, modifier::Modifier};
 
 /// Region based on height
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Region {
     /// Label of the region
@@ -119,10 +38,10 @@ impl Default for Region {
 }
 
 /// Gradient used to map color values
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Gradient {
-    /// Image handle of gradient
+    /// Image handle of gradient, terrains set `GeneratedTerrain::gradient` instead
     #[serde(skip)]
     pub image: Handle<Image>,
     /// Size of gradient
@@ -145,7 +64,7 @@ impl Default for Gradient {
 }
 
 /// Noise configuration
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct Noise {
     pub(crate) size: [u32; 2],
@@ -159,6 +78,8 @@ pub struct Noise {
     pub method: Method,
     /// Function used to generate noise
     pub function: Function,
+    /// Precision the noise maps are stored with, `Precision::Double` for modifiers telling apart nearly equal values
+    pub precision: Precision,
     /// Vector of regions
     pub regions: Vec<Region>,
     /// Gradient determines how the noise values are mapped to colors
@@ -166,6 +87,8 @@ pub struct Noise {
     /// Base color of the gradient.
     /// If gradient has transparency, base color will be blended with the gradient
     pub base_color: [u8; 4],
+    /// Modifiers applied in order to the generated noise map
+    pub modifiers: Vec<Modifier>,
 }
 
 impl Default for Noise {
@@ -177,6 +100,7 @@ impl Default for Noise {
             offset: [0.0; 2],
             method: Method::Perlin,
             function: Function::default(),
+            precision: Precision::Single,
             regions: vec![
                 Region {
                     label: "Region #1".to_string(),
@@ -191,242 +115,291 @@ impl Default for Noise {
             ],
             gradient: Gradient::default(),
             base_color: [255, 255, 255, 255],
+            modifiers: vec![],
         }
     }
 }
 
-pub(crate) fn generate_noise_map(noise: &Noise) -> Vec<Vec<f64>> {
-    noise.function.name.as_ref().map_or_else(
-        || {
-            let generate_noise_map = match noise.method {
-                Method::OpenSimplex => generate_noise::<OpenSimplex>,
-                Method::Perlin => generate_noise::<Perlin>,
-                Method::PerlinSurflet => generate_noise::<PerlinSurflet>,
-                Method::Simplex => generate_noise::<Simplex>,
-                Method::SuperSimplex => generate_noise::<SuperSimplex>,
-                Method::Value => generate_noise::<Value>,
-                Method::Worley => generate_noise::<Worley>,
-            };
-            generate_noise_map(noise.size, noise.seed, noise.scale, noise.offset)
-        },
-        |function_name| {
-            let generate_noise_map = match function_name {
-                FunctionName::BasicMulti => match noise.method {
-                    Method::OpenSimplex => generate_fractal_noise::<BasicMulti<OpenSimplex>>,
-                    Method::Perlin => generate_fractal_noise::<BasicMulti<Perlin>>,
-                    Method::PerlinSurflet => generate_fractal_noise::<BasicMulti<PerlinSurflet>>,
-                    Method::Simplex => generate_fractal_noise::<BasicMulti<Simplex>>,
-                    Method::SuperSimplex => generate_fractal_noise::<BasicMulti<SuperSimplex>>,
-                    Method::Value => generate_fractal_noise::<BasicMulti<Value>>,
-                    Method::Worley => generate_fractal_noise::<BasicMulti<Worley>>,
-                },
-                FunctionName::Billow => match noise.method {
-                    Method::OpenSimplex => generate_fractal_noise::<Billow<OpenSimplex>>,
-                    Method::Perlin => generate_fractal_noise::<Billow<Perlin>>,
-                    Method::PerlinSurflet => generate_fractal_noise::<Billow<PerlinSurflet>>,
-                    Method::Simplex => generate_fractal_noise::<Billow<Simplex>>,
-                    Method::SuperSimplex => generate_fractal_noise::<Billow<SuperSimplex>>,
-                    Method::Value => generate_fractal_noise::<Billow<Value>>,
-                    Method::Worley => generate_fractal_noise::<Billow<Worley>>,
-                },
-                FunctionName::Fbm => match noise.method {
-                    Method::OpenSimplex => generate_fractal_noise::<Fbm<OpenSimplex>>,
-                    Method::Perlin => generate_fractal_noise::<Fbm<Perlin>>,
-                    Method::PerlinSurflet => generate_fractal_noise::<Fbm<PerlinSurflet>>,
-                    Method::Simplex => generate_fractal_noise::<Fbm<Simplex>>,
-                    Method::SuperSimplex => generate_fractal_noise::<Fbm<SuperSimplex>>,
-                    Method::Value => generate_fractal_noise::<Fbm<Value>>,
-                    Method::Worley => generate_fractal_noise::<Fbm<Worley>>,
-                },
-                FunctionName::HybridMulti => match noise.method {
-                    Method::OpenSimplex => generate_fractal_noise::<HybridMulti<OpenSimplex>>,
-                    Method::Perlin => generate_fractal_noise::<HybridMulti<Perlin>>,
-                    Method::PerlinSurflet => generate_fractal_noise::<HybridMulti<PerlinSurflet>>,
-                    Method::Simplex => generate_fractal_noise::<HybridMulti<Simplex>>,
-                    Method::SuperSimplex => generate_fractal_noise::<HybridMulti<SuperSimplex>>,
-                    Method::Value => generate_fractal_noise::<HybridMulti<Value>>,
-                    Method::Worley => generate_fractal_noise::<HybridMulti<Worley>>,
-                },
-                FunctionName::RidgedMulti => match noise.method {
-                    Method::OpenSimplex => generate_fractal_noise::<RidgedMulti<OpenSimplex>>,
-                    Method::Perlin => generate_fractal_noise::<RidgedMulti<Perlin>>,
-                    Method::PerlinSurflet => generate_fractal_noise::<RidgedMulti<PerlinSurflet>>,
-                    Method::Simplex => generate_fractal_noise::<RidgedMulti<Simplex>>,
-                    Method::SuperSimplex => generate_fractal_noise::<RidgedMulti<SuperSimplex>>,
-                    Method::Value => generate_fractal_noise::<RidgedMulti<Value>>,
-                    Method::Worley => generate_fractal_noise::<RidgedMulti<Worley>>,
-                },
-            };
-            generate_noise_map(
-                noise.size,
-                noise.seed,
-                noise.scale,
-                noise.offset,
-                &noise.function,
-            )
-        },
-    )
-}
+impl Noise {
+    /// Builder starting from the default configuration
+    #[must_use]
+    pub fn builder() -> NoiseBuilder {
+        NoiseBuilder::default()
+    }
+
+    /// Checks the scale, the octaves of the function, the regions and the gradient size
+    ///
+    /// # Errors
+    /// The first invalid setting
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.check_generation()?;
+        let max = noise::Fbm::<noise::Perlin>::MAX_OCTAVES;
+        if self.function.name.is_some() && !(1..=max).contains(&self.function.octaves) {
+            return Err(ConfigError::Octaves {
+                octaves: self.function.octaves,
+                max,
+            });
+        }
+        if self.regions.is_empty() {
+            return Err(ConfigError::NoRegions);
+        }
+        for (index, region) in self.regions.iter().enumerate() {
+            if !(0.0..=100.0).contains(&region.position) {
+                return Err(ConfigError::RegionPosition {
+                    index,
+                    position: region.position,
+                });
+            }
+            if index > 0 && region.position <= self.regions[index - 1].position {
+                return Err(ConfigError::RegionOrder { index });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the settings that would panic or produce NaN during generation, the scale and the gradient size
+    pub(crate) fn check_generation(&self) -> Result<(), ConfigError> {
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            return Err(ConfigError::Scale(self.scale));
+        }
+        if self.gradient.size.contains(&0) {
+            return Err(ConfigError::EmptyGradient);
+        }
+        Ok(())
+    }
+
+    /// Region rendered at a noise value, the region with the lowest position at or above `value`
+    #[must_use]
+    pub fn region(&self, value: f64) -> Option<&Region> {
+        self.region_index(value).map(|index| &self.regions[index])
+    }
+
+    /// Index of the region rendered at a noise value, see [`Noise::region`]
+    #[must_use]
+    pub fn region_index(&self, value: f64) -> Option<usize> {
+        let regions = self.regions.iter().enumerate();
+        regions
+            .clone()
+            .filter(|(_, region)| region.position >= value)
+            .min_by(|(_, a), (_, b)| a.position.total_cmp(&b.position))
+            .or_else(|| regions.max_by(|(_, a), (_, b)| a.position.total_cmp(&b.position)))
+            .map(|(index, _)| index)
+    }
+
+    /// Noise with `offset` added to its seed and the seeds of its modifiers
+    #[must_use]
+    pub fn offset_seeds(&self, offset: u32) -> Self {
+        let mut noise = self.clone();
+        noise.seed = noise.seed.wrapping_add(offset);
+        for modifier in &mut noise.modifiers {
+            modifier.offset_seed(offset);
+        }
+        noise
+    }
+
+    /// Applies the modifiers in order to a complete `noise_map`
+    #[cfg(any(feature = "terrain", feature = "map"))]
+    pub(crate) fn apply_modifiers(&self, noise_map: &mut NoiseMap) {
+        for modifier in &self.modifiers {
+            match noise_map {
+                NoiseMap::Single(values) => modifier.apply(values),
+                NoiseMap::Double(values) => modifier.apply(values),
+            }
+        }
+    }
 
-fn generate_noise<T>(size: [u32; 2], seed: u32, scale: f64, offset: [f64; 2]) -> Vec<Vec<f64>>
-where
-    T: Default + Seedable + NoiseFn<f64, 2>,
-{
-    let mut noise = T::default();
-    noise = noise.set_seed(seed);
-    generate_noise_vector(noise, size, scale, offset)
+    /// 1D lookup texture of the colors terrains give to noise values, for custom materials.
+    ///
+    /// The `Rgba8Unorm` image is `width` texels wide, texel `i` holds the color of noise value `(i + 0.5) * 100 / width`.
+    /// Colors are stored as computed, like vertex colors, so sampling at `value / 100` matches the CPU path.
+    /// See `material::GradientLutPlugin` to bind it and `bevy_generative::gradient` for the shader functions
+    ///
+    /// # Errors
+    /// If the gradient could not be built from the regions
+    #[cfg(any(feature = "terrain", feature = "map", feature = "planet"))]
+    pub fn gradient_lut(&self, width: u32) -> Result<Image, GenerationFailure> {
+        let grad = build_gradient(&self.regions, &self.gradient)
+            .map_err(|error| GenerationFailure::Gradient(error.to_string()))?;
+        let width = width.max(1);
+        let mut data = Vec::with_capacity(width as usize * 4);
+        for i in 0..width {
+            data.extend(
+                grad.at((f64::from(i) + 0.5) * 100.0 / f64::from(width))
+                    .to_rgba8(),
+            );
+        }
+        Ok(Image::new(
+            Extent3d {
+                width,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D1,
+            data,
+            TextureFormat::Rgba8Unorm,
+        ))
+    }
 }
 
-fn generate_fractal_noise<T>(
-    size: [u32; 2],
-    seed: u32,
-    scale: f64,
-    offset: [f64; 2],
-    function: &Function,
-) -> Vec<Vec<f64>>
-where
-    T: Default + Seedable + NoiseFn<f64, 2> + MultiFractal,
-{
-    let mut noise = T::default();
-    noise = noise.set_seed(seed);
-    noise = noise.set_octaves(function.octaves);
-    noise = noise.set_frequency(function.frequency);
-    noise = noise.set_lacunarity(function.lacunarity);
-    noise = noise.set_persistence(function.persistence);
-    generate_noise_vector(noise, size, scale, offset)
+/// Builder of a validated `Noise`
+#[derive(Default)]
+pub struct NoiseBuilder {
+    noise: Noise,
 }
 
-fn generate_noise_vector(
-    noise: impl NoiseFn<f64, 2>,
-    size: [u32; 2],
-    scale: f64,
-    offset: [f64; 2],
-) -> Vec<Vec<f64>> {
-    let mut noise_vector: Vec<Vec<f64>> = Vec::with_capacity(size[0] as usize);
-    let noise = noise::Clamp::new(noise).set_bounds(-1.0, 1.0);
-    for i in 0..=size[0] {
-        let mut row: Vec<f64> = Vec::with_capacity(size[1] as usize);
-        for j in 0..=size[1] {
-            let x = f64::from(i as i32 - (size[0] / 2) as i32) / scale + offset[0];
-            let y = f64::from(j as i32 - (size[1] / 2) as i32) / scale + offset[1];
-            let value = (noise.get([x, y]) + 1.0) / 2.0 * 100.0;
-            row.push(value);
-        }
-        noise_vector.push(row);
+impl NoiseBuilder {
+    /// Sets the seed of the noise
+    #[must_use]
+    pub const fn seed(mut self, seed: u32) -> Self {
+        self.noise.seed = seed;
+        self
+    }
+
+    /// Sets the scale of the noise
+    #[must_use]
+    pub const fn scale(mut self, scale: f64) -> Self {
+        self.noise.scale = scale;
+        self
+    }
+
+    /// Sets the offset of the noise
+    #[must_use]
+    pub const fn offset(mut self, offset: [f64; 2]) -> Self {
+        self.noise.offset = offset;
+        self
+    }
+
+    /// Sets the method used to generate noise
+    #[must_use]
+    pub const fn method(mut self, method: Method) -> Self {
+        self.noise.method = method;
+        self
+    }
+
+    /// Sets the fractal function applied on the noise values
+    #[must_use]
+    pub const fn function(mut self, function: Function) -> Self {
+        self.noise.function = function;
+        self
+    }
+
+    /// Sets the precision the noise maps are stored with
+    #[must_use]
+    pub const fn precision(mut self, precision: Precision) -> Self {
+        self.noise.precision = precision;
+        self
+    }
+
+    /// Replaces the regions, ordered by position
+    #[must_use]
+    pub fn regions(mut self, regions: Vec<Region>) -> Self {
+        self.noise.regions = regions;
+        self
+    }
+
+    /// Sets the gradient the noise values are mapped to colors with
+    #[must_use]
+    pub fn gradient(mut self, gradient: Gradient) -> Self {
+        self.noise.gradient = gradient;
+        self
+    }
+
+    /// Sets the base color of the gradient
+    #[must_use]
+    pub const fn base_color(mut self, base_color: [u8; 4]) -> Self {
+        self.noise.base_color = base_color;
+        self
+    }
+
+    /// Adds a modifier, applied after the ones added before it
+    #[must_use]
+    pub fn modifier(mut self, modifier: Modifier) -> Self {
+        self.noise.modifiers.push(modifier);
+        self
+    }
+
+    /// Validated noise configuration
+    ///
+    /// # Errors
+    /// If the configuration is invalid, see [`Noise::validate`]
+    pub fn build(self) -> Result<Noise, ConfigError> {
+        self.noise.validate()?;
+        Ok(self.noise)
     }
-    noise_vector
 }
 
-pub(crate) fn get_noise_at_point_3d(
-    point: [f64; 3],
-    seed: u32,
-    scale: f64,
-    offset: [f64; 3],
-    method: &Method,
-    function: &Function,
-) -> f64 {
-    function.name.as_ref().map_or_else(
-        || {
-            let noise_at_point_3d = match method {
-                Method::OpenSimplex => noise_at_point_3d::<OpenSimplex>,
-                Method::Perlin => noise_at_point_3d::<Perlin>,
-                Method::PerlinSurflet => noise_at_point_3d::<PerlinSurflet>,
-                Method::Simplex => noise_at_point_3d::<Simplex>,
-                Method::SuperSimplex => noise_at_point_3d::<SuperSimplex>,
-                Method::Value => noise_at_point_3d::<Value>,
-                Method::Worley => noise_at_point_3d::<Worley>,
-            };
-            noise_at_point_3d(point, seed, scale, offset)
-        },
-        |function_name| {
-            let noise_at_point_3d = match function_name {
-                FunctionName::BasicMulti => match method {
-                    Method::OpenSimplex => fractal_noise_at_point_3d::<BasicMulti<OpenSimplex>>,
-                    Method::Perlin => fractal_noise_at_point_3d::<BasicMulti<Perlin>>,
-                    Method::PerlinSurflet => fractal_noise_at_point_3d::<BasicMulti<PerlinSurflet>>,
-                    Method::Simplex => fractal_noise_at_point_3d::<BasicMulti<Simplex>>,
-                    Method::SuperSimplex => fractal_noise_at_point_3d::<BasicMulti<SuperSimplex>>,
-                    Method::Value => fractal_noise_at_point_3d::<BasicMulti<Value>>,
-                    Method::Worley => fractal_noise_at_point_3d::<BasicMulti<Worley>>,
-                },
-                FunctionName::Billow => match method {
-                    Method::OpenSimplex => fractal_noise_at_point_3d::<Billow<OpenSimplex>>,
-                    Method::Perlin => fractal_noise_at_point_3d::<Billow<Perlin>>,
-                    Method::PerlinSurflet => fractal_noise_at_point_3d::<Billow<PerlinSurflet>>,
-                    Method::Simplex => fractal_noise_at_point_3d::<Billow<Simplex>>,
-                    Method::SuperSimplex => fractal_noise_at_point_3d::<Billow<SuperSimplex>>,
-                    Method::Value => fractal_noise_at_point_3d::<Billow<Value>>,
-                    Method::Worley => fractal_noise_at_point_3d::<Billow<Worley>>,
-                },
-                FunctionName::Fbm => match method {
-                    Method::OpenSimplex => fractal_noise_at_point_3d::<Fbm<OpenSimplex>>,
-                    Method::Perlin => fractal_noise_at_point_3d::<Fbm<Perlin>>,
-                    Method::PerlinSurflet => fractal_noise_at_point_3d::<Fbm<PerlinSurflet>>,
-                    Method::Simplex => fractal_noise_at_point_3d::<Fbm<Simplex>>,
-                    Method::SuperSimplex => fractal_noise_at_point_3d::<Fbm<SuperSimplex>>,
-                    Method::Value => fractal_noise_at_point_3d::<Fbm<Value>>,
-                    Method::Worley => fractal_noise_at_point_3d::<Fbm<Worley>>,
-                },
-                FunctionName::HybridMulti => match method {
-                    Method::OpenSimplex => fractal_noise_at_point_3d::<HybridMulti<OpenSimplex>>,
-                    Method::Perlin => fractal_noise_at_point_3d::<HybridMulti<Perlin>>,
-                    Method::PerlinSurflet => {
-                        fractal_noise_at_point_3d::<HybridMulti<PerlinSurflet>>
-                    }
-                    Method::Simplex => fractal_noise_at_point_3d::<HybridMulti<Simplex>>,
-                    Method::SuperSimplex => fractal_noise_at_point_3d::<HybridMulti<SuperSimplex>>,
-                    Method::Value => fractal_noise_at_point_3d::<HybridMulti<Value>>,
-                    Method::Worley => fractal_noise_at_point_3d::<HybridMulti<Worley>>,
-                },
-                FunctionName::RidgedMulti => match method {
-                    Method::OpenSimplex => fractal_noise_at_point_3d::<RidgedMulti<OpenSimplex>>,
-                    Method::Perlin => fractal_noise_at_point_3d::<RidgedMulti<Perlin>>,
-                    Method::PerlinSurflet => {
-                        fractal_noise_at_point_3d::<RidgedMulti<PerlinSurflet>>
-                    }
-                    Method::Simplex => fractal_noise_at_point_3d::<RidgedMulti<Simplex>>,
-                    Method::SuperSimplex => fractal_noise_at_point_3d::<RidgedMulti<SuperSimplex>>,
-                    Method::Value => fractal_noise_at_point_3d::<RidgedMulti<Value>>,
-                    Method::Worley => fractal_noise_at_point_3d::<RidgedMulti<Worley>>,
-                },
-            };
-            noise_at_point_3d(point, seed, scale, offset, function)
-        },
-    )
+/// Regions of a preset from their label, position and color
+#[cfg(any(feature = "terrain", feature = "map"))]
+pub(crate) fn regions(stops: &[(&str, f64, [u8; 4])]) -> Vec<Region> {
+    stops
+        .iter()
+        .map(|&(label, position, color)| Region {
+            label: label.to_string(),
+            position,
+            color,
+        })
+        .collect()
+}
+
+/// Gradient through the colors of the regions at their positions, evenly spaced if the positions are invalid
+#[cfg(any(feature = "terrain", feature = "map", feature = "planet"))]
+pub(crate) fn build_gradient(
+    regions: &[Region],
+    gradient: &Gradient,
+) -> Result<colorgrad::Gradient, colorgrad::CustomGradientError> {
+    let mut colors: Vec<colorgrad::Color> = Vec::with_capacity(regions.len());
+    let mut domain: Vec<f64> = Vec::with_capacity(regions.len());
+    for region in regions {
+        colors.push(colorgrad::Color {
+            r: f64::from(region.color[0]) / 255.0,
+            g: f64::from(region.color[1]) / 255.0,
+            b: f64::from(region.color[2]) / 255.0,
+            a: f64::from(region.color[3]) / 255.0,
+        });
+        domain.push(region.position);
+    }
+    let mut grad = colorgrad::CustomGradient::new()
+        .colors(&colors)
+        .domain(&domain)
+        .build()
+        .or_else(|_| colorgrad::CustomGradient::new().colors(&colors).build())?;
+
+    if gradient.segments != 0 {
+        grad = grad.sharp(gradient.segments, gradient.smoothness);
+    }
+    Ok(grad)
 }
 
-fn fractal_noise_at_point_3d<T>(
-    point: [f64; 3],
-    seed: u32,
-    scale: f64,
-    offset: [f64; 3],
-    function: &Function,
-) -> f64
-where
-    T: Default + Seedable + NoiseFn<f64, 3> + MultiFractal,
-{
-    let mut noise = T::default();
-    noise = noise.set_seed(seed);
-    noise = noise.set_octaves(function.octaves);
-    noise = noise.set_frequency(function.frequency);
-    noise = noise.set_lacunarity(function.lacunarity);
-    noise = noise.set_persistence(function.persistence);
-    let x = point[0] / scale + offset[0];
-    let y = point[1] / scale + offset[1];
-    let z = point[2] / scale + offset[2];
-    let noise = noise::Clamp::new(noise).set_bounds(-1.0, 1.0);
-    noise.get([x, y, z])
+/// Noise map of `size` values, the size of the noise is only used by maps
+#[cfg(feature = "map")]
+pub(crate) fn generate_noise_map(noise: &Noise, size: [u32; 2]) -> NoiseMap {
+    generate_noise_map_at(noise, size, noise.offset)
 }
 
-fn noise_at_point_3d<T>(point: [f64; 3], seed: u32, scale: f64, offset: [f64; 3]) -> f64
-where
-    T: Default + Seedable + NoiseFn<f64, 3>,
-{
-    let mut noise = T::default();
-    noise = noise.set_seed(seed);
-    let x = point[0] / scale + offset[0];
-    let y = point[1] / scale + offset[1];
-    let z = point[2] / scale + offset[2];
-    let noise = noise::Clamp::new(noise).set_bounds(-1.0, 1.0);
-    noise.get([x, y, z])
+/// Noise map of `size` values sampled at `offset` instead of the offset of the noise
+#[cfg(any(feature = "terrain", feature = "map"))]
+pub(crate) fn generate_noise_map_at(noise: &Noise, size: [u32; 2], offset: [f64; 2]) -> NoiseMap {
+    #[cfg(not(feature = "parallel"))]
+    let mut noise_map = NoiseMap::generate(
+        noise.precision,
+        size,
+        noise.seed,
+        noise.scale,
+        offset,
+        &noise.method,
+        &noise.function,
+    );
+    #[cfg(feature = "parallel")]
+    let mut noise_map = NoiseMap::generate_parallel(
+        noise.precision,
+        size,
+        noise.seed,
+        noise.scale,
+        offset,
+        &noise.method,
+        &noise.function,
+        std::thread::available_parallelism().map_or(1, usize::from),
+    );
+    noise.apply_modifiers(&mut noise_map);
+    noise_map
 }
@@ -0,0 +1,352 @@
+//! Generate ore veins below terrain
+//! # Example
+//! For configuration, see [`OreVeins`](struct.OreVeins.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::ore::{OrePlugin, OreVeins, OreVolume};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, OrePlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_center)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), OreVeins::default()));
+//! }
+//!
+//! fn print_center(query: Query<&OreVolume, Changed<OreVolume>>) {
+//!     for volume in &query {
+//!         for ore in volume.column(0.0, 0.0).iter().flatten() {
+//!             println!("{}", volume.ores[*ore]);
+//!         }
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    noise::{get_noise_at_point_3d, Function, Method},
+    terrain::GeneratedTerrain,
+    util::Rng,
+};
+
+/// Shape of the veins of an ore
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VeinShape {
+    /// Cells where 3D noise is highest
+    Blobs {
+        /// Scale of the noise in world units
+        scale: f64,
+    },
+    /// Random walks through the volume
+    Worms {
+        /// Number of steps of a walk, one cell per step
+        length: u32,
+        /// Radius of a walk in cells
+        radius: f32,
+    },
+}
+
+/// Configuration of one ore
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Ore {
+    /// Name of the ore, copied to `OreVolume::ores`
+    pub label: String,
+    /// Fraction of the cells within `depth` filled with the ore, 0 to 1
+    pub rarity: f32,
+    /// Minimum and maximum depth below the surface in world units
+    pub depth: [f32; 2],
+    /// Shape of the veins
+    pub shape: VeinShape,
+}
+
+impl Default for Ore {
+    fn default() -> Self {
+        Self {
+            label: String::new(),
+            rarity: 0.05,
+            depth: [0.0, 1.0],
+            shape: VeinShape::Blobs { scale: 0.2 },
+        }
+    }
+}
+
+/// Component for ore vein configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct OreVeins {
+    /// Seed of the noise and the random walks
+    pub seed: u32,
+    /// Ores in order of priority, earlier ores are not replaced by later ones
+    pub ores: Vec<Ore>,
+    /// Number of cells per world unit along `x` and `z`
+    pub resolution: u32,
+    /// Depth of the volume below the surface in world units
+    pub depth: f32,
+    /// Number of cells along the depth
+    pub layers: u32,
+    /// Noise method used by `VeinShape::Blobs`
+    pub method: Method,
+    /// Noise function used by `VeinShape::Blobs`
+    pub function: Function,
+}
+
+impl Default for OreVeins {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            ores: vec![
+                Ore {
+                    label: "coal".to_string(),
+                    rarity: 0.08,
+                    depth: [0.0, 0.6],
+                    shape: VeinShape::Blobs { scale: 0.25 },
+                },
+                Ore {
+                    label: "iron".to_string(),
+                    rarity: 0.05,
+                    depth: [0.2, 1.0],
+                    shape: VeinShape::Worms {
+                        length: 24,
+                        radius: 1.0,
+                    },
+                },
+                Ore {
+                    label: "gold".to_string(),
+                    rarity: 0.01,
+                    depth: [0.6, 1.0],
+                    shape: VeinShape::Blobs { scale: 0.1 },
+                },
+            ],
+            resolution: 8,
+            depth: 1.0,
+            layers: 16,
+            method: Method::Perlin,
+            function: Function {
+                name: None,
+                ..default()
+            },
+        }
+    }
+}
+
+/// Generated ore cells below the terrain surface, inserted on the entity with the `OreVeins` component.
+/// Coordinates are relative to the terrain entity, depth is measured downwards from the surface
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct OreVolume {
+    /// Labels of the ores, indexed by the values in `cells`
+    pub ores: Vec<String>,
+    /// Index into `ores` of every cell, `None` for plain rock. Indexed by `[x][z][layer]`
+    pub cells: Vec<Vec<Vec<Option<usize>>>>,
+    /// Number of cells per world unit along `x` and `z`
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+    /// Depth of the volume below the surface in world units
+    pub depth: f32,
+}
+
+impl OreVolume {
+    /// Ore at `x`, `z` and `depth` below the surface, `None` for plain rock or outside of the volume
+    #[must_use]
+    pub fn get(&self, x: f32, z: f32, depth: f32) -> Option<usize> {
+        let column = self.column(x, z);
+        if !(0.0..self.depth).contains(&depth) {
+            return None;
+        }
+        let layer = (depth / self.depth * column.len() as f32) as usize;
+        column.get(layer).copied().flatten()
+    }
+
+    /// Ores of the cells below `x`, `z` from the surface downwards, empty outside of the volume
+    #[must_use]
+    pub fn column(&self, x: f32, z: f32) -> &[Option<usize>] {
+        let resolution = self.resolution as f32;
+        let i = ((x + self.size[0] as f32 / 2.0) * resolution).round();
+        let j = ((z + self.size[1] as f32 / 2.0) * resolution).round();
+        if i < 0.0 || j < 0.0 {
+            return &[];
+        }
+        self.cells
+            .get(i as usize)
+            .and_then(|column| column.get(j as usize))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Depth of the center of `layer` below the surface in world units
+    #[must_use]
+    pub fn layer_depth(&self, layer: usize) -> f32 {
+        let layers = self
+            .cells
+            .first()
+            .and_then(|column| column.first())
+            .map_or(1, Vec::len);
+        (layer as f32 + 0.5) * self.depth / layers.max(1) as f32
+    }
+}
+
+/// Plugin to generate ore veins
+pub struct OrePlugin;
+
+impl Plugin for OrePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_ore);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_ore(
+    mut commands: Commands,
+    query: Query<
+        (Entity, &OreVeins, &GeneratedTerrain, Option<&OreVolume>),
+        Or<(Changed<OreVeins>, Changed<GeneratedTerrain>)>,
+    >,
+) {
+    for (entity, veins, terrain_data, volume) in &query {
+        let new_volume = generate_volume(veins, terrain_data.size);
+        if volume != Some(&new_volume) {
+            commands.entity(entity).insert(new_volume);
+        }
+    }
+}
+
+fn generate_volume(veins: &OreVeins, size: [u32; 2]) -> OreVolume {
+    let resolution = veins.resolution.max(1);
+    let columns = (size[0] * resolution + 1) as usize;
+    let rows = (size[1] * resolution + 1) as usize;
+    let layers = veins.layers.max(1) as usize;
+    let mut volume = OreVolume {
+        ores: veins.ores.iter().map(|ore| ore.label.clone()).collect(),
+        cells: vec![vec![vec![None; layers]; rows]; columns],
+        resolution,
+        size,
+        depth: veins.depth,
+    };
+    let layer_depth = |layer: usize| volume.layer_depth(layer);
+    let depths: Vec<f32> = (0..layers).map(layer_depth).collect();
+
+    for (index, ore) in veins.ores.iter().enumerate() {
+        let in_range = |layer: usize| (ore.depth[0]..=ore.depth[1]).contains(&depths[layer]);
+        let range_layers = (0..layers).filter(|&layer| in_range(layer)).count();
+        let target = (ore.rarity.clamp(0.0, 1.0) * (columns * rows * range_layers) as f32) as usize;
+        if target == 0 {
+            continue;
+        }
+        let seed = veins.seed.wrapping_add(index as u32);
+        match &ore.shape {
+            VeinShape::Blobs { scale } => {
+                // The highest noise values become ore, so `rarity` is met exactly
+                let mut candidates: Vec<(f64, [usize; 3])> = vec![];
+                for i in 0..columns {
+                    for j in 0..rows {
+                        for layer in (0..layers).filter(|&layer| in_range(layer)) {
+                            if volume.cells[i][j][layer].is_some() {
+                                continue;
+                            }
+                            let point = [
+                                f64::from(i as f32 / resolution as f32),
+                                -f64::from(depths[layer]),
+                                f64::from(j as f32 / resolution as f32),
+                            ];
+                            let value = get_noise_at_point_3d(
+                                point,
+                                seed,
+                                scale.max(f64::EPSILON),
+                                [0.0; 3],
+                                &veins.method,
+                                &veins.function,
+                            );
+                            candidates.push((value, [i, j, layer]));
+                        }
+                    }
+                }
+                candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+                for (_, [i, j, layer]) in candidates.into_iter().take(target) {
+                    volume.cells[i][j][layer] = Some(index);
+                }
+            }
+            VeinShape::Worms { length, radius } => {
+                let mut rng = Rng::new(u64::from(seed));
+                let mut filled = 0;
+                let reach = radius.max(0.0).ceil() as i64;
+                let bounds = [columns as f32, rows as f32, layers as f32];
+                // Bounded so unreachable targets, e.g. when the range is filled by other ores, terminate
+                for _ in 0..target.saturating_mul(4).max(16) {
+                    if filled >= target {
+                        break;
+                    }
+                    let start_layer = (0..layers)
+                        .filter(|&layer| in_range(layer))
+                        .nth(rng.range(0, range_layers as u32 - 1) as usize)
+                        .unwrap_or(0);
+                    let mut position = Vec3::new(
+                        rng.next_f64() as f32 * bounds[0],
+                        rng.next_f64() as f32 * bounds[1],
+                        start_layer as f32 + 0.5,
+                    );
+                    let mut direction = random_direction(&mut rng);
+                    for _ in 0..*length {
+                        let center = position.floor().as_i64vec3();
+                        for di in -reach..=reach {
+                            for dj in -reach..=reach {
+                                for dl in -reach..=reach {
+                                    let offset = Vec3::new(di as f32, dj as f32, dl as f32);
+                                    if offset.length() > *radius {
+                                        continue;
+                                    }
+                                    let [i, j, layer] =
+                                        [center.x + di, center.y + dj, center.z + dl];
+                                    if i < 0 || j < 0 || layer < 0 {
+                                        continue;
+                                    }
+                                    let [i, j, layer] = [i, j, layer].map(|value| value as usize);
+                                    if i >= columns || j >= rows || layer >= layers {
+                                        continue;
+                                    }
+                                    let cell = &mut volume.cells[i][j][layer];
+                                    if cell.is_none() && in_range(layer) && filled < target {
+                                        *cell = Some(index);
+                                        filled += 1;
+                                    }
+                                }
+                            }
+                        }
+                        direction =
+                            (direction + random_direction(&mut rng) * 0.5).normalize_or_zero();
+                        position += direction;
+                        if position.cmplt(Vec3::ZERO).any()
+                            || position.cmpge(Vec3::from(bounds)).any()
+                        {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    volume
+}
+
+fn random_direction(rng: &mut Rng) -> Vec3 {
+    let theta = rng.next_f64() as f32 * std::f32::consts::TAU;
+    let y = (rng.next_f64() as f32).mul_add(2.0, -1.0);
+    let r = y.mul_add(-y, 1.0).sqrt();
+    Vec3::new(r * theta.cos(), r * theta.sin(), y)
+}
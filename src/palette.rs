@@ -0,0 +1,221 @@
+//! Color terrain with gradients selected per biome
+//! # Example
+//! For configuration, see [`BiomePalettes`](struct.BiomePalettes.html).
+//! Vertices take the colors of the palette of their biome in the `BiomeMap` of the terrain,
+//! blended across biome borders. Biomes without a palette keep the gradient of the noise
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::biome::{Biome, BiomePlugin, Whittaker};
+//! use bevy_generative::climate::{Climate, ClimatePlugin};
+//! use bevy_generative::noise::Region;
+//! use bevy_generative::palette::{BiomePalette, BiomePalettes};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, ClimatePlugin, BiomePlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn region(position: f64, color: [u8; 4]) -> Region {
+//!     Region {
+//!         position,
+//!         color,
+//!         ..default()
+//!     }
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Climate::default(),
+//!         Whittaker::default(),
+//!         BiomePalettes {
+//!             palettes: vec![
+//!                 BiomePalette {
+//!                     biomes: vec![Biome::SubtropicalDesert, Biome::TemperateGrassland],
+//!                     regions: vec![
+//!                         region(10.0, [200, 160, 100, 255]),
+//!                         region(60.0, [230, 190, 130, 255]),
+//!                         region(100.0, [180, 120, 80, 255]),
+//!                     ],
+//!                     ..default()
+//!                 },
+//!                 BiomePalette {
+//!                     biomes: vec![Biome::Tundra],
+//!                     regions: vec![
+//!                         region(10.0, [120, 130, 120, 255]),
+//!                         region(100.0, [240, 245, 250, 255]),
+//!                     ],
+//!                     ..default()
+//!                 },
+//!             ],
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::{Biome, BiomeMap, BiomeTransition, BiomeWeights},
+    error::{fail, GenerationError, GenerationFailure},
+    noise::{build_gradient, Gradient, Region},
+    terrain::{GeneratedTerrain, TerrainColors},
+};
+
+/// Regions and gradient used for the vertices of some biomes
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BiomePalette {
+    /// Biomes colored with the palette
+    pub biomes: Vec<Biome>,
+    /// Regions of the palette by noise value, like `Noise::regions`.
+    /// `GeneratedTerrain::regions` still follows the regions of the noise
+    pub regions: Vec<Region>,
+    /// Segments and smoothness of the palette, the image and size are not used
+    pub gradient: Gradient,
+}
+
+/// Component for biome palettes, added to an entity with a `Terrain` and a `Whittaker` component
+#[derive(Component, Clone, Default, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct BiomePalettes {
+    /// Palettes, the first palette listing a biome is used
+    pub palettes: Vec<BiomePalette>,
+    /// Blending of the palettes across biome borders
+    pub transition: BiomeTransition,
+}
+
+impl BiomePalettes {
+    /// Palette weights of every vertex of the terrain of `biomes`
+    ///
+    /// # Errors
+    /// If the gradient of a palette can not be built, with the message of the gradient builder
+    pub(crate) fn blend(&self, biomes: &BiomeMap) -> Result<PaletteBlend, String> {
+        let gradients = self
+            .palettes
+            .iter()
+            .map(|palette| build_gradient(&palette.regions, &palette.gradient))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| error.to_string())?;
+        let weights = self
+            .palettes
+            .iter()
+            .enumerate()
+            .map(|(index, palette)| {
+                // Biomes listed by an earlier palette keep that one
+                let selected: Vec<Biome> = palette
+                    .biomes
+                    .iter()
+                    .copied()
+                    .filter(|biome| {
+                        !self.palettes[..index]
+                            .iter()
+                            .any(|earlier| earlier.biomes.contains(biome))
+                    })
+                    .collect();
+                biomes.weights(&selected, &self.transition)
+            })
+            .collect();
+        Ok(PaletteBlend { gradients, weights })
+    }
+}
+
+/// Gradients of the palettes and their weight at every vertex
+pub(crate) struct PaletteBlend {
+    gradients: Vec<colorgrad::Gradient>,
+    weights: Vec<BiomeWeights>,
+}
+
+impl PaletteBlend {
+    /// `color` of the noise gradient at vertex `i`, `j` blended with the palettes at `value`
+    pub(crate) fn color(&self, i: usize, j: usize, value: f64, color: [f32; 4]) -> [f32; 4] {
+        // The weights of the palettes and the noise gradient add up to 1
+        let mut blended = Vec4::from(color);
+        for (gradient, weights) in self.gradients.iter().zip(&self.weights) {
+            let weight = weights
+                .weights
+                .get(i)
+                .and_then(|column| column.get(j))
+                .copied()
+                .unwrap_or_default();
+            if weight <= 0.0 {
+                continue;
+            }
+            let palette = gradient.at(value);
+            let palette = Vec4::new(
+                palette.r as f32,
+                palette.g as f32,
+                palette.b as f32,
+                palette.a as f32,
+            );
+            blended += (palette - Vec4::from(color)) * weight;
+        }
+        blended.to_array()
+    }
+}
+
+/// Palettes of a terrain blended by its biomes, kept until either changes.
+/// `None` if a palette could not be built
+#[derive(Component)]
+pub(crate) struct BlendedPalettes(Option<PaletteBlend>);
+
+/// Colors recolored terrains with the palettes of their biomes
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+pub(crate) fn blend_palettes(
+    mut commands: Commands,
+    mut errors: EventWriter<GenerationError>,
+    mut query: Query<(
+        Entity,
+        &mut TerrainColors,
+        &GeneratedTerrain,
+        Ref<BiomePalettes>,
+        Ref<BiomeMap>,
+        Option<&BlendedPalettes>,
+    )>,
+) {
+    for (entity, mut colors, terrain_data, biome_palettes, biomes, blended) in &mut query {
+        if !colors.is_recoloring() {
+            continue;
+        }
+        // Changes to the palettes or biomes recolor the terrain, so they are seen here the same frame
+        let fresh =
+            (blended.is_none() || biome_palettes.is_changed() || biomes.is_changed()).then(|| {
+                match biome_palettes.blend(&biomes) {
+                    Ok(palettes) => Some(palettes),
+                    Err(error) => {
+                        fail(&mut errors, entity, GenerationFailure::Gradient(error));
+                        None
+                    }
+                }
+            });
+        let palettes = fresh.as_ref().map_or_else(
+            || blended.and_then(|blended| blended.0.as_ref()),
+            Option::as_ref,
+        );
+        if let Some(palettes) = palettes {
+            colors.blend(terrain_data, |[i, j], _, color| {
+                terrain_data
+                    .noise
+                    .get(i, j)
+                    .map_or(color, |value| palettes.color(i, j, value, color))
+            });
+        }
+        if let Some(fresh) = fresh {
+            commands.entity(entity).insert(BlendedPalettes(fresh));
+        }
+    }
+}
@@ -0,0 +1,486 @@
+//! Save and load terrain edits per chunk
+//! # Example
+//! For configuration, see [`PersistentChunk`](struct.PersistentChunk.html) and [`ChunkStore`](struct.ChunkStore.html).
+//! Deformations, painted splat maps and scatter edits of every persistent chunk are saved on `SaveChunks`
+//! and applied again when a chunk with the same key is spawned, so edits to a seeded world survive restarts.
+//! Files are read and written on the `IoTaskPool`, saved edits are applied a few frames after the chunk is spawned
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::deform::DeformPlugin;
+//! use bevy_generative::persist::{PersistPlugin, PersistentChunk, SaveChunks};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, DeformPlugin, PersistPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, save)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         PersistentChunk {
+//!             key: "0_0".to_string(),
+//!         },
+//!     ));
+//! }
+//!
+//! fn save(keys: Res<Input<KeyCode>>, mut events: EventWriter<SaveChunks>) {
+//!     if keys.just_pressed(KeyCode::F5) {
+//!         events.send(SaveChunks);
+//!     }
+//! }
+//! ```
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex, PoisonError,
+    },
+};
+
+use bevy::{prelude::*, tasks::IoTaskPool};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::{deform::TerrainDeformation, scatter::ScatterEdits, splat::SplatMap};
+
+/// Bytes every chunk file starts with
+const MAGIC: &[u8; 4] = b"BGCK";
+
+/// Version of the chunk file format, written after the magic bytes
+pub const CHUNK_VERSION: u32 = 1;
+
+/// Component marking a terrain entity whose edits are saved, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct PersistentChunk {
+    /// Name of the chunk file, unique per chunk, e.g. built from the chunk coordinates.
+    /// Only ASCII letters, digits, `-`, `_` and `.` are allowed, so the file stays in the store directory
+    pub key: String,
+}
+
+/// Resource with the directory chunk files are stored in
+#[derive(Resource, Clone, PartialEq, Eq, Debug)]
+pub struct ChunkStore {
+    /// Directory of the chunk files, created on the first save
+    pub directory: PathBuf,
+}
+
+impl Default for ChunkStore {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("chunks"),
+        }
+    }
+}
+
+impl ChunkStore {
+    /// Path of the file of the chunk with `key`
+    ///
+    /// # Errors
+    /// If `key` is empty or contains other characters than ASCII letters, digits, `-`, `_` and `.`
+    pub fn path(&self, key: &str) -> Result<PathBuf, ChunkError> {
+        let is_valid = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !is_valid {
+            return Err(ChunkError::Key(key.to_string()));
+        }
+        Ok(self.directory.join(format!("{key}.chunk")))
+    }
+}
+
+/// Edits of one chunk as stored in its file
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChunkEdits {
+    /// Height offsets of the chunk
+    pub deformation: Option<TerrainDeformation>,
+    /// Painted layers of the chunk
+    pub splat_map: Option<SplatMap>,
+    /// Changes to the scattered objects of the chunk
+    pub scatter: Option<ScatterEdits>,
+}
+
+impl ChunkEdits {
+    /// Returns true if there is nothing to save
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.deformation.is_none() && self.splat_map.is_none() && self.scatter.is_none()
+    }
+
+    /// Encodes the edits as magic bytes, format version and compressed JSON
+    ///
+    /// # Errors
+    /// If the edits cannot be serialized or compressed
+    pub fn encode(&self) -> Result<Vec<u8>, ChunkError> {
+        let json = serde_json::to_vec(self).map_err(ChunkError::Json)?;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(CHUNK_VERSION.to_le_bytes());
+        let mut encoder = ZlibEncoder::new(bytes, Compression::default());
+        encoder.write_all(&json).map_err(ChunkError::Io)?;
+        encoder.finish().map_err(ChunkError::Io)
+    }
+
+    /// Decodes edits encoded by `encode`
+    ///
+    /// # Errors
+    /// If `bytes` are not a chunk file or were written by a newer format version
+    pub fn decode(bytes: &[u8]) -> Result<Self, ChunkError> {
+        if bytes.len() < 8 || &bytes[..4] != MAGIC {
+            return Err(ChunkError::Format);
+        }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version > CHUNK_VERSION {
+            return Err(ChunkError::Version(version));
+        }
+        let mut json = vec![];
+        ZlibDecoder::new(&bytes[8..])
+            .read_to_end(&mut json)
+            .map_err(ChunkError::Io)?;
+        serde_json::from_slice(&json).map_err(ChunkError::Json)
+    }
+}
+
+/// Error saving or loading a chunk file
+#[derive(Debug)]
+pub enum ChunkError {
+    /// The file could not be read or written
+    Io(io::Error),
+    /// The edits could not be serialized or deserialized
+    Json(serde_json::Error),
+    /// The file is not a chunk file
+    Format,
+    /// The file was written by a newer format version
+    Version(u32),
+    /// The key of the chunk is not a valid file name
+    Key(String),
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Could not access chunk file: {error}"),
+            Self::Json(error) => write!(f, "Could not parse chunk edits: {error}"),
+            Self::Format => write!(f, "Not a chunk file"),
+            Self::Version(version) => write!(
+                f,
+                "Chunk file version {version} is newer than supported version {CHUNK_VERSION}"
+            ),
+            Self::Key(key) => write!(f, "Chunk key {key:?} is not a valid file name"),
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+/// Event to save the edits of every persistent chunk, the files of chunks without edits are removed
+#[derive(Event, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct SaveChunks;
+
+/// Event sent when a chunk file could not be saved or loaded
+#[derive(Event, Debug)]
+pub struct ChunkFailed {
+    /// Entity of the chunk
+    pub chunk: Entity,
+    /// Why the file could not be saved or loaded
+    pub error: ChunkError,
+}
+
+/// Result of a file task, the loaded edits or `None` if there was nothing to load
+type ChunkResult = (Entity, Result<Option<ChunkEdits>, ChunkError>);
+
+/// Channel file tasks on the `IoTaskPool` send their results through
+#[derive(Resource)]
+struct ChunkTasks {
+    sender: Sender<ChunkResult>,
+    receiver: Mutex<Receiver<ChunkResult>>,
+}
+
+impl Default for ChunkTasks {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+/// Plugin to save and load persistent chunks
+pub struct PersistPlugin;
+
+impl Plugin for PersistPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkStore>()
+            .init_resource::<ChunkTasks>()
+            .add_event::<SaveChunks>()
+            .add_event::<ChunkFailed>()
+            .add_systems(Update, (load_chunks, save_chunks, finish_chunks).chain());
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn load_chunks(
+    mut failed: EventWriter<ChunkFailed>,
+    store: Res<ChunkStore>,
+    tasks: Res<ChunkTasks>,
+    query: Query<(Entity, &PersistentChunk), Added<PersistentChunk>>,
+) {
+    let pool = IoTaskPool::get();
+    for (entity, chunk) in &query {
+        let path = match store.path(&chunk.key) {
+            Ok(path) => path,
+            Err(error) => {
+                failed.send(ChunkFailed {
+                    chunk: entity,
+                    error,
+                });
+                continue;
+            }
+        };
+        let sender = tasks.sender.clone();
+        pool.spawn(async move {
+            let edits = match fs::read(path) {
+                Ok(bytes) => ChunkEdits::decode(&bytes).map(Some),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(error) => Err(ChunkError::Io(error)),
+            };
+            let _ = sender.send((entity, edits));
+        })
+        .detach();
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn save_chunks(
+    mut events: EventReader<SaveChunks>,
+    mut failed: EventWriter<ChunkFailed>,
+    store: Res<ChunkStore>,
+    tasks: Res<ChunkTasks>,
+    query: Query<(
+        Entity,
+        &PersistentChunk,
+        Option<&TerrainDeformation>,
+        Option<&SplatMap>,
+        Option<&ScatterEdits>,
+    )>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let pool = IoTaskPool::get();
+    for (entity, chunk, deformation, splat_map, scatter) in &query {
+        let edits = ChunkEdits {
+            deformation: deformation.cloned(),
+            splat_map: splat_map.cloned(),
+            scatter: scatter.cloned(),
+        };
+        // Without edits the file is removed, so edits removed since the last save are not loaded again
+        let encoded = store.path(&chunk.key).and_then(|path| {
+            let bytes = if edits.is_empty() {
+                None
+            } else {
+                Some(edits.encode()?)
+            };
+            Ok((path, bytes))
+        });
+        let (path, bytes) = match encoded {
+            Ok(encoded) => encoded,
+            Err(error) => {
+                failed.send(ChunkFailed {
+                    chunk: entity,
+                    error,
+                });
+                continue;
+            }
+        };
+        let directory = store.directory.clone();
+        let sender = tasks.sender.clone();
+        pool.spawn(async move {
+            let saved = match bytes {
+                Some(bytes) => fs::create_dir_all(directory).and_then(|()| fs::write(path, bytes)),
+                None => match fs::remove_file(path) {
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+                    removed => removed,
+                },
+            };
+            let _ = sender.send((entity, saved.map(|()| None).map_err(ChunkError::Io)));
+        })
+        .detach();
+    }
+}
+
+/// Applies loaded edits and reports failed tasks. Edits already on the chunk, e.g. restored by streaming
+/// or made before its file was read, are newer than the file and kept
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn finish_chunks(
+    mut commands: Commands,
+    mut failed: EventWriter<ChunkFailed>,
+    mut tasks: ResMut<ChunkTasks>,
+    query: Query<
+        (Has<TerrainDeformation>, Has<SplatMap>, Has<ScatterEdits>),
+        With<PersistentChunk>,
+    >,
+) {
+    let receiver = tasks
+        .receiver
+        .get_mut()
+        .unwrap_or_else(PoisonError::into_inner);
+    while let Ok((entity, edits)) = receiver.try_recv() {
+        let edits = match edits {
+            Ok(Some(edits)) => edits,
+            Ok(None) => continue,
+            Err(error) => {
+                failed.send(ChunkFailed {
+                    chunk: entity,
+                    error,
+                });
+                continue;
+            }
+        };
+        let Ok((has_deformation, has_splat_map, has_scatter)) = query.get(entity) else {
+            continue;
+        };
+        let mut entity = commands.entity(entity);
+        if let Some(deformation) = edits.deformation.filter(|_| !has_deformation) {
+            entity.insert(deformation);
+        }
+        if let Some(splat_map) = edits.splat_map.filter(|_| !has_splat_map) {
+            entity.insert(splat_map);
+        }
+        if let Some(scatter) = edits.scatter.filter(|_| !has_scatter) {
+            entity.insert(scatter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edits() -> ChunkEdits {
+        ChunkEdits {
+            deformation: Some(TerrainDeformation::default()),
+            splat_map: Some(SplatMap::default()),
+            scatter: Some(ScatterEdits::default()),
+        }
+    }
+
+    #[test]
+    fn edits_round_trip() {
+        let edits = edits();
+        assert_eq!(ChunkEdits::decode(&edits.encode().unwrap()).unwrap(), edits);
+        let empty = ChunkEdits::default();
+        assert_eq!(ChunkEdits::decode(&empty.encode().unwrap()).unwrap(), empty);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut bytes = edits().encode().unwrap();
+        bytes[4..8].copy_from_slice(&(CHUNK_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            ChunkEdits::decode(&bytes),
+            Err(ChunkError::Version(version)) if version == CHUNK_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let mut bytes = edits().encode().unwrap();
+        bytes[..4].copy_from_slice(b"BGCP");
+        assert!(matches!(
+            ChunkEdits::decode(&bytes),
+            Err(ChunkError::Format)
+        ));
+        assert!(matches!(ChunkEdits::decode(MAGIC), Err(ChunkError::Format)));
+    }
+
+    #[test]
+    fn keys_stay_in_the_store_directory() {
+        let store = ChunkStore::default();
+        assert_eq!(
+            store.path("-1_2").unwrap(),
+            store.directory.join("-1_2.chunk")
+        );
+        for key in ["", "../x", "a/b", "a\\b", "C:x"] {
+            assert!(matches!(store.path(key), Err(ChunkError::Key(_))), "{key}");
+        }
+    }
+
+    #[test]
+    fn saved_edits_are_loaded_into_new_chunks() {
+        let directory =
+            std::env::temp_dir().join(format!("bevy_generative_{}", std::process::id()));
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, PersistPlugin))
+            .insert_resource(ChunkStore {
+                directory: directory.clone(),
+            });
+        let chunk = || PersistentChunk {
+            key: "0_0".to_string(),
+        };
+        let splat_map = SplatMap::default();
+        app.world.spawn((chunk(), splat_map.clone()));
+        app.update();
+        app.world.send_event(SaveChunks);
+        for _ in 0..100_000 {
+            app.update();
+            let saved = fs::read(directory.join("0_0.chunk"));
+            if saved.is_ok_and(|bytes| ChunkEdits::decode(&bytes).is_ok()) {
+                break;
+            }
+            std::thread::yield_now();
+        }
+
+        let loaded = app.world.spawn(chunk()).id();
+        for _ in 0..100_000 {
+            app.update();
+            if app.world.get::<SplatMap>(loaded).is_some() {
+                break;
+            }
+            std::thread::yield_now();
+        }
+        assert_eq!(app.world.get::<SplatMap>(loaded), Some(&splat_map));
+
+        // Chunks whose edits were all removed must not load the old file again
+        let chunks: Vec<Entity> = app
+            .world
+            .query_filtered::<Entity, With<PersistentChunk>>()
+            .iter(&app.world)
+            .collect();
+        for entity in chunks {
+            app.world.entity_mut(entity).remove::<SplatMap>();
+        }
+        app.world.send_event(SaveChunks);
+        for _ in 0..100_000 {
+            app.update();
+            if !directory.join("0_0.chunk").exists() {
+                break;
+            }
+            std::thread::yield_now();
+        }
+        // The file is gone, so the chunk has nothing to load however long its task takes
+        let cleared = app.world.spawn(chunk()).id();
+        app.update();
+        let _ = fs::remove_dir_all(&directory);
+        assert!(!directory.join("0_0.chunk").exists());
+        assert_eq!(app.world.get::<SplatMap>(cleared), None);
+    }
+}
@@ -27,22 +27,28 @@
 //! ```
 use bevy::{
     prelude::{
-        App, Assets, Bundle, Component, Handle, Image, Mesh, PbrBundle, Plugin, Query, ResMut,
-        StandardMaterial, Update, Vec3,
+        shape, AlphaMode, App, Assets, BuildChildren, Bundle, Changed, Children, Color, Commands,
+        Component, DespawnRecursiveExt, DetectChangesMut, Entity, EventWriter, Handle, Image, Mesh,
+        Or, PbrBundle, Plugin, Query, Reflect, ReflectComponent, ReflectDeserialize,
+        ReflectSerialize, ResMut, StandardMaterial, Transform, Update, Vec3, With,
     },
-    render::render_resource::{PrimitiveTopology, TextureFormat},
+    render::render_resource::{Extent3d, PrimitiveTopology, TextureDimension, TextureFormat},
 };
 use image::Pixel;
 use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
 
 use crate::{
-    noise::{get_noise_at_point_3d, Function, Gradient, Method, Region},
+    error::{fail, GenerationError, GenerationFailure},
+    modifier::{Crater, Craters},
+    noise::{build_gradient, get_noise_at_point_3d, Function, Gradient, Method, Region},
     util::export_model,
 };
 
 /// Component for planet configuration
-#[derive(Component, Serialize, Deserialize)]
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
 pub struct Planet {
     /// Seed of the noise
     pub seed: u32,
@@ -71,6 +77,15 @@ pub struct Planet {
     /// Percentage of planet that should appear under sea
     /// The mesh below this value will be flat
     pub sea_percent: f32,
+    /// If true, spawns an ocean sphere at sea level as a child of the planet
+    pub ocean: bool,
+    /// Color of the ocean sphere, alpha determines its transparency
+    pub ocean_color: [u8; 4],
+    /// Width of the band (in percent) around sea level where the land color
+    /// is blended with the ocean color. 0 disables blending
+    pub shoreline_blend: f32,
+    /// Craters stamped onto the planet surface
+    pub craters: Option<Craters>,
     /// If true, exports model in glb format
     /// Native: Shows save file dialog.
     /// WASM: Downloads model based on browser configuration.
@@ -104,6 +119,10 @@ impl Default for Planet {
             wireframe: false,
             height_exponent: 1.5,
             sea_percent: 50.0,
+            ocean: false,
+            ocean_color: [30, 90, 180, 180],
+            shoreline_blend: 0.0,
+            craters: None,
             export: false,
         }
     }
@@ -123,10 +142,56 @@ pub struct PlanetPlugin;
 
 impl Plugin for PlanetPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, generate_planet);
+        app.add_event::<GenerationError>().add_systems(
+            Update,
+            (generate_planet, generate_ocean, generate_planet_texture),
+        );
     }
 }
 
+/// Equirectangular surface textures rendered from the `Planet` on the same entity.
+///
+/// The textures can be applied to a simple UV sphere or used as a skybox
+/// instead of the generated planet mesh.
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct PlanetTexture {
+    /// Size of the textures, width should be twice the height
+    pub size: [u32; 2],
+    /// Strength of the bumps in the normal map
+    pub normal_strength: f32,
+    /// Surface color texture
+    #[serde(skip)]
+    pub color: Handle<Image>,
+    /// Tangent space normal map derived from the surface height
+    #[serde(skip)]
+    pub normal: Handle<Image>,
+    /// White where the surface is below sea level, black elsewhere.
+    /// Can be used as a specular or roughness mask
+    #[serde(skip)]
+    pub ocean_mask: Handle<Image>,
+}
+
+impl Default for PlanetTexture {
+    fn default() -> Self {
+        Self {
+            size: [1024, 512],
+            normal_strength: 1.0,
+            color: Handle::default(),
+            normal: Handle::default(),
+            ocean_mask: Handle::default(),
+        }
+    }
+}
+
+/// Marker component for the ocean sphere spawned around a `Planet`
+#[derive(Component)]
+pub struct PlanetOcean;
+
+/// Radius of the ocean sphere, slightly above the flattened sea floor to avoid z-fighting
+const OCEAN_RADIUS: f32 = 1.001;
+
 struct MeshData {
     positions: Vec<[f32; 3]>,
     indices: Vec<u32>,
@@ -136,17 +201,38 @@ struct MeshData {
 }
 
 fn generate_planet(
-    mut images: ResMut<Assets<Image>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut query: Query<(&mut Planet, &mut Handle<Mesh>, &Handle<StandardMaterial>)>,
+    mut errors: EventWriter<GenerationError>,
+    images: Option<ResMut<Assets<Image>>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<(
+        Entity,
+        &mut Planet,
+        &mut Handle<Mesh>,
+        &Handle<StandardMaterial>,
+    )>,
 ) {
-    for (mut planet, mut mesh_handle, material) in &mut query {
+    // Without the render assets there is nothing to generate
+    let (Some(mut images), Some(mut materials), Some(mut meshes)) = (images, materials, meshes)
+    else {
+        return;
+    };
+    for (entity, mut planet, mut mesh_handle, material) in &mut query {
+        // The gradient image is an output, writing it must not count as a change of the configuration
+        let grad = match generate_gradient(&mut images, planet.bypass_change_detection()) {
+            Ok(grad) => grad,
+            Err(reason) => {
+                fail(&mut errors, entity, reason);
+                continue;
+            }
+        };
         if let Some(material) = materials.get_mut(material) {
             *material = StandardMaterial::default();
         }
-
-        let grad = generate_gradient(&mut images, &mut planet);
+        let craters = planet
+            .craters
+            .as_ref()
+            .map_or_else(Vec::new, Craters::sample);
 
         let mut positions: Vec<[f32; 3]> = vec![];
         let mut indices: Vec<u32> = vec![];
@@ -163,7 +249,7 @@ fn generate_planet(
             Vec3::Z,
             Vec3::NEG_Z,
         ] {
-            let mut mesh_data = generate_face(&planet, direction, &grad);
+            let mut mesh_data = generate_face(&planet, direction, &grad, &craters);
             positions.extend(mesh_data.positions);
             mesh_data.indices = mesh_data
                 .indices
@@ -207,36 +293,77 @@ fn generate_planet(
     }
 }
 
-fn generate_gradient(
-    images: &mut ResMut<Assets<Image>>,
-    planet: &mut Planet,
-) -> colorgrad::Gradient {
-    let mut colors: Vec<colorgrad::Color> = Vec::with_capacity(planet.regions.len());
-    let mut domain: Vec<f64> = Vec::with_capacity(planet.regions.len());
-    for region in &planet.regions {
-        colors.push(colorgrad::Color {
-            r: f64::from(region.color[0]) / 255.0,
-            g: f64::from(region.color[1]) / 255.0,
-            b: f64::from(region.color[2]) / 255.0,
-            a: f64::from(region.color[3]) / 255.0,
-        });
-        domain.push(region.position);
-    }
-    let mut grad = colorgrad::CustomGradient::new()
-        .colors(&colors)
-        .domain(&domain)
-        .build()
-        .unwrap_or_else(|_| {
-            colorgrad::CustomGradient::new()
-                .colors(&colors)
-                .build()
-                .expect("Gradient generation failed")
+#[allow(clippy::needless_pass_by_value)]
+fn generate_ocean(
+    mut commands: Commands,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+    planets: Query<(Entity, &Planet, Option<&Children>)>,
+    oceans: Query<&Handle<StandardMaterial>, With<PlanetOcean>>,
+) {
+    // Without the render assets there is nothing to generate
+    let (Some(mut meshes), Some(mut materials)) = (meshes, materials) else {
+        return;
+    };
+    for (entity, planet, children) in &planets {
+        let ocean = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| oceans.contains(**child))
+                .copied()
         });
-
-    if planet.gradient.segments != 0 {
-        grad = grad.sharp(planet.gradient.segments, planet.gradient.smoothness);
+        let [r, g, b, a] = planet.ocean_color;
+        let color = Color::rgba_u8(r, g, b, a);
+        match (planet.ocean, ocean) {
+            (true, Some(ocean)) => {
+                let Ok(handle) = oceans.get(ocean) else {
+                    continue;
+                };
+                // `get_mut` marks the material as modified, which prepares it again
+                if materials
+                    .get(handle)
+                    .is_some_and(|material| material.base_color != color)
+                {
+                    if let Some(material) = materials.get_mut(handle) {
+                        material.base_color = color;
+                    }
+                }
+            }
+            (true, None) => {
+                let ocean = commands
+                    .spawn((
+                        PlanetOcean,
+                        PbrBundle {
+                            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                                radius: 1.0,
+                                sectors: 64,
+                                stacks: 32,
+                            })),
+                            material: materials.add(StandardMaterial {
+                                base_color: color,
+                                alpha_mode: AlphaMode::Blend,
+                                perceptual_roughness: 0.2,
+                                ..Default::default()
+                            }),
+                            transform: Transform::from_scale(Vec3::splat(OCEAN_RADIUS)),
+                            ..Default::default()
+                        },
+                    ))
+                    .id();
+                commands.entity(entity).add_child(ocean);
+            }
+            (false, Some(ocean)) => commands.entity(ocean).despawn_recursive(),
+            (false, None) => {}
+        }
     }
+}
 
+fn generate_gradient(
+    images: &mut ResMut<Assets<Image>>,
+    planet: &mut Planet,
+) -> Result<colorgrad::Gradient, GenerationFailure> {
+    let grad = build_gradient(&planet.regions, &planet.gradient)
+        .map_err(|error| GenerationFailure::Gradient(error.to_string()))?;
     let mut gradient_buffer = image::ImageBuffer::from_pixel(
         planet.gradient.size[0],
         planet.gradient.size[1],
@@ -253,12 +380,17 @@ fn generate_gradient(
     planet.gradient.image = images.add(
         Image::from_dynamic(gradient_buffer.into(), true)
             .convert(TextureFormat::Rgba8UnormSrgb)
-            .expect("Could not convert to Rgba8UnormSrgb"),
+            .ok_or(GenerationFailure::Texture)?,
     );
-    grad
+    Ok(grad)
 }
 
-fn generate_face(planet: &Planet, local_up: Vec3, grad: &colorgrad::Gradient) -> MeshData {
+fn generate_face(
+    planet: &Planet,
+    local_up: Vec3,
+    grad: &colorgrad::Gradient,
+    craters: &[Crater],
+) -> MeshData {
     let axis_a = Vec3::new(local_up.y, local_up.z, local_up.x);
     let axis_b = local_up.cross(axis_a);
     let vertices_count = (planet.resolution * planet.resolution) as usize;
@@ -277,26 +409,12 @@ fn generate_face(planet: &Planet, local_up: Vec3, grad: &colorgrad::Gradient) ->
             let vertex =
                 (local_up + (x_percent - 0.5) * 2.0 * axis_a + (y_percent - 0.5) * 2.0 * axis_b)
                     .normalize();
-            let noise_value = (get_noise_at_point_3d(
-                [
-                    f64::from(vertex[0]),
-                    f64::from(vertex[1]),
-                    f64::from(vertex[2]),
-                ],
-                planet.seed,
-                planet.scale / 100.0,
-                planet.offset,
-                &planet.method,
-                &planet.function,
-            ) as f32
-                + 1.0)
-                * 0.5;
-            let height_value = (0_f32.max(noise_value - planet.sea_percent / 100.0)) * 0.2;
-            let vertex = vertex * (1.0 + height_value.powf(planet.height_exponent));
+            let noise_value = noise_at_direction(planet, craters, vertex);
+            let vertex = vertex * (1.0 + surface_height(planet, noise_value));
             let i = x + y * resolution;
             positions.push([vertex.x, vertex.y, vertex.z]);
             normals.push([vertex.x, vertex.y, vertex.z]);
-            let color = grad.at(f64::from(noise_value) * 100.0);
+            let color = surface_color(planet, grad, noise_value);
             let color = [
                 color.r as f32,
                 color.g as f32,
@@ -325,3 +443,157 @@ fn generate_face(planet: &Planet, local_up: Vec3, grad: &colorgrad::Gradient) ->
         colors,
     }
 }
+
+/// Noise value in the range 0 to 1 at a point on the unit sphere
+fn noise_at_direction(planet: &Planet, craters: &[Crater], direction: Vec3) -> f32 {
+    let point = [
+        f64::from(direction[0]),
+        f64::from(direction[1]),
+        f64::from(direction[2]),
+    ];
+    let mut noise_value = (get_noise_at_point_3d(
+        point,
+        planet.seed,
+        planet.scale / 100.0,
+        planet.offset,
+        &planet.method,
+        &planet.function,
+    ) + 1.0)
+        * 0.5;
+    if let Some(config) = &planet.craters {
+        noise_value += config.offset_at_direction(craters, point) / 100.0;
+    }
+    noise_value.clamp(0.0, 1.0) as f32
+}
+
+/// Height above the unit sphere for a noise value, flat below sea level
+fn surface_height(planet: &Planet, noise_value: f32) -> f32 {
+    let height_value = (0_f32.max(noise_value - planet.sea_percent / 100.0)) * 0.2;
+    height_value.powf(planet.height_exponent)
+}
+
+/// Gradient color for a noise value, blended with the ocean color near the shoreline
+fn surface_color(
+    planet: &Planet,
+    grad: &colorgrad::Gradient,
+    noise_value: f32,
+) -> colorgrad::Color {
+    let color = grad.at(f64::from(noise_value) * 100.0);
+    if planet.shoreline_blend <= 0.0 {
+        return color;
+    }
+    let distance = noise_value.mul_add(100.0, -planet.sea_percent);
+    let t = (distance / planet.shoreline_blend).mul_add(0.5, 0.5);
+    let ocean = planet.ocean_color;
+    colorgrad::Color::from_rgba8(ocean[0], ocean[1], ocean[2], ocean[3])
+        .interpolate_rgb(&color, f64::from(t.clamp(0.0, 1.0)))
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_planet_texture(
+    mut errors: EventWriter<GenerationError>,
+    images: Option<ResMut<Assets<Image>>>,
+    mut query: Query<
+        (Entity, &Planet, &mut PlanetTexture),
+        Or<(Changed<Planet>, Changed<PlanetTexture>)>,
+    >,
+) {
+    // Without the render assets there is nothing to generate
+    let Some(mut images) = images else {
+        return;
+    };
+    for (entity, planet, mut texture) in &mut query {
+        let [width, height] = texture.size;
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let grad = match build_gradient(&planet.regions, &planet.gradient) {
+            Ok(grad) => grad,
+            Err(error) => {
+                fail(
+                    &mut errors,
+                    entity,
+                    GenerationFailure::Gradient(error.to_string()),
+                );
+                continue;
+            }
+        };
+        let craters = planet
+            .craters
+            .as_ref()
+            .map_or_else(Vec::new, Craters::sample);
+
+        let mut noise_values: Vec<f32> = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let latitude = ((y as f32 + 0.5) / height as f32).mul_add(-PI, FRAC_PI_2);
+            for x in 0..width {
+                let longitude = ((x as f32 + 0.5) / width as f32).mul_add(TAU, -PI);
+                let direction = Vec3::new(
+                    latitude.cos() * longitude.cos(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.sin(),
+                );
+                noise_values.push(noise_at_direction(planet, &craters, direction));
+            }
+        }
+        let height_at =
+            |x: u32, y: u32| surface_height(planet, noise_values[(y * width + x) as usize]);
+
+        let pixel_count = (width * height * 4) as usize;
+        let mut color_data: Vec<u8> = Vec::with_capacity(pixel_count);
+        let mut normal_data: Vec<u8> = Vec::with_capacity(pixel_count);
+        let mut ocean_data: Vec<u8> = Vec::with_capacity(pixel_count);
+        for y in 0..height {
+            for x in 0..width {
+                let noise_value = noise_values[(y * width + x) as usize];
+                color_data.extend(surface_color(planet, &grad, noise_value).to_rgba8());
+
+                // Longitude wraps around, latitude is clamped at the poles
+                let dx = height_at((x + 1) % width, y) - height_at((x + width - 1) % width, y);
+                let dy = height_at(x, (y + 1).min(height - 1)) - height_at(x, y.saturating_sub(1));
+                let strength = texture.normal_strength * width as f32;
+                let normal = Vec3::new(-dx * strength, dy * strength, 1.0).normalize();
+                let normal = normal * 0.5 + 0.5;
+                normal_data.extend([
+                    (normal.x * 255.0).round() as u8,
+                    (normal.y * 255.0).round() as u8,
+                    (normal.z * 255.0).round() as u8,
+                    255,
+                ]);
+
+                let ocean = if noise_value * 100.0 < planet.sea_percent {
+                    255
+                } else {
+                    0
+                };
+                ocean_data.extend([ocean, ocean, ocean, 255]);
+            }
+        }
+
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        // Setting the handles must not trigger the system again
+        let texture = texture.bypass_change_detection();
+        texture.color = images.add(Image::new(
+            size,
+            TextureDimension::D2,
+            color_data,
+            TextureFormat::Rgba8UnormSrgb,
+        ));
+        texture.normal = images.add(Image::new(
+            size,
+            TextureDimension::D2,
+            normal_data,
+            TextureFormat::Rgba8Unorm,
+        ));
+        texture.ocean_mask = images.add(Image::new(
+            size,
+            TextureDimension::D2,
+            ocean_data,
+            TextureFormat::Rgba8Unorm,
+        ));
+    }
+}
@@ -0,0 +1,373 @@
+//! Generate plants and trees with L-systems
+//! # Example
+//! For configuration, see [`Plant`](struct.Plant.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::plant::{PlantBundle, PlantPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(PlantPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(PlantBundle::default());
+//! }
+//! ```
+use bevy::{prelude::*, render::render_resource::PrimitiveTopology};
+use serde::{Deserialize, Serialize};
+
+use crate::util::{export_model, Rng};
+
+/// Upper bound for the length of the expanded L-system string
+const MAX_SYMBOLS: usize = 500_000;
+
+/// Rewriting rule of an L-system
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    /// Symbol that is replaced
+    pub symbol: char,
+    /// Symbols replacing `symbol` in every iteration
+    pub replacement: String,
+}
+
+/// Component for plant configuration
+///
+/// The expanded string is interpreted by a turtle starting at the origin, facing up:
+/// - `F` draws a branch segment, `f` moves without drawing
+/// - `+` and `-` turn, `&` and `^` pitch, `\` and `/` roll by `angle`, `|` turns around
+/// - `[` starts a thinner, shorter branch, `]` returns to where the branch started
+/// - `L` places a leaf
+///
+/// Other symbols are only used for rewriting.
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Plant {
+    /// Seed of the angle and length jitter
+    pub seed: u32,
+    /// Initial string of the L-system
+    pub axiom: String,
+    /// Rewriting rules applied to the axiom
+    pub rules: Vec<Rule>,
+    /// Number of times the rules are applied
+    pub iterations: u32,
+    /// Turning angle in degrees
+    pub angle: f32,
+    /// Maximum random deviation of the turning angle in degrees
+    pub angle_jitter: f32,
+    /// Length of a branch segment
+    pub length: f32,
+    /// Maximum random deviation of the segment length, relative to `length`
+    pub length_jitter: f32,
+    /// Factor applied to segment length in every nested branch
+    pub length_scale: f32,
+    /// Radius of the trunk
+    pub radius: f32,
+    /// Factor applied to the radius in every nested branch
+    pub radius_scale: f32,
+    /// Number of sides of the branch cylinders
+    pub sides: u32,
+    /// If true, `L` places leaves made of two crossed quads
+    pub leaves: bool,
+    /// Size of a leaf
+    pub leaf_size: f32,
+    /// Color of the branches
+    pub bark_color: [u8; 4],
+    /// Color of the leaves
+    pub leaf_color: [u8; 4],
+    /// If true, renders plant mesh as wireframe
+    pub wireframe: bool,
+    /// If true, exports model in glb format
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for Plant {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            axiom: "X".to_string(),
+            rules: vec![
+                Rule {
+                    symbol: 'X',
+                    replacement: "F[&+XL]/[&-XL]//[&XL]FX".to_string(),
+                },
+                Rule {
+                    symbol: 'F',
+                    replacement: "FF".to_string(),
+                },
+            ],
+            iterations: 4,
+            angle: 28.0,
+            angle_jitter: 6.0,
+            length: 0.06,
+            length_jitter: 0.2,
+            length_scale: 0.9,
+            radius: 0.04,
+            radius_scale: 0.65,
+            sides: 6,
+            leaves: true,
+            leaf_size: 0.12,
+            bark_color: [100, 70, 45, 255],
+            leaf_color: [60, 140, 50, 255],
+            wireframe: false,
+            export: false,
+        }
+    }
+}
+
+/// Render `Plant` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct PlantBundle {
+    /// Plant configuration
+    pub plant: Plant,
+    /// Generated mesh data is written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate plants
+pub struct PlantPlugin;
+
+impl Plugin for PlantPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_plant);
+    }
+}
+
+/// Mesh data generated from a plant
+struct PlantMesh {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl Plant {
+    /// Applies the rules to the axiom `iterations` times
+    #[must_use]
+    pub fn expand(&self) -> String {
+        let mut current = self.axiom.clone();
+        for _ in 0..self.iterations {
+            let mut next = String::with_capacity(current.len() * 2);
+            for symbol in current.chars() {
+                match self.rules.iter().find(|rule| rule.symbol == symbol) {
+                    Some(rule) => next.push_str(&rule.replacement),
+                    None => next.push(symbol),
+                }
+                if next.len() > MAX_SYMBOLS {
+                    return current;
+                }
+            }
+            current = next;
+        }
+        current
+    }
+
+    /// Generates the plant mesh, for use outside of `PlantPlugin`
+    #[must_use]
+    pub fn mesh(&self) -> Mesh {
+        let PlantMesh {
+            positions,
+            normals,
+            colors,
+            indices,
+        } = self.build();
+        let uvs = vec![[0.0; 2]; positions.len()];
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices)));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh
+    }
+
+    fn build(&self) -> PlantMesh {
+        let mut plant_mesh = PlantMesh {
+            positions: vec![],
+            normals: vec![],
+            colors: vec![],
+            indices: vec![],
+        };
+        let bark_color = self.bark_color.map(|channel| f32::from(channel) / 255.0);
+        let leaf_color = self.leaf_color.map(|channel| f32::from(channel) / 255.0);
+        let sides = self.sides.max(3);
+        let mut rng = Rng::new(u64::from(self.seed));
+        let mut jitter = |amount: f32| (rng.next_f64() as f32).mul_add(2.0, -1.0) * amount;
+
+        let mut position = Vec3::ZERO;
+        let mut rotation = Quat::IDENTITY;
+        let mut length = self.length;
+        let mut radius = self.radius;
+        let mut stack: Vec<(Vec3, Quat, f32, f32)> = vec![];
+
+        for symbol in self.expand().chars() {
+            let angle = (self.angle + jitter(self.angle_jitter)).to_radians();
+            match symbol {
+                'F' | 'f' => {
+                    let segment_length = length * (1.0 + jitter(self.length_jitter));
+                    let end = position + rotation * Vec3::Y * segment_length;
+                    if symbol == 'F' {
+                        plant_mesh
+                            .push_cylinder(position, end, rotation, radius, sides, bark_color);
+                    }
+                    position = end;
+                }
+                '+' => rotation *= Quat::from_rotation_z(angle),
+                '-' => rotation *= Quat::from_rotation_z(-angle),
+                '&' => rotation *= Quat::from_rotation_x(angle),
+                '^' => rotation *= Quat::from_rotation_x(-angle),
+                '\\' => rotation *= Quat::from_rotation_y(angle),
+                '/' => rotation *= Quat::from_rotation_y(-angle),
+                '|' => rotation *= Quat::from_rotation_z(std::f32::consts::PI),
+                '[' => {
+                    stack.push((position, rotation, length, radius));
+                    length *= self.length_scale;
+                    radius *= self.radius_scale;
+                }
+                ']' => {
+                    if let Some(state) = stack.pop() {
+                        (position, rotation, length, radius) = state;
+                    }
+                }
+                'L' if self.leaves => {
+                    plant_mesh.push_leaf(position, rotation, self.leaf_size, leaf_color);
+                }
+                _ => {}
+            }
+        }
+        plant_mesh
+    }
+}
+
+impl PlantMesh {
+    fn push_cylinder(
+        &mut self,
+        start: Vec3,
+        end: Vec3,
+        rotation: Quat,
+        radius: f32,
+        sides: u32,
+        color: [f32; 4],
+    ) {
+        let first = self.positions.len() as u32;
+        for side in 0..=sides {
+            let theta = side as f32 / sides as f32 * std::f32::consts::TAU;
+            let normal = rotation * Vec3::new(theta.cos(), 0.0, theta.sin());
+            for center in [start, end] {
+                self.positions.push((center + normal * radius).to_array());
+                self.normals.push(normal.to_array());
+                self.colors.push(color);
+            }
+        }
+        for side in 0..sides {
+            let bottom = first + side * 2;
+            self.indices.extend([
+                bottom,
+                bottom + 1,
+                bottom + 2,
+                bottom + 2,
+                bottom + 1,
+                bottom + 3,
+            ]);
+        }
+    }
+
+    /// Two crossed quads, each visible from both sides
+    fn push_leaf(&mut self, position: Vec3, rotation: Quat, size: f32, color: [f32; 4]) {
+        let up = rotation * Vec3::Y * size;
+        for side in [rotation * Vec3::X, rotation * Vec3::Z] {
+            let side = side * size / 2.0;
+            for flip in [1.0, -1.0] {
+                let first = self.positions.len() as u32;
+                let normal = (side.cross(up) * flip).normalize_or_zero().to_array();
+                for corner in [
+                    position - side,
+                    position + side,
+                    position + side + up,
+                    position - side + up,
+                ] {
+                    self.positions.push(corner.to_array());
+                    self.normals.push(normal);
+                    self.colors.push(color);
+                }
+                if flip > 0.0 {
+                    self.indices
+                        .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+                } else {
+                    self.indices
+                        .extend([first, first + 2, first + 1, first, first + 3, first + 2]);
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_plant(
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<(&mut Plant, &mut Handle<Mesh>, &Handle<StandardMaterial>), Changed<Plant>>,
+) {
+    // Without the render assets there is nothing to generate
+    let (Some(mut materials), Some(mut meshes)) = (materials, meshes) else {
+        return;
+    };
+    for (mut plant, mut mesh_handle, material) in &mut query {
+        if let Some(material) = materials.get_mut(material) {
+            *material = StandardMaterial {
+                perceptual_roughness: 0.9,
+                ..default()
+            };
+        }
+
+        let PlantMesh {
+            positions,
+            normals,
+            colors,
+            mut indices,
+        } = plant.build();
+        let uvs = vec![[0.0; 2]; positions.len()];
+
+        if plant.wireframe {
+            let triangle_number = indices.len() / 3;
+            let cloned_indices = indices.clone();
+            indices = vec![];
+            for i in 0..triangle_number {
+                for j in &[0, 1, 1, 2, 2, 0] {
+                    indices.push(cloned_indices[i * 3 + j]);
+                }
+            }
+        }
+
+        let mut mesh = if plant.wireframe {
+            Mesh::new(PrimitiveTopology::LineList)
+        } else {
+            Mesh::new(PrimitiveTopology::TriangleList)
+        };
+        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        *mesh_handle = meshes.add(mesh);
+
+        if plant.export {
+            export_model(&positions, indices, &colors);
+            plant.bypass_change_detection().export = false;
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Place points of interest on terrain
+//! # Example
+//! For configuration, see [`PointsOfInterest`](struct.PointsOfInterest.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::poi::{PoiPlugin, PointOfInterest, PointsOfInterest};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, PoiPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_points)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), PointsOfInterest::default()));
+//! }
+//!
+//! fn print_points(query: Query<(&PointOfInterest, &GlobalTransform), Added<PointOfInterest>>) {
+//!     for (point, transform) in &query {
+//!         println!("{} at {}", point.kind, transform.translation());
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    suitability::SuitabilityMap,
+    terrain::{GeneratedTerrain, Terrain},
+    util::Rng,
+};
+
+/// Constraints for one kind of point of interest
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PoiType {
+    /// Kind of the point, copied to `PointOfInterest::kind`
+    pub kind: String,
+    /// Number of points to place, fewer are placed if the constraints can not be met
+    pub count: u32,
+    /// Minimum distance to other points of the same kind in world units
+    pub min_spacing: f32,
+    /// Minimum height above sea level in world units
+    pub min_height: f32,
+    /// Maximum height above sea level in world units
+    pub max_height: f32,
+    /// Maximum slope in degrees
+    pub max_slope: f32,
+    /// Labels of the noise regions the points are placed in, any region if empty
+    pub regions: Vec<String>,
+    /// Minimum `SuitabilityMap` score from 0 to 1, ignored if the terrain has no `SuitabilityMap`
+    pub min_suitability: f32,
+}
+
+impl Default for PoiType {
+    fn default() -> Self {
+        Self {
+            kind: String::new(),
+            count: 1,
+            min_spacing: 0.0,
+            min_height: 0.05,
+            max_height: f32::MAX,
+            max_slope: 30.0,
+            regions: vec![],
+            min_suitability: 0.0,
+        }
+    }
+}
+
+/// Component for point of interest configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct PointsOfInterest {
+    /// Seed used to place the points
+    pub seed: u32,
+    /// Kinds of points, placed in order
+    pub types: Vec<PoiType>,
+    /// Minimum distance between any two points in world units
+    pub min_spacing: f32,
+    /// Number of random candidates tried for every point
+    pub attempts: u32,
+}
+
+impl Default for PointsOfInterest {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            types: vec![
+                PoiType {
+                    kind: "spawn".to_string(),
+                    max_slope: 10.0,
+                    ..default()
+                },
+                PoiType {
+                    kind: "dungeonEntrance".to_string(),
+                    count: 3,
+                    min_spacing: 1.0,
+                    min_height: 0.3,
+                    ..default()
+                },
+                PoiType {
+                    kind: "quest".to_string(),
+                    count: 5,
+                    min_spacing: 0.5,
+                    ..default()
+                },
+            ],
+            min_spacing: 0.2,
+            attempts: 100,
+        }
+    }
+}
+
+/// Marker for a placed point of interest, spawned as a child of the terrain entity
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct PointOfInterest {
+    /// Kind of the point, see `PoiType::kind`
+    pub kind: String,
+}
+
+/// Plugin to place points of interest
+pub struct PoiPlugin;
+
+impl Plugin for PoiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, place_points);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn place_points(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &PointsOfInterest,
+            &Terrain,
+            &GeneratedTerrain,
+            Option<&SuitabilityMap>,
+        ),
+        Or<(
+            Changed<PointsOfInterest>,
+            Changed<GeneratedTerrain>,
+            Changed<SuitabilityMap>,
+        )>,
+    >,
+    points: Query<(Entity, &Parent), With<PointOfInterest>>,
+) {
+    for (entity, config, terrain, terrain_data, suitability) in &query {
+        for (point, parent) in &points {
+            if parent.get() == entity {
+                commands.entity(point).despawn_recursive();
+            }
+        }
+        commands.entity(entity).with_children(|children| {
+            for (kind, position) in place(config, terrain, terrain_data, suitability) {
+                children.spawn((
+                    PointOfInterest { kind },
+                    SpatialBundle::from_transform(Transform::from_translation(position)),
+                ));
+            }
+        });
+    }
+}
+
+fn place(
+    config: &PointsOfInterest,
+    terrain: &Terrain,
+    terrain_data: &GeneratedTerrain,
+    suitability: Option<&SuitabilityMap>,
+) -> Vec<(String, Vec3)> {
+    let mut rng = Rng::new(u64::from(config.seed));
+    let [width, depth] = terrain_data.size.map(|side| side as f32);
+    let mut placed: Vec<(String, Vec3)> = vec![];
+    for poi_type in &config.types {
+        let max_slope = poi_type.max_slope.to_radians();
+        let mut count = 0;
+        for _ in 0..poi_type.count.saturating_mul(config.attempts.max(1)) {
+            if count >= poi_type.count {
+                break;
+            }
+            let x = (rng.next_f64() as f32 - 0.5) * width;
+            let z = (rng.next_f64() as f32 - 0.5) * depth;
+            let Some(height) = terrain_data.height(x, z) else {
+                continue;
+            };
+            let above_sea = height - terrain_data.sea_level;
+            if above_sea < poi_type.min_height
+                || above_sea > poi_type.max_height
+                || terrain_data
+                    .slope(x, z)
+                    .is_none_or(|slope| slope > max_slope)
+            {
+                continue;
+            }
+            if suitability
+                .and_then(|suitability| suitability.score_at(x, z))
+                .is_some_and(|score| score < poi_type.min_suitability)
+            {
+                continue;
+            }
+            if !poi_type.regions.is_empty() {
+                let region = terrain_data
+                    .noise_at(x, z)
+                    .and_then(|value| terrain.noise.region(value));
+                if !region.is_some_and(|region| poi_type.regions.contains(&region.label)) {
+                    continue;
+                }
+            }
+            let position = Vec3::new(x, height, z);
+            let clear = placed.iter().all(|(kind, other)| {
+                let distance = other.xz().distance(position.xz());
+                distance >= config.min_spacing
+                    && (*kind != poi_type.kind || distance >= poi_type.min_spacing)
+            });
+            if clear {
+                placed.push((poi_type.kind.clone(), position));
+                count += 1;
+            }
+        }
+    }
+    placed
+}
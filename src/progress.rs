@@ -0,0 +1,76 @@
+//! Report the progress of generations spread over several frames
+//! # Example
+//! Terrains with `Terrain::columns_per_frame` generate their noise a few columns every frame
+//! and insert a `GenerationProgress`, which can drive a loading bar
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::progress::GenerationProgress;
+//! use bevy_generative::terrain::{Terrain, TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, report)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(TerrainBundle {
+//!         terrain: Terrain {
+//!             size: [20, 20],
+//!             columns_per_frame: Some(16),
+//!             ..default()
+//!         },
+//!         ..default()
+//!     });
+//! }
+//!
+//! fn report(query: Query<&GenerationProgress, Changed<GenerationProgress>>) {
+//!     for progress in &query {
+//!         println!("{} {:.0}%", progress.stage, progress.progress * 100.0);
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+
+/// Stage of a generation that is still running
+pub const STAGE_NOISE: &str = "noise";
+/// Stage of a finished generation
+pub const STAGE_DONE: &str = "done";
+
+/// Progress of the generation on the same entity, updated as work proceeds
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct GenerationProgress {
+    /// Fraction of the work done, from 0 to 1
+    pub progress: f32,
+    /// Name of the current stage, e.g. [`STAGE_NOISE`]
+    pub stage: String,
+}
+
+impl Default for GenerationProgress {
+    fn default() -> Self {
+        Self {
+            progress: 0.0,
+            stage: STAGE_NOISE.to_string(),
+        }
+    }
+}
+
+impl GenerationProgress {
+    /// Progress of a finished generation
+    #[must_use]
+    pub fn done() -> Self {
+        Self {
+            progress: 1.0,
+            stage: STAGE_DONE.to_string(),
+        }
+    }
+
+    /// Returns true once the generation has finished
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.progress >= 1.0
+    }
+}
@@ -0,0 +1,428 @@
+//! Trace rivers along the flow over terrain and build their water surfaces
+//! # Example
+//! For configuration, see [`Rivers`](struct.Rivers.html).
+//! Rivers follow the `FlowData` of the terrain, see [`Flow`](../flow/struct.Flow.html).
+//! The splines are inserted as a `RiverNetwork` and the ribbon meshes along them are spawned as a `RiverSurface` child.
+//! The `v` coordinate of the surface runs downstream, so scrolling a texture along it animates the flow.
+//! Waterfalls are spawned as `Waterfall` children of the terrain where the rivers drop steeply
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::flow::{Flow, FlowPlugin};
+//! use bevy_generative::river::{RiverPlugin, Rivers};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, FlowPlugin, RiverPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Flow::default(),
+//!         Rivers {
+//!             width: 0.03,
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use std::cmp::Reverse;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{flow::FlowData, headless::DataOnly, road::spline, terrain::GeneratedTerrain};
+
+/// Component for river configuration, added to an entity with a `Terrain` and a `Flow` component
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Rivers {
+    /// Number of vertices that must drain through a vertex for it to carry a river
+    pub river_accumulation: f32,
+    /// Rivers with fewer vertices are dropped, tributaries count up to the river they join
+    pub min_length: u32,
+    /// Distance between the control points of the river splines, in terrain vertices
+    pub spline_spacing: u32,
+    /// Width in world units where a river starts, at `river_accumulation`
+    pub width: f32,
+    /// Widest a river gets in world units, widths grow with the square root of the accumulation
+    pub max_width: f32,
+    /// Height of the surface above the riverbed in world units
+    pub depth: f32,
+    /// World units along the river covered by one repetition of the `v` coordinate
+    pub uv_length: f32,
+    /// Color of the surface
+    pub color: [u8; 4],
+    /// Roughness of the surface
+    pub roughness: f32,
+    /// Drop per world unit along a river where it falls as a waterfall, 1 is 45 degrees
+    pub waterfall_slope: f32,
+    /// Lowest drop in world units marked as a `Waterfall`
+    pub waterfall_height: f32,
+}
+
+impl Default for Rivers {
+    fn default() -> Self {
+        Self {
+            river_accumulation: 30.0,
+            min_length: 4,
+            spline_spacing: 2,
+            width: 0.01,
+            max_width: 0.08,
+            depth: 0.004,
+            uv_length: 0.1,
+            color: [40, 105, 150, 210],
+            roughness: 0.1,
+            waterfall_slope: 1.0,
+            waterfall_height: 0.02,
+        }
+    }
+}
+
+/// River from its source to the sea, a sink, the side of the terrain or the river it joins
+#[derive(Clone, PartialEq, Debug)]
+pub struct River {
+    /// Points along the river spline from the source downstream, relative to the terrain entity
+    pub points: Vec<Vec3>,
+    /// Width of the river at every point in world units
+    pub widths: Vec<f32>,
+}
+
+/// Generated river splines, inserted on the entity with the `Rivers` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct RiverNetwork {
+    /// Rivers, longest first
+    pub rivers: Vec<River>,
+    /// Steep drops along the rivers, see [`Rivers::waterfall_slope`]
+    pub waterfalls: Vec<Waterfall>,
+}
+
+/// Waterfall where a river drops steeply, spawned as a child of the terrain at its lip.
+/// The transform looks downstream, so particle effects and audio can be attached to the entity as they are
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct Waterfall {
+    /// Top of the waterfall on the river surface, relative to the terrain entity
+    pub position: Vec3,
+    /// Horizontal direction of the flow over the lip
+    pub direction: Vec3,
+    /// Drop in world units
+    pub height: f32,
+    /// Width of the river at the lip in world units
+    pub width: f32,
+}
+
+/// Marker of the river surface child of a terrain with a `Rivers` component
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct RiverSurface;
+
+/// Plugin to generate rivers
+pub struct RiverPlugin;
+
+impl Plugin for RiverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (generate_rivers, remove_rivers));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_rivers(
+    mut commands: Commands,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    query: Query<
+        (
+            Entity,
+            &Rivers,
+            &GeneratedTerrain,
+            &FlowData,
+            Option<&Children>,
+            Has<DataOnly>,
+        ),
+        Or<(
+            Changed<Rivers>,
+            Changed<GeneratedTerrain>,
+            Changed<FlowData>,
+        )>,
+    >,
+    mut surfaces: Query<(&mut Handle<Mesh>, &Handle<StandardMaterial>), With<RiverSurface>>,
+    waterfalls: Query<&Waterfall>,
+) {
+    for (entity, rivers, terrain_data, flow, children, data_only) in &query {
+        let network = generate_network(rivers, terrain_data, flow);
+        let old_waterfalls: Vec<(Entity, &Waterfall)> = children
+            .into_iter()
+            .flatten()
+            .filter_map(|&child| Some((child, waterfalls.get(child).ok()?)))
+            .collect();
+        // Waterfalls are kept while they stay the same, so effects attached to them keep running
+        if !old_waterfalls
+            .iter()
+            .map(|(_, waterfall)| *waterfall)
+            .eq(&network.waterfalls)
+        {
+            for (child, _) in old_waterfalls {
+                commands.entity(child).despawn_recursive();
+            }
+            for waterfall in &network.waterfalls {
+                let mut transform = Transform::from_translation(waterfall.position);
+                if waterfall.direction != Vec3::ZERO {
+                    transform.look_to(waterfall.direction, Vec3::Y);
+                }
+                let child = commands
+                    .spawn((
+                        *waterfall,
+                        SpatialBundle {
+                            transform,
+                            ..default()
+                        },
+                    ))
+                    .id();
+                commands.entity(entity).add_child(child);
+            }
+        }
+        // Without the render assets only the network is generated
+        let (false, Some(meshes), Some(materials)) =
+            (data_only, meshes.as_deref_mut(), materials.as_deref_mut())
+        else {
+            commands.entity(entity).insert(network);
+            continue;
+        };
+        let mesh = meshes.add(surface_mesh(&network, rivers.uv_length));
+        let [r, g, b, a] = rivers.color;
+        let material = StandardMaterial {
+            base_color: Color::rgba_u8(r, g, b, a),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: rivers.roughness,
+            ..default()
+        };
+        commands.entity(entity).insert(network);
+
+        let surface = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| surfaces.contains(**child))
+                .copied()
+        });
+        let existing = surface.and_then(|surface| surfaces.get_mut(surface).ok());
+        if let Some((mut mesh_handle, material_handle)) = existing {
+            *mesh_handle = mesh;
+            if let Some(existing) = materials.get_mut(material_handle) {
+                *existing = material;
+            }
+        } else {
+            let surface = commands
+                .spawn((
+                    RiverSurface,
+                    PbrBundle {
+                        mesh,
+                        material: materials.add(material),
+                        ..default()
+                    },
+                ))
+                .id();
+            commands.entity(entity).add_child(surface);
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn remove_rivers(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Rivers>,
+    children: Query<&Children>,
+    surfaces: Query<(), Or<(With<RiverSurface>, With<Waterfall>)>>,
+) {
+    for entity in removed.read() {
+        let Ok(children) = children.get(entity) else {
+            continue;
+        };
+        for &child in children {
+            if surfaces.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Traces every river from its source until it leaves the river vertices or reaches a river traced before.
+/// Sources are traced by descending accumulation at their mouth, so the main rivers are traced first
+fn generate_network(rivers: &Rivers, terrain: &GeneratedTerrain, flow: &FlowData) -> RiverNetwork {
+    let accumulation = |(i, j): (usize, usize)| {
+        flow.accumulation
+            .get(i)
+            .and_then(|column| column.get(j))
+            .copied()
+            .unwrap_or_default()
+    };
+    let is_river = |(i, j): (usize, usize)| {
+        let Some(&height) = terrain.heights.get(i).and_then(|column| column.get(j)) else {
+            return false;
+        };
+        height > terrain.sea_level + f32::EPSILON
+            && accumulation((i, j)) >= rivers.river_accumulation
+    };
+    let columns = terrain.heights.len();
+    let rows = terrain.heights.first().map_or(0, Vec::len);
+
+    // Sources are river vertices without an upstream river vertex
+    let mut fed = vec![vec![false; rows]; columns];
+    for i in 0..columns {
+        for j in 0..rows {
+            if is_river((i, j)) {
+                if let Some((di, dj)) = flow.downstream(i, j) {
+                    if let Some(fed) = fed.get_mut(di).and_then(|column| column.get_mut(dj)) {
+                        *fed = true;
+                    }
+                }
+            }
+        }
+    }
+    let mut paths: Vec<Vec<(usize, usize)>> = (0..columns)
+        .flat_map(|i| (0..rows).map(move |j| (i, j)))
+        .filter(|&vertex| is_river(vertex) && !fed[vertex.0][vertex.1])
+        .map(|(i, j)| flow.path(i, j))
+        .collect();
+    paths.sort_by(|a, b| {
+        let mouth = |path: &[(usize, usize)]| {
+            path.iter()
+                .copied()
+                .filter(|&vertex| is_river(vertex))
+                .map(accumulation)
+                .fold(0.0, f32::max)
+        };
+        mouth(b).total_cmp(&mouth(a)).then_with(|| a.cmp(b))
+    });
+
+    let mut traced = vec![vec![false; rows]; columns];
+    let mut network = RiverNetwork::default();
+    for path in paths {
+        let mut vertices = Vec::new();
+        for vertex in path {
+            vertices.push(vertex);
+            // The river ends in the first vertex that is not a river or was traced before
+            if !is_river(vertex) || traced[vertex.0][vertex.1] {
+                break;
+            }
+            traced[vertex.0][vertex.1] = true;
+        }
+        if vertices.len() < rivers.min_length.max(2) as usize {
+            continue;
+        }
+        let controls: Vec<Vec4> = vertices
+            .iter()
+            .filter_map(|&(i, j)| {
+                let position = terrain.vertex_position(i, j)?;
+                let strength = accumulation((i, j)) / rivers.river_accumulation.max(f32::EPSILON);
+                let width = (rivers.width * strength.max(1.0).sqrt()).min(rivers.max_width);
+                Some(position.extend(width))
+            })
+            .collect();
+        let samples = spline(&controls, rivers.spline_spacing.max(1) as usize);
+        let (points, widths) = samples
+            .into_iter()
+            .map(|sample| {
+                let point = sample.truncate();
+                // The spline must not cut through the banks between its control points
+                let bed = terrain.height(point.x, point.z).unwrap_or(point.y);
+                (
+                    Vec3::new(point.x, point.y.max(bed) + rivers.depth, point.z),
+                    sample.w,
+                )
+            })
+            .unzip();
+        let river = River { points, widths };
+        network.waterfalls.extend(find_waterfalls(
+            &river,
+            rivers.waterfall_slope,
+            rivers.waterfall_height,
+        ));
+        network.rivers.push(river);
+    }
+    network
+        .rivers
+        .sort_by_key(|river| Reverse(river.points.len()));
+    network
+}
+
+/// Runs of segments of `river` steeper than `slope` that drop at least `min_height`
+fn find_waterfalls(river: &River, slope: f32, min_height: f32) -> Vec<Waterfall> {
+    let mut waterfalls = Vec::new();
+    let mut lip: Option<usize> = None;
+    for k in 0..river.points.len() {
+        let steep = river.points.get(k + 1).is_some_and(|&next| {
+            let point = river.points[k];
+            let run = Vec2::new(next.x - point.x, next.z - point.z).length();
+            point.y > next.y && point.y - next.y >= run * slope
+        });
+        match (steep, lip) {
+            (true, None) => lip = Some(k),
+            (false, Some(top)) => {
+                lip = None;
+                let (position, bottom) = (river.points[top], river.points[k]);
+                let height = position.y - bottom.y;
+                if height >= min_height {
+                    let direction = Vec3::new(bottom.x - position.x, 0.0, bottom.z - position.z);
+                    waterfalls.push(Waterfall {
+                        position,
+                        direction: direction.normalize_or_zero(),
+                        height,
+                        width: river.widths[top],
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    waterfalls
+}
+
+/// Ribbon along every river of `network`, `u` is 0 to 1 across and `v` grows downstream by 1 every `uv_length`
+fn surface_mesh(network: &RiverNetwork, uv_length: f32) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for river in &network.rivers {
+        let points = &river.points;
+        let mut length = 0.0;
+        for (k, (&point, &width)) in points.iter().zip(&river.widths).enumerate() {
+            let previous = points[k.saturating_sub(1)];
+            let next = points[(k + 1).min(points.len() - 1)];
+            let along = Vec2::new(next.x - previous.x, next.z - previous.z).normalize_or_zero();
+            let side = Vec3::new(-along.y, 0.0, along.x) * width / 2.0;
+            length += Vec2::new(point.x - previous.x, point.z - previous.z).length();
+            let v = length / uv_length.max(f32::EPSILON);
+            let first = positions.len() as u32;
+            positions.extend([(point - side).to_array(), (point + side).to_array()]);
+            uvs.extend([[0.0, v], [1.0, v]]);
+            if k > 0 {
+                // Counter-clockwise seen from above
+                indices.extend([first - 2, first - 1, first, first - 1, first + 1, first]);
+            }
+        }
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 1.0, 0.0]; positions.len()],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
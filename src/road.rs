@@ -0,0 +1,444 @@
+//! Generate road networks on terrain
+//! # Example
+//! For configuration, see [`Roads`](struct.Roads.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::road::{RoadPlugin, Roads};
+//! use bevy_generative::settlement::{Settlement, SettlementPlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, SettlementPlugin, RoadPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Settlement::default(),
+//!         Roads::default(),
+//!     ));
+//! }
+//! ```
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    ops::{Add, Mul, Sub},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{settlement::SettlementLayout, terrain::GeneratedTerrain};
+
+/// Component for road network configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Roads {
+    /// Points connected by roads, relative to the terrain entity
+    pub points: Vec<[f32; 2]>,
+    /// If true, the centers of all settlements on the terrain are connected as well
+    pub connect_settlements: bool,
+    /// Additional cost for climbing, multiplied with the slope of a step
+    pub slope_cost: f32,
+    /// Additional cost for crossing the sea, multiplied with the length of a step
+    pub water_cost: f32,
+    /// Cost multiplier for travelling along existing roads.
+    /// Lower values merge roads into shared trunk roads
+    pub reuse_cost: f32,
+    /// Distance between the control points of the road splines, in terrain vertices
+    pub spline_spacing: u32,
+    /// If true, the terrain is flattened along the roads
+    pub flatten: bool,
+    /// Width of the flattened road surface in world units
+    pub width: f32,
+    /// Width of the transition between the road and the terrain in world units
+    pub falloff: f32,
+}
+
+impl Default for Roads {
+    fn default() -> Self {
+        Self {
+            points: vec![],
+            connect_settlements: true,
+            slope_cost: 20.0,
+            water_cost: 50.0,
+            reuse_cost: 0.3,
+            spline_spacing: 3,
+            flatten: true,
+            width: 0.03,
+            falloff: 0.03,
+        }
+    }
+}
+
+/// Road between two nodes of a `RoadNetwork`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Road {
+    /// Index of the first node
+    pub from: usize,
+    /// Index of the second node
+    pub to: usize,
+    /// Points along the road spline, relative to the terrain entity
+    pub points: Vec<Vec3>,
+}
+
+/// Generated road graph, inserted on the entity with the `Roads` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct RoadNetwork {
+    /// Connected points and junctions where roads merge, relative to the terrain entity
+    pub nodes: Vec<Vec3>,
+    /// Roads connecting the nodes
+    pub roads: Vec<Road>,
+    /// If true, the terrain is flattened along the roads
+    pub flatten: bool,
+    /// Width of the flattened road surface in world units
+    pub width: f32,
+    /// Width of the transition between the road and the terrain in world units
+    pub falloff: f32,
+}
+
+impl RoadNetwork {
+    /// Blends `heights` towards the road surface, `heights` are indexed by `[x][z]`
+    pub(crate) fn flatten(&self, heights: &mut [Vec<f32>], resolution: u32, size: [u32; 2]) {
+        if !self.flatten {
+            return;
+        }
+        let resolution = resolution.max(1) as f32;
+        let half_width = self.width / 2.0;
+        let reach = half_width + self.falloff;
+        let columns = heights.len();
+        let rows = heights.first().map_or(0, Vec::len);
+        let index = |coordinate: f32, side: u32, count: usize| {
+            (((coordinate + side as f32 / 2.0) * resolution).max(0.0) as usize).min(count - 1)
+        };
+        for segment in self.roads.iter().flat_map(|road| road.points.windows(2)) {
+            let (start, end) = (segment[0], segment[1]);
+            let min_x = index(start.x.min(end.x) - reach, size[0], columns);
+            let max_x = index(start.x.max(end.x) + reach, size[0], columns) + 1;
+            let min_z = index(start.z.min(end.z) - reach, size[1], rows);
+            let max_z = index(start.z.max(end.z) + reach, size[1], rows) + 1;
+            let direction = end.xz() - start.xz();
+            let length_squared = direction.length_squared().max(f32::EPSILON);
+            for (i, column) in heights
+                .iter_mut()
+                .enumerate()
+                .take(max_x.min(columns))
+                .skip(min_x)
+            {
+                for (j, height) in column
+                    .iter_mut()
+                    .enumerate()
+                    .take(max_z.min(rows))
+                    .skip(min_z)
+                {
+                    let point = Vec2::new(
+                        i as f32 / resolution - size[0] as f32 / 2.0,
+                        j as f32 / resolution - size[1] as f32 / 2.0,
+                    );
+                    let t = ((point - start.xz()).dot(direction) / length_squared).clamp(0.0, 1.0);
+                    let closest = start.xz() + direction * t;
+                    let distance = point.distance(closest);
+                    if distance >= reach {
+                        continue;
+                    }
+                    let road_height = (end.y - start.y).mul_add(t, start.y);
+                    let blend = if distance <= half_width {
+                        1.0
+                    } else {
+                        let x = 1.0 - (distance - half_width) / self.falloff.max(f32::EPSILON);
+                        x * x * 2.0f32.mul_add(-x, 3.0)
+                    };
+                    *height += (road_height - *height) * blend;
+                }
+            }
+        }
+    }
+}
+
+/// Plugin to generate road networks
+pub struct RoadPlugin;
+
+impl Plugin for RoadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_roads);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_roads(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &Roads,
+            &GeneratedTerrain,
+            Option<&SettlementLayout>,
+            Option<&RoadNetwork>,
+        ),
+        Or<(
+            Changed<Roads>,
+            Changed<GeneratedTerrain>,
+            Changed<SettlementLayout>,
+        )>,
+    >,
+) {
+    for (entity, roads, terrain_data, settlements, network) in &query {
+        let mut points = roads.points.clone();
+        if roads.connect_settlements {
+            if let Some(settlements) = settlements {
+                points.extend(
+                    settlements
+                        .sites
+                        .iter()
+                        .map(|site| [site.center.x, site.center.z]),
+                );
+            }
+        }
+        let new_network = generate_network(roads, terrain_data, &points);
+        if network != Some(&new_network) {
+            commands.entity(entity).insert(new_network);
+        }
+    }
+}
+
+/// Roads are planned on the heights before flattening, so flattening does not change the network
+fn generate_network(roads: &Roads, terrain: &GeneratedTerrain, points: &[[f32; 2]]) -> RoadNetwork {
+    let heights = &terrain.base_heights;
+    let columns = heights.len();
+    let rows = heights.first().map_or(0, Vec::len);
+    let mut network = RoadNetwork {
+        flatten: roads.flatten,
+        width: roads.width,
+        falloff: roads.falloff,
+        ..default()
+    };
+    if columns < 2 || rows < 2 {
+        return network;
+    }
+    let resolution = terrain.resolution.max(1) as f32;
+    let position = |cell: usize| {
+        let (i, j) = (cell / rows, cell % rows);
+        Vec3::new(
+            i as f32 / resolution - terrain.size[0] as f32 / 2.0,
+            heights[i][j],
+            j as f32 / resolution - terrain.size[1] as f32 / 2.0,
+        )
+    };
+    let to_cell = |point: [f32; 2]| {
+        let i = ((point[0] + terrain.size[0] as f32 / 2.0) * resolution).round();
+        let j = ((point[1] + terrain.size[1] as f32 / 2.0) * resolution).round();
+        (i as usize).min(columns - 1) * rows + (j as usize).min(rows - 1)
+    };
+
+    // Paths are stored as cell indices while planning
+    let mut node_cells: Vec<usize> = points.iter().map(|&point| to_cell(point)).collect();
+    node_cells.dedup();
+    let mut paths: Vec<(usize, usize, Vec<usize>)> = vec![];
+    let mut connected = vec![false; node_cells.len()];
+    if let Some(first) = connected.first_mut() {
+        *first = true;
+    }
+    for _ in 1..connected.len() {
+        // Connect the point closest to the network first
+        let Some(next) = (0..node_cells.len())
+            .filter(|&i| !connected[i])
+            .min_by(|&a, &b| {
+                let distance = |i: usize| {
+                    (0..node_cells.len())
+                        .filter(|&j| connected[j])
+                        .map(|j| {
+                            position(node_cells[i])
+                                .xz()
+                                .distance(position(node_cells[j]).xz())
+                        })
+                        .fold(f32::INFINITY, f32::min)
+                };
+                distance(a).total_cmp(&distance(b))
+            })
+        else {
+            break;
+        };
+        connected[next] = true;
+
+        let mut on_road = vec![None; columns * rows];
+        for (road, (_, _, path)) in paths.iter().enumerate() {
+            for (index, &cell) in path.iter().enumerate() {
+                on_road[cell] = Some((road, index));
+            }
+        }
+        let targets: Vec<bool> = (0..columns * rows)
+            .map(|cell| {
+                on_road[cell].is_some()
+                    || node_cells
+                        .iter()
+                        .zip(&connected)
+                        .any(|(&node, &connected)| {
+                            connected && node == cell && cell != node_cells[next]
+                        })
+            })
+            .collect();
+        let Some(path) = find_path(roads, terrain, node_cells[next], &targets, &on_road) else {
+            continue;
+        };
+        let Some(&end) = path.last() else {
+            continue;
+        };
+        let end_node = if let Some(node) = node_cells.iter().position(|&node| node == end) {
+            node
+        } else if let Some((road, index)) = on_road[end] {
+            // Split the existing road at a new junction
+            node_cells.push(end);
+            connected.push(true);
+            let junction = node_cells.len() - 1;
+            let (from, to, cells) = paths[road].clone();
+            paths[road] = (from, junction, cells[..=index].to_vec());
+            paths.push((junction, to, cells[index..].to_vec()));
+            junction
+        } else {
+            continue;
+        };
+        paths.push((next, end_node, path));
+    }
+
+    network.nodes = node_cells.iter().map(|&cell| position(cell)).collect();
+    network.roads = paths
+        .into_iter()
+        .map(|(from, to, cells)| Road {
+            from,
+            to,
+            points: spline(
+                &cells.iter().map(|&cell| position(cell)).collect::<Vec<_>>(),
+                roads.spline_spacing.max(1) as usize,
+            ),
+        })
+        .collect();
+    network
+}
+
+/// Dijkstra from `start` to the closest target cell, 8-connected over the terrain vertices
+fn find_path(
+    roads: &Roads,
+    terrain: &GeneratedTerrain,
+    start: usize,
+    targets: &[bool],
+    on_road: &[Option<(usize, usize)>],
+) -> Option<Vec<usize>> {
+    let heights = &terrain.base_heights;
+    let columns = heights.len();
+    let rows = heights.first().map_or(0, Vec::len);
+    let step = 1.0 / terrain.resolution.max(1) as f32;
+    let is_sea = |i: usize, j: usize| heights[i][j] <= terrain.sea_level + f32::EPSILON;
+
+    let mut costs = vec![f32::INFINITY; columns * rows];
+    let mut previous = vec![usize::MAX; columns * rows];
+    let mut queue = BinaryHeap::new();
+    costs[start] = 0.0;
+    // Costs are stored as ordered bits, valid for non-negative floats
+    queue.push(Reverse((0_u32, start)));
+    while let Some(Reverse((cost_bits, cell))) = queue.pop() {
+        let cost = f32::from_bits(cost_bits);
+        if cost > costs[cell] {
+            continue;
+        }
+        if targets[cell] {
+            let mut path = vec![cell];
+            let mut current = cell;
+            while current != start {
+                current = previous[current];
+                path.push(current);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        let (i, j) = (cell / rows, cell % rows);
+        for (di, dj) in [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ] {
+            let (Some(ni), Some(nj)) = (i.checked_add_signed(di), j.checked_add_signed(dj)) else {
+                continue;
+            };
+            if ni >= columns || nj >= rows {
+                continue;
+            }
+            let next = ni * rows + nj;
+            let distance = step
+                * if di != 0 && dj != 0 {
+                    2_f32.sqrt()
+                } else {
+                    1.0
+                };
+            let slope = (heights[ni][nj] - heights[i][j]).abs() / distance;
+            let mut step_cost = distance * roads.slope_cost.mul_add(slope, 1.0);
+            if is_sea(ni, nj) {
+                step_cost += distance * roads.water_cost;
+            }
+            if on_road[next].is_some() {
+                step_cost *= roads.reuse_cost;
+            }
+            let next_cost = cost + step_cost.max(0.0);
+            if next_cost < costs[next] {
+                costs[next] = next_cost;
+                previous[next] = cell;
+                queue.push(Reverse((next_cost.to_bits(), next)));
+            }
+        }
+    }
+    None
+}
+
+/// Catmull-Rom spline through every `spacing`th point, keeping both ends.
+/// Points can carry more than a position, e.g. `Vec4` with a width
+pub(crate) fn spline<T>(points: &[T], spacing: usize) -> Vec<T>
+where
+    T: Copy + PartialEq + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let mut controls: Vec<T> = points.iter().step_by(spacing).copied().collect();
+    if let Some(&last) = points.last() {
+        if controls.last() != Some(&last) {
+            controls.push(last);
+        }
+    }
+    if controls.len() < 3 {
+        return controls;
+    }
+    let mut samples = vec![controls[0]];
+    for i in 0..controls.len() - 1 {
+        let p0 = controls[i.saturating_sub(1)];
+        let p1 = controls[i];
+        let p2 = controls[i + 1];
+        let p3 = controls[(i + 2).min(controls.len() - 1)];
+        for s in 1..=4 {
+            let t = s as f32 / 4.0;
+            let t2 = t * t;
+            let t3 = t2 * t;
+            samples.push(
+                (p1 * 2.0
+                    + (p2 - p0) * t
+                    + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+                    + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+                    * 0.5,
+            );
+        }
+    }
+    samples
+}
@@ -0,0 +1,207 @@
+//! Generate rocks and asteroids
+//! # Example
+//! For configuration, see [`Rock`](struct.Rock.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::rock::{RockBundle, RockPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(RockPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(RockBundle::default());
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::{mesh::VertexAttributeValues, render_resource::PrimitiveTopology},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    modifier::Craters,
+    noise::{get_noise_at_point_3d, Function, Method},
+    util::{export_model, smooth_normals},
+};
+
+/// Component for rock and asteroid configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Rock {
+    /// Seed of the noise
+    pub seed: u32,
+    /// Scale of the noise
+    pub scale: f64,
+    /// Offset of the noise
+    pub offset: [f64; 3],
+    /// Method used to generate noise
+    pub method: Method,
+    /// Function used to generate noise
+    pub function: Function,
+    /// Number of icosphere subdivisions, must be less than 80
+    pub subdivisions: usize,
+    /// Strength of the noise displacement, relative to the radius
+    pub displacement: f32,
+    /// Scale applied to the rock along each axis, used to stretch boulders and asteroids
+    pub stretch: [f32; 3],
+    /// Craters stamped onto the rock surface
+    pub craters: Option<Craters>,
+    /// Color of the rock
+    pub color: [u8; 4],
+    /// If true, renders rock mesh as wireframe
+    pub wireframe: bool,
+    /// If true, exports model in glb format
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for Rock {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: 60.0,
+            offset: [0.0; 3],
+            method: Method::Perlin,
+            function: Function::default(),
+            subdivisions: 12,
+            displacement: 0.3,
+            stretch: [1.0; 3],
+            craters: None,
+            color: [120, 110, 100, 255],
+            wireframe: false,
+            export: false,
+        }
+    }
+}
+
+/// Render `Rock` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct RockBundle {
+    /// Rock configuration
+    pub rock: Rock,
+    /// Generated mesh data is written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate rocks and asteroids
+pub struct RockPlugin;
+
+impl Plugin for RockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_rock);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_rock(
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<(&mut Rock, &mut Handle<Mesh>, &Handle<StandardMaterial>), Changed<Rock>>,
+) {
+    // Without the render assets there is nothing to generate
+    let (Some(mut materials), Some(mut meshes)) = (materials, meshes) else {
+        return;
+    };
+    for (mut rock, mut mesh_handle, material) in &mut query {
+        let [r, g, b, a] = rock.color;
+        if let Some(material) = materials.get_mut(material) {
+            *material = StandardMaterial {
+                base_color: Color::rgba_u8(r, g, b, a),
+                perceptual_roughness: 0.9,
+                ..default()
+            };
+        }
+
+        let Ok(sphere) = Mesh::try_from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: rock.subdivisions.min(79),
+        }) else {
+            continue;
+        };
+        let Some(VertexAttributeValues::Float32x3(sphere_positions)) =
+            sphere.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            continue;
+        };
+        let Some(sphere_indices) = sphere.indices() else {
+            continue;
+        };
+        let mut indices: Vec<u32> = sphere_indices.iter().map(|i| i as u32).collect();
+
+        let craters = rock.craters.as_ref().map_or_else(Vec::new, Craters::sample);
+        let stretch = Vec3::from(rock.stretch);
+        let positions: Vec<[f32; 3]> = sphere_positions
+            .iter()
+            .map(|position| {
+                let direction = Vec3::from(*position).normalize();
+                let point = [
+                    f64::from(direction.x),
+                    f64::from(direction.y),
+                    f64::from(direction.z),
+                ];
+                let mut height = get_noise_at_point_3d(
+                    point,
+                    rock.seed,
+                    rock.scale / 100.0,
+                    rock.offset,
+                    &rock.method,
+                    &rock.function,
+                ) as f32
+                    * rock.displacement;
+                if let Some(config) = &rock.craters {
+                    height += config.offset_at_direction(&craters, point) as f32 / 100.0;
+                }
+                (direction * (1.0 + height).max(0.05) * stretch).to_array()
+            })
+            .collect();
+        let normals = smooth_normals(&positions, &indices);
+        let uvs = vec![[0.0; 2]; positions.len()];
+
+        if rock.wireframe {
+            let triangle_number = indices.len() / 3;
+            let cloned_indices = indices.clone();
+            indices = vec![];
+            for i in 0..triangle_number {
+                for j in &[0, 1, 1, 2, 2, 0] {
+                    indices.push(cloned_indices[i * 3 + j]);
+                }
+            }
+        }
+
+        let mut mesh = if rock.wireframe {
+            Mesh::new(PrimitiveTopology::LineList)
+        } else {
+            Mesh::new(PrimitiveTopology::TriangleList)
+        };
+        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        *mesh_handle = meshes.add(mesh);
+
+        if rock.export {
+            let color = [
+                f32::from(r) / 255.0,
+                f32::from(g) / 255.0,
+                f32::from(b) / 255.0,
+                f32::from(a) / 255.0,
+            ];
+            export_model(&positions, indices, &vec![color; positions.len()]);
+            rock.export = false;
+        }
+    }
+}
@@ -0,0 +1,463 @@
+//! Scatter objects over terrain from data-driven rules
+//! # Example
+//! For configuration, see [`Scatter`](struct.Scatter.html) and [`ScatterRule`](struct.ScatterRule.html).
+//! Rules are loaded from `.scatter.ron` files and scattered again whenever a file changes
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::scatter::{Scatter, ScatterPlugin, Scattered};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, ScatterPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_scattered)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Scatter {
+//!             rules: asset_server.load("forest.scatter.ron"),
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//!
+//! fn print_scattered(query: Query<(&Scattered, &GlobalTransform), Added<Scattered>>) {
+//!     for (scattered, transform) in &query {
+//!         println!("{} at {:?}", scattered.kind, transform.translation());
+//!     }
+//! }
+//! ```
+//! A rules file lists what to spawn where
+//! ```ron
+//! (
+//!     rules: [
+//!         (
+//!             kind: "pine",
+//!             biomes: [borealForest],
+//!             biomeTransition: Some((width: 0.2, dither: 0.05)),
+//!             height: (0.1, 0.8),
+//!             slope: (0.0, 30.0),
+//!             density: 40.0,
+//!             clustering: 0.6,
+//!             minSpacing: 0.03,
+//!         ),
+//!     ],
+//! )
+//! ```
+use std::fmt;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::{Biome, BiomeMap, BiomeTransition},
+    deform::FlattenArea,
+    snap::aligned,
+    terrain::GeneratedTerrain,
+    util::Rng,
+};
+
+/// What to spawn where, a rule of `ScatterRules`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScatterRule {
+    /// Kind of the spawned objects, copied to `Scattered::kind`
+    pub kind: String,
+    /// Biomes the objects are spawned in, any biome if empty. Ignored if the terrain has no `BiomeMap`
+    pub biomes: Vec<Biome>,
+    /// If set, the density fades out across the borders of `biomes` instead of stopping at them
+    pub biome_transition: Option<BiomeTransition>,
+    /// Minimum and maximum height above sea level in world units
+    pub height: [f32; 2],
+    /// Minimum and maximum slope in degrees
+    pub slope: [f32; 2],
+    /// Number of candidates per square world unit, fewer objects are spawned where the constraints are not met
+    pub density: f32,
+    /// Fraction of the candidates placed around cluster centers instead of uniformly, 0 to 1
+    pub clustering: f32,
+    /// Radius of a cluster in world units
+    pub cluster_radius: f32,
+    /// Minimum distance between objects of this rule in world units
+    pub min_spacing: f32,
+    /// Minimum and maximum uniform scale of the objects
+    pub scale: [f32; 2],
+    /// If true, the objects are rotated to the surface normal
+    pub align_to_normal: bool,
+}
+
+impl Default for ScatterRule {
+    fn default() -> Self {
+        Self {
+            kind: String::new(),
+            biomes: vec![],
+            biome_transition: None,
+            height: [0.0, f32::MAX],
+            slope: [0.0, 90.0],
+            density: 10.0,
+            clustering: 0.0,
+            cluster_radius: 0.1,
+            min_spacing: 0.0,
+            scale: [1.0, 1.0],
+            align_to_normal: false,
+        }
+    }
+}
+
+/// Asset of scatter rules, loaded from `.scatter.ron` files
+#[derive(Asset, TypePath, Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScatterRules {
+    /// Rules executed in order
+    pub rules: Vec<ScatterRule>,
+}
+
+impl ScatterRules {
+    /// Placements of every rule as kind and transform relative to the terrain entity.
+    /// Biomes are only checked if `biomes` is given
+    #[must_use]
+    pub fn place(
+        &self,
+        seed: u32,
+        terrain_data: &GeneratedTerrain,
+        biomes: Option<&BiomeMap>,
+    ) -> Vec<(String, Transform)> {
+        let mut rng = Rng::new(u64::from(seed));
+        let [width, depth] = terrain_data.size.map(|side| side as f32);
+        let uniform = |rng: &mut Rng| {
+            Vec2::new(
+                (rng.next_f64() as f32 - 0.5) * width,
+                (rng.next_f64() as f32 - 0.5) * depth,
+            )
+        };
+        let mut placed = vec![];
+        for rule in &self.rules {
+            let candidates = (rule.density.max(0.0) * width * depth).round() as usize;
+            let clustering = rule.clustering.clamp(0.0, 1.0);
+            let centers: Vec<Vec2> = (0..((1.0 - clustering) * candidates as f32).ceil() as usize)
+                .map(|_| uniform(&mut rng))
+                .collect();
+            let [min_slope, max_slope] = rule.slope.map(f32::to_radians);
+            let biome_weights = biomes
+                .filter(|_| !rule.biomes.is_empty())
+                .zip(rule.biome_transition)
+                .map(|(biomes, transition)| biomes.weights(&rule.biomes, &transition));
+            let mut positions: Vec<Vec2> = vec![];
+            for _ in 0..candidates {
+                let point = match centers.get(rng.next_u64() as usize % centers.len().max(1)) {
+                    Some(center) if (rng.next_f64() as f32) < clustering => {
+                        let angle = rng.next_f64() as f32 * std::f32::consts::TAU;
+                        // Square root spreads the points evenly over the disk
+                        let distance = (rng.next_f64() as f32).sqrt() * rule.cluster_radius;
+                        *center + Vec2::from_angle(angle) * distance
+                    }
+                    _ => uniform(&mut rng),
+                };
+                // Drawn before the constraints are checked, so rejected candidates do not shift the ones after them
+                let (yaw, scale) = (rng.next_f64() as f32, rng.next_f64() as f32);
+                let Some(height) = terrain_data.height(point.x, point.y) else {
+                    continue;
+                };
+                let above_sea = height - terrain_data.sea_level;
+                if above_sea < rule.height[0]
+                    || above_sea > rule.height[1]
+                    || terrain_data.is_sea(point.x, point.y)
+                    || !terrain_data
+                        .slope(point.x, point.y)
+                        .is_some_and(|slope| (min_slope..=max_slope).contains(&slope))
+                {
+                    continue;
+                }
+                if let Some(weights) = &biome_weights {
+                    // Hashed from the position, so the candidates keep their random values
+                    let mut chance = Rng::new(
+                        (u64::from(point.x.to_bits()) << 32 | u64::from(point.y.to_bits()))
+                            ^ u64::from(seed),
+                    );
+                    if chance.next_f64() as f32 >= weights.weight_at(point.x, point.y) {
+                        continue;
+                    }
+                } else if !rule.biomes.is_empty() {
+                    if let Some(biomes) = biomes {
+                        let resolution = terrain_data.resolution.max(1) as f32;
+                        let vertex = (point + Vec2::new(width, depth) / 2.0) * resolution;
+                        let biome = biomes
+                            .cells
+                            .get(vertex.x.round() as usize)
+                            .and_then(|column| column.get(vertex.y.round() as usize));
+                        if !biome.is_some_and(|biome| rule.biomes.contains(biome)) {
+                            continue;
+                        }
+                    }
+                }
+                if positions
+                    .iter()
+                    .any(|other| other.distance(point) < rule.min_spacing)
+                {
+                    continue;
+                }
+                positions.push(point);
+                let mut rotation = Quat::from_rotation_y(yaw * std::f32::consts::TAU);
+                if rule.align_to_normal {
+                    if let Some(normal) = terrain_data.normal(point.x, point.y) {
+                        rotation = aligned(rotation, normal);
+                    }
+                }
+                let scale = (rule.scale[1] - rule.scale[0]).mul_add(scale, rule.scale[0]);
+                placed.push((
+                    rule.kind.clone(),
+                    Transform {
+                        translation: Vec3::new(point.x, height, point.y),
+                        rotation,
+                        scale: Vec3::splat(scale),
+                    },
+                ));
+            }
+        }
+        placed
+    }
+}
+
+/// Error loading `ScatterRules`
+#[derive(Debug)]
+pub enum ScatterRulesError {
+    /// The file could not be read
+    Io(std::io::Error),
+    /// The file is not valid RON
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for ScatterRulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Could not read scatter rules: {error}"),
+            Self::Ron(error) => write!(f, "Could not parse scatter rules: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ScatterRulesError {}
+
+/// Loader of `.scatter.ron` files
+#[derive(Default)]
+pub struct ScatterRulesLoader;
+
+impl AssetLoader for ScatterRulesLoader {
+    type Asset = ScatterRules;
+    type Settings = ();
+    type Error = ScatterRulesError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = vec![];
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(ScatterRulesError::Io)?;
+            ron::de::from_bytes(&bytes).map_err(ScatterRulesError::Ron)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scatter.ron"]
+    }
+}
+
+/// Component for scatter configuration, added to an entity with a `Terrain` component.
+/// Biomes are checked if the entity also has a `BiomeMap`
+#[derive(Component, Clone, Default, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Scatter {
+    /// Seed used to place the objects
+    pub seed: u32,
+    /// Rules to execute
+    #[serde(skip)]
+    pub rules: Handle<ScatterRules>,
+}
+
+/// Component for changes to the scattered objects, e.g. trees cut down or planted by the player.
+/// Applied whenever the objects are scattered again
+#[derive(Component, Clone, Default, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct ScatterEdits {
+    /// `x`, `z` relative to the terrain of removed objects
+    pub removed: Vec<Vec2>,
+    /// Objects added to the scattered ones
+    pub added: Vec<ScatterAddition>,
+    /// Areas as `x`, `z` relative to the terrain without scattered objects, e.g. under a building
+    pub cleared: Vec<FlattenArea>,
+}
+
+impl ScatterEdits {
+    /// Distance in world units within which a placement matches a removed position
+    const TOLERANCE: f32 = 1e-3;
+
+    /// Removes the object placed at `x`, `z` relative to the terrain
+    pub fn remove(&mut self, x: f32, z: f32) {
+        let point = Vec2::new(x, z);
+        let added = self.added.len();
+        self.added
+            .retain(|addition| addition.translation.xz().distance(point) > Self::TOLERANCE);
+        if self.added.len() == added {
+            self.removed.push(point);
+        }
+    }
+
+    /// Removes the scattered and added objects inside `area` relative to the terrain.
+    /// Objects added afterwards are kept
+    pub fn clear(&mut self, area: FlattenArea) {
+        self.added
+            .retain(|addition| area.distance(addition.translation.xz()) > 0.0);
+        self.cleared.push(area);
+    }
+
+    /// Adds an object of `kind` at `transform` relative to the terrain
+    pub fn add(&mut self, kind: impl Into<String>, transform: Transform) {
+        self.added.push(ScatterAddition {
+            kind: kind.into(),
+            translation: transform.translation,
+            rotation: transform.rotation,
+            scale: transform.scale,
+        });
+    }
+
+    /// `placements` without the removed and cleared objects and with the added ones
+    fn apply(&self, placements: Vec<(String, Transform)>) -> Vec<(String, Transform)> {
+        placements
+            .into_iter()
+            .filter(|(_, transform)| {
+                let point = transform.translation.xz();
+                !self
+                    .removed
+                    .iter()
+                    .any(|removed| point.distance(*removed) <= Self::TOLERANCE)
+                    && !self.cleared.iter().any(|area| area.distance(point) <= 0.0)
+            })
+            .chain(self.added.iter().map(|addition| {
+                (
+                    addition.kind.clone(),
+                    Transform {
+                        translation: addition.translation,
+                        rotation: addition.rotation,
+                        scale: addition.scale,
+                    },
+                )
+            }))
+            .collect()
+    }
+}
+
+/// Object added by `ScatterEdits`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ScatterAddition {
+    /// Kind of the object, see `ScatterRule::kind`
+    pub kind: String,
+    /// Position relative to the terrain
+    pub translation: Vec3,
+    /// Rotation relative to the terrain
+    pub rotation: Quat,
+    /// Scale of the object
+    pub scale: Vec3,
+}
+
+impl Default for ScatterAddition {
+    fn default() -> Self {
+        Self {
+            kind: String::new(),
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+/// Marker for a scattered object, spawned as a child of the terrain entity
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct Scattered {
+    /// Kind of the object, see `ScatterRule::kind`
+    pub kind: String,
+}
+
+/// Plugin to scatter objects
+pub struct ScatterPlugin;
+
+impl Plugin for ScatterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ScatterRules>()
+            .init_asset_loader::<ScatterRulesLoader>()
+            .add_systems(Update, scatter);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn scatter(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<ScatterRules>>,
+    rules: Res<Assets<ScatterRules>>,
+    query: Query<(
+        Entity,
+        Ref<Scatter>,
+        Ref<GeneratedTerrain>,
+        Option<Ref<BiomeMap>>,
+        Option<Ref<ScatterEdits>>,
+    )>,
+    scattered: Query<(Entity, &Parent), With<Scattered>>,
+) {
+    let mut changed_rules = vec![];
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event {
+            changed_rules.push(*id);
+        }
+    }
+    for (entity, config, terrain_data, biomes, edits) in &query {
+        if !(config.is_changed()
+            || terrain_data.is_changed()
+            || biomes.as_ref().is_some_and(DetectChanges::is_changed)
+            || edits.as_ref().is_some_and(DetectChanges::is_changed)
+            || changed_rules.contains(&config.rules.id()))
+        {
+            continue;
+        }
+        let Some(scatter_rules) = rules.get(&config.rules) else {
+            continue;
+        };
+        for (child, parent) in &scattered {
+            if parent.get() == entity {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+        let mut placements = scatter_rules.place(config.seed, &terrain_data, biomes.as_deref());
+        if let Some(edits) = edits {
+            placements = edits.apply(placements);
+        }
+        commands.entity(entity).with_children(|children| {
+            for (kind, transform) in placements {
+                children.spawn((Scattered { kind }, SpatialBundle::from_transform(transform)));
+            }
+        });
+    }
+}
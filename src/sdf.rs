@@ -0,0 +1,226 @@
+//! Compose signed distance fields from primitives
+//! # Example
+//! For details, see [`Sdf`](enum.Sdf.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::sdf::Sdf;
+//! use bevy_generative::volume::mesh_from_density;
+//!
+//! // An arch: a box with a smoothly blended capsule cut out of it
+//! let arch = Sdf::cuboid([0.0; 3], [0.6, 0.4, 0.2])
+//!     .subtract(Sdf::capsule([0.0, -0.4, -0.5], [0.0, -0.4, 0.5], 0.35), 0.05);
+//! assert!(arch.distance(Vec3::new(0.5, -0.3, 0.0)) < 0.0);
+//! assert!(arch.distance(Vec3::new(0.0, -0.3, 0.0)) > 0.0);
+//!
+//! let mesh = mesh_from_density(&arch.density_field([32; 3], 0.05));
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    noise::{get_noise_at_point_3d, Function, Method},
+    volume::DensityField,
+};
+
+/// Signed distance field, negative inside of the shape
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Sdf {
+    /// Sphere around `center`
+    Sphere {
+        /// Center of the sphere
+        center: [f32; 3],
+        /// Radius of the sphere
+        radius: f32,
+    },
+    /// Axis aligned box around `center`
+    Cuboid {
+        /// Center of the box
+        center: [f32; 3],
+        /// Half of the size of the box along each axis
+        half_extents: [f32; 3],
+    },
+    /// Segment from `from` to `to` with rounded ends
+    Capsule {
+        /// Center of the first end
+        from: [f32; 3],
+        /// Center of the second end
+        to: [f32; 3],
+        /// Radius of the capsule
+        radius: f32,
+    },
+    /// Space covered by any of the shapes
+    Union {
+        /// Shapes to combine
+        shapes: Vec<Self>,
+        /// Distance over which the shapes blend into each other, 0 for a sharp seam
+        smoothness: f32,
+    },
+    /// Space covered by all of the shapes
+    Intersect {
+        /// Shapes to intersect
+        shapes: Vec<Self>,
+        /// Distance over which the shapes blend into each other, 0 for a sharp seam
+        smoothness: f32,
+    },
+    /// Space covered by `base` but not by `cut`
+    Subtract {
+        /// Shape to cut from
+        base: Box<Self>,
+        /// Shape removed from `base`
+        cut: Box<Self>,
+        /// Distance over which the cut blends into the base, 0 for a sharp edge
+        smoothness: f32,
+    },
+    /// Surface of `shape` moved outwards by 3D noise
+    Displace {
+        /// Shape to displace
+        shape: Box<Self>,
+        /// Maximum displacement in world units
+        amplitude: f32,
+        /// Seed of the noise
+        seed: u32,
+        /// Scale of the noise
+        scale: f64,
+        /// Method used to generate noise
+        method: Method,
+        /// Function used to generate noise
+        function: Function,
+    },
+}
+
+impl Sdf {
+    /// Sphere around `center`
+    #[must_use]
+    pub const fn sphere(center: [f32; 3], radius: f32) -> Self {
+        Self::Sphere { center, radius }
+    }
+
+    /// Axis aligned box around `center`
+    #[must_use]
+    pub const fn cuboid(center: [f32; 3], half_extents: [f32; 3]) -> Self {
+        Self::Cuboid {
+            center,
+            half_extents,
+        }
+    }
+
+    /// Segment from `from` to `to` with rounded ends
+    #[must_use]
+    pub const fn capsule(from: [f32; 3], to: [f32; 3], radius: f32) -> Self {
+        Self::Capsule { from, to, radius }
+    }
+
+    /// Union with `other`, blended over `smoothness`
+    #[must_use]
+    pub fn union(self, other: Self, smoothness: f32) -> Self {
+        Self::Union {
+            shapes: vec![self, other],
+            smoothness,
+        }
+    }
+
+    /// Intersection with `other`, blended over `smoothness`
+    #[must_use]
+    pub fn intersect(self, other: Self, smoothness: f32) -> Self {
+        Self::Intersect {
+            shapes: vec![self, other],
+            smoothness,
+        }
+    }
+
+    /// Removes `cut` from the shape, blended over `smoothness`
+    #[must_use]
+    pub fn subtract(self, cut: Self, smoothness: f32) -> Self {
+        Self::Subtract {
+            base: Box::new(self),
+            cut: Box::new(cut),
+            smoothness,
+        }
+    }
+
+    /// Displaces the surface by Perlin fBm noise of up to `amplitude`
+    #[must_use]
+    pub fn displace(self, amplitude: f32, seed: u32, scale: f64) -> Self {
+        Self::Displace {
+            shape: Box::new(self),
+            amplitude,
+            seed,
+            scale,
+            method: Method::Perlin,
+            function: Function::default(),
+        }
+    }
+
+    /// Signed distance from `point` to the surface, negative inside of the shape.
+    /// Blends and displacement only keep the sign exact, the distance is approximate
+    #[must_use]
+    pub fn distance(&self, point: Vec3) -> f32 {
+        match self {
+            Self::Sphere { center, radius } => point.distance(Vec3::from(*center)) - radius,
+            Self::Cuboid {
+                center,
+                half_extents,
+            } => {
+                let offset = (point - Vec3::from(*center)).abs() - Vec3::from(*half_extents);
+                offset.max(Vec3::ZERO).length() + offset.max_element().min(0.0)
+            }
+            Self::Capsule { from, to, radius } => {
+                let [from, to] = [Vec3::from(*from), Vec3::from(*to)];
+                let segment = to - from;
+                let t = ((point - from).dot(segment) / segment.length_squared().max(f32::EPSILON))
+                    .clamp(0.0, 1.0);
+                point.distance(from + segment * t) - radius
+            }
+            Self::Union { shapes, smoothness } => shapes
+                .iter()
+                .map(|shape| shape.distance(point))
+                .reduce(|a, b| smooth_min(a, b, *smoothness))
+                .unwrap_or(f32::MAX),
+            Self::Intersect { shapes, smoothness } => shapes
+                .iter()
+                .map(|shape| shape.distance(point))
+                .reduce(|a, b| -smooth_min(-a, -b, *smoothness))
+                .unwrap_or(f32::MAX),
+            Self::Subtract {
+                base,
+                cut,
+                smoothness,
+            } => -smooth_min(-base.distance(point), cut.distance(point), *smoothness),
+            Self::Displace {
+                shape,
+                amplitude,
+                seed,
+                scale,
+                method,
+                function,
+            } => {
+                let noise = get_noise_at_point_3d(
+                    point.as_dvec3().to_array(),
+                    *seed,
+                    *scale,
+                    [0.0; 3],
+                    method,
+                    function,
+                ) as f32;
+                shape.distance(point) - noise * amplitude
+            }
+        }
+    }
+
+    /// Samples the shape into a density field centered on the origin for the volume meshers.
+    /// The density is the negated distance, so the inside of the shape is solid
+    #[must_use]
+    pub fn density_field(&self, samples: [usize; 3], spacing: f32) -> DensityField {
+        DensityField::from_fn(samples, spacing, |position| -self.distance(position))
+    }
+}
+
+/// Polynomial smooth minimum, equal to `a.min(b)` when the values are further than `smoothness` apart
+fn smooth_min(a: f32, b: f32, smoothness: f32) -> f32 {
+    if smoothness <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / smoothness).clamp(0.0, 1.0);
+    (smoothness * h).mul_add(-(1.0 - h), (a - b).mul_add(h, b))
+}
@@ -0,0 +1,658 @@
+//! Generate terrain chunks on demand for dedicated servers
+//! # Example
+//! For configuration, see [`ChunkServer`](struct.ChunkServer.html).
+//! Chunks sent in a `ChunkRequest` are generated on the async compute pool, those nearest to a player first,
+//! and sent back as serializable [`ChunkPayload`](struct.ChunkPayload.html)s in a `ChunkReady` to be forwarded
+//! to the clients. No entities or meshes are spawned, so the service runs with `MinimalPlugins`.
+//! Payloads are encoded compactly with [`ChunkPayload::encode`](struct.ChunkPayload.html#method.encode),
+//! quantizing the heights to a precision in world units
+//! ```no_run
+//! use bevy::prelude::*;
+//! use bevy_generative::server::{ChunkReady, ChunkRequest, ChunkServer, ChunkServerPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(ChunkServerPlugin)
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, (request, send))
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(ChunkServer::default());
+//! }
+//!
+//! fn request(
+//!     mut servers: Query<(Entity, &mut ChunkServer)>,
+//!     mut requests: EventWriter<ChunkRequest>,
+//!     mut requested: Local<bool>,
+//! ) {
+//!     let Ok((server, mut config)) = servers.get_single_mut() else {
+//!         return;
+//!     };
+//!     // Positions of the players as received from the game
+//!     config.players = vec![Vec2::new(3.0, -1.0)];
+//!     if !*requested {
+//!         for x in -2..=2 {
+//!             for z in -2..=2 {
+//!                 requests.send(ChunkRequest {
+//!                     server,
+//!                     coord: IVec2::new(x, z),
+//!                 });
+//!             }
+//!         }
+//!         *requested = true;
+//!     }
+//! }
+//!
+//! fn send(mut events: EventReader<ChunkReady>) {
+//!     for event in events.read() {
+//!         let bytes = event.payload.encode(Some(0.001)).unwrap();
+//!         println!("Chunk {} ready, {} bytes", event.payload.coord, bytes.len());
+//!     }
+//! }
+//! ```
+use std::{
+    io::{Read, Write},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex, PoisonError,
+    },
+};
+
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    noise::generate_noise_map_at,
+    persist::ChunkError,
+    terrain::Terrain,
+    util::chunk,
+    verify::{ChunkHash, WorldFingerprint},
+};
+
+/// Bytes every encoded chunk payload starts with
+const MAGIC: &[u8; 4] = b"BGCP";
+
+/// Version of the chunk payload encoding, written after the magic bytes
+pub const PAYLOAD_VERSION: u32 = 1;
+
+/// Bytes of the compressed fields before the heights: coordinates, size, resolution, world size,
+/// sea level, hash, columns and rows
+const HEADER_LEN: usize = 48;
+
+/// Most bytes a varint of a 64 bit value takes
+const MAX_VARINT_LEN: usize = 10;
+
+/// Component for a chunk generation service
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct ChunkServer {
+    /// Configuration of every chunk, the world offset is moved to every chunk so neighbours line up.
+    /// Chunks are centered on their position, the anchor is ignored.
+    /// Changing the heights it generates drops the queued chunks and those being generated, request them again
+    pub chunk: Terrain,
+    /// Chunks generated at the same time
+    pub max_tasks: u32,
+    /// Positions of the players as `x`, `z` relative to the server entity, updated by the game.
+    /// Requested chunks nearest to a player are generated first, without players in request order
+    #[serde(skip)]
+    pub players: Vec<Vec2>,
+}
+
+impl Default for ChunkServer {
+    fn default() -> Self {
+        Self {
+            chunk: Terrain::default(),
+            max_tasks: 4,
+            players: vec![],
+        }
+    }
+}
+
+impl ChunkServer {
+    /// Size of a chunk in world units along `x` and `z`
+    #[must_use]
+    pub fn chunk_size(&self) -> Vec2 {
+        chunk::chunk_size(&self.chunk)
+    }
+
+    /// Coordinates of the chunk containing `position`, relative to the server entity
+    #[must_use]
+    pub fn chunk_coord(&self, position: Vec2) -> IVec2 {
+        chunk::chunk_coord(&self.chunk, position)
+    }
+
+    /// Configuration of the chunk at `coord`, sampling the noise next to its neighbours
+    #[must_use]
+    pub fn chunk_terrain(&self, coord: IVec2) -> Terrain {
+        chunk::chunk_terrain(&self.chunk, coord)
+    }
+
+    /// Squared distance from the chunk at `coord` to the nearest player
+    fn priority(&self, coord: IVec2) -> f32 {
+        let center = coord.as_vec2() * self.chunk_size();
+        self.players
+            .iter()
+            .map(|player| player.distance_squared(center))
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+/// Heightfield of a generated chunk, serializable to be sent to clients
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChunkPayload {
+    /// Coordinates of the chunk
+    pub coord: IVec2,
+    /// Size of the chunk, like `Terrain::size`
+    pub size: [u32; 2],
+    /// Vertices per unit of the chunk, like `Terrain::resolution`
+    pub resolution: u32,
+    /// Size of the chunk in world units along `x` and `z`
+    pub world_size: Vec2,
+    /// Sea level in world units
+    pub sea_level: f32,
+    /// Height of every vertex in world units, indexed like `GeneratedTerrain::heights`
+    pub heights: Vec<Vec<f32>>,
+    /// Hash of the heightfield, a client generating the chunk itself gets the same `ChunkHash`
+    pub hash: ChunkHash,
+}
+
+impl ChunkPayload {
+    /// Generates the heightfield of `terrain` as chunk `coord`, the same heights as `GeneratedTerrain::heights`
+    /// of a `Terrain` without stamps, roads, deformations or snow
+    #[must_use]
+    pub fn generate(terrain: &Terrain, coord: IVec2) -> Self {
+        let noise_values = generate_noise_map_at(
+            &terrain.instance_noise(),
+            [
+                terrain.size[0] * terrain.resolution,
+                terrain.size[1] * terrain.resolution,
+            ],
+            terrain.noise_offset(),
+        );
+        let mut heights = terrain.heights(&noise_values);
+        let sea_level = terrain.generated_sea_level();
+        let lift = terrain.lift(&heights, sea_level);
+        if lift != 0.0 {
+            for height in heights.iter_mut().flatten() {
+                *height += lift;
+            }
+        }
+        let sea_level = sea_level + lift;
+        Self {
+            coord,
+            size: terrain.size,
+            resolution: terrain.resolution,
+            world_size: chunk::chunk_size(terrain),
+            sea_level,
+            hash: ChunkHash::of(terrain.resolution, terrain.size, sea_level, &heights),
+            heights,
+        }
+    }
+
+    /// Encodes the payload as magic bytes, format version and compressed binary heightfield.
+    /// Heights are rounded to multiples of `precision` in world units above the lowest height and stored
+    /// as differences to the previous vertex, `None` keeps them exact. `hash` stays the hash of the exact heights
+    ///
+    /// # Errors
+    /// If the columns of the heights differ in length or the payload cannot be compressed
+    pub fn encode(&self, precision: Option<f32>) -> Result<Vec<u8>, ChunkError> {
+        let columns = self.heights.len();
+        let rows = self.heights.first().map_or(0, Vec::len);
+        if self.heights.iter().any(|column| column.len() != rows) {
+            return Err(ChunkError::Format);
+        }
+        let mut body = vec![];
+        for value in [self.coord.x, self.coord.y] {
+            body.extend(value.to_le_bytes());
+        }
+        for value in [self.size[0], self.size[1], self.resolution] {
+            body.extend(value.to_le_bytes());
+        }
+        for value in [self.world_size.x, self.world_size.y, self.sea_level] {
+            body.extend(value.to_le_bytes());
+        }
+        body.extend(self.hash.content.to_le_bytes());
+        for value in [columns as u32, rows as u32] {
+            body.extend(value.to_le_bytes());
+        }
+        let heights = self.heights.iter().flatten().copied();
+        match precision.filter(|&precision| precision > 0.0) {
+            None => {
+                body.push(0);
+                // Neighbouring heights share their sign, exponent and leading mantissa bits
+                let mut previous = 0;
+                for height in heights {
+                    write_varint(&mut body, u64::from(height.to_bits() ^ previous));
+                    previous = height.to_bits();
+                }
+            }
+            Some(precision) => {
+                body.push(1);
+                let min = heights.clone().fold(f32::INFINITY, f32::min);
+                let min = if min.is_finite() { min } else { 0.0 };
+                body.extend(min.to_le_bytes());
+                body.extend(precision.to_le_bytes());
+                let mut previous = 0_i64;
+                for height in heights {
+                    let step = ((height - min) / precision).round() as i64;
+                    write_varint(&mut body, zigzag(step - previous));
+                    previous = step;
+                }
+            }
+        }
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(PAYLOAD_VERSION.to_le_bytes());
+        let mut encoder = ZlibEncoder::new(bytes, Compression::best());
+        encoder.write_all(&body).map_err(ChunkError::Io)?;
+        encoder.finish().map_err(ChunkError::Io)
+    }
+
+    /// Decodes a payload encoded by `encode`
+    ///
+    /// # Errors
+    /// If `bytes` are not an encoded payload or were written by a newer format version
+    pub fn decode(bytes: &[u8]) -> Result<Self, ChunkError> {
+        if bytes.len() < 8 || &bytes[..4] != MAGIC {
+            return Err(ChunkError::Format);
+        }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version > PAYLOAD_VERSION {
+            return Err(ChunkError::Version(version));
+        }
+        let mut decoder = ZlibDecoder::new(&bytes[8..]);
+        let mut header = [0; HEADER_LEN];
+        decoder.read_exact(&mut header).map_err(ChunkError::Io)?;
+        let mut reader = Reader(&header);
+        let coord = IVec2::new(reader.i32()?, reader.i32()?);
+        let size = [reader.u32()?, reader.u32()?];
+        let resolution = reader.u32()?;
+        let world_size = Vec2::new(reader.f32()?, reader.f32()?);
+        let sea_level = reader.f32()?;
+        let hash = ChunkHash {
+            content: u64::from_le_bytes(reader.bytes()?),
+        };
+        let [columns, rows] = [reader.u32()? as usize, reader.u32()? as usize];
+        // The format byte, the minimum and precision of quantized heights and a varint per height,
+        // so a corrupt stream does not inflate beyond the heightfield of its header
+        let limit = columns
+            .checked_mul(rows)
+            .and_then(|heights| heights.checked_mul(MAX_VARINT_LEN))
+            .and_then(|heights| heights.checked_add(9))
+            .ok_or(ChunkError::Format)?;
+        let mut body = vec![];
+        decoder
+            .take(limit as u64)
+            .read_to_end(&mut body)
+            .map_err(ChunkError::Io)?;
+        let mut reader = Reader(&body);
+        // Every height takes at least a byte, so corrupt sizes do not allocate
+        if columns.saturating_mul(rows) > reader.0.len() {
+            return Err(ChunkError::Format);
+        }
+        let mut heights = vec![Vec::with_capacity(rows); columns];
+        match reader.bytes::<1>()? {
+            [0] => {
+                let mut previous = 0;
+                for column in &mut heights {
+                    for _ in 0..rows {
+                        let bits = u32::try_from(reader.varint()?)
+                            .map_err(|_| ChunkError::Format)?
+                            ^ previous;
+                        column.push(f32::from_bits(bits));
+                        previous = bits;
+                    }
+                }
+            }
+            [1] => {
+                let min = reader.f32()?;
+                let precision = reader.f32()?;
+                let mut step = 0_i64;
+                for column in &mut heights {
+                    for _ in 0..rows {
+                        step = step
+                            .checked_add(unzigzag(reader.varint()?))
+                            .ok_or(ChunkError::Format)?;
+                        column.push(
+                            (step as f64).mul_add(f64::from(precision), f64::from(min)) as f32
+                        );
+                    }
+                }
+            }
+            _ => return Err(ChunkError::Format),
+        }
+        Ok(Self {
+            coord,
+            size,
+            resolution,
+            world_size,
+            sea_level,
+            heights,
+            hash,
+        })
+    }
+}
+
+/// Appends `value` as LEB128, 7 bits per byte with the high bit set on every byte but the last
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Maps signed differences to unsigned ones, small differences of either sign get small values
+const fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Inverse of `zigzag`
+const fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Reads the fields of an encoded payload in order
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], ChunkError> {
+        let (bytes, rest) = self.0.split_at_checked(N).ok_or(ChunkError::Format)?;
+        self.0 = rest;
+        Ok(bytes.try_into().unwrap_or([0; N]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ChunkError> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, ChunkError> {
+        self.bytes().map(i32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32, ChunkError> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, ChunkError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let [byte] = self.bytes()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ChunkError::Format)
+    }
+}
+
+/// Event requesting a chunk from a `ChunkServer`, ignored while the chunk is queued or generated
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChunkRequest {
+    /// Entity of the `ChunkServer`
+    pub server: Entity,
+    /// Coordinates of the chunk
+    pub coord: IVec2,
+}
+
+/// Event sent when a requested chunk is generated
+#[derive(Event, Clone, Debug)]
+pub struct ChunkReady {
+    /// Entity of the `ChunkServer`
+    pub server: Entity,
+    /// Generated chunk
+    pub payload: ChunkPayload,
+}
+
+/// Queued and running chunks of a `ChunkServer`, running tasks send their payload through the channel
+/// tagged with the generation of the configuration they were started with
+#[derive(Component)]
+struct ChunkServerState {
+    queued: Vec<IVec2>,
+    running: Vec<IVec2>,
+    fingerprint: Option<WorldFingerprint>,
+    generation: u64,
+    sender: Sender<(u64, ChunkPayload)>,
+    receiver: Mutex<Receiver<(u64, ChunkPayload)>>,
+}
+
+impl Default for ChunkServerState {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            queued: vec![],
+            running: vec![],
+            fingerprint: None,
+            generation: 0,
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+/// Plugin to generate chunks on demand
+pub struct ChunkServerPlugin;
+
+impl Plugin for ChunkServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ChunkRequest>()
+            .add_event::<ChunkReady>()
+            .add_systems(Update, serve_chunks);
+    }
+}
+
+fn serve_chunks(
+    mut commands: Commands,
+    mut requests: EventReader<ChunkRequest>,
+    mut ready: EventWriter<ChunkReady>,
+    mut servers: Query<(Entity, Ref<ChunkServer>, Option<&mut ChunkServerState>)>,
+) {
+    let requests: Vec<ChunkRequest> = requests.read().copied().collect();
+    for (entity, server, state) in &mut servers {
+        let mut inserted = ChunkServerState::default();
+        let is_new = state.is_none();
+        let state = state.map_or(&mut inserted, Mut::into_inner);
+        // The players change every frame, only a new fingerprint makes queued and running chunks stale
+        if server.is_changed() {
+            let fingerprint = WorldFingerprint::new(&server.chunk);
+            if state.fingerprint.is_some_and(|old| old != fingerprint) {
+                state.queued.clear();
+                state.running.clear();
+                state.generation += 1;
+            }
+            state.fingerprint = Some(fingerprint);
+        }
+        for request in requests.iter().filter(|request| request.server == entity) {
+            if !state.queued.contains(&request.coord) && !state.running.contains(&request.coord) {
+                state.queued.push(request.coord);
+            }
+        }
+
+        let receiver = state
+            .receiver
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        while let Ok((generation, payload)) = receiver.try_recv() {
+            if generation != state.generation {
+                continue;
+            }
+            state.running.retain(|&coord| coord != payload.coord);
+            ready.send(ChunkReady {
+                server: entity,
+                payload,
+            });
+        }
+
+        // Stable, so chunks at the same distance keep the request order
+        state
+            .queued
+            .sort_by(|a, b| server.priority(*a).total_cmp(&server.priority(*b)));
+        let free = (server.max_tasks.max(1) as usize).saturating_sub(state.running.len());
+        let pool = AsyncComputeTaskPool::get();
+        for coord in state.queued.drain(..free.min(state.queued.len())) {
+            let terrain = server.chunk_terrain(coord);
+            let sender = state.sender.clone();
+            let generation = state.generation;
+            // Without threads the task runs right away and the payload is received next frame
+            pool.spawn(async move {
+                let _ = sender.send((generation, ChunkPayload::generate(&terrain, coord)));
+            })
+            .detach();
+            state.running.push(coord);
+        }
+        if is_new {
+            commands.entity(entity).insert(inserted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::ManualEventReader;
+
+    use super::*;
+
+    fn payload() -> ChunkPayload {
+        ChunkPayload::generate(&Terrain::default(), IVec2::new(2, -3))
+    }
+
+    /// Magic bytes, current version and the compressed `body`
+    fn compressed(body: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(PAYLOAD_VERSION.to_le_bytes());
+        let mut encoder = ZlibEncoder::new(bytes, Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Updates `app` until a chunk is ready, at most `max_updates` times
+    fn ready(
+        app: &mut App,
+        reader: &mut ManualEventReader<ChunkReady>,
+        max_updates: u32,
+    ) -> Vec<ChunkPayload> {
+        let mut payloads = vec![];
+        for _ in 0..max_updates {
+            app.update();
+            let events = app.world.resource::<Events<ChunkReady>>();
+            payloads.extend(reader.read(events).map(|event| event.payload.clone()));
+            if !payloads.is_empty() {
+                break;
+            }
+            std::thread::yield_now();
+        }
+        payloads
+    }
+
+    #[test]
+    fn payloads_of_changed_servers_are_dropped() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, ChunkServerPlugin));
+        let server = app
+            .world
+            .spawn(ChunkServer {
+                chunk: Terrain {
+                    size: [2, 2],
+                    ..default()
+                },
+                max_tasks: 1,
+                ..default()
+            })
+            .id();
+        let request = |app: &mut App, coord| {
+            app.world.send_event(ChunkRequest { server, coord });
+        };
+        request(&mut app, IVec2::ZERO);
+        request(&mut app, IVec2::ONE);
+        app.update();
+        app.world
+            .get_mut::<ChunkServer>(server)
+            .unwrap()
+            .chunk
+            .noise
+            .seed += 1;
+        app.update();
+        // A payload of the previous configuration arriving late, whenever its task finishes
+        let state = app.world.get::<ChunkServerState>(server).unwrap();
+        assert_eq!(state.generation, 1);
+        assert!(state.queued.is_empty() && state.running.is_empty());
+        state.sender.send((0, payload())).unwrap();
+        let mut reader = ManualEventReader::<ChunkReady>::default();
+        assert!(ready(&mut app, &mut reader, 1).is_empty());
+
+        // Payloads of the previous configuration are never sent, so the first payload is the requested one
+        request(&mut app, IVec2::ONE);
+        let payloads = ready(&mut app, &mut reader, 100_000);
+        let chunk = app
+            .world
+            .get::<ChunkServer>(server)
+            .unwrap()
+            .chunk_terrain(IVec2::ONE);
+        assert_eq!(payloads, vec![ChunkPayload::generate(&chunk, IVec2::ONE)]);
+    }
+
+    #[test]
+    fn lossless_payload_round_trips() {
+        let payload = payload();
+        let decoded = ChunkPayload::decode(&payload.encode(None).unwrap()).unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[test]
+    fn quantized_payload_round_trips_within_precision() {
+        let payload = payload();
+        let precision = 0.001;
+        let decoded = ChunkPayload::decode(&payload.encode(Some(precision)).unwrap()).unwrap();
+        assert_eq!(decoded.heights.len(), payload.heights.len());
+        for (decoded, exact) in decoded.heights.iter().zip(&payload.heights) {
+            assert_eq!(decoded.len(), exact.len());
+            for (decoded, exact) in decoded.iter().zip(exact) {
+                assert!(
+                    (decoded - exact).abs() <= precision * 0.51,
+                    "{decoded} {exact}"
+                );
+            }
+        }
+        assert_eq!(
+            ChunkPayload {
+                heights: payload.heights.clone(),
+                ..decoded
+            },
+            payload
+        );
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut bytes = payload().encode(None).unwrap();
+        bytes[4..8].copy_from_slice(&(PAYLOAD_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            ChunkPayload::decode(&bytes),
+            Err(ChunkError::Version(version)) if version == PAYLOAD_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn overflowing_quantized_steps_are_rejected() {
+        let mut body = vec![0; HEADER_LEN - 8];
+        for value in [1_u32, 2] {
+            body.extend(value.to_le_bytes());
+        }
+        body.push(1);
+        body.extend(0_f32.to_le_bytes());
+        body.extend(1_f32.to_le_bytes());
+        for _ in 0..2 {
+            write_varint(&mut body, zigzag(i64::MAX));
+        }
+        assert!(matches!(
+            ChunkPayload::decode(&compressed(&body)),
+            Err(ChunkError::Format)
+        ));
+    }
+}
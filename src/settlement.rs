@@ -0,0 +1,299 @@
+//! Generate settlement layouts on terrain
+//! # Example
+//! For configuration, see [`Settlement`](struct.Settlement.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::settlement::{Settlement, SettlementPlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, SettlementPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Settlement::default()));
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{suitability::SuitabilityMap, terrain::GeneratedTerrain, util::Rng};
+
+/// Component for settlement configuration, added to an entity with a `Terrain` component.
+/// Sites are chosen by their `SuitabilityMap` score if the entity has one, otherwise by their flatness
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Settlement {
+    /// Seed used to pick sites and lay out streets
+    pub seed: u32,
+    /// Maximum number of settlements
+    pub count: usize,
+    /// Radius of a settlement in world units
+    pub radius: f32,
+    /// Minimum distance between settlement centers in world units
+    pub min_spacing: f32,
+    /// Maximum slope of sites, streets and plots in degrees
+    pub max_slope: f32,
+    /// Minimum height above sea level of sites, streets and plots in world units
+    pub min_height: f32,
+    /// Number of streets leaving the center of a settlement
+    pub streets: u32,
+    /// Length of a street segment in world units
+    pub street_segment: f32,
+    /// Width of the streets in world units
+    pub street_width: f32,
+    /// Width and depth of a building plot in world units
+    pub plot_size: [f32; 2],
+    /// Distance between plots along a street in world units
+    pub plot_spacing: f32,
+}
+
+impl Default for Settlement {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            count: 3,
+            radius: 0.3,
+            min_spacing: 0.8,
+            max_slope: 15.0,
+            min_height: 0.05,
+            streets: 4,
+            street_segment: 0.05,
+            street_width: 0.02,
+            plot_size: [0.03, 0.04],
+            plot_spacing: 0.045,
+        }
+    }
+}
+
+/// Building plot along a street
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lot {
+    /// Placement of a building, relative to the terrain entity. The building faces the street along `-Z`
+    pub transform: Transform,
+    /// Width and depth of the plot in world units
+    pub size: [f32; 2],
+    /// Index of the street edge in `Site::edges` the plot faces
+    pub street: usize,
+    /// Distance from the center of the settlement in world units
+    pub distance: f32,
+}
+
+/// Settlement placed on the terrain
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Site {
+    /// Center of the settlement, relative to the terrain entity
+    pub center: Vec3,
+    /// Nodes of the street graph, the first node is the center
+    pub nodes: Vec<Vec3>,
+    /// Streets connecting two nodes
+    pub edges: Vec<[usize; 2]>,
+    /// Building plots along the streets
+    pub lots: Vec<Lot>,
+}
+
+/// Generated settlements, inserted on the entity with the `Settlement` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct SettlementLayout {
+    /// Settlements ordered from the most to the least suitable site
+    pub sites: Vec<Site>,
+}
+
+/// Plugin to generate settlement layouts
+pub struct SettlementPlugin;
+
+impl Plugin for SettlementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_settlements);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_settlements(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &Settlement,
+            &GeneratedTerrain,
+            Option<&SuitabilityMap>,
+            Option<&SettlementLayout>,
+        ),
+        Or<(
+            Changed<Settlement>,
+            Changed<GeneratedTerrain>,
+            Changed<SuitabilityMap>,
+        )>,
+    >,
+) {
+    for (entity, settlement, terrain_data, suitability, layout) in &query {
+        // Sites are chosen on the heights before roads are flattened, roads connecting the sites would move them otherwise
+        let terrain_data = GeneratedTerrain {
+            heights: terrain_data.base_heights.clone(),
+            ..terrain_data.clone()
+        };
+        let new_layout = generate_layout(settlement, &terrain_data, suitability);
+        if layout != Some(&new_layout) {
+            commands.entity(entity).insert(new_layout);
+        }
+    }
+}
+
+fn generate_layout(
+    settlement: &Settlement,
+    terrain: &GeneratedTerrain,
+    suitability: Option<&SuitabilityMap>,
+) -> SettlementLayout {
+    let mut rng = Rng::new(u64::from(settlement.seed));
+    let max_slope = settlement.max_slope.to_radians();
+    let min_height = terrain.sea_level + settlement.min_height;
+    let buildable = |x: f32, z: f32| {
+        terrain
+            .height(x, z)
+            .is_some_and(|height| height >= min_height)
+            && terrain.slope(x, z).is_some_and(|slope| slope <= max_slope)
+    };
+
+    // Score candidate sites by their average unsuitability or slope, better sites come first
+    let radius = settlement.radius.max(f32::EPSILON);
+    let step = radius / 2.0;
+    let [width, depth] = terrain.size.map(|side| side as f32);
+    let mut candidates: Vec<(f32, Vec2)> = vec![];
+    let steps =
+        [width, depth].map(|side| (radius.mul_add(-2.0, side) / step).max(-1.0).floor() as i32 + 1);
+    for j in 0..steps[1] {
+        let z = (j as f32).mul_add(step, -depth / 2.0 + radius);
+        for i in 0..steps[0] {
+            let x = (i as f32).mul_add(step, -width / 2.0 + radius);
+            let cost = |point: Vec2| {
+                if !buildable(point.x, point.y) {
+                    return None;
+                }
+                suitability.map_or_else(
+                    || terrain.slope(point.x, point.y),
+                    |suitability| {
+                        suitability
+                            .score_at(point.x, point.y)
+                            .map(|score| 1.0 - score)
+                    },
+                )
+            };
+            let samples: Vec<Option<f32>> = (0..8)
+                .map(|i| {
+                    let angle = i as f32 / 8.0 * std::f32::consts::TAU;
+                    cost(Vec2::new(x, z) + Vec2::from_angle(angle) * radius * 0.7)
+                })
+                .chain([cost(Vec2::new(x, z))])
+                .collect();
+            if samples.iter().all(Option::is_some) {
+                let cost = samples.into_iter().flatten().sum::<f32>() / 9.0;
+                // Jitter breaks ties between equally good sites
+                candidates.push(((rng.next_f64() as f32).mul_add(0.01, cost), Vec2::new(x, z)));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut centers: Vec<Vec2> = vec![];
+    for (_, candidate) in candidates {
+        if centers.len() >= settlement.count {
+            break;
+        }
+        if centers
+            .iter()
+            .all(|center| center.distance(candidate) >= settlement.min_spacing)
+        {
+            centers.push(candidate);
+        }
+    }
+
+    let at = |point: Vec2| {
+        Vec3::new(
+            point.x,
+            terrain.height(point.x, point.y).unwrap_or(0.0),
+            point.y,
+        )
+    };
+    let sites = centers
+        .into_iter()
+        .map(|center| {
+            let mut site = Site {
+                center: at(center),
+                nodes: vec![at(center)],
+                ..default()
+            };
+            let mut plots: Vec<Vec2> = vec![];
+            let streets = settlement.streets.max(1);
+            let segment = settlement.street_segment.max(f32::EPSILON);
+            let offset = settlement.street_width / 2.0 + settlement.plot_size[1] / 2.0;
+            let plot_radius = settlement.plot_size[0].max(settlement.plot_size[1]) / 2.0;
+            let start_angle = rng.next_f64() as f32 * std::f32::consts::TAU;
+            for street in 0..streets {
+                let mut angle = (rng.next_f64() as f32 - 0.5).mul_add(
+                    0.5,
+                    (street as f32 / streets as f32).mul_add(std::f32::consts::TAU, start_angle),
+                );
+                let mut previous = 0;
+                let mut position = center;
+                // Streets wander slightly and stop at the edge of the settlement or unbuildable ground
+                for _ in 0..(radius / segment) as u32 {
+                    angle += (rng.next_f64() as f32 - 0.5) * 0.4;
+                    let next = position + Vec2::from_angle(angle) * segment;
+                    if !buildable(next.x, next.y) {
+                        break;
+                    }
+                    site.nodes.push(at(next));
+                    site.edges.push([previous, site.nodes.len() - 1]);
+                    let edge = site.edges.len() - 1;
+                    previous = site.nodes.len() - 1;
+
+                    let direction = (next - position).normalize_or_zero();
+                    let normal = direction.perp();
+                    let plot_spacing = settlement.plot_spacing.max(f32::EPSILON);
+                    for plot_index in 0..(segment / plot_spacing).ceil() as u32 {
+                        let along = (plot_index as f32 + 0.5) * plot_spacing;
+                        for side in [1.0, -1.0] {
+                            let street_point = position + direction * along;
+                            let plot = street_point + normal * offset * side;
+                            let clear = plots
+                                .iter()
+                                .all(|other| other.distance(plot) >= plot_radius * 2.0)
+                                && plot.distance(center) >= offset;
+                            if !clear || !buildable(plot.x, plot.y) {
+                                continue;
+                            }
+                            plots.push(plot);
+                            site.lots.push(Lot {
+                                transform: Transform::from_translation(at(plot)).looking_at(
+                                    Vec3::new(street_point.x, at(plot).y, street_point.y),
+                                    Vec3::Y,
+                                ),
+                                size: settlement.plot_size,
+                                street: edge,
+                                distance: plot.distance(center),
+                            });
+                        }
+                    }
+
+                    position = next;
+                }
+            }
+            site
+        })
+        .collect();
+
+    SettlementLayout { sites }
+}
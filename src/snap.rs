@@ -0,0 +1,137 @@
+//! Snap entities to the terrain surface
+//! # Example
+//! For configuration, see [`SnapToTerrain`](struct.SnapToTerrain.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::snap::{SnapPlugin, SnapToTerrain};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, SnapPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(TerrainBundle::default());
+//!     commands.spawn((
+//!         PbrBundle {
+//!             transform: Transform::from_xyz(0.3, 0.0, -0.2),
+//!             ..default()
+//!         },
+//!         SnapToTerrain {
+//!             offset: 0.05,
+//!             align_to_normal: true,
+//!         },
+//!     ));
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::GeneratedTerrain;
+
+/// Component to keep an entity on the terrain surface.
+/// Children of a terrain entity are snapped in its local space, entities without a parent are snapped
+/// to the first terrain below them in world space
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct SnapToTerrain {
+    /// Height above the surface in world units
+    pub offset: f32,
+    /// If true, the entity's up axis is rotated to the surface normal, keeping its heading
+    pub align_to_normal: bool,
+}
+
+impl Default for SnapToTerrain {
+    fn default() -> Self {
+        Self {
+            offset: 0.0,
+            align_to_normal: false,
+        }
+    }
+}
+
+/// Plugin to snap entities to the terrain
+pub struct SnapPlugin;
+
+impl Plugin for SnapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, snap_to_terrain);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn snap_to_terrain(
+    terrains: Query<(Ref<GeneratedTerrain>, &GlobalTransform)>,
+    mut query: Query<(Ref<SnapToTerrain>, &mut Transform, Option<&Parent>)>,
+) {
+    let any_terrain_changed = terrains.iter().any(|(data, _)| data.is_changed());
+    for (snap, mut transform, parent) in &mut query {
+        let surface = if let Some(parent) = parent {
+            let Ok((terrain_data, _)) = terrains.get(parent.get()) else {
+                continue;
+            };
+            if !(snap.is_changed() || transform.is_changed() || terrain_data.is_changed()) {
+                continue;
+            }
+            local_surface(&terrain_data, transform.translation)
+        } else {
+            if !(snap.is_changed() || transform.is_changed() || any_terrain_changed) {
+                continue;
+            }
+            terrains
+                .iter()
+                .find_map(|(terrain_data, terrain_transform)| {
+                    let affine = terrain_transform.affine();
+                    let local = affine.inverse().transform_point3(transform.translation);
+                    let (point, normal) = local_surface(&terrain_data, local)?;
+                    // Normals transform with the inverse transpose to stay perpendicular under scaling
+                    let normal = Mat3::from(affine.matrix3).inverse().transpose() * normal;
+                    Some((affine.transform_point3(point), normal.normalize()))
+                })
+        };
+        let Some((point, normal)) = surface else {
+            continue;
+        };
+
+        // Only write changes so the entity is not snapped again every frame
+        let height = point.y + snap.offset;
+        if (transform.translation.y - height).abs() > 1e-5 {
+            transform.translation.y = height;
+        }
+        if snap.align_to_normal {
+            let rotation = aligned(transform.rotation, normal);
+            if !transform.rotation.abs_diff_eq(rotation, 1e-5) {
+                transform.rotation = rotation;
+            }
+        }
+    }
+}
+
+/// Surface point and normal below `position` in the local space of the terrain
+fn local_surface(terrain_data: &GeneratedTerrain, position: Vec3) -> Option<(Vec3, Vec3)> {
+    let height = terrain_data.height(position.x, position.z)?;
+    let normal = terrain_data.normal(position.x, position.z)?;
+    Some((Vec3::new(position.x, height, position.z), normal))
+}
+
+/// `rotation` turned so its up axis is `normal`, keeping the forward axis as close as possible
+pub(crate) fn aligned(rotation: Quat, normal: Vec3) -> Quat {
+    let forward = rotation * Vec3::NEG_Z;
+    let heading = (forward - normal * forward.dot(normal))
+        .try_normalize()
+        .unwrap_or_else(|| normal.any_orthonormal_vector());
+    Transform::IDENTITY.looking_to(heading, normal).rotation
+}
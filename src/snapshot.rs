@@ -0,0 +1,305 @@
+//! Save the generator configs of a world into a scene and regenerate the world from it
+//! # Example
+//! [`WorldSnapshot`](struct.WorldSnapshot.html) captures the configuration components of generators,
+//! e.g. `Terrain`, `Scatter` or `Dungeon`, with the transforms, names and parents of their entities into a `DynamicScene`.
+//! Generated data, meshes and textures are left out. Spawning the scene gives the entities their meshes or images again,
+//! so the generators regenerate the same world
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::snapshot::{SnapshotPlugin, WorldSnapshot};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, SnapshotPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, save)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     if std::path::Path::new("assets/world.scn.ron").exists() {
+//!         commands.spawn(DynamicSceneBundle {
+//!             scene: asset_server.load("world.scn.ron"),
+//!             ..default()
+//!         });
+//!     } else {
+//!         commands.spawn(TerrainBundle::default());
+//!     }
+//! }
+//!
+//! fn save(world: &mut World) {
+//!     if world.resource::<Input<KeyCode>>().just_pressed(KeyCode::F5) {
+//!         let scene = WorldSnapshot::serialize(world).unwrap();
+//!         std::fs::write("assets/world.scn.ron", scene).unwrap();
+//!     }
+//! }
+//! ```
+use std::any::TypeId;
+
+use bevy::{
+    prelude::*,
+    reflect::GetTypeRegistration,
+    scene::{DynamicEntity, SceneFilter},
+};
+
+use crate::headless::DataOnly;
+#[cfg(feature = "terrain")]
+use crate::streaming::TerrainChunk;
+
+/// How the entity of a snapshot was rendered, added to every captured entity.
+/// Spawning the snapshot gives the entity a mesh, an image or neither for its generator to fill
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug, Reflect)]
+#[reflect(Component)]
+pub enum SnapshotRender {
+    /// Mesh and standard material, like the `PbrBundle` of most generator bundles
+    #[default]
+    Mesh,
+    /// UI image, like the `ImageBundle` of `MapBundle`
+    Image,
+    /// Neither, e.g. for `DataOnly` entities or generators without a bundle
+    Data,
+}
+
+/// Components captured by `WorldSnapshot`, entities with one of them are captured
+#[derive(Resource, Default)]
+struct SnapshotComponents {
+    types: Vec<TypeId>,
+}
+
+/// Captures generator configs into a `DynamicScene`, with the components registered by the `SnapshotPlugin`
+pub struct WorldSnapshot;
+
+impl WorldSnapshot {
+    /// Captures every entity with a generator config, except the chunks spawned by `TerrainStreaming`
+    #[must_use]
+    pub fn capture(world: &World) -> DynamicScene {
+        let types = world
+            .get_resource::<SnapshotComponents>()
+            .map_or(&[][..], |components| &components.types);
+        let entities: Vec<Entity> = world
+            .iter_entities()
+            .filter(|entity| {
+                #[cfg(feature = "terrain")]
+                if entity.contains::<TerrainChunk>() {
+                    return false;
+                }
+                types.iter().any(|&id| entity.contains_type_id(id))
+            })
+            .map(|entity| entity.id())
+            .collect();
+        Self::capture_entities(world, &entities)
+    }
+
+    /// Captures the generator configs, transforms, names, UI styles and `DataOnly` markers of `entities`.
+    /// Parents are kept if they are captured too, their other children are left out
+    #[must_use]
+    pub fn capture_entities(world: &World, entities: &[Entity]) -> DynamicScene {
+        let mut filter = SceneFilter::deny_all()
+            .allow::<Transform>()
+            .allow::<Name>()
+            .allow::<Style>()
+            .allow::<DataOnly>();
+        if let Some(components) = world.get_resource::<SnapshotComponents>() {
+            for &id in &components.types {
+                filter = filter.allow_by_id(id);
+            }
+        }
+        let (children, roots): (Vec<Entity>, Vec<Entity>) =
+            entities.iter().copied().partition(|&entity| {
+                world
+                    .get::<Parent>(entity)
+                    .is_some_and(|parent| entities.contains(&parent.get()))
+            });
+        let extract = |filter: SceneFilter, entities: Vec<Entity>| {
+            DynamicSceneBuilder::from_world(world)
+                .with_filter(filter)
+                .deny_all_resources()
+                .extract_entities(entities.into_iter())
+                .build()
+                .entities
+        };
+        let mut captured = extract(filter.clone(), roots);
+        captured.extend(extract(filter.allow::<Parent>(), children));
+        for DynamicEntity { entity, components } in &mut captured {
+            let render = if world.get::<Handle<Mesh>>(*entity).is_some() {
+                SnapshotRender::Mesh
+            } else if world.get::<UiImage>(*entity).is_some() {
+                SnapshotRender::Image
+            } else {
+                SnapshotRender::Data
+            };
+            components.push(Box::new(render));
+        }
+        DynamicScene {
+            resources: vec![],
+            entities: captured,
+        }
+    }
+
+    /// Captures every entity with a generator config like `capture` and serializes the scene as RON
+    ///
+    /// # Errors
+    /// If a captured component can not be serialized
+    pub fn serialize(world: &World) -> Result<String, ron::Error> {
+        Self::capture(world).serialize_ron(world.resource::<AppTypeRegistry>())
+    }
+}
+
+/// Extension of `App` capturing further components in snapshots, e.g. of custom generators
+pub trait SnapshotAppExt {
+    /// Registers `T` for reflection and captures it and its entities in snapshots.
+    /// `T` needs `#[reflect(Component)]` or `#[reflect_value(Component)]`
+    fn register_snapshot_component<T: Component + GetTypeRegistration>(&mut self) -> &mut Self;
+}
+
+impl SnapshotAppExt for App {
+    fn register_snapshot_component<T: Component + GetTypeRegistration>(&mut self) -> &mut Self {
+        self.register_type::<T>();
+        let mut components = self
+            .world
+            .get_resource_or_insert_with(SnapshotComponents::default);
+        if !components.types.contains(&TypeId::of::<T>()) {
+            components.types.push(TypeId::of::<T>());
+        }
+        self
+    }
+}
+
+/// Plugin to capture generator configs in snapshots and restore spawned snapshots
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        // Registered by the transform, hierarchy and UI plugins too, but needed to capture headless worlds
+        app.register_type::<Transform>()
+            .register_type::<Name>()
+            .register_type::<Parent>()
+            .register_type::<Style>()
+            .register_type::<DataOnly>()
+            .register_type::<SnapshotRender>()
+            .add_systems(Update, restore_snapshots);
+        app.register_snapshot_component::<crate::caustics::WaterTexture>()
+            .register_snapshot_component::<crate::cloud::Clouds>()
+            .register_snapshot_component::<crate::plant::Plant>()
+            .register_snapshot_component::<crate::rock::Rock>()
+            .register_snapshot_component::<crate::sprite::PixelSprite>()
+            .register_snapshot_component::<crate::starfield::Starfield>()
+            .register_snapshot_component::<crate::synthesis::TextureSynthesis>()
+            .register_snapshot_component::<crate::volume::Volume>();
+        #[cfg(any(feature = "terrain", feature = "map"))]
+        app.register_snapshot_component::<crate::heatmap::DebugView>();
+        #[cfg(feature = "terrain")]
+        app.register_snapshot_component::<crate::terrain::Terrain>()
+            .register_snapshot_component::<crate::beach::Beach>()
+            .register_snapshot_component::<crate::biome::Whittaker>()
+            .register_snapshot_component::<crate::border::Borders>()
+            .register_snapshot_component::<crate::bridge::Bridges>()
+            .register_snapshot_component::<crate::climate::Climate>()
+            .register_snapshot_component::<crate::deform::TerrainDeformation>()
+            .register_snapshot_component::<crate::distance::SeaDistance>()
+            .register_snapshot_component::<crate::export::EngineExport>()
+            .register_snapshot_component::<crate::export::SceneExport>()
+            .register_snapshot_component::<crate::export::WorldExport>()
+            .register_snapshot_component::<crate::flow::Flow>()
+            .register_snapshot_component::<crate::fog::FogOfWar>()
+            .register_snapshot_component::<crate::fog::FogRevealer>()
+            .register_snapshot_component::<crate::hole::TerrainHoles>()
+            .register_snapshot_component::<crate::lake::Lakes>()
+            .register_snapshot_component::<crate::layer::HeightLayers>()
+            .register_snapshot_component::<crate::ore::OreVeins>()
+            .register_snapshot_component::<crate::palette::BiomePalettes>()
+            .register_snapshot_component::<crate::persist::PersistentChunk>()
+            .register_snapshot_component::<crate::poi::PointsOfInterest>()
+            .register_snapshot_component::<crate::river::Rivers>()
+            .register_snapshot_component::<crate::road::Roads>()
+            .register_snapshot_component::<crate::scatter::Scatter>()
+            .register_snapshot_component::<crate::scatter::ScatterEdits>()
+            .register_snapshot_component::<crate::server::ChunkServer>()
+            .register_snapshot_component::<crate::settlement::Settlement>()
+            .register_snapshot_component::<crate::snap::SnapToTerrain>()
+            .register_snapshot_component::<crate::snow::Snow>()
+            .register_snapshot_component::<crate::splat::SplatMap>()
+            .register_snapshot_component::<crate::stamp::Stamps>()
+            .register_snapshot_component::<crate::streaming::TerrainStreaming>()
+            .register_snapshot_component::<crate::structure::StructurePlacement>()
+            .register_snapshot_component::<crate::suitability::Suitability>()
+            .register_snapshot_component::<crate::territory::Territory>()
+            .register_snapshot_component::<crate::verify::ExpectedChunkHash>()
+            .register_snapshot_component::<crate::walkability::Walkability>()
+            .register_snapshot_component::<crate::water::Water>()
+            .register_snapshot_component::<crate::weather::Weather>();
+        #[cfg(feature = "navmesh")]
+        app.register_snapshot_component::<crate::navmesh::NavMeshTerrain>();
+        #[cfg(feature = "map")]
+        app.register_snapshot_component::<crate::map::Map>()
+            .register_snapshot_component::<crate::flipbook::NoiseAnimation>()
+            .register_snapshot_component::<crate::texture::ProceduralTexture>();
+        #[cfg(feature = "planet")]
+        app.register_snapshot_component::<crate::planet::Planet>()
+            .register_snapshot_component::<crate::planet::PlanetTexture>()
+            .register_snapshot_component::<crate::gas_giant::GasGiant>();
+        #[cfg(feature = "dungeon")]
+        app.register_snapshot_component::<crate::dungeon::Dungeon>()
+            .register_snapshot_component::<crate::cave::Cave>()
+            .register_snapshot_component::<crate::maze::Maze>()
+            .register_snapshot_component::<crate::mission::Mission>()
+            .register_snapshot_component::<crate::wfc::Wfc>();
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn restore_snapshots(
+    mut commands: Commands,
+    restored: Query<
+        (
+            Entity,
+            &SnapshotRender,
+            Option<&Transform>,
+            Option<&Style>,
+            Option<&Parent>,
+        ),
+        Added<SnapshotRender>,
+    >,
+    children: Query<&Children>,
+) {
+    for (entity, render, transform, style, parent) in &restored {
+        let transform = transform.copied().unwrap_or_default();
+        let mut entity_commands = commands.entity(entity);
+        match render {
+            SnapshotRender::Mesh => {
+                entity_commands.insert(PbrBundle {
+                    transform,
+                    ..default()
+                });
+            }
+            SnapshotRender::Image => {
+                entity_commands.insert(ImageBundle {
+                    style: style.cloned().unwrap_or_default(),
+                    ..default()
+                });
+            }
+            SnapshotRender::Data => {
+                entity_commands.insert(TransformBundle::from_transform(transform));
+            }
+        }
+        // Scenes store parents but not the children pointing back at them
+        if let Some(parent) = parent {
+            let listed = children
+                .get(parent.get())
+                .is_ok_and(|children| children.contains(&entity));
+            if !listed {
+                commands.entity(parent.get()).add_child(entity);
+            }
+        }
+    }
+}
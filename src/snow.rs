@@ -0,0 +1,145 @@
+//! Cover terrain with snow by altitude and slope
+//! # Example
+//! For configuration, see [`Snow`](struct.Snow.html).
+//! The terrain is generated again whenever `Snow` changes, so the snowline can be animated for seasons or weather
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::snow::Snow;
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, seasons)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Snow::default()));
+//! }
+//!
+//! fn seasons(mut query: Query<&mut Snow>, time: Res<Time>) {
+//!     for mut snow in &mut query {
+//!         snow.snowline = 0.8 + time.elapsed_seconds().sin() * 0.4;
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::terrain::{GeneratedTerrain, TerrainColors};
+
+/// Component for snow configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Snow {
+    /// Height above sea level in world units where the snow cover is half complete
+    pub snowline: f32,
+    /// Height in world units over which the snow fades in around the snowline
+    pub transition: f32,
+    /// Slope in degrees where snow cover is half complete, steeper slopes shed their snow
+    pub max_slope: f32,
+    /// Slope in degrees over which the snow fades out around `max_slope`
+    pub slope_transition: f32,
+    /// Overall amount of snow from 0 to 1, e.g. for seasons
+    pub amount: f32,
+    /// Color of the snow
+    pub color: [u8; 4],
+    /// Height the snow raises fully covered terrain by in world units
+    pub depth: f32,
+}
+
+impl Default for Snow {
+    fn default() -> Self {
+        Self {
+            snowline: 0.8,
+            transition: 0.2,
+            max_slope: 40.0,
+            slope_transition: 10.0,
+            amount: 1.0,
+            color: [245, 248, 252, 255],
+            depth: 0.0,
+        }
+    }
+}
+
+impl Snow {
+    /// Snow cover of every vertex of `heights` from 0 to 1, `heights` are indexed by `[x][z]`
+    #[must_use]
+    pub fn coverage(&self, heights: &[Vec<f32>], resolution: u32, sea_level: f32) -> Vec<Vec<f32>> {
+        let step = 1.0 / resolution.max(1) as f32;
+        let fade = |value: f32, width: f32| {
+            let x = (value / width.max(f32::EPSILON) + 0.5).clamp(0.0, 1.0);
+            x * x * 2.0f32.mul_add(-x, 3.0)
+        };
+        let amount = self.amount.clamp(0.0, 1.0);
+        heights
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let rows = column.len();
+                (0..rows)
+                    .map(|j| {
+                        let slope = slope_degrees(heights, i, j, step);
+                        let altitude = fade(column[j] - sea_level - self.snowline, self.transition);
+                        let flat = fade(self.max_slope - slope, self.slope_transition);
+                        altitude * flat * amount
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// `color` with the snow blended over it by `coverage`
+    pub(crate) fn blend(&self, color: [f32; 4], coverage: f32) -> [f32; 4] {
+        let snow = Vec4::from(self.color.map(|channel| f32::from(channel) / 255.0));
+        Vec4::from(color).lerp(snow, coverage).to_array()
+    }
+}
+
+/// Slope of vertex `i`, `j` of `heights` in degrees, with central differences over vertices `step` apart.
+/// One sided at the sides of the grid
+pub(crate) fn slope_degrees(heights: &[Vec<f32>], i: usize, j: usize, step: f32) -> f32 {
+    let columns = heights.len();
+    let rows = heights[i].len();
+    let [left, right] = [i.saturating_sub(1), (i + 1).min(columns - 1)];
+    let [near, far] = [j.saturating_sub(1), (j + 1).min(rows - 1)];
+    let dx = (heights[right][j] - heights[left][j]) / ((right - left).max(1) as f32 * step);
+    let dz = (heights[i][far] - heights[i][near]) / ((far - near).max(1) as f32 * step);
+    dx.hypot(dz).atan().to_degrees()
+}
+
+/// Snow cover of the terrain, inserted on the entity with the `Snow` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct SnowCover {
+    /// Snow cover of every vertex from 0 to 1, indexed like `GeneratedTerrain::heights`
+    pub coverage: Vec<Vec<f32>>,
+}
+
+/// Blends the snow over recolored terrains by their snow cover
+pub(crate) fn blend_snow(
+    mut query: Query<(&mut TerrainColors, &GeneratedTerrain, &Snow, &SnowCover)>,
+) {
+    for (mut colors, terrain_data, snow, snow_cover) in &mut query {
+        if colors.is_recoloring() {
+            colors.blend(terrain_data, |[i, j], _, color| {
+                snow_cover
+                    .coverage
+                    .get(i)
+                    .and_then(|column| column.get(j))
+                    .map_or(color, |cover| snow.blend(color, *cover))
+            });
+        }
+    }
+}
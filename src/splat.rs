@@ -0,0 +1,416 @@
+//! Paint splat map layers onto terrain at runtime
+//! # Example
+//! For configuration, see [`SplatMap`](struct.SplatMap.html) and [`SplatBrush`](struct.SplatBrush.html).
+//! The splat map serializes with its painted weights, so painting is saved along with the other components.
+//! Layers with [`SplatLayer::biomes`](struct.SplatLayer.html#structfield.biomes) also cover their biomes
+//! of a `BiomeMap`, fading out across the borders by [`SplatMap::biome_transition`](struct.SplatMap.html#structfield.biome_transition)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::splat::{SplatBrush, SplatMap, SplatPlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, SplatPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, paint)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), SplatMap::default()));
+//! }
+//!
+//! fn paint(mut brush: EventWriter<SplatBrush>, time: Res<Time>) {
+//!     let angle = time.elapsed_seconds();
+//!     brush.send(SplatBrush {
+//!         position: Vec3::new(angle.cos(), 0.0, angle.sin()) * 0.5,
+//!         radius: 0.05,
+//!         layer: 0,
+//!         strength: 0.1,
+//!         ..default()
+//!     });
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::{Biome, BiomeMap, BiomeTransition, BiomeWeights},
+    terrain::{GeneratedTerrain, TerrainColors, TerrainSet},
+};
+
+/// Layer of a `SplatMap`
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SplatLayer {
+    /// Name of the layer, e.g. `"path"`
+    pub name: String,
+    /// Color blended over the terrain where the layer is painted
+    pub color: [u8; 4],
+    /// Biomes of the `BiomeMap` of the terrain covered by the layer on top of its painted weights
+    pub biomes: Vec<Biome>,
+}
+
+impl Default for SplatLayer {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            color: [255; 4],
+            biomes: vec![],
+        }
+    }
+}
+
+/// Component for splat map painting, added to an entity with a `Terrain` component.
+/// Up to four layers are stored in the channels of one texture and blended over the terrain colors in order
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct SplatMap {
+    /// Painted layers, only the first four are used
+    pub layers: Vec<SplatLayer>,
+    /// Number of texels per world unit
+    pub resolution: u32,
+    /// Number of texels along `x` and `z`, set from the size of the terrain
+    pub size: [u32; 2],
+    /// Weight of every layer in every texel, rows along `x` ordered by `z`
+    pub weights: Vec<[u8; 4]>,
+    /// Blending of layers with `SplatLayer::biomes` across the borders of their biomes
+    pub biome_transition: BiomeTransition,
+    /// Weight of every layer from its biomes, laid out like `weights`. Empty if no layer has biomes
+    #[serde(skip)]
+    pub biome_weights: Vec<[u8; 4]>,
+    /// Texture of the weights with one layer per channel, updated as the map is painted
+    #[serde(skip)]
+    pub texture: Handle<Image>,
+}
+
+impl Default for SplatMap {
+    fn default() -> Self {
+        Self {
+            layers: vec![
+                SplatLayer {
+                    name: "path".to_string(),
+                    color: [133, 108, 78, 255],
+                    biomes: vec![],
+                },
+                SplatLayer {
+                    name: "scorch".to_string(),
+                    color: [38, 33, 30, 255],
+                    biomes: vec![],
+                },
+            ],
+            resolution: 64,
+            size: [0; 2],
+            weights: vec![],
+            biome_transition: BiomeTransition::default(),
+            biome_weights: vec![],
+            texture: Handle::default(),
+        }
+    }
+}
+
+impl SplatMap {
+    /// Weights of the layers at `x`, `z` on the overlay from 0 to 1, 0 outside of the map.
+    /// See `GeneratedTerrain::to_overlay`
+    #[must_use]
+    pub fn weights_at(&self, x: f32, z: f32, terrain_size: [u32; 2]) -> [f32; 4] {
+        let resolution = self.resolution.max(1) as f32;
+        let i = ((x + terrain_size[0] as f32 / 2.0) * resolution).round();
+        let j = ((z + terrain_size[1] as f32 / 2.0) * resolution).round();
+        if i < 0.0 || j < 0.0 || i >= self.size[0] as f32 || j >= self.size[1] as f32 {
+            return [0.0; 4];
+        }
+        self.texel(j as usize * self.size[0] as usize + i as usize)
+            .map_or([0.0; 4], |weights| {
+                weights.map(|weight| f32::from(weight) / 255.0)
+            })
+    }
+
+    /// Painted weights of texel `index` raised to the weights of the biomes
+    fn texel(&self, index: usize) -> Option<[u8; 4]> {
+        let painted = *self.weights.get(index)?;
+        Some(self.biome_weights.get(index).map_or(painted, |biome| {
+            [0, 1, 2, 3].map(|layer| painted[layer].max(biome[layer]))
+        }))
+    }
+
+    /// Weights of the layers with biomes in every texel, empty if no layer has biomes
+    fn layer_biome_weights(&self, biomes: &BiomeMap, terrain_size: [u32; 2]) -> Vec<[u8; 4]> {
+        if self
+            .layers
+            .iter()
+            .take(4)
+            .all(|layer| layer.biomes.is_empty())
+        {
+            return vec![];
+        }
+        let layers: Vec<Option<BiomeWeights>> = self
+            .layers
+            .iter()
+            .take(4)
+            .map(|layer| {
+                (!layer.biomes.is_empty())
+                    .then(|| biomes.weights(&layer.biomes, &self.biome_transition))
+            })
+            .collect();
+        let resolution = self.resolution.max(1) as f32;
+        let half = Vec2::new(terrain_size[0] as f32, terrain_size[1] as f32) / 2.0;
+        let [columns, rows] = self.size.map(|side| side as usize);
+        let mut texels = Vec::with_capacity(columns * rows);
+        for j in 0..rows {
+            for i in 0..columns {
+                let point = Vec2::new(i as f32, j as f32) / resolution - half;
+                let mut texel = [0; 4];
+                for (weight, layer) in texel.iter_mut().zip(&layers) {
+                    if let Some(layer) = layer {
+                        *weight = (layer.weight_at(point.x, point.y) * 255.0).round() as u8;
+                    }
+                }
+                texels.push(texel);
+            }
+        }
+        texels
+    }
+
+    /// `color` with every layer blended over it by its weight at `x`, `z`
+    pub(crate) fn blend(
+        &self,
+        color: [f32; 4],
+        x: f32,
+        z: f32,
+        terrain_size: [u32; 2],
+    ) -> [f32; 4] {
+        let weights = self.weights_at(x, z, terrain_size);
+        let mut color = Vec4::from(color);
+        for (layer, weight) in self.layers.iter().zip(weights) {
+            let layer_color = Vec4::from(layer.color.map(|channel| f32::from(channel) / 255.0));
+            color = color.lerp(layer_color, weight);
+        }
+        color.to_array()
+    }
+
+    /// Returns true if the weights cover a terrain of `terrain_size`
+    fn fits(&self, terrain_size: [u32; 2]) -> bool {
+        let size = terrain_size.map(|side| side * self.resolution.max(1) + 1);
+        self.size == size && self.weights.len() == (size[0] * size[1]) as usize
+    }
+
+    /// Clears the weights to cover a terrain of `terrain_size`
+    fn fit(&mut self, terrain_size: [u32; 2]) {
+        self.size = terrain_size.map(|side| side * self.resolution.max(1) + 1);
+        self.weights = vec![[0; 4]; (self.size[0] * self.size[1]) as usize];
+        self.biome_weights.clear();
+    }
+
+    fn to_image(&self) -> Image {
+        Image::new(
+            Extent3d {
+                width: self.size[0],
+                height: self.size[1],
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            (0..self.weights.len())
+                .filter_map(|index| self.texel(index))
+                .flatten()
+                .collect(),
+            TextureFormat::Rgba8Unorm,
+        )
+    }
+}
+
+/// Event to paint a splat map layer on every terrain at a position, e.g. under the cursor
+#[derive(Event, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SplatBrush {
+    /// Center of the brush in world space, only `x` and `z` are used
+    pub position: Vec3,
+    /// Radius of the brush in world units
+    pub radius: f32,
+    /// Index of the painted layer
+    pub layer: usize,
+    /// Weight added at the center of the brush from 0 to 1, negative values erase
+    pub strength: f32,
+    /// Fraction of the radius painted at full strength, the rest fades out
+    pub hardness: f32,
+}
+
+impl Default for SplatBrush {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            radius: 0.1,
+            layer: 0,
+            strength: 1.0,
+            hardness: 0.5,
+        }
+    }
+}
+
+/// Plugin to paint splat maps
+pub struct SplatPlugin;
+
+impl Plugin for SplatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SplatBrush>().add_systems(
+            Update,
+            (prepare_splat_maps, cover_biomes, paint_splat_maps)
+                .chain()
+                .before(TerrainSet::Color),
+        );
+    }
+}
+
+/// Sizes splat maps to their terrain and creates their textures, e.g. after a splat map is loaded.
+/// Without the render assets splat maps get no texture
+fn prepare_splat_maps(
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut query: Query<(&mut SplatMap, &GeneratedTerrain)>,
+) {
+    for (mut splat_map, terrain_data) in &mut query {
+        if !splat_map.fits(terrain_data.size) {
+            splat_map.fit(terrain_data.size);
+        } else if images
+            .as_ref()
+            .is_none_or(|images| images.contains(&splat_map.texture))
+        {
+            continue;
+        }
+        if let Some(images) = images.as_deref_mut() {
+            splat_map.texture = images.add(splat_map.to_image());
+        }
+    }
+}
+
+/// Updates the weights of layers with biomes when the biomes or the layers change
+#[allow(clippy::type_complexity)]
+fn cover_biomes(
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut query: Query<
+        (&mut SplatMap, &GeneratedTerrain, &BiomeMap),
+        Or<(Changed<SplatMap>, Changed<BiomeMap>)>,
+    >,
+) {
+    for (mut splat_map, terrain_data, biomes) in &mut query {
+        if !splat_map.fits(terrain_data.size) {
+            continue;
+        }
+        let biome_weights = splat_map.layer_biome_weights(biomes, terrain_data.size);
+        if biome_weights != splat_map.biome_weights {
+            splat_map.biome_weights = biome_weights;
+            if let Some(images) = images.as_deref_mut() {
+                splat_map.texture = images.add(splat_map.to_image());
+            }
+        }
+    }
+}
+
+/// Paints the weights and their texels in place. The `SplatMap` is not marked changed, so strokes
+/// only update the texture and recolor the terrain
+fn paint_splat_maps(
+    mut events: EventReader<SplatBrush>,
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut query: Query<(
+        &mut SplatMap,
+        &GeneratedTerrain,
+        &GlobalTransform,
+        Option<&mut TerrainColors>,
+    )>,
+) {
+    let brushes: Vec<SplatBrush> = events.read().copied().collect();
+    if brushes.is_empty() {
+        return;
+    }
+    for (mut splat_map, terrain_data, transform, colors) in &mut query {
+        let splat_map = splat_map.bypass_change_detection();
+        let mut stroked = false;
+        let to_local = transform.affine().inverse();
+        let resolution = splat_map.resolution.max(1) as f32;
+        let half = Vec2::new(terrain_data.size[0] as f32, terrain_data.size[1] as f32) / 2.0;
+        let [columns, rows] = splat_map.size.map(|side| side as usize);
+        for brush in &brushes {
+            if brush.layer >= 4 || columns == 0 || rows == 0 {
+                continue;
+            }
+            let center = terrain_data.to_overlay(to_local.transform_point3(brush.position).xz());
+            let radius = terrain_data
+                .to_overlay_radius(brush.radius)
+                .max(f32::EPSILON);
+            let min = ((center - radius + half) * resolution).floor();
+            let max = ((center + radius + half) * resolution).ceil();
+            if max.x < 0.0 || max.y < 0.0 || min.x >= columns as f32 || min.y >= rows as f32 {
+                continue;
+            }
+            let [min_i, min_j] = [min.x.max(0.0) as usize, min.y.max(0.0) as usize];
+            let [max_i, max_j] = [
+                (max.x as usize).min(columns - 1),
+                (max.y as usize).min(rows - 1),
+            ];
+            let hardness = brush.hardness.clamp(0.0, 1.0);
+            for j in min_j..=max_j {
+                for i in min_i..=max_i {
+                    let point = Vec2::new(i as f32, j as f32) / resolution - half;
+                    let distance = point.distance(center) / radius;
+                    if distance >= 1.0 {
+                        continue;
+                    }
+                    let falloff = if distance <= hardness {
+                        1.0
+                    } else {
+                        let x = 1.0 - (distance - hardness) / (1.0 - hardness).max(f32::EPSILON);
+                        x * x * 2.0f32.mul_add(-x, 3.0)
+                    };
+                    let weight = &mut splat_map.weights[j * columns + i][brush.layer];
+                    let painted = (brush.strength * falloff).mul_add(255.0, f32::from(*weight));
+                    *weight = painted.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+            stroked = true;
+            // Only the painted region of the texture is written
+            let texture = splat_map.texture.clone();
+            if let Some(image) = images
+                .as_deref_mut()
+                .and_then(|images| images.get_mut(&texture))
+            {
+                for j in min_j..=max_j {
+                    let start = j * columns + min_i;
+                    let texels: Vec<u8> = (start..=j * columns + max_i)
+                        .filter_map(|index| splat_map.texel(index))
+                        .flatten()
+                        .collect();
+                    image.data[start * 4..start * 4 + texels.len()].copy_from_slice(&texels);
+                }
+            }
+        }
+        if let Some(mut colors) = colors.filter(|_| stroked) {
+            colors.recolor();
+        }
+    }
+}
+
+/// Blends the painted layers over recolored terrains
+pub(crate) fn blend_splat_maps(
+    mut query: Query<(&mut TerrainColors, &GeneratedTerrain, &SplatMap)>,
+) {
+    for (mut colors, terrain_data, splat_map) in &mut query {
+        if colors.is_recoloring() {
+            colors.blend(terrain_data, |_, point, color| {
+                splat_map.blend(color, point.x, point.y, terrain_data.size)
+            });
+        }
+    }
+}
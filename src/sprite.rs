@@ -0,0 +1,308 @@
+//! Generate pixel-art sprites
+//! # Example
+//! For configuration, see [`PixelSprite`](struct.PixelSprite.html).
+//! Every sprite is a random fill mirrored along its axes, smoothed by a cellular automaton and colored from a palette.
+//! A `SpriteBundle` on the entity shows the sheet, a `Handle<TextureAtlas>` is replaced by the generated layout
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::sprite::{PixelSprite, PixelSpritePlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(PixelSpritePlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(Camera2dBundle::default());
+//!     commands.spawn((
+//!         PixelSprite {
+//!             count: 16,
+//!             ..default()
+//!         },
+//!         SpriteBundle {
+//!             transform: Transform::from_scale(Vec3::splat(8.0)),
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "image")]
+use crate::util::export_asset;
+use crate::util::{pack_sheet, sheet_grid, Rng};
+
+/// Axes a `PixelSprite` is mirrored along
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Mirror {
+    /// Not mirrored
+    None,
+    /// Left half mirrored to the right, like ships and faces
+    #[default]
+    Horizontal,
+    /// Top half mirrored to the bottom
+    Vertical,
+    /// Mirrored along both axes, like items and runes
+    Both,
+}
+
+/// Component for pixel sprite configuration
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct PixelSprite {
+    /// Seed of the first sprite, every further sprite of the sheet continues from it
+    pub seed: u32,
+    /// Width and height of a sprite in pixels
+    pub size: [u32; 2],
+    /// Number of sprites in the sheet
+    pub count: u32,
+    /// Axes the sprites are mirrored along
+    pub mirror: Mirror,
+    /// Probability of a pixel to be filled before smoothing, from 0 to 1
+    pub fill: f64,
+    /// Iterations of the cellular automaton smoothing the fill
+    pub smoothing: u32,
+    /// Colors of the filled pixels, picked from top to bottom with random shading
+    pub palette: Vec<[u8; 4]>,
+    /// Color of the border around the filled pixels, if any
+    pub outline: Option<[u8; 4]>,
+    /// If true, exports the sheet in png format
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for PixelSprite {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: [12, 12],
+            count: 1,
+            mirror: Mirror::Horizontal,
+            fill: 0.5,
+            smoothing: 2,
+            palette: vec![
+                [230, 220, 160, 255],
+                [200, 110, 60, 255],
+                [140, 60, 70, 255],
+                [70, 50, 90, 255],
+            ],
+            outline: Some([20, 16, 30, 255]),
+            export: false,
+        }
+    }
+}
+
+impl PixelSprite {
+    /// Number of columns and rows of the sheet, as square as possible
+    #[must_use]
+    pub fn grid(&self) -> [u32; 2] {
+        sheet_grid(self.count)
+    }
+
+    /// Sprite at `index` of the sheet, with a nearest neighbour sampler to keep the pixels sharp
+    #[must_use]
+    pub fn sprite(&self, index: u32) -> Image {
+        let [width, height] = self.size.map(|size| size.max(1));
+        let data = self.pixels(index).into_iter().flatten().collect();
+        sprite_image(width, height, data)
+    }
+
+    /// All sprites laid out in the rows and columns of [`PixelSprite::grid`], empty cells are transparent
+    #[must_use]
+    pub fn sheet(&self) -> Image {
+        let size = self.size.map(|size| size.max(1));
+        let [columns, rows] = self.grid();
+        let frames: Vec<Vec<u8>> = (0..self.count.max(1))
+            .map(|index| self.pixels(index).into_iter().flatten().collect())
+            .collect();
+        sprite_image(columns * size[0], rows * size[1], pack_sheet(&frames, size))
+    }
+
+    /// Colors of the sprite at `index` row by row
+    fn pixels(&self, index: u32) -> Vec<[u8; 4]> {
+        let [width, height] = self.size.map(|size| size.max(1) as usize);
+        let mut rng = Rng::new(u64::from(self.seed) << 32 | u64::from(index));
+        // Only the cells up to the mirror axes are random, the rest copy them
+        let (mirror_x, mirror_y) = match self.mirror {
+            Mirror::None => (false, false),
+            Mirror::Horizontal => (true, false),
+            Mirror::Vertical => (false, true),
+            Mirror::Both => (true, true),
+        };
+        let source = |x: usize, y: usize| {
+            (
+                if mirror_x { x.min(width - 1 - x) } else { x },
+                if mirror_y { y.min(height - 1 - y) } else { y },
+            )
+        };
+        let random: Vec<Vec<(bool, f64)>> = (0..height)
+            .map(|_| {
+                (0..width)
+                    .map(|_| (rng.next_f64() < self.fill, rng.next_f64()))
+                    .collect()
+            })
+            .collect();
+        let mut filled: Vec<Vec<bool>> = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        // Edge pixels stay empty to leave room for the outline
+                        let edge = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                        let (x, y) = source(x, y);
+                        random[y][x].0 && !(edge && self.outline.is_some())
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Cells outside the sprite count as empty, so shapes pull away from the edges
+        let is_filled = |filled: &[Vec<bool>], x: isize, y: isize| {
+            x >= 0
+                && y >= 0
+                && (x as usize) < width
+                && (y as usize) < height
+                && filled[y as usize][x as usize]
+        };
+        for _ in 0..self.smoothing {
+            filled = (0..height)
+                .map(|y| {
+                    (0..width)
+                        .map(|x| {
+                            let neighbours = (-1..=1)
+                                .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                                .filter(|&(dx, dy)| {
+                                    (dx, dy) != (0, 0)
+                                        && is_filled(&filled, x as isize + dx, y as isize + dy)
+                                })
+                                .count();
+                            neighbours >= 5 || (filled[y][x] && neighbours >= 4)
+                        })
+                        .collect()
+                })
+                .collect();
+        }
+
+        let palette = if self.palette.is_empty() {
+            vec![[255; 4]]
+        } else {
+            self.palette.clone()
+        };
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let color = if filled[y][x] {
+                    let (source_x, source_y) = source(x, y);
+                    // Half from the height in the sprite, half random shading
+                    let value = 0.5f64
+                        .mul_add(random[source_y][source_x].1, 0.5 * y as f64 / height as f64)
+                        .clamp(0.0, 1.0);
+                    palette[((value * palette.len() as f64) as usize).min(palette.len() - 1)]
+                } else {
+                    let bordering = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                        .iter()
+                        .any(|&(dx, dy)| is_filled(&filled, x as isize + dx, y as isize + dy));
+                    match self.outline {
+                        Some(outline) if bordering => outline,
+                        _ => [0; 4],
+                    }
+                };
+                pixels.push(color);
+            }
+        }
+        pixels
+    }
+}
+
+fn sprite_image(width: u32, height: u32, data: Vec<u8>) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+/// Sheet of the sprites of a `PixelSprite`, inserted on the entity with the `PixelSprite` component
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug)]
+pub struct PixelSprites {
+    /// Image of all sprites
+    pub sheet: Handle<Image>,
+    /// Layout of the sprites in the sheet, indexed like the sprites
+    pub atlas: Handle<TextureAtlas>,
+}
+
+/// Plugin to generate pixel sprites
+pub struct PixelSpritePlugin;
+
+impl Plugin for PixelSpritePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_pixel_sprites);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_pixel_sprites(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut PixelSprite,
+            Option<&mut Handle<Image>>,
+            Option<&mut Handle<TextureAtlas>>,
+        ),
+        Changed<PixelSprite>,
+    >,
+) {
+    for (entity, mut sprite, image_handle, atlas_handle) in &mut query {
+        let sheet = sprite.sheet();
+        #[cfg(feature = "image")]
+        if sprite.export {
+            if let Some(buffer) =
+                image::ImageBuffer::from_raw(sheet.width(), sheet.height(), sheet.data.clone())
+            {
+                export_asset(buffer);
+            }
+        }
+        sprite.bypass_change_detection().export = false;
+        let [columns, rows] = sprite.grid();
+        let [width, height] = sprite.size.map(|size| size.max(1));
+        let sheet = images.add(sheet);
+        let atlas = atlases.add(TextureAtlas::from_grid(
+            sheet.clone(),
+            Vec2::new(width as f32, height as f32),
+            columns as usize,
+            rows as usize,
+            None,
+            None,
+        ));
+        if let Some(mut image_handle) = image_handle {
+            *image_handle = sheet.clone();
+        }
+        if let Some(mut atlas_handle) = atlas_handle {
+            *atlas_handle = atlas.clone();
+        }
+        commands
+            .entity(entity)
+            .insert(PixelSprites { sheet, atlas });
+    }
+}
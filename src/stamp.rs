@@ -0,0 +1,560 @@
+//! Stamp landforms onto terrain
+//! # Example
+//! For configuration, see [`Stamps`](struct.Stamps.html) and [`Landform`](enum.Landform.html).
+//! Stamps are applied before roads and deformations, so roads run over them and craters cut into them.
+//! Artist-authored landforms are stamped from grayscale images with [`Landform::Heightmap`](enum.Landform.html#variant.Heightmap),
+//! custom roads, trenches and racetracks are carved along splines with [`Landform::Carve`](enum.Landform.html#variant.Carve)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::stamp::{CarveProfile, Landform, Stamp, StampBlend, Stamps};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(TerrainPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Stamps {
+//!             stamps: vec![
+//!                 Stamp {
+//!                     landform: Landform::Volcano {
+//!                         radius: 0.4,
+//!                         height: 0.5,
+//!                         caldera_radius: 0.08,
+//!                         caldera_depth: 0.1,
+//!                     },
+//!                     position: Vec2::new(-0.4, 0.3),
+//!                     ..default()
+//!                 },
+//!                 Stamp {
+//!                     landform: Landform::Canyon {
+//!                         points: vec![Vec2::new(-0.6, 0.0), Vec2::new(0.0, 0.2), Vec2::new(0.6, 0.0)],
+//!                         width: 0.2,
+//!                         floor_width: 0.05,
+//!                         depth: 0.2,
+//!                     },
+//!                     position: Vec2::new(0.2, -0.4),
+//!                     rotation: 30.0,
+//!                     ..default()
+//!                 },
+//!                 Stamp {
+//!                     landform: Landform::Heightmap {
+//!                         image: asset_server.load("stamps/mountain.png"),
+//!                         size: Vec2::splat(0.5),
+//!                         height: 0.4,
+//!                         blend: StampBlend::Max,
+//!                     },
+//!                     position: Vec2::new(0.5, 0.5),
+//!                     scale: 1.5,
+//!                     ..default()
+//!                 },
+//!                 Stamp {
+//!                     landform: Landform::Carve {
+//!                         points: vec![
+//!                             Vec3::new(-0.8, 0.0, -0.6),
+//!                             Vec3::new(-0.2, 0.05, -0.7),
+//!                             Vec3::new(0.6, 0.1, -0.5),
+//!                         ],
+//!                         width: 0.06,
+//!                         depth: 0.0,
+//!                         profile: CarveProfile::Flat,
+//!                         flatten: true,
+//!                     },
+//!                     falloff: 0.08,
+//!                     ..default()
+//!                 },
+//!             ],
+//!         },
+//!     ));
+//! }
+//! ```
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::road::spline;
+
+/// How a `Landform::Heightmap` is combined with the terrain
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StampBlend {
+    /// Adds the heightmap to the terrain
+    #[default]
+    Add,
+    /// Raises the terrain to the heightmap, measured from the height at the center of the stamp
+    Max,
+    /// Lowers the terrain to the heightmap, measured from the height at the center of the stamp
+    Min,
+}
+
+/// Cross-section of a `Landform::Carve`, from the spline to the edges of the carve
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CarveProfile {
+    /// Full depth across the width, the falloff of the stamp shapes the banks
+    #[default]
+    Flat,
+    /// Deepest along the spline, rising linearly to the edges
+    V,
+    /// Rounded channel, deepest along the spline
+    U,
+    /// Fraction of the depth at evenly spaced distances from the spline to the edges, linearly interpolated
+    Custom(Vec<f32>),
+}
+
+impl CarveProfile {
+    /// Fraction of the depth at `distance` from the spline, 0 at the spline and 1 at the edges
+    fn depth(&self, distance: f32) -> f32 {
+        let distance = distance.clamp(0.0, 1.0);
+        match self {
+            Self::Flat => 1.0,
+            Self::V => 1.0 - distance,
+            Self::U => (1.0 - distance * distance).sqrt(),
+            Self::Custom(samples) => match samples.len() {
+                0 => 1.0,
+                1 => samples[0],
+                count => {
+                    let x = distance * (count - 1) as f32;
+                    let i = (x as usize).min(count - 2);
+                    (samples[i + 1] - samples[i]).mul_add(x - i as f32, samples[i])
+                }
+            },
+        }
+    }
+}
+
+/// Shape of a `Stamp`, in world units
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum Landform {
+    /// Cone raised on top of the terrain, with a caldera at its summit
+    Volcano {
+        /// Radius of the base of the cone
+        radius: f32,
+        /// Height of the cone above the terrain
+        height: f32,
+        /// Radius of the caldera, 0 for a closed summit
+        caldera_radius: f32,
+        /// Depth of the caldera below the rim
+        caldera_depth: f32,
+    },
+    /// Ravine carved into the terrain along a Catmull-Rom spline
+    Canyon {
+        /// Control points of the spline, relative to the stamp
+        points: Vec<Vec2>,
+        /// Width between the rims
+        width: f32,
+        /// Width of the flat floor
+        floor_width: f32,
+        /// Depth below the terrain
+        depth: f32,
+    },
+    /// Channel carved along a Catmull-Rom spline, e.g. for roads, trenches and racetracks
+    Carve {
+        /// Control points of the spline, `x` and `z` relative to the stamp. With `flatten`,
+        /// `y` is the height of the bed above the height at the center of the stamp
+        points: Vec<Vec3>,
+        /// Width of the cross-section
+        width: f32,
+        /// Depth of the cross-section below the terrain, or below the bed with `flatten`
+        depth: f32,
+        /// Shape of the cross-section
+        profile: CarveProfile,
+        /// If true, the terrain is leveled to the bed along the spline, raising it where it is lower.
+        /// Otherwise the carve follows the terrain
+        flatten: bool,
+    },
+    /// Flat topped hill, the falloff of the stamp sets the steepness of its cliffs
+    Mesa {
+        /// Radius of the flat top
+        radius: f32,
+        /// Height of the top above the terrain at the center of the stamp
+        height: f32,
+    },
+    /// Grayscale image, e.g. an artist-authored mountain. Black is 0 and white is `height`.
+    /// The stamp is skipped until the image is loaded
+    Heightmap {
+        /// Grayscale image, the first channel is used
+        #[serde(skip)]
+        image: Handle<Image>,
+        /// Size of the image along `x` and `z`, the top row of the image faces `-z`
+        size: Vec2,
+        /// Height of white pixels
+        height: f32,
+        /// How the image is combined with the terrain
+        blend: StampBlend,
+    },
+}
+
+impl Default for Landform {
+    fn default() -> Self {
+        Self::Volcano {
+            radius: 0.3,
+            height: 0.4,
+            caldera_radius: 0.05,
+            caldera_depth: 0.08,
+        }
+    }
+}
+
+impl Landform {
+    /// Distance from the center of the stamp beyond which the landform has no effect, without falloff
+    fn reach(&self) -> f32 {
+        match self {
+            Self::Volcano { radius, .. } | Self::Mesa { radius, .. } => *radius,
+            Self::Heightmap { size, .. } => size.length() / 2.0,
+            Self::Canyon { points, width, .. } => {
+                points
+                    .iter()
+                    .map(|point| point.length())
+                    .fold(0.0, f32::max)
+                    + width / 2.0
+            }
+            Self::Carve { points, width, .. } => {
+                points
+                    .iter()
+                    .map(|point| point.xz().length())
+                    .fold(0.0, f32::max)
+                    + width / 2.0
+            }
+        }
+    }
+
+    /// Landform with every dimension multiplied by `scale`
+    fn scaled(&self, scale: f32) -> Self {
+        match self {
+            Self::Volcano {
+                radius,
+                height,
+                caldera_radius,
+                caldera_depth,
+            } => Self::Volcano {
+                radius: radius * scale,
+                height: height * scale,
+                caldera_radius: caldera_radius * scale,
+                caldera_depth: caldera_depth * scale,
+            },
+            Self::Canyon {
+                points,
+                width,
+                floor_width,
+                depth,
+            } => Self::Canyon {
+                points: points.iter().map(|point| *point * scale).collect(),
+                width: width * scale,
+                floor_width: floor_width * scale,
+                depth: depth * scale,
+            },
+            Self::Carve {
+                points,
+                width,
+                depth,
+                profile,
+                flatten,
+            } => Self::Carve {
+                points: points.iter().map(|point| *point * scale).collect(),
+                width: width * scale,
+                depth: depth * scale,
+                profile: profile.clone(),
+                flatten: *flatten,
+            },
+            Self::Mesa { radius, height } => Self::Mesa {
+                radius: radius * scale,
+                height: height * scale,
+            },
+            Self::Heightmap {
+                image,
+                size,
+                height,
+                blend,
+            } => Self::Heightmap {
+                image: image.clone(),
+                size: *size * scale,
+                height: height * scale,
+                blend: *blend,
+            },
+        }
+    }
+
+    /// Height of the landform at `local` relative to the stamp and its distance outside of the landform.
+    /// `existing` is the height of the terrain at `local` and `base` the height at the center of the stamp.
+    /// `samples` are the points of a canyon or carve spline and `image` the image of a heightmap
+    fn evaluate(
+        &self,
+        local: Vec2,
+        existing: f32,
+        base: f32,
+        samples: &[Vec3],
+        image: Option<&Image>,
+    ) -> (f32, f32) {
+        match self {
+            Self::Volcano {
+                radius,
+                height,
+                caldera_radius,
+                caldera_depth,
+            } => {
+                let radius = radius.max(f32::EPSILON);
+                let distance = local.length();
+                // Concave flanks, steepest near the summit
+                let cone = |distance: f32| height * (1.0 - distance / radius).max(0.0).powf(1.5);
+                let offset = if distance < *caldera_radius {
+                    let rim = cone(*caldera_radius);
+                    let t = (distance / caldera_radius).powi(4);
+                    rim - caldera_depth * (1.0 - t)
+                } else {
+                    cone(distance)
+                };
+                (existing + offset, distance - radius)
+            }
+            Self::Canyon {
+                width,
+                floor_width,
+                depth,
+                ..
+            } => {
+                let (distance, _) = closest(samples, local);
+                let [half_width, half_floor] = [width / 2.0, floor_width / 2.0];
+                let wall = if distance <= half_floor {
+                    1.0
+                } else {
+                    let x = (1.0
+                        - (distance - half_floor) / (half_width - half_floor).max(f32::EPSILON))
+                    .clamp(0.0, 1.0);
+                    x * x * 2.0f32.mul_add(-x, 3.0)
+                };
+                (existing - depth * wall, distance - half_width)
+            }
+            Self::Carve {
+                width,
+                depth,
+                profile,
+                flatten,
+                ..
+            } => {
+                let (distance, bed) = closest(samples, local);
+                let half_width = (width / 2.0).max(f32::EPSILON);
+                let carve = depth * profile.depth(distance / half_width);
+                let target = if *flatten {
+                    base + bed - carve
+                } else {
+                    existing - carve
+                };
+                (target, distance - half_width)
+            }
+            Self::Mesa { radius, height } => (existing.max(base + height), local.length() - radius),
+            Self::Heightmap {
+                size,
+                height,
+                blend,
+                ..
+            } => {
+                let size = size.max(Vec2::splat(f32::EPSILON));
+                let uv = local / size + 0.5;
+                let outside = ((uv - 0.5).abs() - 0.5).max(Vec2::ZERO) * size;
+                let value = image
+                    .map_or(0.0, |image| sample(image, uv.clamp(Vec2::ZERO, Vec2::ONE)))
+                    * height;
+                let target = match blend {
+                    StampBlend::Add => existing + value,
+                    StampBlend::Max => existing.max(base + value),
+                    StampBlend::Min => existing.min(base + value),
+                };
+                (target, outside.max_element())
+            }
+        }
+    }
+}
+
+/// Distance from `local` to the polyline through `samples` and the height of the polyline at the closest point
+fn closest(samples: &[Vec3], local: Vec2) -> (f32, f32) {
+    let nearest = |start: Vec3, end: Vec3| {
+        let direction = end.xz() - start.xz();
+        let t = ((local - start.xz()).dot(direction)
+            / direction.length_squared().max(f32::EPSILON))
+        .clamp(0.0, 1.0);
+        let point = start.lerp(end, t);
+        (local.distance(point.xz()), point.y)
+    };
+    samples
+        .windows(2)
+        .map(|segment| nearest(segment[0], segment[1]))
+        .fold((f32::MAX, 0.0), |a, b| if b.0 < a.0 { b } else { a })
+}
+
+/// Landform placed on the terrain
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Stamp {
+    /// Shape of the stamp
+    pub landform: Landform,
+    /// Center of the stamp as `x`, `z` relative to the terrain entity
+    pub position: Vec2,
+    /// Rotation of the stamp around `y` in degrees
+    pub rotation: f32,
+    /// Uniform scale of the landform
+    pub scale: f32,
+    /// Width of the transition between the landform and the terrain around it in world units
+    pub falloff: f32,
+}
+
+impl Default for Stamp {
+    fn default() -> Self {
+        Self {
+            landform: Landform::default(),
+            position: Vec2::ZERO,
+            rotation: 0.0,
+            scale: 1.0,
+            falloff: 0.05,
+        }
+    }
+}
+
+/// Component for landform stamps, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Default, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Stamps {
+    /// Stamps applied in order
+    pub stamps: Vec<Stamp>,
+}
+
+impl Stamps {
+    /// If true, a heightmap stamp samples `image`
+    pub(crate) fn uses_image(&self, image: AssetId<Image>) -> bool {
+        self.stamps.iter().any(|stamp| {
+            matches!(&stamp.landform, Landform::Heightmap { image: handle, .. } if handle.id() == image)
+        })
+    }
+
+    /// Applies every stamp to `heights`, `heights` are indexed by `[x][z]`
+    pub(crate) fn apply(
+        &self,
+        heights: &mut [Vec<f32>],
+        resolution: u32,
+        size: [u32; 2],
+        images: &Assets<Image>,
+    ) {
+        let resolution = resolution.max(1) as f32;
+        let half = Vec2::new(size[0] as f32, size[1] as f32) / 2.0;
+        let columns = heights.len();
+        let rows = heights.first().map_or(0, Vec::len);
+        if columns == 0 || rows == 0 {
+            return;
+        }
+        let index = |coordinate: f32, count: usize| {
+            (coordinate * resolution)
+                .round()
+                .clamp(0.0, (count - 1) as f32) as usize
+        };
+        for stamp in &self.stamps {
+            let landform = stamp.landform.scaled(stamp.scale);
+            let image = match &landform {
+                Landform::Heightmap { image, .. } => match images.get(image) {
+                    Some(image) => Some(image),
+                    None => continue,
+                },
+                _ => None,
+            };
+            let samples = match &landform {
+                Landform::Canyon { points, .. } => spline(
+                    &points
+                        .iter()
+                        .map(|point| Vec3::new(point.x, 0.0, point.y))
+                        .collect::<Vec<_>>(),
+                    1,
+                ),
+                Landform::Carve { points, .. } => spline(points, 1),
+                _ => vec![],
+            };
+            let base = heights[index(stamp.position.x + half.x, columns)]
+                [index(stamp.position.y + half.y, rows)];
+            let falloff = stamp.falloff.max(0.0);
+            let reach = landform.reach() + falloff;
+            let min = stamp.position - reach + half;
+            let max = stamp.position + reach + half;
+            let to_local = Mat2::from_angle(-stamp.rotation.to_radians());
+            for (i, column) in heights
+                .iter_mut()
+                .enumerate()
+                .take(index(max.x, columns) + 1)
+                .skip(index(min.x, columns))
+            {
+                for (j, height) in column
+                    .iter_mut()
+                    .enumerate()
+                    .take(index(max.y, rows) + 1)
+                    .skip(index(min.y, rows))
+                {
+                    let point = Vec2::new(i as f32, j as f32) / resolution - half;
+                    let local = to_local * (point - stamp.position);
+                    let (target, outside) =
+                        landform.evaluate(local, *height, base, &samples, image);
+                    let blend = if outside <= 0.0 {
+                        1.0
+                    } else if outside >= falloff {
+                        0.0
+                    } else {
+                        let x = 1.0 - outside / falloff;
+                        x * x * 2.0f32.mul_add(-x, 3.0)
+                    };
+                    *height += (target - *height) * blend;
+                }
+            }
+        }
+    }
+}
+
+/// Bilinearly sampled first channel of `image` at `uv` from 0 to 1
+pub(crate) fn sample(image: &Image, uv: Vec2) -> f32 {
+    let [width, height] = [
+        image.texture_descriptor.size.width as usize,
+        image.texture_descriptor.size.height as usize,
+    ];
+    let texel = |x: usize, y: usize| {
+        let index = y * width + x;
+        let bytes = &image.data;
+        match image.texture_descriptor.format {
+            TextureFormat::R8Unorm => bytes.get(index).map(|&value| f32::from(value) / 255.0),
+            TextureFormat::Rg8Unorm => bytes.get(index * 2).map(|&value| f32::from(value) / 255.0),
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+                bytes.get(index * 4).map(|&value| f32::from(value) / 255.0)
+            }
+            TextureFormat::R16Unorm => bytes
+                .get(index * 2..index * 2 + 2)
+                .map(|value| f32::from(u16::from_le_bytes([value[0], value[1]])) / 65535.0),
+            TextureFormat::Rgba16Unorm => bytes
+                .get(index * 8..index * 8 + 2)
+                .map(|value| f32::from(u16::from_le_bytes([value[0], value[1]])) / 65535.0),
+            TextureFormat::R32Float => bytes
+                .get(index * 4..index * 4 + 4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]])),
+            _ => None,
+        }
+        .unwrap_or(0.0)
+    };
+    if width == 0 || height == 0 {
+        return 0.0;
+    }
+    let x = uv.x * (width - 1) as f32;
+    let y = uv.y * (height - 1) as f32;
+    let (i, j) = (x as usize, y as usize);
+    let (tx, ty) = (x - i as f32, y - j as f32);
+    let [right, below] = [(i + 1).min(width - 1), (j + 1).min(height - 1)];
+    let top = (texel(right, j) - texel(i, j)).mul_add(tx, texel(i, j));
+    let bottom = (texel(right, below) - texel(i, below)).mul_add(tx, texel(i, below));
+    (bottom - top).mul_add(ty, top)
+}
@@ -0,0 +1,309 @@
+//! Generate starfield skyboxes
+//! # Example
+//! For configuration, see [`Starfield`](struct.Starfield.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::starfield::{Starfield, StarfieldPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(StarfieldPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(Camera3dBundle::default());
+//!     commands.spawn(Starfield::default());
+//! }
+//! ```
+use bevy::{
+    core_pipeline::Skybox,
+    prelude::*,
+    render::render_resource::{
+        Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    noise::{get_noise_at_point_3d, Function, Method},
+    util::Rng,
+};
+
+/// Noise layer rendered behind the stars
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Nebula {
+    /// Seed of the noise
+    pub seed: u32,
+    /// Scale of the noise
+    pub scale: f64,
+    /// Offset of the noise
+    pub offset: [f64; 3],
+    /// Method used to generate noise
+    pub method: Method,
+    /// Function used to generate noise
+    pub function: Function,
+    /// Color of the nebula
+    pub color: [u8; 4],
+    /// Percentage of noise below which the nebula is transparent
+    pub threshold: f64,
+    /// Brightness of the nebula
+    pub intensity: f64,
+}
+
+impl Default for Nebula {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: 80.0,
+            offset: [0.0; 3],
+            method: Method::Perlin,
+            function: Function::default(),
+            color: [90, 40, 140, 255],
+            threshold: 50.0,
+            intensity: 0.6,
+        }
+    }
+}
+
+/// Component for starfield configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Starfield {
+    /// Seed used to place the stars
+    pub seed: u32,
+    /// Size of each cubemap face in pixels
+    pub size: u32,
+    /// Number of stars across the whole sky
+    pub star_count: usize,
+    /// Minimum and maximum star color temperature in Kelvin
+    pub temperature: [f64; 2],
+    /// Exponent of the temperature distribution, 1 spreads the temperatures evenly.
+    /// Higher values result in more hot, blue stars, values below 1 in more cool, red stars
+    pub temperature_distribution: f64,
+    /// Minimum and maximum star brightness (0 to 1)
+    pub brightness: [f64; 2],
+    /// Background color of the sky
+    pub background: [u8; 4],
+    /// Nebula layers, blended additively behind the stars
+    pub nebulae: Vec<Nebula>,
+    /// If true, the generated cubemap is attached as `Skybox` to every 3D camera
+    pub skybox: bool,
+    /// Generated cubemap image
+    #[serde(skip)]
+    pub image: Handle<Image>,
+}
+
+impl Default for Starfield {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: 512,
+            star_count: 4000,
+            temperature: [2500.0, 12000.0],
+            temperature_distribution: 2.0,
+            brightness: [0.2, 1.0],
+            background: [2, 2, 8, 255],
+            nebulae: vec![Nebula::default()],
+            skybox: true,
+            image: Handle::default(),
+        }
+    }
+}
+
+/// Plugin to generate starfields
+pub struct StarfieldPlugin;
+
+impl Plugin for StarfieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (generate_starfield, attach_skybox).chain());
+    }
+}
+
+/// Direction of a pixel on a cubemap face, faces are ordered +X, -X, +Y, -Y, +Z, -Z
+fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    }
+    .normalize()
+}
+
+/// Inverse of `face_direction`, returns the face and coordinates in the range -1 to 1
+fn direction_face(direction: Vec3) -> (usize, f32, f32) {
+    let abs = direction.abs();
+    if abs.x >= abs.y && abs.x >= abs.z {
+        let d = direction / abs.x;
+        if direction.x > 0.0 {
+            (0, -d.z, -d.y)
+        } else {
+            (1, d.z, -d.y)
+        }
+    } else if abs.y >= abs.z {
+        let d = direction / abs.y;
+        if direction.y > 0.0 {
+            (2, d.x, d.z)
+        } else {
+            (3, d.x, -d.z)
+        }
+    } else {
+        let d = direction / abs.z;
+        if direction.z > 0.0 {
+            (4, d.x, -d.y)
+        } else {
+            (5, -d.x, -d.y)
+        }
+    }
+}
+
+/// Uniformly distributed direction on the unit sphere
+fn random_direction(rng: &mut Rng) -> Vec3 {
+    let z = rng.next_f64().mul_add(2.0, -1.0);
+    let phi = rng.next_f64() * std::f64::consts::TAU;
+    let r = z.mul_add(-z, 1.0).sqrt();
+    Vec3::new((r * phi.cos()) as f32, (r * phi.sin()) as f32, z as f32)
+}
+
+/// Approximate color of a black body at `temperature` Kelvin
+fn temperature_color(temperature: f64) -> [f64; 3] {
+    let t = temperature / 100.0;
+    let red = if t <= 66.0 {
+        1.0
+    } else {
+        329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2) / 255.0
+    };
+    let green = if t <= 66.0 {
+        99.470_802_586_1f64.mul_add(t.ln(), -161.119_568_166_1) / 255.0
+    } else {
+        288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2) / 255.0
+    };
+    let blue = if t >= 66.0 {
+        1.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_731_223_1f64.mul_add((t - 10.0).ln(), -305.044_792_730_7) / 255.0
+    };
+    [red, green, blue].map(|channel| channel.clamp(0.0, 1.0))
+}
+
+fn generate_starfield(
+    mut images: ResMut<Assets<Image>>,
+    mut query: Query<&mut Starfield, Changed<Starfield>>,
+) {
+    for mut starfield in &mut query {
+        let size = starfield.size.max(1);
+        let face_pixels = (size * size) as usize;
+        let background = starfield
+            .background
+            .map(|channel| f64::from(channel) / 255.0);
+        let mut pixels: Vec<[f64; 3]> = Vec::with_capacity(face_pixels * 6);
+
+        for face in 0..6 {
+            for y in 0..size {
+                for x in 0..size {
+                    let u = ((x as f32 + 0.5) / size as f32).mul_add(2.0, -1.0);
+                    let v = ((y as f32 + 0.5) / size as f32).mul_add(2.0, -1.0);
+                    let direction = face_direction(face, u, v);
+                    let point = [
+                        f64::from(direction.x),
+                        f64::from(direction.y),
+                        f64::from(direction.z),
+                    ];
+                    let mut color = [background[0], background[1], background[2]];
+                    for nebula in &starfield.nebulae {
+                        let value = (get_noise_at_point_3d(
+                            point,
+                            nebula.seed,
+                            nebula.scale / 100.0,
+                            nebula.offset,
+                            &nebula.method,
+                            &nebula.function,
+                        ) + 1.0)
+                            * 50.0;
+                        let threshold = nebula.threshold.clamp(0.0, 99.9);
+                        let strength = ((value - threshold) / (100.0 - threshold)).max(0.0)
+                            * nebula.intensity
+                            * f64::from(nebula.color[3])
+                            / 255.0;
+                        for (channel, nebula_channel) in color.iter_mut().zip(nebula.color) {
+                            *channel += f64::from(nebula_channel) / 255.0 * strength;
+                        }
+                    }
+                    pixels.push(color);
+                }
+            }
+        }
+
+        let mut rng = Rng::new(u64::from(starfield.seed));
+        for _ in 0..starfield.star_count {
+            let direction = random_direction(&mut rng);
+            let cooling = rng
+                .next_f64()
+                .powf(starfield.temperature_distribution.max(f64::EPSILON));
+            let temperature = (starfield.temperature[1] - starfield.temperature[0])
+                .mul_add(-cooling, starfield.temperature[1]);
+            let brightness = (starfield.brightness[1] - starfield.brightness[0])
+                .mul_add(rng.next_f64(), starfield.brightness[0]);
+
+            let (face, u, v) = direction_face(direction);
+            let column = ((f32::midpoint(u, 1.0) * size as f32) as u32).min(size - 1);
+            let row = ((f32::midpoint(v, 1.0) * size as f32) as u32).min(size - 1);
+            let pixel = &mut pixels[face * face_pixels + (row * size + column) as usize];
+            for (channel, star_channel) in pixel.iter_mut().zip(temperature_color(temperature)) {
+                *channel += star_channel * brightness;
+            }
+        }
+
+        let data: Vec<u8> = pixels
+            .into_iter()
+            .flat_map(|[r, g, b]| {
+                [r, g, b]
+                    .map(|channel| (channel.clamp(0.0, 1.0) * 255.0) as u8)
+                    .into_iter()
+                    .chain([255])
+            })
+            .collect();
+        let mut image = Image::new(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        image.texture_view_descriptor = Some(TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..default()
+        });
+        starfield.bypass_change_detection().image = images.add(image);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn attach_skybox(
+    mut commands: Commands,
+    starfields: Query<&Starfield>,
+    cameras: Query<(Entity, Option<&Skybox>), With<Camera3d>>,
+) {
+    let Some(starfield) = starfields.iter().find(|starfield| starfield.skybox) else {
+        return;
+    };
+    for (entity, skybox) in &cameras {
+        if skybox.is_none_or(|skybox| skybox.0 != starfield.image) {
+            commands
+                .entity(entity)
+                .insert(Skybox(starfield.image.clone()));
+        }
+    }
+}
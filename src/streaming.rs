@@ -0,0 +1,417 @@
+//! Stream terrain chunks around a focus
+//! # Example
+//! For configuration, see [`TerrainStreaming`](struct.TerrainStreaming.html).
+//! Chunks within `view_distance` of the `StreamingFocus` are shown, the ring of `pregenerate_distance`
+//! chunks beyond them is generated hidden ahead of time, so fast movement does not reveal chunks popping in late.
+//! Chunks leaving the ring are kept in a memory cache of `cache_budget` bytes and restored instantly when revisited,
+//! together with their mesh parts and edits such as deformations, painted splat maps, scatter edits and stamps
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::streaming::{StreamingFocus, StreamingPlugin, TerrainStreaming};
+//! use bevy_generative::terrain::TerrainPlugin;
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, StreamingPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn((
+//!         Camera3dBundle {
+//!             transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!             ..default()
+//!         },
+//!         StreamingFocus,
+//!     ));
+//!     commands.spawn((
+//!         TerrainStreaming::default(),
+//!         SpatialBundle::default(),
+//!     ));
+//! }
+//! ```
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::SpawnGeneratorExt,
+    deform::TerrainDeformation,
+    noise::Precision,
+    persist::{ChunkEdits, PersistentChunk},
+    scatter::ScatterEdits,
+    splat::SplatMap,
+    stamp::Stamps,
+    terrain::{
+        GeneratedTerrain, RestoredTerrain, Terrain, TerrainBundle, TerrainColors, TerrainMeshPart,
+    },
+    util::chunk,
+};
+
+/// Component marking the entity chunks are streamed around, e.g. the player or the camera
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct StreamingFocus;
+
+/// Component of every chunk spawned by a `TerrainStreaming`, a child of the streaming entity
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TerrainChunk {
+    /// Coordinates of the chunk along `x` and `z`, chunk `(0, 0)` is centered on the streaming entity
+    pub coord: IVec2,
+}
+
+/// Component streaming chunks of a terrain around the `StreamingFocus`
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct TerrainStreaming {
+    /// Configuration of every chunk, the world offset is moved to every chunk so neighbours line up.
+    /// Chunks are centered on their position, the anchor is ignored
+    pub chunk: Terrain,
+    /// Chunks shown in every direction around the chunk of the focus
+    pub view_distance: u32,
+    /// Width in chunks of the ring beyond `view_distance` generated hidden ahead of time
+    pub pregenerate_distance: u32,
+    /// Chunks spawned per frame, those in view first
+    pub chunks_per_frame: u32,
+    /// Preference for chunks ahead of the moving focus over those behind it, in chunks of distance.
+    /// At 0 the nearest chunks are spawned first
+    pub direction_weight: f32,
+    /// Chunks beyond the ring stay loaded until they are this many chunks further away,
+    /// so moving back and forth across a chunk border does not unload and restore them
+    pub unload_margin: u32,
+    /// Bytes of mesh and heightfield data of unloaded chunks kept for revisits,
+    /// the least recently unloaded chunks are dropped first. At 0 unloaded chunks are dropped right away
+    pub cache_budget: usize,
+}
+
+impl Default for TerrainStreaming {
+    fn default() -> Self {
+        Self {
+            chunk: Terrain::default(),
+            view_distance: 2,
+            pregenerate_distance: 1,
+            chunks_per_frame: 2,
+            direction_weight: 1.0,
+            unload_margin: 1,
+            cache_budget: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl TerrainStreaming {
+    /// Size of a chunk in world units along `x` and `z`
+    #[must_use]
+    pub fn chunk_size(&self) -> Vec2 {
+        chunk::chunk_size(&self.chunk)
+    }
+
+    /// Coordinates of the chunk containing `position`, relative to the streaming entity
+    #[must_use]
+    pub fn chunk_coord(&self, position: Vec2) -> IVec2 {
+        chunk::chunk_coord(&self.chunk, position)
+    }
+
+    /// Configuration of the chunk at `coord`, sampling the noise next to its neighbours
+    fn chunk_terrain(&self, coord: IVec2) -> Terrain {
+        chunk::chunk_terrain(&self.chunk, coord)
+    }
+}
+
+/// Generated data and edits of an unloaded chunk
+struct CachedChunk {
+    mesh: Handle<Mesh>,
+    /// Meshes of the child entities a mesh above `Terrain::max_vertices` is split into, by part index
+    parts: Vec<(usize, Handle<Mesh>)>,
+    material: Option<Handle<StandardMaterial>>,
+    data: GeneratedTerrain,
+    colors: Option<TerrainColors>,
+    /// Edits not saved yet are kept with the chunk, saved files are only read for chunks without them
+    edits: ChunkEdits,
+    stamps: Option<Stamps>,
+    bytes: usize,
+    /// Value of `StreamingState::unloads` when the chunk was unloaded
+    unloaded: u64,
+}
+
+/// Chunks spawned by a `TerrainStreaming`, the cache of unloaded chunks and the movement of its focus
+#[derive(Component, Default)]
+struct StreamingState {
+    chunks: HashMap<IVec2, Entity>,
+    cache: HashMap<IVec2, CachedChunk>,
+    unloads: u64,
+    last_focus: Option<Vec2>,
+    heading: Vec2,
+}
+
+impl StreamingState {
+    /// Drops the least recently unloaded chunks until the cache fits in `budget` bytes
+    fn evict(&mut self, budget: usize) {
+        let mut bytes: usize = self.cache.values().map(|chunk| chunk.bytes).sum();
+        while bytes > budget {
+            let Some(&oldest) = self
+                .cache
+                .iter()
+                .min_by_key(|(_, chunk)| chunk.unloaded)
+                .map(|(coord, _)| coord)
+            else {
+                break;
+            };
+            if let Some(chunk) = self.cache.remove(&oldest) {
+                bytes -= chunk.bytes;
+            }
+        }
+    }
+}
+
+/// Approximate memory of the heightfield, vertex colors and meshes of a chunk in bytes
+fn chunk_bytes<'a>(
+    data: &GeneratedTerrain,
+    colors: Option<&TerrainColors>,
+    meshes: impl Iterator<Item = &'a Mesh>,
+) -> usize {
+    let vertices: usize = data.heights.iter().map(Vec::len).sum();
+    let noise = match data.noise.precision() {
+        Precision::Single => 4,
+        Precision::Double => 8,
+    };
+    // Heights and base heights as f32, noise and region indices
+    let heightfield = vertices * (2 * 4 + noise + std::mem::size_of::<usize>());
+    let colors = colors.map_or(0, |colors| {
+        (colors.base.len() + colors.colors.len()) * std::mem::size_of::<[f32; 4]>()
+    });
+    let meshes: usize = meshes
+        .map(|mesh| {
+            mesh.attributes()
+                .map(|(_, values)| values.get_bytes().len())
+                .sum::<usize>()
+                + mesh.get_index_buffer_bytes().map_or(0, <[u8]>::len)
+        })
+        .sum();
+    heightfield + colors + meshes
+}
+
+/// Plugin to stream terrain chunks
+pub struct StreamingPlugin;
+
+impl Plugin for StreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, stream_chunks);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn stream_chunks(
+    mut commands: Commands,
+    focus: Query<&GlobalTransform, With<StreamingFocus>>,
+    mut query: Query<(
+        Entity,
+        Ref<TerrainStreaming>,
+        &GlobalTransform,
+        Option<&mut StreamingState>,
+    )>,
+    mut visibilities: Query<&mut Visibility, With<TerrainChunk>>,
+    generated: Query<
+        (
+            &Handle<Mesh>,
+            Option<&Handle<StandardMaterial>>,
+            &GeneratedTerrain,
+            Option<&TerrainColors>,
+            Option<&Children>,
+            (
+                Option<&TerrainDeformation>,
+                Option<&SplatMap>,
+                Option<&ScatterEdits>,
+                Option<&Stamps>,
+            ),
+        ),
+        With<TerrainChunk>,
+    >,
+    parts: Query<(&TerrainMeshPart, &Handle<Mesh>)>,
+    meshes: Option<Res<Assets<Mesh>>>,
+) {
+    let Some(focus) = focus.iter().next() else {
+        return;
+    };
+    for (entity, streaming, transform, state) in &mut query {
+        let mut inserted = StreamingState::default();
+        let is_new = state.is_none();
+        let state = state.map_or(&mut inserted, Mut::into_inner);
+        if streaming.is_changed() {
+            for (_, chunk) in state.chunks.drain() {
+                commands.entity(chunk).despawn_recursive();
+            }
+            state.cache.clear();
+        }
+
+        let local = transform
+            .affine()
+            .inverse()
+            .transform_point3(focus.translation());
+        let position = Vec2::new(local.x, local.z);
+        if let Some(last_focus) = state.last_focus {
+            let movement = position - last_focus;
+            if movement.length_squared() > f32::EPSILON {
+                state.heading = movement.normalize();
+            }
+        }
+        state.last_focus = Some(position);
+
+        let center = streaming.chunk_coord(position);
+        let view = streaming.view_distance as i32;
+        let reach = view + streaming.pregenerate_distance as i32;
+        let distance = |coord: IVec2| (coord - center).abs().max_element();
+
+        let unload = reach + streaming.unload_margin as i32;
+        let mut unloaded = Vec::new();
+        state.chunks.retain(|&coord, chunk| {
+            let keep = distance(coord) <= unload;
+            if !keep {
+                commands.entity(*chunk).despawn_recursive();
+                unloaded.push((coord, *chunk));
+            } else if let Ok(mut visibility) = visibilities.get_mut(*chunk) {
+                let shown = if distance(coord) <= view {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+                if *visibility != shown {
+                    *visibility = shown;
+                }
+            }
+            keep
+        });
+        if streaming.cache_budget > 0 {
+            for (coord, chunk) in unloaded {
+                let Ok((
+                    mesh,
+                    material,
+                    data,
+                    colors,
+                    children,
+                    (deformation, splat_map, scatter, stamps),
+                )) = generated.get(chunk)
+                else {
+                    continue;
+                };
+                let parts: Vec<(usize, Handle<Mesh>)> = children
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|&child| parts.get(child).ok())
+                    .map(|(part, mesh)| (part.index, mesh.clone()))
+                    .collect();
+                let bytes = chunk_bytes(
+                    data,
+                    colors,
+                    std::iter::once(mesh)
+                        .chain(parts.iter().map(|(_, mesh)| mesh))
+                        .filter_map(|mesh| meshes.as_ref().and_then(|meshes| meshes.get(mesh))),
+                );
+                state.unloads += 1;
+                let cached = CachedChunk {
+                    mesh: mesh.clone(),
+                    parts,
+                    material: material.cloned(),
+                    data: data.clone(),
+                    colors: colors.cloned(),
+                    edits: ChunkEdits {
+                        deformation: deformation.cloned(),
+                        splat_map: splat_map.cloned(),
+                        scatter: scatter.cloned(),
+                    },
+                    stamps: stamps.cloned(),
+                    bytes,
+                    unloaded: state.unloads,
+                };
+                state.cache.insert(coord, cached);
+            }
+            state.evict(streaming.cache_budget);
+        }
+
+        let chunk_size = streaming.chunk_size();
+        let priority = |coord: IVec2| {
+            let offset = coord.as_vec2() * chunk_size - position;
+            let along = offset.normalize_or_zero().dot(state.heading);
+            along.mul_add(-streaming.direction_weight, (offset / chunk_size).length())
+        };
+        let mut missing: Vec<(bool, f32, IVec2)> = (-reach..=reach)
+            .flat_map(|x| (-reach..=reach).map(move |z| center + IVec2::new(x, z)))
+            .filter(|coord| !state.chunks.contains_key(coord))
+            .map(|coord| (distance(coord) > view, priority(coord), coord))
+            .collect();
+        missing.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+        // Cached chunks are restored without counting towards the chunks spawned per frame
+        let mut spawned = 0;
+        for (hidden, _, coord) in missing {
+            let cached = state.cache.remove(&coord);
+            if cached.is_none() {
+                if spawned == streaming.chunks_per_frame {
+                    continue;
+                }
+                spawned += 1;
+            }
+            let translation = coord.as_vec2() * chunk_size;
+            let components = (
+                TerrainChunk { coord },
+                PersistentChunk {
+                    key: format!("{}_{}", coord.x, coord.y),
+                },
+                Transform::from_xyz(translation.x, 0.0, translation.y),
+                if hidden {
+                    Visibility::Hidden
+                } else {
+                    Visibility::Inherited
+                },
+            );
+            let terrain = streaming.chunk_terrain(coord);
+            let mut chunk = match cached {
+                Some(cached) => {
+                    let mut chunk = commands.spawn(TerrainBundle {
+                        terrain,
+                        pbr_bundle: PbrBundle {
+                            mesh: cached.mesh,
+                            ..default()
+                        },
+                    });
+                    chunk.insert((cached.data, RestoredTerrain));
+                    if let Some(colors) = cached.colors {
+                        chunk.insert(colors);
+                    }
+                    if let Some(material) = cached.material {
+                        chunk.insert(material);
+                    }
+                    if let Some(deformation) = cached.edits.deformation {
+                        chunk.insert(deformation);
+                    }
+                    if let Some(splat_map) = cached.edits.splat_map {
+                        chunk.insert(splat_map);
+                    }
+                    if let Some(scatter) = cached.edits.scatter {
+                        chunk.insert(scatter);
+                    }
+                    if let Some(stamps) = cached.stamps {
+                        chunk.insert(stamps);
+                    }
+                    chunk.with_children(|chunk| {
+                        for (index, mesh) in cached.parts {
+                            chunk.spawn((
+                                mesh,
+                                SpatialBundle::default(),
+                                TerrainMeshPart { index },
+                            ));
+                        }
+                    });
+                    chunk
+                }
+                None => commands.spawn_terrain(terrain),
+            };
+            let chunk = chunk.insert(components).set_parent(entity).id();
+            state.chunks.insert(coord, chunk);
+        }
+
+        if is_new {
+            commands.entity(entity).insert(inserted);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Place buildings and other structures on terrain
+//! # Example
+//! For configuration, see [`StructurePlacement`](struct.StructurePlacement.html).
+//! A structure is a child of the terrain entity. Once the terrain is generated, its footprint is levelled,
+//! scattered objects around it are cleared, a hole is cut if requested and the structure is moved onto the level
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::deform::DeformPlugin;
+//! use bevy_generative::structure::{PlaceStructureExt, StructurePlacement, StructurePlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, DeformPlugin, StructurePlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     let terrain = commands.spawn(TerrainBundle::default()).id();
+//!     commands.place_structure(
+//!         terrain,
+//!         StructurePlacement {
+//!             position: Vec2::new(0.1, -0.2),
+//!             rotation: 30.0,
+//!             footprint: Vec2::new(0.12, 0.08),
+//!             ..default()
+//!         },
+//!         PbrBundle {
+//!             mesh: meshes.add(shape::Box::new(0.12, 0.1, 0.08).into()),
+//!             ..default()
+//!         },
+//!     );
+//! }
+//! ```
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deform::{FlattenArea, TerrainDeformation, TerrainDeformed},
+    hole::TerrainHoles,
+    scatter::ScatterEdits,
+    terrain::GeneratedTerrain,
+};
+
+/// Height a structure levels its footprint to
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StructureLevel {
+    /// Mean height of the terrain under the footprint
+    #[default]
+    Mean,
+    /// Lowest height under the footprint, the terrain is cut down to it
+    Min,
+    /// Highest height under the footprint, the terrain is raised to it
+    Max,
+    /// Height relative to the terrain entity
+    Fixed(f32),
+}
+
+/// Component for the placement of a structure, added to a child of an entity with a `Terrain` component.
+/// Changing it places the structure again
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct StructurePlacement {
+    /// Center of the footprint as `x`, `z` relative to the terrain
+    pub position: Vec2,
+    /// Rotation around the `y` axis in degrees
+    pub rotation: f32,
+    /// Size of the rectangular footprint along the `x` and `z` axes of the structure
+    pub footprint: Vec2,
+    /// Height the footprint is levelled to
+    pub level: StructureLevel,
+    /// Height of the structure above the level, e.g. negative to sink its foundation into the ground
+    pub height_offset: f32,
+    /// Distance around the footprint in world units over which the level blends into the terrain
+    pub blend_radius: f32,
+    /// Distance around the footprint in world units cleared of scattered objects, `None` keeps them
+    pub scatter_margin: Option<f32>,
+    /// If true, the footprint is cut out of the terrain mesh, e.g. for a basement or a cave entrance
+    pub cut_hole: bool,
+}
+
+impl Default for StructurePlacement {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            rotation: 0.0,
+            footprint: Vec2::splat(0.1),
+            level: StructureLevel::default(),
+            height_offset: 0.0,
+            blend_radius: 0.05,
+            scatter_margin: Some(0.02),
+            cut_hole: false,
+        }
+    }
+}
+
+impl StructurePlacement {
+    /// Corners of the footprint grown by `margin` as `x`, `z` relative to the terrain
+    #[must_use]
+    pub fn corners(&self, margin: f32) -> Vec<Vec2> {
+        let rotation = self.rotation();
+        let half = self.footprint.abs() / 2.0 + margin;
+        [
+            Vec2::new(-half.x, -half.y),
+            Vec2::new(half.x, -half.y),
+            half,
+            Vec2::new(-half.x, half.y),
+        ]
+        .into_iter()
+        .map(|corner| self.position + (rotation * Vec3::new(corner.x, 0.0, corner.y)).xz())
+        .collect()
+    }
+
+    /// Height of the level on `terrain_data`, `None` if the footprint misses the terrain
+    #[must_use]
+    pub fn height(&self, terrain_data: &GeneratedTerrain) -> Option<f32> {
+        if let StructureLevel::Fixed(height) = self.level {
+            return Some(height);
+        }
+        let corners = self.corners(0.0);
+        let area = FlattenArea::Polygon(corners.clone());
+        let mut heights: Vec<f32> = corners
+            .iter()
+            .chain([&self.position])
+            .filter_map(|point| terrain_data.height(point.x, point.y))
+            .collect();
+        for (i, column) in terrain_data.heights.iter().enumerate() {
+            for j in 0..column.len() {
+                if let Some(vertex) = terrain_data.vertex_position(i, j) {
+                    if area.distance(vertex.xz()) <= 0.0 {
+                        heights.push(vertex.y);
+                    }
+                }
+            }
+        }
+        if heights.is_empty() {
+            return None;
+        }
+        Some(match self.level {
+            StructureLevel::Min => heights.iter().copied().fold(f32::INFINITY, f32::min),
+            StructureLevel::Max => heights.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+            _ => heights.iter().sum::<f32>() / heights.len() as f32,
+        })
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.rotation.to_radians())
+    }
+}
+
+/// Result of a placement, inserted on the entity with the `StructurePlacement` component
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct PlacedStructure {
+    /// Height of the levelled footprint relative to the terrain
+    pub height: f32,
+    /// Corners of the footprint as `x`, `z` relative to the terrain
+    pub footprint: Vec<Vec2>,
+    /// Area cleared of scattered objects
+    cleared: Option<FlattenArea>,
+    /// Polygon cut out of the terrain
+    hole: Option<Vec<Vec2>>,
+}
+
+/// Extension of `Commands` placing a structure on terrain in one call
+pub trait PlaceStructureExt<'w, 's> {
+    /// Spawns `bundle` with `placement` as a child of `terrain`.
+    /// The transform of the structure is set by the placement
+    fn place_structure<'a>(
+        &'a mut self,
+        terrain: Entity,
+        placement: StructurePlacement,
+        bundle: impl Bundle,
+    ) -> EntityCommands<'w, 's, 'a>;
+}
+
+impl<'w, 's> PlaceStructureExt<'w, 's> for Commands<'w, 's> {
+    fn place_structure<'a>(
+        &'a mut self,
+        terrain: Entity,
+        placement: StructurePlacement,
+        bundle: impl Bundle,
+    ) -> EntityCommands<'w, 's, 'a> {
+        let mut entity = self.spawn((bundle, placement));
+        entity.set_parent(terrain);
+        entity
+    }
+}
+
+/// Plugin to place structures on terrain
+pub struct StructurePlugin;
+
+impl Plugin for StructurePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TerrainDeformed>()
+            .add_systems(Update, place_structures);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn place_structures(
+    mut commands: Commands,
+    mut deformed: EventWriter<TerrainDeformed>,
+    mut structures: Query<(
+        Entity,
+        Ref<StructurePlacement>,
+        &Parent,
+        Option<&mut Transform>,
+        Option<&PlacedStructure>,
+    )>,
+    mut terrains: Query<(
+        &GeneratedTerrain,
+        Option<&mut TerrainDeformation>,
+        Option<&mut ScatterEdits>,
+        Option<&mut TerrainHoles>,
+    )>,
+) {
+    // One structure per terrain and frame, so overlapping footprints level the regenerated surface
+    let mut levelled = vec![];
+    for (entity, placement, parent, transform, placed) in &mut structures {
+        if placed.is_some() && !placement.is_changed() {
+            continue;
+        }
+        let terrain = parent.get();
+        if levelled.contains(&terrain) {
+            continue;
+        }
+        let Ok((terrain_data, deformation, edits, holes)) = terrains.get_mut(terrain) else {
+            continue;
+        };
+        let Some(height) = placement.height(terrain_data) else {
+            continue;
+        };
+        levelled.push(terrain);
+        let footprint = placement.corners(0.0);
+
+        let mut new = deformation.as_deref().cloned().unwrap_or_default();
+        if let Some([min, max]) = new.flatten_region(
+            terrain_data,
+            &FlattenArea::Polygon(footprint.clone()),
+            height,
+            placement.blend_radius,
+        ) {
+            deformed.send(TerrainDeformed { terrain, min, max });
+            match deformation {
+                Some(mut deformation) => *deformation = new,
+                None => {
+                    commands.entity(terrain).insert(new);
+                }
+            }
+        }
+
+        let cleared = placement
+            .scatter_margin
+            .map(|margin| FlattenArea::Polygon(placement.corners(margin.max(0.0))));
+        let previous_cleared = placed.and_then(|placed| placed.cleared.as_ref());
+        if cleared.as_ref() != previous_cleared {
+            let mut new = edits.as_deref().cloned().unwrap_or_default();
+            if let Some(previous) = previous_cleared {
+                if let Some(index) = new.cleared.iter().position(|area| area == previous) {
+                    new.cleared.remove(index);
+                }
+            }
+            if let Some(area) = cleared.clone() {
+                new.clear(area);
+            }
+            match edits {
+                Some(mut edits) => *edits = new,
+                None => {
+                    commands.entity(terrain).insert(new);
+                }
+            }
+        }
+
+        let hole = placement.cut_hole.then(|| footprint.clone());
+        let previous_hole = placed.and_then(|placed| placed.hole.as_ref());
+        if hole.as_ref() != previous_hole {
+            let mut new = holes.as_deref().cloned().unwrap_or_default();
+            if let Some(previous) = previous_hole {
+                if let Some(index) = new.polygons.iter().position(|polygon| polygon == previous) {
+                    new.polygons.remove(index);
+                }
+            }
+            new.polygons.extend(hole.clone());
+            match holes {
+                Some(mut holes) => *holes = new,
+                None => {
+                    commands.entity(terrain).insert(new);
+                }
+            }
+        }
+
+        let translation = Vec3::new(
+            placement.position.x,
+            height + placement.height_offset,
+            placement.position.y,
+        );
+        match transform {
+            Some(mut transform) => {
+                transform.translation = translation;
+                transform.rotation = placement.rotation();
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(SpatialBundle::from_transform(
+                        Transform::from_translation(translation)
+                            .with_rotation(placement.rotation()),
+                    ));
+            }
+        }
+        commands.entity(entity).insert(PlacedStructure {
+            height,
+            footprint,
+            cleared,
+            hole,
+        });
+    }
+}
@@ -0,0 +1,284 @@
+//! Score how suitable terrain is for settling
+//! # Example
+//! For configuration, see [`Suitability`](struct.Suitability.html).
+//! Settlements and points of interest prefer suitable sites when the terrain has a [`SuitabilityMap`](struct.SuitabilityMap.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::suitability::{Suitability, SuitabilityMap, SuitabilityPlugin};
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, SuitabilityPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_center)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Suitability::default()));
+//! }
+//!
+//! fn print_center(query: Query<&SuitabilityMap, Changed<SuitabilityMap>>) {
+//!     for suitability in &query {
+//!         println!("{:?}", suitability.score_at(0.0, 0.0));
+//!     }
+//! }
+//! ```
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::{Biome, BiomeMap},
+    distance::DistanceField,
+    flow::FlowData,
+    terrain::GeneratedTerrain,
+};
+
+/// Component for suitability configuration, added to an entity with a `Terrain` component.
+/// Biomes are weighted if the entity also has a `BiomeMap`
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Suitability {
+    /// Weight of flat ground
+    pub flatness: f32,
+    /// Weight of nearby fresh water
+    pub water: f32,
+    /// Weight of a nearby coast
+    pub coast: f32,
+    /// Slope in degrees at which ground no longer counts as flat
+    pub max_slope: f32,
+    /// Number of vertices that must drain through a vertex for it to carry a river
+    pub river_accumulation: f32,
+    /// Distance in world units at which fresh water no longer adds to the score
+    pub water_distance: f32,
+    /// Distance in world units at which the coast no longer adds to the score
+    pub coast_distance: f32,
+    /// Score multiplier of every biome, biomes without a weight are not scaled
+    pub biomes: HashMap<Biome, f32>,
+    /// If true, the scores are also written to `SuitabilityMap::texture`
+    pub texture: bool,
+}
+
+impl Default for Suitability {
+    fn default() -> Self {
+        Self {
+            flatness: 1.0,
+            water: 1.0,
+            coast: 0.5,
+            max_slope: 20.0,
+            river_accumulation: 30.0,
+            water_distance: 0.3,
+            coast_distance: 0.3,
+            biomes: HashMap::from([
+                (Biome::Tundra, 0.2),
+                (Biome::BorealForest, 0.6),
+                (Biome::TemperateGrassland, 1.0),
+                (Biome::Woodland, 0.9),
+                (Biome::TemperateSeasonalForest, 1.0),
+                (Biome::TemperateRainforest, 0.7),
+                (Biome::SubtropicalDesert, 0.3),
+                (Biome::TropicalSeasonalForest, 0.8),
+                (Biome::TropicalRainforest, 0.6),
+            ]),
+            texture: false,
+        }
+    }
+}
+
+/// Suitability of every terrain vertex, inserted on the entity with the `Suitability` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct SuitabilityMap {
+    /// Score of every vertex from 0 to 1, 0 in the sea. Indexed like `GeneratedTerrain::heights`
+    pub scores: Vec<Vec<f32>>,
+    /// Texture of the scores if `Suitability::texture` is set, red is unsuitable and green is suitable
+    pub texture: Option<Handle<Image>>,
+    /// Number of vertices per world unit
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl SuitabilityMap {
+    /// Score at `x`, `z`, bilinearly interpolated between vertices. `None` outside of the terrain
+    #[must_use]
+    pub fn score_at(&self, x: f32, z: f32) -> Option<f32> {
+        let resolution = self.resolution as f32;
+        let column = (x + self.size[0] as f32 / 2.0) * resolution;
+        let row = (z + self.size[1] as f32 / 2.0) * resolution;
+        let columns = self.scores.len().checked_sub(1)?;
+        let rows = self.scores.first()?.len().checked_sub(1)?;
+        if !(0.0..=columns as f32).contains(&column) || !(0.0..=rows as f32).contains(&row) {
+            return None;
+        }
+        let i = (column as usize).min(columns.saturating_sub(1));
+        let j = (row as usize).min(rows.saturating_sub(1));
+        let (tx, tz) = (column - i as f32, row - j as f32);
+        let value = |di: usize, dj: usize| self.scores[(i + di).min(columns)][(j + dj).min(rows)];
+        let near = (value(1, 0) - value(0, 0)).mul_add(tx, value(0, 0));
+        let far = (value(1, 1) - value(0, 1)).mul_add(tx, value(0, 1));
+        Some((far - near).mul_add(tz, near))
+    }
+
+    /// Texture of the scores, one pixel per vertex. Red is unsuitable and green is suitable
+    #[must_use]
+    pub fn to_image(&self) -> Image {
+        let width = self.scores.len() as u32;
+        let height = self.scores.first().map_or(0, Vec::len) as u32;
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for j in 0..height as usize {
+            for column in &self.scores {
+                let score = column[j].clamp(0.0, 1.0);
+                data.extend([
+                    ((1.0 - score) * 255.0).round() as u8,
+                    (score * 255.0).round() as u8,
+                    0,
+                    255,
+                ]);
+            }
+        }
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+}
+
+impl Suitability {
+    /// Scores every vertex of the terrain, weighting biomes by `biomes` if given
+    #[must_use]
+    pub fn score(
+        &self,
+        terrain_data: &GeneratedTerrain,
+        biomes: Option<&BiomeMap>,
+    ) -> Vec<Vec<f32>> {
+        let heights = &terrain_data.base_heights;
+        let cell_size = 1.0 / terrain_data.resolution.max(1) as f32;
+        let sea: Vec<Vec<bool>> = heights
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|&height| height <= terrain_data.sea_level + f32::EPSILON)
+                    .collect()
+            })
+            .collect();
+        let flow = FlowData::from_heights(heights, &sea, true);
+        let rivers: Vec<Vec<bool>> = flow
+            .accumulation
+            .iter()
+            .zip(&sea)
+            .map(|(column, sea)| {
+                column
+                    .iter()
+                    .zip(sea)
+                    .map(|(&accumulation, &sea)| !sea && accumulation >= self.river_accumulation)
+                    .collect()
+            })
+            .collect();
+        let sea_distance = DistanceField::from_mask(&sea, cell_size);
+        let river_distance = DistanceField::from_mask(&rivers, cell_size);
+        let max_slope = self.max_slope.to_radians().max(f32::EPSILON);
+        let total = (self.flatness + self.water + self.coast).max(f32::EPSILON);
+        let falloff =
+            |distance: f32, range: f32| 1.0 - (distance / range.max(f32::EPSILON)).clamp(0.0, 1.0);
+        let [width, depth] = terrain_data.size.map(|side| side as f32 / 2.0);
+
+        sea.iter()
+            .enumerate()
+            .map(|(i, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .map(|(j, &sea)| {
+                        if sea {
+                            return 0.0;
+                        }
+                        let (x, z) = (
+                            (i as f32).mul_add(cell_size, -width),
+                            (j as f32).mul_add(cell_size, -depth),
+                        );
+                        let slope = terrain_data.slope(x, z).unwrap_or(max_slope);
+                        let flatness = 1.0 - (slope / max_slope).clamp(0.0, 1.0);
+                        let water = falloff(
+                            river_distance.get(i, j).unwrap_or(f32::INFINITY).max(0.0),
+                            self.water_distance,
+                        );
+                        let coast = falloff(
+                            sea_distance.get(i, j).unwrap_or(f32::INFINITY),
+                            self.coast_distance,
+                        );
+                        let biome = biomes
+                            .and_then(|biomes| biomes.cells.get(i)?.get(j))
+                            .and_then(|biome| self.biomes.get(biome))
+                            .copied()
+                            .unwrap_or(1.0);
+                        let score = self
+                            .coast
+                            .mul_add(coast, self.flatness.mul_add(flatness, self.water * water))
+                            / total;
+                        (score * biome).clamp(0.0, 1.0)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Plugin to score suitability
+pub struct SuitabilityPlugin;
+
+impl Plugin for SuitabilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, score_suitability);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn score_suitability(
+    mut commands: Commands,
+    mut images: Option<ResMut<Assets<Image>>>,
+    query: Query<
+        (Entity, &Suitability, &GeneratedTerrain, Option<&BiomeMap>),
+        Or<(
+            Changed<Suitability>,
+            Changed<GeneratedTerrain>,
+            Changed<BiomeMap>,
+        )>,
+    >,
+) {
+    for (entity, config, terrain_data, biomes) in &query {
+        let mut map = SuitabilityMap {
+            scores: config.score(terrain_data, biomes),
+            texture: None,
+            resolution: terrain_data.resolution.max(1),
+            size: terrain_data.size,
+        };
+        // Without the render assets only the scores are computed
+        if let Some(images) = images.as_deref_mut().filter(|_| config.texture) {
+            map.texture = Some(images.add(map.to_image()));
+        }
+        commands.entity(entity).insert(map);
+    }
+}
@@ -0,0 +1,356 @@
+//! Synthesize larger tileable textures from small example images
+//! # Example
+//! For configuration, see [`TextureSynthesis`](struct.TextureSynthesis.html).
+//! Patches of the exemplar are quilted together along the cuts where they differ least, wrapping around the edges.
+//! The `SynthesizedTexture` is inserted once the exemplar is loaded
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::synthesis::{SynthesizedTexture, SynthesisPlugin, TextureSynthesis};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(SynthesisPlugin)
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, report)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+//!     commands.spawn(TextureSynthesis {
+//!         exemplar: asset_server.load("pebbles.png"),
+//!         size: [512, 512],
+//!         ..default()
+//!     });
+//! }
+//!
+//! fn report(query: Query<&SynthesizedTexture, Added<SynthesizedTexture>>) {
+//!     for texture in &query {
+//!         println!("Synthesized {:?}", texture.image);
+//!     }
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::util::Rng;
+
+/// Component for texture synthesis configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct TextureSynthesis {
+    /// Example image the texture is made of
+    #[serde(skip)]
+    pub exemplar: Handle<Image>,
+    /// Size of the synthesized texture in pixels, rounded up to whole patches
+    pub size: [u32; 2],
+    /// Width and height of the patches copied from the exemplar, at most the size of the exemplar
+    pub patch_size: u32,
+    /// Width of the overlap between neighbouring patches, where they are cut
+    pub overlap: u32,
+    /// Random positions in the exemplar compared for every patch
+    pub candidates: u32,
+    /// Patches within this fraction of the best match are picked at random, for variety
+    pub tolerance: f32,
+    /// Seed of the candidate positions
+    pub seed: u32,
+}
+
+impl Default for TextureSynthesis {
+    fn default() -> Self {
+        Self {
+            exemplar: Handle::default(),
+            size: [256, 256],
+            patch_size: 32,
+            overlap: 8,
+            candidates: 200,
+            tolerance: 0.1,
+            seed: 0,
+        }
+    }
+}
+
+/// Pixels of an RGBA image with 8 bits per channel
+struct Pixels<'a> {
+    data: &'a [u8],
+    width: usize,
+}
+
+impl Pixels<'_> {
+    fn get(&self, x: usize, y: usize) -> &[u8] {
+        let start = (y * self.width + x) * 4;
+        &self.data[start..start + 4]
+    }
+}
+
+impl TextureSynthesis {
+    /// Tileable texture quilted from patches of `exemplar`, in the format of the exemplar if it has 8 bit RGBA channels.
+    /// `None` if the exemplar is empty or cannot be converted
+    #[must_use]
+    pub fn synthesize(&self, exemplar: &Image) -> Option<Image> {
+        let format = exemplar.texture_descriptor.format;
+        let converted;
+        let (data, format) = if matches!(
+            format,
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm
+        ) {
+            (&exemplar.data, format)
+        } else {
+            converted = exemplar.convert(TextureFormat::Rgba8UnormSrgb)?;
+            (&converted.data, TextureFormat::Rgba8UnormSrgb)
+        };
+        let (width, height) = (exemplar.width() as usize, exemplar.height() as usize);
+        if width == 0 || height == 0 || data.len() < width * height * 4 {
+            return None;
+        }
+        let source = Pixels { data, width };
+
+        let patch = (self.patch_size as usize).clamp(2, width.min(height));
+        let overlap = (self.overlap as usize).min(patch / 2);
+        let step = patch - overlap;
+        let columns = (self.size[0] as usize).div_ceil(step).max(1);
+        let rows = (self.size[1] as usize).div_ceil(step).max(1);
+        let (out_width, out_height) = (columns * step, rows * step);
+        let mut output = vec![0; out_width * out_height * 4];
+        let mut filled = vec![false; out_width * out_height];
+        let mut rng = Rng::new(u64::from(self.seed));
+
+        for row in 0..rows {
+            for column in 0..columns {
+                // Pixels of the patch wrap around the output, so the last patches meet the first ones
+                let target = |x: usize, y: usize| {
+                    ((row * step + y) % out_height) * out_width + (column * step + x) % out_width
+                };
+                let cost = |sx: usize, sy: usize| {
+                    let mut cost = 0_u64;
+                    for y in 0..patch {
+                        for x in 0..patch {
+                            let index = target(x, y);
+                            if filled[index] {
+                                cost += squared_difference(
+                                    source.get(sx + x, sy + y),
+                                    &output[index * 4..index * 4 + 4],
+                                );
+                            }
+                        }
+                    }
+                    cost
+                };
+                let candidates: Vec<(usize, usize)> = (0..self.candidates.max(1))
+                    .map(|_| {
+                        (
+                            rng.range(0, (width - patch) as u32) as usize,
+                            rng.range(0, (height - patch) as u32) as usize,
+                        )
+                    })
+                    .collect();
+                let costs: Vec<u64> = candidates.iter().map(|&(x, y)| cost(x, y)).collect();
+                let best = costs.iter().copied().min().unwrap_or_default();
+                let limit = best as f64 * (1.0 + f64::from(self.tolerance.max(0.0)));
+                let good: Vec<usize> = (0..candidates.len())
+                    .filter(|&index| costs[index] as f64 <= limit)
+                    .collect();
+                let (sx, sy) = candidates[good[rng.range(0, good.len() as u32 - 1) as usize]];
+
+                let errors: Vec<Vec<u64>> = (0..patch)
+                    .map(|y| {
+                        (0..patch)
+                            .map(|x| {
+                                let index = target(x, y);
+                                if filled[index] {
+                                    squared_difference(
+                                        source.get(sx + x, sy + y),
+                                        &output[index * 4..index * 4 + 4],
+                                    )
+                                } else {
+                                    0
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect();
+                let band_filled = |xs: std::ops::Range<usize>, mut ys: std::ops::Range<usize>| {
+                    ys.any(|y| xs.clone().any(|x| filled[target(x, y)]))
+                };
+                let keep_new = patch_mask(
+                    &errors,
+                    overlap,
+                    [
+                        band_filled(0..overlap, 0..patch),
+                        band_filled(patch - overlap..patch, 0..patch),
+                        band_filled(0..patch, 0..overlap),
+                        band_filled(0..patch, patch - overlap..patch),
+                    ],
+                );
+                for (y, keep_row) in keep_new.iter().enumerate() {
+                    for (x, &keep) in keep_row.iter().enumerate() {
+                        let index = target(x, y);
+                        if !filled[index] || keep {
+                            output[index * 4..index * 4 + 4]
+                                .copy_from_slice(source.get(sx + x, sy + y));
+                            filled[index] = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut image = Image::new(
+            Extent3d {
+                width: out_width as u32,
+                height: out_height as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            output,
+            format,
+        );
+        image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: ImageAddressMode::Repeat,
+            address_mode_v: ImageAddressMode::Repeat,
+            ..ImageSamplerDescriptor::linear()
+        });
+        Some(image)
+    }
+}
+
+fn squared_difference(a: &[u8], b: &[u8]) -> u64 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let difference = i64::from(*a) - i64::from(*b);
+            (difference * difference) as u64
+        })
+        .sum()
+}
+
+/// Whether every pixel of a patch replaces the output, from the minimum error cuts through the bands
+/// of the left, right, top and bottom sides that overlap filled pixels. Indexed by `[y][x]`
+fn patch_mask(errors: &[Vec<u64>], overlap: usize, sides: [bool; 4]) -> Vec<Vec<bool>> {
+    let patch = errors.len();
+    let mut mask = vec![vec![true; patch]; patch];
+    if overlap == 0 {
+        return mask;
+    }
+    let [left, right, top, bottom] = sides;
+    if left || right {
+        for (band, start) in [(left, 0), (right, patch - overlap)] {
+            if !band {
+                continue;
+            }
+            let cut = min_cut(errors, start, overlap);
+            for (row, &x_cut) in mask.iter_mut().zip(&cut) {
+                for (x, keep) in row.iter_mut().enumerate() {
+                    // The new patch lies right of the left cut and left of the right cut
+                    if (start == 0 && x < x_cut) || (start != 0 && x > x_cut) {
+                        *keep = false;
+                    }
+                }
+            }
+        }
+    }
+    if top || bottom {
+        let transposed: Vec<Vec<u64>> = (0..patch)
+            .map(|x| (0..patch).map(|y| errors[y][x]).collect())
+            .collect();
+        for (band, start) in [(top, 0), (bottom, patch - overlap)] {
+            if !band {
+                continue;
+            }
+            let cut = min_cut(&transposed, start, overlap);
+            for (x, &y_cut) in cut.iter().enumerate() {
+                for (y, row) in mask.iter_mut().enumerate() {
+                    if (start == 0 && y < y_cut) || (start != 0 && y > y_cut) {
+                        row[x] = false;
+                    }
+                }
+            }
+        }
+    }
+    mask
+}
+
+/// Column of the cheapest path from the first to the last row of `errors` within `width` columns from `start`,
+/// moving at most one column per row
+fn min_cut(errors: &[Vec<u64>], start: usize, width: usize) -> Vec<usize> {
+    let rows = errors.len();
+    let mut totals = vec![vec![0_u64; width]; rows];
+    for y in 0..rows {
+        for x in 0..width {
+            let previous = if y == 0 {
+                0
+            } else {
+                let from = x.saturating_sub(1);
+                let to = (x + 1).min(width - 1);
+                (from..=to)
+                    .map(|x| totals[y - 1][x])
+                    .min()
+                    .unwrap_or_default()
+            };
+            totals[y][x] = errors[y][start + x] + previous;
+        }
+    }
+    let mut cut = vec![0; rows];
+    let mut x = (0..width)
+        .min_by_key(|&x| totals[rows - 1][x])
+        .unwrap_or_default();
+    for y in (0..rows).rev() {
+        cut[y] = start + x;
+        if y > 0 {
+            let from = x.saturating_sub(1);
+            let to = (x + 1).min(width - 1);
+            x = (from..=to).min_by_key(|&x| totals[y - 1][x]).unwrap_or(x);
+        }
+    }
+    cut
+}
+
+/// Texture synthesized from a `TextureSynthesis`, inserted on the entity with the `TextureSynthesis` component
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug)]
+pub struct SynthesizedTexture {
+    /// Tileable image with a repeating sampler
+    pub image: Handle<Image>,
+}
+
+/// Plugin to synthesize textures
+pub struct SynthesisPlugin;
+
+impl Plugin for SynthesisPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, synthesize_textures);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn synthesize_textures(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    query: Query<(Entity, Ref<TextureSynthesis>, Option<&SynthesizedTexture>)>,
+) {
+    for (entity, synthesis, synthesized) in &query {
+        // Waits for the exemplar to load before the first synthesis
+        if !synthesis.is_changed() && synthesized.is_some() {
+            continue;
+        }
+        let Some(exemplar) = images.get(&synthesis.exemplar) else {
+            continue;
+        };
+        let Some(image) = synthesis.synthesize(exemplar) else {
+            warn!(
+                "Could not synthesize a texture from {:?}",
+                synthesis.exemplar
+            );
+            continue;
+        };
+        commands.entity(entity).insert(SynthesizedTexture {
+            image: images.add(image),
+        });
+    }
+}
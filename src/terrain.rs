@@ -26,17 +26,214 @@
 //! }
 //! ```
 use bevy::{
+    ecs::{event::ManualEventReader, system::SystemParam},
+    math::DVec2,
     prelude::*,
-    render::render_resource::{PrimitiveTopology, TextureFormat},
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::{Extent3d, PrimitiveTopology, TextureDimension, TextureFormat},
+    },
+    utils::HashSet,
+};
+use bevy_generative_core::{
+    heightmap,
+    mesh::{GridMesh, MergeCriteria},
 };
 use image::Pixel;
 use serde::{Deserialize, Serialize};
 
-use crate::{noise::generate_noise_map, noise::Noise, util::export_model};
+use crate::{
+    beach::{cover_beaches, Beach},
+    biome::BiomeMap,
+    cell::{TerrainCell, TerrainCells},
+    climate::ClimateData,
+    deform::TerrainDeformation,
+    error::{fail, ConfigError, GenerationError, GenerationFailure},
+    flow::FlowData,
+    fog::{darken_fog, FogOfWar},
+    headless::DataOnly,
+    heatmap::{show_debug_views, DebugView},
+    hole::TerrainHoles,
+    lake::{LakeMap, Lakes},
+    layer::HeightLayers,
+    modifier::{Dunes, Modifier, Tectonics},
+    noise::{
+        build_gradient, generate_detail_map, generate_noise_map_at, regions, Function,
+        FunctionName, Gradient, Noise, NoiseMap, NoiseValue,
+    },
+    palette::{blend_palettes, BiomePalettes},
+    progress::{GenerationProgress, STAGE_NOISE},
+    road::RoadNetwork,
+    snow::{blend_snow, Snow, SnowCover},
+    splat::{blend_splat_maps, SplatMap},
+    stamp::Stamps,
+    territory::{blend_territories, Territory},
+    util::export_model,
+};
+
+/// Texture coordinates of the terrain mesh
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UvMode {
+    /// Grid index of every vertex as `x`, `z`
+    #[default]
+    Grid,
+    /// 0 to 1 across the terrain
+    Normalized,
+    /// Position in world units divided by the size of a tile, for tiling textures
+    World {
+        /// World units covered by one repetition of the texture
+        tile_size: f32,
+    },
+    /// Like `World`, with the tile size multiplied by the scale of the region of every vertex
+    Regions {
+        /// World units covered by one repetition of the texture before scaling
+        tile_size: f32,
+        /// Scale of the tile size per region, indexed like `Noise::regions`. Missing regions use 1
+        scales: Vec<f32>,
+    },
+}
+
+impl UvMode {
+    /// Texture coordinates of every vertex of `grid`, whose uvs still hold the grid indices
+    fn uvs(&self, grid: &GridMesh, regions: &[Vec<usize>]) -> Vec<[f32; 2]> {
+        let last = grid.uvs.last().copied().unwrap_or_default();
+        let world = |position: &[f32; 3], tile_size: f32| {
+            let tile_size = if tile_size > 0.0 { tile_size } else { 1.0 };
+            [position[0] / tile_size, position[2] / tile_size]
+        };
+        match self {
+            Self::Grid => grid.uvs.clone(),
+            Self::Normalized => grid
+                .uvs
+                .iter()
+                .map(|[x, z]| [x / last[0].max(1.0), z / last[1].max(1.0)])
+                .collect(),
+            Self::World { tile_size } => grid
+                .positions
+                .iter()
+                .map(|position| world(position, *tile_size))
+                .collect(),
+            Self::Regions { tile_size, scales } => grid
+                .positions
+                .iter()
+                .zip(&grid.uvs)
+                .map(|(position, [x, z])| {
+                    let region = regions
+                        .get(*x as usize)
+                        .and_then(|column| column.get(*z as usize));
+                    let scale = region
+                        .and_then(|region| scales.get(*region))
+                        .copied()
+                        .unwrap_or(1.0);
+                    world(position, tile_size * scale)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Point of the terrain mesh placed at the origin of the entity
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Anchor {
+    /// Center of the terrain
+    #[default]
+    Center,
+    /// Corner with the lowest `x` and `z`, the terrain extends along `+x` and `+z`
+    Corner,
+    /// Center of the terrain moved by an offset in world units
+    Offset(Vec3),
+}
+
+/// Normal map of noise octaves finer than the mesh, lighting detail the vertex heights leave out.
+///
+/// With a `StandardMaterial` it is only applied with `UvMode::Normalized`, `TerrainPbrMaterial` always applies it
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DetailNormals {
+    /// Octaves following those of the noise function
+    pub octaves: usize,
+    /// Texels of the normal map per unit of `Terrain::size`
+    pub resolution: u32,
+    /// Height of the detail in world units
+    pub strength: f32,
+}
+
+impl Default for DetailNormals {
+    fn default() -> Self {
+        Self {
+            octaves: 4,
+            resolution: 64,
+            strength: 0.02,
+        }
+    }
+}
+
+impl DetailNormals {
+    /// Tangent space normal map of `terrain` as `Rgba8Unorm`, `u` along `x` and `v` along `z`
+    fn normal_map(&self, terrain: &Terrain, world_size: Vec2) -> Image {
+        let noise_size = terrain.size.map(|side| side * terrain.resolution);
+        let texels = terrain.size.map(|side| (side * self.resolution).max(1));
+        let detail = generate_detail_map(
+            noise_size,
+            texels,
+            terrain.noise.seed.wrapping_add(terrain.seed_offset),
+            terrain.noise.scale,
+            terrain.noise_offset(),
+            &terrain.noise.method,
+            &terrain.noise.function,
+            self.octaves,
+        );
+        let spacing = world_size / Vec2::new(texels[0] as f32, texels[1] as f32);
+        let height = |i: usize, j: usize| detail[i][j] as f32 * self.strength;
+        let mut data = Vec::with_capacity(texels[0] as usize * texels[1] as usize * 4);
+        for j in 0..texels[1] as usize {
+            for i in 0..texels[0] as usize {
+                let (left, right) = (i.saturating_sub(1), (i + 1).min(texels[0] as usize - 1));
+                let (back, front) = (j.saturating_sub(1), (j + 1).min(texels[1] as usize - 1));
+                let dx = (height(right, j) - height(left, j))
+                    / (spacing.x * (right - left).max(1) as f32);
+                let dz = (height(i, front) - height(i, back))
+                    / (spacing.y * (front - back).max(1) as f32);
+                let normal = Vec3::new(-dx, 1.0, -dz).normalize();
+                // Tangent along `x`, bitangent along `z`
+                for value in [normal.x, normal.z, normal.y] {
+                    data.push((value.mul_add(0.5, 0.5) * 255.0).round() as u8);
+                }
+                data.push(255);
+            }
+        }
+        Image::new(
+            Extent3d {
+                width: texels[0],
+                height: texels[1],
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8Unorm,
+        )
+    }
+}
+
+/// Height of the terrain mesh placed at `y = 0`, before the offset of `Anchor::Offset`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VerticalAnchor {
+    /// Heights are kept as generated
+    #[default]
+    Generated,
+    /// The flat sea floor is at 0
+    SeaLevel,
+    /// The lowest vertex is at 0
+    MinHeight,
+}
 
 /// Component for terrain configuration
-#[derive(Component, Serialize, Deserialize)]
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
 pub struct Terrain {
     /// Noise configuration for terrain
     pub noise: Noise,
@@ -52,6 +249,45 @@ pub struct Terrain {
     /// Percentage of terrain that should appear under sea
     /// The mesh below this value will be flat
     pub sea_percent: f32,
+    /// Height of the flat sea floor in world units, replacing `sea_percent` if set.
+    /// The waterline stays put while the noise is tuned
+    pub sea_level: Option<f32>,
+    /// Size of the mesh in world units, the `size` grid is stretched to it.
+    /// Overlays such as splat maps and fog are laid out over `size` and stretch along.
+    /// If `None`, the mesh is `size` world units
+    pub world_size: Option<Vec2>,
+    /// Height of noise value 100 above the flat sea floor in world units, the sea floor is at 0.
+    /// If `None`, heights roughly range from -1 to 1.4
+    pub height_scale: Option<f32>,
+    /// Point of the mesh at the origin of the entity.
+    /// Overlays such as splat maps and fog move along
+    pub anchor: Anchor,
+    /// Height of the mesh at `y = 0`
+    pub vertical_anchor: VerticalAnchor,
+    /// Texture coordinates of the mesh
+    pub uv_mode: UvMode,
+    /// If set, flat areas are merged into larger triangles while no height moves by more than about this many world units.
+    /// The heights of `GeneratedTerrain` keep every vertex
+    pub simplification: Option<f32>,
+    /// If set, the mesh is only dense where the surface curves by more than this many degrees,
+    /// e.g. at ridges and cliffs, and sparse on plains. Combined with `simplification` both have to allow merging
+    pub adaptive_resolution: Option<f32>,
+    /// If true, every triangle has its own vertices with the normal and the mean color of the face, for a low-poly look
+    pub flat_shading: bool,
+    /// If set, octaves finer than the mesh are baked into `GeneratedTerrain::detail_normals`
+    pub detail_normals: Option<DetailNormals>,
+    /// If set, the noise of large terrains is generated this many columns per frame and the previous mesh stays
+    /// until it is complete, reported by a `GenerationProgress` inserted on the entity
+    pub columns_per_frame: Option<u32>,
+    /// If set, meshes with more vertices are split into child entities with a `TerrainMeshPart`
+    /// of at most this many vertices each. `GeneratedTerrain` stays on the terrain entity and covers every part
+    pub max_vertices: Option<u32>,
+    /// Position of the terrain center in world units, in `f64` for worlds far larger than `f32` resolves,
+    /// e.g. the cell of a floating origin. The noise is sampled at this position while the mesh stays around the entity
+    pub world_offset: DVec2,
+    /// Added to the seeds of the noise and its modifiers, so terrains spawned from one configuration
+    /// are distinct but reproducible variants, see [`Terrain::instance`]
+    pub seed_offset: u32,
     /// If true, exports model in glb format
     #[serde(skip)]
     pub export: bool,
@@ -66,11 +302,887 @@ impl Default for Terrain {
             wireframe: false,
             height_exponent: 1.0,
             sea_percent: 10.0,
+            sea_level: None,
+            world_size: None,
+            height_scale: None,
+            anchor: Anchor::Center,
+            vertical_anchor: VerticalAnchor::Generated,
+            uv_mode: UvMode::Grid,
+            simplification: None,
+            adaptive_resolution: None,
+            flat_shading: false,
+            detail_normals: None,
+            columns_per_frame: None,
+            max_vertices: None,
+            world_offset: DVec2::ZERO,
+            seed_offset: 0,
             export: false,
         }
     }
 }
 
+impl Terrain {
+    /// Highest supported resolution
+    pub const MAX_RESOLUTION: u32 = 1024;
+
+    /// Builder starting from the default configuration
+    #[must_use]
+    pub fn builder() -> TerrainBuilder {
+        TerrainBuilder::default()
+    }
+
+    /// Preset of snow-capped ridges over green valleys
+    #[must_use]
+    pub fn mountains() -> Self {
+        Self {
+            noise: Noise {
+                scale: 80.0,
+                function: Function {
+                    name: Some(FunctionName::RidgedMulti),
+                    octaves: 6,
+                    ..default()
+                },
+                regions: regions(&[
+                    ("Valley", 0.0, [86, 125, 70, 255]),
+                    ("Forest", 30.0, [52, 94, 56, 255]),
+                    ("Rock", 45.0, [120, 110, 100, 255]),
+                    ("Snow", 60.0, [245, 245, 250, 255]),
+                ]),
+                ..default()
+            },
+            size: [4; 2],
+            resolution: 32,
+            height_exponent: 2.2,
+            sea_percent: 0.0,
+            ..default()
+        }
+    }
+
+    /// Preset of scattered islands with beaches in a shallow sea
+    #[must_use]
+    pub fn islands() -> Self {
+        Self {
+            noise: Noise {
+                scale: 60.0,
+                regions: regions(&[
+                    ("Deep water", 0.0, [16, 50, 120, 255]),
+                    ("Shallow water", 50.0, [40, 120, 190, 255]),
+                    ("Sand", 56.0, [230, 215, 160, 255]),
+                    ("Grass", 64.0, [95, 160, 70, 255]),
+                    ("Forest", 85.0, [40, 100, 50, 255]),
+                ]),
+                ..default()
+            },
+            size: [4; 2],
+            resolution: 24,
+            height_exponent: 1.3,
+            sea_percent: 55.0,
+            ..default()
+        }
+    }
+
+    /// Preset of wind-blown sand dunes
+    #[must_use]
+    pub fn dunes() -> Self {
+        Self {
+            noise: Noise {
+                scale: 120.0,
+                function: Function {
+                    name: Some(FunctionName::Billow),
+                    octaves: 3,
+                    ..default()
+                },
+                regions: regions(&[
+                    ("Trough", 0.0, [190, 140, 80, 255]),
+                    ("Sand", 50.0, [225, 180, 110, 255]),
+                    ("Crest", 100.0, [245, 215, 160, 255]),
+                ]),
+                modifiers: vec![Modifier::Dunes(Dunes {
+                    wind_direction: 30.0,
+                    height: 15.0,
+                    ..default()
+                })],
+                ..default()
+            },
+            size: [4; 2],
+            resolution: 32,
+            height_exponent: 1.0,
+            sea_percent: 0.0,
+            ..default()
+        }
+    }
+
+    /// Preset of small continents and island chains along drifting plates
+    #[must_use]
+    pub fn archipelago() -> Self {
+        Self {
+            noise: Noise {
+                scale: 40.0,
+                regions: regions(&[
+                    ("Deep water", 0.0, [12, 40, 100, 255]),
+                    ("Shallow water", 58.0, [45, 130, 180, 255]),
+                    ("Sand", 65.0, [225, 210, 150, 255]),
+                    ("Grass", 72.0, [90, 150, 70, 255]),
+                    ("Rock", 78.0, [110, 105, 95, 255]),
+                ]),
+                modifiers: vec![Modifier::Tectonics(Tectonics {
+                    plates: 24,
+                    continental: 0.2,
+                    ..default()
+                })],
+                ..default()
+            },
+            size: [6; 2],
+            resolution: 20,
+            height_exponent: 1.2,
+            sea_percent: 65.0,
+            ..default()
+        }
+    }
+
+    /// Preset of flat mesas with banded rock strata
+    #[must_use]
+    pub fn canyonlands() -> Self {
+        Self {
+            noise: Noise {
+                scale: 70.0,
+                function: Function {
+                    name: Some(FunctionName::HybridMulti),
+                    octaves: 5,
+                    ..default()
+                },
+                regions: regions(&[
+                    ("Riverbed", 0.0, [120, 70, 45, 255]),
+                    ("Red rock", 35.0, [175, 85, 50, 255]),
+                    ("Sandstone", 70.0, [210, 140, 90, 255]),
+                    ("Mesa", 100.0, [230, 190, 140, 255]),
+                ]),
+                // Sharp gradient segments draw the strata
+                gradient: Gradient {
+                    segments: 8,
+                    smoothness: 0.2,
+                    ..default()
+                },
+                ..default()
+            },
+            size: [4; 2],
+            resolution: 32,
+            height_exponent: 0.6,
+            sea_percent: 0.0,
+            ..default()
+        }
+    }
+
+    /// Offset of the noise including `world_offset`, in noise coordinates
+    #[must_use]
+    pub fn noise_offset(&self) -> [f64; 2] {
+        self.noise_offset_of(&self.noise)
+    }
+
+    /// Offset of `noise` sampled across the terrain including `world_offset`, in noise coordinates
+    pub(crate) fn noise_offset_of(&self, noise: &Noise) -> [f64; 2] {
+        let world_size = self.world_size.map_or_else(
+            || self.size.map(f64::from),
+            |world_size| [f64::from(world_size.x), f64::from(world_size.y)],
+        );
+        let mut offset = noise.offset;
+        for (axis, offset) in offset.iter_mut().enumerate() {
+            // Noise columns per world unit divided by the noise columns per noise unit
+            let columns = f64::from(self.size[axis]) * f64::from(self.resolution);
+            *offset += self.world_offset[axis] * columns
+                / world_size[axis].max(f64::EPSILON)
+                / noise.scale;
+        }
+        offset
+    }
+
+    /// Copy of the terrain as variant `index`, e.g. one island of an archipelago spawned from a shared configuration.
+    /// The same index always gives the same variant, index 0 is the configuration itself
+    #[must_use]
+    pub fn instance(&self, index: u32) -> Self {
+        Self {
+            seed_offset: self.seed_offset.wrapping_add(index),
+            ..self.clone()
+        }
+    }
+
+    /// Noise the terrain is generated from, with `seed_offset` added to its seeds
+    #[must_use]
+    pub fn instance_noise(&self) -> Noise {
+        self.noise.offset_seeds(self.seed_offset)
+    }
+
+    /// Heights of the vertices of `noise_values` with the sea flattened, before stamps, roads and deformations
+    pub(crate) fn heights(&self, noise_values: &NoiseMap) -> Vec<Vec<f32>> {
+        match noise_values {
+            NoiseMap::Single(values) => self.heights_of(values),
+            NoiseMap::Double(values) => self.heights_of(values),
+        }
+    }
+
+    fn heights_of<T: NoiseValue>(&self, noise_values: &[Vec<T>]) -> Vec<Vec<f32>> {
+        // An absolute sea level replaces the percent, the heights are flattened to it afterwards
+        let sea_percent = if self.sea_level.is_some() {
+            0.0
+        } else {
+            self.sea_percent
+        };
+        let mut heights = self.height_scale.map_or_else(
+            || heightmap::heights(noise_values, sea_percent, self.height_exponent),
+            |height_scale| {
+                heightmap::scaled_heights(
+                    noise_values,
+                    sea_percent,
+                    self.height_exponent,
+                    height_scale,
+                )
+            },
+        );
+        if let Some(sea_level) = self.sea_level {
+            for height in heights.iter_mut().flatten() {
+                *height = height.max(sea_level);
+            }
+        }
+        heights
+    }
+
+    /// Sea level of `heights` before the anchors lift them
+    pub(crate) fn generated_sea_level(&self) -> f32 {
+        match (self.sea_level, self.height_scale) {
+            (Some(sea_level), _) => sea_level,
+            (None, Some(_)) => 0.0,
+            (None, None) => heightmap::sea_level(self.height_exponent),
+        }
+    }
+
+    /// Height added to every vertex of `heights` by `anchor` and `vertical_anchor`
+    pub(crate) fn lift(&self, heights: &[Vec<f32>], sea_level: f32) -> f32 {
+        let offset = match self.anchor {
+            Anchor::Offset(offset) => offset,
+            Anchor::Center | Anchor::Corner => Vec3::ZERO,
+        };
+        offset.y
+            - match self.vertical_anchor {
+                VerticalAnchor::Generated => 0.0,
+                VerticalAnchor::SeaLevel => sea_level,
+                VerticalAnchor::MinHeight => heights
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .reduce(f32::min)
+                    .unwrap_or_default(),
+            }
+    }
+
+    /// Checks the resolution, the size, the sea level and percent, the height and world size settings,
+    /// the detail normals and the noise
+    ///
+    /// # Errors
+    /// The first invalid setting
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.check_generation()?;
+        if !(0.0..=100.0).contains(&self.sea_percent) {
+            return Err(ConfigError::SeaPercent(self.sea_percent));
+        }
+        if let Some(tolerance) = self
+            .simplification
+            .filter(|tolerance| !(tolerance.is_finite() && *tolerance >= 0.0))
+        {
+            return Err(ConfigError::Simplification(tolerance));
+        }
+        if let Some(max_angle) = self
+            .adaptive_resolution
+            .filter(|max_angle| !(max_angle.is_finite() && *max_angle >= 0.0))
+        {
+            return Err(ConfigError::AdaptiveResolution(max_angle));
+        }
+        self.noise.validate()
+    }
+
+    /// Checks the settings that would panic or produce NaN during generation. Generation checks terrains
+    /// built without the builder with it and sends a `GenerationError` instead of generating them
+    pub(crate) fn check_generation(&self) -> Result<(), ConfigError> {
+        if !(1..=Self::MAX_RESOLUTION).contains(&self.resolution) {
+            return Err(ConfigError::Resolution {
+                resolution: self.resolution,
+                max: Self::MAX_RESOLUTION,
+            });
+        }
+        if self.size.contains(&0) {
+            return Err(ConfigError::EmptySize);
+        }
+        let vertices = self
+            .size
+            .iter()
+            .map(|side| u64::from(*side) * u64::from(self.resolution) + 1)
+            .product();
+        if vertices > u64::from(u32::MAX) {
+            return Err(ConfigError::TooManyVertices { vertices });
+        }
+        if !(self.height_exponent.is_finite() && self.height_exponent > 0.0) {
+            return Err(ConfigError::HeightExponent(self.height_exponent));
+        }
+        if let Some(sea_level) = self.sea_level.filter(|sea_level| !sea_level.is_finite()) {
+            return Err(ConfigError::SeaLevel(sea_level));
+        }
+        if let Some(detail_normals) = &self.detail_normals {
+            if detail_normals.resolution == 0 || detail_normals.resolution > Self::MAX_RESOLUTION {
+                return Err(ConfigError::DetailResolution {
+                    resolution: detail_normals.resolution,
+                    max: Self::MAX_RESOLUTION,
+                });
+            }
+            if !detail_normals.strength.is_finite() {
+                return Err(ConfigError::DetailStrength(detail_normals.strength));
+            }
+        }
+        if let Some(world_size) = self.world_size {
+            if !(world_size.is_finite() && world_size.cmpgt(Vec2::ZERO).all()) {
+                return Err(ConfigError::WorldSize(world_size));
+            }
+        }
+        if let Some(height_scale) = self.height_scale {
+            if !(height_scale.is_finite() && height_scale >= 0.0) {
+                return Err(ConfigError::HeightScale(height_scale));
+            }
+        }
+        self.noise.check_generation()
+    }
+}
+
+/// Builder of a validated `Terrain`
+#[derive(Default)]
+pub struct TerrainBuilder {
+    terrain: Terrain,
+}
+
+impl TerrainBuilder {
+    /// Sets the noise configuration
+    #[must_use]
+    pub fn noise(mut self, noise: Noise) -> Self {
+        self.terrain.noise = noise;
+        self
+    }
+
+    /// Sets the size of the terrain in world units
+    #[must_use]
+    pub const fn size(mut self, size: [u32; 2]) -> Self {
+        self.terrain.size = size;
+        self
+    }
+
+    /// Sets the number of vertices per world unit
+    #[must_use]
+    pub const fn resolution(mut self, resolution: u32) -> Self {
+        self.terrain.resolution = resolution;
+        self
+    }
+
+    /// Renders the terrain as wireframe if true
+    #[must_use]
+    pub const fn wireframe(mut self, wireframe: bool) -> Self {
+        self.terrain.wireframe = wireframe;
+        self
+    }
+
+    /// Sets the exponent the heights are raised to
+    #[must_use]
+    pub const fn height_exponent(mut self, height_exponent: f32) -> Self {
+        self.terrain.height_exponent = height_exponent;
+        self
+    }
+
+    /// Sets the percentage of the terrain under sea
+    #[must_use]
+    pub const fn sea_percent(mut self, sea_percent: f32) -> Self {
+        self.terrain.sea_percent = sea_percent;
+        self
+    }
+
+    /// Sets the height of the flat sea floor in world units, replacing the sea percent
+    #[must_use]
+    pub const fn sea_level(mut self, sea_level: f32) -> Self {
+        self.terrain.sea_level = Some(sea_level);
+        self
+    }
+
+    /// Stretches the mesh to `world_size` world units
+    #[must_use]
+    pub const fn world_size(mut self, world_size: Vec2) -> Self {
+        self.terrain.world_size = Some(world_size);
+        self
+    }
+
+    /// Sets the height of noise value 100 above the sea floor in world units
+    #[must_use]
+    pub const fn height_scale(mut self, height_scale: f32) -> Self {
+        self.terrain.height_scale = Some(height_scale);
+        self
+    }
+
+    /// Sets the point of the mesh at the origin of the entity
+    #[must_use]
+    pub const fn anchor(mut self, anchor: Anchor) -> Self {
+        self.terrain.anchor = anchor;
+        self
+    }
+
+    /// Sets the height of the mesh at `y = 0`
+    #[must_use]
+    pub const fn vertical_anchor(mut self, vertical_anchor: VerticalAnchor) -> Self {
+        self.terrain.vertical_anchor = vertical_anchor;
+        self
+    }
+
+    /// Sets the texture coordinates of the mesh
+    #[must_use]
+    pub fn uv_mode(mut self, uv_mode: UvMode) -> Self {
+        self.terrain.uv_mode = uv_mode;
+        self
+    }
+
+    /// Merges flat areas into larger triangles within `tolerance` world units
+    #[must_use]
+    pub const fn simplification(mut self, tolerance: f32) -> Self {
+        self.terrain.simplification = Some(tolerance);
+        self
+    }
+
+    /// Keeps the mesh dense only where the surface curves by more than `max_angle` degrees
+    #[must_use]
+    pub const fn adaptive_resolution(mut self, max_angle: f32) -> Self {
+        self.terrain.adaptive_resolution = Some(max_angle);
+        self
+    }
+
+    /// Flat shades every triangle if true
+    #[must_use]
+    pub const fn flat_shading(mut self, flat_shading: bool) -> Self {
+        self.terrain.flat_shading = flat_shading;
+        self
+    }
+
+    /// Bakes octaves finer than the mesh into a normal map
+    #[must_use]
+    pub const fn detail_normals(mut self, detail_normals: DetailNormals) -> Self {
+        self.terrain.detail_normals = Some(detail_normals);
+        self
+    }
+
+    /// Spreads the noise generation over several frames, `columns` per frame
+    #[must_use]
+    pub const fn columns_per_frame(mut self, columns: u32) -> Self {
+        self.terrain.columns_per_frame = Some(columns);
+        self
+    }
+
+    /// Splits meshes with more than `max_vertices` vertices into child entities
+    #[must_use]
+    pub const fn max_vertices(mut self, max_vertices: u32) -> Self {
+        self.terrain.max_vertices = Some(max_vertices);
+        self
+    }
+
+    /// Sets the world position the noise is sampled at
+    #[must_use]
+    pub const fn world_offset(mut self, world_offset: DVec2) -> Self {
+        self.terrain.world_offset = world_offset;
+        self
+    }
+
+    /// Sets the offset added to the seeds of the noise and its modifiers
+    #[must_use]
+    pub const fn seed_offset(mut self, seed_offset: u32) -> Self {
+        self.terrain.seed_offset = seed_offset;
+        self
+    }
+
+    /// Validated terrain configuration
+    ///
+    /// # Errors
+    /// If the configuration is invalid, see [`Terrain::validate`]
+    pub fn build(self) -> Result<Terrain, ConfigError> {
+        self.terrain.validate()?;
+        Ok(self.terrain)
+    }
+}
+
+/// Width of `GeneratedTerrain::gradient_lut` in texels
+pub const GRADIENT_LUT_WIDTH: u32 = 256;
+
+/// Output of the terrain generation, inserted on the entity with the `Terrain` component.
+/// Coordinates are relative to the terrain entity
+///
+/// Only changes when the generated terrain does, generation never writes to the `Terrain` configuration
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct GeneratedTerrain {
+    /// Height of every vertex in world units including snow, indexed by `[x][z]`
+    pub heights: Vec<Vec<f32>>,
+    /// Height of every vertex before roads are flattened into the terrain, including stamps and deformations. Indexed by `[x][z]`
+    pub base_heights: Vec<Vec<f32>>,
+    /// Noise value of every vertex (0 to 100) with the precision of `Noise::precision`, indexed by `[x][z]`
+    pub noise: NoiseMap,
+    /// Number of vertices per unit of `size`
+    pub resolution: u32,
+    /// Size of the terrain grid, see `Terrain::size`
+    pub size: [u32; 2],
+    /// Size of the terrain in world units, see `Terrain::world_size`
+    pub world_size: Vec2,
+    /// Position of the center of the terrain along `x` and `z` in world units, see `Terrain::anchor`
+    pub center: Vec2,
+    /// Height of the flat sea floor in world units
+    pub sea_level: f32,
+    /// Index into `Noise::regions` of the region of every vertex, indexed by `[x][z]`. See [`Noise::region`]
+    pub regions: Vec<Vec<usize>>,
+    /// Summary of the heightfield
+    pub stats: TerrainStats,
+    /// Image of the gradient the terrain is colored with
+    pub gradient: Handle<Image>,
+    /// 1D lookup texture of the vertex colors, see [`Noise::gradient_lut`]
+    pub gradient_lut: Handle<Image>,
+    /// Normal map of `Terrain::detail_normals` across the terrain, if set
+    pub detail_normals: Option<Handle<Image>>,
+    /// Cells cut out of the mesh by `TerrainHoles`, by the index of the vertex at their lower corner.
+    /// Empty without holes
+    pub holes: Vec<Vec<bool>>,
+}
+
+/// Renamed to `GeneratedTerrain`
+#[deprecated(note = "renamed to `GeneratedTerrain`")]
+pub type TerrainData = GeneratedTerrain;
+
+/// Summary of a generated heightfield
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct TerrainStats {
+    /// Lowest height in world units
+    pub min_height: f32,
+    /// Highest height in world units
+    pub max_height: f32,
+    /// Mean height in world units
+    pub mean_height: f32,
+    /// Fraction of the vertices above the sea floor, 0 to 1
+    pub land: f32,
+    /// Fraction of the vertices in every region, indexed like `Noise::regions`
+    pub region_coverage: Vec<f32>,
+}
+
+impl TerrainStats {
+    fn new(
+        heights: &[Vec<f32>],
+        regions: &[Vec<usize>],
+        region_count: usize,
+        sea_level: f32,
+    ) -> Self {
+        let count = heights.iter().map(Vec::len).sum::<usize>().max(1) as f32;
+        let (mut min_height, mut max_height, mut sum, mut land) = (f32::MAX, f32::MIN, 0.0, 0);
+        for &height in heights.iter().flatten() {
+            min_height = min_height.min(height);
+            max_height = max_height.max(height);
+            sum += height;
+            if height > sea_level + f32::EPSILON {
+                land += 1;
+            }
+        }
+        let mut region_coverage = vec![0.0; region_count];
+        for &region in regions.iter().flatten() {
+            if let Some(coverage) = region_coverage.get_mut(region) {
+                *coverage += 1.0 / count;
+            }
+        }
+        if min_height > max_height {
+            (min_height, max_height) = (0.0, 0.0);
+        }
+        Self {
+            min_height,
+            max_height,
+            mean_height: sum / count,
+            land: land as f32 / count,
+            region_coverage,
+        }
+    }
+}
+
+impl GeneratedTerrain {
+    /// Height at `x`, `z`, bilinearly interpolated between vertices.
+    /// `None` outside of the terrain
+    #[must_use]
+    pub fn height(&self, x: f32, z: f32) -> Option<f32> {
+        let (column, row, tx, tz) = self.locate(x, z)?;
+        let height = |i: usize, j: usize| self.heights[column + i][row + j];
+        let near = (height(1, 0) - height(0, 0)).mul_add(tx, height(0, 0));
+        let far = (height(1, 1) - height(0, 1)).mul_add(tx, height(0, 1));
+        Some((far - near).mul_add(tz, near))
+    }
+
+    /// Noise value at `x`, `z`, bilinearly interpolated between vertices.
+    /// `None` outside of the terrain
+    #[must_use]
+    pub fn noise_at(&self, x: f32, z: f32) -> Option<f64> {
+        let (column, row, tx, tz) = self.locate(x, z)?;
+        let (tx, tz) = (f64::from(tx), f64::from(tz));
+        let noise = |i: usize, j: usize| self.noise.get(column + i, row + j);
+        let near = (noise(1, 0)? - noise(0, 0)?).mul_add(tx, noise(0, 0)?);
+        let far = (noise(1, 1)? - noise(0, 1)?).mul_add(tx, noise(0, 1)?);
+        Some((far - near).mul_add(tz, near))
+    }
+
+    /// Surface normal at `x`, `z`, `None` outside of the terrain
+    #[must_use]
+    pub fn normal(&self, x: f32, z: f32) -> Option<Vec3> {
+        let step = self.stretch() / self.resolution.max(1) as f32;
+        let height = self.height(x, z)?;
+        let dx = self.height(x + step.x, z).map_or_else(
+            || height - self.height(x - step.x, z).unwrap_or(height),
+            |right| right - height,
+        );
+        let dz = self.height(x, z + step.y).map_or_else(
+            || height - self.height(x, z - step.y).unwrap_or(height),
+            |far| far - height,
+        );
+        Some(Vec3::new(-dx / step.x, 1.0, -dz / step.y).normalize())
+    }
+
+    /// Slope at `x`, `z` in radians, 0 is flat. `None` outside of the terrain
+    #[must_use]
+    pub fn slope(&self, x: f32, z: f32) -> Option<f32> {
+        self.normal(x, z)
+            .map(|normal| normal.angle_between(Vec3::Y))
+    }
+
+    /// Position of vertex `i`, `j` relative to the terrain entity, `None` outside of the grid
+    #[must_use]
+    pub fn vertex_position(&self, i: usize, j: usize) -> Option<Vec3> {
+        let height = *self.heights.get(i)?.get(j)?;
+        let resolution = self.resolution.max(1) as f32;
+        let stretch = self.stretch();
+        let x =
+            (i as f32 / resolution - self.size[0] as f32 / 2.0).mul_add(stretch.x, self.center.x);
+        let z =
+            (j as f32 / resolution - self.size[1] as f32 / 2.0).mul_add(stretch.y, self.center.y);
+        Some(Vec3::new(x, height, z))
+    }
+
+    /// Point of the overlays of the terrain, e.g. a `FogOfWar` or `SplatMap`, at `local` relative to the
+    /// terrain entity. Overlays span `Terrain::size` around the origin whatever the world size and anchor
+    #[must_use]
+    pub fn to_overlay(&self, local: Vec2) -> Vec2 {
+        (local - self.center) / self.stretch()
+    }
+
+    /// Point relative to the terrain entity at `point` of the overlays, the inverse of `to_overlay`
+    #[must_use]
+    pub fn from_overlay(&self, point: Vec2) -> Vec2 {
+        point * self.stretch() + self.center
+    }
+
+    /// `radius` in world units as a radius on the overlays, by the mean stretch along `x` and `z`
+    #[must_use]
+    pub fn to_overlay_radius(&self, radius: f32) -> f32 {
+        let stretch = self.stretch();
+        radius * 2.0 / (stretch.x + stretch.y)
+    }
+
+    /// Returns true if `x`, `z` is in a cell cut out by `TerrainHoles`
+    #[must_use]
+    pub fn is_hole(&self, x: f32, z: f32) -> bool {
+        self.locate(x, z)
+            .is_some_and(|(i, j, _, _)| self.is_hole_cell(i, j))
+    }
+
+    pub(crate) fn is_hole_cell(&self, i: usize, j: usize) -> bool {
+        self.holes
+            .get(i)
+            .and_then(|column| column.get(j))
+            .is_some_and(|&hole| hole)
+    }
+
+    /// Returns true if `x`, `z` is on the flat sea floor
+    #[must_use]
+    pub fn is_sea(&self, x: f32, z: f32) -> bool {
+        self.height(x, z)
+            .is_some_and(|height| height <= self.sea_level + f32::EPSILON)
+    }
+
+    /// Cell of the vertex nearest to `x`, `z`, `None` outside of the terrain.
+    /// See [`TerrainCells`] for neighbours, areas and biome or flow data
+    #[must_use]
+    pub fn cell(&self, x: f32, z: f32) -> Option<TerrainCell> {
+        self.cells().cell(x, z)
+    }
+
+    /// Cell queries over the grid of the terrain
+    #[must_use]
+    pub const fn cells(&self) -> TerrainCells<'_> {
+        TerrainCells::new(self)
+    }
+
+    /// First point where `ray` hits the surface, both relative to the terrain entity.
+    ///
+    /// The ray walks the grid cell by cell and is tested against the two triangles of every cell it crosses,
+    /// the same triangles as the mesh before simplification. Nothing is allocated, so it is cheap enough for
+    /// picking and projectiles every frame. Transform world rays with the inverse of the `GlobalTransform` first
+    #[must_use]
+    pub fn raycast(&self, ray: Ray) -> Option<TerrainHit> {
+        let columns = self.heights.len();
+        let rows = self.heights.first()?.len();
+        let direction = ray.direction.normalize_or_zero();
+        if columns < 2 || rows < 2 || direction == Vec3::ZERO {
+            return None;
+        }
+        let first = self.vertex_position(0, 0)?;
+        let last = self.vertex_position(columns - 1, rows - 1)?;
+        let padding = Vec3::splat(1e-4);
+        let min = Vec3::new(first.x, self.stats.min_height, first.z) - padding;
+        let max = Vec3::new(last.x, self.stats.max_height, last.z) + padding;
+        let (enter, exit) = slab(ray.origin, direction, min, max)?;
+
+        // Grid coordinates are in cells from the first vertex
+        let cell_size = self.stretch() / self.resolution.max(1) as f32;
+        let start = ray.origin + direction * enter;
+        let start = Vec2::new(start.x - first.x, start.z - first.z) / cell_size;
+        let last_cell = IVec2::new(columns as i32 - 2, rows as i32 - 2);
+        let mut cell = start.floor().as_ivec2().clamp(IVec2::ZERO, last_cell);
+        let velocity = Vec2::new(direction.x, direction.z) / cell_size;
+        let step = IVec2::new(velocity.x.signum() as i32, velocity.y.signum() as i32);
+        let mut next = Vec2::ZERO;
+        let mut delta = Vec2::ZERO;
+        for axis in 0..2 {
+            if velocity[axis] == 0.0 {
+                next[axis] = f32::INFINITY;
+                delta[axis] = f32::INFINITY;
+                continue;
+            }
+            let boundary = if velocity[axis] > 0.0 {
+                cell[axis] as f32 + 1.0
+            } else {
+                cell[axis] as f32
+            };
+            next[axis] = enter + (boundary - start[axis]) / velocity[axis];
+            delta[axis] = 1.0 / velocity[axis].abs();
+        }
+
+        loop {
+            let (i, j) = (cell.x as usize, cell.y as usize);
+            let corner = |di: usize, dj: usize| self.vertex_position(i + di, j + dj);
+            let [near, right, far, both] =
+                [corner(0, 0)?, corner(1, 0)?, corner(0, 1)?, corner(1, 1)?];
+            // Same triangles as `grid_indices`, rays pass through holes
+            let hit = [[near, far, right], [right, far, both]]
+                .into_iter()
+                .filter(|_| !self.is_hole_cell(i, j))
+                .filter_map(|triangle| {
+                    intersect_triangle(ray.origin, direction, triangle)
+                        .map(|distance| (distance, triangle))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            if let Some((distance, [a, b, c])) = hit {
+                let normal = (c - a).cross(b - a).normalize_or_zero();
+                return Some(TerrainHit {
+                    position: ray.origin + direction * distance,
+                    normal: if normal.y < 0.0 { -normal } else { normal },
+                    distance,
+                });
+            }
+            let axis = usize::from(next.y < next.x);
+            if next[axis] > exit {
+                return None;
+            }
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+            if cell[axis] < 0 || cell[axis] > last_cell[axis] {
+                return None;
+            }
+        }
+    }
+
+    /// World units per unit of `size` along `x` and `z`
+    pub(crate) fn stretch(&self) -> Vec2 {
+        let size = Vec2::new(self.size[0] as f32, self.size[1] as f32);
+        if size.cmpgt(Vec2::ZERO).all() && self.world_size.cmpgt(Vec2::ZERO).all() {
+            self.world_size / size
+        } else {
+            Vec2::ONE
+        }
+    }
+
+    /// Position of `x`, `z` in vertices from the first vertex, fractional between vertices
+    pub(crate) fn grid_position(&self, x: f32, z: f32) -> Vec2 {
+        let resolution = self.resolution as f32;
+        let stretch = self.stretch();
+        let column = ((x - self.center.x) / stretch.x + self.size[0] as f32 / 2.0) * resolution;
+        let row = ((z - self.center.y) / stretch.y + self.size[1] as f32 / 2.0) * resolution;
+        Vec2::new(column, row)
+    }
+
+    /// Vertex at the lower corner of the cell containing `x`, `z` and the position within the cell
+    fn locate(&self, x: f32, z: f32) -> Option<(usize, usize, f32, f32)> {
+        let Vec2 { x: column, y: row } = self.grid_position(x, z);
+        // Index of the last cell along each axis
+        let columns = self.heights.len().checked_sub(2)?;
+        let rows = self.heights.first()?.len().checked_sub(2)?;
+        if !(0.0..=columns as f32 + 1.0).contains(&column)
+            || !(0.0..=rows as f32 + 1.0).contains(&row)
+        {
+            return None;
+        }
+        let i = (column as usize).min(columns);
+        let j = (row as usize).min(rows);
+        Some((i, j, column - i as f32, row - j as f32))
+    }
+}
+
+/// Point where a ray hits the terrain surface, see [`GeneratedTerrain::raycast`]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TerrainHit {
+    /// Position of the hit relative to the terrain entity
+    pub position: Vec3,
+    /// Normal of the hit triangle, facing up
+    pub normal: Vec3,
+    /// Distance from the origin of the ray along its normalized direction
+    pub distance: f32,
+}
+
+/// Distances along the ray where it enters and leaves the box from `min` to `max`, `None` if it misses it
+fn slab(origin: Vec3, direction: Vec3, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+    let inverse = direction.recip();
+    let (a, b) = ((min - origin) * inverse, (max - origin) * inverse);
+    // Axes the ray is parallel to give NaN when the origin is on a face, those axes do not limit the ray
+    let enter = a
+        .min(b)
+        .to_array()
+        .into_iter()
+        .filter(|t| !t.is_nan())
+        .fold(0.0, f32::max);
+    let exit = a
+        .max(b)
+        .to_array()
+        .into_iter()
+        .filter(|t| !t.is_nan())
+        .fold(f32::INFINITY, f32::min);
+    (enter <= exit).then_some((enter, exit))
+}
+
+/// Distance along the ray to triangle `a`, `b`, `c` with the Möller-Trumbore algorithm
+fn intersect_triangle(origin: Vec3, direction: Vec3, triangle: [Vec3; 3]) -> Option<f32> {
+    let [first, second, third] = triangle;
+    let (edge, other_edge) = (second - first, third - first);
+    let across = direction.cross(other_edge);
+    let determinant = edge.dot(across);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse = determinant.recip();
+    let relative = origin - first;
+    let u = relative.dot(across) * inverse;
+    let up = relative.cross(edge);
+    let v = direction.dot(up) * inverse;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = other_edge.dot(up) * inverse;
+    (distance >= 0.0).then_some(distance)
+}
+
 /// Render `Terrain` as a `PbrBundle`
 #[derive(Bundle, Default)]
 pub struct TerrainBundle {
@@ -80,162 +1192,1017 @@ pub struct TerrainBundle {
     pub pbr_bundle: PbrBundle,
 }
 
+/// Marker for terrains spawned with their `GeneratedTerrain` and mesh, e.g. chunks restored from the streaming cache.
+/// The terrain is not generated again until it or one of its inputs changes after the spawn
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub(crate) struct RestoredTerrain;
+
+/// Marker for terrains whose last generation failed with a `GenerationError`.
+/// The terrain is not generated again until it or one of its inputs changes
+#[derive(Component, Clone, Copy, Default, Debug)]
+struct FailedTerrain;
+
+/// Component of the child entities a terrain mesh is split into, see `Terrain::max_vertices`
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TerrainMeshPart {
+    /// Index of the part, parts follow each other along `x`
+    pub index: usize,
+}
+
+/// Stages of the `TerrainPlugin` in `Update`, in order.
+/// Systems changing the inputs of a terrain, e.g. painting a `SplatMap`, run before `TerrainSet::Color`
+/// to show up in the same frame
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TerrainSet {
+    /// Heightfield and mesh of changed terrains, see `GeneratedTerrain`
+    Generate,
+    /// Vertex colors of recolored terrains by their palettes, splat maps, snow, beaches, territories,
+    /// fog and debug views, see `TerrainColors`
+    Color,
+    /// Vertex colors written to the terrain meshes
+    Apply,
+}
+
+/// Vertex colors of a terrain, inserted with its `GeneratedTerrain` unless it is `DataOnly`.
+///
+/// The colors are only blended again after [`TerrainColors::recolor`], which generating the terrain
+/// and changing a color input such as a `FogOfWar` does. They are then written to the existing meshes
+/// without generating the terrain again
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct TerrainColors {
+    /// Gradient color of every vertex, in the order of `GeneratedTerrain::heights`
+    pub base: Vec<[f32; 4]>,
+    /// Color of every vertex after the systems in `TerrainSet::Color`, laid out like `base`
+    pub colors: Vec<[f32; 4]>,
+    recolor: bool,
+    layout: MeshLayout,
+}
+
+/// Vertices of the meshes of a terrain by the vertices of its grid they are colored from
+#[derive(Clone, Default, PartialEq, Debug)]
+struct MeshLayout {
+    /// Grid vertex of every vertex kept by `Terrain::simplification` or `Terrain::adaptive_resolution`
+    simplified: Option<Vec<u32>>,
+    /// Vertex of the three corners of every face with `Terrain::flat_shading`
+    flat: Option<Vec<u32>>,
+    /// Vertices of every `TerrainMeshPart` by their index, empty if the mesh is not split
+    parts: Vec<Vec<u32>>,
+}
+
+impl TerrainColors {
+    /// Resets the colors to `base` to blend them again and write them to the meshes this frame
+    pub fn recolor(&mut self) {
+        self.colors.clone_from(&self.base);
+        self.recolor = true;
+    }
+
+    /// Returns true if the colors are blended again this frame
+    #[must_use]
+    pub const fn is_recoloring(&self) -> bool {
+        self.recolor
+    }
+
+    /// Replaces the color of every vertex while recoloring. `blend` gets the indices of the vertex,
+    /// its position relative to the terrain center in units of `Terrain::size` and its color
+    pub(crate) fn blend(
+        &mut self,
+        terrain_data: &GeneratedTerrain,
+        mut blend: impl FnMut([usize; 2], Vec2, [f32; 4]) -> [f32; 4],
+    ) {
+        let rows = terrain_data.heights.first().map_or(0, Vec::len);
+        if !self.recolor || rows == 0 || self.colors.len() != terrain_data.heights.len() * rows {
+            return;
+        }
+        let resolution = terrain_data.resolution.max(1) as f32;
+        let half = Vec2::new(terrain_data.size[0] as f32, terrain_data.size[1] as f32) / 2.0;
+        for (index, color) in self.colors.iter_mut().enumerate() {
+            let [i, j] = [index / rows, index % rows];
+            let point = Vec2::new(i as f32, j as f32) / resolution - half;
+            *color = blend([i, j], point, *color);
+        }
+    }
+
+    /// Colors of the vertices of the terrain mesh, or of every `TerrainMeshPart` if it is split
+    fn mesh_colors(&self) -> Vec<Vec<[f32; 4]>> {
+        let mut colors = self.layout.simplified.as_ref().map_or_else(
+            || self.colors.clone(),
+            |sources| {
+                sources
+                    .iter()
+                    .map(|&index| self.colors[index as usize])
+                    .collect()
+            },
+        );
+        if let Some(corners) = &self.layout.flat {
+            // Every face gets the mean color of its corners
+            colors = corners
+                .chunks_exact(3)
+                .flat_map(|triangle| {
+                    let mut color = [0.0; 4];
+                    for &index in triangle {
+                        for (channel, value) in color.iter_mut().zip(colors[index as usize]) {
+                            *channel += value / 3.0;
+                        }
+                    }
+                    [color; 3]
+                })
+                .collect();
+        }
+        if self.layout.parts.is_empty() {
+            return vec![colors];
+        }
+        self.layout
+            .parts
+            .iter()
+            .map(|sources| {
+                sources
+                    .iter()
+                    .map(|&index| colors[index as usize])
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Recolors terrains whose `T` changed or was removed
+#[allow(clippy::needless_pass_by_value)]
+fn recolor_on_change<T: Component>(
+    mut query: Query<&mut TerrainColors>,
+    changed: Query<Entity, (Changed<T>, With<TerrainColors>)>,
+    mut removed: RemovedComponents<T>,
+) {
+    for entity in changed.iter().chain(removed.read()) {
+        if let Ok(mut colors) = query.get_mut(entity) {
+            colors.recolor();
+        }
+    }
+}
+
+/// Writes the colors of recolored terrains to their meshes, and exports the terrains with `Terrain::export`
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn apply_terrain_colors(
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<(
+        &mut Terrain,
+        &mut TerrainColors,
+        Option<&Handle<Mesh>>,
+        Option<&Children>,
+    )>,
+    parts: Query<(&TerrainMeshPart, &Handle<Mesh>)>,
+) {
+    let Some(meshes) = meshes.as_deref_mut() else {
+        return;
+    };
+    for (mut terrain, mut colors, mesh, children) in &mut query {
+        if !colors.recolor {
+            continue;
+        }
+        colors.bypass_change_detection().recolor = false;
+        if colors.colors.len() != colors.base.len() {
+            continue;
+        }
+        let handles: Vec<&Handle<Mesh>> = if colors.layout.parts.is_empty() {
+            mesh.into_iter().collect()
+        } else {
+            let mut parts: Vec<(usize, &Handle<Mesh>)> = children
+                .into_iter()
+                .flatten()
+                .filter_map(|&child| parts.get(child).ok())
+                .map(|(part, mesh)| (part.index, mesh))
+                .collect();
+            parts.sort_by_key(|&(index, _)| index);
+            parts.into_iter().map(|(_, mesh)| mesh).collect()
+        };
+        let (mut positions, mut indices, mut export_colors) = (vec![], vec![], vec![]);
+        for (handle, mesh_colors) in handles.into_iter().zip(colors.mesh_colors()) {
+            let Some(mesh) = meshes.get_mut(handle) else {
+                continue;
+            };
+            if mesh.count_vertices() != mesh_colors.len() {
+                continue;
+            }
+            if terrain.export {
+                // Parts are exported as one model
+                if let (
+                    Some(VertexAttributeValues::Float32x3(mesh_positions)),
+                    Some(Indices::U32(mesh_indices)),
+                ) = (mesh.attribute(Mesh::ATTRIBUTE_POSITION), mesh.indices())
+                {
+                    let offset = positions.len() as u32;
+                    positions.extend_from_slice(mesh_positions);
+                    indices.extend(mesh_indices.iter().map(|index| index + offset));
+                    export_colors.extend_from_slice(&mesh_colors);
+                }
+            }
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, mesh_colors);
+        }
+        if terrain.export {
+            export_model(&positions, indices, &export_colors);
+            terrain.bypass_change_detection().export = false;
+        }
+    }
+}
+
+/// Gives every `TerrainMeshPart` the `M` material of its terrain.
+/// Added for `StandardMaterial` by the `TerrainPlugin`, add it for custom terrain materials
+#[allow(clippy::needless_pass_by_value)]
+pub fn share_part_materials<M: Asset>(
+    mut commands: Commands,
+    terrains: Query<(&Handle<M>, &Children), With<Terrain>>,
+    parts: Query<Option<&Handle<M>>, With<TerrainMeshPart>>,
+) {
+    for (material, children) in &terrains {
+        for &child in children {
+            if let Ok(part_material) = parts.get(child) {
+                if part_material != Some(material) {
+                    commands.entity(child).insert(material.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Noise columns of a terrain with `Terrain::columns_per_frame` generated so far
+#[derive(Component, Default)]
+struct PendingNoise {
+    values: NoiseMap,
+    complete: bool,
+}
+
+impl PendingNoise {
+    /// Generates the next `columns` columns of the noise of `noise_size` at `offset`,
+    /// the modifiers are applied once every column is there
+    fn advance(&mut self, noise: &Noise, offset: [f64; 2], noise_size: [u32; 2], columns: u32) {
+        if self.complete {
+            return;
+        }
+        let total = noise_size[0] + 1;
+        if self.values.is_empty() {
+            self.values = NoiseMap::new(noise.precision);
+        }
+        let start = self.values.len() as u32;
+        let end = start.saturating_add(columns.max(1)).min(total);
+        self.values.extend_columns(
+            noise_size,
+            start..end,
+            noise.seed,
+            noise.scale,
+            offset,
+            &noise.method,
+            &noise.function,
+        );
+        if end == total {
+            noise.apply_modifiers(&mut self.values);
+            self.complete = true;
+        }
+    }
+
+    /// Fraction of the columns generated
+    fn progress(&self, noise_size: [u32; 2]) -> f32 {
+        self.values.len() as f32 / (noise_size[0] + 1) as f32
+    }
+}
+
 /// Plugin to generate terrain
 pub struct TerrainPlugin;
 
 impl Plugin for TerrainPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, generate_terrain);
+        app.add_event::<GenerationError>()
+            .configure_sets(
+                Update,
+                (TerrainSet::Generate, TerrainSet::Color, TerrainSet::Apply).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    generate_terrain.in_set(TerrainSet::Generate),
+                    // New terrains and their covers are inserted with commands
+                    apply_deferred
+                        .after(TerrainSet::Generate)
+                        .before(TerrainSet::Color),
+                    (
+                        (
+                            recolor_on_change::<BiomePalettes>,
+                            recolor_on_change::<BiomeMap>,
+                            recolor_on_change::<SplatMap>,
+                            recolor_on_change::<Beach>,
+                            recolor_on_change::<Territory>,
+                            recolor_on_change::<FogOfWar>,
+                            recolor_on_change::<DebugView>,
+                            recolor_on_change::<ClimateData>,
+                            recolor_on_change::<FlowData>,
+                        ),
+                        blend_palettes,
+                        blend_splat_maps,
+                        blend_snow,
+                        cover_beaches,
+                        blend_territories,
+                        darken_fog,
+                        show_debug_views,
+                    )
+                        .chain()
+                        .in_set(TerrainSet::Color),
+                    (
+                        apply_terrain_colors,
+                        share_part_materials::<StandardMaterial>,
+                    )
+                        .in_set(TerrainSet::Apply),
+                ),
+            );
+    }
+}
+
+/// Input components of `generate_terrain` besides `Terrain`, the terrain is generated again when one of them changes.
+/// Inputs that only change the colors recolor the terrain instead, see `TerrainColors`
+type InputChanged = Or<(
+    Changed<RoadNetwork>,
+    Changed<TerrainDeformation>,
+    Changed<Stamps>,
+    Changed<Snow>,
+    Changed<Lakes>,
+    Changed<TerrainHoles>,
+    Changed<HeightLayers>,
+    Changed<DataOnly>,
+)>;
+
+/// Changes to the inputs of `generate_terrain` since its last run
+#[derive(SystemParam)]
+struct InputChanges<'w, 's> {
+    changed: Query<'w, 's, (), InputChanged>,
+    // Missing without the render assets
+    image_events: Option<Res<'w, Events<AssetEvent<Image>>>>,
+    image_reader: Local<'s, ManualEventReader<AssetEvent<Image>>>,
+    road_networks: RemovedComponents<'w, 's, RoadNetwork>,
+    deformations: RemovedComponents<'w, 's, TerrainDeformation>,
+    stamps: RemovedComponents<'w, 's, Stamps>,
+    snow: RemovedComponents<'w, 's, Snow>,
+    lakes: RemovedComponents<'w, 's, Lakes>,
+    holes: RemovedComponents<'w, 's, TerrainHoles>,
+    height_layers: RemovedComponents<'w, 's, HeightLayers>,
+    data_only: RemovedComponents<'w, 's, DataOnly>,
+}
+
+impl InputChanges<'_, '_> {
+    /// Images loaded or modified
+    fn loaded_images(&mut self) -> Vec<AssetId<Image>> {
+        let Some(image_events) = &self.image_events else {
+            return vec![];
+        };
+        self.image_reader
+            .read(image_events)
+            .filter_map(|event| match event {
+                AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+                    Some(*id)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Entities that lost an input component
+    fn removed(&mut self) -> HashSet<Entity> {
+        let mut entities: HashSet<Entity> = self.road_networks.read().collect();
+        entities.extend(self.deformations.read());
+        entities.extend(self.stamps.read());
+        entities.extend(self.snow.read());
+        entities.extend(self.lakes.read());
+        entities.extend(self.holes.read());
+        entities.extend(self.height_layers.read());
+        entities.extend(self.data_only.read());
+        entities
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn generate_terrain(
-    mut images: ResMut<Assets<Image>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut query: Query<(&mut Terrain, &mut Handle<Mesh>, &Handle<StandardMaterial>)>,
+    mut commands: Commands,
+    mut errors: EventWriter<GenerationError>,
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<(
+        Entity,
+        Ref<Terrain>,
+        Option<&mut Handle<Mesh>>,
+        Option<&Handle<StandardMaterial>>,
+        Option<&mut GeneratedTerrain>,
+        Option<&mut TerrainColors>,
+        Option<&RoadNetwork>,
+        Option<&TerrainDeformation>,
+        Option<&Stamps>,
+        Option<&Snow>,
+        Option<&SnowCover>,
+        (Has<RestoredTerrain>, Has<FailedTerrain>),
+        (
+            Option<&mut GenerationProgress>,
+            Option<&mut PendingNoise>,
+            Option<&Children>,
+            Option<&Lakes>,
+            Option<&LakeMap>,
+            Option<&TerrainHoles>,
+            Option<&HeightLayers>,
+            Has<DataOnly>,
+        ),
+    )>,
+    mut parts: Query<(&TerrainMeshPart, &mut Handle<Mesh>), Without<Terrain>>,
+    mut inputs: InputChanges,
 ) {
-    for (mut terrain, mut mesh_handle, material) in &mut query {
-        if let Some(material) = materials.get_mut(material) {
-            *material = StandardMaterial::default();
+    // Stamps and layers sample no images without the render assets
+    let no_images = Assets::<Image>::default();
+    let removed = inputs.removed();
+    let loaded = inputs.loaded_images();
+    for (
+        entity,
+        terrain,
+        mesh_handle,
+        material,
+        terrain_data,
+        terrain_colors,
+        road_network,
+        deformation,
+        stamps,
+        snow,
+        snow_cover,
+        (restored, failed),
+        (progress, pending, children, lakes, lake_map, terrain_holes, height_layers, data_only),
+    ) in &mut query
+    {
+        // Every input of a restored terrain was just added, its data is still up to date
+        if restored {
+            commands.entity(entity).remove::<RestoredTerrain>();
+            continue;
+        }
+        // Stamps and layers skipped until their images load are applied once they do
+        let image_loaded = loaded.iter().any(|&id| {
+            stamps.is_some_and(|stamps| stamps.uses_image(id))
+                || height_layers.is_some_and(|height_layers| height_layers.uses_image(id))
+        });
+        let regenerate = terrain.is_changed()
+            || inputs.changed.contains(entity)
+            || removed.contains(&entity)
+            || (terrain_data.is_none() && !failed)
+            || pending.as_ref().is_some_and(|pending| !pending.complete)
+            || image_loaded;
+        if !regenerate {
+            continue;
+        }
+        if let Err(error) = terrain.check_generation() {
+            fail_terrain(
+                &mut commands,
+                &mut errors,
+                entity,
+                GenerationFailure::Config(error),
+            );
+            continue;
         }
-        terrain.noise.size = [
+        // Configurations that cannot be colored fail before the noise is sampled
+        let grad = match build_gradient(&terrain.noise.regions, &terrain.noise.gradient) {
+            Ok(grad) => grad,
+            Err(error) => {
+                fail_terrain(
+                    &mut commands,
+                    &mut errors,
+                    entity,
+                    GenerationFailure::Gradient(error.to_string()),
+                );
+                continue;
+            }
+        };
+        // Without the render assets only the data is generated
+        let data_only = data_only || meshes.is_none() || images.is_none();
+        let noise_size = [
             terrain.size[0] * terrain.resolution,
             terrain.size[1] * terrain.resolution,
         ];
-        let noise_values = generate_noise_map(&terrain.noise);
-
-        let mut colors: Vec<colorgrad::Color> = Vec::with_capacity(terrain.noise.regions.len());
-        let mut domain: Vec<f64> = Vec::with_capacity(terrain.noise.regions.len());
-        for region in &terrain.noise.regions {
-            colors.push(colorgrad::Color {
-                r: f64::from(region.color[0]) / 255.0,
-                g: f64::from(region.color[1]) / 255.0,
-                b: f64::from(region.color[2]) / 255.0,
-                a: f64::from(region.color[3]) / 255.0,
-            });
-            domain.push(region.position);
-        }
-        let mut grad = colorgrad::CustomGradient::new()
-            .colors(&colors)
-            .domain(&domain)
-            .build()
-            .unwrap_or_else(|_| {
-                colorgrad::CustomGradient::new()
-                    .colors(&colors)
-                    .build()
-                    .expect("Gradient generation failed")
-            });
+        let mut noise_values = if let Some(columns) = terrain.columns_per_frame {
+            let mut inserted = PendingNoise::default();
+            let is_new = pending.is_none();
+            let pending = pending.map_or(&mut inserted, Mut::into_inner);
+            if terrain.is_changed() {
+                *pending = PendingNoise::default();
+            }
+            pending.advance(
+                &terrain.instance_noise(),
+                terrain.noise_offset(),
+                noise_size,
+                columns,
+            );
+            let update = if pending.complete {
+                GenerationProgress::done()
+            } else {
+                GenerationProgress {
+                    progress: pending.progress(noise_size),
+                    stage: STAGE_NOISE.to_string(),
+                }
+            };
+            let noise_values = pending.complete.then(|| pending.values.clone());
+            if is_new {
+                commands.entity(entity).insert(inserted);
+            }
+            match progress {
+                Some(mut progress) => {
+                    progress.set_if_neq(update);
+                }
+                None => {
+                    commands.entity(entity).insert(update);
+                }
+            }
+            // The previous mesh stays until every column is generated
+            let Some(noise_values) = noise_values else {
+                continue;
+            };
+            noise_values
+        } else {
+            if let Some(mut progress) = progress {
+                progress.set_if_neq(GenerationProgress::done());
+            }
+            generate_noise_map_at(
+                &terrain.instance_noise(),
+                noise_size,
+                terrain.noise_offset(),
+            )
+        };
 
-        if terrain.noise.gradient.segments != 0 {
-            grad = grad.sharp(
-                terrain.noise.gradient.segments,
-                terrain.noise.gradient.smoothness,
+        if let Some(height_layers) = height_layers {
+            height_layers.blend_noise(
+                &mut noise_values,
+                &terrain,
+                images.as_deref().unwrap_or(&no_images),
             );
         }
 
-        let mut gradient_buffer = image::ImageBuffer::from_pixel(
-            terrain.noise.gradient.size[0],
-            terrain.noise.gradient.size[1],
-            image::Rgba(terrain.noise.base_color),
-        );
-
-        for (x, _, pixel) in gradient_buffer.enumerate_pixels_mut() {
-            let rgba = grad
-                .at(f64::from(x) * 100.0 / f64::from(terrain.noise.gradient.size[0]))
-                .to_rgba8();
-            pixel.blend(&image::Rgba(rgba));
+        let (gradient, gradient_lut) = match images.as_deref_mut().filter(|_| !data_only) {
+            Some(images) => match gradient_images(&terrain, &grad, terrain_data.as_deref(), images)
+            {
+                Ok(handles) => handles,
+                Err(reason) => {
+                    fail_terrain(&mut commands, &mut errors, entity, reason);
+                    continue;
+                }
+            },
+            None => (Handle::default(), Handle::default()),
+        };
+        if let Some(material) = materials
+            .as_deref_mut()
+            .zip(material)
+            .and_then(|(materials, material)| materials.get_mut(material))
+            .filter(|_| !data_only)
+        {
+            *material = StandardMaterial::default();
         }
 
-        terrain.noise.gradient.image = images.add(
-            Image::from_dynamic(gradient_buffer.into(), true)
-                .convert(TextureFormat::Rgba8UnormSrgb)
-                .expect("Could not convert to Rgba8UnormSrgb"),
-        );
-
-        let vertices_count: usize =
-            ((terrain.noise.size[0] + 1) * (terrain.noise.size[1] + 1)) as usize;
-        let triangle_count: usize =
-            (terrain.noise.size[0] * terrain.noise.size[1] * 2 * 3) as usize;
-
-        let mut positions: Vec<[f32; 3]> = Vec::with_capacity(vertices_count);
-        let mut normals: Vec<[f32; 3]> = Vec::with_capacity(vertices_count);
-        let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(vertices_count);
-        let mut indices: Vec<u32> = Vec::with_capacity(triangle_count);
-        let mut colors: Vec<[f32; 4]> = Vec::with_capacity(vertices_count);
-
-        let rows = terrain.size[0] * terrain.resolution + 1;
         let cols = terrain.size[1] * terrain.resolution + 1;
-        let width = terrain.size[0] as f32 + 1.0;
-        let depth = terrain.size[1] as f32 + 1.0;
-        for row in 0..rows {
-            for col in 0..cols {
-                let row = row as f32;
-                let col = col as f32;
-                let noise_value = noise_values[row as usize][col as usize] as f32;
-                let height_value = (0_f32.max(noise_value - terrain.sea_percent)) / 100.0;
-                let x = (row / terrain.resolution as f32 - width / 2.0) + 0.5;
-                let y = ((height_value * 1.2).powf(terrain.height_exponent) - 0.5) * 2.0;
-                let z = (col / terrain.resolution as f32 - depth / 2.0) + 0.5;
-
-                let color = grad.at(noise_values[row as usize][col as usize]);
-                let color = [
+        let mut heights = terrain.heights(&noise_values);
+        let resolution = terrain.resolution.max(1) as f32;
+        let colors: Vec<[f32; 4]> = noise_values
+            .columns()
+            .flatten()
+            .map(|noise_value| {
+                let color = grad.at(noise_value);
+                [
                     color.r as f32,
                     color.g as f32,
                     color.b as f32,
                     color.a as f32,
-                ];
+                ]
+            })
+            .collect();
 
-                positions.push([x, y, z]);
-                normals.push([0.0, 1.0, 0.0]);
-                uvs.push([row, col]);
-                colors.push(color);
+        if let Some(height_layers) = height_layers {
+            height_layers.blend_stamps(
+                &mut heights,
+                &terrain,
+                images.as_deref().unwrap_or(&no_images),
+            );
+        }
+        if let Some(stamps) = stamps {
+            stamps.apply(
+                &mut heights,
+                terrain.resolution,
+                terrain.size,
+                images.as_deref().unwrap_or(&no_images),
+            );
+        }
+        let mut base_heights = heights.clone();
+        if let Some(road_network) = road_network {
+            road_network.flatten(&mut heights, terrain.resolution, terrain.size);
+        }
+        // Deformations apply to roads as well, so craters also cut into them
+        if let Some(deformation) = deformation {
+            deformation.apply(&mut base_heights);
+            deformation.apply(&mut heights);
+        }
+        let sea_level = terrain.generated_sea_level();
+        if let Some(snow) = snow {
+            let coverage = snow.coverage(&heights, terrain.resolution, sea_level);
+            for (column, cover) in heights.iter_mut().zip(&coverage) {
+                for (height, cover) in column.iter_mut().zip(cover) {
+                    *height += snow.depth * cover;
+                }
+            }
+            let new = SnowCover { coverage };
+            if snow_cover != Some(&new) {
+                commands.entity(entity).insert(new);
             }
         }
-
-        for i in 0..(rows - 1) {
-            for j in 0..(cols - 1) {
-                let current = i * cols + j;
-                let next_row = (i + 1) * cols + j;
-
-                // Triangle 1
-                indices.push(current);
-                indices.push(current + 1);
-                indices.push(next_row);
-
-                // Triangle 2
-                indices.push(next_row);
-                indices.push(current + 1);
-                indices.push(next_row + 1);
+        let lift = terrain.lift(&heights, sea_level);
+        if lift != 0.0 {
+            for height in heights.iter_mut().chain(&mut base_heights).flatten() {
+                *height += lift;
             }
         }
-
-        if terrain.wireframe {
-            let triangle_number = indices.len() / 3;
-            let cloned_indices = indices.clone();
-            indices = vec![];
-            for i in 0..triangle_number {
-                for j in &[0, 1, 1, 2, 2, 0] {
-                    indices.push(cloned_indices[i * 3 + j]);
+        let sea_level = sea_level + lift;
+        let new_lake_map = lakes.map(|lakes| LakeMap::from_heights(&heights, sea_level, lakes));
+        let world_size = terrain
+            .world_size
+            .unwrap_or_else(|| Vec2::new(terrain.size[0] as f32, terrain.size[1] as f32));
+        let center = match terrain.anchor {
+            Anchor::Center => Vec2::ZERO,
+            Anchor::Corner => world_size / 2.0,
+            Anchor::Offset(offset) => offset.xz(),
+        };
+        let half = Vec2::new(terrain.size[0] as f32, terrain.size[1] as f32) / 2.0;
+        let stretch = world_size / (half * 2.0).max(Vec2::ONE);
+        let holes = terrain_holes.map_or_else(Vec::new, |terrain_holes| {
+            terrain_holes.mask(heights.len() - 1, cols as usize - 1, |i, j| {
+                (Vec2::new(i as f32 + 0.5, j as f32 + 0.5) / resolution - half) * stretch + center
+            })
+        });
+        let mut regions: Vec<Vec<usize>> = noise_values
+            .columns()
+            .map(|column| {
+                column
+                    .map(|value| terrain.noise.region_index(value).unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        if let (Some(lakes), Some(new_lake_map)) = (lakes, new_lake_map) {
+            if let Some(lake_region) = lakes.region {
+                for (column, lake_column) in regions.iter_mut().zip(&new_lake_map.cells) {
+                    for (region, lake) in column.iter_mut().zip(lake_column) {
+                        if lake.is_some() {
+                            *region = lake_region;
+                        }
+                    }
                 }
             }
+            if lake_map != Some(&new_lake_map) {
+                commands.entity(entity).insert(new_lake_map);
+            }
         }
+        let detail_normals = match (meshes.as_deref_mut(), images.as_deref_mut()) {
+            (Some(meshes), Some(images)) if !data_only => {
+                let mut grid = GridMesh::new(&heights, terrain.resolution, terrain.size);
+                let mut layout = MeshLayout::default();
+                if terrain.simplification.is_some() || terrain.adaptive_resolution.is_some() {
+                    let criteria = MergeCriteria {
+                        tolerance: terrain.simplification,
+                        max_angle: terrain.adaptive_resolution.map(f32::to_radians),
+                    };
+                    layout.simplified = Some(grid.simplify(heights.len(), cols as usize, criteria));
+                }
+                if terrain.world_size.is_some() {
+                    grid.scale([
+                        world_size.x / terrain.size[0].max(1) as f32,
+                        1.0,
+                        world_size.y / terrain.size[1].max(1) as f32,
+                    ]);
+                }
+                if center != Vec2::ZERO {
+                    grid.translate([center.x, 0.0, center.y]);
+                }
+                if holes.iter().flatten().any(|&hole| hole) {
+                    // Simplified triangles spanning several cells are cut by the cell of their centroid
+                    let positions = &grid.positions;
+                    grid.indices = grid
+                        .indices
+                        .chunks_exact(3)
+                        .filter(|triangle| {
+                            let centroid = triangle
+                                .iter()
+                                .map(|&index| Vec3::from(positions[index as usize]).xz())
+                                .sum::<Vec2>()
+                                / 3.0;
+                            let cell =
+                                (((centroid - center) / stretch + half) * resolution).floor();
+                            !holes
+                                .get(cell.x as usize)
+                                .and_then(|column| column.get(cell.y as usize))
+                                .is_some_and(|&hole| hole)
+                        })
+                        .flatten()
+                        .copied()
+                        .collect();
+                }
+                grid.uvs = terrain.uv_mode.uvs(&grid, &regions);
+                if terrain.flat_shading {
+                    layout.flat = Some(grid.flat_shade());
+                }
+                if terrain.wireframe {
+                    grid.wireframe();
+                }
+
+                let detail_normals = terrain.detail_normals.as_ref().map(|detail_normals| {
+                    let normal_map = detail_normals.normal_map(&terrain, world_size);
+                    match terrain_data
+                        .as_ref()
+                        .and_then(|data| data.detail_normals.clone())
+                    {
+                        Some(handle)
+                            if images.get(&handle).map(|image| &image.data)
+                                == Some(&normal_map.data) =>
+                        {
+                            handle
+                        }
+                        _ => images.add(normal_map),
+                    }
+                });
+                if let Some(detail_normals) = &detail_normals {
+                    if terrain.uv_mode == UvMode::Normalized {
+                        if let Some(material) = materials
+                            .as_deref_mut()
+                            .zip(material)
+                            .and_then(|(materials, material)| materials.get_mut(material))
+                        {
+                            material.normal_map_texture = Some(detail_normals.clone());
+                        }
+                    }
+                }
+                // Colors are written in `TerrainSet::Apply`
+                let build_mesh = |grid: &GridMesh| {
+                    let mut mesh = if terrain.wireframe {
+                        Mesh::new(PrimitiveTopology::LineList)
+                    } else {
+                        Mesh::new(PrimitiveTopology::TriangleList)
+                    };
+                    mesh.set_indices(Some(Indices::U32(grid.indices.clone())));
+                    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, grid.positions.clone());
+                    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, grid.normals.clone());
+                    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, grid.uvs.clone());
+                    if detail_normals.is_some() && !terrain.wireframe {
+                        if let Err(error) = mesh.generate_tangents() {
+                            warn!("Could not generate tangents for detail normals: {error}");
+                        }
+                    }
+                    mesh
+                };
+                let split = terrain
+                    .max_vertices
+                    .filter(|&max_vertices| grid.positions.len() > max_vertices as usize)
+                    .map(|max_vertices| {
+                        let primitive = if terrain.wireframe { 2 } else { 3 };
+                        grid.split(max_vertices as usize, primitive)
+                    })
+                    .unwrap_or_default();
+                let mut existing: Vec<(usize, Entity)> = children
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|&child| parts.get(child).ok().map(|(part, _)| (part.index, child)))
+                    .collect();
+                if split.is_empty() {
+                    if let Some(mut mesh_handle) = mesh_handle {
+                        *mesh_handle = meshes.add(build_mesh(&grid));
+                    }
+                } else {
+                    // The parts render the terrain, the entity keeps no mesh of its own
+                    if let Some(mut mesh_handle) = mesh_handle {
+                        *mesh_handle = Handle::default();
+                    }
+                    for (index, (part, _)) in split.iter().enumerate() {
+                        let handle = meshes.add(build_mesh(part));
+                        match existing
+                            .iter()
+                            .position(|&(part_index, _)| part_index == index)
+                        {
+                            Some(position) => {
+                                let (_, child) = existing.swap_remove(position);
+                                if let Ok((_, mut part_mesh)) = parts.get_mut(child) {
+                                    *part_mesh = handle;
+                                }
+                            }
+                            None => {
+                                commands
+                                    .spawn((
+                                        handle,
+                                        SpatialBundle::default(),
+                                        TerrainMeshPart { index },
+                                    ))
+                                    .set_parent(entity);
+                            }
+                        }
+                    }
+                }
+                for (_, child) in existing {
+                    commands.entity(child).despawn_recursive();
+                }
+                layout.parts = split.into_iter().map(|(_, sources)| sources).collect();
+
+                if let Some(mut terrain_colors) = terrain_colors {
+                    terrain_colors.base = colors;
+                    terrain_colors.layout = layout;
+                    terrain_colors.recolor();
+                } else {
+                    let mut terrain_colors = TerrainColors {
+                        base: colors,
+                        layout,
+                        ..default()
+                    };
+                    terrain_colors.recolor();
+                    commands.entity(entity).insert(terrain_colors);
+                }
+                detail_normals
+            }
+            _ => {
+                if terrain_colors.is_some() {
+                    commands.entity(entity).remove::<TerrainColors>();
+                }
+                None
+            }
+        };
 
-        let mut mesh = if terrain.wireframe {
-            Mesh::new(PrimitiveTopology::LineList)
+        let stats = TerrainStats::new(&heights, &regions, terrain.noise.regions.len(), sea_level);
+        let data = GeneratedTerrain {
+            heights,
+            base_heights,
+            noise: noise_values,
+            resolution: terrain.resolution,
+            size: terrain.size,
+            world_size,
+            center,
+            sea_level,
+            stats,
+            regions,
+            gradient,
+            gradient_lut,
+            detail_normals,
+            holes,
+        };
+        if let Some(mut terrain_data) = terrain_data {
+            terrain_data.set_if_neq(data);
         } else {
-            Mesh::new(PrimitiveTopology::TriangleList)
+            commands.entity(entity).insert(data);
+        }
+        if failed {
+            commands.entity(entity).remove::<FailedTerrain>();
+        }
+    }
+}
+
+/// Fails the generation of `entity` and marks it, so it is not generated again every frame
+/// Gradient texture and lookup table of a terrain, reusing the handles of `terrain_data` if their images are unchanged
+fn gradient_images(
+    terrain: &Terrain,
+    grad: &colorgrad::Gradient,
+    terrain_data: Option<&GeneratedTerrain>,
+    images: &mut Assets<Image>,
+) -> Result<(Handle<Image>, Handle<Image>), GenerationFailure> {
+    let mut gradient_buffer = image::ImageBuffer::from_pixel(
+        terrain.noise.gradient.size[0],
+        terrain.noise.gradient.size[1],
+        image::Rgba(terrain.noise.base_color),
+    );
+
+    for (x, _, pixel) in gradient_buffer.enumerate_pixels_mut() {
+        let rgba = grad
+            .at(f64::from(x) * 100.0 / f64::from(terrain.noise.gradient.size[0]))
+            .to_rgba8();
+        pixel.blend(&image::Rgba(rgba));
+    }
+
+    let gradient_image = Image::from_dynamic(gradient_buffer.into(), true)
+        .convert(TextureFormat::Rgba8UnormSrgb)
+        .ok_or(GenerationFailure::Texture)?;
+    let gradient = match terrain_data.map(|data| data.gradient.clone()) {
+        Some(handle)
+            if images.get(&handle).map(|image| &image.data) == Some(&gradient_image.data) =>
+        {
+            handle
+        }
+        _ => images.add(gradient_image),
+    };
+    let lut = terrain.noise.gradient_lut(GRADIENT_LUT_WIDTH)?;
+    let gradient_lut = match terrain_data.map(|data| data.gradient_lut.clone()) {
+        Some(handle) if images.get(&handle).map(|image| &image.data) == Some(&lut.data) => handle,
+        _ => images.add(lut),
+    };
+    Ok((gradient, gradient_lut))
+}
+
+fn fail_terrain(
+    commands: &mut Commands,
+    errors: &mut EventWriter<GenerationError>,
+    entity: Entity,
+    reason: GenerationFailure,
+) {
+    fail(errors, entity, reason);
+    commands.entity(entity).insert(FailedTerrain);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOPE: f32 = 0.5;
+
+    /// Ramp rising along `x` on a stretched grid that is not centered on the entity
+    fn ramp() -> GeneratedTerrain {
+        let mut terrain_data = GeneratedTerrain {
+            resolution: 2,
+            size: [2, 3],
+            world_size: Vec2::new(8.0, 3.0),
+            center: Vec2::new(1.0, -2.0),
+            heights: vec![vec![0.0; 7]; 5],
+            ..default()
         };
-        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-        *mesh_handle = meshes.add(mesh);
+        for i in 0..5 {
+            for j in 0..7 {
+                let x = terrain_data.vertex_position(i, j).unwrap().x;
+                terrain_data.heights[i][j] = x * SLOPE;
+            }
+        }
+        terrain_data.stats = TerrainStats::new(&terrain_data.heights, &[], 0, f32::MIN);
+        terrain_data
+    }
 
-        if terrain.export {
-            export_model(&positions, indices, &colors);
-            terrain.export = false;
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(
+            actual.distance(expected) < 1e-4,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn rays_hit_ramps_at_their_height() {
+        let terrain_data = ramp();
+        let normal = Vec3::new(-SLOPE, 1.0, 0.0).normalize();
+        for point in [
+            Vec2::new(-2.9, -3.4),
+            Vec2::new(0.3, -1.7),
+            Vec2::new(4.9, -0.6),
+        ] {
+            let hit = terrain_data
+                .raycast(Ray {
+                    origin: Vec3::new(point.x, 10.0, point.y),
+                    direction: Vec3::NEG_Y,
+                })
+                .unwrap();
+            assert_near(hit.position, Vec3::new(point.x, point.x * SLOPE, point.y));
+            assert_near(hit.normal, normal);
+            assert!((hit.distance - point.x.mul_add(-SLOPE, 10.0)).abs() < 1e-4);
+        }
+
+        // Across several cells from the high end
+        let target = Vec3::new(-1.5, -1.5 * SLOPE, -1.0);
+        let origin = Vec3::new(6.0, 8.0, -3.0);
+        let hit = terrain_data
+            .raycast(Ray {
+                origin,
+                direction: target - origin,
+            })
+            .unwrap();
+        assert_near(hit.position, target);
+        assert!((hit.distance - origin.distance(target)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rays_beside_or_away_from_ramps_miss() {
+        let terrain_data = ramp();
+        for ray in [
+            Ray {
+                origin: Vec3::new(6.0, 10.0, -2.0),
+                direction: Vec3::NEG_Y,
+            },
+            Ray {
+                origin: Vec3::new(0.0, 10.0, 0.0),
+                direction: Vec3::NEG_Y,
+            },
+            Ray {
+                origin: Vec3::new(0.0, 10.0, -2.0),
+                direction: Vec3::Y,
+            },
+            Ray {
+                origin: Vec3::new(-4.0, 10.0, -2.0),
+                direction: Vec3::X,
+            },
+        ] {
+            assert_eq!(terrain_data.raycast(ray), None, "{ray:?}");
         }
     }
+
+    #[test]
+    fn rays_pass_through_holes() {
+        let mut terrain_data = ramp();
+        terrain_data.holes = vec![vec![false; 7]; 5];
+        terrain_data.holes[2][3] = true;
+        let ray = |x: f32, z: f32| Ray {
+            origin: Vec3::new(x, 10.0, z),
+            direction: Vec3::NEG_Y,
+        };
+        // Cell 2, 3 spans 1 to 3 along x and -2 to -1.5 along z
+        assert!(terrain_data.is_hole(2.0, -1.75));
+        assert_eq!(terrain_data.raycast(ray(2.0, -1.75)), None);
+        let hit = terrain_data.raycast(ray(2.0, -1.25)).unwrap();
+        assert_near(hit.position, Vec3::new(2.0, 2.0 * SLOPE, -1.25));
+    }
 }
@@ -0,0 +1,127 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::alpha_discard,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+struct TerrainLayer {
+    color: vec4<f32>,
+    height: vec2<f32>,
+    slope: vec2<f32>,
+    blend: vec2<f32>,
+}
+
+struct TerrainMaterialSettings {
+    layers: array<TerrainLayer, 4>,
+    layer_count: u32,
+    tile_size: f32,
+    splat_strength: f32,
+    bounds: vec4<f32>,
+    detail_normals: u32,
+}
+
+@group(1) @binding(100) var<uniform> terrain: TerrainMaterialSettings;
+@group(1) @binding(101) var heightmap: texture_2d<f32>;
+@group(1) @binding(102) var splat_texture: texture_2d<f32>;
+@group(1) @binding(103) var splat_sampler: sampler;
+@group(1) @binding(104) var layer_texture_0: texture_2d<f32>;
+@group(1) @binding(105) var layer_sampler_0: sampler;
+@group(1) @binding(106) var layer_texture_1: texture_2d<f32>;
+@group(1) @binding(107) var layer_sampler_1: sampler;
+@group(1) @binding(108) var layer_texture_2: texture_2d<f32>;
+@group(1) @binding(109) var layer_sampler_2: sampler;
+@group(1) @binding(110) var layer_texture_3: texture_2d<f32>;
+@group(1) @binding(111) var layer_sampler_3: sampler;
+@group(1) @binding(112) var detail_texture: texture_2d<f32>;
+@group(1) @binding(113) var detail_sampler: sampler;
+
+fn height_at(texel: vec2<i32>) -> f32 {
+    let last = vec2<i32>(textureDimensions(heightmap)) - 1;
+    return textureLoad(heightmap, clamp(texel, vec2<i32>(0), last), 0).r;
+}
+
+// Heights are bilinearly interpolated between texels like `GeneratedTerrain::height`
+fn sample_height(uv: vec2<f32>) -> f32 {
+    let position = clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)) * vec2<f32>(textureDimensions(heightmap) - 1u);
+    let base = vec2<i32>(floor(position));
+    let t = fract(position);
+    let near = mix(height_at(base), height_at(base + vec2<i32>(1, 0)), t.x);
+    let far = mix(height_at(base + vec2<i32>(0, 1)), height_at(base + vec2<i32>(1, 1)), t.x);
+    return mix(near, far, t.y);
+}
+
+fn range_weight(value: f32, range: vec2<f32>, blend: f32) -> f32 {
+    let width = max(blend, 0.0001);
+    return smoothstep(range.x - width, range.x, value) * (1.0 - smoothstep(range.y, range.y + width, value));
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    let uv = (in.world_position.xz - terrain.bounds.xy) / terrain.bounds.zw;
+    let cells = vec2<f32>(max(textureDimensions(heightmap), vec2<u32>(2u)) - 1u);
+    let texel = 1.0 / cells;
+    let spacing = terrain.bounds.zw / cells;
+    let height = sample_height(uv);
+    let dx = (sample_height(uv + vec2<f32>(texel.x, 0.0)) - sample_height(uv - vec2<f32>(texel.x, 0.0))) / (2.0 * spacing.x);
+    let dz = (sample_height(uv + vec2<f32>(0.0, texel.y)) - sample_height(uv - vec2<f32>(0.0, texel.y))) / (2.0 * spacing.y);
+    var normal = normalize(vec3<f32>(-dx, 1.0, -dz));
+    let slope = acos(clamp(normal.y, -1.0, 1.0));
+
+    // Every texture is sampled in uniform control flow, before the loop over the layers
+    let tiled = in.world_position.xz / max(terrain.tile_size, 0.0001);
+    var samples = array<vec4<f32>, 4>(
+        textureSample(layer_texture_0, layer_sampler_0, tiled),
+        textureSample(layer_texture_1, layer_sampler_1, tiled),
+        textureSample(layer_texture_2, layer_sampler_2, tiled),
+        textureSample(layer_texture_3, layer_sampler_3, tiled),
+    );
+    // Detail normals only light the surface, the slope of the layers stays that of the heightmap
+    let detail = textureSample(detail_texture, detail_sampler, clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0))).xy * 2.0 - 1.0;
+    if terrain.detail_normals != 0u {
+        normal = normalize(vec3<f32>(normal.x + detail.x, normal.y, normal.z + detail.y));
+    }
+    let splat = textureSample(splat_texture, splat_sampler, clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0))) * terrain.splat_strength;
+
+    var color = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var i = 0u; i < min(terrain.layer_count, 4u); i += 1u) {
+        let layer = terrain.layers[i];
+        let weight = range_weight(height, layer.height, layer.blend.x) * range_weight(slope, layer.slope, layer.blend.y) + splat[i];
+        color += layer.color.rgb * samples[i].rgb * weight;
+        total += weight;
+    }
+    // Without a matching layer the vertex colors of the terrain are kept
+    if total > 0.0 {
+        pbr_input.material.base_color = vec4<f32>(color / total, pbr_input.material.base_color.a);
+    }
+    pbr_input.N = normal;
+    pbr_input.world_normal = normal;
+
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(in, pbr_input);
+#else
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+
+    return out;
+}
@@ -0,0 +1,419 @@
+//! Color terrain and maps by territory ownership
+//! # Example
+//! For configuration, see [`Territory`](struct.Territory.html) and [`ClaimTerritory`](struct.ClaimTerritory.html).
+//! Terrain is tinted by the owner of every vertex. On a `Map`, the overlay is sized to the map cells and
+//! `Territory::texture` can be displayed on top of it
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//! use bevy_generative::territory::{ClaimTerritory, Faction, Territory, TerritoryPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, TerritoryPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, expand)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     let mut territory = Territory::default();
+//!     territory.factions = vec![
+//!         Faction {
+//!             name: "Red".to_string(),
+//!             color: [220, 40, 40, 255],
+//!         },
+//!         Faction {
+//!             name: "Blue".to_string(),
+//!             color: [40, 80, 220, 255],
+//!         },
+//!     ];
+//!     commands.spawn((TerrainBundle::default(), territory));
+//! }
+//!
+//! fn expand(mut claims: EventWriter<ClaimTerritory>, time: Res<Time>) {
+//!     let radius = (time.elapsed_seconds() * 0.05).min(0.8);
+//!     claims.send(ClaimTerritory {
+//!         position: Vec3::new(-0.5, 0.0, 0.0),
+//!         radius,
+//!         owner: Some(0),
+//!     });
+//!     claims.send(ClaimTerritory {
+//!         position: Vec3::new(0.5, 0.0, 0.0),
+//!         radius,
+//!         owner: Some(1),
+//!     });
+//! }
+//! ```
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "map")]
+use crate::map::Map;
+use crate::terrain::{GeneratedTerrain, TerrainColors, TerrainSet};
+
+/// Owner of cells of a `Territory`
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Faction {
+    /// Name of the faction
+    pub name: String,
+    /// Color of the cells owned by the faction
+    pub color: [u8; 4],
+}
+
+impl Default for Faction {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            color: [255; 4],
+        }
+    }
+}
+
+/// Component for a territory overlay, added to an entity with a `Terrain` or `Map` component.
+/// The owners serialize with it, so ownership is saved along with the other components
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Territory {
+    /// Factions, indexed by the owner IDs
+    pub factions: Vec<Faction>,
+    /// Number of cells per world unit on terrain, maps have one cell per map cell
+    pub resolution: u32,
+    /// Opacity of the color inside owned territory, 0 to 1
+    pub opacity: f32,
+    /// Opacity of the color at borders between territories, 0 to 1
+    pub border_opacity: f32,
+    /// Width of the borders in cells
+    pub border_width: u32,
+    /// Number of cells along `x` and `z`, set from the size of the terrain or map
+    pub size: [u32; 2],
+    /// Owner of every cell, rows along `x` ordered by `z`
+    pub owners: Vec<Option<u32>>,
+    /// Texture of the overlay, updated as ownership changes
+    #[serde(skip)]
+    pub texture: Handle<Image>,
+    /// Lower and upper corner of the cells changed since the texture was updated
+    #[serde(skip)]
+    changed: Option<[[usize; 2]; 2]>,
+}
+
+impl Default for Territory {
+    fn default() -> Self {
+        Self {
+            factions: vec![],
+            resolution: 32,
+            opacity: 0.3,
+            border_opacity: 0.9,
+            border_width: 1,
+            size: [0; 2],
+            owners: vec![],
+            texture: Handle::default(),
+            changed: None,
+        }
+    }
+}
+
+impl Territory {
+    /// Owner of the cell at `i`, `j`, `None` if unowned or outside of the overlay
+    #[must_use]
+    pub fn owner(&self, i: usize, j: usize) -> Option<u32> {
+        if i >= self.size[0] as usize {
+            return None;
+        }
+        self.owners
+            .get(j * self.size[0] as usize + i)
+            .copied()
+            .flatten()
+    }
+
+    /// Owner of the cell at `x`, `z` on the overlay of a terrain of `terrain_size`, see `GeneratedTerrain::to_overlay`
+    #[must_use]
+    pub fn owner_at(&self, x: f32, z: f32, terrain_size: [u32; 2]) -> Option<u32> {
+        let (i, j) = self.cell(x, z, terrain_size)?;
+        self.owner(i, j)
+    }
+
+    /// Sets the owner of the cell at `i`, `j`, the texture is updated around changed cells only
+    pub fn set_owner(&mut self, i: usize, j: usize, owner: Option<u32>) {
+        let columns = self.size[0] as usize;
+        if i >= columns || self.owners.get(j * columns + i) == Some(&owner) {
+            return;
+        }
+        let Some(cell) = self.owners.get_mut(j * columns + i) else {
+            return;
+        };
+        *cell = owner;
+        self.changed = Some(self.changed.map_or([[i, j]; 2], |[min, max]| {
+            [
+                [min[0].min(i), min[1].min(j)],
+                [max[0].max(i), max[1].max(j)],
+            ]
+        }));
+    }
+
+    /// Sets the owner of the cells within `radius` of `center` on the overlay of a terrain of `terrain_size`
+    pub fn claim_circle(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        owner: Option<u32>,
+        terrain_size: [u32; 2],
+    ) {
+        let resolution = self.resolution.max(1) as f32;
+        let half = Vec2::new(terrain_size[0] as f32, terrain_size[1] as f32) / 2.0;
+        let [columns, rows] = self.size.map(|side| side as usize);
+        let min = ((center - radius + half) * resolution)
+            .floor()
+            .max(Vec2::ZERO);
+        let max = ((center + radius + half) * resolution).ceil();
+        if columns == 0 || rows == 0 || max.x < 0.0 || max.y < 0.0 {
+            return;
+        }
+        for j in min.y as usize..=(max.y as usize).min(rows - 1) {
+            for i in min.x as usize..=(max.x as usize).min(columns - 1) {
+                let point = Vec2::new(i as f32, j as f32) / resolution - half;
+                if point.distance(center) <= radius {
+                    self.set_owner(i, j, owner);
+                }
+            }
+        }
+    }
+
+    /// `color` with the overlay at `x`, `z` blended over it
+    pub(crate) fn blend(
+        &self,
+        color: [f32; 4],
+        x: f32,
+        z: f32,
+        terrain_size: [u32; 2],
+    ) -> [f32; 4] {
+        let Some((i, j)) = self.cell(x, z, terrain_size) else {
+            return color;
+        };
+        let overlay = Vec4::from(self.texel(i, j).map(|channel| f32::from(channel) / 255.0));
+        let blended = Vec4::from(color).lerp(overlay.xyz().extend(color[3]), overlay.w);
+        blended.to_array()
+    }
+
+    fn cell(&self, x: f32, z: f32, terrain_size: [u32; 2]) -> Option<(usize, usize)> {
+        let resolution = self.resolution.max(1) as f32;
+        let i = ((x + terrain_size[0] as f32 / 2.0) * resolution).round();
+        let j = ((z + terrain_size[1] as f32 / 2.0) * resolution).round();
+        (i >= 0.0 && j >= 0.0).then_some((i as usize, j as usize))
+    }
+
+    /// Color of the overlay at `i`, `j`, stronger within `border_width` of a differently owned cell
+    fn texel(&self, i: usize, j: usize) -> [u8; 4] {
+        let owner = self.owner(i, j);
+        let Some(color) = owner.and_then(|owner| self.factions.get(owner as usize)) else {
+            return [0; 4];
+        };
+        let width = self.border_width as usize;
+        let [columns, rows] = self.size.map(|side| side as usize);
+        let border = width > 0
+            && (j.saturating_sub(width)..=(j + width).min(rows - 1)).any(|nj| {
+                (i.saturating_sub(width)..=(i + width).min(columns - 1))
+                    .any(|ni| self.owner(ni, nj) != owner)
+            });
+        let opacity = if border {
+            self.border_opacity
+        } else {
+            self.opacity
+        };
+        let alpha = f32::from(color.color[3]) * opacity.clamp(0.0, 1.0);
+        [
+            color.color[0],
+            color.color[1],
+            color.color[2],
+            alpha.round() as u8,
+        ]
+    }
+
+    /// Resizes the overlay to `size` cells, clearing the owners if the size changed
+    fn fit(&mut self, size: [u32; 2]) -> bool {
+        let cells = (size[0] * size[1]) as usize;
+        if self.size == size && self.owners.len() == cells {
+            return false;
+        }
+        self.size = size;
+        self.owners = vec![None; cells];
+        true
+    }
+
+    fn to_image(&self) -> Image {
+        let [columns, rows] = self.size.map(|side| side as usize);
+        Image::new(
+            Extent3d {
+                width: self.size[0],
+                height: self.size[1],
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            (0..rows)
+                .flat_map(|j| (0..columns).flat_map(move |i| self.texel(i, j)))
+                .collect(),
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+}
+
+/// Event to set the owner of the cells around a position on every terrain with a `Territory`
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub struct ClaimTerritory {
+    /// Center of the claimed area in world space, only `x` and `z` are used
+    pub position: Vec3,
+    /// Radius of the claimed area in world units
+    pub radius: f32,
+    /// New owner, `None` to release the cells
+    pub owner: Option<u32>,
+}
+
+/// Plugin to update territory overlays
+pub struct TerritoryPlugin;
+
+impl Plugin for TerritoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClaimTerritory>().add_systems(
+            Update,
+            (prepare_territories, claim_territories, update_territories)
+                .chain()
+                .before(TerrainSet::Color),
+        );
+        #[cfg(feature = "map")]
+        app.add_systems(Update, prepare_map_territories.before(update_territories));
+    }
+}
+
+/// Sizes overlays to their terrain and creates their textures, e.g. after an overlay is loaded.
+/// Without the render assets overlays get no texture
+fn prepare_territories(
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut query: Query<(&mut Territory, &GeneratedTerrain)>,
+) {
+    for (mut territory, terrain_data) in &mut query {
+        let size = terrain_data
+            .size
+            .map(|side| side * territory.resolution.max(1) + 1);
+        if prepare(
+            territory.bypass_change_detection(),
+            size,
+            images.as_deref_mut(),
+        ) {
+            territory.set_changed();
+        }
+    }
+}
+
+/// Sizes overlays to their map cells and creates their textures
+#[cfg(feature = "map")]
+fn prepare_map_territories(
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut query: Query<(&mut Territory, &Map), Without<GeneratedTerrain>>,
+) {
+    for (mut territory, map) in &mut query {
+        if prepare(
+            territory.bypass_change_detection(),
+            map.size,
+            images.as_deref_mut(),
+        ) {
+            territory.set_changed();
+        }
+    }
+}
+
+/// If true, the overlay was resized or its texture created
+fn prepare(territory: &mut Territory, size: [u32; 2], images: Option<&mut Assets<Image>>) -> bool {
+    if !territory.fit(size)
+        && images
+            .as_ref()
+            .is_none_or(|images| images.contains(&territory.texture))
+    {
+        return false;
+    }
+    territory.changed = None;
+    if let Some(images) = images {
+        territory.texture = images.add(territory.to_image());
+    }
+    true
+}
+
+fn claim_territories(
+    mut events: EventReader<ClaimTerritory>,
+    mut query: Query<(&mut Territory, &GeneratedTerrain, &GlobalTransform)>,
+) {
+    let claims: Vec<ClaimTerritory> = events.read().copied().collect();
+    if claims.is_empty() {
+        return;
+    }
+    for (mut changed_territory, terrain_data, transform) in &mut query {
+        // Claims of cells that already have the owner leave the overlay and the terrain colors as they are
+        let territory = changed_territory.bypass_change_detection();
+        let old = territory.changed;
+        let to_local = transform.affine().inverse();
+        for claim in &claims {
+            let center = terrain_data.to_overlay(to_local.transform_point3(claim.position).xz());
+            let radius = terrain_data.to_overlay_radius(claim.radius);
+            territory.claim_circle(center, radius, claim.owner, terrain_data.size);
+        }
+        if territory.changed != old {
+            changed_territory.set_changed();
+        }
+    }
+}
+
+/// Writes the changed cells and the borders around them to the textures
+fn update_territories(mut images: Option<ResMut<Assets<Image>>>, mut query: Query<&mut Territory>) {
+    for mut territory in &mut query {
+        let territory = territory.bypass_change_detection();
+        let Some([min, max]) = territory.changed else {
+            continue;
+        };
+        territory.changed = None;
+        let Some(image) = images
+            .as_deref_mut()
+            .and_then(|images| images.get_mut(&territory.texture))
+        else {
+            continue;
+        };
+        let width = territory.border_width as usize;
+        let [columns, rows] = territory.size.map(|side| side as usize);
+        let [min_i, min_j] = min.map(|side| side.saturating_sub(width));
+        let [max_i, max_j] = [
+            (max[0] + width).min(columns - 1),
+            (max[1] + width).min(rows - 1),
+        ];
+        for j in min_j..=max_j {
+            for i in min_i..=max_i {
+                let start = (j * columns + i) * 4;
+                image.data[start..start + 4].copy_from_slice(&territory.texel(i, j));
+            }
+        }
+    }
+}
+
+/// Tints recolored terrains by their owners
+pub(crate) fn blend_territories(
+    mut query: Query<(&mut TerrainColors, &GeneratedTerrain, &Territory)>,
+) {
+    for (mut colors, terrain_data, territory) in &mut query {
+        if colors.is_recoloring() {
+            colors.blend(terrain_data, |_, point, color| {
+                territory.blend(color, point.x, point.y, terrain_data.size)
+            });
+        }
+    }
+}
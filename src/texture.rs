@@ -0,0 +1,447 @@
+//! Generate seamless material textures from layered noise
+//! # Example
+//! For configuration, see [`ProceduralTexture`](struct.ProceduralTexture.html) and [`TextureLayer`](struct.TextureLayer.html).
+//! Start from a preset such as `ProceduralTexture::stone()`, `sand()` or `bark()`.
+//! With a `StandardMaterial` on the entity its base color, normal map and roughness textures are replaced
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::texture::{ProceduralTexture, ProceduralTexturePlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(ProceduralTexturePlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(
+//!     mut commands: Commands,
+//!     mut meshes: ResMut<Assets<Mesh>>,
+//!     mut materials: ResMut<Assets<StandardMaterial>>,
+//! ) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     let mut mesh = Mesh::from(shape::Cube::new(1.0));
+//!     mesh.generate_tangents().unwrap();
+//!     commands.spawn((
+//!         ProceduralTexture::stone(),
+//!         PbrBundle {
+//!             mesh: meshes.add(mesh),
+//!             material: materials.add(StandardMaterial::default()),
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use std::f64::consts::TAU;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::GenerationFailure,
+    noise::{
+        build_gradient, get_noise_at_point_4d, regions, Function, FunctionName, Gradient, Method,
+        Region,
+    },
+    util::export_files,
+};
+
+/// How the noise of a `TextureLayer` is shaped before it is added to the height
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LayerShape {
+    /// Noise as generated
+    #[default]
+    Smooth,
+    /// Sharp crests where the noise crosses 0, e.g. for cracks and fibers
+    Ridged,
+    /// Sharp valleys where the noise crosses 0, e.g. for pebbles and grains
+    Billowed,
+}
+
+/// Noise layer of a `ProceduralTexture`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TextureLayer {
+    /// Added to the seed of the texture
+    pub seed: u32,
+    /// Roughly the number of features across the texture along `u` and `v`, rounded so the texture tiles
+    pub scale: [f64; 2],
+    /// Method used to generate noise
+    pub method: Method,
+    /// Fractal function applied on the noise
+    pub function: Function,
+    /// Shape of the noise
+    pub shape: LayerShape,
+    /// Contribution to the height, relative to the other layers
+    pub weight: f32,
+}
+
+impl Default for TextureLayer {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: [4.0; 2],
+            method: Method::Perlin,
+            function: Function::default(),
+            shape: LayerShape::Smooth,
+            weight: 1.0,
+        }
+    }
+}
+
+/// Component for procedural texture configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct ProceduralTexture {
+    /// Seed of the noise
+    pub seed: u32,
+    /// Width and height of the images in texels
+    pub size: u32,
+    /// Layers summed into the height of every texel
+    pub layers: Vec<TextureLayer>,
+    /// Colors by height, positions from 0 to 100 like the regions of a terrain
+    pub regions: Vec<Region>,
+    /// Roughness at the lowest and at the highest height
+    pub roughness: [f32; 2],
+    /// How strongly the height tilts the normal map, 0 is flat
+    pub normal_strength: f32,
+    /// If true, exports the albedo, normal and roughness images as png
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for ProceduralTexture {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: 256,
+            layers: vec![TextureLayer::default()],
+            regions: vec![
+                Region {
+                    label: "Low".to_string(),
+                    position: 0.0,
+                    color: [40, 40, 40, 255],
+                },
+                Region {
+                    label: "High".to_string(),
+                    position: 100.0,
+                    color: [220, 220, 220, 255],
+                },
+            ],
+            roughness: [0.8, 0.8],
+            normal_strength: 1.0,
+            export: false,
+        }
+    }
+}
+
+impl ProceduralTexture {
+    /// Preset of grey stone with cracks between weathered cells
+    #[must_use]
+    pub fn stone() -> Self {
+        Self {
+            layers: vec![
+                TextureLayer {
+                    scale: [6.0; 2],
+                    method: Method::Worley,
+                    function: Function {
+                        name: None,
+                        ..default()
+                    },
+                    weight: 1.0,
+                    ..default()
+                },
+                TextureLayer {
+                    seed: 1,
+                    scale: [12.0; 2],
+                    shape: LayerShape::Ridged,
+                    weight: 0.4,
+                    ..default()
+                },
+                TextureLayer {
+                    seed: 2,
+                    scale: [48.0; 2],
+                    weight: 0.15,
+                    ..default()
+                },
+            ],
+            regions: regions(&[
+                ("Crack", 0.0, [52, 50, 48, 255]),
+                ("Stone", 40.0, [118, 114, 108, 255]),
+                ("Weathered", 100.0, [172, 168, 160, 255]),
+            ]),
+            roughness: [0.95, 0.7],
+            normal_strength: 2.0,
+            ..default()
+        }
+    }
+
+    /// Preset of sand with wind ripples
+    #[must_use]
+    pub fn sand() -> Self {
+        Self {
+            layers: vec![
+                TextureLayer {
+                    scale: [10.0, 2.0],
+                    function: Function {
+                        octaves: 2,
+                        ..default()
+                    },
+                    weight: 1.0,
+                    ..default()
+                },
+                TextureLayer {
+                    seed: 1,
+                    scale: [128.0; 2],
+                    method: Method::Value,
+                    function: Function {
+                        name: None,
+                        ..default()
+                    },
+                    shape: LayerShape::Billowed,
+                    weight: 0.2,
+                },
+            ],
+            regions: regions(&[
+                ("Trough", 0.0, [178, 150, 104, 255]),
+                ("Crest", 100.0, [226, 204, 158, 255]),
+            ]),
+            roughness: [1.0, 0.9],
+            normal_strength: 0.6,
+            ..default()
+        }
+    }
+
+    /// Preset of bark with fibers along `v` and deep furrows
+    #[must_use]
+    pub fn bark() -> Self {
+        Self {
+            layers: vec![
+                TextureLayer {
+                    scale: [8.0, 1.0],
+                    function: Function {
+                        name: Some(FunctionName::RidgedMulti),
+                        octaves: 3,
+                        ..default()
+                    },
+                    shape: LayerShape::Ridged,
+                    weight: 1.0,
+                    ..default()
+                },
+                TextureLayer {
+                    seed: 1,
+                    scale: [32.0, 4.0],
+                    weight: 0.3,
+                    ..default()
+                },
+            ],
+            regions: regions(&[
+                ("Furrow", 0.0, [34, 24, 16, 255]),
+                ("Bark", 50.0, [86, 62, 42, 255]),
+                ("Ridge", 100.0, [128, 100, 74, 255]),
+            ]),
+            roughness: [1.0, 0.85],
+            normal_strength: 3.0,
+            ..default()
+        }
+    }
+
+    /// Height of every texel from 0 to 1, indexed by `[v][u]`. Both axes wrap around circles of a 4D torus
+    #[must_use]
+    pub fn heights(&self) -> Vec<Vec<f32>> {
+        let size = self.size.max(1);
+        let total: f32 = self.layers.iter().map(|layer| layer.weight.max(0.0)).sum();
+        let mut heights = vec![vec![0.0; size as usize]; size as usize];
+        for layer in &self.layers {
+            let weight = layer.weight.max(0.0) / total.max(f32::EPSILON);
+            // Whole numbers of features along each axis keep opposite edges equal
+            let radius = layer.scale.map(|scale| scale.round().max(1.0) / TAU);
+            for (j, row) in heights.iter_mut().enumerate() {
+                let v = TAU * j as f64 / f64::from(size);
+                for (i, height) in row.iter_mut().enumerate() {
+                    let u = TAU * i as f64 / f64::from(size);
+                    let point = [
+                        radius[0] * u.cos(),
+                        radius[0] * u.sin(),
+                        radius[1] * v.cos(),
+                        radius[1] * v.sin(),
+                    ];
+                    let noise = get_noise_at_point_4d(
+                        point,
+                        self.seed.wrapping_add(layer.seed),
+                        1.0,
+                        [0.0; 4],
+                        &layer.method,
+                        &layer.function,
+                    ) as f32;
+                    let value = match layer.shape {
+                        LayerShape::Smooth => noise * 0.5 + 0.5,
+                        LayerShape::Ridged => 1.0 - noise.abs(),
+                        LayerShape::Billowed => noise.abs(),
+                    };
+                    *height += value * weight;
+                }
+            }
+        }
+        heights
+    }
+
+    /// Albedo, normal and roughness images, see [`ProceduralTextureImages`]
+    ///
+    /// # Errors
+    /// If the gradient could not be built from the regions
+    pub fn images(&self) -> Result<[Image; 3], GenerationFailure> {
+        let grad = build_gradient(&self.regions, &Gradient::default())
+            .map_err(|error| GenerationFailure::Gradient(error.to_string()))?;
+        let heights = self.heights();
+        let size = self.size.max(1);
+        let last = size as usize - 1;
+        let texels = size as usize * size as usize * 4;
+        let (mut albedo, mut normal, mut roughness) = (
+            Vec::with_capacity(texels),
+            Vec::with_capacity(texels),
+            Vec::with_capacity(texels),
+        );
+        // Heights range from 0 to 1 over about a tenth of the texture
+        let slope = self.normal_strength * size as f32 / 20.0;
+        for (j, row) in heights.iter().enumerate() {
+            for (i, &height) in row.iter().enumerate() {
+                albedo.extend(grad.at(f64::from(height) * 100.0).to_rgba8());
+
+                let (left, right) = (
+                    if i == 0 { last } else { i - 1 },
+                    if i == last { 0 } else { i + 1 },
+                );
+                let (back, front) = (
+                    if j == 0 { last } else { j - 1 },
+                    if j == last { 0 } else { j + 1 },
+                );
+                let dx = (row[right] - row[left]) * slope;
+                let dz = (heights[front][i] - heights[back][i]) * slope;
+                let texel = Vec3::new(-dx, 1.0, -dz).normalize();
+                // Tangent along `u`, bitangent along `v`
+                for value in [texel.x, texel.z, texel.y] {
+                    normal.push((value.mul_add(0.5, 0.5) * 255.0).round() as u8);
+                }
+                normal.push(255);
+
+                // Roughness in the green channel and no metal in the blue channel, like glTF
+                let value =
+                    (self.roughness[1] - self.roughness[0]).mul_add(height, self.roughness[0]);
+                let value = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                roughness.extend([value, value, 0, 255]);
+            }
+        }
+        let image = |data, format| {
+            let mut image = Image::new(
+                Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                data,
+                format,
+            );
+            image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+                address_mode_u: ImageAddressMode::Repeat,
+                address_mode_v: ImageAddressMode::Repeat,
+                ..ImageSamplerDescriptor::linear()
+            });
+            image
+        };
+        Ok([
+            image(albedo, TextureFormat::Rgba8UnormSrgb),
+            image(normal, TextureFormat::Rgba8Unorm),
+            image(roughness, TextureFormat::Rgba8Unorm),
+        ])
+    }
+}
+
+/// Images of a `ProceduralTexture`, inserted on the entity with the `ProceduralTexture` component
+#[derive(Component, Clone, Default, PartialEq, Eq, Debug)]
+pub struct ProceduralTextureImages {
+    /// Base color in sRGB
+    pub albedo: Handle<Image>,
+    /// Tangent space normal map
+    pub normal: Handle<Image>,
+    /// Roughness in the green channel, metallic in the blue channel
+    pub roughness: Handle<Image>,
+}
+
+/// Plugin to generate procedural textures
+pub struct ProceduralTexturePlugin;
+
+impl Plugin for ProceduralTexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_procedural_textures);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_procedural_textures(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut ProceduralTexture,
+            Option<&Handle<StandardMaterial>>,
+        ),
+        Changed<ProceduralTexture>,
+    >,
+) {
+    for (entity, mut texture, material) in &mut query {
+        let [albedo, normal, roughness] = match texture.images() {
+            Ok(generated) => generated,
+            Err(error) => {
+                warn!("Could not generate procedural texture {entity:?}: {error}");
+                continue;
+            }
+        };
+        if texture.export {
+            export_files(
+                [
+                    ("albedo", &albedo),
+                    ("normal", &normal),
+                    ("roughness", &roughness),
+                ]
+                .into_iter()
+                .filter_map(|(name, image)| {
+                    image::ImageBuffer::from_raw(image.width(), image.height(), image.data.clone())
+                        .map(|buffer| (format!("{name}.png"), buffer))
+                })
+                .collect(),
+            );
+        }
+        texture.bypass_change_detection().export = false;
+        let generated = ProceduralTextureImages {
+            albedo: images.add(albedo),
+            normal: images.add(normal),
+            roughness: images.add(roughness),
+        };
+        if let Some(material) = material.and_then(|material| materials.get_mut(material)) {
+            material.base_color_texture = Some(generated.albedo.clone());
+            material.normal_map_texture = Some(generated.normal.clone());
+            material.metallic_roughness_texture = Some(generated.roughness.clone());
+        }
+        commands.entity(entity).insert(generated);
+    }
+}
@@ -0,0 +1,24 @@
+//! Addressing of terrain chunks, shared by streaming and the chunk server
+use bevy::prelude::*;
+
+use crate::terrain::Terrain;
+
+/// Size of a chunk configured by `chunk` in world units along `x` and `z`
+pub fn chunk_size(chunk: &Terrain) -> Vec2 {
+    chunk
+        .world_size
+        .unwrap_or_else(|| Vec2::new(chunk.size[0] as f32, chunk.size[1] as f32))
+}
+
+/// Coordinates of the chunk containing `position`, relative to the chunk at `(0, 0)`
+pub fn chunk_coord(chunk: &Terrain, position: Vec2) -> IVec2 {
+    (position / chunk_size(chunk)).round().as_ivec2()
+}
+
+/// Configuration `chunk` moved to `coord`, sampling the noise next to its neighbours and centered on its position
+pub fn chunk_terrain(chunk: &Terrain, coord: IVec2) -> Terrain {
+    let mut terrain = chunk.clone();
+    terrain.world_offset += coord.as_dvec2() * chunk_size(chunk).as_dvec2();
+    terrain.anchor = default();
+    terrain
+}
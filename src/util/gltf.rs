@@ -7,6 +7,8 @@ use wasm_bindgen::prelude::wasm_bindgen;
 
 use std::{fs, mem};
 
+use super::{ExportMaterial, ExportMesh, ExportNode, ExportScene};
+
 use gltf::json::validation::Checked::Valid;
 use json::validation::USize64;
 use std::borrow::Cow;
@@ -25,20 +27,12 @@ pub enum Output {
     Binary,
 }
 
-#[derive(Copy, Clone, Debug)]
-#[repr(C)]
-pub struct Vertex {
-    pub position: [f32; 3],
-    pub color: [f32; 3],
-}
-
-/// Calculate bounding coordinates of a list of vertices, used for the clipping distance of the model
-fn bounding_coords(points: &[Vertex]) -> ([f32; 3], [f32; 3]) {
+/// Calculate bounding coordinates of a list of points, used for the clipping distance of the model
+fn bounding_coords(points: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
     let mut min = [f32::MAX, f32::MAX, f32::MAX];
     let mut max = [f32::MIN, f32::MIN, f32::MIN];
 
-    for point in points {
-        let p = point.position;
+    for p in points {
         for i in 0..3 {
             min[i] = f32::min(min[i], p[i]);
             max[i] = f32::max(max[i], p[i]);
@@ -47,7 +41,7 @@ fn bounding_coords(points: &[Vertex]) -> ([f32; 3], [f32; 3]) {
     (min, max)
 }
 
-fn align_to_multiple_of_four(n: &mut usize) {
+const fn align_to_multiple_of_four(n: &mut usize) {
     *n = (*n + 3) & !3;
 }
 
@@ -63,116 +57,264 @@ fn to_padded_byte_vector<T>(vec: Vec<T>) -> Vec<u8> {
     new_vec
 }
 
-pub fn export_gltf(output: Output, vertices: Vec<Vertex>) {
-    let (min, max) = bounding_coords(&vertices);
-
-    let buffer_length = vertices.len() * mem::size_of::<Vertex>();
-    let buffer = json::Buffer {
-        byte_length: USize64::from(buffer_length),
-        extensions: Option::default(),
-        extras: Default::default(),
-        name: None,
-        uri: if output == Output::Standard {
-            Some("buffer0.bin".into())
-        } else {
-            None
-        },
-    };
-    let buffer_view = json::buffer::View {
-        buffer: json::Index::new(0),
-        byte_length: buffer.byte_length,
-        byte_offset: None,
-        byte_stride: Some(json::buffer::Stride(mem::size_of::<Vertex>())),
-        extensions: Option::default(),
-        extras: Default::default(),
-        name: None,
-        target: Some(Valid(json::buffer::Target::ArrayBuffer)),
-    };
-    let positions = json::Accessor {
-        buffer_view: Some(json::Index::new(0)),
+/// Accessor of `count` tightly packed elements of `component` in buffer view `view`
+fn accessor(
+    view: usize,
+    count: usize,
+    component: json::accessor::ComponentType,
+    type_: json::accessor::Type,
+    bounds: Option<([f32; 3], [f32; 3])>,
+) -> json::Accessor {
+    json::Accessor {
+        buffer_view: Some(json::Index::new(view as u32)),
         byte_offset: Some(USize64(0)),
-        count: USize64::from(vertices.len()),
-        component_type: Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
+        count: USize64::from(count),
+        component_type: Valid(json::accessor::GenericComponentType(component)),
         extensions: Option::default(),
         extras: Default::default(),
-        type_: Valid(json::accessor::Type::Vec3),
-        min: Some(json::Value::from(Vec::from(min))),
-        max: Some(json::Value::from(Vec::from(max))),
+        type_: Valid(type_),
+        min: bounds.map(|(min, _)| json::Value::from(Vec::from(min))),
+        max: bounds.map(|(_, max)| json::Value::from(Vec::from(max))),
         name: None,
         normalized: false,
         sparse: None,
-    };
-    let colors = json::Accessor {
-        buffer_view: Some(json::Index::new(0)),
-        byte_offset: Some(USize64::from(3 * mem::size_of::<f32>())),
-        count: USize64::from(vertices.len()),
-        component_type: Valid(json::accessor::GenericComponentType(
-            json::accessor::ComponentType::F32,
-        )),
-        extensions: Option::default(),
-        extras: Default::default(),
-        type_: Valid(json::accessor::Type::Vec3),
-        min: None,
-        max: None,
-        name: None,
-        normalized: false,
-        sparse: None,
-    };
+    }
+}
 
+/// Mesh of a single primitive with the `attributes` accessors
+fn mesh(
+    name: Option<String>,
+    attributes: &[(json::mesh::Semantic, u32)],
+    indices: Option<u32>,
+    material: Option<u32>,
+    mode: json::mesh::Mode,
+) -> json::Mesh {
     let primitive = json::mesh::Primitive {
-        attributes: {
-            let mut map = std::collections::BTreeMap::new();
-            map.insert(Valid(json::mesh::Semantic::Positions), json::Index::new(0));
-            map.insert(Valid(json::mesh::Semantic::Colors(0)), json::Index::new(1));
-            map
-        },
+        attributes: attributes
+            .iter()
+            .map(|(semantic, index)| (Valid(semantic.clone()), json::Index::new(*index)))
+            .collect(),
         extensions: Option::default(),
         extras: Default::default(),
-        indices: None,
-        material: None,
-        mode: Valid(json::mesh::Mode::Triangles),
+        indices: indices.map(json::Index::new),
+        material: material.map(json::Index::new),
+        mode: Valid(mode),
         targets: None,
     };
-
-    let mesh = json::Mesh {
+    json::Mesh {
         extensions: Option::default(),
         extras: Default::default(),
-        name: None,
+        name,
         primitives: vec![primitive],
         weights: None,
-    };
+    }
+}
+
+/// Buffers, accessors, materials, meshes and nodes of an exported scene
+#[derive(Default)]
+struct Builder {
+    bin: Vec<u8>,
+    root: json::Root,
+}
+
+impl Builder {
+    /// Appends `data` to the buffer as a new buffer view, returns the index of the view
+    fn view<T>(&mut self, data: Vec<T>, target: json::buffer::Target) -> usize {
+        let offset = self.bin.len();
+        let bytes = to_padded_byte_vector(data);
+        self.root.buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: USize64::from(bytes.len()),
+            byte_offset: Some(USize64::from(offset)),
+            byte_stride: None,
+            extensions: Option::default(),
+            extras: Default::default(),
+            name: None,
+            target: Some(Valid(target)),
+        });
+        self.bin.extend(bytes);
+        self.root.buffer_views.len() - 1
+    }
+
+    /// Appends `data` as a new buffer view with an accessor of vertex attributes, returns the index of the accessor
+    fn attribute<T>(
+        &mut self,
+        data: Vec<T>,
+        type_: json::accessor::Type,
+        bounds: Option<([f32; 3], [f32; 3])>,
+    ) -> u32 {
+        let count = data.len();
+        let view = self.view(data, json::buffer::Target::ArrayBuffer);
+        self.root.accessors.push(accessor(
+            view,
+            count,
+            json::accessor::ComponentType::F32,
+            type_,
+            bounds,
+        ));
+        self.root.accessors.len() as u32 - 1
+    }
+
+    fn material(&mut self, material: ExportMaterial) {
+        self.root.materials.push(json::Material {
+            alpha_cutoff: material.alpha_cutoff.map(json::material::AlphaCutoff),
+            alpha_mode: Valid(if material.blend {
+                json::material::AlphaMode::Blend
+            } else if material.alpha_cutoff.is_some() {
+                json::material::AlphaMode::Mask
+            } else {
+                json::material::AlphaMode::Opaque
+            }),
+            double_sided: material.double_sided,
+            name: (!material.name.is_empty()).then_some(material.name),
+            pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                base_color_factor: json::material::PbrBaseColorFactor(material.base_color),
+                metallic_factor: json::material::StrengthFactor(material.metallic),
+                roughness_factor: json::material::StrengthFactor(material.roughness),
+                ..Default::default()
+            },
+            emissive_factor: json::material::EmissiveFactor(material.emissive),
+            ..Default::default()
+        });
+    }
+
+    /// Adds the indexed triangle mesh `data`
+    fn mesh(&mut self, data: ExportMesh) {
+        let vertices = data.positions.len();
+        let bounds = bounding_coords(&data.positions);
+        let mut attributes = vec![(
+            json::mesh::Semantic::Positions,
+            self.attribute(data.positions, json::accessor::Type::Vec3, Some(bounds)),
+        )];
+        if !data.normals.is_empty() && data.normals.len() == vertices {
+            attributes.push((
+                json::mesh::Semantic::Normals,
+                self.attribute(data.normals, json::accessor::Type::Vec3, None),
+            ));
+        }
+        if !data.colors.is_empty() && data.colors.len() == vertices {
+            attributes.push((
+                json::mesh::Semantic::Colors(0),
+                self.attribute(data.colors, json::accessor::Type::Vec4, None),
+            ));
+        }
+        let count = data.indices.len();
+        let view = self.view(data.indices, json::buffer::Target::ElementArrayBuffer);
+        self.root.accessors.push(accessor(
+            view,
+            count,
+            json::accessor::ComponentType::U32,
+            json::accessor::Type::Scalar,
+            None,
+        ));
+        let indices = self.root.accessors.len() as u32 - 1;
+        self.root.meshes.push(mesh(
+            (!data.name.is_empty()).then_some(data.name),
+            &attributes,
+            Some(indices),
+            data.material.map(|material| material as u32),
+            json::mesh::Mode::Triangles,
+        ));
+    }
+
+    /// Adds `node` and its children, returns the index of `node`
+    fn node(&mut self, node: ExportNode) -> u32 {
+        let mesh_index = match node.mesh {
+            Some(index) => Some(json::Index::new(index as u32)),
+            None => (node.line.len() > 1).then(|| {
+                let bounds = bounding_coords(&node.line);
+                let positions = self.attribute(node.line, json::accessor::Type::Vec3, Some(bounds));
+                self.root.meshes.push(mesh(
+                    None,
+                    &[(json::mesh::Semantic::Positions, positions)],
+                    None,
+                    None,
+                    json::mesh::Mode::LineStrip,
+                ));
+                json::Index::new(self.root.meshes.len() as u32 - 1)
+            }),
+        };
+        let children: Vec<json::Index<json::Node>> = node
+            .children
+            .into_iter()
+            .map(|child| json::Index::new(self.node(child)))
+            .collect();
+        self.push_node(json::Node {
+            children: (!children.is_empty()).then_some(children),
+            extras: node
+                .extras
+                .and_then(|extras| json::extras::RawValue::from_string(extras).ok()),
+            mesh: mesh_index,
+            name: (!node.name.is_empty()).then_some(node.name),
+            rotation: Some(json::scene::UnitQuaternion(node.rotation)),
+            scale: Some(node.scale),
+            translation: Some(node.translation),
+            ..empty_node()
+        })
+    }
+
+    fn push_node(&mut self, node: json::Node) -> u32 {
+        self.root.nodes.push(node);
+        self.root.nodes.len() as u32 - 1
+    }
+}
 
-    let node = json::Node {
+const fn empty_node() -> json::Node {
+    json::Node {
         camera: None,
         children: None,
-        extensions: Option::default(),
-        extras: Default::default(),
+        extensions: None,
+        extras: None,
         matrix: None,
-        mesh: Some(json::Index::new(0)),
+        mesh: None,
         name: None,
         rotation: None,
         scale: None,
         translation: None,
         skin: None,
         weights: None,
-    };
+    }
+}
 
-    let root = json::Root {
-        accessors: vec![positions, colors],
-        buffers: vec![buffer],
-        buffer_views: vec![buffer_view],
-        meshes: vec![mesh],
-        nodes: vec![node],
-        scenes: vec![json::Scene {
-            extensions: Option::default(),
-            extras: Default::default(),
-            name: None,
-            nodes: vec![json::Index::new(0)],
-        }],
-        ..json::Root::default()
-    };
+/// Exports the meshes, materials and nodes of `scene`.
+/// Its sidecar is saved next to the model as `.json`
+pub fn export_gltf(output: Output, scene: ExportScene) {
+    let ExportScene {
+        meshes,
+        materials,
+        nodes,
+        sidecar,
+    } = scene;
+    let mut builder = Builder::default();
+    for material in materials {
+        builder.material(material);
+    }
+    // Meshes come first, so the mesh indices of the nodes are their indices in the scene
+    for mesh in meshes {
+        builder.mesh(mesh);
+    }
+    let scene_nodes = nodes
+        .into_iter()
+        .map(|node| json::Index::new(builder.node(node)))
+        .collect();
+    let Builder { bin, mut root } = builder;
+    root.buffers.push(json::Buffer {
+        byte_length: USize64::from(bin.len()),
+        extensions: Option::default(),
+        extras: Default::default(),
+        name: None,
+        uri: if output == Output::Standard {
+            Some("buffer0.bin".into())
+        } else {
+            None
+        },
+    });
+    root.scenes.push(json::Scene {
+        extensions: Option::default(),
+        extras: Default::default(),
+        name: None,
+        nodes: scene_nodes,
+    });
 
     match output {
         Output::Standard => {
@@ -181,21 +323,23 @@ pub fn export_gltf(output: Output, vertices: Vec<Vertex>) {
             let writer = fs::File::create("triangle/triangle.gltf").expect("I/O error");
             json::serialize::to_writer_pretty(writer, &root).expect("Serialization error");
 
-            let bin = to_padded_byte_vector(vertices);
             let mut writer = fs::File::create("triangle/buffer0.bin").expect("I/O error");
             writer.write_all(&bin).expect("I/O error");
+            if let Some(sidecar) = sidecar {
+                let _ = fs::write("triangle/triangle.json", sidecar);
+            }
         }
         Output::Binary => {
             let json_string = json::serialize::to_string(&root).expect("Serialization error");
-            let mut json_offset = json_string.len() as usize;
+            let mut json_offset = json_string.len();
             align_to_multiple_of_four(&mut json_offset);
             let glb = gltf::binary::Glb {
                 header: gltf::binary::Header {
                     magic: *b"glTF",
                     version: 2,
-                    length: (json_offset + buffer_length) as u32, // This may truncate long buffers
+                    length: (json_offset + bin.len()) as u32, // This may truncate long buffers
                 },
-                bin: Some(Cow::Owned(to_padded_byte_vector(vertices))),
+                bin: Some(Cow::Owned(bin)),
                 json: Cow::Owned(json_string.into_bytes()),
             };
 
@@ -203,11 +347,17 @@ pub fn export_gltf(output: Output, vertices: Vec<Vertex>) {
             {
                 let buffer = glb.to_vec().expect("glTF binary output error");
                 save(&buffer, "model.glb", "model/gtlf-binary");
+                if let Some(sidecar) = sidecar {
+                    save(sidecar.as_bytes(), "model.json", "application/json");
+                }
             }
             #[cfg(not(target_arch = "wasm32"))]
             if let Some(file_path) = FileDialog::new().save_file() {
-                let writer = std::fs::File::create(file_path).expect("I/O error");
+                let writer = std::fs::File::create(&file_path).expect("I/O error");
                 glb.to_writer(writer).expect("glTF binary output error");
+                if let Some(sidecar) = sidecar {
+                    let _ = fs::write(file_path.with_extension("json"), sidecar);
+                }
             }
         }
     }
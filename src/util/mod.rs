@@ -1,19 +1,161 @@
+#[cfg(feature = "terrain")]
+pub mod chunk;
+#[cfg(feature = "export")]
 mod gltf;
-use gltf::{export_gltf, Output, Vertex};
-#[cfg(not(target_arch = "wasm32"))]
+use bevy::{
+    prelude::{Mesh, Vec3},
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+pub use bevy_generative_core::Rng;
+#[cfg(feature = "export")]
+use gltf::{export_gltf, Output};
+#[cfg(all(feature = "export", not(target_arch = "wasm32")))]
 use image::save_buffer;
-use image::{codecs::png::PngEncoder, DynamicImage, ImageBuffer, ImageEncoder, Rgba};
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "export")]
+use image::{codecs::png::PngEncoder, DynamicImage, ImageEncoder};
+#[cfg(feature = "image")]
+use image::{ImageBuffer, Rgba};
+#[cfg(all(feature = "export", not(target_arch = "wasm32")))]
 use rfd::FileDialog;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "export", target_arch = "wasm32"))]
 use wasm_bindgen::prelude::wasm_bindgen;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "export", target_arch = "wasm32"))]
 #[wasm_bindgen(module = "/src/util/save.js")]
 extern "C" {
     fn save(data: &[u8], filename: &str, r#type: &str);
 }
 
+/// Image saved under its file name by `export_files`
+#[cfg(any(feature = "map", feature = "export"))]
+pub type NamedImage = (String, ImageBuffer<Rgba<u8>, Vec<u8>>);
+
+/// Without the `export` feature nothing is saved
+#[cfg(all(feature = "image", not(feature = "export")))]
+pub fn export_asset(_image_buffer: ImageBuffer<Rgba<u8>, Vec<u8>>) {}
+
+/// Without the `export` feature nothing is saved
+#[cfg(all(feature = "image", not(feature = "export")))]
+pub fn export_sequence(_name: &str, _frames: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>>) {}
+
+/// Without the `export` feature nothing is saved
+#[cfg(all(feature = "map", not(feature = "export")))]
+pub fn export_files(_files: Vec<NamedImage>) {}
+
+/// Without the `export` feature nothing is saved
+#[cfg(not(feature = "export"))]
+pub fn export_model(_positions: &[[f32; 3]], _indices: Vec<u32>, _colors: &[[f32; 4]]) {}
+
+/// Without the `export` feature nothing is saved
+#[cfg(all(feature = "terrain", not(feature = "export")))]
+pub fn export_scene(_scene: ExportScene) {}
+
+/// Without the `export` feature nothing is saved
+#[cfg(all(feature = "terrain", not(feature = "export")))]
+pub fn export_bytes(_files: Vec<(String, Vec<u8>)>) {}
+
+/// Meshes, materials and nodes of an exported scene
+#[cfg(any(feature = "terrain", feature = "export"))]
+// Only read by the glTF writer of the `export` feature
+#[cfg_attr(not(feature = "export"), allow(dead_code))]
+#[derive(Clone, Debug, Default)]
+pub struct ExportScene {
+    pub meshes: Vec<ExportMesh>,
+    pub materials: Vec<ExportMaterial>,
+    /// Root nodes of the scene
+    pub nodes: Vec<ExportNode>,
+    /// JSON saved next to the model as `.json`
+    pub sidecar: Option<String>,
+}
+
+/// Triangle mesh of an exported scene, shared by every node referencing it
+#[cfg(any(feature = "terrain", feature = "export"))]
+// Only read by the glTF writer of the `export` feature
+#[cfg_attr(not(feature = "export"), allow(dead_code))]
+#[derive(Clone, Debug, Default)]
+pub struct ExportMesh {
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    /// Indices of the vertices of every triangle
+    pub indices: Vec<u32>,
+    /// Vertex colors, empty without colors
+    pub colors: Vec<[f32; 4]>,
+    /// Vertex normals, empty without normals
+    pub normals: Vec<[f32; 3]>,
+    /// Index into the materials of the scene
+    pub material: Option<usize>,
+}
+
+/// Metallic roughness material of an exported scene, the colors are linear
+#[cfg(any(feature = "terrain", feature = "export"))]
+// Only read by the glTF writer of the `export` feature
+#[cfg_attr(not(feature = "export"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub struct ExportMaterial {
+    pub name: String,
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    /// If true, the alpha of the base color blends the surface with what is behind it
+    pub blend: bool,
+    /// Alpha below which the surface is cut out, `None` keeps it opaque
+    pub alpha_cutoff: Option<f32>,
+    pub double_sided: bool,
+}
+
+#[cfg(any(feature = "terrain", feature = "export"))]
+impl Default for ExportMaterial {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: [1.0; 4],
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: [0.0; 3],
+            blend: false,
+            alpha_cutoff: None,
+            double_sided: false,
+        }
+    }
+}
+
+/// Node of an exported scene, e.g. a terrain, a scattered object or a road
+#[cfg(any(feature = "terrain", feature = "export"))]
+// Only read by the glTF writer of the `export` feature
+#[cfg_attr(not(feature = "export"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub struct ExportNode {
+    pub name: String,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    /// Index into the meshes of the scene, nodes referencing the same mesh are instances of it
+    pub mesh: Option<usize>,
+    /// Points of a line strip relative to the node, e.g. of a spline, used without a mesh
+    pub line: Vec<[f32; 3]>,
+    /// JSON stored as the extras of the node
+    pub extras: Option<String>,
+    pub children: Vec<Self>,
+}
+
+#[cfg(any(feature = "terrain", feature = "export"))]
+impl Default for ExportNode {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            translation: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+            mesh: None,
+            line: vec![],
+            extras: None,
+            children: vec![],
+        }
+    }
+}
+
+#[cfg(feature = "export")]
 pub fn export_asset(image_buffer: ImageBuffer<Rgba<u8>, Vec<u8>>) {
     {
         let mut png_buffer: Vec<u8> = vec![];
@@ -45,18 +187,170 @@ pub fn export_asset(image_buffer: ImageBuffer<Rgba<u8>, Vec<u8>>) {
     }
 }
 
+/// Saves every frame as `{name}_{index}.png`, into a chosen folder on native targets
+#[cfg(feature = "export")]
+pub fn export_sequence(name: &str, frames: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>>) {
+    export_files(
+        frames
+            .into_iter()
+            .enumerate()
+            .map(|(index, frame)| (format!("{name}_{index:03}.png"), frame))
+            .collect(),
+    );
+}
+
+/// Saves every image under its file name, into a chosen folder on native targets
+#[cfg(feature = "export")]
+pub fn export_files(files: Vec<NamedImage>) {
+    #[cfg(target_arch = "wasm32")]
+    for (file_name, image_buffer) in &files {
+        let mut png_buffer: Vec<u8> = vec![];
+        PngEncoder::new(&mut png_buffer)
+            .write_image(
+                image_buffer,
+                image_buffer.width(),
+                image_buffer.height(),
+                DynamicImage::from(image_buffer.clone()).color(),
+            )
+            .expect("Failed to write to png");
+        save(&png_buffer, file_name, "image/png");
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Some(folder) = FileDialog::new().pick_folder() {
+            for (file_name, image_buffer) in files {
+                let _ = save_buffer(
+                    folder.join(file_name),
+                    &image_buffer,
+                    image_buffer.width(),
+                    image_buffer.height(),
+                    DynamicImage::from(image_buffer.clone()).color(),
+                );
+            }
+        }
+    }
+}
+
+/// Saves every file under its name, into a chosen folder on native targets
+#[cfg(all(feature = "export", feature = "terrain"))]
+pub fn export_bytes(files: Vec<(String, Vec<u8>)>) {
+    #[cfg(target_arch = "wasm32")]
+    for (file_name, bytes) in &files {
+        save(bytes, file_name, "application/octet-stream");
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(folder) = FileDialog::new().pick_folder() {
+        for (file_name, bytes) in files {
+            let _ = std::fs::write(folder.join(file_name), bytes);
+        }
+    }
+}
+
+#[cfg(feature = "export")]
 pub fn export_model(positions: &[[f32; 3]], indices: Vec<u32>, colors: &[[f32; 4]]) {
-    let mut vertices: Vec<Vertex> = vec![];
-
-    for i in indices {
-        vertices.push(Vertex {
-            position: positions[i as usize],
-            color: [
-                colors[i as usize][0],
-                colors[i as usize][1],
-                colors[i as usize][2],
-            ],
-        });
-    }
-    export_gltf(Output::Binary, vertices);
+    export_gltf(
+        Output::Binary,
+        ExportScene {
+            meshes: vec![ExportMesh {
+                positions: positions.to_vec(),
+                indices,
+                colors: colors.to_vec(),
+                ..ExportMesh::default()
+            }],
+            nodes: vec![ExportNode {
+                mesh: Some(0),
+                ..ExportNode::default()
+            }],
+            ..ExportScene::default()
+        },
+    );
+}
+
+/// Saves `scene` in glb format, its sidecar is saved next to the model as `.json`
+#[cfg(all(feature = "export", feature = "terrain"))]
+pub fn export_scene(scene: ExportScene) {
+    export_gltf(Output::Binary, scene);
+}
+
+/// UV sphere with its poles on the `y` axis in the layout of equirectangular textures,
+/// `u` goes around from `+x` towards `+z` and `v` from the `+y` pole to the `-y` pole
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Mesh {
+    let (sectors, stacks) = (sectors.max(3), stacks.max(2));
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    for i in 0..=stacks {
+        let v = i as f32 / stacks as f32;
+        let latitude = v.mul_add(-std::f32::consts::PI, std::f32::consts::FRAC_PI_2);
+        for j in 0..=sectors {
+            let u = j as f32 / sectors as f32;
+            let longitude = u * std::f32::consts::TAU;
+            let normal = Vec3::new(
+                latitude.cos() * longitude.cos(),
+                latitude.sin(),
+                latitude.cos() * longitude.sin(),
+            );
+            positions.push((normal * radius).to_array());
+            normals.push(normal.to_array());
+            uvs.push([u, v]);
+        }
+    }
+    let mut indices = vec![];
+    for i in 0..stacks {
+        for j in 0..sectors {
+            let top = i * (sectors + 1) + j;
+            let bottom = top + sectors + 1;
+            // The triangles at the poles collapse into the pole
+            if i != 0 {
+                indices.extend([top, top + 1, bottom]);
+            }
+            if i != stacks - 1 {
+                indices.extend([top + 1, bottom + 1, bottom]);
+            }
+        }
+    }
+    Mesh::new(PrimitiveTopology::TriangleList)
+        .with_indices(Some(Indices::U32(indices)))
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+}
+
+pub fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+        let normal = (Vec3::from(positions[b]) - Vec3::from(positions[a]))
+            .cross(Vec3::from(positions[c]) - Vec3::from(positions[a]));
+        normals[a] += normal;
+        normals[b] += normal;
+        normals[c] += normal;
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.normalize_or_zero().to_array())
+        .collect()
+}
+
+/// Columns and rows of a sheet of `count` frames, as square as possible
+pub fn sheet_grid(count: u32) -> [u32; 2] {
+    let count = count.max(1);
+    let columns = f64::from(count).sqrt().ceil() as u32;
+    [columns, count.div_ceil(columns)]
+}
+
+/// RGBA pixels of `frames` laid out row by row in the grid of [`sheet_grid`], empty cells are transparent
+pub fn pack_sheet(frames: &[Vec<u8>], frame_size: [u32; 2]) -> Vec<u8> {
+    let [width, height] = frame_size.map(|size| size as usize);
+    let [columns, rows] = sheet_grid(frames.len() as u32).map(|count| count as usize);
+    let row_bytes = width * 4;
+    let mut data = vec![0; columns * rows * width * height * 4];
+    for (index, frame) in frames.iter().enumerate() {
+        let (column, row) = (index % columns, index / columns);
+        for (y, line) in frame.chunks_exact(row_bytes).take(height).enumerate() {
+            let start = ((row * height + y) * columns * width + column * width) * 4;
+            data[start..start + row_bytes].copy_from_slice(line);
+        }
+    }
+    data
 }
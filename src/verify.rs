@@ -0,0 +1,329 @@
+//! Verify that clients generate identical terrain
+//! # Example
+//! For configuration, see [`WorldFingerprint`](struct.WorldFingerprint.html) and [`ChunkHash`](struct.ChunkHash.html).
+//! Compare fingerprints when a client joins, then chunk hashes as chunks are generated.
+//! Insert the hash received from the server as `ExpectedChunkHash` to get a `ChunkDiverged` event on mismatch
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::terrain::{Terrain, TerrainBundle, TerrainPlugin};
+//! use bevy_generative::verify::{ChunkDiverged, VerifyPlugin, WorldFingerprint};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, VerifyPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, report)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     let bundle = TerrainBundle::default();
+//!     println!("Fingerprint {}", WorldFingerprint::new(&bundle.terrain));
+//!     commands.spawn(bundle);
+//! }
+//!
+//! fn report(mut events: EventReader<ChunkDiverged>) {
+//!     for event in events.read() {
+//!         println!("{:?} diverged from the server", event.chunk);
+//!     }
+//! }
+//! ```
+use std::fmt;
+
+use bevy::{math::DVec2, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    modifier::Modifier,
+    noise::{Function, Method, Precision},
+    terrain::{Anchor, GeneratedTerrain, Terrain, VerticalAnchor},
+};
+
+/// 64-bit FNV-1a hash, stable across platforms and Rust versions unlike `DefaultHasher`
+struct Fnv(u64);
+
+impl Fnv {
+    const fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Fields of `Noise` the heights depend on, the regions, gradient and base color only color them
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NoiseParameters<'a> {
+    seed: u32,
+    scale: f64,
+    offset: [f64; 2],
+    method: &'a Method,
+    function: &'a Function,
+    precision: Precision,
+    modifiers: &'a [Modifier],
+}
+
+/// Parameters of a `Terrain` its heights depend on, render and performance settings are left out
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HeightParameters<'a> {
+    noise: NoiseParameters<'a>,
+    size: [u32; 2],
+    resolution: u32,
+    height_exponent: f32,
+    sea_percent: f32,
+    sea_level: Option<f32>,
+    height_scale: Option<f32>,
+    // Scales the world offset into noise coordinates
+    world_size: Option<Vec2>,
+    world_offset: DVec2,
+    seed_offset: u32,
+    // `Terrain::lift` adds both to every height
+    anchor_height: f32,
+    vertical_anchor: VerticalAnchor,
+}
+
+impl<'a> HeightParameters<'a> {
+    fn new(terrain: &'a Terrain) -> Self {
+        let noise = &terrain.noise;
+        Self {
+            noise: NoiseParameters {
+                seed: noise.seed,
+                scale: noise.scale,
+                offset: noise.offset,
+                method: &noise.method,
+                function: &noise.function,
+                precision: noise.precision,
+                modifiers: &noise.modifiers,
+            },
+            size: terrain.size,
+            resolution: terrain.resolution,
+            height_exponent: terrain.height_exponent,
+            sea_percent: terrain.sea_percent,
+            sea_level: terrain.sea_level,
+            height_scale: terrain.height_scale,
+            world_size: terrain.world_size,
+            world_offset: terrain.world_offset,
+            seed_offset: terrain.seed_offset,
+            anchor_height: match terrain.anchor {
+                Anchor::Offset(offset) => offset.y,
+                Anchor::Center | Anchor::Corner => 0.0,
+            },
+            vertical_anchor: terrain.vertical_anchor,
+        }
+    }
+}
+
+/// Identity of a generated world, equal on every client using the same terrain configuration
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldFingerprint {
+    /// Seed of the terrain noise, including `Terrain::seed_offset`
+    pub seed: u32,
+    /// Hash of the parameters the heights depend on, such as the noise, size, resolution, sea and anchor settings.
+    /// Render and performance settings such as `wireframe` or `max_vertices` and the noise colors are left out
+    pub parameters: u64,
+}
+
+impl WorldFingerprint {
+    /// Fingerprint of the world generated from `terrain`
+    #[must_use]
+    pub fn new(terrain: &Terrain) -> Self {
+        let mut hash = Fnv::new();
+        // Field order and float formatting of serde_json are deterministic
+        hash.write(&serde_json::to_vec(&HeightParameters::new(terrain)).unwrap_or_default());
+        Self {
+            seed: terrain.noise.seed.wrapping_add(terrain.seed_offset),
+            parameters: hash.0,
+        }
+    }
+}
+
+impl fmt::Display for WorldFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}-{:016x}", self.seed, self.parameters)
+    }
+}
+
+/// Hash of the generated heightfield, inserted on the entity with the `Terrain` component
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChunkHash {
+    /// Hash of the size, resolution, sea level and every height
+    pub content: u64,
+}
+
+impl ChunkHash {
+    /// Hash of `terrain_data`. Heights are hashed bit for bit, so any divergence is detected
+    #[must_use]
+    pub fn new(terrain_data: &GeneratedTerrain) -> Self {
+        Self::of(
+            terrain_data.resolution,
+            terrain_data.size,
+            terrain_data.sea_level,
+            &terrain_data.heights,
+        )
+    }
+
+    /// Hash of a heightfield with `heights` indexed by `[x][z]`, like `new`
+    pub(crate) fn of(
+        resolution: u32,
+        size: [u32; 2],
+        sea_level: f32,
+        heights: &[Vec<f32>],
+    ) -> Self {
+        let mut hash = Fnv::new();
+        hash.write(&resolution.to_le_bytes());
+        for side in size {
+            hash.write(&side.to_le_bytes());
+        }
+        hash.write(&sea_level.to_bits().to_le_bytes());
+        for column in heights {
+            for height in column {
+                hash.write(&height.to_bits().to_le_bytes());
+            }
+        }
+        Self { content: hash.0 }
+    }
+}
+
+/// Component with the hash a chunk is expected to have, e.g. received from the server
+#[derive(
+    Component, Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Reflect, Serialize, Deserialize,
+)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct ExpectedChunkHash {
+    /// Expected `ChunkHash::content`
+    pub content: u64,
+}
+
+/// Event sent when the hash of a chunk differs from its `ExpectedChunkHash`
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChunkDiverged {
+    /// Entity of the chunk
+    pub chunk: Entity,
+    /// Hash the chunk is expected to have
+    pub expected: ChunkHash,
+    /// Hash of the generated chunk
+    pub actual: ChunkHash,
+}
+
+/// Plugin to hash chunks and detect divergence
+pub struct VerifyPlugin;
+
+impl Plugin for VerifyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ChunkDiverged>()
+            .add_systems(Update, (hash_chunks, verify_chunks).chain());
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn hash_chunks(
+    mut commands: Commands,
+    query: Query<(Entity, &GeneratedTerrain, Option<&ChunkHash>), Changed<GeneratedTerrain>>,
+) {
+    for (entity, terrain_data, old) in &query {
+        let new = ChunkHash::new(terrain_data);
+        if old != Some(&new) {
+            commands.entity(entity).insert(new);
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn verify_chunks(
+    mut diverged: EventWriter<ChunkDiverged>,
+    query: Query<
+        (Entity, &ChunkHash, &ExpectedChunkHash),
+        Or<(Changed<ChunkHash>, Changed<ExpectedChunkHash>)>,
+    >,
+) {
+    for (entity, actual, expected) in &query {
+        if actual.content != expected.content {
+            diverged.send(ChunkDiverged {
+                chunk: entity,
+                expected: ChunkHash {
+                    content: expected.content,
+                },
+                actual: *actual,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_settings_keep_the_fingerprint() {
+        let terrain = Terrain::default();
+        let rendered = Terrain {
+            wireframe: true,
+            flat_shading: true,
+            columns_per_frame: Some(4),
+            max_vertices: Some(64),
+            simplification: Some(0.1),
+            ..terrain.clone()
+        };
+        assert_eq!(
+            WorldFingerprint::new(&terrain),
+            WorldFingerprint::new(&rendered)
+        );
+    }
+
+    #[test]
+    fn colors_keep_the_fingerprint() {
+        let terrain = Terrain::default();
+        let mut recolored = terrain.clone();
+        recolored.noise.regions[0].color = [0, 255, 0, 255];
+        recolored.noise.base_color = [0, 0, 0, 255];
+        assert_eq!(
+            WorldFingerprint::new(&terrain),
+            WorldFingerprint::new(&recolored)
+        );
+    }
+
+    #[test]
+    fn vertical_anchor_changes_the_fingerprint() {
+        let terrain = Terrain {
+            vertical_anchor: VerticalAnchor::Generated,
+            ..default()
+        };
+        let anchored = Terrain {
+            vertical_anchor: VerticalAnchor::SeaLevel,
+            ..terrain.clone()
+        };
+        assert_ne!(
+            WorldFingerprint::new(&terrain),
+            WorldFingerprint::new(&anchored)
+        );
+    }
+
+    #[test]
+    fn noise_changes_the_fingerprint() {
+        let terrain = Terrain::default();
+        let mut changed = terrain.clone();
+        changed.noise.scale *= 2.0;
+        assert_ne!(
+            WorldFingerprint::new(&terrain),
+            WorldFingerprint::new(&changed)
+        );
+    }
+}
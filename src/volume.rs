@@ -0,0 +1,754 @@
+//! Generate meshes from 3D density fields
+//! # Example
+//! For configuration, see [`Volume`](struct.Volume.html).
+//! Density fields can also be meshed directly with [`mesh_from_density`](fn.mesh_from_density.html)
+//! or, with another [`Mesher`](enum.Mesher.html), [`mesh_from_density_with`](fn.mesh_from_density_with.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::volume::{VolumeBundle, VolumePlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(VolumePlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(VolumeBundle::default());
+//! }
+//! ```
+use std::collections::HashMap;
+
+use bevy::{prelude::*, render::render_resource::PrimitiveTopology};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    noise::{get_noise_at_point_3d, Function, Method},
+    sdf::Sdf,
+    util::export_model,
+};
+
+/// Corners of a cube cell, bit 0 is the `x` offset, bit 1 the `y` offset and bit 2 the `z` offset
+const CORNERS: [[usize; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+];
+
+/// Corners of the faces of a cube cell, counter-clockwise seen from outside of the cell
+const FACES: [[usize; 4]; 6] = [
+    [0, 4, 6, 2],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 6, 7, 3],
+    [0, 2, 3, 1],
+    [4, 5, 7, 6],
+];
+
+/// Scalar field sampled on a regular grid, values above zero are solid
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct DensityField {
+    /// Number of samples along each axis
+    pub samples: [usize; 3],
+    /// Distance between samples in world units
+    pub spacing: f32,
+    /// Position of the first sample
+    pub origin: Vec3,
+    /// Samples ordered by `x`, then `y`, then `z`
+    pub values: Vec<f32>,
+}
+
+impl DensityField {
+    /// Field of empty samples, centered on the origin
+    #[must_use]
+    pub fn new(samples: [usize; 3], spacing: f32) -> Self {
+        let extent = Vec3::new(
+            samples[0].saturating_sub(1) as f32,
+            samples[1].saturating_sub(1) as f32,
+            samples[2].saturating_sub(1) as f32,
+        ) * spacing;
+        Self {
+            samples,
+            spacing,
+            origin: -extent / 2.0,
+            values: vec![-1.0; samples[0] * samples[1] * samples[2]],
+        }
+    }
+
+    /// Field centered on the origin, sampling `density` at the position of every sample
+    #[must_use]
+    pub fn from_fn(samples: [usize; 3], spacing: f32, density: impl Fn(Vec3) -> f32) -> Self {
+        let mut field = Self::new(samples, spacing);
+        for z in 0..samples[2] {
+            for y in 0..samples[1] {
+                for x in 0..samples[0] {
+                    let value = density(field.position(x, y, z));
+                    field.set(x, y, z, value);
+                }
+            }
+        }
+        field
+    }
+
+    /// Field centered on the origin, sampling 3D noise scaled to -1 to 1
+    #[must_use]
+    pub fn from_noise(
+        samples: [usize; 3],
+        spacing: f32,
+        seed: u32,
+        scale: f64,
+        offset: [f64; 3],
+        method: &Method,
+        function: &Function,
+    ) -> Self {
+        Self::from_fn(samples, spacing, |position| {
+            get_noise_at_point_3d(
+                position.as_dvec3().to_array(),
+                seed,
+                scale,
+                offset,
+                method,
+                function,
+            ) as f32
+        })
+    }
+
+    /// Density of the sample at `x`, `y`, `z`, `None` outside of the field
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<f32> {
+        self.index(x, y, z).map(|index| self.values[index])
+    }
+
+    /// Sets the density of the sample at `x`, `y`, `z`, ignored outside of the field
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: f32) {
+        if let Some(index) = self.index(x, y, z) {
+            self.values[index] = value;
+        }
+    }
+
+    /// Position of the sample at `x`, `y`, `z`
+    #[must_use]
+    pub fn position(&self, x: usize, y: usize, z: usize) -> Vec3 {
+        self.origin + Vec3::new(x as f32, y as f32, z as f32) * self.spacing
+    }
+
+    /// Direction of increasing density at the sample at `x`, `y`, `z`, from central differences
+    #[must_use]
+    pub fn gradient(&self, x: usize, y: usize, z: usize) -> Vec3 {
+        let point = [x, y, z];
+        let axis = |axis: usize| {
+            let mut below = point;
+            let mut above = point;
+            below[axis] = below[axis].saturating_sub(1);
+            above[axis] = (above[axis] + 1).min(self.samples[axis].saturating_sub(1));
+            let value = |[x, y, z]: [usize; 3]| self.get(x, y, z).unwrap_or(0.0);
+            (value(above) - value(below)) / ((above[axis] - below[axis]).max(1) as f32)
+        };
+        Vec3::new(axis(0), axis(1), axis(2))
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        let [width, height, depth] = self.samples;
+        (x < width && y < height && z < depth).then_some(x + width * (y + height * z))
+    }
+}
+
+/// Algorithm used to extract the surface of a density field
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Mesher {
+    /// Vertices on the grid edges, smooths away sharp features
+    #[default]
+    MarchingCubes,
+    /// One vertex per cell at the average of its edge crossings, smooth with fewer triangles
+    SurfaceNets,
+    /// One vertex per cell fitted to the crossing normals, keeps cliffs and cuts sharp and shades them flat
+    DualContouring,
+}
+
+impl Mesher {
+    pub(crate) fn extract(self, field: &DensityField) -> SurfaceMesh {
+        match self {
+            Self::MarchingCubes => marching_cubes(field),
+            Self::SurfaceNets => dual_mesh(field, false),
+            Self::DualContouring => dual_mesh(field, true),
+        }
+    }
+}
+
+/// Component for volume configuration
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Volume {
+    /// Seed of the noise
+    pub seed: u32,
+    /// Scale of the noise
+    pub scale: f64,
+    /// Offset of the noise
+    pub offset: [f64; 3],
+    /// Method used to generate noise
+    pub method: Method,
+    /// Function used to generate noise
+    pub function: Function,
+    /// Number of samples along each axis
+    pub samples: [usize; 3],
+    /// Distance between samples in world units
+    pub spacing: f32,
+    /// Density added to every sample, higher values produce more solid volumes
+    pub bias: f32,
+    /// Density removed per world unit of height, positive values produce ground, negative values ceilings
+    pub height_falloff: f32,
+    /// Density removed towards the sides of the volume, 0 to 1. Higher values produce floating islands
+    pub edge_falloff: f32,
+    /// Shape sculpted from signed distance fields, replaces the noise and falloffs if set
+    pub shape: Option<Sdf>,
+    /// Algorithm used to extract the surface
+    pub mesher: Mesher,
+    /// Color of the mesh
+    pub color: [u8; 4],
+    /// If true, renders volume mesh as wireframe
+    pub wireframe: bool,
+    /// If true, exports model in glb format
+    #[serde(skip)]
+    pub export: bool,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: 0.5,
+            offset: [0.0; 3],
+            method: Method::Perlin,
+            function: Function::default(),
+            samples: [32; 3],
+            spacing: 0.06,
+            bias: 0.0,
+            height_falloff: 0.5,
+            edge_falloff: 0.5,
+            shape: None,
+            mesher: Mesher::MarchingCubes,
+            color: [130, 120, 110, 255],
+            wireframe: false,
+            export: false,
+        }
+    }
+}
+
+impl Volume {
+    /// Samples the density field described by the configuration
+    #[must_use]
+    pub fn density_field(&self) -> DensityField {
+        if let Some(shape) = &self.shape {
+            let bias = self.bias;
+            return DensityField::from_fn(self.samples, self.spacing, |position| {
+                bias - shape.distance(position)
+            });
+        }
+        let extent = Vec3::new(
+            self.samples[0].saturating_sub(1) as f32,
+            self.samples[1].saturating_sub(1) as f32,
+            self.samples[2].saturating_sub(1) as f32,
+        ) * self.spacing
+            / 2.0;
+        let mut field = DensityField::from_noise(
+            self.samples,
+            self.spacing,
+            self.seed,
+            self.scale,
+            self.offset,
+            &self.method,
+            &self.function,
+        );
+        for z in 0..self.samples[2] {
+            for y in 0..self.samples[1] {
+                for x in 0..self.samples[0] {
+                    let position = field.position(x, y, z);
+                    let edge = (position.xz() / extent.xz().max(Vec2::splat(f32::EPSILON)))
+                        .length()
+                        .min(1.0);
+                    let value = (edge * edge * self.edge_falloff).mul_add(
+                        -2.0,
+                        position.y.mul_add(
+                            -self.height_falloff,
+                            field.get(x, y, z).unwrap_or(0.0) + self.bias,
+                        ),
+                    );
+                    field.set(x, y, z, value);
+                }
+            }
+        }
+        field
+    }
+}
+
+/// Render `Volume` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct VolumeBundle {
+    /// Volume configuration
+    pub volume: Volume,
+    /// Generated mesh data is written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate volumes
+pub struct VolumePlugin;
+
+impl Plugin for VolumePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_volume);
+    }
+}
+
+/// Mesh data extracted from a density field
+pub(crate) struct SurfaceMesh {
+    pub(crate) positions: Vec<[f32; 3]>,
+    pub(crate) normals: Vec<[f32; 3]>,
+    pub(crate) indices: Vec<u32>,
+}
+
+/// Extracts the surface where the density crosses zero with marching cubes
+#[must_use]
+pub fn mesh_from_density(field: &DensityField) -> Mesh {
+    mesh_from_density_with(field, Mesher::MarchingCubes)
+}
+
+/// Extracts the surface where the density crosses zero with `mesher`
+#[must_use]
+pub fn mesh_from_density_with(field: &DensityField, mesher: Mesher) -> Mesh {
+    let SurfaceMesh {
+        positions,
+        normals,
+        indices,
+    } = mesher.extract(field);
+    let uvs = vec![[0.0; 2]; positions.len()];
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices)));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
+
+/// Polygons are traced over the faces of every cell instead of looked up in a case table.
+/// Ambiguous faces are resolved by the average of their corners, which neighbouring cells agree on,
+/// so the surface has no cracks
+pub(crate) fn marching_cubes(field: &DensityField) -> SurfaceMesh {
+    let mut surface = SurfaceMesh {
+        positions: vec![],
+        normals: vec![],
+        indices: vec![],
+    };
+    // Vertices are shared between cells, keyed by the lower sample of their grid edge and its axis
+    let mut vertices: HashMap<([isize; 3], usize), u32> = HashMap::new();
+    let [width, height, depth] = field.samples.map(|samples| samples as isize);
+    // Samples outside of the field are empty, so the surface is closed at the sides of the field
+    let in_field = |[x, y, z]: [isize; 3]| {
+        (0..width).contains(&x) && (0..height).contains(&y) && (0..depth).contains(&z)
+    };
+    let sample = |[x, y, z]: [isize; 3]| {
+        if in_field([x, y, z]) {
+            field
+                .get(x as usize, y as usize, z as usize)
+                .unwrap_or(-1.0)
+        } else {
+            -1.0
+        }
+    };
+    let clamp = |[x, y, z]: [isize; 3]| {
+        [
+            x.clamp(0, width - 1) as usize,
+            y.clamp(0, height - 1) as usize,
+            z.clamp(0, depth - 1) as usize,
+        ]
+    };
+    for z in -1..depth {
+        for y in -1..height {
+            for x in -1..width {
+                let samples =
+                    CORNERS.map(|[dx, dy, dz]| [x + dx as isize, y + dy as isize, z + dz as isize]);
+                let values = samples.map(sample);
+                let solid = values.map(|value| value > 0.0);
+                if solid.iter().all(|&solid| solid) || solid.iter().all(|&solid| !solid) {
+                    continue;
+                }
+
+                // Trace the border of the solid region over the faces, with solid corners on the left
+                let mut next: HashMap<[usize; 2], [usize; 2]> = HashMap::new();
+                for face in FACES {
+                    let inside = face.map(|corner| solid[corner]);
+                    let edge = |i: usize| {
+                        let [a, b] = [face[i], face[(i + 1) % 4]];
+                        [a.min(b), a.max(b)]
+                    };
+                    let crossing: Vec<usize> = (0..4)
+                        .filter(|&i| inside[i] != inside[(i + 1) % 4])
+                        .collect();
+                    let segments: Vec<[usize; 2]> = match crossing.len() {
+                        2 => vec![[crossing[0], crossing[1]]],
+                        4 => {
+                            let center =
+                                face.iter().map(|&corner| values[corner]).sum::<f32>() / 4.0 > 0.0;
+                            (0..4)
+                                .filter(|&k| inside[k] != center)
+                                .map(|k| [(k + 3) % 4, k])
+                                .collect()
+                        }
+                        _ => vec![],
+                    };
+                    for [first, second] in segments {
+                        let [from, to] = if inside[(second + 1) % 4] {
+                            [first, second]
+                        } else {
+                            [second, first]
+                        };
+                        next.insert(edge(from), edge(to));
+                    }
+                }
+
+                let mut starts: Vec<[usize; 2]> = next.keys().copied().collect();
+                starts.sort_unstable();
+                for start in starts {
+                    let mut polygon: Vec<u32> = vec![];
+                    let mut current = start;
+                    while let Some(following) = next.remove(&current) {
+                        let [low, high] = current;
+                        let axis = (low ^ high).trailing_zeros() as usize;
+                        let key = (samples[low], axis);
+                        let vertex = *vertices.entry(key).or_insert_with(|| {
+                            let t = values[low] / (values[low] - values[high]);
+                            // Crossings with the outside lie on the sides of the field
+                            let ends = [samples[low], samples[high]].map(clamp);
+                            let [pa, pb] = ends.map(|[i, j, k]| field.position(i, j, k));
+                            let [ga, gb] = ends.map(|[i, j, k]| field.gradient(i, j, k));
+                            // Density increases into the solid, so the surface faces the other way
+                            let normal = if !in_field(samples[low]) {
+                                -Vec3::AXES[axis]
+                            } else if !in_field(samples[high]) {
+                                Vec3::AXES[axis]
+                            } else {
+                                -ga.lerp(gb, t)
+                            };
+                            surface.positions.push(pa.lerp(pb, t).to_array());
+                            surface.normals.push(normal.normalize_or_zero().to_array());
+                            surface.positions.len() as u32 - 1
+                        });
+                        polygon.push(vertex);
+                        current = following;
+                    }
+                    // The traced border runs clockwise seen from the empty side
+                    for i in 1..polygon.len().saturating_sub(1) {
+                        surface
+                            .indices
+                            .extend([polygon[0], polygon[i + 1], polygon[i]]);
+                    }
+                }
+            }
+        }
+    }
+    surface
+}
+
+/// Places one vertex in every cell the surface passes through and connects the vertices of the
+/// four cells around every crossed grid edge with a quad.
+/// With `sharp`, vertices minimize the distance to the planes of the crossings, which moves them onto
+/// edges and corners of the surface, and every triangle gets its own flat normal
+fn dual_mesh(field: &DensityField, sharp: bool) -> SurfaceMesh {
+    let mut surface = SurfaceMesh {
+        positions: vec![],
+        normals: vec![],
+        indices: vec![],
+    };
+    let [width, height, depth] = field.samples.map(|samples| samples as isize);
+    let in_field = |[x, y, z]: [isize; 3]| {
+        (0..width).contains(&x) && (0..height).contains(&y) && (0..depth).contains(&z)
+    };
+    // Samples outside of the field are empty, so the surface is closed at the sides of the field
+    let sample = |[x, y, z]: [isize; 3]| {
+        if in_field([x, y, z]) {
+            field
+                .get(x as usize, y as usize, z as usize)
+                .unwrap_or(-1.0)
+        } else {
+            -1.0
+        }
+    };
+    let clamp = |[x, y, z]: [isize; 3]| {
+        [
+            x.clamp(0, width - 1) as usize,
+            y.clamp(0, height - 1) as usize,
+            z.clamp(0, depth - 1) as usize,
+        ]
+    };
+
+    // Vertex of every cell the surface passes through, keyed by the lowest sample of the cell
+    let mut cells: HashMap<[isize; 3], u32> = HashMap::new();
+    let mut points: Vec<Vec3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    for z in -1..depth {
+        for y in -1..height {
+            for x in -1..width {
+                let samples =
+                    CORNERS.map(|[dx, dy, dz]| [x + dx as isize, y + dy as isize, z + dz as isize]);
+                let values = samples.map(sample);
+                let mut crossings: Vec<(Vec3, Vec3)> = vec![];
+                for low in 0..8 {
+                    for axis in 0..3 {
+                        let high = low | 1 << axis;
+                        if high == low || (values[low] > 0.0) == (values[high] > 0.0) {
+                            continue;
+                        }
+                        let t = values[low] / (values[low] - values[high]);
+                        // Crossings with the outside lie on the sides of the field
+                        let ends = [samples[low], samples[high]].map(clamp);
+                        let [pa, pb] = ends.map(|[i, j, k]| field.position(i, j, k));
+                        let [ga, gb] = ends.map(|[i, j, k]| field.gradient(i, j, k));
+                        let normal = if !in_field(samples[low]) {
+                            -Vec3::AXES[axis]
+                        } else if !in_field(samples[high]) {
+                            Vec3::AXES[axis]
+                        } else {
+                            -ga.lerp(gb, t)
+                        };
+                        crossings.push((pa.lerp(pb, t), normal.normalize_or_zero()));
+                    }
+                }
+                if crossings.is_empty() {
+                    continue;
+                }
+                let mass = crossings.iter().map(|(point, _)| *point).sum::<Vec3>()
+                    / crossings.len() as f32;
+                let point = if sharp {
+                    let [min, max] = [samples[0], samples[7]].map(|corner| {
+                        let [i, j, k] = clamp(corner);
+                        field.position(i, j, k)
+                    });
+                    fit_vertex(&crossings, mass).clamp(min, max)
+                } else {
+                    mass
+                };
+                let normal = crossings.iter().map(|(_, normal)| *normal).sum::<Vec3>();
+                cells.insert([x, y, z], points.len() as u32);
+                points.push(point);
+                normals.push(normal.normalize_or_zero());
+            }
+        }
+    }
+
+    for z in -1..depth {
+        for y in -1..height {
+            for x in -1..width {
+                let low = [x, y, z];
+                for axis in 0..3 {
+                    let mut high = low;
+                    high[axis] += 1;
+                    let solid = sample(low) > 0.0;
+                    if solid == (sample(high) > 0.0) {
+                        continue;
+                    }
+                    // The cells around the edge, counter-clockwise seen from the high end
+                    let [first, second] = [(axis + 1) % 3, (axis + 2) % 3];
+                    let around = [[1, 1], [0, 1], [0, 0], [1, 0]].map(|[du, dv]| {
+                        let mut cell = low;
+                        cell[first] -= du;
+                        cell[second] -= dv;
+                        cell
+                    });
+                    let Some(mut quad) = around
+                        .iter()
+                        .map(|cell| cells.get(cell).copied())
+                        .collect::<Option<Vec<u32>>>()
+                    else {
+                        continue;
+                    };
+                    // The surface faces the empty end of the edge
+                    if !solid {
+                        quad.reverse();
+                    }
+                    for triangle in [[0, 1, 2], [0, 2, 3]] {
+                        let corners = triangle.map(|i| quad[i]);
+                        if sharp {
+                            let [a, b, c] = corners.map(|index| points[index as usize]);
+                            let normal = (b - a).cross(c - a).normalize_or_zero();
+                            for point in [a, b, c] {
+                                surface.positions.push(point.to_array());
+                                surface.normals.push(normal.to_array());
+                            }
+                            let index = surface.positions.len() as u32;
+                            surface.indices.extend([index - 3, index - 2, index - 1]);
+                        } else {
+                            surface.indices.extend(corners);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if !sharp {
+        surface.positions = points.iter().map(Vec3::to_array).collect();
+        surface.normals = normals.iter().map(Vec3::to_array).collect();
+    }
+    surface
+}
+
+/// Point minimizing the squared distances to the planes through the crossings,
+/// pulled slightly towards `mass` so flat and ambiguous cells stay well defined
+fn fit_vertex(crossings: &[(Vec3, Vec3)], mass: Vec3) -> Vec3 {
+    const PULL: f32 = 0.05;
+    let mut matrix = Mat3::from_diagonal(Vec3::splat(PULL));
+    let mut target = Vec3::ZERO;
+    for &(point, normal) in crossings {
+        matrix += Mat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z);
+        target += normal * normal.dot(point - mass);
+    }
+    if matrix.determinant().abs() < f32::EPSILON {
+        return mass;
+    }
+    mass + matrix.inverse() * target
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_volume(
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<(&mut Volume, &mut Handle<Mesh>, &Handle<StandardMaterial>), Changed<Volume>>,
+) {
+    // Without the render assets there is nothing to generate
+    let (Some(mut materials), Some(mut meshes)) = (materials, meshes) else {
+        return;
+    };
+    for (mut volume, mut mesh_handle, material) in &mut query {
+        if let Some(material) = materials.get_mut(material) {
+            *material = StandardMaterial {
+                perceptual_roughness: 0.9,
+                ..default()
+            };
+        }
+
+        let SurfaceMesh {
+            positions,
+            normals,
+            mut indices,
+        } = volume.mesher.extract(&volume.density_field());
+        let color = volume.color.map(|channel| f32::from(channel) / 255.0);
+        let colors = vec![color; positions.len()];
+        let uvs = vec![[0.0; 2]; positions.len()];
+
+        if volume.wireframe {
+            let triangle_number = indices.len() / 3;
+            let cloned_indices = indices.clone();
+            indices = vec![];
+            for i in 0..triangle_number {
+                for j in &[0, 1, 1, 2, 2, 0] {
+                    indices.push(cloned_indices[i * 3 + j]);
+                }
+            }
+        }
+
+        let mut mesh = if volume.wireframe {
+            Mesh::new(PrimitiveTopology::LineList)
+        } else {
+            Mesh::new(PrimitiveTopology::TriangleList)
+        };
+        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        *mesh_handle = meshes.add(mesh);
+
+        if volume.export {
+            export_model(&positions, indices, &colors);
+            volume.bypass_change_detection().export = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 0.8;
+
+    fn sphere() -> DensityField {
+        DensityField::from_fn([24; 3], 0.1, |position| RADIUS - position.length())
+    }
+
+    /// Every directed edge of a closed, consistently wound mesh is used once in each direction
+    fn assert_closed(surface: &SurfaceMesh) {
+        let mut edges: HashMap<[u32; 2], usize> = HashMap::new();
+        for triangle in surface.indices.chunks_exact(3) {
+            for k in 0..3 {
+                *edges
+                    .entry([triangle[k], triangle[(k + 1) % 3]])
+                    .or_default() += 1;
+            }
+        }
+        for (&[a, b], &count) in &edges {
+            assert_eq!(count, 1, "edge {a} {b} is used {count} times");
+            assert_eq!(edges.get(&[b, a]), Some(&1), "edge {a} {b} is open");
+        }
+    }
+
+    #[test]
+    fn spheres_give_closed_meshes_on_their_radius() {
+        let field = sphere();
+        for mesher in [Mesher::MarchingCubes, Mesher::SurfaceNets] {
+            let surface = mesher.extract(&field);
+            assert!(!surface.indices.is_empty());
+            assert_closed(&surface);
+            for position in &surface.positions {
+                let distance = Vec3::from(*position).length();
+                assert!(
+                    (distance - RADIUS).abs() < field.spacing / 2.0,
+                    "{mesher:?} {distance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn normals_face_out_of_the_solid() {
+        let field = sphere();
+        for mesher in [
+            Mesher::MarchingCubes,
+            Mesher::SurfaceNets,
+            Mesher::DualContouring,
+        ] {
+            let surface = mesher.extract(&field);
+            for (position, normal) in surface.positions.iter().zip(&surface.normals) {
+                let outward = Vec3::from(*position).normalize();
+                assert!(Vec3::from(*normal).dot(outward) > 0.5, "{mesher:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn solids_touching_the_sides_are_closed() {
+        let field = DensityField::from_fn([6; 3], 0.1, |_| 1.0);
+        let surface = marching_cubes(&field);
+        assert_closed(&surface);
+        let extent = field.position(5, 5, 5);
+        for position in &surface.positions {
+            let position = Vec3::from(*position);
+            assert!(position.abs().cmple(extent.abs() + f32::EPSILON).all());
+            assert!((position.abs() - extent.abs()).abs().min_element() < 1e-5);
+        }
+    }
+}
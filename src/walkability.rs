@@ -0,0 +1,269 @@
+//! Derive walkability grids for pathfinding over terrain
+//! # Example
+//! For configuration, see [`Walkability`](struct.Walkability.html).
+//! [`WalkabilityGrid::successors`](struct.WalkabilityGrid.html#method.successors) and
+//! [`WalkabilityGrid::heuristic`](struct.WalkabilityGrid.html#method.heuristic) can be passed to
+//! the successor and heuristic closures of `astar` and `dijkstra` in the `pathfinding` crate
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//! use bevy_generative::walkability::{Walkability, WalkabilityGrid, WalkabilityPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, WalkabilityPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_neighbours)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((TerrainBundle::default(), Walkability::default()));
+//! }
+//!
+//! fn print_neighbours(query: Query<&WalkabilityGrid, Changed<WalkabilityGrid>>) {
+//!     for grid in &query {
+//!         if let Some(center) = grid.vertex(0.0, 0.0) {
+//!             println!("{:?}", grid.successors(&center));
+//!         }
+//!     }
+//! }
+//! ```
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::{Biome, BiomeMap},
+    terrain::GeneratedTerrain,
+};
+
+/// Component for walkability configuration, added to an entity with a `Terrain` component.
+/// Biomes are taken into account if the entity also has a `BiomeMap`
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Walkability {
+    /// Maximum walkable slope in degrees
+    pub max_slope: f32,
+    /// Additional cost for walking on slopes, multiplied with the slope in radians
+    pub slope_cost: f32,
+    /// If true, the sea is walkable, e.g. for swimming or boats
+    pub sea: bool,
+    /// Additional cost for crossing the sea if it is walkable
+    pub sea_cost: f32,
+    /// Cost multiplier of every biome, biomes without a multiplier are not scaled.
+    /// Multipliers below 1 are raised to 1 to keep `WalkabilityGrid::heuristic` admissible
+    pub biome_costs: HashMap<Biome, f32>,
+    /// Biomes that are not walkable
+    pub blocked_biomes: Vec<Biome>,
+}
+
+impl Default for Walkability {
+    fn default() -> Self {
+        Self {
+            max_slope: 35.0,
+            slope_cost: 2.0,
+            sea: false,
+            sea_cost: 5.0,
+            biome_costs: HashMap::from([
+                (Biome::BorealForest, 1.5),
+                (Biome::TemperateRainforest, 2.0),
+                (Biome::TropicalRainforest, 2.5),
+                (Biome::SubtropicalDesert, 1.3),
+            ]),
+            blocked_biomes: vec![],
+        }
+    }
+}
+
+/// Walkable vertices of the terrain and their costs, inserted on the entity with the `Walkability` component.
+/// Vertices are addressed by `(x, z)` indices like `GeneratedTerrain::heights`
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct WalkabilityGrid {
+    /// If true, the vertex can be walked on. Indexed like `GeneratedTerrain::heights`
+    pub walkable: Vec<Vec<bool>>,
+    /// Cost of walking one vertex step across every vertex, at least 1. Indexed like `GeneratedTerrain::heights`
+    pub costs: Vec<Vec<f32>>,
+    /// Number of vertices per world unit
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl WalkabilityGrid {
+    /// Scale of the integer costs of `successors` and `heuristic`, a straight step of cost 1
+    pub const COST_SCALE: f32 = 1000.0;
+
+    /// Derives the grid from the terrain, weighting biomes by `biomes` if given
+    #[must_use]
+    pub fn new(
+        walkability: &Walkability,
+        terrain_data: &GeneratedTerrain,
+        biomes: Option<&BiomeMap>,
+    ) -> Self {
+        let cell_size = 1.0 / terrain_data.resolution.max(1) as f32;
+        let [width, depth] = terrain_data.size.map(|side| side as f32 / 2.0);
+        let max_slope = walkability.max_slope.to_radians();
+        let mut walkable = vec![];
+        let mut costs = vec![];
+        for (i, column) in terrain_data.heights.iter().enumerate() {
+            let mut walkable_column = Vec::with_capacity(column.len());
+            let mut cost_column = Vec::with_capacity(column.len());
+            for (j, &height) in column.iter().enumerate() {
+                let (x, z) = (
+                    (i as f32).mul_add(cell_size, -width),
+                    (j as f32).mul_add(cell_size, -depth),
+                );
+                let sea = height <= terrain_data.sea_level + f32::EPSILON;
+                let slope = terrain_data.slope(x, z).unwrap_or(0.0);
+                let biome = biomes.and_then(|biomes| biomes.cells.get(i)?.get(j));
+                let mut cost = walkability.slope_cost.mul_add(slope, 1.0).max(1.0);
+                if sea {
+                    cost += walkability.sea_cost.max(0.0);
+                } else if let Some(multiplier) =
+                    biome.and_then(|biome| walkability.biome_costs.get(biome))
+                {
+                    cost *= multiplier.max(1.0);
+                }
+                walkable_column.push(if sea {
+                    walkability.sea
+                } else {
+                    slope <= max_slope
+                        && !biome.is_some_and(|biome| walkability.blocked_biomes.contains(biome))
+                });
+                cost_column.push(cost);
+            }
+            walkable.push(walkable_column);
+            costs.push(cost_column);
+        }
+        Self {
+            walkable,
+            costs,
+            resolution: terrain_data.resolution.max(1),
+            size: terrain_data.size,
+        }
+    }
+
+    /// Returns true if the vertex `i`, `j` exists and is walkable
+    #[must_use]
+    pub fn is_walkable(&self, (i, j): (usize, usize)) -> bool {
+        self.walkable
+            .get(i)
+            .and_then(|column| column.get(j))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Walkable neighbours of `vertex` with the cost of the step in thousandths, see [`Self::COST_SCALE`].
+    /// Diagonal steps between two blocked vertices are not allowed
+    #[must_use]
+    pub fn successors(&self, vertex: &(usize, usize)) -> Vec<((usize, usize), u32)> {
+        let &(i, j) = vertex;
+        if !self.is_walkable(*vertex) {
+            return vec![];
+        }
+        let mut successors = Vec::with_capacity(8);
+        for (di, dj) in [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ] {
+            let (Some(ni), Some(nj)) = (i.checked_add_signed(di), j.checked_add_signed(dj)) else {
+                continue;
+            };
+            if !self.is_walkable((ni, nj)) {
+                continue;
+            }
+            let diagonal = di != 0 && dj != 0;
+            if diagonal && !self.is_walkable((ni, j)) && !self.is_walkable((i, nj)) {
+                continue;
+            }
+            let distance = if diagonal { 2_f32.sqrt() } else { 1.0 };
+            let cost = distance * (self.costs[i][j] + self.costs[ni][nj]) / 2.0;
+            successors.push(((ni, nj), (cost * Self::COST_SCALE).round() as u32));
+        }
+        successors
+    }
+
+    /// Lower bound of the cost from `from` to `to` in thousandths, the octile distance
+    #[must_use]
+    pub fn heuristic(from: &(usize, usize), to: &(usize, usize)) -> u32 {
+        let dx = from.0.abs_diff(to.0) as f32;
+        let dz = from.1.abs_diff(to.1) as f32;
+        let octile = (2_f32.sqrt() - 1.0).mul_add(dx.min(dz), dx.max(dz));
+        // Rounded down so rounding never overestimates
+        (octile * Self::COST_SCALE).floor() as u32
+    }
+
+    /// Vertex nearest to `x`, `z`, `None` outside of the terrain
+    #[must_use]
+    pub fn vertex(&self, x: f32, z: f32) -> Option<(usize, usize)> {
+        let resolution = self.resolution as f32;
+        let i = ((x + self.size[0] as f32 / 2.0) * resolution).round();
+        let j = ((z + self.size[1] as f32 / 2.0) * resolution).round();
+        if i < 0.0 || j < 0.0 {
+            return None;
+        }
+        let vertex = (i as usize, j as usize);
+        self.walkable.get(vertex.0)?.get(vertex.1)?;
+        Some(vertex)
+    }
+
+    /// Position of the vertex `i`, `j` as `x`, `z`, relative to the terrain entity
+    #[must_use]
+    pub fn position(&self, (i, j): (usize, usize)) -> Vec2 {
+        Vec2::new(i as f32, j as f32) / self.resolution.max(1) as f32
+            - Vec2::new(self.size[0] as f32, self.size[1] as f32) / 2.0
+    }
+}
+
+/// Plugin to derive walkability grids
+pub struct WalkabilityPlugin;
+
+impl Plugin for WalkabilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, generate_walkability);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_walkability(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &Walkability,
+            &GeneratedTerrain,
+            Option<&BiomeMap>,
+            Option<&WalkabilityGrid>,
+        ),
+        Or<(
+            Changed<Walkability>,
+            Changed<GeneratedTerrain>,
+            Changed<BiomeMap>,
+        )>,
+    >,
+) {
+    for (entity, walkability, terrain_data, biomes, old) in &query {
+        let new = WalkabilityGrid::new(walkability, terrain_data, biomes);
+        if old != Some(&new) {
+            commands.entity(entity).insert(new);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! Generate animated water surfaces at the sea level of terrains
+//! # Example
+//! For configuration, see [`Water`](struct.Water.html).
+//! The surface is spawned as a `WaterSurface` child of the terrain and animated in the shader,
+//! the normal maps scroll with time and foam gathers along the shoreline
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//! use bevy_generative::water::{Water, WaterPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, WaterPlugin))
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn(Camera3dBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         TerrainBundle::default(),
+//!         Water {
+//!             wave_speed: 0.2,
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+use bevy::{
+    asset::{load_internal_asset, Asset},
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    reflect::TypePath,
+    render::{
+        mesh::Indices,
+        render_resource::{AsBindGroup, PrimitiveTopology, ShaderRef},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    caustics::{WaterTexture, WaterTextureKind},
+    distance::{sea_mask, DistanceField},
+    headless::DataOnly,
+    terrain::GeneratedTerrain,
+};
+
+pub use settings::WaterSettings;
+
+const WATER_SHADER: Handle<Shader> =
+    Handle::weak_from_u128(0x2c84_e9a1_5f3b_4d70_b6e2_91c5_08fa_d3e7);
+
+/// `StandardMaterial` extended by a `WaterMaterial`, used as the material of a `WaterSurface`
+pub type WaterPbrMaterial = ExtendedMaterial<StandardMaterial, WaterMaterial>;
+
+/// Component for water configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, PartialEq, Debug, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Water {
+    /// Seed of the wave noise
+    pub seed: u32,
+    /// Color of open water
+    pub color: [u8; 4],
+    /// Color of the foam
+    pub foam_color: [u8; 4],
+    /// World units covered by one repetition of the wave normal map
+    pub wave_scale: f32,
+    /// World units per second the waves move
+    pub wave_speed: f32,
+    /// How strongly the waves tilt the surface normals, 0 is a mirror
+    pub wave_strength: f32,
+    /// Distance from the shoreline in world units where the foam fades out, 0 for no foam
+    pub foam_distance: f32,
+    /// Width and height of the wave normal map in texels
+    pub texture_size: u32,
+    /// Roughness of the surface
+    pub roughness: f32,
+}
+
+impl Default for Water {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            color: [28, 84, 130, 200],
+            foam_color: [235, 245, 250, 255],
+            wave_scale: 1.0,
+            wave_speed: 0.1,
+            wave_strength: 0.6,
+            foam_distance: 0.1,
+            texture_size: 128,
+            roughness: 0.1,
+        }
+    }
+}
+
+/// Marker of the water surface child of a terrain with a `Water` component
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct WaterSurface;
+
+// The derive of `ShaderType` generates layout checks the lint only sees used by the render pipeline
+#[allow(dead_code)]
+mod settings {
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// Uniform of a `WaterMaterial`
+    #[derive(Clone, Copy, Default, PartialEq, Debug, ShaderType)]
+    pub struct WaterSettings {
+        /// Linear color of the foam
+        pub foam_color: Vec4,
+        /// See `Water::wave_scale`
+        pub wave_scale: f32,
+        /// See `Water::wave_speed`
+        pub wave_speed: f32,
+        /// See `Water::wave_strength`
+        pub wave_strength: f32,
+        /// See `Water::foam_distance`
+        pub foam_distance: f32,
+    }
+}
+
+/// Material extension scrolling wave normal maps and blending in foam near the shore
+#[derive(Asset, TypePath, AsBindGroup, Clone, Default, Debug)]
+pub struct WaterMaterial {
+    /// Wave and foam settings
+    #[uniform(100)]
+    pub settings: WaterSettings,
+    /// Tileable tangent space normal map of the waves
+    #[texture(101)]
+    #[sampler(102)]
+    pub waves: Option<Handle<Image>>,
+    /// Distance to the shore across the surface, see [`DistanceField::to_image`]
+    #[texture(103)]
+    #[sampler(104)]
+    pub shore_distance: Option<Handle<Image>>,
+}
+
+impl MaterialExtension for WaterMaterial {
+    fn fragment_shader() -> ShaderRef {
+        WATER_SHADER.into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        WATER_SHADER.into()
+    }
+}
+
+/// Plugin to generate water surfaces
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(app, WATER_SHADER, "water.wgsl", Shader::from_wgsl);
+        app.add_plugins(MaterialPlugin::<WaterPbrMaterial>::default())
+            .add_systems(Update, (generate_water, remove_water));
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn generate_water(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<WaterPbrMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query: Query<
+        (Entity, Ref<Water>, &GeneratedTerrain, Option<&Children>),
+        (
+            Or<(Changed<Water>, Changed<GeneratedTerrain>)>,
+            Without<DataOnly>,
+        ),
+    >,
+    mut surfaces: Query<(&mut Handle<Mesh>, &Handle<WaterPbrMaterial>), With<WaterSurface>>,
+) {
+    for (entity, water, terrain_data, children) in &query {
+        let surface = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| surfaces.contains(**child))
+                .copied()
+        });
+        let mesh = meshes.add(surface_mesh(terrain_data));
+        // The distance field is laid out over the grid, so it stretches along with `world_size`
+        let stretch = terrain_data.world_size.x / terrain_data.size[0].max(1) as f32;
+        let field = DistanceField::from_mask(
+            &sea_mask(terrain_data),
+            stretch / terrain_data.resolution.max(1) as f32,
+        );
+        let shore_distance = images.add(field.to_image(water.foam_distance));
+        let [r, g, b, a] = water.color;
+        let [foam_r, foam_g, foam_b, foam_a] = water.foam_color;
+        let settings = WaterSettings {
+            foam_color: Color::rgba_u8(foam_r, foam_g, foam_b, foam_a)
+                .as_linear_rgba_f32()
+                .into(),
+            wave_scale: water.wave_scale,
+            wave_speed: water.wave_speed,
+            wave_strength: water.wave_strength,
+            foam_distance: water.foam_distance,
+        };
+        let base = StandardMaterial {
+            base_color: Color::rgba_u8(r, g, b, a),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: water.roughness,
+            ..default()
+        };
+
+        let existing = surface.and_then(|surface| surfaces.get_mut(surface).ok());
+        if let Some((mut mesh_handle, material)) = existing {
+            *mesh_handle = mesh;
+            if let Some(material) = materials.get_mut(material) {
+                // Waves only depend on the configuration, not on the terrain
+                if water.is_changed() || material.extension.waves.is_none() {
+                    material.extension.waves = Some(images.add(wave_normal_map(&water)));
+                }
+                material.base = base;
+                material.extension.settings = settings;
+                material.extension.shore_distance = Some(shore_distance);
+            }
+        } else {
+            let material = materials.add(WaterPbrMaterial {
+                base,
+                extension: WaterMaterial {
+                    settings,
+                    waves: Some(images.add(wave_normal_map(&water))),
+                    shore_distance: Some(shore_distance),
+                },
+            });
+            let surface = commands
+                .spawn((
+                    WaterSurface,
+                    MaterialMeshBundle {
+                        mesh,
+                        material,
+                        ..default()
+                    },
+                ))
+                .id();
+            commands.entity(entity).add_child(surface);
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn remove_water(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Water>,
+    children: Query<&Children>,
+    surfaces: Query<(), With<WaterSurface>>,
+) {
+    for entity in removed.read() {
+        let Ok(children) = children.get(entity) else {
+            continue;
+        };
+        for &child in children {
+            if surfaces.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Quad covering `terrain_data` at its sea level, `uv` 0 to 1 along `x` and `z`
+pub(crate) fn surface_mesh(terrain_data: &GeneratedTerrain) -> Mesh {
+    let min = terrain_data.center - terrain_data.world_size / 2.0;
+    let max = terrain_data.center + terrain_data.world_size / 2.0;
+    let y = terrain_data.sea_level;
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![
+            [min.x, y, min.y],
+            [max.x, y, min.y],
+            [max.x, y, max.y],
+            [min.x, y, max.y],
+        ],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; 4]);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+    );
+    mesh.set_indices(Some(Indices::U32(vec![0, 2, 1, 0, 3, 2])));
+    mesh
+}
+
+/// Tileable normal map of the waves of `water`
+fn wave_normal_map(water: &Water) -> Image {
+    WaterTexture {
+        kind: WaterTextureKind::Normals,
+        seed: water.seed,
+        size: water.texture_size,
+        scale: 12.0,
+        ..default()
+    }
+    .frame(0.0)
+}
@@ -0,0 +1,69 @@
+#import bevy_pbr::{
+    mesh_view_bindings::globals,
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::alpha_discard,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+struct WaterSettings {
+    foam_color: vec4<f32>,
+    wave_scale: f32,
+    wave_speed: f32,
+    wave_strength: f32,
+    foam_distance: f32,
+}
+
+@group(1) @binding(100) var<uniform> water: WaterSettings;
+@group(1) @binding(101) var waves_texture: texture_2d<f32>;
+@group(1) @binding(102) var waves_sampler: sampler;
+@group(1) @binding(103) var shore_texture: texture_2d<f32>;
+@group(1) @binding(104) var shore_sampler: sampler;
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    // Two layers of the normal map scroll in different directions, so the pattern never repeats visibly
+    let uv = in.world_position.xz / max(water.wave_scale, 0.0001);
+    let offset = globals.time * water.wave_speed / max(water.wave_scale, 0.0001);
+    let first = textureSample(waves_texture, waves_sampler, uv + vec2<f32>(offset, offset * 0.6)).xy * 2.0 - 1.0;
+    let second = textureSample(waves_texture, waves_sampler, uv * 0.7 - vec2<f32>(offset * 0.8, -offset * 0.3)).xy * 2.0 - 1.0;
+    let tilt = (first + second) * 0.5 * water.wave_strength;
+    let normal = normalize(vec3<f32>(tilt.x, 1.0, tilt.y));
+
+    // The shore texture maps the shoreline to 0.5 and `foam_distance` into the sea to 0
+    let shore = (textureSample(shore_texture, shore_sampler, in.uv).r * 2.0 - 1.0) * water.foam_distance;
+    var foam = 0.0;
+    if water.foam_distance > 0.0 {
+        foam = clamp(1.0 + shore / water.foam_distance + (first.x - second.y) * 0.25, 0.0, 1.0);
+    }
+    pbr_input.material.base_color = mix(pbr_input.material.base_color, water.foam_color, foam);
+    pbr_input.N = normal;
+    pbr_input.world_normal = normal;
+
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(in, pbr_input);
+#else
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+
+    return out;
+}
@@ -0,0 +1,347 @@
+//! Animate weather over terrain
+//! # Example
+//! For configuration, see [`Weather`](struct.Weather.html).
+//! Entities with a [`WeatherObserver`](struct.WeatherObserver.html), e.g. the player, receive a
+//! [`WeatherChanged`](struct.WeatherChanged.html) event whenever the weather where they stand changes.
+//! Precipitation falls as snow where the terrain also has a freezing `ClimateData`
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::terrain::{TerrainBundle, TerrainPlugin};
+//! use bevy_generative::weather::{Weather, WeatherChanged, WeatherObserver, WeatherPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins((TerrainPlugin, WeatherPlugin))
+//!         .add_systems(Startup, setup)
+//!         .add_systems(Update, print_changes)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(PointLightBundle {
+//!         transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!         ..default()
+//!     });
+//!     commands.spawn((
+//!         Camera3dBundle {
+//!             transform: Transform::from_xyz(-2.0, 2.5, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+//!             ..default()
+//!         },
+//!         WeatherObserver::default(),
+//!     ));
+//!     commands.spawn((TerrainBundle::default(), Weather::default()));
+//! }
+//!
+//! fn print_changes(mut events: EventReader<WeatherChanged>) {
+//!     for event in events.read() {
+//!         println!("{:?} turned to {:?}", event.previous, event.condition);
+//!     }
+//! }
+//! ```
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    climate::ClimateData,
+    noise::{get_noise_at_point_3d, Function, Method},
+    terrain::GeneratedTerrain,
+};
+
+/// Component for weather configuration, added to an entity with a `Terrain` component
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Weather {
+    /// Seed of the noise
+    pub seed: u32,
+    /// Size of the weather systems in world units
+    pub scale: f64,
+    /// Method used to generate noise
+    pub method: Method,
+    /// Function used to generate noise
+    pub function: Function,
+    /// Number of samples per world unit
+    pub resolution: u32,
+    /// Percentage of the sky covered by clouds
+    pub cloud_cover: f64,
+    /// Width of the transition between clear sky and clouds, in percent
+    pub softness: f64,
+    /// Cloud thickness from 0 to 1 above which it rains or snows, 1 is the densest possible cloud
+    pub precipitation_threshold: f32,
+    /// Precipitation from 0 to 1 above which it storms, unless it snows
+    pub storm_threshold: f32,
+    /// Temperature in °C below which precipitation falls as snow, requires `ClimateData`
+    pub freezing: f32,
+    /// Direction the weather moves towards in degrees, 0 is `+x` and 90 is `+z`
+    pub wind_direction: f32,
+    /// Speed the weather moves with in world units per second
+    pub wind_speed: f32,
+    /// Speed at which the weather evolves, in noise units per second
+    pub speed: f64,
+    /// Seconds between weather updates, 0 updates every frame
+    pub interval: f64,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            scale: 1.0,
+            method: Method::Perlin,
+            function: Function::default(),
+            resolution: 8,
+            cloud_cover: 50.0,
+            softness: 10.0,
+            precipitation_threshold: 0.2,
+            storm_threshold: 0.6,
+            freezing: 0.0,
+            wind_direction: 0.0,
+            wind_speed: 0.05,
+            speed: 0.02,
+            interval: 0.5,
+        }
+    }
+}
+
+/// Weather at a position
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WeatherCondition {
+    /// Less than half of the sky is covered
+    #[default]
+    Clear,
+    /// Overcast without precipitation
+    Cloudy,
+    /// Precipitation above freezing
+    Rain,
+    /// Precipitation below freezing
+    Snow,
+    /// Heavy precipitation above freezing
+    Storm,
+}
+
+/// Current weather of every sample, inserted on the entity with the `Weather` component
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct WeatherMap {
+    /// Cloud density from 0 to 1, indexed by `[x][z]`
+    pub cloud_cover: Vec<Vec<f32>>,
+    /// Precipitation intensity from 0 to 1, indexed by `[x][z]`
+    pub precipitation: Vec<Vec<f32>>,
+    /// Condition of every sample, indexed by `[x][z]`
+    pub conditions: Vec<Vec<WeatherCondition>>,
+    /// Number of samples per world unit
+    pub resolution: u32,
+    /// Size of the terrain in world units
+    pub size: [u32; 2],
+}
+
+impl WeatherMap {
+    /// Cloud density at `x`, `z` from 0 to 1, `None` outside of the terrain
+    #[must_use]
+    pub fn cloud_cover_at(&self, x: f32, z: f32) -> Option<f32> {
+        self.sample(&self.cloud_cover, x, z)
+    }
+
+    /// Precipitation intensity at `x`, `z` from 0 to 1, `None` outside of the terrain
+    #[must_use]
+    pub fn precipitation_at(&self, x: f32, z: f32) -> Option<f32> {
+        self.sample(&self.precipitation, x, z)
+    }
+
+    /// Condition of the sample nearest to `x`, `z`, `None` outside of the terrain
+    #[must_use]
+    pub fn condition_at(&self, x: f32, z: f32) -> Option<WeatherCondition> {
+        let resolution = self.resolution as f32;
+        let column = ((x + self.size[0] as f32 / 2.0) * resolution).round();
+        let row = ((z + self.size[1] as f32 / 2.0) * resolution).round();
+        if column < 0.0 || row < 0.0 {
+            return None;
+        }
+        self.conditions
+            .get(column as usize)
+            .and_then(|column| column.get(row as usize))
+            .copied()
+    }
+
+    fn sample(&self, grid: &[Vec<f32>], x: f32, z: f32) -> Option<f32> {
+        let resolution = self.resolution as f32;
+        let column = (x + self.size[0] as f32 / 2.0) * resolution;
+        let row = (z + self.size[1] as f32 / 2.0) * resolution;
+        let columns = grid.len().checked_sub(1)?;
+        let rows = grid.first()?.len().checked_sub(1)?;
+        if !(0.0..=columns as f32).contains(&column) || !(0.0..=rows as f32).contains(&row) {
+            return None;
+        }
+        let i = (column as usize).min(columns.saturating_sub(1));
+        let j = (row as usize).min(rows.saturating_sub(1));
+        let (tx, tz) = (column - i as f32, row - j as f32);
+        let value = |di: usize, dj: usize| grid[(i + di).min(columns)][(j + dj).min(rows)];
+        let near = (value(1, 0) - value(0, 0)).mul_add(tx, value(0, 0));
+        let far = (value(1, 1) - value(0, 1)).mul_add(tx, value(0, 1));
+        Some((far - near).mul_add(tz, near))
+    }
+}
+
+/// Component for entities notified of the weather where they stand
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct WeatherObserver {
+    /// Weather where the entity stands, `Clear` outside of every terrain
+    pub condition: WeatherCondition,
+    /// Terrain entity the entity stands on
+    pub terrain: Option<Entity>,
+}
+
+/// Event sent when the weather where a `WeatherObserver` stands changes
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WeatherChanged {
+    /// Entity with the `WeatherObserver`
+    pub observer: Entity,
+    /// Terrain entity the observer stands on
+    pub terrain: Option<Entity>,
+    /// Previous weather
+    pub previous: WeatherCondition,
+    /// Current weather
+    pub condition: WeatherCondition,
+}
+
+/// Plugin to animate weather
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<WeatherChanged>()
+            .add_systems(Update, (update_weather, observe_weather).chain());
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::type_complexity)]
+fn update_weather(
+    mut commands: Commands,
+    time: Res<Time>,
+    query: Query<(
+        Entity,
+        Ref<Weather>,
+        Ref<GeneratedTerrain>,
+        Option<&ClimateData>,
+        Option<&WeatherMap>,
+    )>,
+) {
+    let elapsed = time.elapsed_seconds_f64();
+    let previous = elapsed - time.delta_seconds_f64();
+    for (entity, weather, terrain_data, climate, old) in &query {
+        let interval = weather.interval.max(f64::EPSILON);
+        let stepped = (elapsed / interval) as u64 != (previous / interval) as u64;
+        if !stepped && !weather.is_changed() && !terrain_data.is_changed() {
+            continue;
+        }
+        let new = forecast(&weather, &terrain_data, climate, elapsed as f32);
+        if old != Some(&new) {
+            commands.entity(entity).insert(new);
+        }
+    }
+}
+
+/// Weather over the terrain after `elapsed` seconds
+fn forecast(
+    weather: &Weather,
+    terrain_data: &GeneratedTerrain,
+    climate: Option<&ClimateData>,
+    elapsed: f32,
+) -> WeatherMap {
+    let resolution = weather.resolution.max(1);
+    let half = Vec2::new(terrain_data.size[0] as f32, terrain_data.size[1] as f32) / 2.0;
+    let drift =
+        Vec2::from_angle(weather.wind_direction.to_radians()) * weather.wind_speed * elapsed;
+    let threshold = 100.0 - weather.cloud_cover;
+    let softness = weather.softness.max(f64::EPSILON);
+    let [columns, rows] = terrain_data
+        .size
+        .map(|side| (side * resolution + 1) as usize);
+
+    let mut map = WeatherMap {
+        resolution,
+        size: terrain_data.size,
+        ..default()
+    };
+    for i in 0..columns {
+        let mut cloud_cover = Vec::with_capacity(rows);
+        let mut precipitation = Vec::with_capacity(rows);
+        let mut conditions = Vec::with_capacity(rows);
+        for j in 0..rows {
+            let point = Vec2::new(i as f32, j as f32) / resolution as f32 - half;
+            // The noise moves against the wind, so the weather moves with it
+            let sample = point - drift;
+            let value = (get_noise_at_point_3d(
+                [f64::from(sample.x), f64::from(sample.y), 0.0],
+                weather.seed,
+                weather.scale.max(f64::EPSILON),
+                [0.0, 0.0, f64::from(elapsed) * weather.speed],
+                &weather.method,
+                &weather.function,
+            ) + 1.0)
+                * 50.0;
+            let density = ((value - threshold) / softness + 0.5).clamp(0.0, 1.0) as f32;
+            let density = density * density * 2.0f32.mul_add(-density, 3.0);
+            // Precipitation depends on how thick the clouds are, not only whether there are any
+            let thickness = ((value - threshold) / (100.0 - threshold).max(f64::EPSILON))
+                .clamp(0.0, 1.0) as f32;
+            let rain = ((thickness - weather.precipitation_threshold)
+                / (1.0 - weather.precipitation_threshold).max(f32::EPSILON))
+            .clamp(0.0, 1.0);
+            let freezing = climate
+                .and_then(|climate| climate.temperature_at(point.x, point.y))
+                .is_some_and(|temperature| temperature < weather.freezing);
+            conditions.push(if density < 0.5 {
+                WeatherCondition::Clear
+            } else if rain <= 0.0 {
+                WeatherCondition::Cloudy
+            } else if freezing {
+                WeatherCondition::Snow
+            } else if rain >= weather.storm_threshold {
+                WeatherCondition::Storm
+            } else {
+                WeatherCondition::Rain
+            });
+            cloud_cover.push(density);
+            precipitation.push(rain);
+        }
+        map.cloud_cover.push(cloud_cover);
+        map.precipitation.push(precipitation);
+        map.conditions.push(conditions);
+    }
+    map
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn observe_weather(
+    mut changed: EventWriter<WeatherChanged>,
+    terrains: Query<(Entity, &WeatherMap, &GlobalTransform)>,
+    mut observers: Query<(Entity, &mut WeatherObserver, &GlobalTransform)>,
+) {
+    for (observer, mut state, transform) in &mut observers {
+        let position = transform.translation();
+        let (terrain, condition) = terrains
+            .iter()
+            .find_map(|(terrain, map, terrain_transform)| {
+                let local = terrain_transform
+                    .affine()
+                    .inverse()
+                    .transform_point3(position);
+                map.condition_at(local.x, local.z)
+                    .map(|condition| (Some(terrain), condition))
+            })
+            .unwrap_or((None, WeatherCondition::Clear));
+        if state.condition != condition || state.terrain != terrain {
+            if state.condition != condition {
+                changed.send(WeatherChanged {
+                    observer,
+                    terrain,
+                    previous: state.condition,
+                    condition,
+                });
+            }
+            *state = WeatherObserver { condition, terrain };
+        }
+    }
+}
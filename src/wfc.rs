@@ -0,0 +1,648 @@
+//! Generate tile grids with wave function collapse
+//! # Example
+//! For configuration, see [`Wfc`](struct.Wfc.html)
+//! ```
+//! use bevy::prelude::*;
+//! use bevy_generative::wfc::{Wfc, WfcPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(WfcPlugin)
+//!         .add_systems(Startup, setup)
+//!         .run();
+//! }
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn(Camera2dBundle::default());
+//!     commands.spawn((ImageBundle::default(), Wfc::default()));
+//! }
+//! ```
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{PrimitiveTopology, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dungeon::{Tile, TileMesh},
+    error::{fail, GenerationError, GenerationFailure},
+    headless::DataOnly,
+    util::{export_asset, export_model, Rng},
+};
+
+/// Direction from a tile to its neighbour
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    /// Neighbour with a higher x coordinate
+    Right,
+    /// Neighbour with a lower x coordinate
+    Left,
+    /// Neighbour with a higher y coordinate, rows increase downwards in images
+    Down,
+    /// Neighbour with a lower y coordinate
+    Up,
+}
+
+impl Direction {
+    const ALL: [Self; 4] = [Self::Right, Self::Left, Self::Down, Self::Up];
+
+    const fn opposite(self) -> Self {
+        match self {
+            Self::Right => Self::Left,
+            Self::Left => Self::Right,
+            Self::Down => Self::Up,
+            Self::Up => Self::Down,
+        }
+    }
+}
+
+/// Tile that can be placed by wave function collapse
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WfcTile {
+    /// Label of the tile, referenced by adjacency rules
+    pub label: String,
+    /// Color of the tile in the generated image
+    pub color: [u8; 4],
+    /// Relative frequency of the tile
+    pub weight: f64,
+    /// Tile written to the tile grid, determines walkability and the extruded mesh
+    pub tile: Tile,
+}
+
+impl Default for WfcTile {
+    fn default() -> Self {
+        Self {
+            label: String::new(),
+            color: [255; 4],
+            weight: 1.0,
+            tile: Tile::Floor,
+        }
+    }
+}
+
+/// Allows tile `to` to be placed next to tile `from` in `direction`, and the reverse
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Adjacency {
+    /// Label of the first tile
+    pub from: String,
+    /// Label of the neighbouring tile
+    pub to: String,
+    /// Direction from the first to the neighbouring tile
+    pub direction: Direction,
+}
+
+/// Tiles and the rules determining which tiles can be neighbours
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TileSet {
+    /// Tiles that can be placed
+    pub tiles: Vec<WfcTile>,
+    /// Pairs of tiles that can be neighbours
+    pub adjacencies: Vec<Adjacency>,
+}
+
+impl Default for TileSet {
+    fn default() -> Self {
+        let tile = |label: &str, color, weight, tile| WfcTile {
+            label: label.to_string(),
+            color,
+            weight,
+            tile,
+        };
+        let tiles = vec![
+            tile("Water", [30, 90, 180, 255], 1.0, Tile::Empty),
+            tile("Sand", [220, 200, 140, 255], 1.0, Tile::Floor),
+            tile("Grass", [80, 160, 60, 255], 3.0, Tile::Floor),
+            tile("Forest", [30, 100, 40, 255], 2.0, Tile::Wall),
+        ];
+        let mut adjacencies = vec![];
+        for [from, to] in [
+            ["Water", "Water"],
+            ["Water", "Sand"],
+            ["Sand", "Sand"],
+            ["Sand", "Grass"],
+            ["Grass", "Grass"],
+            ["Grass", "Forest"],
+            ["Forest", "Forest"],
+        ] {
+            for direction in Direction::ALL {
+                adjacencies.push(Adjacency {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    direction,
+                });
+            }
+        }
+        Self { tiles, adjacencies }
+    }
+}
+
+impl TileSet {
+    /// Learns adjacency rules and weights from an example tile grid, indexed by `[x][y]`.
+    /// Values of the grid are indices into `tiles`
+    #[must_use]
+    pub fn from_example(example: &[Vec<usize>], mut tiles: Vec<WfcTile>) -> Self {
+        let mut counts = vec![0_usize; tiles.len()];
+        let mut adjacencies: Vec<Adjacency> = vec![];
+        let mut learned = vec![[false; 2]; tiles.len() * tiles.len()];
+        for (x, column) in example.iter().enumerate() {
+            for (y, &index) in column.iter().enumerate() {
+                if index >= tiles.len() {
+                    continue;
+                }
+                counts[index] += 1;
+                let neighbours = [
+                    (example.get(x + 1).and_then(|column| column.get(y)), 0),
+                    (column.get(y + 1), 1),
+                ];
+                for (neighbour, axis) in neighbours {
+                    let Some(&neighbour) = neighbour.filter(|&&other| other < tiles.len()) else {
+                        continue;
+                    };
+                    let seen = &mut learned[index * tiles.len() + neighbour][axis];
+                    if !*seen {
+                        *seen = true;
+                        adjacencies.push(Adjacency {
+                            from: tiles[index].label.clone(),
+                            to: tiles[neighbour].label.clone(),
+                            direction: if axis == 0 {
+                                Direction::Right
+                            } else {
+                                Direction::Down
+                            },
+                        });
+                    }
+                }
+            }
+        }
+        for (tile, count) in tiles.iter_mut().zip(counts) {
+            tile.weight = count.max(1) as f64;
+        }
+        Self { tiles, adjacencies }
+    }
+
+    /// Learns tiles, adjacency rules and weights from an example image.
+    /// Every distinct color becomes a tile, the image must use an 8 bit RGBA format
+    #[must_use]
+    pub fn from_image(image: &Image) -> Self {
+        let width = image.texture_descriptor.size.width as usize;
+        let height = image.texture_descriptor.size.height as usize;
+        let mut tiles: Vec<WfcTile> = vec![];
+        let mut lookup: HashMap<[u8; 4], usize> = HashMap::new();
+        let mut example = vec![vec![0; height]; width];
+        for (i, pixel) in image.data.chunks_exact(4).take(width * height).enumerate() {
+            let color = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            let index = *lookup.entry(color).or_insert_with(|| {
+                tiles.push(WfcTile {
+                    label: format!(
+                        "#{:02x}{:02x}{:02x}{:02x}",
+                        color[0], color[1], color[2], color[3]
+                    ),
+                    color,
+                    ..default()
+                });
+                tiles.len() - 1
+            });
+            example[i % width][i / width] = index;
+        }
+        Self::from_example(&example, tiles)
+    }
+
+    /// Compatibility table, `[from][direction][to]`
+    fn rules(&self) -> Vec<[Vec<bool>; 4]> {
+        let count = self.tiles.len();
+        let index: HashMap<&str, usize> = self
+            .tiles
+            .iter()
+            .enumerate()
+            .map(|(i, tile)| (tile.label.as_str(), i))
+            .collect();
+        let mut rules = vec![std::array::from_fn(|_| vec![false; count]); count];
+        for adjacency in &self.adjacencies {
+            let (Some(&from), Some(&to)) = (
+                index.get(adjacency.from.as_str()),
+                index.get(adjacency.to.as_str()),
+            ) else {
+                continue;
+            };
+            rules[from][adjacency.direction as usize][to] = true;
+            rules[to][adjacency.direction.opposite() as usize][from] = true;
+        }
+        rules
+    }
+}
+
+/// Component for wave function collapse configuration
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Component, Clone, Reflect, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+#[reflect_value(Component, Serialize, Deserialize)]
+pub struct Wfc {
+    /// Seed used to collapse the grid
+    pub seed: u32,
+    /// Size of the grid in tiles
+    pub size: [u32; 2],
+    /// Tiles and adjacency rules
+    pub tile_set: TileSet,
+    /// If true, the grid wraps around at its edges so it can be tiled
+    pub periodic: bool,
+    /// Maximum number of times a contradiction is resolved by undoing earlier choices
+    pub max_backtracks: usize,
+    /// Size of a tile in pixels
+    pub pixels_per_tile: u32,
+    /// If true, extrudes floor and wall mesh written to `PbrBundle`
+    pub mesh: bool,
+    /// Size of a tile in world units
+    pub tile_size: f32,
+    /// Height of the walls in world units
+    pub wall_height: f32,
+    /// If true, renders mesh as wireframe
+    pub wireframe: bool,
+    /// If true, exports model in glb format when `mesh` is true, else image in png format
+    #[serde(skip)]
+    pub export: bool,
+    /// Generated image, also written to `UiImage`
+    #[serde(skip)]
+    pub image: Handle<Image>,
+}
+
+impl Default for Wfc {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: [48, 48],
+            tile_set: TileSet::default(),
+            periodic: false,
+            max_backtracks: 1000,
+            pixels_per_tile: 8,
+            mesh: false,
+            tile_size: 0.05,
+            wall_height: 0.1,
+            wireframe: false,
+            export: false,
+            image: Handle::default(),
+        }
+    }
+}
+
+/// Generated grid, inserted on the entity with the `Wfc` component
+#[derive(Component, Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WfcLayout {
+    /// Index into `TileSet::tiles` of every cell, indexed by `[x][y]`
+    pub indices: Vec<Vec<usize>>,
+    /// Tile grid, indexed by `[x][y]`
+    pub tiles: Vec<Vec<Tile>>,
+    /// False if contradictions could not be resolved within `Wfc::max_backtracks`,
+    /// unresolved cells use the first tile that was still possible
+    pub complete: bool,
+}
+
+/// Render `Wfc` as a `PbrBundle`
+#[derive(Bundle, Default)]
+pub struct WfcBundle {
+    /// Wave function collapse configuration
+    pub wfc: Wfc,
+    /// Generated mesh data is written to `PbrBundle`
+    pub pbr_bundle: PbrBundle,
+}
+
+/// Plugin to generate tile grids with wave function collapse
+pub struct WfcPlugin;
+
+impl Plugin for WfcPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GenerationError>()
+            .add_systems(Update, generate_wfc);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn generate_wfc(
+    mut commands: Commands,
+    mut errors: EventWriter<GenerationError>,
+    mut images: Option<ResMut<Assets<Image>>>,
+    mut materials: Option<ResMut<Assets<StandardMaterial>>>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Wfc,
+            Option<&mut Handle<Mesh>>,
+            Option<&Handle<StandardMaterial>>,
+            Option<&mut UiImage>,
+            Has<DataOnly>,
+        ),
+        Changed<Wfc>,
+    >,
+) {
+    for (entity, mut wfc, mesh_handle, material, ui_image, data_only) in &mut query {
+        if wfc.tile_set.tiles.is_empty() || wfc.size.contains(&0) {
+            continue;
+        }
+        let layout = collapse(&wfc);
+
+        let scale = wfc.pixels_per_tile.max(1);
+        let tiles = &wfc.tile_set.tiles;
+        let image_buffer =
+            image::ImageBuffer::from_fn(wfc.size[0] * scale, wfc.size[1] * scale, |x, y| {
+                image::Rgba(tiles[layout.indices[(x / scale) as usize][(y / scale) as usize]].color)
+            });
+        if wfc.export && !wfc.mesh {
+            export_asset(image_buffer.clone());
+            wfc.bypass_change_detection().export = false;
+        }
+        // Without the render assets only the layout is generated
+        if let (false, Some(images)) = (data_only, images.as_deref_mut()) {
+            let Some(image) = Image::from_dynamic(image_buffer.into(), true)
+                .convert(TextureFormat::Rgba8UnormSrgb)
+            else {
+                fail(&mut errors, entity, GenerationFailure::Texture);
+                continue;
+            };
+            let image = images.add(image);
+            if let Some(mut ui_image) = ui_image {
+                ui_image.texture = image.clone();
+            }
+            wfc.bypass_change_detection().image = image;
+        }
+
+        if let (true, Some(meshes)) = (wfc.mesh && !data_only, meshes.as_deref_mut()) {
+            if let Some(material) = materials
+                .as_deref_mut()
+                .zip(material)
+                .and_then(|(materials, material)| materials.get_mut(material))
+            {
+                *material = StandardMaterial {
+                    perceptual_roughness: 0.9,
+                    ..default()
+                };
+            }
+            let tiles = &wfc.tile_set.tiles;
+            let floor_color = tiles
+                .iter()
+                .find(|tile| tile.tile.is_walkable())
+                .map_or([255; 4], |tile| tile.color);
+            let wall_color = tiles
+                .iter()
+                .find(|tile| tile.tile == Tile::Wall)
+                .map_or([255; 4], |tile| tile.color);
+            let TileMesh {
+                positions,
+                normals,
+                uvs,
+                colors,
+                mut indices,
+            } = TileMesh::extrude(
+                &layout.tiles,
+                wfc.tile_size,
+                wfc.wall_height,
+                floor_color,
+                wall_color,
+            );
+
+            if wfc.wireframe {
+                let triangle_number = indices.len() / 3;
+                let cloned_indices = indices.clone();
+                indices = vec![];
+                for i in 0..triangle_number {
+                    for j in &[0, 1, 1, 2, 2, 0] {
+                        indices.push(cloned_indices[i * 3 + j]);
+                    }
+                }
+            }
+
+            let mut mesh = if wfc.wireframe {
+                Mesh::new(PrimitiveTopology::LineList)
+            } else {
+                Mesh::new(PrimitiveTopology::TriangleList)
+            };
+            mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices.clone())));
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+            if let Some(mut mesh_handle) = mesh_handle {
+                *mesh_handle = meshes.add(mesh);
+            }
+
+            if wfc.export {
+                export_model(&positions, indices, &colors);
+                wfc.bypass_change_detection().export = false;
+            }
+        }
+
+        commands.entity(entity).insert(layout);
+    }
+}
+
+fn collapse(wfc: &Wfc) -> WfcLayout {
+    let [width, depth] = wfc.size.map(|side| side as usize);
+    let tiles = &wfc.tile_set.tiles;
+    let rules = wfc.tile_set.rules();
+    let mut rng = Rng::new(u64::from(wfc.seed));
+
+    let neighbour = |cell: usize, direction: Direction| -> Option<usize> {
+        let (x, y) = (cell % width, cell / width);
+        let (x, y) = match direction {
+            Direction::Right if x + 1 < width => (x + 1, y),
+            Direction::Left if x > 0 => (x - 1, y),
+            Direction::Down if y + 1 < depth => (x, y + 1),
+            Direction::Up if y > 0 => (x, y - 1),
+            Direction::Right if wfc.periodic => (0, y),
+            Direction::Left if wfc.periodic => (width - 1, y),
+            Direction::Down if wfc.periodic => (x, 0),
+            Direction::Up if wfc.periodic => (x, depth - 1),
+            _ => return None,
+        };
+        Some(y * width + x)
+    };
+
+    // Removes tiles without support from their neighbours, false on contradiction
+    let propagate = |cells: &mut Vec<Vec<bool>>, mut stack: Vec<usize>| -> bool {
+        while let Some(cell) = stack.pop() {
+            for direction in Direction::ALL {
+                let Some(other) = neighbour(cell, direction) else {
+                    continue;
+                };
+                let mut changed = false;
+                for to in 0..tiles.len() {
+                    if !cells[other][to] {
+                        continue;
+                    }
+                    let supported = (0..tiles.len())
+                        .any(|from| cells[cell][from] && rules[from][direction as usize][to]);
+                    if !supported {
+                        cells[other][to] = false;
+                        changed = true;
+                    }
+                }
+                if changed {
+                    if !cells[other].contains(&true) {
+                        return false;
+                    }
+                    stack.push(other);
+                }
+            }
+        }
+        true
+    };
+
+    let mut cells = vec![vec![true; tiles.len()]; width * depth];
+    let mut complete = propagate(&mut cells, (0..width * depth).collect());
+    let mut history: Vec<(Vec<Vec<bool>>, usize, usize)> = vec![];
+    let mut backtracks = 0;
+    'collapse: while complete {
+        // Observe the cell with the fewest remaining options
+        let mut lowest = usize::MAX;
+        let mut candidates = vec![];
+        for (cell, options) in cells.iter().enumerate() {
+            let count = options.iter().filter(|&&option| option).count();
+            if count <= 1 || count > lowest {
+                continue;
+            }
+            if count < lowest {
+                lowest = count;
+                candidates.clear();
+            }
+            candidates.push(cell);
+        }
+        if candidates.is_empty() {
+            break;
+        }
+        let cell = candidates[rng.range(0, candidates.len() as u32 - 1) as usize];
+        let total: f64 = (0..tiles.len())
+            .filter(|&tile| cells[cell][tile])
+            .map(|tile| tiles[tile].weight.max(f64::EPSILON))
+            .sum();
+        let mut target = rng.next_f64() * total;
+        let mut choice = 0;
+        for tile in (0..tiles.len()).filter(|&tile| cells[cell][tile]) {
+            choice = tile;
+            target -= tiles[tile].weight.max(f64::EPSILON);
+            if target <= 0.0 {
+                break;
+            }
+        }
+
+        history.push((cells.clone(), cell, choice));
+        for (tile, option) in cells[cell].iter_mut().enumerate() {
+            *option = tile == choice;
+        }
+        let mut consistent = propagate(&mut cells, vec![cell]);
+        // Undo choices leading to contradictions, ruling out the chosen tile
+        while !consistent {
+            backtracks += 1;
+            let Some((snapshot, cell, choice)) = history.pop() else {
+                complete = false;
+                break 'collapse;
+            };
+            if backtracks > wfc.max_backtracks {
+                complete = false;
+                break 'collapse;
+            }
+            cells = snapshot;
+            cells[cell][choice] = false;
+            consistent = cells[cell].contains(&true) && propagate(&mut cells, vec![cell]);
+        }
+    }
+
+    let mut indices = vec![vec![0; depth]; width];
+    let mut tile_grid = vec![vec![Tile::Empty; depth]; width];
+    for (cell, options) in cells.iter().enumerate() {
+        let index = options.iter().position(|&option| option).unwrap_or(0);
+        indices[cell % width][cell / width] = index;
+        tile_grid[cell % width][cell / width] = tiles[index].tile;
+    }
+    WfcLayout {
+        indices,
+        tiles: tile_grid,
+        complete,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Panics if two neighbouring cells of `layout` are not allowed next to each other
+    fn assert_adjacent(wfc: &Wfc, layout: &WfcLayout) {
+        let rules = wfc.tile_set.rules();
+        let [width, depth] = wfc.size.map(|side| side as usize);
+        for x in 0..width {
+            for y in 0..depth {
+                let tile = layout.indices[x][y];
+                let right = (x + 1 < width || wfc.periodic).then(|| (x + 1) % width);
+                let down = (y + 1 < depth || wfc.periodic).then(|| (y + 1) % depth);
+                if let Some(right) = right {
+                    let neighbour = layout.indices[right][y];
+                    assert!(rules[tile][Direction::Right as usize][neighbour]);
+                }
+                if let Some(down) = down {
+                    let neighbour = layout.indices[x][down];
+                    assert!(rules[tile][Direction::Down as usize][neighbour]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn layouts_obey_the_adjacency_rules() {
+        for periodic in [false, true] {
+            let wfc = Wfc {
+                size: [24, 16],
+                periodic,
+                ..default()
+            };
+            let layout = collapse(&wfc);
+            assert!(layout.complete);
+            assert_eq!(layout.indices.len(), 24);
+            assert!(layout.indices.iter().all(|column| column.len() == 16));
+            assert_adjacent(&wfc, &layout);
+        }
+    }
+
+    #[test]
+    fn seeds_repeat() {
+        let wfc = |seed| Wfc {
+            seed,
+            size: [16, 16],
+            ..default()
+        };
+        assert_eq!(collapse(&wfc(7)).indices, collapse(&wfc(7)).indices);
+        assert_ne!(collapse(&wfc(7)).indices, collapse(&wfc(8)).indices);
+    }
+
+    #[test]
+    fn learned_rules_only_allow_neighbours_of_the_example() {
+        // Stripes along x, so tiles only border each other across columns
+        let example = vec![vec![0; 4], vec![1; 4], vec![0; 4], vec![1; 4]];
+        let tiles = vec![
+            WfcTile {
+                label: "a".to_string(),
+                ..default()
+            },
+            WfcTile {
+                label: "b".to_string(),
+                ..default()
+            },
+        ];
+        let wfc = Wfc {
+            size: [8, 8],
+            tile_set: TileSet::from_example(&example, tiles),
+            ..default()
+        };
+        let rules = wfc.tile_set.rules();
+        assert!(!rules[0][Direction::Right as usize][0]);
+        assert!(rules[0][Direction::Down as usize][0]);
+        let layout = collapse(&wfc);
+        assert!(layout.complete);
+        assert_adjacent(&wfc, &layout);
+    }
+}
@@ -0,0 +1,183 @@
+//! Overlays must recolor the existing mesh of a terrain without generating it again
+#![cfg(feature = "terrain")]
+use bevy::prelude::*;
+use bevy_generative::{
+    fog::{FogOfWar, FogPlugin, FogRevealer},
+    splat::{SplatBrush, SplatMap, SplatPlugin},
+    terrain::{GeneratedTerrain, TerrainBundle, TerrainPlugin},
+    territory::{ClaimTerritory, Faction, Territory, TerritoryPlugin},
+};
+
+fn colors(app: &App, entity: Entity) -> Vec<[f32; 4]> {
+    let handle = app.world.get::<Handle<Mesh>>(entity).unwrap();
+    let mesh = app.world.resource::<Assets<Mesh>>().get(handle).unwrap();
+    let Some(bevy::render::mesh::VertexAttributeValues::Float32x4(colors)) =
+        mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+    else {
+        panic!("terrain meshes have vertex colors");
+    };
+    colors.clone()
+}
+
+/// Tick `GeneratedTerrain` last changed at and the mesh handle, both stay the same while recoloring
+fn generation(app: &App, entity: Entity) -> (u32, AssetId<Mesh>) {
+    let ticks = app
+        .world
+        .entity(entity)
+        .get_change_ticks::<GeneratedTerrain>()
+        .unwrap();
+    let mesh = app.world.get::<Handle<Mesh>>(entity).unwrap();
+    (ticks.last_changed_tick().get(), mesh.id())
+}
+
+#[test]
+fn overlays_recolor_without_generating() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .init_asset::<Image>()
+        .add_plugins((TerrainPlugin, FogPlugin, SplatPlugin, TerritoryPlugin));
+    let entity = app.world.spawn(TerrainBundle::default()).id();
+    app.update();
+    let generated = generation(&app, entity);
+    let base = colors(&app, entity);
+
+    app.world.entity_mut(entity).insert(FogOfWar::default());
+    app.update();
+    let fogged = colors(&app, entity);
+    assert_eq!(fogged.len(), base.len());
+    for (fogged, base) in fogged.iter().zip(&base) {
+        let expected = base.map(|channel| channel * 0.1);
+        for channel in 0..3 {
+            assert!(
+                (fogged[channel] - expected[channel]).abs() < 1e-5,
+                "unexplored fog"
+            );
+        }
+    }
+    assert_eq!(
+        generation(&app, entity),
+        generated,
+        "fog regenerated the terrain"
+    );
+
+    app.world.entity_mut(entity).remove::<FogOfWar>();
+    app.update();
+    assert_eq!(colors(&app, entity), base, "removed fog");
+
+    let mut territory = Territory::default();
+    territory.factions.push(Faction {
+        name: "red".to_string(),
+        color: [255, 0, 0, 255],
+    });
+    app.world
+        .entity_mut(entity)
+        .insert((SplatMap::default(), territory));
+    app.update();
+    assert_eq!(colors(&app, entity), base, "nothing painted or claimed yet");
+
+    let splat_ticks = app
+        .world
+        .entity(entity)
+        .get_change_ticks::<SplatMap>()
+        .unwrap();
+    app.world.send_event(SplatBrush {
+        radius: 0.2,
+        ..default()
+    });
+    app.update();
+    let painted = colors(&app, entity);
+    assert_ne!(painted, base, "painted path");
+    let ticks = app
+        .world
+        .entity(entity)
+        .get_change_ticks::<SplatMap>()
+        .unwrap();
+    assert_eq!(
+        ticks.last_changed_tick(),
+        splat_ticks.last_changed_tick(),
+        "strokes leave the splat map unchanged"
+    );
+
+    app.world.send_event(ClaimTerritory {
+        position: Vec3::ZERO,
+        radius: 0.2,
+        owner: Some(0),
+    });
+    app.update();
+    let claimed = colors(&app, entity);
+    assert_ne!(claimed, painted, "claimed territory");
+    let claimed_at = app
+        .world
+        .entity(entity)
+        .get_change_ticks::<Territory>()
+        .unwrap()
+        .last_changed_tick();
+
+    // Claiming cells for their owner again changes nothing
+    app.world.send_event(ClaimTerritory {
+        position: Vec3::ZERO,
+        radius: 0.2,
+        owner: Some(0),
+    });
+    app.update();
+    let ticks = app
+        .world
+        .entity(entity)
+        .get_change_ticks::<Territory>()
+        .unwrap();
+    assert_eq!(ticks.last_changed_tick(), claimed_at, "claimed again");
+    assert_eq!(colors(&app, entity), claimed);
+    assert_eq!(
+        generation(&app, entity),
+        generated,
+        "overlays regenerated the terrain"
+    );
+}
+
+#[test]
+fn fog_texture_follows_revealed_cells() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .init_asset::<Image>()
+        .add_plugins((TerrainPlugin, FogPlugin));
+    let entity = app
+        .world
+        .spawn((TerrainBundle::default(), FogOfWar::default()))
+        .id();
+    let revealer = app
+        .world
+        .spawn((TransformBundle::default(), FogRevealer::default()))
+        .id();
+    // The fog is sized to the terrain the frame after it is generated
+    app.update();
+    let mut texture = None;
+    for x in [-0.3, -0.1, 0.1, 0.3] {
+        app.world
+            .entity_mut(revealer)
+            .insert(GlobalTransform::from_xyz(x, 0.0, 0.0));
+        app.update();
+        let fog = app.world.get::<FogOfWar>(entity).unwrap();
+        let expected: Vec<u8> = fog
+            .explored
+            .iter()
+            .zip(&fog.visible)
+            .map(|(&explored, &visible)| match (explored, visible) {
+                (_, true) => 255,
+                (true, false) => 128,
+                (false, false) => 0,
+            })
+            .collect();
+        let image = app
+            .world
+            .resource::<Assets<Image>>()
+            .get(&fog.texture)
+            .unwrap();
+        assert_eq!(image.data, expected, "texture with the revealer at {x}");
+        // The texture is created once and then updated in place
+        assert_eq!(*texture.get_or_insert(fog.texture.id()), fog.texture.id());
+    }
+}
@@ -0,0 +1,84 @@
+//! Parallel noise generation must produce the same heightmaps and meshes as the serial path
+use bevy_generative_core::{
+    heightmap::heights,
+    mesh::GridMesh,
+    noise::{
+        generate_base_noise_map, generate_base_noise_map_parallel, Function, FunctionName, Method,
+    },
+};
+
+const METHODS: [Method; 7] = [
+    Method::OpenSimplex,
+    Method::Perlin,
+    Method::PerlinSurflet,
+    Method::Simplex,
+    Method::SuperSimplex,
+    Method::Value,
+    Method::Worley,
+];
+
+const FUNCTIONS: [Option<FunctionName>; 6] = [
+    None,
+    Some(FunctionName::BasicMulti),
+    Some(FunctionName::Billow),
+    Some(FunctionName::Fbm),
+    Some(FunctionName::HybridMulti),
+    Some(FunctionName::RidgedMulti),
+];
+
+fn bits(noise_map: &[Vec<f64>]) -> Vec<Vec<u64>> {
+    noise_map
+        .iter()
+        .map(|column| column.iter().map(|value| value.to_bits()).collect())
+        .collect()
+}
+
+fn mesh_bits(mesh: &GridMesh) -> Vec<u32> {
+    mesh.positions
+        .iter()
+        .chain(&mesh.normals)
+        .flatten()
+        .chain(mesh.uvs.iter().flatten())
+        .map(|value| value.to_bits())
+        .chain(mesh.indices.iter().copied())
+        .collect()
+}
+
+#[test]
+fn parallel_noise_matches_serial() {
+    let (size, resolution) = ([3, 2], 7);
+    let noise_size = [size[0] * resolution, size[1] * resolution];
+    for method in &METHODS {
+        for name in FUNCTIONS {
+            let label = name
+                .as_ref()
+                .map_or_else(|| "none".to_string(), ToString::to_string);
+            let function = Function {
+                name,
+                ..Function::default()
+            };
+            let serial =
+                generate_base_noise_map(noise_size, 7, 20.0, [3.5, -2.0], method, &function);
+            let serial_mesh = GridMesh::new(&heights(&serial, 10.0, 1.2), resolution, size);
+            // Uneven splits and more threads than columns included
+            for threads in [0, 1, 2, 5, 8, 64] {
+                let parallel = generate_base_noise_map_parallel(
+                    noise_size,
+                    7,
+                    20.0,
+                    [3.5, -2.0],
+                    method,
+                    &function,
+                    threads,
+                );
+                assert_eq!(
+                    bits(&parallel),
+                    bits(&serial),
+                    "{method} {label} on {threads} threads"
+                );
+                let parallel_mesh = GridMesh::new(&heights(&parallel, 10.0, 1.2), resolution, size);
+                assert_eq!(mesh_bits(&parallel_mesh), mesh_bits(&serial_mesh));
+            }
+        }
+    }
+}
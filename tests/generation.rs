@@ -0,0 +1,103 @@
+//! Invalid terrains must be reported once and retried only when the configuration changes,
+//! valid ones keep their noise with the precision of their configuration
+#![cfg(feature = "terrain")]
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use bevy_generative::{
+    error::{ConfigError, GenerationError, GenerationFailure},
+    noise::{Noise, NoiseMap, Precision},
+    terrain::{GeneratedTerrain, Terrain, TerrainPlugin},
+};
+
+/// Errors sent during `updates` frames
+fn errors(
+    app: &mut App,
+    reader: &mut ManualEventReader<GenerationError>,
+    updates: u32,
+) -> Vec<GenerationError> {
+    let mut errors = vec![];
+    for _ in 0..updates {
+        app.update();
+        errors.extend(
+            reader
+                .read(app.world.resource::<Events<GenerationError>>())
+                .cloned(),
+        );
+    }
+    errors
+}
+
+#[test]
+fn invalid_terrains_fail_once_until_changed() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TerrainPlugin));
+    let entity = app
+        .world
+        .spawn(Terrain {
+            resolution: 0,
+            ..default()
+        })
+        .id();
+    let mut reader = ManualEventReader::default();
+    let failed = errors(&mut app, &mut reader, 5);
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].entity, entity);
+    assert!(matches!(
+        failed[0].reason,
+        GenerationFailure::Config(ConfigError::Resolution { resolution: 0, .. })
+    ));
+    assert!(app.world.get::<GeneratedTerrain>(entity).is_none());
+
+    app.world.get_mut::<Terrain>(entity).unwrap().resolution = 15;
+    assert!(errors(&mut app, &mut reader, 2).is_empty());
+    assert!(app.world.get::<GeneratedTerrain>(entity).is_some());
+}
+
+#[test]
+fn terrains_rejected_by_the_builder_still_generate() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TerrainPlugin));
+    let mut terrain = Terrain {
+        sea_percent: 120.0,
+        ..default()
+    };
+    terrain.noise.regions.reverse();
+    assert!(terrain.validate().is_err());
+    let entity = app.world.spawn(terrain).id();
+    let mut reader = ManualEventReader::default();
+    assert!(errors(&mut app, &mut reader, 2).is_empty());
+    assert!(app.world.get::<GeneratedTerrain>(entity).is_some());
+}
+
+#[test]
+fn noise_keeps_the_precision_of_the_terrain() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TerrainPlugin));
+    let single = app.world.spawn(Terrain::default()).id();
+    let double = app
+        .world
+        .spawn(Terrain {
+            noise: Noise::builder()
+                .precision(Precision::Double)
+                .build()
+                .unwrap(),
+            ..default()
+        })
+        .id();
+    app.update();
+    let noise = |entity| {
+        app.world
+            .get::<GeneratedTerrain>(entity)
+            .unwrap()
+            .noise
+            .clone()
+    };
+    let (NoiseMap::Single(single), NoiseMap::Double(double)) = (noise(single), noise(double))
+    else {
+        panic!("noise maps do not have the precision of their terrains");
+    };
+    let rounded: Vec<Vec<f32>> = double
+        .iter()
+        .map(|column| column.iter().map(|&value| value as f32).collect())
+        .collect();
+    assert_eq!(single, rounded);
+}
@@ -0,0 +1,42 @@
+//! Overlays and mesh generators must not need the render assets
+#![cfg(feature = "terrain")]
+use bevy::prelude::*;
+use bevy_generative::{
+    fog::{FogOfWar, FogPlugin},
+    rock::{Rock, RockPlugin},
+    splat::{SplatMap, SplatPlugin},
+    terrain::{GeneratedTerrain, Terrain, TerrainPlugin},
+};
+
+#[test]
+fn overlays_run_without_render_assets() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TransformPlugin))
+        .add_plugins((TerrainPlugin, SplatPlugin, FogPlugin, RockPlugin));
+    let terrain = app
+        .world
+        .spawn((
+            Terrain::default(),
+            SplatMap::default(),
+            FogOfWar::default(),
+            TransformBundle::default(),
+        ))
+        .id();
+    app.world.spawn(Rock::default());
+    for _ in 0..3 {
+        app.update();
+    }
+    let size = app.world.get::<GeneratedTerrain>(terrain).unwrap().size;
+    let splat_map = app.world.get::<SplatMap>(terrain).unwrap();
+    assert_eq!(
+        splat_map.size,
+        size.map(|side| side * splat_map.resolution + 1)
+    );
+    assert_eq!(splat_map.texture, Handle::default());
+    assert!(!app
+        .world
+        .get::<FogOfWar>(terrain)
+        .unwrap()
+        .size
+        .contains(&0));
+}
@@ -0,0 +1,86 @@
+//! Holes must cut exactly the triangles of their cells out of the mesh, wherever the terrain is anchored
+#![cfg(feature = "terrain")]
+use bevy::{prelude::*, render::mesh::Indices};
+use bevy_generative::{
+    hole::TerrainHoles,
+    terrain::{Anchor, GeneratedTerrain, Terrain, TerrainPlugin},
+};
+
+#[test]
+fn holes_cut_their_cells() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .init_asset::<Image>()
+        .add_plugins(TerrainPlugin);
+    // 8 by 8 cells of 1 by 0.5 world units, from 0, 0 to 8, 4
+    let entity = app
+        .world
+        .spawn((
+            Terrain {
+                resolution: 4,
+                world_size: Some(Vec2::new(8.0, 4.0)),
+                anchor: Anchor::Corner,
+                ..default()
+            },
+            Handle::<Mesh>::default(),
+            TerrainHoles {
+                cells: vec![UVec2::new(0, 0), UVec2::new(7, 7)],
+                // Holds the centers of cells 2 and 3 along both axes
+                polygons: vec![vec![
+                    Vec2::new(2.0, 1.0),
+                    Vec2::new(4.0, 1.0),
+                    Vec2::new(4.0, 2.0),
+                    Vec2::new(2.0, 2.0),
+                ]],
+            },
+        ))
+        .id();
+    app.update();
+
+    let terrain_data = app.world.get::<GeneratedTerrain>(entity).unwrap();
+    let cut: Vec<(usize, usize)> = terrain_data
+        .holes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, column)| {
+            column
+                .iter()
+                .enumerate()
+                .filter(|(_, &hole)| hole)
+                .map(move |(j, _)| (i, j))
+        })
+        .collect();
+    assert_eq!(
+        cut,
+        [(0, 0), (2, 2), (2, 3), (3, 2), (3, 3), (7, 7)],
+        "cut cells"
+    );
+    assert!(terrain_data.is_hole(0.5, 0.25));
+    assert!(terrain_data.is_hole(3.0, 1.5));
+    assert!(!terrain_data.is_hole(4.5, 1.5));
+
+    let handle = app.world.get::<Handle<Mesh>>(entity).unwrap();
+    let mesh = app.world.resource::<Assets<Mesh>>().get(handle).unwrap();
+    let positions = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(|positions| positions.as_float3())
+        .unwrap();
+    let Some(Indices::U32(indices)) = mesh.indices() else {
+        panic!("terrain meshes have u32 indices");
+    };
+    // Two triangles per cell
+    assert_eq!(indices.len(), (8 * 8 - cut.len()) * 2 * 3);
+    for triangle in indices.chunks_exact(3) {
+        let centroid = triangle
+            .iter()
+            .map(|&index| Vec3::from(positions[index as usize]).xz())
+            .sum::<Vec2>()
+            / 3.0;
+        assert!(
+            !terrain_data.is_hole(centroid.x, centroid.y),
+            "triangle over a hole at {centroid}"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Overlays must be edited and sampled at the same place on terrains stretched to a world size or anchored off center
+#![cfg(feature = "terrain")]
+use bevy::prelude::*;
+use bevy_generative::{
+    deform::{DeformPlugin, DeformProfile, TerrainDeform, TerrainDeformation, TerrainDeformed},
+    fog::{FogOfWar, FogPlugin, FogReveal, FogRevealer},
+    splat::{SplatBrush, SplatMap, SplatPlugin},
+    terrain::{Anchor, GeneratedTerrain, Terrain, TerrainBundle, TerrainPlugin},
+    territory::{ClaimTerritory, Faction, Territory, TerritoryPlugin},
+};
+
+/// Edits every overlay of a terrain within one world unit of `target`, relative to the terrain
+fn edit_overlays(terrain: Terrain, target: Vec2) -> (App, Entity) {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .init_asset::<Image>()
+        .add_plugins((
+            TerrainPlugin,
+            FogPlugin,
+            SplatPlugin,
+            TerritoryPlugin,
+            DeformPlugin,
+        ));
+    let mut territory = Territory::default();
+    territory.factions.push(Faction::default());
+    let entity = app
+        .world
+        .spawn((
+            TerrainBundle {
+                terrain,
+                ..default()
+            },
+            FogOfWar::default(),
+            SplatMap::default(),
+            territory,
+        ))
+        .id();
+    // The overlays are sized to the terrain the frame after it is generated
+    app.update();
+    app.update();
+
+    let position = Vec3::new(target.x, 0.0, target.y);
+    app.world.send_event(FogReveal {
+        position,
+        revealer: FogRevealer {
+            radius: 1.0,
+            ..default()
+        },
+    });
+    app.world.send_event(SplatBrush {
+        position,
+        radius: 1.0,
+        hardness: 1.0,
+        ..default()
+    });
+    app.world.send_event(ClaimTerritory {
+        position,
+        radius: 1.0,
+        owner: Some(0),
+    });
+    app.world.send_event(TerrainDeform {
+        position,
+        radius: 1.0,
+        depth: 0.5,
+        profile: DeformProfile::Flat,
+    });
+    app.update();
+    app.update();
+    (app, entity)
+}
+
+/// Asserts the overlays are edited at `target` but not `miss`, both relative to the terrain
+fn assert_edited(app: &App, entity: Entity, target: Vec2, miss: Vec2) {
+    let terrain_data = app.world.get::<GeneratedTerrain>(entity).unwrap();
+    let fog = app.world.get::<FogOfWar>(entity).unwrap();
+    let splat_map = app.world.get::<SplatMap>(entity).unwrap();
+    let territory = app.world.get::<Territory>(entity).unwrap();
+    let size = terrain_data.size;
+    for (point, edited) in [(target, true), (miss, false)] {
+        let Vec2 { x, y: z } = terrain_data.to_overlay(point);
+        assert_eq!(fog.is_explored(x, z, size), edited, "fog at {point}");
+        assert_eq!(
+            splat_map.weights_at(x, z, size)[0] > 0.0,
+            edited,
+            "splat at {point}"
+        );
+        assert_eq!(
+            territory.owner_at(x, z, size).is_some(),
+            edited,
+            "territory at {point}"
+        );
+    }
+
+    // Offsets are indexed like the vertices, the nearest vertex of a flat deformation is lowered fully
+    let deformation = app.world.get::<TerrainDeformation>(entity).unwrap();
+    let offset = |point: Vec2| {
+        let [i, j] = ((terrain_data.to_overlay(point)
+            + Vec2::new(size[0] as f32, size[1] as f32) / 2.0)
+            * terrain_data.resolution as f32)
+            .round()
+            .to_array()
+            .map(|index| index as usize);
+        deformation.offsets[i][j]
+    };
+    assert_eq!(offset(target), -0.5, "deformation at {target}");
+    assert_eq!(offset(miss), 0.0, "deformation at {miss}");
+
+    let events = app.world.resource::<Events<TerrainDeformed>>();
+    let mut reader = events.get_reader();
+    let area = reader.read(events).next().expect("deformed the terrain");
+    assert!(
+        area.min.cmple(target).all() && area.max.cmpge(target).all() && area.max.cmplt(miss).any(),
+        "deformed {area:?} around {target}"
+    );
+}
+
+#[test]
+fn overlays_follow_stretched_terrains() {
+    let terrain = Terrain {
+        world_size: Some(Vec2::new(8.0, 6.0)),
+        ..default()
+    };
+    let target = Vec2::new(2.0, 1.0);
+    let (app, entity) = edit_overlays(terrain, target);
+    assert_edited(&app, entity, target, target + Vec2::new(1.5, 0.0));
+}
+
+#[test]
+fn overlays_follow_anchored_terrains() {
+    for anchor in [Anchor::Corner, Anchor::Offset(Vec3::new(-3.0, 0.0, 5.0))] {
+        let terrain = Terrain {
+            world_size: Some(Vec2::new(8.0, 6.0)),
+            anchor,
+            ..default()
+        };
+        let target = match anchor {
+            Anchor::Corner => Vec2::new(6.0, 4.0),
+            _ => Vec2::new(-1.0, 6.0),
+        };
+        let (app, entity) = edit_overlays(terrain, target);
+        assert_edited(&app, entity, target, target + Vec2::new(1.5, 0.0));
+    }
+}
+
+#[test]
+fn walls_block_the_line_of_sight_on_anchored_terrains() {
+    // Vertices one world unit apart from 0, 0 to 4, 2 with a wall along x = 2
+    let mut terrain_data = GeneratedTerrain {
+        resolution: 2,
+        size: [2, 1],
+        world_size: Vec2::new(4.0, 2.0),
+        center: Vec2::new(2.0, 1.0),
+        heights: vec![vec![0.0; 3]; 5],
+        ..default()
+    };
+    terrain_data.heights[2] = vec![10.0; 3];
+    let mut fog = FogOfWar {
+        resolution: 2,
+        size: [5, 3],
+        explored: vec![false; 15],
+        visible: vec![false; 15],
+        ..default()
+    };
+    fog.reveal_line_of_sight(&terrain_data, Vec3::new(0.5, 1.0, 1.0), 4.0);
+    let visible = |x: f32| {
+        let point = terrain_data.to_overlay(Vec2::new(x, 1.0));
+        fog.is_visible(point.x, point.y, terrain_data.size)
+    };
+    assert!(
+        visible(0.0) && visible(1.0) && visible(2.0),
+        "in front of the wall"
+    );
+    assert!(!visible(3.0) && !visible(4.0), "behind the wall");
+}
@@ -0,0 +1,184 @@
+//! Streamed chunks must be generated once and keep their meshes while nothing changes
+#![cfg(feature = "terrain")]
+use bevy::prelude::*;
+use bevy_generative::{
+    splat::SplatMap,
+    streaming::{StreamingFocus, StreamingPlugin, TerrainChunk, TerrainStreaming},
+    terrain::{GeneratedTerrain, Terrain, TerrainMeshPart, TerrainPlugin},
+};
+
+fn app() -> App {
+    app_with(TerrainStreaming {
+        view_distance: 1,
+        pregenerate_distance: 0,
+        chunks_per_frame: 9,
+        ..default()
+    })
+}
+
+fn app_with(streaming: TerrainStreaming) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        TransformPlugin,
+        HierarchyPlugin,
+    ))
+    .init_asset::<Mesh>()
+    .init_asset::<StandardMaterial>()
+    .init_asset::<Image>()
+    .add_plugins((TerrainPlugin, StreamingPlugin));
+    app.world
+        .spawn((TransformBundle::default(), StreamingFocus));
+    app.world.spawn((streaming, SpatialBundle::default()));
+    app
+}
+
+/// Moves the focus far enough for every chunk to be unloaded into the cache, then back again
+fn revisit(app: &mut App) {
+    let chunk_size = app
+        .world
+        .query::<&TerrainStreaming>()
+        .single(&app.world)
+        .chunk_size();
+    let focus = app
+        .world
+        .query_filtered::<Entity, With<StreamingFocus>>()
+        .single(&app.world);
+    for x in [10.0, 0.0] {
+        app.world
+            .entity_mut(focus)
+            .insert(Transform::from_xyz(x * chunk_size.x, 0.0, 0.0));
+        for _ in 0..3 {
+            app.update();
+        }
+    }
+}
+
+fn chunk_meshes(app: &mut App) -> Vec<(IVec2, AssetId<Mesh>)> {
+    let mut meshes: Vec<(IVec2, AssetId<Mesh>)> = app
+        .world
+        .query::<(&TerrainChunk, &Handle<Mesh>)>()
+        .iter(&app.world)
+        .map(|(chunk, mesh)| (chunk.coord, mesh.id()))
+        .collect();
+    meshes.sort_by_key(|(coord, _)| (coord.x, coord.y));
+    meshes
+}
+
+#[test]
+fn streamed_chunks_are_not_regenerated() {
+    let mut app = app();
+    for _ in 0..3 {
+        app.update();
+    }
+    let generated = chunk_meshes(&mut app);
+    assert_eq!(generated.len(), 9);
+    assert!(generated
+        .iter()
+        .all(|(_, mesh)| *mesh != AssetId::default()));
+    for _ in 0..5 {
+        app.update();
+    }
+    assert_eq!(chunk_meshes(&mut app), generated);
+}
+
+#[test]
+fn restored_chunks_keep_their_cached_meshes() {
+    let mut app = app();
+    for _ in 0..3 {
+        app.update();
+    }
+    let generated = chunk_meshes(&mut app);
+    revisit(&mut app);
+    assert_eq!(chunk_meshes(&mut app), generated);
+    for _ in 0..5 {
+        app.update();
+    }
+    assert_eq!(chunk_meshes(&mut app), generated);
+}
+
+#[test]
+fn restored_chunks_keep_their_mesh_parts_and_edits() {
+    let mut app = app_with(TerrainStreaming {
+        chunk: Terrain {
+            max_vertices: Some(256),
+            ..default()
+        },
+        view_distance: 0,
+        pregenerate_distance: 0,
+        ..default()
+    });
+    for _ in 0..3 {
+        app.update();
+    }
+    let chunk = app
+        .world
+        .query_filtered::<Entity, With<TerrainChunk>>()
+        .single(&app.world);
+    app.world.entity_mut(chunk).insert(SplatMap::default());
+    for _ in 0..2 {
+        app.update();
+    }
+    let parts = |app: &mut App| {
+        let mut parts: Vec<(usize, AssetId<Mesh>)> = app
+            .world
+            .query::<(&TerrainMeshPart, &Handle<Mesh>)>()
+            .iter(&app.world)
+            .map(|(part, mesh)| (part.index, mesh.id()))
+            .collect();
+        parts.sort_by_key(|(index, _)| *index);
+        parts
+    };
+    let generated = parts(&mut app);
+    assert!(generated.len() > 1);
+    let splat_map = app.world.get::<SplatMap>(chunk).cloned();
+
+    revisit(&mut app);
+    assert_eq!(parts(&mut app), generated);
+    let chunk = app
+        .world
+        .query_filtered::<Entity, With<TerrainChunk>>()
+        .single(&app.world);
+    assert_eq!(app.world.get::<SplatMap>(chunk).cloned(), splat_map);
+}
+
+#[test]
+fn chunks_stream_without_render_assets() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TransformPlugin, HierarchyPlugin))
+        .add_plugins((TerrainPlugin, StreamingPlugin));
+    let focus = app
+        .world
+        .spawn((TransformBundle::default(), StreamingFocus))
+        .id();
+    app.world.spawn((
+        TerrainStreaming {
+            view_distance: 0,
+            pregenerate_distance: 0,
+            unload_margin: 0,
+            ..default()
+        },
+        SpatialBundle::default(),
+    ));
+    for _ in 0..3 {
+        app.update();
+    }
+    let chunks = |app: &mut App| {
+        app.world
+            .query_filtered::<&TerrainChunk, With<GeneratedTerrain>>()
+            .iter(&app.world)
+            .map(|chunk| chunk.coord)
+            .collect::<Vec<IVec2>>()
+    };
+    assert_eq!(chunks(&mut app), [IVec2::ZERO]);
+    // Unloading caches the chunk without its mesh, and the chunk further on is generated
+    app.world
+        .entity_mut(focus)
+        .insert(Transform::from_xyz(10.0, 0.0, 0.0));
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(chunks(&mut app).len(), 1);
+    assert_ne!(chunks(&mut app), [IVec2::ZERO]);
+}